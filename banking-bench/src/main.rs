@@ -4,7 +4,10 @@ use crossbeam_channel::unbounded;
 use log::*;
 use rand::{thread_rng, Rng};
 use rayon::prelude::*;
-use solana_core::{banking_stage::BankingStage, cost_model::CostModel, cost_tracker::CostTracker};
+use solana_core::{
+    banking_stage::BankingStage, cost_model::CostModel, cost_tracker::CostTracker,
+    transaction_scheduler::TransactionSchedulerType,
+};
 use solana_gossip::{cluster_info::ClusterInfo, cluster_info::Node};
 use solana_ledger::{
     blockstore::Blockstore,
@@ -146,6 +149,14 @@ fn main() {
                 .takes_value(true)
                 .help("Number of iterations"),
         )
+        .arg(
+            Arg::with_name("scheduler")
+                .long("scheduler")
+                .takes_value(true)
+                .possible_values(&["thread-local", "central"])
+                .default_value("thread-local")
+                .help("How the banking stage assigns transactions to its threads"),
+        )
         .get_matches();
 
     let num_threads =
@@ -154,6 +165,8 @@ fn main() {
     let num_chunks = value_t!(matches, "num_chunks", usize).unwrap_or(16);
     let packets_per_chunk = value_t!(matches, "packets_per_chunk", usize).unwrap_or(192);
     let iterations = value_t!(matches, "iterations", usize).unwrap_or(1000);
+    let scheduler_type = value_t!(matches, "scheduler", TransactionSchedulerType)
+        .unwrap_or_else(|_| TransactionSchedulerType::default());
 
     let total_num_transactions = num_chunks * num_threads * packets_per_chunk;
     let mint_total = 1_000_000_000_000;
@@ -217,7 +230,7 @@ fn main() {
             create_test_recorder(&bank, &blockstore, None);
         let cluster_info = ClusterInfo::new_with_invalid_keypair(Node::new_localhost().info);
         let cluster_info = Arc::new(cluster_info);
-        let banking_stage = BankingStage::new(
+        let banking_stage = BankingStage::new_with_scheduler(
             &cluster_info,
             &poh_recorder,
             verified_receiver,
@@ -227,6 +240,7 @@ fn main() {
             Arc::new(RwLock::new(CostTracker::new(Arc::new(RwLock::new(
                 CostModel::default(),
             ))))),
+            scheduler_type,
         );
         poh_recorder.lock().unwrap().set_bank(&bank);
 
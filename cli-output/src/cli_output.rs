@@ -16,7 +16,7 @@ use {
     solana_clap_utils::keypair::SignOnly,
     solana_client::rpc_response::{
         RpcAccountBalance, RpcContactInfo, RpcInflationGovernor, RpcInflationRate, RpcKeyedAccount,
-        RpcSupply, RpcVoteAccountInfo,
+        RpcRestartIntent, RpcSupply, RpcVoteAccountInfo,
     },
     solana_sdk::{
         clock::{Epoch, Slot, UnixTimestamp},
@@ -576,6 +576,40 @@ impl fmt::Display for CliValidators {
             )?;
         }
 
+        let now = solana_sdk::timing::timestamp();
+        let mut restarts: Vec<_> = self
+            .validators
+            .iter()
+            .filter_map(|validator| {
+                let intent = validator.restart_intent.as_ref()?;
+                (intent.end() > now).then(|| (validator, intent))
+            })
+            .collect();
+        if !restarts.is_empty() {
+            restarts.sort_by_key(|(_, intent)| intent.start);
+            writeln!(f)?;
+            writeln!(f, "{}", style("Announced Restarts:").bold())?;
+            for (validator, intent) in restarts {
+                writeln!(
+                    f,
+                    "{} {:<44} {} for {}{}",
+                    if validator.delinquent {
+                        WARNING.to_string()
+                    } else {
+                        "\u{a0}".to_string()
+                    },
+                    validator.identity_pubkey,
+                    Local.timestamp_millis(intent.start as i64),
+                    humantime::format_duration(Duration::from_secs(intent.expected_duration_secs)),
+                    intent
+                        .target_version
+                        .as_ref()
+                        .map(|version| format!(", upgrading to {}", version))
+                        .unwrap_or_default(),
+                )?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -594,6 +628,8 @@ pub struct CliValidator {
     pub version: String,
     pub delinquent: bool,
     pub skip_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart_intent: Option<RpcRestartIntent>,
 }
 
 impl CliValidator {
@@ -602,6 +638,7 @@ impl CliValidator {
         current_epoch: Epoch,
         version: String,
         skip_rate: Option<f64>,
+        restart_intent: Option<RpcRestartIntent>,
         address_labels: &HashMap<String, String>,
     ) -> Self {
         Self::_new(
@@ -609,6 +646,7 @@ impl CliValidator {
             current_epoch,
             version,
             skip_rate,
+            restart_intent,
             address_labels,
             false,
         )
@@ -619,6 +657,7 @@ impl CliValidator {
         current_epoch: Epoch,
         version: String,
         skip_rate: Option<f64>,
+        restart_intent: Option<RpcRestartIntent>,
         address_labels: &HashMap<String, String>,
     ) -> Self {
         Self::_new(
@@ -626,6 +665,7 @@ impl CliValidator {
             current_epoch,
             version,
             skip_rate,
+            restart_intent,
             address_labels,
             true,
         )
@@ -636,6 +676,7 @@ impl CliValidator {
         current_epoch: Epoch,
         version: String,
         skip_rate: Option<f64>,
+        restart_intent: Option<RpcRestartIntent>,
         address_labels: &HashMap<String, String>,
         delinquent: bool,
     ) -> Self {
@@ -662,6 +703,7 @@ impl CliValidator {
             version,
             delinquent,
             skip_rate,
+            restart_intent,
         }
     }
 }
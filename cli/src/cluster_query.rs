@@ -1818,8 +1818,12 @@ pub fn process_show_validators(
 
     progress_bar.set_message("Fetching version information...");
     let mut node_version = HashMap::new();
+    let mut node_restart_intent = HashMap::new();
     let unknown_version = "unknown".to_string();
     for contact_info in rpc_client.get_cluster_nodes()? {
+        if let Some(restart_intent) = contact_info.restart_intent {
+            node_restart_intent.insert(contact_info.pubkey.clone(), restart_intent);
+        }
         node_version.insert(
             contact_info.pubkey,
             contact_info
@@ -1856,6 +1860,7 @@ pub fn process_show_validators(
                     .unwrap_or(&unknown_version)
                     .clone(),
                 skip_rate.get(&vote_account.node_pubkey).cloned(),
+                node_restart_intent.get(&vote_account.node_pubkey).cloned(),
                 &config.address_labels,
             )
         })
@@ -1872,6 +1877,7 @@ pub fn process_show_validators(
                     .unwrap_or(&unknown_version)
                     .clone(),
                 skip_rate.get(&vote_account.node_pubkey).cloned(),
+                node_restart_intent.get(&vote_account.node_pubkey).cloned(),
                 &config.address_labels,
             )
         })
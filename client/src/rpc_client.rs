@@ -960,6 +960,10 @@ impl RpcClient {
         self.send(RpcRequest::GetRecentPerformanceSamples, json!([limit]))
     }
 
+    pub fn get_recent_block_costs(&self, limit: Option<usize>) -> ClientResult<Vec<RpcBlockCost>> {
+        self.send(RpcRequest::GetRecentBlockCosts, json!([limit]))
+    }
+
     pub fn get_identity(&self) -> ClientResult<Pubkey> {
         let rpc_identity: RpcIdentity = self.send(RpcRequest::GetIdentity, Value::Null)?;
 
@@ -226,6 +226,29 @@ pub struct RpcContactInfo {
     pub feature_set: Option<u32>,
     /// Shred version
     pub shred_version: Option<u16>,
+    /// Restart announced by the node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart_intent: Option<RpcRestartIntent>,
+}
+
+/// A restart a node announced ahead of time, e.g. for an upgrade
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcRestartIntent {
+    /// Start of the restart, in milliseconds since the UNIX epoch
+    pub start: u64,
+    /// Expected duration of the restart, in seconds
+    pub expected_duration_secs: u64,
+    /// Software version the node will be running after the restart
+    pub target_version: Option<String>,
+}
+
+impl RpcRestartIntent {
+    /// End of the restart window, in milliseconds since the UNIX epoch
+    pub fn end(&self) -> u64 {
+        self.start
+            .saturating_add(self.expected_duration_secs.saturating_mul(1000))
+    }
 }
 
 /// Map of leader base58 identity pubkeys to the slot indices relative to the first epoch slot
@@ -398,6 +421,26 @@ pub struct RpcPerfSample {
     pub sample_period_secs: u16,
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAccountCost {
+    pub pubkey: String,
+    pub cost: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcBlockCost {
+    pub slot: Slot,
+    pub block_cost: u64,
+    pub block_cost_limit: u64,
+    pub account_cost_limit: u64,
+    pub transaction_count: u64,
+    pub would_exceed_block_limit_count: u64,
+    pub would_exceed_account_limit_count: u64,
+    pub account_costs: Vec<RpcAccountCost>,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RpcInflationReward {
@@ -4,6 +4,7 @@ extern crate solana_core;
 extern crate test;
 
 use log::*;
+use lru::LruCache;
 use solana_core::retransmit_stage::retransmitter;
 use solana_gossip::cluster_info::{ClusterInfo, Node};
 use solana_gossip::contact_info::ContactInfo;
@@ -99,6 +100,7 @@ fn bench_retransmitter(bencher: &mut Bencher) {
         packet_receiver,
         &Arc::new(MaxSlots::default()),
         None,
+        Arc::new(Mutex::new(LruCache::new(1_000))),
     );
 
     let mut index = 0;
@@ -15,9 +15,9 @@ use solana_sdk::{clock::Slot, hash::Hash, pubkey::Pubkey};
 use std::collections::{HashMap, HashSet};
 use std::{
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         mpsc::RecvTimeoutError,
-        Arc,
+        Arc, RwLock,
     },
     thread::{self, Builder, JoinHandle},
     time::Duration,
@@ -33,10 +33,10 @@ impl AccountsHashVerifier {
         pending_snapshot_package: Option<PendingSnapshotPackage>,
         exit: &Arc<AtomicBool>,
         cluster_info: &Arc<ClusterInfo>,
-        trusted_validators: Option<HashSet<Pubkey>>,
+        trusted_validators: Arc<RwLock<Option<HashSet<Pubkey>>>>,
         halt_on_trusted_validators_accounts_hash_mismatch: bool,
         fault_injection_rate_slots: u64,
-        snapshot_interval_slots: u64,
+        snapshot_interval_slots: Arc<AtomicU64>,
     ) -> Self {
         let exit = exit.clone();
         let cluster_info = cluster_info.clone();
@@ -59,6 +59,11 @@ impl AccountsHashVerifier {
                                     Some(accounts_db::make_min_priority_thread_pool());
                             }
 
+                            // Both may be changed through the admin rpc at any time
+                            let trusted_validators = trusted_validators.read().unwrap().clone();
+                            let snapshot_interval_slots =
+                                snapshot_interval_slots.load(Ordering::Relaxed);
+
                             Self::process_accounts_package_pre(
                                 accounts_package,
                                 &cluster_info,
@@ -0,0 +1,588 @@
+//! The `ancestor_hashes_service` module asks staked peers for the (slot, hash) chain of
+//! ancestors of slots whose local version disagrees with the version the cluster duplicate
+//! confirmed. Responses are aggregated by stake, and the earliest ancestor where the local
+//! fork diverged from the agreed chain is sent to `ReplayStage` to dump and repair.
+use crate::{
+    outstanding_requests::OutstandingRequests,
+    repair_response,
+    replay_stage::DUPLICATE_THRESHOLD,
+    result::{Error, Result},
+    serve_repair::{AncestorHashesRepairType, AncestorHashesResponseVersion, ServeRepair},
+};
+use crossbeam_channel::{Receiver as CrossbeamReceiver, Sender as CrossbeamSender};
+use solana_gossip::{cluster_info::ClusterInfo, weighted_shuffle::weighted_shuffle};
+use solana_ledger::{blockstore::Blockstore, shred::Nonce, shred::SIZE_OF_NONCE};
+use solana_perf::{packet::limited_deserialize, recycler::Recycler};
+use solana_runtime::bank_forks::BankForks;
+use solana_sdk::{
+    clock::Slot,
+    hash::Hash,
+    pubkey::Pubkey,
+    timing::{duration_as_ms, timestamp},
+};
+use solana_streamer::streamer::{self, PacketReceiver};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, RecvTimeoutError},
+        Arc, RwLock,
+    },
+    thread::{self, Builder, JoinHandle},
+    time::{Duration, Instant},
+};
+
+// Slots whose local version does not match the cluster duplicate confirmed version
+pub type AncestorHashesRequestSender = CrossbeamSender<Slot>;
+pub type AncestorHashesRequestReceiver = CrossbeamReceiver<Slot>;
+// Earliest divergent ancestor to dump, and the hash the cluster agreed on for it
+pub type AncestorDuplicateSlotsSender = CrossbeamSender<(Slot, Hash)>;
+pub type AncestorDuplicateSlotsReceiver = CrossbeamReceiver<(Slot, Hash)>;
+
+pub type OutstandingAncestorHashesRepairs = OutstandingRequests<AncestorHashesRepairType>;
+
+// Number of staked peers asked for the ancestors of each slot
+pub const ANCESTOR_HASHES_SAMPLE_SIZE: usize = 21;
+// Maximum number of slots with ancestor hashes requests in flight
+pub const MAX_PENDING_ANCESTOR_HASHES_REQUESTS: usize = 10;
+// Time to wait for responses before sampling a new set of peers
+pub const ANCESTOR_HASHES_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DuplicateAncestorDecision {
+    // The local version of every slot in the agreed ancestor chain matches the cluster
+    AncestorsAllMatch,
+    // Earliest slot in the agreed ancestor chain whose local version differs from the
+    // cluster, with the hash the cluster agreed on
+    EarliestMismatchFound(Slot, Hash),
+    // The frozen slots of the agreed ancestor chain match the cluster, but some are not
+    // frozen locally yet. The slot is requested again once the retry interval elapsed.
+    AncestorsNotFrozen,
+}
+
+impl DuplicateAncestorDecision {
+    // Compares the agreed ancestor chain, in descending slot order, against the
+    // frozen hashes in the blockstore. Slots at or below the root are skipped as they
+    // cannot be dumped anymore, and slots not frozen locally as there is nothing to dump.
+    fn new(blockstore: &Blockstore, root: Slot, agreed_slot_hashes: &[(Slot, Hash)]) -> Self {
+        let mut all_frozen = true;
+        for (slot, hash) in agreed_slot_hashes
+            .iter()
+            .rev()
+            .filter(|(slot, _)| *slot > root)
+        {
+            match blockstore.get_bank_hash(*slot) {
+                None => all_frozen = false,
+                Some(frozen_hash) if frozen_hash != *hash => {
+                    return DuplicateAncestorDecision::EarliestMismatchFound(*slot, *hash);
+                }
+                Some(_) => (),
+            }
+        }
+        if all_frozen {
+            DuplicateAncestorDecision::AncestorsAllMatch
+        } else {
+            DuplicateAncestorDecision::AncestorsNotFrozen
+        }
+    }
+}
+
+struct SampledPeer {
+    pubkey: Pubkey,
+    stake: u64,
+    nonce: Nonce,
+}
+
+// Status of the ancestor hashes requests for a single slot
+struct AncestorRequestStatus {
+    // Peers asked for the ancestors, keyed by their serve repair address
+    sampled_peers: HashMap<SocketAddr, SampledPeer>,
+    responded_peers: HashSet<SocketAddr>,
+    // Total stake of the peers agreeing on each distinct ancestor chain
+    response_stakes: HashMap<Vec<(Slot, Hash)>, u64>,
+    total_sampled_stake: u64,
+    start: Instant,
+}
+
+impl AncestorRequestStatus {
+    fn new(sampled_peers: HashMap<SocketAddr, SampledPeer>) -> Self {
+        let total_sampled_stake = sampled_peers.values().map(|peer| peer.stake).sum();
+        Self {
+            sampled_peers,
+            responded_peers: HashSet::new(),
+            response_stakes: HashMap::new(),
+            total_sampled_stake,
+            start: Instant::now(),
+        }
+    }
+
+    // Records the response of a sampled peer. Returns the agreed ancestor chain once
+    // peers holding at least `DUPLICATE_THRESHOLD` of the sampled stake returned it.
+    fn add_response(
+        &mut self,
+        from_addr: &SocketAddr,
+        slot_hashes: Vec<(Slot, Hash)>,
+    ) -> Option<Vec<(Slot, Hash)>> {
+        let stake = self.sampled_peers.get(from_addr)?.stake;
+        if !self.responded_peers.insert(*from_addr) {
+            return None;
+        }
+        // Peers which have not duplicate confirmed the slot return nothing
+        if slot_hashes.is_empty() {
+            return None;
+        }
+        let agreed_stake = self.response_stakes.entry(slot_hashes.clone()).or_default();
+        *agreed_stake += stake;
+        if *agreed_stake as f64 >= DUPLICATE_THRESHOLD * self.total_sampled_stake as f64 {
+            Some(slot_hashes)
+        } else {
+            None
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.responded_peers.len() == self.sampled_peers.len()
+            || self.start.elapsed() > ANCESTOR_HASHES_RETRY_INTERVAL
+    }
+}
+
+#[derive(Default)]
+struct AncestorHashesStats {
+    requested_slots: usize,
+    sent_requests: usize,
+    responses: usize,
+    invalid_responses: usize,
+    expired_requests: usize,
+    mismatches_found: usize,
+    ancestors_not_frozen: usize,
+}
+
+impl AncestorHashesStats {
+    fn report(&mut self) {
+        datapoint_info!(
+            "ancestor_hashes_service",
+            ("requested_slots", self.requested_slots, i64),
+            ("sent_requests", self.sent_requests, i64),
+            ("responses", self.responses, i64),
+            ("invalid_responses", self.invalid_responses, i64),
+            ("expired_requests", self.expired_requests, i64),
+            ("mismatches_found", self.mismatches_found, i64),
+            ("ancestors_not_frozen", self.ancestors_not_frozen, i64),
+        );
+        *self = AncestorHashesStats::default();
+    }
+}
+
+pub struct AncestorHashesService {
+    thread_hdls: Vec<JoinHandle<()>>,
+}
+
+impl AncestorHashesService {
+    pub fn new(
+        exit: &Arc<AtomicBool>,
+        blockstore: Arc<Blockstore>,
+        ancestor_hashes_request_socket: UdpSocket,
+        cluster_info: Arc<ClusterInfo>,
+        bank_forks: Arc<RwLock<BankForks>>,
+        repair_validators: Arc<RwLock<Option<HashSet<Pubkey>>>>,
+        ancestor_hashes_request_receiver: AncestorHashesRequestReceiver,
+        ancestor_duplicate_slots_sender: AncestorDuplicateSlotsSender,
+    ) -> Self {
+        let ancestor_hashes_request_socket = Arc::new(ancestor_hashes_request_socket);
+        let (response_sender, response_receiver) = channel();
+        let t_receiver = streamer::receiver(
+            ancestor_hashes_request_socket.clone(),
+            exit,
+            response_sender,
+            Recycler::default(),
+            "ancestor_hashes_response_receiver",
+            1,
+            false,
+        );
+        let exit = exit.clone();
+        let t_ancestor_hashes = Builder::new()
+            .name("solana-ancestor-hashes-service".to_string())
+            .spawn(move || {
+                Self::run(
+                    &exit,
+                    &blockstore,
+                    &ancestor_hashes_request_socket,
+                    &ServeRepair::new(cluster_info.clone()),
+                    &cluster_info,
+                    &bank_forks,
+                    &repair_validators,
+                    &ancestor_hashes_request_receiver,
+                    &response_receiver,
+                    &ancestor_duplicate_slots_sender,
+                )
+            })
+            .unwrap();
+
+        Self {
+            thread_hdls: vec![t_receiver, t_ancestor_hashes],
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn run(
+        exit: &AtomicBool,
+        blockstore: &Blockstore,
+        ancestor_hashes_request_socket: &UdpSocket,
+        serve_repair: &ServeRepair,
+        cluster_info: &ClusterInfo,
+        bank_forks: &RwLock<BankForks>,
+        repair_validators: &RwLock<Option<HashSet<Pubkey>>>,
+        ancestor_hashes_request_receiver: &AncestorHashesRequestReceiver,
+        response_receiver: &PacketReceiver,
+        ancestor_duplicate_slots_sender: &AncestorDuplicateSlotsSender,
+    ) {
+        let mut outstanding_requests = OutstandingAncestorHashesRepairs::default();
+        let mut request_statuses: HashMap<Slot, AncestorRequestStatus> = HashMap::new();
+        // Slots waiting for a free request slot, or to be retried with new peers
+        let mut pending_slots: HashSet<Slot> = HashSet::new();
+        // Slots for which the sampled peers recently agreed on the ancestors; `ReplayStage`
+        // asks again every retry interval until the slot is repaired
+        let mut decided_slots: HashMap<Slot, Instant> = HashMap::new();
+        let mut stats = AncestorHashesStats::default();
+        let mut last_stats_report = Instant::now();
+        loop {
+            if exit.load(Ordering::Relaxed) {
+                break;
+            }
+            let root_bank = bank_forks.read().unwrap().root_bank();
+            let root = root_bank.slot();
+            decided_slots.retain(|slot, decided| {
+                *slot > root && decided.elapsed() < ANCESTOR_HASHES_RETRY_INTERVAL
+            });
+            pending_slots.extend(ancestor_hashes_request_receiver.try_iter().filter(|slot| {
+                !request_statuses.contains_key(slot) && !decided_slots.contains_key(slot)
+            }));
+            pending_slots.retain(|slot| *slot > root);
+            request_statuses.retain(|slot, _| *slot > root);
+
+            match Self::process_responses(
+                blockstore,
+                root,
+                response_receiver,
+                &mut outstanding_requests,
+                &mut request_statuses,
+                &mut decided_slots,
+                ancestor_duplicate_slots_sender,
+                &mut stats,
+            ) {
+                Err(Error::RecvTimeout(RecvTimeoutError::Disconnected)) => break,
+                Err(Error::RecvTimeout(RecvTimeoutError::Timeout)) | Ok(()) => (),
+                Err(err) => warn!("ancestor hashes service error: {:?}", err),
+            }
+
+            // Retry slots without agreement among the sampled peers
+            request_statuses.retain(|slot, status| {
+                if status.is_expired() {
+                    stats.expired_requests += 1;
+                    pending_slots.insert(*slot);
+                    false
+                } else {
+                    true
+                }
+            });
+
+            let staked_nodes = root_bank
+                .epoch_staked_nodes(root_bank.epoch())
+                .unwrap_or_default();
+            let repair_validators = repair_validators.read().unwrap().clone();
+            let num_new_requests =
+                MAX_PENDING_ANCESTOR_HASHES_REQUESTS.saturating_sub(request_statuses.len());
+            let new_request_slots: Vec<Slot> = pending_slots
+                .iter()
+                .copied()
+                .take(num_new_requests)
+                .collect();
+            for slot in new_request_slots {
+                let sampled_peers = Self::sample_peers(
+                    slot,
+                    cluster_info,
+                    &staked_nodes,
+                    &repair_validators,
+                    &mut outstanding_requests,
+                );
+                if sampled_peers.is_empty() {
+                    // Keep the slot pending until staked peers show up in gossip
+                    continue;
+                }
+                pending_slots.remove(&slot);
+                for (addr, peer) in &sampled_peers {
+                    match serve_repair.ancestor_repair_request_bytes(slot, peer.nonce) {
+                        Ok(request) => {
+                            if let Err(err) = ancestor_hashes_request_socket.send_to(&request, addr)
+                            {
+                                debug!(
+                                    "ancestor hashes request for slot {} to {} ({}) failed: {:?}",
+                                    slot, peer.pubkey, addr, err
+                                );
+                            } else {
+                                stats.sent_requests += 1;
+                            }
+                        }
+                        Err(err) => warn!("unable to serialize ancestor hashes request: {:?}", err),
+                    }
+                }
+                stats.requested_slots += 1;
+                request_statuses.insert(slot, AncestorRequestStatus::new(sampled_peers));
+            }
+
+            if duration_as_ms(&last_stats_report.elapsed()) > 2_000 {
+                stats.report();
+                last_stats_report = Instant::now();
+            }
+        }
+    }
+
+    // Samples up to `ANCESTOR_HASHES_SAMPLE_SIZE` staked peers, weighted by stake,
+    // and registers an outstanding request for each of them.
+    fn sample_peers(
+        slot: Slot,
+        cluster_info: &ClusterInfo,
+        staked_nodes: &HashMap<Pubkey, u64>,
+        repair_validators: &Option<HashSet<Pubkey>>,
+        outstanding_requests: &mut OutstandingAncestorHashesRepairs,
+    ) -> HashMap<SocketAddr, SampledPeer> {
+        let (peers, weights): (Vec<_>, Vec<u64>) = cluster_info
+            .repair_peers(slot)
+            .into_iter()
+            .filter(|peer| {
+                repair_validators
+                    .as_ref()
+                    .map(|repair_validators| repair_validators.contains(&peer.id))
+                    .unwrap_or(true)
+            })
+            .filter_map(|peer| {
+                let stake = staked_nodes.get(&peer.id).copied().unwrap_or_default();
+                (stake > 0).then(|| ((peer.id, peer.serve_repair), stake))
+            })
+            .unzip();
+        let now = timestamp();
+        weighted_shuffle(&weights, solana_sdk::pubkey::new_rand().to_bytes())
+            .into_iter()
+            .take(ANCESTOR_HASHES_SAMPLE_SIZE)
+            .map(|index| {
+                let (pubkey, addr) = peers[index];
+                let nonce = outstanding_requests.add_request(AncestorHashesRepairType(slot), now);
+                let peer = SampledPeer {
+                    pubkey,
+                    stake: weights[index],
+                    nonce,
+                };
+                (addr, peer)
+            })
+            .collect()
+    }
+
+    fn process_responses(
+        blockstore: &Blockstore,
+        root: Slot,
+        response_receiver: &PacketReceiver,
+        outstanding_requests: &mut OutstandingAncestorHashesRepairs,
+        request_statuses: &mut HashMap<Slot, AncestorRequestStatus>,
+        decided_slots: &mut HashMap<Slot, Instant>,
+        ancestor_duplicate_slots_sender: &AncestorDuplicateSlotsSender,
+        stats: &mut AncestorHashesStats,
+    ) -> Result<()> {
+        let timeout = Duration::from_millis(200);
+        let mut packets = vec![response_receiver.recv_timeout(timeout)?];
+        packets.extend(response_receiver.try_iter());
+        let now = timestamp();
+        for packet in packets.iter().flat_map(|packets| packets.packets.iter()) {
+            stats.responses += 1;
+            let from_addr = packet.meta.addr();
+            let (response, nonce) =
+                match Self::deserialize_response(&packet.data[..packet.meta.size]) {
+                    Some((response, nonce))
+                        if outstanding_requests.register_response(nonce, &response, now) =>
+                    {
+                        (response, nonce)
+                    }
+                    _ => {
+                        stats.invalid_responses += 1;
+                        continue;
+                    }
+                };
+            let (slot, decision) = match Self::process_response(
+                response,
+                nonce,
+                &from_addr,
+                blockstore,
+                root,
+                request_statuses,
+            ) {
+                Some(slot_and_decision) => slot_and_decision,
+                None => continue,
+            };
+            decided_slots.insert(slot, Instant::now());
+            match decision {
+                DuplicateAncestorDecision::EarliestMismatchFound(ancestor, hash) => {
+                    stats.mismatches_found += 1;
+                    warn!(
+                        "Cluster agreed on hash {} for slot {}, an ancestor of slot {}, which does not match our version",
+                        hash, ancestor, slot
+                    );
+                    let _ = ancestor_duplicate_slots_sender.send((ancestor, hash));
+                }
+                DuplicateAncestorDecision::AncestorsNotFrozen => stats.ancestors_not_frozen += 1,
+                DuplicateAncestorDecision::AncestorsAllMatch => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn deserialize_response(data: &[u8]) -> Option<(AncestorHashesResponseVersion, Nonce)> {
+        if data.len() < SIZE_OF_NONCE {
+            return None;
+        }
+        let response = limited_deserialize(&data[..data.len() - SIZE_OF_NONCE]).ok()?;
+        let nonce = repair_response::nonce(data)?;
+        Some((response, nonce))
+    }
+
+    // Returns the requested slot and the decision once enough sampled stake agreed on
+    // its ancestor chain, in which case the status of the slot is removed.
+    fn process_response(
+        response: AncestorHashesResponseVersion,
+        nonce: Nonce,
+        from_addr: &SocketAddr,
+        blockstore: &Blockstore,
+        root: Slot,
+        request_statuses: &mut HashMap<Slot, AncestorRequestStatus>,
+    ) -> Option<(Slot, DuplicateAncestorDecision)> {
+        let (slot, status) = request_statuses.iter_mut().find(|(_, status)| {
+            status
+                .sampled_peers
+                .get(from_addr)
+                .map(|peer| peer.nonce == nonce)
+                .unwrap_or(false)
+        })?;
+        let slot = *slot;
+        let agreed_slot_hashes = status.add_response(from_addr, response.into_slot_hashes())?;
+        request_statuses.remove(&slot);
+        let decision = DuplicateAncestorDecision::new(blockstore, root, &agreed_slot_hashes);
+        Some((slot, decision))
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        for thread_hdl in self.thread_hdls {
+            thread_hdl.join()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use solana_ledger::get_tmp_ledger_path;
+
+    fn make_sampled_peers(stakes: &[u64]) -> (Vec<SocketAddr>, HashMap<SocketAddr, SampledPeer>) {
+        let addrs: Vec<SocketAddr> = (0..stakes.len())
+            .map(|i| SocketAddr::from(([127, 0, 0, 1], 8000 + i as u16)))
+            .collect();
+        let sampled_peers = addrs
+            .iter()
+            .zip(stakes)
+            .enumerate()
+            .map(|(nonce, (addr, stake))| {
+                let peer = SampledPeer {
+                    pubkey: Pubkey::new_unique(),
+                    stake: *stake,
+                    nonce: nonce as Nonce,
+                };
+                (*addr, peer)
+            })
+            .collect();
+        (addrs, sampled_peers)
+    }
+
+    #[test]
+    fn test_ancestor_request_status_add_response() {
+        let (addrs, sampled_peers) = make_sampled_peers(&[40, 30, 20, 10]);
+        let mut status = AncestorRequestStatus::new(sampled_peers);
+        assert_eq!(status.total_sampled_stake, 100);
+        let slot_hashes = vec![(5, Hash::new_unique()), (3, Hash::new_unique())];
+        let other_slot_hashes = vec![(5, Hash::new_unique()), (3, Hash::new_unique())];
+
+        // Unknown peers are ignored
+        let unknown_addr = SocketAddr::from(([127, 0, 0, 1], 9000));
+        assert!(status
+            .add_response(&unknown_addr, slot_hashes.clone())
+            .is_none());
+        assert!(status.responded_peers.is_empty());
+
+        assert!(status
+            .add_response(&addrs[0], slot_hashes.clone())
+            .is_none());
+        // A peer's response is only counted once
+        assert!(status
+            .add_response(&addrs[0], slot_hashes.clone())
+            .is_none());
+        assert!(status
+            .add_response(&addrs[1], other_slot_hashes.clone())
+            .is_none());
+        // Empty responses do not count towards agreement
+        assert!(status.add_response(&addrs[3], vec![]).is_none());
+        assert!(!status.is_expired());
+        // 60% of the sampled stake agrees
+        assert_eq!(
+            status.add_response(&addrs[2], slot_hashes.clone()),
+            Some(slot_hashes)
+        );
+        assert!(status.is_expired());
+    }
+
+    #[test]
+    fn test_duplicate_ancestor_decision() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&ledger_path).unwrap();
+            let agreed_slot_hashes: Vec<(Slot, Hash)> = vec![
+                (6, Hash::new_unique()),
+                (4, Hash::new_unique()),
+                (2, Hash::new_unique()),
+                (1, Hash::new_unique()),
+            ];
+            // Slots 4 and 6 are not frozen locally, so there is nothing to dump yet
+            blockstore.insert_bank_hash(2, agreed_slot_hashes[2].1, false);
+            blockstore.insert_bank_hash(1, agreed_slot_hashes[3].1, true);
+            assert_eq!(
+                DuplicateAncestorDecision::new(&blockstore, 0, &agreed_slot_hashes),
+                DuplicateAncestorDecision::AncestorsNotFrozen
+            );
+
+            // Slot 6 froze with a different hash, while slot 4 is still not frozen
+            blockstore.insert_bank_hash(6, Hash::new_unique(), false);
+            assert_eq!(
+                DuplicateAncestorDecision::new(&blockstore, 0, &agreed_slot_hashes),
+                DuplicateAncestorDecision::EarliestMismatchFound(6, agreed_slot_hashes[0].1)
+            );
+
+            // Slot 2 froze with a different hash
+            blockstore.insert_bank_hash(2, Hash::new_unique(), false);
+            assert_eq!(
+                DuplicateAncestorDecision::new(&blockstore, 0, &agreed_slot_hashes),
+                DuplicateAncestorDecision::EarliestMismatchFound(2, agreed_slot_hashes[2].1)
+            );
+            // Slots at or below the root are not considered
+            assert_eq!(
+                DuplicateAncestorDecision::new(&blockstore, 2, &agreed_slot_hashes),
+                DuplicateAncestorDecision::EarliestMismatchFound(6, agreed_slot_hashes[0].1)
+            );
+
+            for (slot, hash) in &agreed_slot_hashes {
+                blockstore.insert_bank_hash(*slot, *hash, false);
+            }
+            assert_eq!(
+                DuplicateAncestorDecision::new(&blockstore, 0, &agreed_slot_hashes),
+                DuplicateAncestorDecision::AncestorsAllMatch
+            );
+        }
+        Blockstore::destroy(&ledger_path).expect("Expected successful database destruction");
+    }
+}
@@ -0,0 +1,78 @@
+//! The `bank_hash_details_service` writes the details of the banks frozen with a
+//! different hash than the one the cluster confirmed, off the replay thread.
+
+use {
+    crossbeam_channel::{Receiver, RecvTimeoutError, Sender},
+    solana_runtime::bank_hash_details::BankHashDetails,
+    solana_sdk::hash::Hash,
+    std::{
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread::{self, Builder, JoinHandle},
+        time::Duration,
+    },
+};
+
+/// The details of our version of a slot, along with the hash the cluster confirmed
+pub type BankHashDetailsSender = Sender<(BankHashDetails, Hash)>;
+pub type BankHashDetailsReceiver = Receiver<(BankHashDetails, Hash)>;
+
+pub struct BankHashDetailsService {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl BankHashDetailsService {
+    pub fn new(
+        bank_hash_details_receiver: BankHashDetailsReceiver,
+        bank_hash_details_dir: PathBuf,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let exit = exit.clone();
+        let thread_hdl = Builder::new()
+            .name("solana-bank-hash-details".to_string())
+            .spawn(move || loop {
+                if exit.load(Ordering::Relaxed) {
+                    break;
+                }
+                match bank_hash_details_receiver.recv_timeout(Duration::from_secs(1)) {
+                    Err(RecvTimeoutError::Disconnected) => break,
+                    Err(RecvTimeoutError::Timeout) => (),
+                    Ok((details, correct_hash)) => Self::write_bank_hash_details(
+                        &details,
+                        &correct_hash,
+                        &bank_hash_details_dir,
+                    ),
+                }
+            })
+            .unwrap();
+        Self { thread_hdl }
+    }
+
+    fn write_bank_hash_details(
+        details: &BankHashDetails,
+        correct_hash: &Hash,
+        bank_hash_details_dir: &Path,
+    ) {
+        match details.write_to_dir(bank_hash_details_dir) {
+            Ok(path) => warn!(
+                "Slot {} frozen with hash {} but the cluster confirmed hash {}, \
+                 wrote bank hash details to {}",
+                details.slot,
+                details.bank_hash,
+                correct_hash,
+                path.display()
+            ),
+            Err(err) => warn!(
+                "Unable to write bank hash details for slot {}: {}",
+                details.slot, err
+            ),
+        }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
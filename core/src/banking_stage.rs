@@ -1,8 +1,16 @@
 //! The `banking_stage` processes Transaction messages. It is intended to be used
 //! to contruct a software pipeline. The stage uses all available CPU cores and
 //! can do its processing in parallel with signature verification on the GPU.
-use crate::{cost_tracker::CostTracker, packet_hasher::PacketHasher};
-use crossbeam_channel::{Receiver as CrossbeamReceiver, RecvTimeoutError};
+use crate::{
+    cost_tracker::{CostTracker, CostTrackerError},
+    packet_hasher::PacketHasher,
+    transaction_scheduler::{
+        BatchOutcome, ScheduledBatch, TransactionScheduler, TransactionSchedulerType,
+    },
+};
+use crossbeam_channel::{
+    unbounded, Receiver as CrossbeamReceiver, RecvTimeoutError, Sender as CrossbeamSender,
+};
 use itertools::Itertools;
 use lru::LruCache;
 use retain_mut::RetainMut;
@@ -90,6 +98,11 @@ pub struct BankingStageStats {
     reset_cost_tracker_count: AtomicUsize,
     cost_tracker_check_count: AtomicUsize,
     cost_forced_retry_transactions_count: AtomicUsize,
+    cost_block_limit_transactions_count: AtomicUsize,
+    cost_account_limit_transactions_count: AtomicUsize,
+    cost_too_expensive_dropped_transactions_count: AtomicUsize,
+    scheduled_batches_count: AtomicUsize,
+    scheduler_retried_transactions_count: AtomicUsize,
 
     // Timing
     consume_buffered_packets_elapsed: AtomicU64,
@@ -177,6 +190,35 @@ impl BankingStageStats {
                         .swap(0, Ordering::Relaxed) as i64,
                     i64
                 ),
+                (
+                    "cost_block_limit_transactions_count",
+                    self.cost_block_limit_transactions_count
+                        .swap(0, Ordering::Relaxed) as i64,
+                    i64
+                ),
+                (
+                    "cost_account_limit_transactions_count",
+                    self.cost_account_limit_transactions_count
+                        .swap(0, Ordering::Relaxed) as i64,
+                    i64
+                ),
+                (
+                    "cost_too_expensive_dropped_transactions_count",
+                    self.cost_too_expensive_dropped_transactions_count
+                        .swap(0, Ordering::Relaxed) as i64,
+                    i64
+                ),
+                (
+                    "scheduled_batches_count",
+                    self.scheduled_batches_count.swap(0, Ordering::Relaxed) as i64,
+                    i64
+                ),
+                (
+                    "scheduler_retried_transactions_count",
+                    self.scheduler_retried_transactions_count
+                        .swap(0, Ordering::Relaxed) as i64,
+                    i64
+                ),
                 (
                     "consume_buffered_packets_elapsed",
                     self.consume_buffered_packets_elapsed
@@ -267,6 +309,31 @@ impl BankingStage {
         transaction_status_sender: Option<TransactionStatusSender>,
         gossip_vote_sender: ReplayVoteSender,
         cost_tracker: Arc<RwLock<CostTracker>>,
+    ) -> Self {
+        Self::new_with_scheduler(
+            cluster_info,
+            poh_recorder,
+            verified_receiver,
+            verified_vote_receiver,
+            transaction_status_sender,
+            gossip_vote_sender,
+            cost_tracker,
+            TransactionSchedulerType::default(),
+        )
+    }
+
+    /// Create the stage with the given way of scheduling non-vote transactions onto the
+    /// banking threads
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_scheduler(
+        cluster_info: &Arc<ClusterInfo>,
+        poh_recorder: &Arc<Mutex<PohRecorder>>,
+        verified_receiver: CrossbeamReceiver<Vec<Packets>>,
+        verified_vote_receiver: CrossbeamReceiver<Vec<Packets>>,
+        transaction_status_sender: Option<TransactionStatusSender>,
+        gossip_vote_sender: ReplayVoteSender,
+        cost_tracker: Arc<RwLock<CostTracker>>,
+        scheduler_type: TransactionSchedulerType,
     ) -> Self {
         Self::new_num_threads(
             cluster_info,
@@ -277,9 +344,11 @@ impl BankingStage {
             transaction_status_sender,
             gossip_vote_sender,
             cost_tracker,
+            scheduler_type,
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn new_num_threads(
         cluster_info: &Arc<ClusterInfo>,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
@@ -289,6 +358,7 @@ impl BankingStage {
         transaction_status_sender: Option<TransactionStatusSender>,
         gossip_vote_sender: ReplayVoteSender,
         cost_tracker: Arc<RwLock<CostTracker>>,
+        scheduler_type: TransactionSchedulerType,
     ) -> Self {
         let batch_limit = TOTAL_BUFFERED_PACKETS / ((num_threads - 1) as usize * PACKETS_PER_BATCH);
         // Single thread to generate entries from many banks.
@@ -298,8 +368,51 @@ impl BankingStage {
             LruCache::new(DEFAULT_LRU_SIZE),
             PacketHasher::default(),
         )));
+        let mut worker_thread_hdls = vec![];
+        // With the central scheduler, a single thread buffers all non-vote packets and hands
+        // non-conflicting batches to the other banking threads
+        let (thread_ids, mut scheduler) = match scheduler_type {
+            TransactionSchedulerType::ThreadLocal => ((0..num_threads).collect_vec(), None),
+            TransactionSchedulerType::Central => {
+                let (outcome_sender, outcome_receiver) = unbounded();
+                let batch_senders = (0..num_threads as usize - 1)
+                    .map(|worker| {
+                        let (batch_sender, batch_receiver) = unbounded();
+                        let outcome_sender = outcome_sender.clone();
+                        let poh_recorder = poh_recorder.clone();
+                        let transaction_status_sender = transaction_status_sender.clone();
+                        let gossip_vote_sender = gossip_vote_sender.clone();
+                        let cost_tracker = cost_tracker.clone();
+                        worker_thread_hdls.push(
+                            Builder::new()
+                                .name("solana-banking-stage-worker".to_string())
+                                .spawn(move || {
+                                    Self::scheduled_worker_loop(
+                                        worker,
+                                        &batch_receiver,
+                                        &outcome_sender,
+                                        &poh_recorder,
+                                        transaction_status_sender,
+                                        gossip_vote_sender,
+                                        &cost_tracker,
+                                    );
+                                })
+                                .unwrap(),
+                        );
+                        batch_sender
+                    })
+                    .collect();
+                let scheduler = TransactionScheduler::new(
+                    batch_senders,
+                    outcome_receiver,
+                    MAX_NUM_TRANSACTIONS_PER_BATCH,
+                );
+                (vec![0, num_threads - 1], Some(scheduler))
+            }
+        };
         // Many banks that process transactions in parallel.
-        let bank_thread_hdls: Vec<JoinHandle<()>> = (0..num_threads)
+        let mut bank_thread_hdls: Vec<JoinHandle<()>> = thread_ids
+            .into_iter()
             .map(|i| {
                 let (verified_receiver, enable_forwarding) = if i < num_threads - 1 {
                     (verified_receiver.clone(), true)
@@ -307,6 +420,13 @@ impl BankingStage {
                     // Disable forwarding of vote transactions, as votes are gossiped
                     (verified_vote_receiver.clone(), false)
                 };
+                let scheduler = if i == 0 { scheduler.take() } else { None };
+                // The scheduling thread buffers the packets of all the other threads
+                let batch_limit = if scheduler.is_some() {
+                    TOTAL_BUFFERED_PACKETS / PACKETS_PER_BATCH
+                } else {
+                    batch_limit
+                };
 
                 let poh_recorder = poh_recorder.clone();
                 let cluster_info = cluster_info.clone();
@@ -330,11 +450,13 @@ impl BankingStage {
                             gossip_vote_sender,
                             &duplicates,
                             &cost_tracker,
+                            scheduler,
                         );
                     })
                     .unwrap()
             })
             .collect();
+        bank_thread_hdls.extend(worker_thread_hdls);
         Self { bank_thread_hdls }
     }
 
@@ -546,6 +668,7 @@ impl BankingStage {
         banking_stage_stats: &BankingStageStats,
         recorder: &TransactionRecorder,
         cost_tracker: &Arc<RwLock<CostTracker>>,
+        scheduler: Option<&mut TransactionScheduler>,
     ) -> BufferedPacketsDecision {
         let bank_start;
         let (
@@ -582,6 +705,16 @@ impl BankingStage {
         );
 
         match decision {
+            BufferedPacketsDecision::Consume(max_tx_ingestion_ns) if scheduler.is_some() => {
+                Self::consume_scheduled_packets(
+                    max_tx_ingestion_ns,
+                    poh_recorder,
+                    buffered_packets,
+                    scheduler.unwrap(),
+                    banking_stage_stats,
+                    cost_tracker,
+                );
+            }
             BufferedPacketsDecision::Consume(max_tx_ingestion_ns) => {
                 Self::consume_buffered_packets(
                     my_pubkey,
@@ -651,6 +784,166 @@ impl BankingStage {
         }
     }
 
+    /// Executes the buffered packets with the central scheduler, leaving the packets that were
+    /// not executed, and are still valid, in the buffer
+    fn consume_scheduled_packets(
+        max_tx_ingestion_ns: u128,
+        poh_recorder: &Arc<Mutex<PohRecorder>>,
+        buffered_packets: &mut UnprocessedPackets,
+        scheduler: &mut TransactionScheduler,
+        banking_stage_stats: &BankingStageStats,
+        cost_tracker: &Arc<RwLock<CostTracker>>,
+    ) {
+        let (bank, bank_creation_time) = match poh_recorder.lock().unwrap().bank_start() {
+            Some(bank_start) => bank_start,
+            None => return,
+        };
+        let mut proc_start = Measure::start("consume_scheduled_process");
+
+        let mut packet_conversion_time = Measure::start("packet_conversion");
+        let mut transactions = vec![];
+        let mut converted_batches = Vec::with_capacity(buffered_packets.len());
+        for (msgs, packet_indexes, _forwarded) in buffered_packets.iter() {
+            let (batch_transactions, transaction_to_packet_indexes, retryable_packet_indexes) =
+                Self::transactions_from_packets(
+                    msgs,
+                    packet_indexes,
+                    cost_tracker,
+                    banking_stage_stats,
+                );
+            banking_stage_stats
+                .cost_forced_retry_transactions_count
+                .fetch_add(retryable_packet_indexes.len(), Ordering::Relaxed);
+            let start = transactions.len();
+            transactions.extend(batch_transactions);
+            converted_batches.push((
+                start..transactions.len(),
+                transaction_to_packet_indexes,
+                retryable_packet_indexes,
+            ));
+        }
+        packet_conversion_time.stop();
+
+        let mut process_tx_time = Measure::start("process_tx_time");
+        let result = scheduler.schedule(
+            &bank,
+            &bank_creation_time,
+            max_tx_ingestion_ns,
+            &transactions,
+        );
+        process_tx_time.stop();
+
+        let mut filter_pending_packets_time = Measure::start("filter_pending_packets_time");
+        let mut new_tx_count = 0;
+        let mut rebuffered_packets_len = 0;
+        let mut converted_batches = converted_batches.into_iter();
+        RetainMut::retain_mut(
+            buffered_packets,
+            |(_msgs, ref mut original_unprocessed_indexes, _forwarded)| {
+                let (range, transaction_to_packet_indexes, retryable_packet_indexes) =
+                    converted_batches.next().unwrap();
+                let unprocessed_tx_indexes = range
+                    .clone()
+                    .filter(|id| !result.processed[*id])
+                    .map(|id| id - range.start)
+                    .collect_vec();
+                new_tx_count += range.len() - unprocessed_tx_indexes.len();
+                let mut new_unprocessed_indexes = Self::filter_pending_packets_from_pending_txs(
+                    &bank,
+                    &transactions[range],
+                    &transaction_to_packet_indexes,
+                    &unprocessed_tx_indexes,
+                );
+                new_unprocessed_indexes.extend(retryable_packet_indexes);
+                rebuffered_packets_len += new_unprocessed_indexes.len();
+                Self::update_buffered_packets_with_new_unprocessed(
+                    original_unprocessed_indexes,
+                    new_unprocessed_indexes,
+                )
+            },
+        );
+        filter_pending_packets_time.stop();
+        proc_start.stop();
+
+        debug!(
+            "bank: {} scheduled {} transactions in {} batches, processed {} retried {}",
+            bank.slot(),
+            transactions.len(),
+            result.num_batches,
+            new_tx_count,
+            result.num_retried,
+        );
+        banking_stage_stats
+            .scheduled_batches_count
+            .fetch_add(result.num_batches, Ordering::Relaxed);
+        banking_stage_stats
+            .scheduler_retried_transactions_count
+            .fetch_add(result.num_retried, Ordering::Relaxed);
+        banking_stage_stats
+            .packet_conversion_elapsed
+            .fetch_add(packet_conversion_time.as_us(), Ordering::Relaxed);
+        banking_stage_stats
+            .transaction_processing_elapsed
+            .fetch_add(process_tx_time.as_us(), Ordering::Relaxed);
+        banking_stage_stats
+            .filter_pending_packets_elapsed
+            .fetch_add(filter_pending_packets_time.as_us(), Ordering::Relaxed);
+        banking_stage_stats
+            .consume_buffered_packets_elapsed
+            .fetch_add(proc_start.as_us(), Ordering::Relaxed);
+        banking_stage_stats
+            .rebuffered_packets_count
+            .fetch_add(rebuffered_packets_len, Ordering::Relaxed);
+        banking_stage_stats
+            .consumed_buffered_packets_count
+            .fetch_add(new_tx_count, Ordering::Relaxed);
+    }
+
+    /// Executes the batches handed out by the central scheduler until the scheduler goes away
+    fn scheduled_worker_loop(
+        worker: usize,
+        batch_receiver: &CrossbeamReceiver<ScheduledBatch>,
+        outcome_sender: &CrossbeamSender<BatchOutcome>,
+        poh_recorder: &Arc<Mutex<PohRecorder>>,
+        transaction_status_sender: Option<TransactionStatusSender>,
+        gossip_vote_sender: ReplayVoteSender,
+        cost_tracker: &Arc<RwLock<CostTracker>>,
+    ) {
+        let recorder = poh_recorder.lock().unwrap().recorder();
+        for batch in batch_receiver.iter() {
+            let ScheduledBatch {
+                bank,
+                transactions,
+                ids,
+            } = batch;
+            let (result, retryable_indexes) = Self::process_and_record_transactions(
+                &bank,
+                &transactions,
+                &recorder,
+                0,
+                transaction_status_sender.clone(),
+                &gossip_vote_sender,
+            );
+            {
+                let mut cost_tracker = cost_tracker.write().unwrap();
+                for (index, tx) in transactions.iter().enumerate() {
+                    if !retryable_indexes.contains(&index) {
+                        cost_tracker.add_transaction_cost(tx.transaction());
+                    }
+                }
+            }
+            let outcome = BatchOutcome {
+                worker,
+                ids,
+                retryable_indexes,
+                max_height_reached: matches!(result, Err(PohRecorderError::MaxHeightReached)),
+            };
+            if outcome_sender.send(outcome).is_err() {
+                break;
+            }
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn process_loop(
         verified_receiver: &CrossbeamReceiver<Vec<Packets>>,
@@ -664,6 +957,7 @@ impl BankingStage {
         gossip_vote_sender: ReplayVoteSender,
         duplicates: &Arc<Mutex<(LruCache<u64, ()>, PacketHasher)>>,
         cost_tracker: &Arc<RwLock<CostTracker>>,
+        mut scheduler: Option<TransactionScheduler>,
     ) {
         let recorder = poh_recorder.lock().unwrap().recorder();
         let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
@@ -684,6 +978,7 @@ impl BankingStage {
                     &banking_stage_stats,
                     &recorder,
                     cost_tracker,
+                    scheduler.as_mut(),
                 );
                 if matches!(decision, BufferedPacketsDecision::Hold)
                     || matches!(decision, BufferedPacketsDecision::ForwardAndHold)
@@ -692,9 +987,17 @@ impl BankingStage {
                     // check the receiver for more transactions/for exiting
                     break;
                 }
+                if scheduler.is_some() {
+                    // The scheduler already handed out everything it could, so take in the
+                    // packets received meanwhile before scheduling what is left again
+                    break;
+                }
             }
 
-            let recv_timeout = if !buffered_packets.is_empty() {
+            let recv_timeout = if scheduler.is_some() && !buffered_packets.is_empty() {
+                // Only pick up what is already queued, the buffered packets keep being scheduled
+                Duration::from_millis(0)
+            } else if !buffered_packets.is_empty() {
                 // If packets are buffered, let's wait for less time on recv from the channel.
                 // This helps detect the next leader faster, and processing the buffered
                 // packets quickly
@@ -719,12 +1022,16 @@ impl BankingStage {
                 duplicates,
                 &recorder,
                 cost_tracker,
+                scheduler.is_some(),
             ) {
                 Ok(()) | Err(RecvTimeoutError::Timeout) => (),
                 Err(RecvTimeoutError::Disconnected) => break,
             }
 
             banking_stage_stats.report(1000);
+            if let Some(scheduler) = scheduler.as_mut() {
+                scheduler.report(1000);
+            }
         }
     }
 
@@ -1054,6 +1361,8 @@ impl BankingStage {
     // and verifies secp256k1 instructions. A list of valid transactions are returned with their message hashes
     // and packet indexes.
     // Also returned is packet indexes for transaction should be retried due to cost limits.
+    // Transactions that exceed the account cost limit on their own can never be packed, they are
+    // neither returned nor retried.
     #[allow(clippy::needless_collect)]
     fn transactions_from_packets(
         msgs: &Packets,
@@ -1083,10 +1392,23 @@ impl BankingStage {
             verified_transactions_with_packet_indexes
                 .into_iter()
                 .filter_map(|(tx, tx_index)| {
-                    let result = cost_tracker_readonly.would_transaction_fit(&tx);
-                    if result.is_err() {
-                        debug!("transaction {:?} would exceed limit: {:?}", tx, result);
-                        retryable_transaction_packet_indexes.push(tx_index);
+                    if let Err(err) = cost_tracker_readonly.would_transaction_fit(&tx) {
+                        debug!("transaction {:?} would exceed limit: {}", tx, err);
+                        let counter = match err {
+                            CostTrackerError::WouldExceedBlockMaxLimit => {
+                                &banking_stage_stats.cost_block_limit_transactions_count
+                            }
+                            CostTrackerError::WouldExceedAccountMaxLimit(_) => {
+                                &banking_stage_stats.cost_account_limit_transactions_count
+                            }
+                            CostTrackerError::TransactionTooExpensive => {
+                                &banking_stage_stats.cost_too_expensive_dropped_transactions_count
+                            }
+                        };
+                        counter.fetch_add(1, Ordering::Relaxed);
+                        if err.is_retryable() {
+                            retryable_transaction_packet_indexes.push(tx_index);
+                        }
                         return None;
                     }
                     Some((tx, tx_index))
@@ -1343,6 +1665,7 @@ impl BankingStage {
         duplicates: &Arc<Mutex<(LruCache<u64, ()>, PacketHasher)>>,
         recorder: &TransactionRecorder,
         cost_tracker: &Arc<RwLock<CostTracker>>,
+        buffer_all_packets: bool,
     ) -> Result<(), RecvTimeoutError> {
         let mut recv_time = Measure::start("process_packets_recv");
         let mms = verified_receiver.recv_timeout(recv_timeout)?;
@@ -1367,7 +1690,10 @@ impl BankingStage {
         while let Some(msgs) = mms_iter.next() {
             let packet_indexes = Self::generate_packet_indexes(&msgs.packets);
             let bank_start = poh.lock().unwrap().bank_start();
-            if PohRecorder::get_bank_still_processing_txs(&bank_start).is_none() {
+            // The central scheduler consumes packets only from the buffer
+            if buffer_all_packets
+                || PohRecorder::get_bank_still_processing_txs(&bank_start).is_none()
+            {
                 Self::push_unprocessed(
                     buffered_packets,
                     msgs,
@@ -1897,6 +2223,7 @@ mod tests {
                     Arc::new(RwLock::new(CostTracker::new(Arc::new(RwLock::new(
                         CostModel::default(),
                     ))))),
+                    TransactionSchedulerType::ThreadLocal,
                 );
 
                 // wait for banking_stage to eat the packets
@@ -1932,6 +2259,87 @@ mod tests {
         Blockstore::destroy(&ledger_path).unwrap();
     }
 
+    #[test]
+    fn test_banking_stage_central_scheduler() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_slow_genesis_config(10_000);
+        let (verified_sender, verified_receiver) = unbounded();
+
+        // Every transfer write locks the mint account, so no two of them may be scheduled
+        // at the same time
+        let recipients: Vec<_> = (0..8).map(|_| Pubkey::new_unique()).collect();
+        let txs: Vec<_> = recipients
+            .iter()
+            .map(|to| system_transaction::transfer(&mint_keypair, to, 1, genesis_config.hash()))
+            .collect();
+        let packets = to_packets_chunked(&txs, 4);
+        verified_sender.send(packets).unwrap();
+
+        let (vote_sender, vote_receiver) = unbounded();
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+            let bank = Arc::new(Bank::new_no_wallclock_throttle(&genesis_config));
+            let blockstore = Arc::new(
+                Blockstore::open(&ledger_path)
+                    .expect("Expected to be able to open database ledger"),
+            );
+            let poh_config = PohConfig {
+                target_tick_count: Some(bank.max_tick_height() - 1),
+                ..PohConfig::default()
+            };
+            let (exit, poh_recorder, poh_service, entry_receiver) =
+                create_test_recorder(&bank, &blockstore, Some(poh_config));
+            let cluster_info = ClusterInfo::new_with_invalid_keypair(Node::new_localhost().info);
+            let cluster_info = Arc::new(cluster_info);
+            let banking_stage = BankingStage::new_num_threads(
+                &cluster_info,
+                &poh_recorder,
+                verified_receiver,
+                vote_receiver,
+                3,
+                None,
+                gossip_vote_sender,
+                Arc::new(RwLock::new(CostTracker::new(Arc::new(RwLock::new(
+                    CostModel::default(),
+                ))))),
+                TransactionSchedulerType::Central,
+            );
+
+            while recipients.iter().any(|to| bank.get_balance(to) == 0) {
+                sleep(Duration::from_millis(100));
+            }
+            drop(verified_sender);
+            drop(vote_sender);
+            banking_stage.join().unwrap();
+            exit.store(true, Ordering::Relaxed);
+            poh_service.join().unwrap();
+            drop(poh_recorder);
+
+            let entries: Vec<_> = entry_receiver
+                .iter()
+                .map(|(_bank, (entry, _tick_height))| entry)
+                .collect();
+            let bank = Bank::new_no_wallclock_throttle(&genesis_config);
+            let mut num_transactions = 0;
+            for entry in &entries {
+                num_transactions += entry.transactions.len();
+                bank.process_transactions(&entry.transactions)
+                    .iter()
+                    .for_each(|x| assert_eq!(*x, Ok(())));
+            }
+            assert_eq!(num_transactions, recipients.len());
+            for to in &recipients {
+                assert_eq!(bank.get_balance(to), 1);
+            }
+        }
+        Blockstore::destroy(&ledger_path).unwrap();
+    }
+
     #[test]
     fn test_bank_record_transactions() {
         solana_logger::setup();
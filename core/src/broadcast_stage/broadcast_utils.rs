@@ -25,6 +25,8 @@ pub struct UnfinishedSlotInfo {
     // MAX_DATA_SHREDS_PER_FEC_BLOCK.
     pub(crate) data_shreds_buffer: Vec<Shred>,
     pub(crate) fec_set_offset: u32, // See Shredder::fec_set_index.
+    // Whether the slot is broadcast as Merkle shreds, see shred::should_use_merkle_shreds.
+    pub(crate) merkle_variant: bool,
 }
 
 /// This parameter tunes how many entries are received in one iteration of recv loop
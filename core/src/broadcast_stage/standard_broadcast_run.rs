@@ -8,8 +8,8 @@ use crate::{broadcast_stage::broadcast_utils::UnfinishedSlotInfo, cluster_nodes:
 use solana_ledger::{
     entry::Entry,
     shred::{
-        ProcessShredsStats, Shred, Shredder, MAX_DATA_SHREDS_PER_FEC_BLOCK,
-        SHRED_TICK_REFERENCE_MASK,
+        should_use_merkle_shreds, ProcessShredsStats, Shred, Shredder,
+        MAX_DATA_SHREDS_PER_FEC_BLOCK, SHRED_TICK_REFERENCE_MASK,
     },
 };
 use solana_sdk::{pubkey::Pubkey, signature::Keypair, timing::duration_as_us};
@@ -76,15 +76,29 @@ impl StandardBroadcastRun {
                     self.shred_version,
                     fec_set_index.unwrap(),
                 );
-                Shredder::sign_shred(keypair, &mut shred);
-                state.data_shreds_buffer.push(shred.clone());
-                let mut shreds = make_coding_shreds(
-                    keypair,
-                    &mut self.unfinished_slot,
-                    true, // is_last_in_slot
-                    stats,
-                );
-                shreds.insert(0, shred);
+                let shreds = if state.merkle_variant {
+                    shred.set_merkle_variant();
+                    state.data_shreds_buffer.push(shred);
+                    let (mut shreds, coding_shreds) = make_merkle_shreds(
+                        keypair,
+                        &mut self.unfinished_slot,
+                        true, // is_last_in_slot
+                        stats,
+                    );
+                    shreds.extend(coding_shreds);
+                    shreds
+                } else {
+                    Shredder::sign_shred(keypair, &mut shred);
+                    state.data_shreds_buffer.push(shred.clone());
+                    let mut shreds = make_coding_shreds(
+                        keypair,
+                        &mut self.unfinished_slot,
+                        true, // is_last_in_slot
+                        stats,
+                    );
+                    shreds.insert(0, shred);
+                    shreds
+                };
                 self.report_and_reset_stats();
                 self.unfinished_slot = None;
                 shreds
@@ -99,6 +113,7 @@ impl StandardBroadcastRun {
         blockstore: &Blockstore,
         reference_tick: u8,
         is_slot_end: bool,
+        merkle_variant: bool,
         process_stats: &mut ProcessShredsStats,
     ) -> Vec<Shred> {
         let (slot, parent_slot) = self.current_slot_and_parent.unwrap();
@@ -115,6 +130,7 @@ impl StandardBroadcastRun {
         let (data_shreds, next_shred_index) =
             Shredder::new(slot, parent_slot, reference_tick, self.shred_version)
                 .unwrap()
+                .with_merkle_variant(merkle_variant)
                 .entries_to_data_shreds(
                     keypair,
                     entries,
@@ -137,6 +153,7 @@ impl StandardBroadcastRun {
             parent: parent_slot,
             data_shreds_buffer,
             fec_set_offset,
+            merkle_variant,
         });
         data_shreds
     }
@@ -204,14 +221,29 @@ impl StandardBroadcastRun {
         // 2) Convert entries to shreds and coding shreds
         let is_last_in_slot = last_tick_height == bank.max_tick_height();
         let reference_tick = bank.tick_height() % bank.ticks_per_slot();
-        let data_shreds = self.entries_to_data_shreds(
+        let merkle_variant = should_use_merkle_shreds(bank.slot(), &bank);
+        let mut data_shreds = self.entries_to_data_shreds(
             keypair,
             &receive_results.entries,
             blockstore,
             reference_tick as u8,
             is_last_in_slot,
+            merkle_variant,
             &mut process_stats,
         );
+        // Merkle data shreds are signed along with the coding shreds of their erasure batch,
+        // so only the data shreds of completed batches are sent out
+        let mut coding_shreds = None;
+        if merkle_variant {
+            let (signed_data_shreds, batch_coding_shreds) = make_merkle_shreds(
+                keypair,
+                &mut self.unfinished_slot,
+                is_last_in_slot,
+                &mut process_stats,
+            );
+            data_shreds = signed_data_shreds;
+            coding_shreds = Some(batch_coding_shreds);
+        }
         // Insert the first shred so blockstore stores that the leader started this block
         // This must be done before the blocks are sent out over the wire.
         if !data_shreds.is_empty() && data_shreds[0].index() == 0 {
@@ -267,12 +299,14 @@ impl StandardBroadcastRun {
         blockstore_sender.send((data_shreds, batch_info.clone()))?;
 
         // Create and send coding shreds
-        let coding_shreds = make_coding_shreds(
-            keypair,
-            &mut self.unfinished_slot,
-            is_last_in_slot,
-            &mut process_stats,
-        );
+        let coding_shreds = coding_shreds.unwrap_or_else(|| {
+            make_coding_shreds(
+                keypair,
+                &mut self.unfinished_slot,
+                is_last_in_slot,
+                &mut process_stats,
+            )
+        });
         let coding_shreds = Arc::new(coding_shreds);
         socket_sender.send(((stakes, coding_shreds.clone()), batch_info.clone()))?;
         blockstore_sender.send((coding_shreds, batch_info))?;
@@ -432,7 +466,34 @@ fn make_coding_shreds(
     is_slot_end: bool,
     stats: &mut ProcessShredsStats,
 ) -> Vec<Shred> {
-    let data_shreds = match unfinished_slot {
+    let data_shreds = drain_data_shreds_buffer(unfinished_slot, is_slot_end);
+    Shredder::data_shreds_to_coding_shreds(keypair, &data_shreds, is_slot_end, stats).unwrap()
+}
+
+// Consumes data_shreds_buffer returning the signed Merkle data shreds along
+// with the coding shreds of their erasure batches.
+fn make_merkle_shreds(
+    keypair: &Keypair,
+    unfinished_slot: &mut Option<UnfinishedSlotInfo>,
+    is_slot_end: bool,
+    stats: &mut ProcessShredsStats,
+) -> (Vec<Shred>, Vec<Shred>) {
+    let mut data_shreds = drain_data_shreds_buffer(unfinished_slot, is_slot_end);
+    let coding_shreds = Shredder::data_shreds_to_merkle_coding_shreds(
+        keypair,
+        &mut data_shreds,
+        is_slot_end,
+        stats,
+    )
+    .unwrap();
+    (data_shreds, coding_shreds)
+}
+
+fn drain_data_shreds_buffer(
+    unfinished_slot: &mut Option<UnfinishedSlotInfo>,
+    is_slot_end: bool,
+) -> Vec<Shred> {
+    match unfinished_slot {
         None => Vec::default(),
         Some(unfinished_slot) => {
             let size = unfinished_slot.data_shreds_buffer.len();
@@ -447,8 +508,7 @@ fn make_coding_shreds(
                 .drain(0..size - offset)
                 .collect()
         }
-    };
-    Shredder::data_shreds_to_coding_shreds(keypair, &data_shreds, is_slot_end, stats).unwrap()
+    }
 }
 
 impl BroadcastRun for StandardBroadcastRun {
@@ -571,6 +631,7 @@ mod test {
             parent,
             data_shreds_buffer: Vec::default(),
             fec_set_offset: next_shred_index,
+            merkle_variant: false,
         });
         run.slot_broadcast_start = Some(Instant::now());
 
@@ -770,6 +831,70 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_buffer_merkle_shreds() {
+        let num_shreds_per_slot = 2;
+        let (
+            blockstore,
+            genesis_config,
+            _cluster_info,
+            bank0,
+            leader_keypair,
+            _socket,
+            _bank_forks,
+        ) = setup(num_shreds_per_slot);
+        // Merkle shreds are enabled from the epoch following the feature activation
+        let slot = bank0.epoch_schedule().get_first_slot_in_epoch(1);
+        let bank = Arc::new(Bank::new_from_parent(
+            &bank0,
+            &leader_keypair.pubkey(),
+            slot,
+        ));
+        assert!(should_use_merkle_shreds(slot, &bank));
+        let (bsend, brecv) = channel();
+        let (ssend, _srecv) = channel();
+        let mut last_tick_height = 0;
+        let mut standard_broadcast_run = StandardBroadcastRun::new(0);
+        let mut process_ticks = |num_ticks| {
+            let ticks = create_ticks(num_ticks, 0, genesis_config.hash());
+            last_tick_height += (ticks.len() - 1) as u64;
+            let receive_results = ReceiveResults {
+                entries: ticks,
+                time_elapsed: Duration::new(1, 0),
+                bank: bank.clone(),
+                last_tick_height,
+            };
+            standard_broadcast_run
+                .process_receive_results(
+                    &leader_keypair,
+                    &blockstore,
+                    &ssend,
+                    &bsend,
+                    receive_results,
+                )
+                .unwrap();
+        };
+        // Data shreds are held back until their erasure batch is complete
+        process_ticks(100);
+        process_ticks(100);
+        let mut shreds = Vec::<Shred>::new();
+        while let Ok((recv_shreds, _)) = brecv.recv_timeout(Duration::from_secs(1)) {
+            shreds.extend(recv_shreds.deref().clone());
+        }
+        assert!(shreds.is_empty(), "shreds.len(): {}", shreds.len());
+        process_ticks(1000);
+        while let Ok((recv_shreds, _)) = brecv.recv_timeout(Duration::from_secs(1)) {
+            shreds.extend(recv_shreds.deref().clone());
+        }
+        assert!(shreds
+            .iter()
+            .all(|shred| shred.is_merkle() && shred.verify(&leader_keypair.pubkey())));
+        let num_data_shreds = shreds.iter().filter(|shred| shred.is_data()).count();
+        assert!(num_data_shreds > 0);
+        assert_eq!(num_data_shreds % MAX_DATA_SHREDS_PER_FEC_BLOCK as usize, 0);
+        assert_eq!(shreds.len(), 2 * num_data_shreds);
+    }
+
     #[test]
     fn test_slot_finish() {
         // Setup
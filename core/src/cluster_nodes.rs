@@ -8,7 +8,7 @@ use {
         weighted_shuffle::{weighted_best, weighted_shuffle},
     },
     solana_sdk::pubkey::Pubkey,
-    std::{any::TypeId, cmp::Reverse, collections::HashMap, marker::PhantomData},
+    std::{any::TypeId, cmp::Reverse, collections::HashMap, marker::PhantomData, net::SocketAddr},
 };
 
 enum NodeId {
@@ -35,6 +35,36 @@ pub struct ClusterNodes<T> {
     _phantom: PhantomData<T>,
 }
 
+/// A peer in the turbine retransmit tree of a shred.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetransmitTreeNode {
+    pub pubkey: Pubkey,
+    pub stake: u64,
+    pub tvu: SocketAddr,
+    pub tvu_forwards: SocketAddr,
+}
+
+/// The turbine retransmit tree of a shred as seen from the local node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetransmitTree {
+    pub slot_leader: Option<Pubkey>,
+    // Position of the local node in the stake weighted shuffle of the cluster.
+    pub index: usize,
+    // Layer of the tree the local node is in; nodes in layer 0 receive the
+    // shred from the leader, or from the root node of the tree.
+    pub layer: usize,
+    // True if the local node is the anchor of its neighborhood, in which case
+    // it receives the shred on the tvu socket instead of tvu-forwards.
+    pub anchor: bool,
+    // Node retransmitting the shred to the local node from the previous layer.
+    pub parent: Option<RetransmitTreeNode>,
+    // Neighborhood of the local node, including the node itself. The first
+    // node is the neighborhood anchor which forwards the shred to the others.
+    pub neighbors: Vec<RetransmitTreeNode>,
+    // Nodes in the next layer the local node retransmits the shred to.
+    pub children: Vec<RetransmitTreeNode>,
+}
+
 impl Node {
     #[inline]
     fn pubkey(&self) -> Pubkey {
@@ -109,6 +139,62 @@ impl ClusterNodes<RetransmitStage> {
         Vec<&ContactInfo>, // neighbors
         Vec<&ContactInfo>, // children
     ) {
+        let (self_index, index) = self.shuffle_retransmit_index(shred_seed, slot_leader);
+        let (neighbors, children) = compute_retransmit_peers(fanout, self_index, &index);
+        // Assert that the node itself is included in the set of neighbors, at
+        // the right offset.
+        debug_assert_eq!(
+            self.nodes[neighbors[self_index % fanout]].pubkey(),
+            self.pubkey
+        );
+        let get_contact_infos = |index: Vec<usize>| -> Vec<&ContactInfo> {
+            index
+                .into_iter()
+                .map(|i| self.nodes[i].contact_info().unwrap())
+                .collect()
+        };
+        (get_contact_infos(neighbors), get_contact_infos(children))
+    }
+
+    /// Returns the full retransmit tree of the shred around the local node,
+    /// including the parent node it should receive the shred from.
+    pub fn get_retransmit_tree(
+        &self,
+        shred_seed: [u8; 32],
+        fanout: usize,
+        slot_leader: Option<Pubkey>,
+    ) -> RetransmitTree {
+        let (self_index, index) = self.shuffle_retransmit_index(shred_seed, slot_leader);
+        let (neighbors, children) = compute_retransmit_peers(fanout, self_index, &index);
+        let get_tree_node = |i: usize| -> RetransmitTreeNode {
+            let node = &self.nodes[i];
+            let contact_info = node.contact_info().unwrap();
+            RetransmitTreeNode {
+                pubkey: node.pubkey(),
+                stake: node.stake,
+                tvu: contact_info.tvu,
+                tvu_forwards: contact_info.tvu_forwards,
+            }
+        };
+        RetransmitTree {
+            slot_leader,
+            index: self_index,
+            layer: retransmit_layer(fanout, self_index),
+            anchor: self_index % fanout == 0,
+            parent: retransmit_parent(fanout, self_index)
+                .map(|parent| get_tree_node(index[parent])),
+            neighbors: neighbors.into_iter().map(get_tree_node).collect(),
+            children: children.into_iter().map(get_tree_node).collect(),
+        }
+    }
+
+    // Returns the stake weighted shuffle of nodes for the given shred seed,
+    // and the position of the local node in it.
+    fn shuffle_retransmit_index(
+        &self,
+        shred_seed: [u8; 32],
+        slot_leader: Option<Pubkey>,
+    ) -> (/*self_index:*/ usize, /*index:*/ Vec<usize>) {
         // Exclude leader from list of nodes.
         let index = self.index.iter().copied();
         let (weights, index): (Vec<u64>, Vec<usize>) = match slot_leader {
@@ -132,23 +218,39 @@ impl ClusterNodes<RetransmitStage> {
             .iter()
             .position(|i| self.nodes[*i].pubkey() == self.pubkey)
             .unwrap();
-        let (neighbors, children) = compute_retransmit_peers(fanout, self_index, &index);
-        // Assert that the node itself is included in the set of neighbors, at
-        // the right offset.
-        debug_assert_eq!(
-            self.nodes[neighbors[self_index % fanout]].pubkey(),
-            self.pubkey
-        );
-        let get_contact_infos = |index: Vec<usize>| -> Vec<&ContactInfo> {
-            index
-                .into_iter()
-                .map(|i| self.nodes[i].contact_info().unwrap())
-                .collect()
-        };
-        (get_contact_infos(neighbors), get_contact_infos(children))
+        (self_index, index)
     }
 }
 
+// Returns the layer of the retransmit tree the node at the given position of
+// the shuffle is in; layer k spans fanout**(k + 1) nodes.
+fn retransmit_layer(fanout: usize, node: usize) -> usize {
+    let mut layer = 0;
+    let mut layer_size = fanout;
+    let mut layer_end = fanout;
+    while node >= layer_end {
+        layer += 1;
+        layer_size = layer_size.saturating_mul(fanout);
+        layer_end = layer_end.saturating_add(layer_size);
+    }
+    layer
+}
+
+// Inverse of the children computation in compute_retransmit_peers: returns
+// the position of the node which has the given node among its children, or
+// None if the node is in the first layer.
+fn retransmit_parent(fanout: usize, node: usize) -> Option<usize> {
+    if node < fanout {
+        return None;
+    }
+    let offset = node % fanout;
+    // node == (anchor + 1) * fanout + offset + k * fanout, where anchor is a
+    // multiple of fanout and 0 <= k < fanout.
+    let anchor_plus_k = node / fanout - 1;
+    let anchor = anchor_plus_k - anchor_plus_k % fanout;
+    Some(anchor + offset)
+}
+
 fn new_cluster_nodes<T: 'static>(
     cluster_info: &ClusterInfo,
     stakes: &HashMap<Pubkey, u64>,
@@ -388,6 +490,68 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_retransmit_parent_and_layer() {
+        for fanout in 1..20 {
+            let index: Vec<usize> = (0..2000).collect();
+            for node in 0..index.len() {
+                let (_, children) = compute_retransmit_peers(fanout, node, &index);
+                for child in children {
+                    assert_eq!(retransmit_parent(fanout, child), Some(node));
+                    assert_eq!(
+                        retransmit_layer(fanout, child),
+                        retransmit_layer(fanout, node) + 1
+                    );
+                }
+            }
+            for node in 0..fanout {
+                assert_eq!(retransmit_parent(fanout, node), None);
+                assert_eq!(retransmit_layer(fanout, node), 0);
+            }
+        }
+        assert_eq!(retransmit_layer(200, 199), 0);
+        assert_eq!(retransmit_layer(200, 200), 1);
+        assert_eq!(retransmit_layer(200, 200 + 200 * 200 - 1), 1);
+        assert_eq!(retransmit_layer(200, 200 + 200 * 200), 2);
+    }
+
+    #[test]
+    fn test_cluster_nodes_retransmit_tree() {
+        let mut rng = rand::thread_rng();
+        let (nodes, stakes, cluster_info) = make_cluster(&mut rng);
+        let cluster_nodes = ClusterNodes::<RetransmitStage>::new(&cluster_info, &stakes);
+        let slot_leader = nodes[1..].choose(&mut rng).unwrap().id;
+        let mut shred_seed = [0u8; 32];
+        rng.fill(&mut shred_seed[..]);
+        for fanout in 1..50 {
+            let (neighbors, children) =
+                cluster_nodes.get_retransmit_peers(shred_seed, fanout, Some(slot_leader));
+            let tree = cluster_nodes.get_retransmit_tree(shred_seed, fanout, Some(slot_leader));
+            assert_eq!(tree.slot_leader, Some(slot_leader));
+            assert_eq!(tree.neighbors[tree.index % fanout].pubkey, nodes[0].id);
+            assert_eq!(
+                tree.neighbors
+                    .iter()
+                    .map(|node| node.pubkey)
+                    .collect::<Vec<_>>(),
+                neighbors.iter().map(|node| node.id).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                tree.children
+                    .iter()
+                    .map(|node| node.pubkey)
+                    .collect::<Vec<_>>(),
+                children.iter().map(|node| node.id).collect::<Vec<_>>()
+            );
+            for node in tree.neighbors.iter().chain(&tree.children) {
+                assert_ne!(node.pubkey, slot_leader);
+                assert_eq!(node.stake, stakes.get(&node.pubkey).copied().unwrap_or(0));
+            }
+            assert_eq!(tree.parent.is_none(), tree.index < fanout);
+            assert_eq!(tree.anchor, tree.neighbors[0].pubkey == nodes[0].id);
+        }
+    }
+
     #[test]
     fn test_cluster_nodes_broadcast() {
         let mut rng = rand::thread_rng();
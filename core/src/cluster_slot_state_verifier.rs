@@ -2,6 +2,7 @@ use crate::{
     fork_choice::ForkChoice, heaviest_subtree_fork_choice::HeaviestSubtreeForkChoice,
     progress_map::ProgressMap,
 };
+use solana_ledger::blockstore::Blockstore;
 use solana_sdk::{clock::Slot, hash::Hash};
 use std::collections::{BTreeMap, BTreeSet, HashSet};
 
@@ -202,6 +203,7 @@ fn apply_state_changes(
     slot: Slot,
     fork_choice: &mut HeaviestSubtreeForkChoice,
     duplicate_slots_to_repair: &mut DuplicateSlotsToRepair,
+    blockstore: &Blockstore,
     state_changes: Vec<ResultingStateChange>,
 ) {
     for state_change in state_changes {
@@ -215,6 +217,9 @@ fn apply_state_changes(
                 duplicate_slots_to_repair.insert((slot, cluster_duplicate_confirmed_hash));
             }
             ResultingStateChange::DuplicateConfirmedSlotMatchesCluster(bank_frozen_hash) => {
+                // Persist the confirmation so that this slot's ancestry can be served
+                // to peers searching for where their version of a fork diverged
+                blockstore.insert_bank_hash(slot, bank_frozen_hash, true);
                 fork_choice.mark_fork_valid_candidate(&(slot, bank_frozen_hash));
             }
         }
@@ -225,6 +230,7 @@ fn apply_state_changes(
 pub(crate) fn check_slot_agrees_with_cluster(
     slot: Slot,
     root: Slot,
+    blockstore: &Blockstore,
     frozen_hash: Option<Hash>,
     duplicate_slots_tracker: &mut DuplicateSlotsTracker,
     gossip_duplicate_confirmed_slots: &GossipDuplicateConfirmedSlots,
@@ -299,13 +305,20 @@ pub(crate) fn check_slot_agrees_with_cluster(
         is_slot_duplicate,
         is_dead,
     );
-    apply_state_changes(slot, fork_choice, duplicate_slots_to_repair, state_changes);
+    apply_state_changes(
+        slot,
+        fork_choice,
+        duplicate_slots_to_repair,
+        blockstore,
+        state_changes,
+    );
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::vote_simulator::VoteSimulator;
+    use solana_ledger::get_tmp_ledger_path;
     use solana_runtime::bank_forks::BankForks;
     use std::{
         collections::{HashMap, HashSet},
@@ -318,6 +331,7 @@ mod test {
         progress: ProgressMap,
         descendants: HashMap<Slot, HashSet<Slot>>,
         bank_forks: Arc<RwLock<BankForks>>,
+        blockstore: Blockstore,
     }
 
     fn setup() -> InitialState {
@@ -332,12 +346,15 @@ mod test {
             .unwrap()
             .descendants()
             .clone();
+        let ledger_path = get_tmp_ledger_path!();
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
 
         InitialState {
             heaviest_subtree_fork_choice: vote_simulator.heaviest_subtree_fork_choice,
             progress: vote_simulator.progress,
             descendants,
             bank_forks: vote_simulator.bank_forks,
+            blockstore,
         }
     }
 
@@ -609,6 +626,7 @@ mod test {
             mut heaviest_subtree_fork_choice,
             descendants,
             bank_forks,
+            blockstore,
             ..
         } = setup();
 
@@ -627,6 +645,7 @@ mod test {
             duplicate_slot,
             &mut heaviest_subtree_fork_choice,
             &mut duplicate_slots_to_repair,
+            &blockstore,
             vec![ResultingStateChange::MarkSlotDuplicate(duplicate_slot_hash)],
         );
         assert!(!heaviest_subtree_fork_choice
@@ -655,6 +674,7 @@ mod test {
             duplicate_slot,
             &mut heaviest_subtree_fork_choice,
             &mut duplicate_slots_to_repair,
+            &blockstore,
             vec![ResultingStateChange::DuplicateConfirmedSlotMatchesCluster(
                 duplicate_slot_hash,
             )],
@@ -676,6 +696,11 @@ mod test {
             .is_candidate(&(duplicate_slot, duplicate_slot_hash))
             .unwrap());
         assert!(duplicate_slots_to_repair.is_empty());
+        assert_eq!(
+            blockstore.get_bank_hash(duplicate_slot),
+            Some(duplicate_slot_hash)
+        );
+        assert!(blockstore.is_duplicate_confirmed(duplicate_slot));
 
         // Simulate detecting another hash that is the correct version,
         // RepairDuplicateConfirmedVersion should add the slot to repair
@@ -685,6 +710,7 @@ mod test {
             duplicate_slot,
             &mut heaviest_subtree_fork_choice,
             &mut duplicate_slots_to_repair,
+            &blockstore,
             vec![ResultingStateChange::RepairDuplicateConfirmedVersion(
                 correct_hash,
             )],
@@ -699,6 +725,7 @@ mod test {
             mut heaviest_subtree_fork_choice,
             progress,
             bank_forks,
+            blockstore,
             ..
         } = setup();
 
@@ -714,6 +741,7 @@ mod test {
         check_slot_agrees_with_cluster(
             duplicate_slot,
             root,
+            &blockstore,
             initial_bank_hash,
             &mut duplicate_slots_tracker,
             &gossip_duplicate_confirmed_slots,
@@ -741,6 +769,7 @@ mod test {
         check_slot_agrees_with_cluster(
             duplicate_slot,
             root,
+            &blockstore,
             Some(frozen_duplicate_slot_hash),
             &mut duplicate_slots_tracker,
             &gossip_duplicate_confirmed_slots,
@@ -785,6 +814,7 @@ mod test {
             mut heaviest_subtree_fork_choice,
             progress,
             bank_forks,
+            blockstore,
             ..
         } = setup();
 
@@ -803,6 +833,7 @@ mod test {
         check_slot_agrees_with_cluster(
             2,
             root,
+            &blockstore,
             Some(slot2_hash),
             &mut duplicate_slots_tracker,
             &gossip_duplicate_confirmed_slots,
@@ -833,6 +864,7 @@ mod test {
         check_slot_agrees_with_cluster(
             3,
             root,
+            &blockstore,
             Some(slot3_hash),
             &mut duplicate_slots_tracker,
             &gossip_duplicate_confirmed_slots,
@@ -876,6 +908,7 @@ mod test {
             mut heaviest_subtree_fork_choice,
             progress,
             bank_forks,
+            blockstore,
             ..
         } = setup();
 
@@ -892,6 +925,7 @@ mod test {
         check_slot_agrees_with_cluster(
             2,
             root,
+            &blockstore,
             Some(bank_forks.read().unwrap().get(2).unwrap().hash()),
             &mut duplicate_slots_tracker,
             &gossip_duplicate_confirmed_slots,
@@ -922,6 +956,7 @@ mod test {
         check_slot_agrees_with_cluster(
             3,
             root,
+            &blockstore,
             Some(slot3_hash),
             &mut duplicate_slots_tracker,
             &gossip_duplicate_confirmed_slots,
@@ -952,6 +987,7 @@ mod test {
             mut heaviest_subtree_fork_choice,
             progress,
             bank_forks,
+            blockstore,
             ..
         } = setup();
 
@@ -970,6 +1006,7 @@ mod test {
         check_slot_agrees_with_cluster(
             3,
             root,
+            &blockstore,
             Some(slot3_hash),
             &mut duplicate_slots_tracker,
             &gossip_duplicate_confirmed_slots,
@@ -1004,6 +1041,7 @@ mod test {
         check_slot_agrees_with_cluster(
             1,
             root,
+            &blockstore,
             Some(slot1_hash),
             &mut duplicate_slots_tracker,
             &gossip_duplicate_confirmed_slots,
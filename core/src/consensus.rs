@@ -20,7 +20,7 @@ use solana_sdk::{
 };
 use solana_vote_program::{
     vote_instruction,
-    vote_state::{BlockTimestamp, Lockout, Vote, VoteState, MAX_LOCKOUT_HISTORY},
+    vote_state::{BlockTimestamp, Lockout, Vote, VoteState, VoteStateUpdate, MAX_LOCKOUT_HISTORY},
 };
 use std::{
     cmp::Ordering,
@@ -72,6 +72,34 @@ impl SwitchForkDecision {
         }
     }
 
+    pub fn to_vote_state_update_instruction(
+        &self,
+        vote_state_update: VoteStateUpdate,
+        vote_account_pubkey: &Pubkey,
+        authorized_voter_pubkey: &Pubkey,
+    ) -> Option<Instruction> {
+        match self {
+            SwitchForkDecision::FailedSwitchThreshold(_, total_stake) => {
+                assert_ne!(*total_stake, 0);
+                None
+            }
+            SwitchForkDecision::FailedSwitchDuplicateRollback(_) => None,
+            SwitchForkDecision::SameFork => Some(vote_instruction::update_vote_state(
+                vote_account_pubkey,
+                authorized_voter_pubkey,
+                vote_state_update,
+            )),
+            SwitchForkDecision::SwitchProof(switch_proof_hash) => {
+                Some(vote_instruction::update_vote_state_switch(
+                    vote_account_pubkey,
+                    authorized_voter_pubkey,
+                    vote_state_update,
+                    *switch_proof_hash,
+                ))
+            }
+        }
+    }
+
     pub fn can_vote(&self) -> bool {
         match self {
             SwitchForkDecision::FailedSwitchThreshold(_, _) => false,
@@ -473,10 +501,29 @@ impl Tower {
         self.stray_restored_slot
     }
 
-    pub fn last_vote(&mut self) -> Vote {
+    pub fn last_vote(&self) -> Vote {
         self.last_vote.clone()
     }
 
+    /// The full local lockout tower and root as of the last vote, for submission
+    /// in a single `UpdateVoteState` instruction.
+    pub fn last_vote_state_update(&self) -> VoteStateUpdate {
+        // The root the tower was initialized with (e.g. slot 0 at genesis) can still
+        // be voted on, in which case it was never rooted by our votes
+        let root = self.vote_state.root_slot.filter(|root| {
+            self.vote_state
+                .votes
+                .front()
+                .map_or(true, |lockout| lockout.slot > *root)
+        });
+        VoteStateUpdate {
+            lockouts: self.vote_state.votes.clone(),
+            root,
+            hash: self.last_vote.hash,
+            timestamp: self.last_vote.timestamp,
+        }
+    }
+
     fn maybe_timestamp(&mut self, current_slot: Slot) -> Option<UnixTimestamp> {
         if current_slot > self.last_timestamp.slot
             || self.last_timestamp.slot == 0 && current_slot == self.last_timestamp.slot
@@ -1459,6 +1506,72 @@ pub mod test {
         );
     }
 
+    #[test]
+    fn test_to_vote_state_update_instruction() {
+        let vote_state_update = VoteStateUpdate::default();
+        let mut decision = SwitchForkDecision::FailedSwitchThreshold(0, 1);
+        assert!(decision
+            .to_vote_state_update_instruction(
+                vote_state_update.clone(),
+                &Pubkey::default(),
+                &Pubkey::default()
+            )
+            .is_none());
+
+        decision = SwitchForkDecision::FailedSwitchDuplicateRollback(0);
+        assert!(decision
+            .to_vote_state_update_instruction(
+                vote_state_update.clone(),
+                &Pubkey::default(),
+                &Pubkey::default()
+            )
+            .is_none());
+
+        decision = SwitchForkDecision::SameFork;
+        assert_eq!(
+            decision.to_vote_state_update_instruction(
+                vote_state_update.clone(),
+                &Pubkey::default(),
+                &Pubkey::default()
+            ),
+            Some(vote_instruction::update_vote_state(
+                &Pubkey::default(),
+                &Pubkey::default(),
+                vote_state_update.clone(),
+            ))
+        );
+
+        decision = SwitchForkDecision::SwitchProof(Hash::default());
+        assert_eq!(
+            decision.to_vote_state_update_instruction(
+                vote_state_update.clone(),
+                &Pubkey::default(),
+                &Pubkey::default()
+            ),
+            Some(vote_instruction::update_vote_state_switch(
+                &Pubkey::default(),
+                &Pubkey::default(),
+                vote_state_update,
+                Hash::default()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_last_vote_state_update() {
+        let mut tower = Tower::new_for_tests(0, 0.67);
+        for slot in 0..40 {
+            tower.record_vote(slot, Hash::new_unique());
+        }
+        let vote_state_update = tower.last_vote_state_update();
+        assert_eq!(vote_state_update.lockouts, tower.vote_state.votes);
+        assert_eq!(vote_state_update.root, Some(tower.root()));
+        assert_eq!(
+            vote_state_update.last_voted_slot_hash(),
+            tower.last_voted_slot_hash()
+        );
+    }
+
     #[test]
     fn test_simple_votes() {
         // Init state
@@ -10,9 +10,33 @@ use std::{
     collections::HashMap,
     sync::{Arc, RwLock},
 };
+use thiserror::Error;
 
 const WRITABLE_ACCOUNTS_PER_BLOCK: usize = 512;
 
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostTrackerError {
+    /// adding the transaction would exceed the block cost limit, it may fit into a later block
+    #[error("would exceed block cost limit")]
+    WouldExceedBlockMaxLimit,
+
+    /// adding the transaction would exceed the cost limit of the writable account
+    #[error("would exceed account cost limit of {0}")]
+    WouldExceedAccountMaxLimit(Pubkey),
+
+    /// the transaction alone costs more than the account cost limit, it never fits into a block
+    #[error("transaction is too expensive, exceeds account cost limit")]
+    TransactionTooExpensive,
+}
+
+impl CostTrackerError {
+    /// Whether the transaction may fit into a later block, or into the same block once other
+    /// writers of the contended account are done
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, CostTrackerError::TransactionTooExpensive)
+    }
+}
+
 #[derive(Debug)]
 pub struct CostTracker {
     cost_model: Arc<RwLock<CostModel>>,
@@ -43,7 +67,7 @@ impl CostTracker {
         }
     }
 
-    pub fn would_transaction_fit(&self, transaction: &Transaction) -> Result<(), &'static str> {
+    pub fn would_transaction_fit(&self, transaction: &Transaction) -> Result<(), CostTrackerError> {
         let mut cost_model = self.cost_model.write().unwrap();
         let tx_cost = cost_model.calculate_cost(transaction);
         self.would_fit(
@@ -73,7 +97,7 @@ impl CostTracker {
         }
     }
 
-    pub fn try_add(&mut self, transaction_cost: &TransactionCost) -> Result<u64, &'static str> {
+    pub fn try_add(&mut self, transaction_cost: &TransactionCost) -> Result<u64, CostTrackerError> {
         let cost = transaction_cost.account_access_cost + transaction_cost.execution_cost;
         self.would_fit(&transaction_cost.writable_accounts, &cost)?;
 
@@ -81,15 +105,15 @@ impl CostTracker {
         Ok(self.block_cost)
     }
 
-    fn would_fit(&self, keys: &[Pubkey], cost: &u64) -> Result<(), &'static str> {
-        // check against the total package cost
-        if self.block_cost + cost > self.block_cost_limit {
-            return Err("would exceed block cost limit");
-        }
-
+    fn would_fit(&self, keys: &[Pubkey], cost: &u64) -> Result<(), CostTrackerError> {
         // check if the transaction itself is more costly than the account_cost_limit
         if *cost > self.account_cost_limit {
-            return Err("Transaction is too expansive, exceeds account cost limit");
+            return Err(CostTrackerError::TransactionTooExpensive);
+        }
+
+        // check against the total package cost
+        if self.block_cost + cost > self.block_cost_limit {
+            return Err(CostTrackerError::WouldExceedBlockMaxLimit);
         }
 
         // check each account against account_cost_limit,
@@ -97,7 +121,7 @@ impl CostTracker {
             match self.cost_by_writable_accounts.get(account_key) {
                 Some(chained_cost) => {
                     if chained_cost + cost > self.account_cost_limit {
-                        return Err("would exceed account cost limit");
+                        return Err(CostTrackerError::WouldExceedAccountMaxLimit(*account_key));
                     } else {
                         continue;
                     }
@@ -149,6 +173,31 @@ impl CostTracker {
 
         stats
     }
+
+    pub fn block_cost(&self) -> u64 {
+        self.block_cost
+    }
+
+    pub fn block_cost_limit(&self) -> u64 {
+        self.block_cost_limit
+    }
+
+    pub fn account_cost_limit(&self) -> u64 {
+        self.account_cost_limit
+    }
+
+    /// Returns up to `num` writable accounts with the highest accumulated cost in the current
+    /// bank, costliest first
+    pub fn get_costliest_accounts(&self, num: usize) -> Vec<(Pubkey, u64)> {
+        let mut account_costs: Vec<_> = self
+            .cost_by_writable_accounts
+            .iter()
+            .map(|(key, cost)| (*key, *cost))
+            .collect();
+        account_costs.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        account_costs.truncate(num);
+        account_costs
+    }
 }
 
 #[cfg(test)]
@@ -278,7 +327,12 @@ mod tests {
         }
         // but no more sapce on the same chain (same signer account)
         {
-            assert!(testee.would_fit(&keys2, &cost2).is_err());
+            assert_eq!(
+                testee.would_fit(&keys2, &cost2),
+                Err(CostTrackerError::WouldExceedAccountMaxLimit(
+                    mint_keypair.pubkey()
+                ))
+            );
         }
     }
 
@@ -302,10 +356,26 @@ mod tests {
         }
         // but no more room for package as whole
         {
-            assert!(testee.would_fit(&keys2, &cost2).is_err());
+            assert_eq!(
+                testee.would_fit(&keys2, &cost2),
+                Err(CostTrackerError::WouldExceedBlockMaxLimit)
+            );
         }
     }
 
+    #[test]
+    fn test_cost_tracker_transaction_too_expensive() {
+        let (mint_keypair, start_hash) = test_setup();
+        let (_tx, keys, cost) = build_simple_transaction(&mint_keypair, &start_hash);
+
+        // the transaction alone exceeds the account limit, even in an empty block
+        let testee = CostTracker::new(Arc::new(RwLock::new(CostModel::new(cost - 1, cost * 10))));
+        let result = testee.would_fit(&keys, &cost);
+        assert_eq!(result, Err(CostTrackerError::TransactionTooExpensive));
+        assert!(!result.unwrap_err().is_retryable());
+        assert!(CostTrackerError::WouldExceedBlockMaxLimit.is_retryable());
+    }
+
     #[test]
     fn test_cost_tracker_reset() {
         let (mint_keypair, start_hash) = test_setup();
@@ -404,12 +474,24 @@ mod tests {
                 account_access_cost: 0,
                 execution_cost: cost,
             };
-            assert!(testee.try_add(&tx_cost).is_err());
+            assert_eq!(
+                testee.try_add(&tx_cost),
+                Err(CostTrackerError::WouldExceedAccountMaxLimit(acct2))
+            );
             let stat = testee.get_stats();
             assert_eq!(cost * 2, stat.total_cost);
             assert_eq!(3, stat.number_of_accounts);
             assert_eq!(cost * 2, stat.costliest_account_cost);
             assert_eq!(acct2, stat.costliest_account);
         }
+
+        // costliest accounts are reported in descending cost order
+        {
+            let costliest_accounts = testee.get_costliest_accounts(2);
+            assert_eq!(2, costliest_accounts.len());
+            assert_eq!((acct2, cost * 2), costliest_accounts[0]);
+            assert_eq!(cost, costliest_accounts[1].1);
+            assert_eq!(3, testee.get_costliest_accounts(10).len());
+        }
     }
 }
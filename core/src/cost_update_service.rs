@@ -2,16 +2,22 @@
 //! update cost_model which is shared with banking_stage to optimize
 //! packing transactions into block; it also triggers persisting cost
 //! table to blockstore.
+//! For every bank frozen by replay_stage it also persists the per-account
+//! cost of the block, accumulated while the block was replayed, to blockstore,
+//! so the most contended accounts of recent blocks can be queried.
 
-use crate::cost_model::CostModel;
-use solana_ledger::blockstore::Blockstore;
+use crate::{
+    cost_model::CostModel,
+    cost_tracker::{CostTracker, CostTrackerError},
+};
+use solana_ledger::{blockstore::Blockstore, blockstore_meta::BlockCost};
 use solana_measure::measure::Measure;
 use solana_runtime::bank::ExecuteTimings;
-use solana_sdk::timing::timestamp;
+use solana_sdk::{clock::Slot, timing::timestamp, transaction::Transaction};
 use std::{
     sync::{
         atomic::{AtomicBool, Ordering},
-        mpsc::Receiver,
+        mpsc::{Receiver, Sender},
         Arc, RwLock,
     },
     thread::{self, Builder, JoinHandle},
@@ -24,6 +30,8 @@ pub struct CostUpdateServiceTiming {
     update_cost_model_count: u64,
     update_cost_model_elapsed: u64,
     persist_cost_table_elapsed: u64,
+    persist_block_cost_count: u64,
+    persist_block_cost_elapsed: u64,
 }
 
 impl CostUpdateServiceTiming {
@@ -32,10 +40,14 @@ impl CostUpdateServiceTiming {
         update_cost_model_count: u64,
         update_cost_model_elapsed: u64,
         persist_cost_table_elapsed: u64,
+        persist_block_cost_count: u64,
+        persist_block_cost_elapsed: u64,
     ) {
         self.update_cost_model_count += update_cost_model_count;
         self.update_cost_model_elapsed += update_cost_model_elapsed;
         self.persist_cost_table_elapsed += persist_cost_table_elapsed;
+        self.persist_block_cost_count += persist_block_cost_count;
+        self.persist_block_cost_elapsed += persist_block_cost_elapsed;
 
         let now = timestamp();
         let elapsed_ms = now - self.last_print;
@@ -58,6 +70,16 @@ impl CostUpdateServiceTiming {
                     self.persist_cost_table_elapsed as i64,
                     i64
                 ),
+                (
+                    "persist_block_cost_count",
+                    self.persist_block_cost_count as i64,
+                    i64
+                ),
+                (
+                    "persist_block_cost_elapsed",
+                    self.persist_block_cost_elapsed as i64,
+                    i64
+                ),
             );
 
             *self = CostUpdateServiceTiming::default();
@@ -66,7 +88,71 @@ impl CostUpdateServiceTiming {
     }
 }
 
-pub type CostUpdateReceiver = Receiver<ExecuteTimings>;
+// number of costliest writable accounts persisted per block
+pub const MAX_BLOCK_COST_ACCOUNTS: usize = 32;
+
+/// Accumulates the cost of a block as its transactions are replayed
+#[derive(Debug)]
+pub struct BlockCostTracker {
+    cost_tracker: CostTracker,
+    block_cost: BlockCost,
+}
+
+impl BlockCostTracker {
+    pub fn new(cost_model: Arc<RwLock<CostModel>>, slot: Slot) -> Self {
+        let mut cost_tracker = CostTracker::new(cost_model);
+        cost_tracker.reset_if_new_bank(slot);
+        let block_cost = BlockCost {
+            block_cost_limit: cost_tracker.block_cost_limit(),
+            account_cost_limit: cost_tracker.account_cost_limit(),
+            ..BlockCost::default()
+        };
+        Self {
+            cost_tracker,
+            block_cost,
+        }
+    }
+
+    pub fn add_transaction(&mut self, transaction: &Transaction) {
+        // the transaction has already been replayed, so it is accounted for, even if it would
+        // have been rejected by a leader packing the block with this cost model
+        match self.cost_tracker.would_transaction_fit(transaction) {
+            Ok(()) => {}
+            Err(CostTrackerError::WouldExceedBlockMaxLimit) => {
+                self.block_cost.would_exceed_block_limit_count += 1;
+            }
+            Err(CostTrackerError::WouldExceedAccountMaxLimit(_))
+            | Err(CostTrackerError::TransactionTooExpensive) => {
+                self.block_cost.would_exceed_account_limit_count += 1;
+            }
+        }
+        self.cost_tracker.add_transaction_cost(transaction);
+        self.block_cost.transaction_count += 1;
+    }
+
+    pub fn finish(self) -> BlockCost {
+        let Self {
+            cost_tracker,
+            mut block_cost,
+        } = self;
+        block_cost.block_cost = cost_tracker.block_cost();
+        block_cost.account_costs = cost_tracker.get_costliest_accounts(MAX_BLOCK_COST_ACCOUNTS);
+        block_cost
+    }
+}
+
+pub enum CostUpdate {
+    FrozenBank {
+        slot: Slot,
+        block_cost: Box<BlockCost>,
+    },
+    ExecuteTiming {
+        execute_timings: Box<ExecuteTimings>,
+    },
+}
+
+pub type CostUpdateSender = Sender<CostUpdate>;
+pub type CostUpdateReceiver = Receiver<CostUpdate>;
 
 pub struct CostUpdateService {
     thread_hdl: JoinHandle<()>,
@@ -110,10 +196,18 @@ impl CostUpdateService {
             }
 
             let mut update_count = 0_u64;
+            let mut frozen_blocks = vec![];
             let mut update_cost_model_time = Measure::start("update_cost_model_time");
             for cost_update in cost_update_receiver.try_iter() {
-                dirty |= Self::update_cost_model(&cost_model, &cost_update);
-                update_count += 1;
+                match cost_update {
+                    CostUpdate::FrozenBank { slot, block_cost } => {
+                        frozen_blocks.push((slot, block_cost))
+                    }
+                    CostUpdate::ExecuteTiming { execute_timings } => {
+                        dirty |= Self::update_cost_model(&cost_model, &execute_timings);
+                        update_count += 1;
+                    }
+                }
             }
             update_cost_model_time.stop();
 
@@ -123,10 +217,18 @@ impl CostUpdateService {
             }
             persist_cost_table_time.stop();
 
+            let mut persist_block_cost_time = Measure::start("persist_block_cost_time");
+            for (slot, block_cost) in &frozen_blocks {
+                Self::persist_block_cost(&blockstore, *slot, block_cost);
+            }
+            persist_block_cost_time.stop();
+
             cost_update_service_timing.update(
                 update_count,
                 update_cost_model_time.as_us(),
                 persist_cost_table_time.as_us(),
+                frozen_blocks.len() as u64,
+                persist_block_cost_time.as_us(),
             );
 
             thread::sleep(wait_timer);
@@ -183,13 +285,28 @@ impl CostUpdateService {
                 .expect("persist program costs to blockstore");
         }
     }
+
+    fn persist_block_cost(blockstore: &Blockstore, slot: Slot, block_cost: &BlockCost) {
+        debug!("slot {} block cost {:?}", slot, block_cost);
+        blockstore
+            .write_block_cost(slot, block_cost)
+            .unwrap_or_else(|err| {
+                warn!("failed to persist block cost of slot {}: {:?}", slot, err)
+            });
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use solana_ledger::get_tmp_ledger_path;
     use solana_runtime::message_processor::ProgramTiming;
-    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::{
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        system_transaction,
+    };
 
     #[test]
     fn test_update_cost_model_with_empty_execute_timings() {
@@ -280,4 +397,50 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_persist_block_cost() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&ledger_path).unwrap();
+            let mint_keypair = Keypair::new();
+            let transactions: Vec<_> = (0..3)
+                .map(|_| {
+                    system_transaction::transfer(
+                        &mint_keypair,
+                        &Pubkey::new_unique(),
+                        1,
+                        Hash::default(),
+                    )
+                })
+                .collect();
+            let tx_cost = {
+                let mut cost_model = CostModel::default();
+                let tx_cost = cost_model.calculate_cost(&transactions[0]);
+                tx_cost.account_access_cost + tx_cost.execution_cost
+            };
+            let slot = 1;
+
+            // room for two transfers from the same payer
+            let cost_model = Arc::new(RwLock::new(CostModel::new(tx_cost * 2, tx_cost * 10)));
+            let mut block_cost_tracker = BlockCostTracker::new(cost_model, slot);
+            for transaction in &transactions {
+                block_cost_tracker.add_transaction(transaction);
+            }
+            CostUpdateService::persist_block_cost(&blockstore, slot, &block_cost_tracker.finish());
+            let block_cost = blockstore.get_block_cost(slot).unwrap().unwrap();
+            assert_eq!(block_cost.transaction_count, 3);
+            assert_eq!(block_cost.block_cost, tx_cost * 3);
+            assert_eq!(block_cost.account_cost_limit, tx_cost * 2);
+            assert_eq!(block_cost.block_cost_limit, tx_cost * 10);
+            assert_eq!(block_cost.would_exceed_account_limit_count, 1);
+            assert_eq!(block_cost.would_exceed_block_limit_count, 0);
+            assert_eq!(block_cost.account_costs.len(), 4);
+            assert_eq!(
+                block_cost.account_costs[0],
+                (mint_keypair.pubkey(), tx_cost * 3)
+            );
+        }
+        Blockstore::destroy(&ledger_path).expect("Expected successful database destruction");
+    }
 }
@@ -2,6 +2,8 @@
 
 use crate::banking_stage::HOLD_TRANSACTIONS_SLOT_OFFSET;
 use crate::result::{Error, Result};
+use crate::stake_weighted_qos::StakeWeightedQos;
+use crate::staked_nodes_updater_service::StakedNodesByIp;
 use solana_metrics::{inc_new_counter_debug, inc_new_counter_info};
 use solana_perf::packet::PacketsRecycler;
 use solana_perf::recycler::Recycler;
@@ -13,6 +15,13 @@ use std::sync::atomic::AtomicBool;
 use std::sync::mpsc::{channel, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, Builder, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Forwarded packets accepted from all sources per `FORWARDED_PACKETS_QOS_INTERVAL`, split
+// between the sources by stake
+pub const MAX_FORWARDED_PACKETS_PER_INTERVAL: usize = 50_000;
+pub const FORWARDED_PACKETS_QOS_INTERVAL: Duration = Duration::from_secs(1);
+pub const QOS_STATS_REPORT_INTERVAL: Duration = Duration::from_secs(2);
 
 pub struct FetchStage {
     thread_hdls: Vec<JoinHandle<()>>,
@@ -26,6 +35,7 @@ impl FetchStage {
         exit: &Arc<AtomicBool>,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
         coalesce_ms: u64,
+        staked_nodes: &StakedNodesByIp,
     ) -> (Self, PacketReceiver) {
         let (sender, receiver) = channel();
         (
@@ -36,6 +46,7 @@ impl FetchStage {
                 &sender,
                 poh_recorder,
                 coalesce_ms,
+                staked_nodes,
             ),
             receiver,
         )
@@ -47,6 +58,7 @@ impl FetchStage {
         sender: &PacketSender,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
         coalesce_ms: u64,
+        staked_nodes: &StakedNodesByIp,
     ) -> Self {
         let tx_sockets = sockets.into_iter().map(Arc::new).collect();
         let tpu_forwards_sockets = tpu_forwards_sockets.into_iter().map(Arc::new).collect();
@@ -57,6 +69,7 @@ impl FetchStage {
             sender,
             poh_recorder,
             coalesce_ms,
+            staked_nodes,
         )
     }
 
@@ -64,6 +77,7 @@ impl FetchStage {
         recvr: &PacketReceiver,
         sendr: &PacketSender,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
+        qos: &mut StakeWeightedQos,
     ) -> Result<()> {
         let msgs = recvr.recv()?;
         let mut len = msgs.packets.len();
@@ -83,7 +97,18 @@ impl FetchStage {
             .would_be_leader(HOLD_TRANSACTIONS_SLOT_OFFSET.saturating_mul(DEFAULT_TICKS_PER_SLOT))
         {
             inc_new_counter_debug!("fetch_stage-honor_forwards", len);
+            for packets in batch.iter_mut() {
+                for packet in packets.packets.iter_mut() {
+                    packet.meta.forward = true;
+                }
+            }
+            // Keep staked nodes' forwards from being crowded out by unstaked senders
+            let num_discarded = qos.discard_excess(&mut batch, |_| true).len();
+            inc_new_counter_info!("fetch_stage-discard_forwards_over_capacity", num_discarded);
             for packets in batch {
+                if packets.packets.iter().all(|packet| packet.meta.discard) {
+                    continue;
+                }
                 if sendr.send(packets).is_err() {
                     return Err(Error::Send);
                 }
@@ -102,6 +127,7 @@ impl FetchStage {
         sender: &PacketSender,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
         coalesce_ms: u64,
+        staked_nodes: &StakedNodesByIp,
     ) -> Self {
         let recycler: PacketsRecycler = Recycler::warmed(1000, 1024);
 
@@ -132,19 +158,34 @@ impl FetchStage {
 
         let sender = sender.clone();
         let poh_recorder = poh_recorder.clone();
+        let mut qos = StakeWeightedQos::new(
+            staked_nodes.clone(),
+            MAX_FORWARDED_PACKETS_PER_INTERVAL,
+            FORWARDED_PACKETS_QOS_INTERVAL,
+        );
 
         let fwd_thread_hdl = Builder::new()
             .name("solana-fetch-stage-fwd-rcvr".to_string())
-            .spawn(move || loop {
-                if let Err(e) =
-                    Self::handle_forwarded_packets(&forward_receiver, &sender, &poh_recorder)
-                {
-                    match e {
-                        Error::RecvTimeout(RecvTimeoutError::Disconnected) => break,
-                        Error::RecvTimeout(RecvTimeoutError::Timeout) => (),
-                        Error::Recv(_) => break,
-                        Error::Send => break,
-                        _ => error!("{:?}", e),
+            .spawn(move || {
+                let mut last_stats_report = Instant::now();
+                loop {
+                    if let Err(e) = Self::handle_forwarded_packets(
+                        &forward_receiver,
+                        &sender,
+                        &poh_recorder,
+                        &mut qos,
+                    ) {
+                        match e {
+                            Error::RecvTimeout(RecvTimeoutError::Disconnected) => break,
+                            Error::RecvTimeout(RecvTimeoutError::Timeout) => (),
+                            Error::Recv(_) => break,
+                            Error::Send => break,
+                            _ => error!("{:?}", e),
+                        }
+                    }
+                    if last_stats_report.elapsed() > QOS_STATS_REPORT_INTERVAL {
+                        qos.take_stats().report("fetch_stage-forwarded_packets_qos");
+                        last_stats_report = Instant::now();
                     }
                 }
             })
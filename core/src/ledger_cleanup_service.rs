@@ -43,7 +43,7 @@ impl LedgerCleanupService {
     pub fn new(
         new_root_receiver: Receiver<Slot>,
         blockstore: Arc<Blockstore>,
-        max_ledger_shreds: u64,
+        max_ledger_shreds: Arc<AtomicU64>,
         exit: &Arc<AtomicBool>,
         compaction_interval: Option<u64>,
         max_compaction_jitter: Option<u64>,
@@ -58,7 +58,8 @@ impl LedgerCleanupService {
 
         info!(
             "LedgerCleanupService active. max ledger shreds={}, compaction interval={}",
-            max_ledger_shreds, compaction_interval,
+            max_ledger_shreds.load(Ordering::Relaxed),
+            compaction_interval,
         );
 
         let exit_compact = exit.clone();
@@ -73,7 +74,7 @@ impl LedgerCleanupService {
                 if let Err(e) = Self::cleanup_ledger(
                     &new_root_receiver,
                     &blockstore,
-                    max_ledger_shreds,
+                    max_ledger_shreds.load(Ordering::Relaxed),
                     &mut last_purge_slot,
                     DEFAULT_PURGE_SLOT_INTERVAL,
                     &last_compact_slot,
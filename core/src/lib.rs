@@ -8,6 +8,8 @@
 //!
 
 pub mod accounts_hash_verifier;
+pub mod ancestor_hashes_service;
+pub mod bank_hash_details_service;
 pub mod banking_stage;
 pub mod broadcast_stage;
 pub mod cache_block_meta_service;
@@ -50,8 +52,11 @@ pub mod sigverify;
 pub mod sigverify_shreds;
 pub mod sigverify_stage;
 pub mod snapshot_packager_service;
+pub mod stake_weighted_qos;
+pub mod staked_nodes_updater_service;
 pub mod test_validator;
 pub mod tpu;
+pub mod transaction_scheduler;
 pub mod tree_diff;
 pub mod tvu;
 pub mod unfrozen_gossip_verified_vote_hashes;
@@ -59,6 +64,7 @@ pub mod validator;
 pub mod verified_vote_packets;
 pub mod vote_simulator;
 pub mod vote_stake_tracker;
+pub mod wen_restart;
 pub mod window_service;
 
 #[macro_use]
@@ -2,11 +2,43 @@ use crate::request_response::RequestResponse;
 use lru::LruCache;
 use rand::{thread_rng, Rng};
 use solana_ledger::shred::Nonce;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
 
 pub const DEFAULT_REQUEST_EXPIRATION_MS: u64 = 60_000;
 
+/// Requests sent to and responses received from a single peer
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PeerRequestStats {
+    pub requests: u64,
+    // Requests which received at least one valid response
+    pub responses: u64,
+    // Responses which matched an outstanding request but failed verification
+    pub invalid_responses: u64,
+    // Sum of the time to the first valid response of each request
+    pub total_latency_ms: u64,
+}
+
+impl PeerRequestStats {
+    pub fn response_rate(&self) -> Option<f64> {
+        (self.requests > 0).then(|| (self.responses as f64 / self.requests as f64).min(1.0))
+    }
+
+    pub fn average_latency_ms(&self) -> Option<u64> {
+        (self.responses > 0).then(|| self.total_latency_ms / self.responses)
+    }
+
+    fn decay(&mut self) {
+        self.requests /= 2;
+        self.responses /= 2;
+        self.invalid_responses /= 2;
+        self.total_latency_ms /= 2;
+    }
+}
+
 pub struct OutstandingRequests<T> {
     requests: LruCache<Nonce, RequestStatus<T>>,
+    peer_stats: HashMap<Pubkey, PeerRequestStats>,
 }
 
 impl<T, S> OutstandingRequests<T>
@@ -16,6 +48,17 @@ where
     // Returns boolean indicating whether sufficient time has passed for a request with
     // the given timestamp to be made
     pub fn add_request(&mut self, request: T, now: u64) -> Nonce {
+        self.do_add_request(request, None, now)
+    }
+
+    // Same as `add_request()`, additionally tracking the responses of the peer the
+    // request is sent to in `peer_stats()`
+    pub fn add_request_to_peer(&mut self, request: T, peer: Pubkey, now: u64) -> Nonce {
+        self.peer_stats.entry(peer).or_default().requests += 1;
+        self.do_add_request(request, Some(peer), now)
+    }
+
+    fn do_add_request(&mut self, request: T, peer: Option<Pubkey>, now: u64) -> Nonce {
         let num_expected_responses = request.num_expected_responses();
         let nonce = thread_rng().gen_range(0, Nonce::MAX);
         self.requests.put(
@@ -24,23 +67,36 @@ where
                 expire_timestamp: now + DEFAULT_REQUEST_EXPIRATION_MS,
                 num_expected_responses,
                 request,
+                peer,
+                request_timestamp: now,
+                has_response: false,
             },
         );
         nonce
     }
 
     pub fn register_response(&mut self, nonce: u32, response: &S, now: u64) -> bool {
+        let peer_stats = &mut self.peer_stats;
         let (is_valid, should_delete) = self
             .requests
             .get_mut(&nonce)
             .map(|status| {
-                if status.num_expected_responses > 0
-                    && now < status.expire_timestamp
-                    && status.request.verify_response(response)
+                let is_verified = status.request.verify_response(response);
+                if status.num_expected_responses > 0 && now < status.expire_timestamp && is_verified
                 {
+                    // Only the first response counts towards the latency of the peer
+                    if let (Some(peer), false) = (status.peer, status.has_response) {
+                        let stats = peer_stats.entry(peer).or_default();
+                        stats.responses += 1;
+                        stats.total_latency_ms += now.saturating_sub(status.request_timestamp);
+                    }
+                    status.has_response = true;
                     status.num_expected_responses -= 1;
                     (true, status.num_expected_responses == 0)
                 } else {
+                    if let (Some(peer), false) = (status.peer, is_verified) {
+                        peer_stats.entry(peer).or_default().invalid_responses += 1;
+                    }
                     (false, true)
                 }
             })
@@ -56,10 +112,26 @@ where
     }
 }
 
+impl<T> OutstandingRequests<T> {
+    pub fn peer_stats(&self) -> &HashMap<Pubkey, PeerRequestStats> {
+        &self.peer_stats
+    }
+
+    // Halves all the peer stats so that they reflect the recent behavior of the peers,
+    // and forgets about peers which have not been sent requests in a while
+    pub fn decay_peer_stats(&mut self) {
+        self.peer_stats.retain(|_, stats| {
+            stats.decay();
+            stats.requests > 0
+        });
+    }
+}
+
 impl<T> Default for OutstandingRequests<T> {
     fn default() -> Self {
         Self {
             requests: LruCache::new(16 * 1024),
+            peer_stats: HashMap::new(),
         }
     }
 }
@@ -68,6 +140,10 @@ pub struct RequestStatus<T> {
     expire_timestamp: u64,
     num_expected_responses: u32,
     request: T,
+    // Peer the request was sent to, if its responses are tracked
+    peer: Option<Pubkey>,
+    request_timestamp: u64,
+    has_response: bool,
 }
 
 #[cfg(test)]
@@ -174,4 +250,51 @@ pub(crate) mod tests {
         }
         assert!(outstanding_requests.requests.get(&nonce).is_none());
     }
+
+    #[test]
+    fn test_peer_stats() {
+        let repair_type = RepairType::Orphan(9);
+        let peer = Pubkey::new_unique();
+        let mut outstanding_requests = OutstandingRequests::default();
+        let now = timestamp();
+        let shred = Shred::new_empty_data_shred();
+
+        // Requests without a peer are not tracked
+        let nonce = outstanding_requests.add_request(repair_type, now);
+        assert!(outstanding_requests.register_response(nonce, &shred, now + 10));
+        assert!(outstanding_requests.peer_stats().is_empty());
+
+        // Only the first valid response of a request counts towards the latency
+        let nonce = outstanding_requests.add_request_to_peer(repair_type, peer, now);
+        assert!(outstanding_requests.register_response(nonce, &shred, now + 10));
+        assert!(outstanding_requests.register_response(nonce, &shred, now + 30));
+        let nonce = outstanding_requests.add_request_to_peer(repair_type, peer, now);
+        assert!(outstanding_requests.register_response(nonce, &shred, now + 30));
+
+        // Response for a different slot
+        let nonce = outstanding_requests.add_request_to_peer(RepairType::Shred(9, 0), peer, now);
+        assert!(!outstanding_requests.register_response(nonce, &shred, now + 10));
+
+        // No response
+        outstanding_requests.add_request_to_peer(repair_type, peer, now);
+
+        let stats = outstanding_requests.peer_stats()[&peer];
+        assert_eq!(
+            stats,
+            PeerRequestStats {
+                requests: 4,
+                responses: 2,
+                invalid_responses: 1,
+                total_latency_ms: 40,
+            }
+        );
+        assert_eq!(stats.response_rate(), Some(0.5));
+        assert_eq!(stats.average_latency_ms(), Some(20));
+
+        outstanding_requests.decay_peer_stats();
+        assert_eq!(outstanding_requests.peer_stats()[&peer].requests, 2);
+        outstanding_requests.decay_peer_stats();
+        outstanding_requests.decay_peer_stats();
+        assert!(outstanding_requests.peer_stats().is_empty());
+    }
 }
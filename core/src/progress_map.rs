@@ -1,6 +1,7 @@
 use crate::{
     cluster_info_vote_listener::SlotVoteTracker,
     cluster_slots::SlotPubkeys,
+    cost_update_service::BlockCostTracker,
     replay_stage::SUPERMINORITY_THRESHOLD,
     {consensus::Stake, consensus::VotedStakes},
 };
@@ -177,6 +178,8 @@ pub struct ForkProgress {
     // so these stats do not span all of time
     pub num_blocks_on_fork: u64,
     pub num_dropped_blocks_on_fork: u64,
+    // cost of the transactions replayed so far, sent to the cost update service on freeze
+    pub block_cost_tracker: Option<BlockCostTracker>,
 }
 
 impl ForkProgress {
@@ -219,6 +222,7 @@ impl ForkProgress {
             replay_progress: ConfirmationProgress::new(last_entry),
             num_blocks_on_fork,
             num_dropped_blocks_on_fork,
+            block_cost_tracker: None,
             propagated_stats: PropagatedStats {
                 propagated_validators,
                 propagated_validators_stake,
@@ -3,7 +3,7 @@
 use crate::{
     cluster_info_vote_listener::VerifiedVoteReceiver,
     cluster_slots::ClusterSlots,
-    outstanding_requests::OutstandingRequests,
+    outstanding_requests::{OutstandingRequests, PeerRequestStats},
     repair_weight::RepairWeight,
     replay_stage::DUPLICATE_THRESHOLD,
     result::Result,
@@ -114,12 +114,42 @@ pub const MAX_REPAIR_PER_DUPLICATE: usize = 20;
 pub const MAX_DUPLICATE_WAIT_MS: usize = 10_000;
 pub const REPAIR_MS: u64 = 100;
 pub const MAX_ORPHANS: usize = 5;
+// Peers sent fewer requests than this are not penalized for their responses
+pub const MIN_REPAIR_REQUESTS_FOR_REPUTATION: u64 = 16;
+// Lowest reputation of a peer, so that it may still be sampled and recover
+pub const MIN_REPAIR_PEER_REPUTATION: f64 = 0.05;
+// Responses faster than this on average do not improve the reputation of a peer
+pub const REPAIR_RESPONSE_LATENCY_TARGET_MS: u64 = 200;
+// Interval at which the repair peer stats are halved, so that reputations reflect
+// the recent behavior of the peers
+pub const REPAIR_PEER_STATS_DECAY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Scores how reliably a peer responds to repair requests, between
+/// `MIN_REPAIR_PEER_REPUTATION` and 1.0. Used to scale the weight of the peer
+/// when sampling peers to send repair requests to.
+pub fn repair_peer_reputation(stats: Option<&PeerRequestStats>) -> f64 {
+    let stats = match stats {
+        Some(stats) if stats.requests >= MIN_REPAIR_REQUESTS_FOR_REPUTATION => stats,
+        _ => return 1.0,
+    };
+    let response_rate = stats.response_rate().unwrap_or_default();
+    let valid_rate =
+        stats.responses as f64 / (stats.responses + stats.invalid_responses).max(1) as f64;
+    let latency_factor = stats
+        .average_latency_ms()
+        .map(|latency_ms| {
+            (REPAIR_RESPONSE_LATENCY_TARGET_MS as f64 / latency_ms.max(1) as f64).min(1.0)
+        })
+        .unwrap_or(1.0);
+    (response_rate * valid_rate * latency_factor).max(MIN_REPAIR_PEER_REPUTATION)
+}
 
 pub struct RepairInfo {
     pub bank_forks: Arc<RwLock<BankForks>>,
     pub epoch_schedule: EpochSchedule,
     pub duplicate_slots_reset_sender: DuplicateSlotsResetSender,
-    pub repair_validators: Option<HashSet<Pubkey>>,
+    // May be changed through the admin rpc while the validator is running
+    pub repair_validators: Arc<RwLock<Option<HashSet<Pubkey>>>>,
 }
 
 pub struct RepairSlotRange {
@@ -192,15 +222,26 @@ impl RepairService {
         let mut repair_stats = RepairStats::default();
         let mut repair_timing = RepairTiming::default();
         let mut last_stats = Instant::now();
+        let mut last_peer_stats_decay = Instant::now();
         let duplicate_slot_repair_statuses: HashMap<Slot, DuplicateSlotRepairStatus> =
             HashMap::new();
         let mut peers_cache = LruCache::new(REPAIR_PEERS_CACHE_CAPACITY);
+        let mut repair_validators = repair_info.repair_validators.read().unwrap().clone();
 
         loop {
             if exit.load(Ordering::Relaxed) {
                 break;
             }
 
+            {
+                let new_repair_validators = repair_info.repair_validators.read().unwrap();
+                if *new_repair_validators != repair_validators {
+                    // Cached peers were sampled from the previous whitelist
+                    repair_validators = new_repair_validators.clone();
+                    peers_cache.clear();
+                }
+            }
+
             let mut set_root_elapsed;
             let mut get_votes_elapsed;
             let mut add_votes_elapsed;
@@ -251,7 +292,7 @@ impl RepairService {
                     blockstore,
                     &serve_repair,
                     &repair_info.duplicate_slots_reset_sender,
-                    &repair_info.repair_validators,
+                    &repair_validators,
                 );
                 Self::generate_and_send_duplicate_repairs(
                     &mut duplicate_slot_repair_statuses,
@@ -260,7 +301,7 @@ impl RepairService {
                     &serve_repair,
                     &mut repair_stats,
                     &repair_socket,
-                    &repair_info.repair_validators,
+                    &repair_validators,
                 );*/
 
                 repair_weight.get_best_weighted_repairs(
@@ -282,7 +323,7 @@ impl RepairService {
                     repair_request,
                     &mut peers_cache,
                     &mut repair_stats,
-                    &repair_info.repair_validators,
+                    &repair_validators,
                     &mut outstanding_requests,
                 ) {
                     repair_socket.send_to(&req, to).unwrap_or_else(|e| {
@@ -292,6 +333,10 @@ impl RepairService {
                 }
             });
             send_repairs_elapsed.stop();
+            if last_peer_stats_decay.elapsed() > REPAIR_PEER_STATS_DECAY_INTERVAL {
+                outstanding_requests.decay_peer_stats();
+                last_peer_stats_decay = Instant::now();
+            }
             repair_timing.update(
                 set_root_elapsed.as_us(),
                 get_votes_elapsed.as_us(),
@@ -353,6 +398,7 @@ impl RepairService {
                         i64
                     ),
                 );
+                Self::report_repair_peer_stats(outstanding_requests.peer_stats());
                 repair_stats = RepairStats::default();
                 repair_timing = RepairTiming::default();
                 last_stats = Instant::now();
@@ -361,6 +407,33 @@ impl RepairService {
         }
     }
 
+    fn report_repair_peer_stats(peer_stats: &HashMap<Pubkey, PeerRequestStats>) {
+        let mut total = PeerRequestStats::default();
+        let mut num_low_reputation_peers = 0;
+        for stats in peer_stats.values() {
+            total.requests += stats.requests;
+            total.responses += stats.responses;
+            total.invalid_responses += stats.invalid_responses;
+            total.total_latency_ms += stats.total_latency_ms;
+            if repair_peer_reputation(Some(stats)) < 0.5 {
+                num_low_reputation_peers += 1;
+            }
+        }
+        datapoint_info!(
+            "serve_repair-repair-peers",
+            ("num-peers", peer_stats.len(), i64),
+            ("num-low-reputation-peers", num_low_reputation_peers, i64),
+            ("requests", total.requests, i64),
+            ("responses", total.responses, i64),
+            ("invalid-responses", total.invalid_responses, i64),
+            (
+                "average-latency-ms",
+                total.average_latency_ms().unwrap_or_default(),
+                i64
+            ),
+        );
+    }
+
     // Generate repairs for all slots `x` in the repair_range.start <= x <= repair_range.end
     pub fn generate_repairs_in_range(
         blockstore: &Blockstore,
@@ -499,7 +572,11 @@ impl RepairService {
                 if let Some(repairs) = repairs {
                     let mut outstanding_requests = outstanding_requests.write().unwrap();
                     for repair_type in repairs {
-                        let nonce = outstanding_requests.add_request(repair_type, timestamp());
+                        let nonce = outstanding_requests.add_request_to_peer(
+                            repair_type,
+                            repair_pubkey,
+                            timestamp(),
+                        );
                         if let Err(e) = Self::serialize_and_send_request(
                             &repair_type,
                             repair_socket,
@@ -1244,4 +1321,52 @@ mod test {
             vec![dead_slot]
         );
     }
+
+    #[test]
+    fn test_repair_peer_reputation() {
+        // Unknown peers and peers with few requests have the full reputation
+        assert_eq!(repair_peer_reputation(None), 1.0);
+        let stats = PeerRequestStats {
+            requests: MIN_REPAIR_REQUESTS_FOR_REPUTATION - 1,
+            ..PeerRequestStats::default()
+        };
+        assert_eq!(repair_peer_reputation(Some(&stats)), 1.0);
+
+        let stats = PeerRequestStats {
+            requests: 100,
+            responses: 100,
+            invalid_responses: 0,
+            total_latency_ms: 100 * REPAIR_RESPONSE_LATENCY_TARGET_MS / 2,
+        };
+        assert_eq!(repair_peer_reputation(Some(&stats)), 1.0);
+
+        // Half the requests are answered, twice as slowly as the target latency
+        let stats = PeerRequestStats {
+            requests: 100,
+            responses: 50,
+            invalid_responses: 0,
+            total_latency_ms: 50 * REPAIR_RESPONSE_LATENCY_TARGET_MS * 2,
+        };
+        assert!((repair_peer_reputation(Some(&stats)) - 0.25).abs() < f64::EPSILON);
+
+        // Unresponsive peers, or peers only sending invalid responses, can still be sampled
+        let stats = PeerRequestStats {
+            requests: 100,
+            ..PeerRequestStats::default()
+        };
+        assert_eq!(
+            repair_peer_reputation(Some(&stats)),
+            MIN_REPAIR_PEER_REPUTATION
+        );
+        let stats = PeerRequestStats {
+            requests: 100,
+            responses: 0,
+            invalid_responses: 100,
+            total_latency_ms: 0,
+        };
+        assert_eq!(
+            repair_peer_reputation(Some(&stats)),
+            MIN_REPAIR_PEER_REPUTATION
+        );
+    }
 }
@@ -1,6 +1,10 @@
 //! The `replay_stage` replays transactions broadcast by the leader.
 
 use crate::{
+    ancestor_hashes_service::{
+        AncestorDuplicateSlotsReceiver, AncestorHashesRequestSender, ANCESTOR_HASHES_RETRY_INTERVAL,
+    },
+    bank_hash_details_service::{BankHashDetailsSender, BankHashDetailsService},
     broadcast_stage::RetransmitSlotsSender,
     cache_block_meta_service::CacheBlockMetaSender,
     cluster_info_vote_listener::{
@@ -13,6 +17,8 @@ use crate::{
     consensus::{
         ComputedBankState, Stake, SwitchForkDecision, Tower, VotedStakes, SWITCH_FORK_THRESHOLD,
     },
+    cost_model::CostModel,
+    cost_update_service::{BlockCostTracker, CostUpdate, CostUpdateSender},
     fork_choice::{ForkChoice, SelectVoteAndResetForkResult},
     heaviest_subtree_fork_choice::HeaviestSubtreeForkChoice,
     latest_validator_votes_for_frozen_banks::LatestValidatorVotesForFrozenBanks,
@@ -22,6 +28,7 @@ use crate::{
     unfrozen_gossip_verified_vote_hashes::UnfrozenGossipVerifiedVoteHashes,
     window_service::DuplicateSlotReceiver,
 };
+use crossbeam_channel::unbounded;
 use solana_client::rpc_response::SlotUpdate;
 use solana_gossip::cluster_info::ClusterInfo;
 use solana_ledger::{
@@ -40,10 +47,12 @@ use solana_rpc::{
 };
 use solana_runtime::{
     accounts_background_service::AbsRequestSender, bank::Bank, bank::ExecuteTimings,
-    bank_forks::BankForks, commitment::BlockCommitmentCache, vote_sender_types::ReplayVoteSender,
+    bank_forks::BankForks, bank_hash_details::BankHashDetails, commitment::BlockCommitmentCache,
+    vote_sender_types::ReplayVoteSender,
 };
 use solana_sdk::{
     clock::{BankId, Slot, MAX_PROCESSING_AGE, NUM_CONSECUTIVE_LEADER_SLOTS},
+    feature_set,
     genesis_config::ClusterType,
     hash::Hash,
     pubkey::Pubkey,
@@ -52,9 +61,9 @@ use solana_sdk::{
     timing::timestamp,
     transaction::Transaction,
 };
-use solana_vote_program::vote_state::Vote;
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
     result,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -125,6 +134,8 @@ pub struct ReplayStageConfig {
     pub cache_block_meta_sender: Option<CacheBlockMetaSender>,
     pub bank_notification_sender: Option<BankNotificationSender>,
     pub wait_for_vote_to_start_leader: bool,
+    pub bank_hash_details_dir: Option<PathBuf>,
+    pub cost_model: Arc<RwLock<CostModel>>,
 }
 
 #[derive(Default)]
@@ -294,6 +305,7 @@ impl ReplayTiming {
 pub struct ReplayStage {
     t_replay: JoinHandle<()>,
     commitment_service: AggregateCommitmentService,
+    bank_hash_details_service: Option<BankHashDetailsService>,
 }
 
 impl ReplayStage {
@@ -315,7 +327,9 @@ impl ReplayStage {
         gossip_duplicate_confirmed_slots_receiver: GossipDuplicateConfirmedSlotsReceiver,
         gossip_verified_vote_hash_receiver: GossipVerifiedVoteHashReceiver,
         cluster_slots_update_sender: ClusterSlotsUpdateSender,
-        cost_update_sender: Sender<ExecuteTimings>,
+        cost_update_sender: CostUpdateSender,
+        ancestor_hashes_request_sender: AncestorHashesRequestSender,
+        ancestor_duplicate_slots_receiver: AncestorDuplicateSlotsReceiver,
     ) -> Self {
         let ReplayStageConfig {
             vote_account,
@@ -331,6 +345,8 @@ impl ReplayStage {
             cache_block_meta_sender,
             bank_notification_sender,
             wait_for_vote_to_start_leader,
+            bank_hash_details_dir,
+            cost_model,
         } = config;
 
         trace!("replay stage");
@@ -340,6 +356,14 @@ impl ReplayStage {
             block_commitment_cache.clone(),
             rpc_subscriptions.clone(),
         );
+        let (bank_hash_details_sender, bank_hash_details_service) = match bank_hash_details_dir {
+            Some(bank_hash_details_dir) => {
+                let (sender, receiver) = unbounded();
+                let service = BankHashDetailsService::new(receiver, bank_hash_details_dir, &exit);
+                (Some(sender), Some(service))
+            }
+            None => (None, None),
+        };
 
         #[allow(clippy::cognitive_complexity)]
         let t_replay = Builder::new()
@@ -365,6 +389,8 @@ impl ReplayStage {
                 let mut duplicate_slots_tracker = DuplicateSlotsTracker::default();
                 let mut gossip_duplicate_confirmed_slots: GossipDuplicateConfirmedSlots = GossipDuplicateConfirmedSlots::default();
                 let mut duplicate_slots_to_repair = HashSet::new();
+                let mut sent_bank_hash_details = HashSet::new();
+                let mut requested_ancestor_hashes = HashMap::new();
                 let mut unfrozen_gossip_verified_vote_hashes: UnfrozenGossipVerifiedVoteHashes = UnfrozenGossipVerifiedVoteHashes::default();
                 let mut latest_validator_votes_for_frozen_banks: LatestValidatorVotesForFrozenBanks = LatestValidatorVotesForFrozenBanks::default();
                 let mut voted_signatures = Vec::new();
@@ -415,6 +441,7 @@ impl ReplayStage {
                         &mut latest_validator_votes_for_frozen_banks,
                         &cluster_slots_update_sender,
                         &cost_update_sender,
+                        &cost_model,
                         &mut duplicate_slots_to_repair
                     );
                     replay_active_banks_time.stop();
@@ -425,6 +452,7 @@ impl ReplayStage {
                     let mut process_gossip_duplicate_confirmed_slots_time = Measure::start("process_gossip_duplicate_confirmed_slots");
                     Self::process_gossip_duplicate_confirmed_slots(
                         &gossip_duplicate_confirmed_slots_receiver,
+                        &blockstore,
                         &mut duplicate_slots_tracker,
                         &mut gossip_duplicate_confirmed_slots,
                         &bank_forks,
@@ -453,6 +481,7 @@ impl ReplayStage {
                     let mut process_duplicate_slots_time = Measure::start("process_duplicate_slots");
                     if !tpu_has_bank {
                         Self::process_duplicate_slots(
+                            &blockstore,
                             &duplicate_slots_receiver,
                             &mut duplicate_slots_tracker,
                             &gossip_duplicate_confirmed_slots,
@@ -464,6 +493,15 @@ impl ReplayStage {
                     }
                     process_duplicate_slots_time.stop();
 
+                    // Queue any ancestors found by `AncestorHashesService` to have diverged
+                    // from the cluster's version of the fork
+                    Self::process_ancestor_duplicate_slots(
+                        &ancestor_duplicate_slots_receiver,
+                        &bank_forks,
+                        &mut heaviest_subtree_fork_choice,
+                        &mut duplicate_slots_to_repair,
+                    );
+
                     let mut collect_frozen_banks_time = Measure::start("frozen_banks");
                     let mut frozen_banks: Vec<_> = bank_forks
                         .read()
@@ -501,7 +539,7 @@ impl ReplayStage {
                             &bank_forks,
                         );
 
-                        Self::mark_slots_confirmed(&confirmed_forks, &bank_forks, &mut progress, &mut duplicate_slots_tracker, &mut heaviest_subtree_fork_choice,  &mut duplicate_slots_to_repair);
+                        Self::mark_slots_confirmed(&confirmed_forks, &blockstore, &bank_forks, &mut progress, &mut duplicate_slots_tracker, &mut heaviest_subtree_fork_choice,  &mut duplicate_slots_to_repair);
                     }
                     compute_slot_stats_time.stop();
 
@@ -688,6 +726,23 @@ impl ReplayStage {
                     let mut dump_then_repair_correct_slots_time = Measure::start("dump_then_repair_correct_slots_time");
                     // Used for correctness check
                     let poh_bank = poh_recorder.lock().unwrap().bank();
+                    // Keep a record of our version of any slot the cluster confirmed with a
+                    // different hash before it gets dumped
+                    if let Some(bank_hash_details_sender) = &bank_hash_details_sender {
+                        Self::send_mismatched_bank_hash_details(
+                            &duplicate_slots_to_repair,
+                            &bank_forks,
+                            &mut sent_bank_hash_details,
+                            bank_hash_details_sender,
+                        );
+                    }
+                    // Our version of a slot may only differ because an ancestor diverged from the
+                    // cluster's version, so ask the cluster for the ancestors of the slots to repair
+                    Self::request_ancestor_hashes(
+                        &duplicate_slots_to_repair,
+                        &mut requested_ancestor_hashes,
+                        &ancestor_hashes_request_sender,
+                    );
                     // Dump any duplicate slots that have been confirmed by the network in
                     // anticipation of repairing the confirmed version of the slot.
                     //
@@ -765,6 +820,7 @@ impl ReplayStage {
         Self {
             t_replay,
             commitment_service,
+            bank_hash_details_service,
         }
     }
 
@@ -823,6 +879,56 @@ impl ReplayStage {
         (progress, heaviest_subtree_fork_choice)
     }
 
+    // The details are collected here, as our version of the slot is purged from the accounts
+    // once dumped, but written to disk by the `BankHashDetailsService`
+    fn send_mismatched_bank_hash_details(
+        duplicate_slots_to_repair: &DuplicateSlotsToRepair,
+        bank_forks: &RwLock<BankForks>,
+        sent_bank_hash_details: &mut HashSet<(Slot, Hash)>,
+        bank_hash_details_sender: &BankHashDetailsSender,
+    ) {
+        sent_bank_hash_details.retain(|(slot, _)| {
+            duplicate_slots_to_repair
+                .iter()
+                .any(|(duplicate_slot, _)| duplicate_slot == slot)
+        });
+        for (duplicate_slot, correct_hash) in duplicate_slots_to_repair {
+            let bank = bank_forks.read().unwrap().get(*duplicate_slot).cloned();
+            let bank = match bank {
+                Some(bank) if bank.is_frozen() && bank.hash() != *correct_hash => bank,
+                _ => continue,
+            };
+            if !sent_bank_hash_details.insert((bank.slot(), bank.hash())) {
+                continue;
+            }
+            let _ = bank_hash_details_sender.send((BankHashDetails::new(&bank), *correct_hash));
+        }
+    }
+
+    // Requests the ancestors of a slot when it is added to the slots to repair, and again
+    // every `ANCESTOR_HASHES_RETRY_INTERVAL` while it is still waiting to be repaired
+    fn request_ancestor_hashes(
+        duplicate_slots_to_repair: &DuplicateSlotsToRepair,
+        requested_ancestor_hashes: &mut HashMap<Slot, Instant>,
+        ancestor_hashes_request_sender: &AncestorHashesRequestSender,
+    ) {
+        requested_ancestor_hashes.retain(|slot, _| {
+            duplicate_slots_to_repair
+                .iter()
+                .any(|(duplicate_slot, _)| duplicate_slot == slot)
+        });
+        for (duplicate_slot, _) in duplicate_slots_to_repair {
+            let is_requested = requested_ancestor_hashes
+                .get(duplicate_slot)
+                .map(|requested| requested.elapsed() < ANCESTOR_HASHES_RETRY_INTERVAL)
+                .unwrap_or(false);
+            if !is_requested {
+                requested_ancestor_hashes.insert(*duplicate_slot, Instant::now());
+                let _ = ancestor_hashes_request_sender.send(*duplicate_slot);
+            }
+        }
+    }
+
     fn dump_then_repair_correct_slots(
         duplicate_slots_to_repair: &mut DuplicateSlotsToRepair,
         ancestors: &mut HashMap<Slot, HashSet<Slot>>,
@@ -1044,6 +1150,7 @@ impl ReplayStage {
     // for duplicate slot recovery.
     fn process_gossip_duplicate_confirmed_slots(
         gossip_duplicate_confirmed_slots_receiver: &GossipDuplicateConfirmedSlotsReceiver,
+        blockstore: &Blockstore,
         duplicate_slots_tracker: &mut DuplicateSlotsTracker,
         gossip_duplicate_confirmed_slots: &mut GossipDuplicateConfirmedSlots,
         bank_forks: &RwLock<BankForks>,
@@ -1067,6 +1174,7 @@ impl ReplayStage {
                 check_slot_agrees_with_cluster(
                     confirmed_slot,
                     root,
+                    blockstore,
                     bank_forks.read().unwrap().bank_hash(confirmed_slot),
                     duplicate_slots_tracker,
                     gossip_duplicate_confirmed_slots,
@@ -1100,6 +1208,7 @@ impl ReplayStage {
 
     // Checks for and handle forks with duplicate slots.
     fn process_duplicate_slots(
+        blockstore: &Blockstore,
         duplicate_slots_receiver: &DuplicateSlotReceiver,
         duplicate_slots_tracker: &mut DuplicateSlotsTracker,
         gossip_duplicate_confirmed_slots: &GossipDuplicateConfirmedSlots,
@@ -1125,6 +1234,7 @@ impl ReplayStage {
             check_slot_agrees_with_cluster(
                 duplicate_slot,
                 root_slot,
+                blockstore,
                 bank_hash,
                 duplicate_slots_tracker,
                 gossip_duplicate_confirmed_slots,
@@ -1136,6 +1246,38 @@ impl ReplayStage {
         }
     }
 
+    // Queues the earliest ancestors that `AncestorHashesService` found to differ from the
+    // version the cluster agreed on to be dumped and repaired
+    fn process_ancestor_duplicate_slots(
+        ancestor_duplicate_slots_receiver: &AncestorDuplicateSlotsReceiver,
+        bank_forks: &RwLock<BankForks>,
+        fork_choice: &mut HeaviestSubtreeForkChoice,
+        duplicate_slots_to_repair: &mut DuplicateSlotsToRepair,
+    ) {
+        let r_bank_forks = bank_forks.read().unwrap();
+        let root = r_bank_forks.root();
+        for (slot, correct_hash) in ancestor_duplicate_slots_receiver.try_iter() {
+            if slot <= root {
+                continue;
+            }
+            // Only our frozen versions can be dumped. Slots which are missing or not
+            // frozen yet are left alone, and ancestor hashes are requested again if
+            // they freeze with the wrong hash.
+            let frozen_hash = match r_bank_forks.bank_hash(slot) {
+                Some(frozen_hash) if frozen_hash != Hash::default() => frozen_hash,
+                _ => continue,
+            };
+            if frozen_hash == correct_hash {
+                // Already repaired since the request was made
+                continue;
+            }
+            // Stop building on our version of the fork until the correct version has
+            // been repaired
+            fork_choice.mark_fork_invalid_candidate(&(slot, frozen_hash));
+            duplicate_slots_to_repair.insert((slot, correct_hash));
+        }
+    }
+
     fn log_leader_change(
         my_pubkey: &Pubkey,
         bank_slot: Slot,
@@ -1341,8 +1483,12 @@ impl ReplayStage {
         transaction_status_sender: Option<&TransactionStatusSender>,
         replay_vote_sender: &ReplayVoteSender,
         verify_recyclers: &VerifyRecyclers,
+        cost_model: &Arc<RwLock<CostModel>>,
     ) -> result::Result<usize, BlockstoreProcessorError> {
         let tx_count_before = bank_progress.replay_progress.num_txs;
+        let block_cost_tracker = bank_progress
+            .block_cost_tracker
+            .get_or_insert_with(|| BlockCostTracker::new(cost_model.clone(), bank.slot()));
         let confirm_result = blockstore_processor::confirm_slot(
             blockstore,
             bank,
@@ -1352,6 +1498,7 @@ impl ReplayStage {
             transaction_status_sender,
             Some(replay_vote_sender),
             None,
+            Some(&mut |transaction: &Transaction| block_cost_tracker.add_transaction(transaction)),
             verify_recyclers,
             false,
         );
@@ -1416,6 +1563,7 @@ impl ReplayStage {
         check_slot_agrees_with_cluster(
             slot,
             root,
+            blockstore,
             Some(bank.hash()),
             duplicate_slots_tracker,
             gossip_duplicate_confirmed_slots,
@@ -1547,7 +1695,7 @@ impl ReplayStage {
         bank: &Bank,
         vote_account_pubkey: &Pubkey,
         authorized_voter_keypairs: &[Arc<Keypair>],
-        vote: Vote,
+        tower: &Tower,
         switch_fork_decision: &SwitchForkDecision,
         vote_signatures: &mut Vec<Signature>,
         has_new_vote_been_rooted: bool,
@@ -1600,14 +1748,25 @@ impl ReplayStage {
             Some(authorized_voter_keypair) => authorized_voter_keypair,
         };
 
-        // Send our last few votes along with the new one
-        let vote_ix = switch_fork_decision
-            .to_vote_instruction(
-                vote,
+        // Send our last few votes along with the new one, or the whole tower once
+        // votes may directly update the vote state
+        let vote_ix = if bank
+            .feature_set
+            .is_active(&feature_set::allow_votes_to_directly_update_vote_state::id())
+        {
+            switch_fork_decision.to_vote_state_update_instruction(
+                tower.last_vote_state_update(),
+                vote_account_pubkey,
+                &authorized_voter_keypair.pubkey(),
+            )
+        } else {
+            switch_fork_decision.to_vote_instruction(
+                tower.last_vote(),
                 vote_account_pubkey,
                 &authorized_voter_keypair.pubkey(),
             )
-            .expect("Switch threshold failure should not lead to voting");
+        }
+        .expect("Switch threshold failure should not lead to voting");
 
         let mut vote_tx = Transaction::new_with_payer(&[vote_ix], Some(&node_keypair.pubkey()));
 
@@ -1678,7 +1837,7 @@ impl ReplayStage {
             heaviest_bank_on_same_fork,
             vote_account_pubkey,
             authorized_voter_keypairs,
-            tower.last_vote(),
+            tower,
             &SwitchForkDecision::SameFork,
             vote_signatures,
             has_new_vote_been_rooted,
@@ -1725,7 +1884,7 @@ impl ReplayStage {
             bank,
             vote_account_pubkey,
             authorized_voter_keypairs,
-            tower.last_vote(),
+            tower,
             switch_fork_decision,
             vote_signatures,
             has_new_vote_been_rooted,
@@ -1814,7 +1973,8 @@ impl ReplayStage {
         unfrozen_gossip_verified_vote_hashes: &mut UnfrozenGossipVerifiedVoteHashes,
         latest_validator_votes_for_frozen_banks: &mut LatestValidatorVotesForFrozenBanks,
         cluster_slots_update_sender: &ClusterSlotsUpdateSender,
-        cost_update_sender: &Sender<ExecuteTimings>,
+        cost_update_sender: &CostUpdateSender,
+        cost_model: &Arc<RwLock<CostModel>>,
         duplicate_slots_to_repair: &mut DuplicateSlotsToRepair,
     ) -> bool {
         let mut did_complete_bank = false;
@@ -1866,6 +2026,7 @@ impl ReplayStage {
                     transaction_status_sender,
                     replay_vote_sender,
                     verify_recyclers,
+                    cost_model,
                 );
                 execute_timings.accumulate(&bank_progress.replay_stats.execute_timings);
                 match replay_result {
@@ -1897,6 +2058,11 @@ impl ReplayStage {
                     bank_progress.replay_progress.num_entries,
                     bank_progress.replay_progress.num_shreds,
                 );
+                // blocks produced by this node are not replayed, so they have no tracker
+                let block_cost = bank_progress
+                    .block_cost_tracker
+                    .take()
+                    .map(BlockCostTracker::finish);
                 did_complete_bank = true;
                 info!("bank frozen: {}", bank.slot());
                 let _ = cluster_slots_update_sender.send(vec![*bank_slot]);
@@ -1917,9 +2083,13 @@ impl ReplayStage {
                     .get_fork_stats_mut(bank.slot())
                     .expect("All frozen banks must exist in the Progress map")
                     .bank_hash = Some(bank.hash());
+                // Recorded before `check_slot_agrees_with_cluster()`, which may mark
+                // this version of the slot as duplicate confirmed
+                blockstore.insert_bank_hash(bank.slot(), bank_hash, false);
                 check_slot_agrees_with_cluster(
                     bank.slot(),
                     bank_forks.read().unwrap().root(),
+                    blockstore,
                     Some(bank.hash()),
                     duplicate_slots_tracker,
                     gossip_duplicate_confirmed_slots,
@@ -1949,6 +2119,14 @@ impl ReplayStage {
                     }
                 }
                 Self::record_rewards(&bank, rewards_recorder_sender);
+                if let Some(block_cost) = block_cost {
+                    cost_update_sender
+                        .send(CostUpdate::FrozenBank {
+                            slot: bank.slot(),
+                            block_cost: Box::new(block_cost),
+                        })
+                        .unwrap_or_else(|err| warn!("cost_update_sender failed: {:?}", err));
+                }
             } else {
                 trace!(
                     "bank {} not completed tick_height: {}, max_tick_height: {}",
@@ -1961,7 +2139,9 @@ impl ReplayStage {
 
         // send accumulated excute-timings to cost_update_service
         cost_update_sender
-            .send(execute_timings)
+            .send(CostUpdate::ExecuteTiming {
+                execute_timings: Box::new(execute_timings),
+            })
             .unwrap_or_else(|err| warn!("cost_update_sender failed: {:?}", err));
 
         inc_new_counter_info!("replay_stage-replay_transactions", tx_count);
@@ -2436,6 +2616,7 @@ impl ReplayStage {
 
     fn mark_slots_confirmed(
         confirmed_forks: &[Slot],
+        blockstore: &Blockstore,
         bank_forks: &RwLock<BankForks>,
         progress: &mut ProgressMap,
         duplicate_slots_tracker: &mut DuplicateSlotsTracker,
@@ -2461,6 +2642,7 @@ impl ReplayStage {
                 check_slot_agrees_with_cluster(
                     *slot,
                     root_slot,
+                    blockstore,
                     bank_hash,
                     duplicate_slots_tracker,
                     // Don't need to pass the gossip confirmed slots since `slot`
@@ -2659,6 +2841,9 @@ impl ReplayStage {
 
     pub fn join(self) -> thread::Result<()> {
         self.commitment_service.join()?;
+        if let Some(bank_hash_details_service) = self.bank_hash_details_service {
+            bank_hash_details_service.join()?;
+        }
         self.t_replay.join().map(|_| ())
     }
 }
@@ -3325,6 +3510,7 @@ mod tests {
                 None,
                 &replay_vote_sender,
                 &VerifyRecyclers::default(),
+                &Arc::new(RwLock::new(CostModel::default())),
             );
 
             let rpc_subscriptions = Arc::new(RpcSubscriptions::new(
@@ -4779,6 +4965,7 @@ mod tests {
         check_slot_agrees_with_cluster(
             4,
             bank_forks.read().unwrap().root(),
+            &blockstore,
             Some(bank4_hash),
             &mut duplicate_slots_tracker,
             &gossip_duplicate_confirmed_slots,
@@ -4805,6 +4992,7 @@ mod tests {
         check_slot_agrees_with_cluster(
             2,
             bank_forks.read().unwrap().root(),
+            &blockstore,
             Some(bank2_hash),
             &mut duplicate_slots_tracker,
             &gossip_duplicate_confirmed_slots,
@@ -4834,6 +5022,7 @@ mod tests {
         check_slot_agrees_with_cluster(
             4,
             bank_forks.read().unwrap().root(),
+            &blockstore,
             Some(bank4_hash),
             &mut duplicate_slots_tracker,
             &gossip_duplicate_confirmed_slots,
@@ -4860,6 +5049,48 @@ mod tests {
         assert_eq!(reset_fork.unwrap(), 4);
     }
 
+    #[test]
+    fn test_request_ancestor_hashes() {
+        let (sender, receiver) = unbounded();
+        let mut requested_ancestor_hashes = HashMap::new();
+        let mut duplicate_slots_to_repair = DuplicateSlotsToRepair::default();
+        duplicate_slots_to_repair.insert((1, Hash::new_unique()));
+        ReplayStage::request_ancestor_hashes(
+            &duplicate_slots_to_repair,
+            &mut requested_ancestor_hashes,
+            &sender,
+        );
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![1]);
+
+        // Only the newly added slot is requested
+        duplicate_slots_to_repair.insert((2, Hash::new_unique()));
+        ReplayStage::request_ancestor_hashes(
+            &duplicate_slots_to_repair,
+            &mut requested_ancestor_hashes,
+            &sender,
+        );
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![2]);
+
+        // Slots still waiting to be repaired are requested again after the retry interval
+        *requested_ancestor_hashes.get_mut(&1).unwrap() -= ANCESTOR_HASHES_RETRY_INTERVAL;
+        ReplayStage::request_ancestor_hashes(
+            &duplicate_slots_to_repair,
+            &mut requested_ancestor_hashes,
+            &sender,
+        );
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![1]);
+
+        // Repaired slots are forgotten
+        duplicate_slots_to_repair.clear();
+        ReplayStage::request_ancestor_hashes(
+            &duplicate_slots_to_repair,
+            &mut requested_ancestor_hashes,
+            &sender,
+        );
+        assert!(requested_ancestor_hashes.is_empty());
+        assert!(receiver.try_iter().next().is_none());
+    }
+
     #[test]
     fn test_dump_then_repair_correct_slots() {
         // Create the tree of banks in a BankForks object
@@ -4914,6 +5145,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_process_ancestor_duplicate_slots() {
+        let forks = tr(0) / (tr(1) / (tr(2)));
+        let ReplayBlockstoreComponents {
+            ref mut vote_simulator,
+            ..
+        } = replay_blockstore_components(Some(forks), 1, None);
+        let VoteSimulator {
+            ref bank_forks,
+            ref mut heaviest_subtree_fork_choice,
+            ..
+        } = vote_simulator;
+        // Slot 3 is not frozen yet, and slot 4 is missing from BankForks
+        let bank3 = Bank::new_from_parent(
+            &bank_forks.read().unwrap().get(2).cloned().unwrap(),
+            &Pubkey::default(),
+            3,
+        );
+        bank_forks.write().unwrap().insert(bank3);
+        let bank2_hash = bank_forks.read().unwrap().bank_hash(2).unwrap();
+
+        let (ancestor_duplicate_slots_sender, ancestor_duplicate_slots_receiver) = unbounded();
+        let mut duplicate_slots_to_repair = DuplicateSlotsToRepair::default();
+        for slot in 1..=4 {
+            ancestor_duplicate_slots_sender
+                .send((slot, Hash::new_unique()))
+                .unwrap();
+        }
+        // The correct version of slot 2 was already repaired
+        ancestor_duplicate_slots_sender
+            .send((2, bank2_hash))
+            .unwrap();
+        ReplayStage::process_ancestor_duplicate_slots(
+            &ancestor_duplicate_slots_receiver,
+            bank_forks,
+            heaviest_subtree_fork_choice,
+            &mut duplicate_slots_to_repair,
+        );
+        let mut slots: Vec<_> = duplicate_slots_to_repair
+            .iter()
+            .map(|(slot, _)| *slot)
+            .collect();
+        slots.sort_unstable();
+        assert_eq!(slots, vec![1, 2]);
+        assert_eq!(
+            heaviest_subtree_fork_choice.is_candidate(&(2, bank2_hash)),
+            Some(false)
+        );
+    }
+
     fn setup_vote_then_rollback(
         first_vote: Slot,
         num_validators: usize,
@@ -4974,6 +5255,7 @@ mod tests {
         check_slot_agrees_with_cluster(
             2,
             bank_forks.read().unwrap().root(),
+            blockstore,
             Some(our_bank2_hash),
             &mut duplicate_slots_tracker,
             &gossip_duplicate_confirmed_slots,
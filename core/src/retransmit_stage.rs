@@ -3,11 +3,11 @@
 
 use crate::{
     cluster_info_vote_listener::VerifiedVoteReceiver,
-    cluster_nodes::ClusterNodes,
+    cluster_nodes::{ClusterNodes, RetransmitTree, RetransmitTreeNode},
     cluster_slots::ClusterSlots,
     cluster_slots_service::{ClusterSlotsService, ClusterSlotsUpdateReceiver},
     completed_data_sets_service::CompletedDataSetsSender,
-    repair_service::{DuplicateSlotsResetSender, RepairInfo},
+    repair_service::{DuplicateSlotsResetSender, OutstandingRepairs, RepairInfo},
     result::{Error, Result},
     window_service::{should_retransmit_and_persist, WindowService},
 };
@@ -15,7 +15,9 @@ use crossbeam_channel::{Receiver, Sender};
 use lru::LruCache;
 use solana_client::rpc_response::SlotUpdate;
 use solana_gossip::cluster_info::{ClusterInfo, DATA_PLANE_FANOUT};
-use solana_ledger::shred::{get_shred_slot_index_type, ShredFetchStats};
+use solana_ledger::shred::{
+    deterministic_shred_seed, get_shred_slot_index_type, Shred, ShredFetchStats,
+};
 use solana_ledger::{blockstore::Blockstore, leader_schedule_cache::LeaderScheduleCache};
 use solana_measure::measure::Measure;
 use solana_metrics::inc_new_counter_error;
@@ -27,7 +29,7 @@ use solana_streamer::streamer::PacketReceiver;
 use std::{
     collections::hash_set::HashSet,
     collections::{BTreeMap, BTreeSet, HashMap},
-    net::UdpSocket,
+    net::{IpAddr, UdpSocket},
     ops::{Deref, DerefMut},
     sync::atomic::{AtomicBool, AtomicU64, Ordering},
     sync::mpsc::channel,
@@ -37,9 +39,12 @@ use std::{
     thread::{self, Builder, JoinHandle},
     time::Duration,
 };
+use thiserror::Error;
 
 const MAX_DUPLICATE_COUNT: usize = 2;
 const DEFAULT_LRU_SIZE: usize = 10_000;
+// Maximum number of source addresses tracked in retransmit peer stats.
+const PEER_STATS_LRU_SIZE: usize = 4_096;
 
 // Limit a given thread to consume about this many packets so that
 // it doesn't pull up too much work.
@@ -62,6 +67,55 @@ struct RetransmitStats {
     packets_by_source: Mutex<BTreeMap<String, usize>>,
 }
 
+/// Turbine shreds received from a peer, keyed by the source ip address.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetransmitPeerStats {
+    pub num_shreds: u64,
+    pub num_duplicates: u64,
+    // Shreds received on the tvu-forwards socket.
+    pub num_forwarded: u64,
+    // Timestamp in milliseconds of the last shred received.
+    pub last_received: u64,
+}
+
+pub type RetransmitPeerStatsCache = Mutex<LruCache<IpAddr, RetransmitPeerStats>>;
+
+impl RetransmitPeerStats {
+    fn record(&mut self, forwarded: bool, duplicate: bool, now: u64) {
+        if duplicate {
+            self.num_duplicates += 1;
+        } else {
+            self.num_shreds += 1;
+        }
+        if forwarded {
+            self.num_forwarded += 1;
+        }
+        self.last_received = now;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.num_shreds += other.num_shreds;
+        self.num_duplicates += other.num_duplicates;
+        self.num_forwarded += other.num_forwarded;
+        self.last_received = self.last_received.max(other.last_received);
+    }
+}
+
+fn update_peer_stats(
+    cache: &RetransmitPeerStatsCache,
+    peer_stats: HashMap<IpAddr, RetransmitPeerStats>,
+) {
+    let mut cache = cache.lock().unwrap();
+    for (addr, stats) in peer_stats {
+        match cache.get_mut(&addr) {
+            Some(entry) => entry.merge(&stats),
+            None => {
+                cache.put(addr, stats);
+            }
+        }
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn update_retransmit_stats(
     stats: &RetransmitStats,
@@ -216,6 +270,8 @@ pub type ShredFilterAndHasher = (ShredFilter, PacketHasher);
 
 // Returns None if shred is already received and should skip retransmit.
 // Otherwise returns shred's slot and whether the shred is a data shred.
+// Merkle shreds reach the retransmit stage once their proof of inclusion has been checked against
+// the verified Merkle roots in sigverify, and are deduplicated by the same key as legacy shreds.
 fn check_if_already_received(
     packet: &Packet,
     shreds_received: &Mutex<ShredFilterAndHasher>,
@@ -283,6 +339,7 @@ fn retransmit(
     max_slots: &MaxSlots,
     first_shreds_received: &Mutex<BTreeSet<Slot>>,
     rpc_subscriptions: &Option<Arc<RpcSubscriptions>>,
+    peer_stats_cache: &RetransmitPeerStatsCache,
 ) -> Result<()> {
     const RECV_TIMEOUT: Duration = Duration::from_secs(1);
     let r_lock = r.lock().unwrap();
@@ -337,6 +394,7 @@ fn retransmit(
     let mut retransmit_tree_mismatch = 0;
     let mut packets_by_slot: HashMap<Slot, usize> = HashMap::new();
     let mut packets_by_source: HashMap<String, usize> = HashMap::new();
+    let mut peer_stats: HashMap<IpAddr, RetransmitPeerStats> = HashMap::new();
     let mut max_slot = 0;
     for packet in packets.iter().flat_map(|p| p.packets.iter()) {
         // skip discarded packets and repair packets
@@ -350,7 +408,12 @@ fn retransmit(
             repair_total += 1;
             continue;
         }
-        let shred_slot = match check_if_already_received(packet, shreds_received) {
+        let shred_slot = check_if_already_received(packet, shreds_received);
+        peer_stats
+            .entry(packet.meta.addr().ip())
+            .or_default()
+            .record(packet.meta.forward, shred_slot.is_none(), now);
+        let shred_slot = match shred_slot {
             Some(slot) => slot,
             None => continue,
         };
@@ -411,6 +474,7 @@ fn retransmit(
         retransmit_total += retransmit_time.as_us();
     }
     max_slots.retransmit.fetch_max(max_slot, Ordering::Relaxed);
+    update_peer_stats(peer_stats_cache, peer_stats);
     timer_start.stop();
     debug!(
         "retransmitted {} packets in {}ms retransmit_time: {}ms id: {}",
@@ -446,6 +510,8 @@ fn retransmit(
 /// * `leader_schedule_cache` - The leader schedule to verify shreds
 /// * `cluster_info` - This structure needs to be updated and populated by the bank and via gossip.
 /// * `r` - Receive channel for shreds to be retransmitted to all the layer 1 nodes.
+/// * `peer_stats` - Per peer receive statistics updated by the retransmit threads.
+#[allow(clippy::too_many_arguments)]
 pub fn retransmitter(
     sockets: Arc<Vec<UdpSocket>>,
     bank_forks: Arc<RwLock<BankForks>>,
@@ -454,6 +520,7 @@ pub fn retransmitter(
     r: Arc<Mutex<PacketReceiver>>,
     max_slots: &Arc<MaxSlots>,
     rpc_subscriptions: Option<Arc<RpcSubscriptions>>,
+    peer_stats: Arc<RetransmitPeerStatsCache>,
 ) -> Vec<JoinHandle<()>> {
     let stats = Arc::new(RetransmitStats::default());
     let shreds_received = Arc::new(Mutex::new((
@@ -475,6 +542,7 @@ pub fn retransmitter(
             let max_slots = max_slots.clone();
             let first_shreds_received = first_shreds_received.clone();
             let rpc_subscriptions = rpc_subscriptions.clone();
+            let peer_stats = peer_stats.clone();
 
             Builder::new()
                 .name("solana-retransmitter".to_string())
@@ -495,6 +563,7 @@ pub fn retransmitter(
                             &max_slots,
                             &first_shreds_received,
                             &rpc_subscriptions,
+                            &peer_stats,
                         ) {
                             match e {
                                 Error::RecvTimeout(RecvTimeoutError::Disconnected) => break,
@@ -512,10 +581,113 @@ pub fn retransmitter(
         .collect()
 }
 
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RetransmitTreeError {
+    #[error("slot {0} has no known leader")]
+    UnknownSlotLeader(Slot),
+    #[error("shred {1} of slot {0} is not in the blockstore and its seed is not deterministic")]
+    ShredSeedUnavailable(Slot, u32),
+}
+
+/// Turbine retransmit tree of the local node together with receive stats of
+/// its peers.
+#[derive(Clone, Debug)]
+pub struct RetransmitTreeInfo {
+    pub slot: Slot,
+    pub shred_index: u32,
+    pub tree: RetransmitTree,
+    pub peer_stats: HashMap<Pubkey, RetransmitPeerStats>,
+}
+
+/// Computes the turbine retransmit tree of arbitrary shreds as the
+/// retransmit stage would, for diagnostics.
+#[derive(Clone)]
+pub struct RetransmitTreeInspector {
+    bank_forks: Arc<RwLock<BankForks>>,
+    leader_schedule_cache: Arc<LeaderScheduleCache>,
+    blockstore: Arc<Blockstore>,
+    cluster_info: Arc<ClusterInfo>,
+    peer_stats: Arc<RetransmitPeerStatsCache>,
+}
+
+impl RetransmitTreeInspector {
+    pub fn new(
+        bank_forks: Arc<RwLock<BankForks>>,
+        leader_schedule_cache: Arc<LeaderScheduleCache>,
+        blockstore: Arc<Blockstore>,
+        cluster_info: Arc<ClusterInfo>,
+        peer_stats: Arc<RetransmitPeerStatsCache>,
+    ) -> Self {
+        Self {
+            bank_forks,
+            leader_schedule_cache,
+            blockstore,
+            cluster_info,
+            peer_stats,
+        }
+    }
+
+    pub fn get_retransmit_tree(
+        &self,
+        slot: Slot,
+        shred_index: u32,
+    ) -> std::result::Result<RetransmitTreeInfo, RetransmitTreeError> {
+        let (working_bank, root_bank) = {
+            let bank_forks = self.bank_forks.read().unwrap();
+            (bank_forks.working_bank(), bank_forks.root_bank())
+        };
+        let slot_leader = self
+            .leader_schedule_cache
+            .slot_leader_at(slot, Some(&working_bank))
+            .ok_or(RetransmitTreeError::UnknownSlotLeader(slot))?;
+        let shred_seed = self
+            .get_shred(slot, shred_index)
+            .map(|shred| shred.seed(Some(slot_leader), &root_bank))
+            .or_else(|| deterministic_shred_seed(slot, shred_index, &slot_leader, &root_bank))
+            .ok_or(RetransmitTreeError::ShredSeedUnavailable(slot, shred_index))?;
+        let bank_epoch = working_bank.get_leader_schedule_epoch(working_bank.slot());
+        let epoch_staked_nodes = working_bank.epoch_staked_nodes(bank_epoch);
+        let cluster_nodes = ClusterNodes::<RetransmitStage>::new(
+            &self.cluster_info,
+            &epoch_staked_nodes.unwrap_or_default(),
+        );
+        let tree =
+            cluster_nodes.get_retransmit_tree(shred_seed, DATA_PLANE_FANOUT, Some(slot_leader));
+        let peer_stats = {
+            let mut cache = self.peer_stats.lock().unwrap();
+            tree.parent
+                .iter()
+                .chain(&tree.neighbors)
+                .chain(&tree.children)
+                .filter_map(|node: &RetransmitTreeNode| {
+                    let stats = cache.get(&node.tvu.ip())?;
+                    Some((node.pubkey, *stats))
+                })
+                .collect()
+        };
+        Ok(RetransmitTreeInfo {
+            slot,
+            shred_index,
+            tree,
+            peer_stats,
+        })
+    }
+
+    fn get_shred(&self, slot: Slot, shred_index: u32) -> Option<Shred> {
+        let index = u64::from(shred_index);
+        let payload = match self.blockstore.get_data_shred(slot, index) {
+            Ok(Some(payload)) => payload,
+            _ => self.blockstore.get_coding_shred(slot, index).ok()??,
+        };
+        Shred::new_from_serialized_shred(payload).ok()
+    }
+}
+
 pub struct RetransmitStage {
     thread_hdls: Vec<JoinHandle<()>>,
     window_service: WindowService,
     cluster_slots_service: ClusterSlotsService,
+    retransmit_tree_inspector: RetransmitTreeInspector,
 }
 
 impl RetransmitStage {
@@ -537,7 +709,7 @@ impl RetransmitStage {
         cluster_slots: Arc<ClusterSlots>,
         duplicate_slots_reset_sender: DuplicateSlotsResetSender,
         verified_vote_receiver: VerifiedVoteReceiver,
-        repair_validators: Option<HashSet<Pubkey>>,
+        repair_validators: Arc<RwLock<Option<HashSet<Pubkey>>>>,
         completed_data_sets_sender: CompletedDataSetsSender,
         max_slots: &Arc<MaxSlots>,
         rpc_subscriptions: Option<Arc<RpcSubscriptions>>,
@@ -546,6 +718,7 @@ impl RetransmitStage {
         let (retransmit_sender, retransmit_receiver) = channel();
 
         let retransmit_receiver = Arc::new(Mutex::new(retransmit_receiver));
+        let peer_stats = Arc::new(Mutex::new(LruCache::new(PEER_STATS_LRU_SIZE)));
         let thread_hdls = retransmitter(
             retransmit_sockets,
             bank_forks.clone(),
@@ -554,6 +727,14 @@ impl RetransmitStage {
             retransmit_receiver,
             max_slots,
             rpc_subscriptions,
+            peer_stats.clone(),
+        );
+        let retransmit_tree_inspector = RetransmitTreeInspector::new(
+            bank_forks.clone(),
+            leader_schedule_cache.clone(),
+            blockstore.clone(),
+            cluster_info.clone(),
+            peer_stats,
         );
 
         let cluster_slots_service = ClusterSlotsService::new(
@@ -606,9 +787,18 @@ impl RetransmitStage {
             thread_hdls,
             window_service,
             cluster_slots_service,
+            retransmit_tree_inspector,
         }
     }
 
+    pub fn retransmit_tree_inspector(&self) -> &RetransmitTreeInspector {
+        &self.retransmit_tree_inspector
+    }
+
+    pub fn outstanding_repairs(&self) -> &Arc<RwLock<OutstandingRepairs>> {
+        self.window_service.outstanding_repairs()
+    }
+
     pub fn join(self) -> thread::Result<()> {
         for thread_hdl in self.thread_hdls {
             thread_hdl.join()?;
@@ -625,10 +815,12 @@ mod tests {
     use solana_gossip::contact_info::ContactInfo;
     use solana_ledger::blockstore_processor::{process_blockstore, ProcessOptions};
     use solana_ledger::create_new_tmp_ledger;
+    use solana_ledger::entry::Entry;
     use solana_ledger::genesis_utils::{create_genesis_config, GenesisConfigInfo};
-    use solana_ledger::shred::Shred;
+    use solana_ledger::shred::{Shred, Shredder};
     use solana_net_utils::find_available_port_in_range;
     use solana_perf::packet::{Packet, Packets};
+    use solana_sdk::signature::Keypair;
     use std::net::{IpAddr, Ipv4Addr};
 
     #[test]
@@ -680,6 +872,7 @@ mod tests {
             Arc::new(Mutex::new(retransmit_receiver)),
             &Arc::new(MaxSlots::default()),
             None,
+            Arc::new(Mutex::new(LruCache::new(PEER_STATS_LRU_SIZE))),
         );
 
         let mut shred = Shred::new_from_data(0, 0, 0, None, true, true, 0, 0x20, 0);
@@ -767,4 +960,26 @@ mod tests {
         assert_eq!(check_if_already_received(&packet, &shreds_received), None);
         assert_eq!(check_if_already_received(&packet, &shreds_received), None);
     }
+
+    #[test]
+    fn test_already_received_merkle_shreds() {
+        let slot = 1;
+        let shredder = Shredder::new(slot, 0, 0, 0)
+            .unwrap()
+            .with_merkle_variant(true);
+        let (data_shreds, coding_shreds, _) =
+            shredder.entries_to_shreds(&Keypair::new(), &[Entry::default()], true, 0);
+        let shreds_received = Arc::new(Mutex::new((LruCache::new(100), PacketHasher::default())));
+        // The shreds of a Merkle erasure batch share their signature, each of them is still
+        // retransmitted once
+        for shred in data_shreds.iter().chain(coding_shreds.iter()) {
+            let mut packet = Packet::default();
+            shred.copy_to_packet(&mut packet);
+            assert_eq!(
+                check_if_already_received(&packet, &shreds_received),
+                Some(slot)
+            );
+            assert_eq!(check_if_already_received(&packet, &shreds_received), None);
+        }
+    }
 }
@@ -1,7 +1,7 @@
 use crate::{
     cluster_slots::ClusterSlots,
     repair_response,
-    repair_service::{OutstandingRepairs, RepairStats},
+    repair_service::{repair_peer_reputation, OutstandingRepairs, RepairStats},
     request_response::RequestResponse,
     result::{Error, Result},
 };
@@ -17,13 +17,14 @@ use solana_gossip::{
     weighted_shuffle::weighted_best,
 };
 use solana_ledger::{
+    ancestor_iterator::AncestorIterator,
     blockstore::Blockstore,
     shred::{Nonce, Shred},
 };
 use solana_measure::measure::Measure;
 use solana_metrics::inc_new_counter_debug;
 use solana_perf::packet::{limited_deserialize, Packets, PacketsRecycler};
-use solana_sdk::{clock::Slot, pubkey::Pubkey, timing::duration_as_ms};
+use solana_sdk::{clock::Slot, hash::Hash, pubkey::Pubkey, timing::duration_as_ms};
 use solana_streamer::streamer::{PacketReceiver, PacketSender};
 use std::{
     collections::HashSet,
@@ -36,6 +37,9 @@ use std::{
 
 /// the number of slots to respond with when responding to `Orphan` requests
 pub const MAX_ORPHAN_REPAIR_RESPONSES: usize = 10;
+/// the maximum number of (slot, hash) pairs in a response to `AncestorHashes` requests,
+/// so that the serialized response and the nonce fit in a single packet
+pub const MAX_ANCESTOR_RESPONSES: usize = 30;
 // Number of slots to cache their respective repair peers and sampling weights.
 pub(crate) const REPAIR_PEERS_CACHE_CAPACITY: usize = 128;
 // Limit cache entries ttl in order to avoid re-using outdated data.
@@ -58,6 +62,56 @@ impl RepairType {
     }
 }
 
+/// Request for the (slot, hash) chain of ancestors of a slot, used to find the
+/// earliest ancestor where the local version of a fork diverged from the cluster
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct AncestorHashesRepairType(pub Slot);
+
+impl AncestorHashesRepairType {
+    pub fn slot(&self) -> Slot {
+        self.0
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum AncestorHashesResponseVersion {
+    // Ancestors of the requested slot, inclusive, in descending slot order.
+    // Empty if the responder has not duplicate confirmed the requested slot.
+    Current(Vec<(Slot, Hash)>),
+}
+
+impl AncestorHashesResponseVersion {
+    pub fn slot_hashes(&self) -> &[(Slot, Hash)] {
+        match self {
+            AncestorHashesResponseVersion::Current(slot_hashes) => slot_hashes,
+        }
+    }
+
+    pub fn into_slot_hashes(self) -> Vec<(Slot, Hash)> {
+        match self {
+            AncestorHashesResponseVersion::Current(slot_hashes) => slot_hashes,
+        }
+    }
+}
+
+impl RequestResponse for AncestorHashesRepairType {
+    type Response = AncestorHashesResponseVersion;
+    fn num_expected_responses(&self) -> u32 {
+        1
+    }
+    fn verify_response(&self, response: &AncestorHashesResponseVersion) -> bool {
+        let slot_hashes = response.slot_hashes();
+        slot_hashes.len() <= MAX_ANCESTOR_RESPONSES
+            && slot_hashes
+                .first()
+                .map(|(slot, _)| *slot == self.0)
+                .unwrap_or(true)
+            && slot_hashes
+                .windows(2)
+                .all(|ancestors| ancestors[0].0 > ancestors[1].0)
+    }
+}
+
 impl RequestResponse for RepairType {
     type Response = Shred;
     fn num_expected_responses(&self) -> u32 {
@@ -89,6 +143,7 @@ pub struct ServeRepairStats {
     pub window_index: usize,
     pub highest_window_index: usize,
     pub orphan: usize,
+    pub ancestor_hashes: usize,
 }
 
 /// Window protocol messages
@@ -100,6 +155,7 @@ pub enum RepairProtocol {
     WindowIndexWithNonce(ContactInfo, u64, u64, Nonce),
     HighestWindowIndexWithNonce(ContactInfo, u64, u64, Nonce),
     OrphanWithNonce(ContactInfo, u64, Nonce),
+    AncestorHashes(ContactInfo, Slot, Nonce),
 }
 
 #[derive(Clone)]
@@ -168,6 +224,7 @@ impl ServeRepair {
             RepairProtocol::WindowIndexWithNonce(ref from, _, _, _) => from,
             RepairProtocol::HighestWindowIndexWithNonce(ref from, _, _, _) => from,
             RepairProtocol::OrphanWithNonce(ref from, _, _) => from,
+            RepairProtocol::AncestorHashes(ref from, _, _) => from,
         }
     }
 
@@ -235,6 +292,13 @@ impl ServeRepair {
                         "OrphanWithNonce",
                     )
                 }
+                RepairProtocol::AncestorHashes(_, slot, nonce) => {
+                    stats.ancestor_hashes += 1;
+                    (
+                        Self::run_ancestor_hashes(recycler, from_addr, blockstore, *slot, *nonce),
+                        "AncestorHashes",
+                    )
+                }
                 _ => (None, "Unsupported repair type"),
             }
         };
@@ -319,6 +383,10 @@ impl ServeRepair {
             stats.highest_window_index
         );
         inc_new_counter_debug!("serve_repair-request-orphan", stats.orphan);
+        inc_new_counter_debug!(
+            "serve_repair-request-ancestor-hashes",
+            stats.ancestor_hashes
+        );
 
         *stats = ServeRepairStats::default();
     }
@@ -417,6 +485,16 @@ impl ServeRepair {
         Ok(out)
     }
 
+    pub(crate) fn ancestor_repair_request_bytes(
+        &self,
+        request_slot: Slot,
+        nonce: Nonce,
+    ) -> Result<Vec<u8>> {
+        let req = RepairProtocol::AncestorHashes(self.my_info(), request_slot, nonce);
+        let out = serialize(&req)?;
+        Ok(out)
+    }
+
     pub(crate) fn repair_request(
         &self,
         cluster_slots: &ClusterSlots,
@@ -434,15 +512,29 @@ impl ServeRepair {
             _ => {
                 peers_cache.pop(&slot);
                 let repair_peers = self.repair_peers(repair_validators, slot);
-                let weights = cluster_slots.compute_weights(slot, &repair_peers);
+                let peer_stats = outstanding_requests.peer_stats();
+                // Bias the selection away from peers which have been unresponsive or
+                // have sent invalid responses
+                let weights: Vec<u64> = cluster_slots
+                    .compute_weights(slot, &repair_peers)
+                    .into_iter()
+                    .zip(&repair_peers)
+                    .map(|(weight, peer)| {
+                        let reputation = repair_peer_reputation(peer_stats.get(&peer.id));
+                        ((weight as f64 * reputation) as u64).max(1)
+                    })
+                    .collect();
                 let repair_peers = RepairPeers::new(Instant::now(), &repair_peers, &weights)?;
                 peers_cache.put(slot, repair_peers);
                 peers_cache.get(&slot).unwrap()
             }
         };
         let (peer, addr) = repair_peers.sample(&mut rand::thread_rng());
-        let nonce =
-            outstanding_requests.add_request(repair_request, solana_sdk::timing::timestamp());
+        let nonce = outstanding_requests.add_request_to_peer(
+            repair_request,
+            peer,
+            solana_sdk::timing::timestamp(),
+        );
         let out = self.map_repair_request(&repair_request, &peer, repair_stats, nonce)?;
         Ok((addr, out))
     }
@@ -620,6 +712,41 @@ impl ServeRepair {
         }
         Some(res)
     }
+
+    fn run_ancestor_hashes(
+        recycler: &PacketsRecycler,
+        from_addr: &SocketAddr,
+        blockstore: Option<&Arc<Blockstore>>,
+        slot: Slot,
+        nonce: Nonce,
+    ) -> Option<Packets> {
+        let blockstore = blockstore?;
+        let ancestor_slot_hashes = if blockstore.is_duplicate_confirmed(slot) {
+            // Stop at the first ancestor without a known frozen hash, i.e. one
+            // that was rooted from a snapshot rather than replayed
+            AncestorIterator::new_inclusive(slot, blockstore)
+                .map(|ancestor_slot| (ancestor_slot, blockstore.get_bank_hash(ancestor_slot)))
+                .take_while(|(_, frozen_hash)| frozen_hash.is_some())
+                .take(MAX_ANCESTOR_RESPONSES)
+                .map(|(ancestor_slot, frozen_hash)| (ancestor_slot, frozen_hash.unwrap()))
+                .collect()
+        } else {
+            // If this slot is not duplicate confirmed, return nothing
+            vec![]
+        };
+        let response = AncestorHashesResponseVersion::Current(ancestor_slot_hashes);
+        let serialized_response = serialize(&response).ok()?;
+        let packet = repair_response::repair_response_packet_from_bytes(
+            serialized_response,
+            from_addr,
+            nonce,
+        )?;
+        Some(Packets::new_unpinned_with_recycler_data(
+            recycler,
+            "run_ancestor_hashes",
+            vec![packet],
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -631,7 +758,7 @@ mod tests {
     use solana_ledger::{
         blockstore::make_many_slot_entries,
         blockstore_processor::fill_blockstore_slot_with_ticks,
-        shred::{max_ticks_per_n_shreds, Shred},
+        shred::{max_ticks_per_n_shreds, Shred, SIZE_OF_NONCE},
     };
     use solana_perf::packet::Packet;
     use solana_sdk::{hash::Hash, pubkey::Pubkey, timing::timestamp};
@@ -1130,6 +1257,111 @@ mod tests {
         assert!(!request.verify_response(&shred));
     }
 
+    #[test]
+    fn test_verify_ancestor_response() {
+        let request = AncestorHashesRepairType(9);
+        let response = |slots: &[Slot]| {
+            AncestorHashesResponseVersion::Current(
+                slots
+                    .iter()
+                    .map(|slot| (*slot, Hash::new_unique()))
+                    .collect(),
+            )
+        };
+        assert!(request.verify_response(&response(&[])));
+        assert!(request.verify_response(&response(&[9])));
+        assert!(request.verify_response(&response(&[9, 8, 5])));
+        // Must start at the requested slot
+        assert!(!request.verify_response(&response(&[8, 5])));
+        assert!(!request.verify_response(&response(&[10, 9])));
+        // Must be in descending slot order
+        assert!(!request.verify_response(&response(&[9, 5, 8])));
+        assert!(!request.verify_response(&response(&[9, 9])));
+        // Must not exceed the maximum number of ancestors
+        let request = AncestorHashesRepairType(MAX_ANCESTOR_RESPONSES as Slot);
+        let slots: Vec<Slot> = (0..=MAX_ANCESTOR_RESPONSES as Slot).rev().collect();
+        assert!(request.verify_response(&response(&slots[..MAX_ANCESTOR_RESPONSES])));
+        assert!(!request.verify_response(&response(&slots)));
+    }
+
+    #[test]
+    fn test_run_ancestor_hashes() {
+        fn deserialize_response(packets: Packets) -> (AncestorHashesResponseVersion, Nonce) {
+            assert_eq!(packets.packets.len(), 1);
+            let packet = &packets.packets[0];
+            let data = &packet.data[..packet.meta.size];
+            let response = limited_deserialize(&data[..data.len() - SIZE_OF_NONCE]).unwrap();
+            (response, repair_response::nonce(data).unwrap())
+        }
+
+        solana_logger::setup();
+        let recycler = PacketsRecycler::default();
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let slot = 0;
+            let num_slots = MAX_ANCESTOR_RESPONSES as u64 + 10;
+            let nonce = 10;
+            let blockstore = Arc::new(Blockstore::open(&ledger_path).unwrap());
+
+            // Create slots [slot, slot + num_slots) with 5 shreds apiece
+            let (shreds, _) = make_many_slot_entries(slot, num_slots, 5);
+            blockstore
+                .insert_shreds(shreds, None, false)
+                .expect("Expect successful ledger write");
+
+            // We don't have slot `slot + num_slots`, so we return empty
+            let rv = ServeRepair::run_ancestor_hashes(
+                &recycler,
+                &socketaddr_any!(),
+                Some(&blockstore),
+                slot + num_slots,
+                nonce,
+            )
+            .expect("run_ancestor_hashes packets");
+            let (response, response_nonce) = deserialize_response(rv);
+            assert_eq!(response_nonce, nonce);
+            assert!(response.slot_hashes().is_empty());
+
+            // `slot + num_slots - 1` is not marked duplicate confirmed so nothing should return
+            // empty
+            let rv = ServeRepair::run_ancestor_hashes(
+                &recycler,
+                &socketaddr_any!(),
+                Some(&blockstore),
+                slot + num_slots - 1,
+                nonce,
+            )
+            .expect("run_ancestor_hashes packets");
+            assert!(deserialize_response(rv).0.slot_hashes().is_empty());
+
+            // Set duplicate confirmed
+            let mut expected_ancestors = Vec::with_capacity(num_slots as usize);
+            for duplicate_confirmed_slot in slot..slot + num_slots {
+                let frozen_hash = Hash::new_unique();
+                expected_ancestors.push((duplicate_confirmed_slot, frozen_hash));
+                blockstore.insert_bank_hash(duplicate_confirmed_slot, frozen_hash, true);
+            }
+            expected_ancestors.reverse();
+            let rv = ServeRepair::run_ancestor_hashes(
+                &recycler,
+                &socketaddr_any!(),
+                Some(&blockstore),
+                slot + num_slots - 1,
+                nonce,
+            )
+            .expect("run_ancestor_hashes packets");
+            let (response, _) = deserialize_response(rv);
+            assert_eq!(
+                response.slot_hashes(),
+                &expected_ancestors[..MAX_ANCESTOR_RESPONSES]
+            );
+            let request = AncestorHashesRepairType(slot + num_slots - 1);
+            assert!(request.verify_response(&response));
+        }
+
+        Blockstore::destroy(&ledger_path).expect("Expected successful database destruction");
+    }
+
     fn verify_responses<'a>(request: &RepairType, packets: impl Iterator<Item = &'a Packet>) {
         for packet in packets {
             let shred_payload = packet.data.to_vec();
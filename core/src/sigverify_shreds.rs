@@ -3,7 +3,7 @@ use crate::sigverify;
 use crate::sigverify_stage::SigVerifier;
 use solana_ledger::leader_schedule_cache::LeaderScheduleCache;
 use solana_ledger::shred::Shred;
-use solana_ledger::sigverify_shreds::verify_shreds_gpu;
+use solana_ledger::sigverify_shreds::{verify_shreds_gpu, MerkleRootCache};
 use solana_perf::{self, packet::Packets, recycler_cache::RecyclerCache};
 use solana_runtime::bank_forks::BankForks;
 use std::collections::{HashMap, HashSet};
@@ -14,6 +14,7 @@ pub struct ShredSigVerifier {
     bank_forks: Arc<RwLock<BankForks>>,
     leader_schedule_cache: Arc<LeaderScheduleCache>,
     recycler_cache: RecyclerCache,
+    merkle_root_cache: Arc<MerkleRootCache>,
 }
 
 impl ShredSigVerifier {
@@ -26,6 +27,7 @@ impl ShredSigVerifier {
             bank_forks,
             leader_schedule_cache,
             recycler_cache: RecyclerCache::warmed(),
+            merkle_root_cache: Arc::default(),
         }
     }
     fn read_slots(batches: &[Packets]) -> HashSet<u64> {
@@ -51,7 +53,12 @@ impl SigVerifier for ShredSigVerifier {
             .collect();
         leader_slots.insert(std::u64::MAX, [0u8; 32]);
 
-        let r = verify_shreds_gpu(&batches, &leader_slots, &self.recycler_cache);
+        let r = verify_shreds_gpu(
+            &batches,
+            &leader_slots,
+            &self.recycler_cache,
+            &self.merkle_root_cache,
+        );
         solana_perf::sigverify::mark_disabled(&mut batches, &r);
         batches
     }
@@ -4,23 +4,253 @@
 //! signature in that packet is valid. It assumes each packet contains one
 //! transaction. All processing is done on the CPU by default and on a GPU
 //! if perf-libs are available
+//!
+//! When load shedding is enabled, duplicate packets are discarded before verification,
+//! and the packets queued beyond what the verifier threads can take at once are discarded
+//! according to a `SigVerifyDiscardPolicy`, so that the backlog cannot grow without bound
+//! under a flood.
 
+use crate::fetch_stage::{FORWARDED_PACKETS_QOS_INTERVAL, QOS_STATS_REPORT_INTERVAL};
+use crate::packet_hasher::PacketHasher;
 use crate::sigverify;
+use crate::stake_weighted_qos::StakeWeightedQos;
+use crate::staked_nodes_updater_service::StakedNodesByIp;
 use crossbeam_channel::{SendError, Sender as CrossbeamSender};
 use solana_measure::measure::Measure;
 use solana_metrics::datapoint_debug;
-use solana_perf::packet::Packets;
+use solana_perf::packet::{Packet, Packets};
 use solana_perf::perf_libs;
-use solana_sdk::timing;
+use solana_perf::sigverify::get_packet_num_signatures;
+use solana_runtime::bloom::Bloom;
+use solana_sdk::timing::{self, duration_as_ms};
 use solana_streamer::streamer::{self, PacketReceiver, StreamerError};
+use std::cmp::Reverse;
+use std::str::FromStr;
 use std::sync::mpsc::{Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, Builder, JoinHandle};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+const NUM_VERIFIER_THREADS: usize = 4;
 const RECV_BATCH_MAX_CPU: usize = 1_000;
 const RECV_BATCH_MAX_GPU: usize = 5_000;
 
+// Packets a verifier thread keeps from a single receive when load shedding is enabled,
+// which is as many as all the verifier threads take together without load shedding
+const MAX_QUEUED_PACKETS_CPU: usize = NUM_VERIFIER_THREADS * RECV_BATCH_MAX_CPU;
+const MAX_QUEUED_PACKETS_GPU: usize = NUM_VERIFIER_THREADS * RECV_BATCH_MAX_GPU;
+
+// Packets a verifier thread drains from the queue at once when load shedding is enabled, so
+// that the receiver lock is not held for as long as a flood lasts
+const MAX_DRAINED_PACKETS_CPU: usize = 4 * MAX_QUEUED_PACKETS_CPU;
+const MAX_DRAINED_PACKETS_GPU: usize = 4 * MAX_QUEUED_PACKETS_GPU;
+
+// The dedup filter is cleared at this interval, so that it neither saturates nor
+// discards a packet legitimately sent again much later
+const DEDUP_FILTER_RESET_INTERVAL: Duration = Duration::from_secs(2);
+const DEDUP_FILTER_MAX_ITEMS: usize = 1_000_000;
+const DEDUP_FILTER_FALSE_RATE: f64 = 0.001;
+const DEDUP_FILTER_MAX_BITS: usize = 1 << 24;
+
+const DISCARD_STATS_REPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+// Forwarded packets verified per `FORWARDED_PACKETS_QOS_INTERVAL` across all verifier
+// threads, split between the sources by stake, so that forwards cannot take over the
+// verification capacity
+pub const MAX_VERIFIED_FORWARDED_PACKETS_PER_INTERVAL: usize = 20_000;
+
+struct ForwardedPacketsQos {
+    qos: StakeWeightedQos,
+    last_stats_report: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SigVerifyDiscardPolicy {
+    /// The packets received last are discarded
+    TailDrop,
+    /// The packets from the sources with the least stake are discarded first
+    PreferStaked,
+    /// The packets of the transactions with the fewest signatures are discarded first
+    PreferMoreSignatures,
+}
+
+impl Default for SigVerifyDiscardPolicy {
+    fn default() -> Self {
+        Self::TailDrop
+    }
+}
+
+impl FromStr for SigVerifyDiscardPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "tail-drop" => Ok(Self::TailDrop),
+            "prefer-staked" => Ok(Self::PreferStaked),
+            "prefer-more-signatures" => Ok(Self::PreferMoreSignatures),
+            _ => Err(format!("Unknown sigverify discard policy: {}", s)),
+        }
+    }
+}
+
+struct PacketDeduper {
+    filter: Bloom<[u8; 8]>,
+    hasher: PacketHasher,
+    last_reset: Instant,
+}
+
+impl Default for PacketDeduper {
+    fn default() -> Self {
+        Self {
+            filter: Bloom::random(
+                DEDUP_FILTER_MAX_ITEMS,
+                DEDUP_FILTER_FALSE_RATE,
+                DEDUP_FILTER_MAX_BITS,
+            ),
+            hasher: PacketHasher::default(),
+            last_reset: Instant::now(),
+        }
+    }
+}
+
+impl PacketDeduper {
+    fn reset_if_expired(&mut self) {
+        if self.last_reset.elapsed() >= DEDUP_FILTER_RESET_INTERVAL {
+            self.filter.clear();
+            self.hasher.reset();
+            self.last_reset = Instant::now();
+        }
+    }
+
+    // Discards the packets seen before and returns their number
+    fn dedup_packets(&mut self, batches: &mut [Packets]) -> usize {
+        self.reset_if_expired();
+        let mut num_dups = 0;
+        for packet in batches
+            .iter_mut()
+            .flat_map(|batch| batch.packets.iter_mut())
+        {
+            if packet.meta.discard {
+                continue;
+            }
+            let key = self.hasher.hash_packet(packet).to_le_bytes();
+            if self.filter.contains(&key) {
+                packet.meta.discard = true;
+                num_dups += 1;
+            } else {
+                self.filter.add(&key);
+            }
+        }
+        num_dups
+    }
+}
+
+struct LoadShedder {
+    discard_policy: SigVerifyDiscardPolicy,
+    staked_nodes: Option<StakedNodesByIp>,
+    deduper: Mutex<PacketDeduper>,
+}
+
+impl LoadShedder {
+    fn new(discard_policy: SigVerifyDiscardPolicy, staked_nodes: Option<StakedNodesByIp>) -> Self {
+        Self {
+            discard_policy,
+            staked_nodes,
+            deduper: Mutex::new(PacketDeduper::default()),
+        }
+    }
+
+    fn dedup_packets(&self, batches: &mut [Packets]) -> usize {
+        self.deduper.lock().unwrap().dedup_packets(batches)
+    }
+
+    // Discards the packets beyond `max_packets` which the policy ranks lowest, and
+    // returns their number
+    fn shed_excess_packets(&self, batches: &mut [Packets], max_packets: usize) -> usize {
+        let stakes = match (self.discard_policy, &self.staked_nodes) {
+            (SigVerifyDiscardPolicy::PreferStaked, Some(staked_nodes)) => {
+                Some(staked_nodes.read().unwrap())
+            }
+            _ => None,
+        };
+        let priority = |packet: &Packet| match self.discard_policy {
+            SigVerifyDiscardPolicy::TailDrop => 0,
+            SigVerifyDiscardPolicy::PreferStaked => stakes
+                .as_ref()
+                .and_then(|stakes| stakes.get(&packet.meta.addr().ip()).copied())
+                .unwrap_or_default(),
+            // The count is only trusted once checked against the message, so that malformed
+            // packets rank lowest.
+            SigVerifyDiscardPolicy::PreferMoreSignatures => {
+                get_packet_num_signatures(packet).unwrap_or_default() as u64
+            }
+        };
+        let mut packets: Vec<_> = batches
+            .iter()
+            .enumerate()
+            .flat_map(|(batch_index, batch)| {
+                batch
+                    .packets
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, packet)| !packet.meta.discard)
+                    .map(move |(packet_index, packet)| (batch_index, packet_index, packet))
+            })
+            .map(|(batch_index, packet_index, packet)| {
+                (priority(packet), batch_index, packet_index)
+            })
+            .collect();
+        drop(stakes);
+        if packets.len() <= max_packets {
+            return 0;
+        }
+        // The sort is stable, so the packets received last go first among equals
+        packets.sort_by_key(|(priority, _, _)| Reverse(*priority));
+        for (_, batch_index, packet_index) in &packets[max_packets..] {
+            batches[*batch_index].packets[*packet_index].meta.discard = true;
+        }
+        packets.len() - max_packets
+    }
+}
+
+struct SigVerifierStats {
+    received_packets: usize,
+    dedup_packets: usize,
+    shed_packets: usize,
+    max_queued_packets: usize,
+    last_report: Instant,
+}
+
+impl Default for SigVerifierStats {
+    fn default() -> Self {
+        Self {
+            received_packets: 0,
+            dedup_packets: 0,
+            shed_packets: 0,
+            max_queued_packets: 0,
+            last_report: Instant::now(),
+        }
+    }
+}
+
+impl SigVerifierStats {
+    fn maybe_report(&mut self, id: usize) {
+        if self.last_report.elapsed() < DISCARD_STATS_REPORT_INTERVAL {
+            return;
+        }
+        datapoint_info!(
+            "sigverify_stage-discarded_packets",
+            ("id", id, i64),
+            ("received_packets", self.received_packets, i64),
+            ("dedup_packets", self.dedup_packets, i64),
+            ("shed_packets", self.shed_packets, i64),
+            ("max_queued_packets", self.max_queued_packets, i64),
+        );
+        *self = Self::default();
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum SigVerifyServiceError {
     #[error("send packets batch error")]
@@ -52,29 +282,104 @@ impl SigVerifier for DisabledSigVerifier {
 
 impl SigVerifyStage {
     #[allow(clippy::new_ret_no_self)]
+    /// `staked_nodes` enables the stake weighted limits on the forwarded packets verified,
+    /// and `discard_policy` enables load shedding
     pub fn new<T: SigVerifier + 'static + Send + Clone>(
         packet_receiver: Receiver<Packets>,
         verified_sender: CrossbeamSender<Vec<Packets>>,
         verifier: T,
+        staked_nodes: Option<StakedNodesByIp>,
+        discard_policy: Option<SigVerifyDiscardPolicy>,
     ) -> Self {
-        let thread_hdls = Self::verifier_services(packet_receiver, verified_sender, verifier);
+        let load_shedder = discard_policy
+            .map(|discard_policy| Arc::new(LoadShedder::new(discard_policy, staked_nodes.clone())));
+        let forwarded_packets_qos = staked_nodes.map(|staked_nodes| {
+            Arc::new(Mutex::new(ForwardedPacketsQos {
+                qos: StakeWeightedQos::new(
+                    staked_nodes,
+                    MAX_VERIFIED_FORWARDED_PACKETS_PER_INTERVAL,
+                    FORWARDED_PACKETS_QOS_INTERVAL,
+                ),
+                last_stats_report: Instant::now(),
+            }))
+        });
+        let thread_hdls = Self::verifier_services(
+            packet_receiver,
+            verified_sender,
+            verifier,
+            forwarded_packets_qos,
+            load_shedder,
+        );
         Self { thread_hdls }
     }
 
+    // Receives the packets queued up to `max_packets`, several times what
+    // `streamer::recv_batch` takes, so that the excess can be discarded
+    fn recv_queued_packets(
+        recvr: &PacketReceiver,
+        max_packets: usize,
+    ) -> Result<(Vec<Packets>, usize, u64)> {
+        let msgs = recvr
+            .recv_timeout(Duration::from_secs(1))
+            .map_err(StreamerError::RecvTimeout)?;
+        let recv_start = Instant::now();
+        let mut len = msgs.packets.len();
+        let mut batch = vec![msgs];
+        while len < max_packets {
+            match recvr.try_recv() {
+                Ok(more) => {
+                    len += more.packets.len();
+                    batch.push(more);
+                }
+                Err(_) => break,
+            }
+        }
+        Ok((batch, len, duration_as_ms(&recv_start.elapsed())))
+    }
+
+    // Discards the forwarded packets exceeding the capacity of their source
+    fn discard_excess_forwarded_packets(
+        forwarded_packets_qos: &Mutex<ForwardedPacketsQos>,
+        batch: &mut [Packets],
+    ) {
+        let mut forwarded_packets_qos = forwarded_packets_qos.lock().unwrap();
+        forwarded_packets_qos
+            .qos
+            .discard_excess(batch, |packet| packet.meta.forward);
+        if forwarded_packets_qos.last_stats_report.elapsed() > QOS_STATS_REPORT_INTERVAL {
+            forwarded_packets_qos
+                .qos
+                .take_stats()
+                .report("sigverify_stage-forwarded_packets_qos");
+            forwarded_packets_qos.last_stats_report = Instant::now();
+        }
+    }
+
     fn verifier<T: SigVerifier>(
         recvr: &Arc<Mutex<PacketReceiver>>,
         sendr: &CrossbeamSender<Vec<Packets>>,
         id: usize,
         verifier: &T,
+        forwarded_packets_qos: Option<&Mutex<ForwardedPacketsQos>>,
+        load_shedder: Option<&LoadShedder>,
+        stats: &mut SigVerifierStats,
     ) -> Result<()> {
-        let (batch, len, recv_time) = streamer::recv_batch(
-            &recvr.lock().expect("'recvr' lock in fn verifier"),
-            if perf_libs::api().is_some() {
-                RECV_BATCH_MAX_GPU
+        let use_gpu = perf_libs::api().is_some();
+        let (mut batch, len, recv_time) = {
+            let recvr = recvr.lock().expect("'recvr' lock in fn verifier");
+            if load_shedder.is_some() {
+                let max_packets = if use_gpu {
+                    MAX_DRAINED_PACKETS_GPU
+                } else {
+                    MAX_DRAINED_PACKETS_CPU
+                };
+                Self::recv_queued_packets(&recvr, max_packets)?
+            } else if use_gpu {
+                streamer::recv_batch(&recvr, RECV_BATCH_MAX_GPU)?
             } else {
-                RECV_BATCH_MAX_CPU
-            },
-        )?;
+                streamer::recv_batch(&recvr, RECV_BATCH_MAX_CPU)?
+            }
+        };
 
         let mut verify_batch_time = Measure::start("sigverify_batch_time");
         let batch_len = batch.len();
@@ -85,9 +390,43 @@ impl SigVerifyStage {
             id
         );
 
-        let verified_batch = verifier.verify_batch(batch);
+        if let Some(load_shedder) = load_shedder {
+            stats.received_packets += len;
+            stats.max_queued_packets = stats.max_queued_packets.max(len);
+            stats.dedup_packets += load_shedder.dedup_packets(&mut batch);
+            let max_packets = if use_gpu {
+                MAX_QUEUED_PACKETS_GPU
+            } else {
+                MAX_QUEUED_PACKETS_CPU
+            };
+            stats.shed_packets += load_shedder.shed_excess_packets(&mut batch, max_packets);
+            stats.maybe_report(id);
+        }
+        if let Some(forwarded_packets_qos) = forwarded_packets_qos {
+            Self::discard_excess_forwarded_packets(forwarded_packets_qos, &mut batch);
+        }
+
+        let verified_batch = if load_shedder.is_none() && forwarded_packets_qos.is_none() {
+            verifier.verify_batch(batch)
+        } else {
+            // Not all verifiers skip the packets already discarded
+            let discarded: Vec<Vec<bool>> = batch
+                .iter()
+                .map(|packets| packets.packets.iter().map(|p| p.meta.discard).collect())
+                .collect();
+            let mut verified_batch = verifier.verify_batch(batch);
+            for (packets, discarded) in verified_batch.iter_mut().zip(discarded) {
+                for (packet, discarded) in packets.packets.iter_mut().zip(discarded) {
+                    packet.meta.discard |= discarded;
+                }
+            }
+            verified_batch
+        };
 
         for v in verified_batch {
+            if load_shedder.is_some() && v.packets.iter().all(|p| p.meta.discard) {
+                continue;
+            }
             sendr.send(vec![v])?;
         }
 
@@ -119,12 +458,23 @@ impl SigVerifyStage {
         verified_sender: CrossbeamSender<Vec<Packets>>,
         id: usize,
         verifier: &T,
+        forwarded_packets_qos: Option<Arc<Mutex<ForwardedPacketsQos>>>,
+        load_shedder: Option<Arc<LoadShedder>>,
     ) -> JoinHandle<()> {
         let verifier = verifier.clone();
+        let mut stats = SigVerifierStats::default();
         Builder::new()
             .name(format!("solana-verifier-{}", id))
             .spawn(move || loop {
-                if let Err(e) = Self::verifier(&packet_receiver, &verified_sender, id, &verifier) {
+                if let Err(e) = Self::verifier(
+                    &packet_receiver,
+                    &verified_sender,
+                    id,
+                    &verifier,
+                    forwarded_packets_qos.as_deref(),
+                    load_shedder.as_deref(),
+                    &mut stats,
+                ) {
                     match e {
                         SigVerifyServiceError::Streamer(StreamerError::RecvTimeout(
                             RecvTimeoutError::Disconnected,
@@ -146,11 +496,20 @@ impl SigVerifyStage {
         packet_receiver: PacketReceiver,
         verified_sender: CrossbeamSender<Vec<Packets>>,
         verifier: T,
+        forwarded_packets_qos: Option<Arc<Mutex<ForwardedPacketsQos>>>,
+        load_shedder: Option<Arc<LoadShedder>>,
     ) -> Vec<JoinHandle<()>> {
         let receiver = Arc::new(Mutex::new(packet_receiver));
-        (0..4)
+        (0..NUM_VERIFIER_THREADS)
             .map(|id| {
-                Self::verifier_service(receiver.clone(), verified_sender.clone(), id, &verifier)
+                Self::verifier_service(
+                    receiver.clone(),
+                    verified_sender.clone(),
+                    id,
+                    &verifier,
+                    forwarded_packets_qos.clone(),
+                    load_shedder.clone(),
+                )
             })
             .collect()
     }
@@ -162,3 +521,145 @@ impl SigVerifyStage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        instruction::AccountMeta, message::Message, pubkey::Pubkey, system_instruction,
+        transaction::Transaction,
+    };
+    use std::{
+        collections::HashMap,
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+        sync::RwLock,
+    };
+
+    // Makes the packet of an unsigned transaction, requiring `num_signatures`
+    fn make_packet(ip: IpAddr, num_signatures: u8, nonce: u8) -> Packet {
+        let signers: Vec<_> = (1..=num_signatures)
+            .map(|i| Pubkey::new_from_array([i; 32]))
+            .collect();
+        let mut instruction =
+            system_instruction::transfer(&signers[0], &signers[0], u64::from(nonce));
+        instruction.accounts.extend(
+            signers[1..]
+                .iter()
+                .map(|signer| AccountMeta::new_readonly(*signer, true)),
+        );
+        let message = Message::new(&[instruction], Some(&signers[0]));
+        let transaction = Transaction::new_unsigned(message);
+        Packet::from_data(Some(&SocketAddr::new(ip, 8000)), &transaction).unwrap()
+    }
+
+    fn discarded(batches: &[Packets]) -> Vec<bool> {
+        batches
+            .iter()
+            .flat_map(|batch| batch.packets.iter().map(|p| p.meta.discard))
+            .collect()
+    }
+
+    #[test]
+    fn test_dedup_packets() {
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let mut deduper = PacketDeduper::default();
+        let mut batches = vec![
+            Packets::new(vec![make_packet(ip, 1, 0), make_packet(ip, 1, 1)]),
+            Packets::new(vec![make_packet(ip, 1, 0), make_packet(ip, 1, 2)]),
+        ];
+        assert_eq!(deduper.dedup_packets(&mut batches), 1);
+        assert_eq!(discarded(&batches), vec![false, false, true, false]);
+
+        let mut batches = vec![Packets::new(vec![make_packet(ip, 1, 1)])];
+        assert_eq!(deduper.dedup_packets(&mut batches), 1);
+
+        // Packets are accepted again once the filter is reset
+        deduper.last_reset -= DEDUP_FILTER_RESET_INTERVAL;
+        let mut batches = vec![Packets::new(vec![make_packet(ip, 1, 1)])];
+        assert_eq!(deduper.dedup_packets(&mut batches), 0);
+    }
+
+    #[test]
+    fn test_recv_queued_packets() {
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        for nonce in 0..3 {
+            let packets = vec![
+                make_packet(ip, 1, 2 * nonce),
+                make_packet(ip, 1, 2 * nonce + 1),
+            ];
+            sender.send(Packets::new(packets)).unwrap();
+        }
+        // The drain stops at the first batch reaching the limit
+        let (batch, len, _) = SigVerifyStage::recv_queued_packets(&receiver, 3).unwrap();
+        assert_eq!((batch.len(), len), (2, 4));
+        let (batch, len, _) = SigVerifyStage::recv_queued_packets(&receiver, 3).unwrap();
+        assert_eq!((batch.len(), len), (1, 2));
+    }
+
+    #[test]
+    fn test_shed_excess_packets() {
+        let staked_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let unstaked_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let make_batches = || {
+            let mut batches = vec![
+                Packets::new(vec![
+                    make_packet(unstaked_ip, 2, 0),
+                    make_packet(staked_ip, 1, 1),
+                ]),
+                Packets::new(vec![
+                    make_packet(unstaked_ip, 3, 2),
+                    make_packet(staked_ip, 1, 3),
+                    make_packet(unstaked_ip, 1, 4),
+                ]),
+            ];
+            batches[0].packets[0].meta.discard = true;
+            batches
+        };
+        let staked_nodes: HashMap<_, _> = vec![(staked_ip, 100)].into_iter().collect();
+        let staked_nodes = Arc::new(RwLock::new(staked_nodes));
+
+        let shedder =
+            LoadShedder::new(SigVerifyDiscardPolicy::TailDrop, Some(staked_nodes.clone()));
+        let mut batches = make_batches();
+        assert_eq!(shedder.shed_excess_packets(&mut batches, 4), 0);
+        assert_eq!(shedder.shed_excess_packets(&mut batches, 2), 2);
+        assert_eq!(discarded(&batches), vec![true, false, false, true, true]);
+
+        let shedder = LoadShedder::new(SigVerifyDiscardPolicy::PreferStaked, Some(staked_nodes));
+        let mut batches = make_batches();
+        assert_eq!(shedder.shed_excess_packets(&mut batches, 2), 2);
+        assert_eq!(discarded(&batches), vec![true, false, true, false, true]);
+
+        let shedder = LoadShedder::new(SigVerifyDiscardPolicy::PreferMoreSignatures, None);
+        let mut batches = make_batches();
+        assert_eq!(shedder.shed_excess_packets(&mut batches, 2), 2);
+        assert_eq!(discarded(&batches), vec![true, false, false, true, true]);
+        assert_eq!(shedder.shed_excess_packets(&mut batches, 1), 1);
+        assert_eq!(discarded(&batches), vec![true, true, false, true, true]);
+    }
+
+    #[test]
+    fn test_shed_bogus_num_signatures() {
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        // Claims more signatures than its message header requires
+        let mut mismatched = make_packet(ip, 1, 0);
+        mismatched.data[0] = 100;
+        // Claims more signatures than fit in the packet
+        let mut truncated = make_packet(ip, 3, 0);
+        truncated.meta.size = 100;
+        let mut batches = vec![Packets::new(vec![
+            mismatched,
+            make_packet(ip, 1, 1),
+            truncated,
+            make_packet(ip, 2, 2),
+        ])];
+        assert_eq!(get_packet_num_signatures(&batches[0].packets[0]), None);
+        assert_eq!(get_packet_num_signatures(&batches[0].packets[1]), Some(1));
+        assert_eq!(get_packet_num_signatures(&batches[0].packets[2]), None);
+
+        let shedder = LoadShedder::new(SigVerifyDiscardPolicy::PreferMoreSignatures, None);
+        assert_eq!(shedder.shed_excess_packets(&mut batches, 2), 2);
+        assert_eq!(discarded(&batches), vec![true, false, true, false]);
+    }
+}
@@ -3,7 +3,7 @@ use solana_runtime::{snapshot_package::AccountsPackage, snapshot_utils};
 use solana_sdk::{clock::Slot, hash::Hash};
 use std::{
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc, Mutex,
     },
     thread::{self, Builder, JoinHandle},
@@ -22,7 +22,7 @@ impl SnapshotPackagerService {
         starting_snapshot_hash: Option<(Slot, Hash)>,
         exit: &Arc<AtomicBool>,
         cluster_info: &Arc<ClusterInfo>,
-        maximum_snapshots_to_retain: usize,
+        maximum_snapshots_to_retain: Arc<AtomicUsize>,
     ) -> Self {
         let exit = exit.clone();
         let cluster_info = cluster_info.clone();
@@ -44,7 +44,7 @@ impl SnapshotPackagerService {
                     if let Some(snapshot_package) = snapshot_package {
                         if let Err(err) = snapshot_utils::archive_snapshot_package(
                             &snapshot_package,
-                            maximum_snapshots_to_retain,
+                            maximum_snapshots_to_retain.load(Ordering::Relaxed),
                         ) {
                             warn!("Failed to create snapshot archive: {}", err);
                         } else {
@@ -0,0 +1,209 @@
+//! The `stake_weighted_qos` module limits the number of packets accepted from each
+//! source over an interval to a share of the capacity proportional to the stake of the
+//! node at the source address. Unstaked sources share a small fixed part of the capacity,
+//! so that unstaked spam cannot crowd out the traffic of staked nodes.
+
+use crate::staked_nodes_updater_service::StakedNodesByIp;
+use solana_perf::packet::Packets;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+// Share of the capacity for the packets of all unstaked sources combined
+pub const UNSTAKED_CAPACITY_SHARE: f64 = 0.1;
+// Minimum number of packets accepted from any staked source in an interval
+pub const MIN_STAKED_SOURCE_CAPACITY: usize = 16;
+
+#[derive(Default, Debug, PartialEq)]
+pub struct StakeWeightedQosStats {
+    pub staked_packets: usize,
+    pub unstaked_packets: usize,
+    pub dropped_staked_packets: usize,
+    pub dropped_unstaked_packets: usize,
+}
+
+impl StakeWeightedQosStats {
+    pub fn report(&self, name: &'static str) {
+        datapoint_info!(
+            name,
+            ("staked_packets", self.staked_packets, i64),
+            ("unstaked_packets", self.unstaked_packets, i64),
+            ("dropped_staked_packets", self.dropped_staked_packets, i64),
+            (
+                "dropped_unstaked_packets",
+                self.dropped_unstaked_packets,
+                i64
+            ),
+        );
+    }
+}
+
+pub struct StakeWeightedQos {
+    staked_nodes: StakedNodesByIp,
+    // Number of packets accepted from all sources in an interval
+    capacity: usize,
+    interval: Duration,
+    interval_start: Instant,
+    // Snapshot of `staked_nodes` taken at the start of the interval
+    stakes: HashMap<IpAddr, u64>,
+    total_stake: u64,
+    // Packets accepted in the current interval from each staked source, or from all
+    // unstaked sources for `None`
+    usage: HashMap<Option<IpAddr>, usize>,
+    stats: StakeWeightedQosStats,
+}
+
+impl StakeWeightedQos {
+    pub fn new(staked_nodes: StakedNodesByIp, capacity: usize, interval: Duration) -> Self {
+        let mut qos = Self {
+            staked_nodes,
+            capacity,
+            interval,
+            interval_start: Instant::now(),
+            stakes: HashMap::new(),
+            total_stake: 0,
+            usage: HashMap::new(),
+            stats: StakeWeightedQosStats::default(),
+        };
+        qos.start_interval();
+        qos
+    }
+
+    fn start_interval(&mut self) {
+        self.stakes = self.staked_nodes.read().unwrap().clone();
+        self.total_stake = self.stakes.values().sum();
+        self.usage.clear();
+        self.interval_start = Instant::now();
+    }
+
+    fn source_capacity(&self, stake: u64) -> usize {
+        if stake == 0 || self.total_stake == 0 {
+            (self.capacity as f64 * UNSTAKED_CAPACITY_SHARE) as usize
+        } else {
+            let share = (1.0 - UNSTAKED_CAPACITY_SHARE) * stake as f64 / self.total_stake as f64;
+            ((self.capacity as f64 * share) as usize).max(MIN_STAKED_SOURCE_CAPACITY)
+        }
+    }
+
+    /// Returns whether a packet from `ip` fits within the capacity of its source
+    pub fn accept(&mut self, ip: IpAddr) -> bool {
+        if self.interval_start.elapsed() >= self.interval {
+            self.start_interval();
+        }
+        let stake = self.stakes.get(&ip).copied().unwrap_or_default();
+        let source = (stake > 0).then(|| ip);
+        let capacity = self.source_capacity(stake);
+        let usage = self.usage.entry(source).or_default();
+        let accepted = *usage < capacity;
+        if accepted {
+            *usage += 1;
+        }
+        match (stake > 0, accepted) {
+            (true, true) => self.stats.staked_packets += 1,
+            (true, false) => self.stats.dropped_staked_packets += 1,
+            (false, true) => self.stats.unstaked_packets += 1,
+            (false, false) => self.stats.dropped_unstaked_packets += 1,
+        }
+        accepted
+    }
+
+    /// Marks the packets matching `filter` which exceed the capacity of their source as
+    /// discarded, and returns the positions of those packets in the batches.
+    pub fn discard_excess<F>(&mut self, batches: &mut [Packets], filter: F) -> Vec<(usize, usize)>
+    where
+        F: Fn(&solana_sdk::packet::Packet) -> bool,
+    {
+        let mut discarded = vec![];
+        for (batch_index, batch) in batches.iter_mut().enumerate() {
+            for (packet_index, packet) in batch.packets.iter_mut().enumerate() {
+                if packet.meta.discard || !filter(packet) {
+                    continue;
+                }
+                if !self.accept(packet.meta.addr().ip()) {
+                    packet.meta.discard = true;
+                    discarded.push((batch_index, packet_index));
+                }
+            }
+        }
+        discarded
+    }
+
+    /// Returns the stats accumulated since the last call
+    pub fn take_stats(&mut self) -> StakeWeightedQosStats {
+        std::mem::take(&mut self.stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::packet::Packet;
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        sync::{Arc, RwLock},
+    };
+
+    fn make_packets(ips: &[IpAddr]) -> Packets {
+        let packets = ips
+            .iter()
+            .map(|ip| {
+                let mut packet = Packet::default();
+                packet.meta.set_addr(&SocketAddr::new(*ip, 8000));
+                packet
+            })
+            .collect();
+        Packets::new(packets)
+    }
+
+    #[test]
+    fn test_stake_weighted_qos() {
+        let staked_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let small_staked_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let unstaked_ips: Vec<_> = (0..4)
+            .map(|i| IpAddr::V4(Ipv4Addr::new(192, 168, 0, i)))
+            .collect();
+        let staked_nodes: HashMap<_, _> = vec![(staked_ip, 999), (small_staked_ip, 1)]
+            .into_iter()
+            .collect();
+        let mut qos = StakeWeightedQos::new(
+            Arc::new(RwLock::new(staked_nodes)),
+            1000,
+            Duration::from_secs(3600),
+        );
+        assert_eq!(qos.source_capacity(999), 899);
+        assert_eq!(qos.source_capacity(1), MIN_STAKED_SOURCE_CAPACITY);
+        assert_eq!(qos.source_capacity(0), 100);
+
+        // Unstaked sources share a single capacity
+        let unstaked_ips: Vec<_> = unstaked_ips.iter().cycle().take(200).copied().collect();
+        let mut batches = vec![make_packets(&unstaked_ips), make_packets(&[staked_ip; 900])];
+        let discarded = qos.discard_excess(&mut batches, |_| true);
+        assert_eq!(discarded.len(), 101);
+        assert!(discarded[..100]
+            .iter()
+            .all(|(batch_index, packet_index)| *batch_index == 0 && *packet_index >= 100));
+        assert_eq!(discarded[100], (1, 899));
+        assert!(batches[1].packets[899].meta.discard);
+        assert!(!batches[1].packets[898].meta.discard);
+
+        // Packets not matching the filter, or already discarded, are not counted
+        let mut batches = vec![make_packets(&[small_staked_ip; 32])];
+        batches[0].packets[0].meta.discard = true;
+        let discarded = qos.discard_excess(&mut batches, |packet| packet.meta.port == 8000);
+        assert_eq!(discarded.len(), 32 - 1 - MIN_STAKED_SOURCE_CAPACITY);
+        let discarded = qos.discard_excess(&mut batches, |packet| packet.meta.port != 8000);
+        assert!(discarded.is_empty());
+
+        assert_eq!(
+            qos.take_stats(),
+            StakeWeightedQosStats {
+                staked_packets: 899 + MIN_STAKED_SOURCE_CAPACITY,
+                unstaked_packets: 100,
+                dropped_staked_packets: 1 + 32 - 1 - MIN_STAKED_SOURCE_CAPACITY,
+                dropped_unstaked_packets: 100,
+            }
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! The `staked_nodes_updater_service` periodically maps the IP addresses advertised in
+//! gossip to the stake of the nodes at those addresses, so that stages receiving packets
+//! can tell which of them come from staked nodes.
+
+use solana_gossip::cluster_info::ClusterInfo;
+use solana_runtime::bank_forks::BankForks;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread::{self, sleep, Builder, JoinHandle},
+    time::{Duration, Instant},
+};
+
+const STAKED_NODES_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+const SLEEP_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Total stake of the nodes at each IP address
+pub type StakedNodesByIp = Arc<RwLock<HashMap<IpAddr, u64>>>;
+
+pub struct StakedNodesUpdaterService {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl StakedNodesUpdaterService {
+    pub fn new(
+        exit: &Arc<AtomicBool>,
+        cluster_info: Arc<ClusterInfo>,
+        bank_forks: Arc<RwLock<BankForks>>,
+        staked_nodes: StakedNodesByIp,
+    ) -> Self {
+        let exit = exit.clone();
+        let thread_hdl = Builder::new()
+            .name("sol-sn-updater".to_string())
+            .spawn(move || {
+                let mut last_update: Option<Instant> = None;
+                while !exit.load(Ordering::Relaxed) {
+                    if last_update
+                        .map(|last_update| last_update.elapsed() >= STAKED_NODES_UPDATE_INTERVAL)
+                        .unwrap_or(true)
+                    {
+                        let new_staked_nodes =
+                            Self::compute_staked_nodes(&cluster_info, &bank_forks);
+                        *staked_nodes.write().unwrap() = new_staked_nodes;
+                        last_update = Some(Instant::now());
+                    }
+                    sleep(SLEEP_INTERVAL);
+                }
+            })
+            .unwrap();
+
+        Self { thread_hdl }
+    }
+
+    fn compute_staked_nodes(
+        cluster_info: &ClusterInfo,
+        bank_forks: &RwLock<BankForks>,
+    ) -> HashMap<IpAddr, u64> {
+        let root_bank = bank_forks.read().unwrap().root_bank();
+        let staked_nodes = root_bank
+            .epoch_staked_nodes(root_bank.epoch())
+            .unwrap_or_default();
+        let mut stakes_by_ip = HashMap::new();
+        for (node, _) in cluster_info.all_peers() {
+            let stake = match staked_nodes.get(&node.id) {
+                Some(stake) if *stake > 0 => *stake,
+                _ => continue,
+            };
+            // Packets are forwarded from an unspecified local port, so the nodes can only
+            // be identified by the addresses of the interfaces they advertise
+            let mut ips = vec![node.gossip.ip(), node.tpu.ip(), node.tpu_forwards.ip()];
+            ips.sort_unstable();
+            ips.dedup();
+            for ip in ips {
+                if !ip.is_unspecified() {
+                    *stakes_by_ip.entry(ip).or_default() += stake;
+                }
+            }
+        }
+        stakes_by_ip
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
@@ -1,5 +1,9 @@
 use {
-    crate::validator::{Validator, ValidatorConfig, ValidatorStartProgress},
+    crate::{
+        repair_service::OutstandingRepairs,
+        retransmit_stage::RetransmitTreeInspector,
+        validator::{LiveValidatorConfig, Validator, ValidatorConfig, ValidatorStartProgress},
+    },
     solana_client::rpc_client::RpcClient,
     solana_gossip::{
         cluster_info::{ClusterInfo, Node},
@@ -607,6 +611,22 @@ impl TestValidator {
     pub fn cluster_info(&self) -> Arc<ClusterInfo> {
         self.validator.as_ref().unwrap().cluster_info.clone()
     }
+
+    pub fn live_config(&self) -> LiveValidatorConfig {
+        self.validator.as_ref().unwrap().live_config.clone()
+    }
+
+    pub fn retransmit_tree_inspector(&self) -> RetransmitTreeInspector {
+        self.validator
+            .as_ref()
+            .unwrap()
+            .retransmit_tree_inspector
+            .clone()
+    }
+
+    pub fn outstanding_repairs(&self) -> Arc<RwLock<OutstandingRepairs>> {
+        self.validator.as_ref().unwrap().outstanding_repairs.clone()
+    }
 }
 
 impl Drop for TestValidator {
@@ -12,7 +12,9 @@ use crate::{
     cost_tracker::CostTracker,
     fetch_stage::FetchStage,
     sigverify::TransactionSigVerifier,
-    sigverify_stage::SigVerifyStage,
+    sigverify_stage::{SigVerifyDiscardPolicy, SigVerifyStage},
+    staked_nodes_updater_service::StakedNodesUpdaterService,
+    transaction_scheduler::TransactionSchedulerType,
 };
 use crossbeam_channel::unbounded;
 use solana_gossip::cluster_info::ClusterInfo;
@@ -27,6 +29,7 @@ use solana_runtime::{
     vote_sender_types::{ReplayVoteReceiver, ReplayVoteSender},
 };
 use std::{
+    collections::HashMap,
     net::UdpSocket,
     sync::{
         atomic::AtomicBool,
@@ -44,6 +47,7 @@ pub struct Tpu {
     banking_stage: BankingStage,
     cluster_info_vote_listener: ClusterInfoVoteListener,
     broadcast_stage: BroadcastStage,
+    staked_nodes_updater_service: StakedNodesUpdaterService,
 }
 
 impl Tpu {
@@ -72,7 +76,17 @@ impl Tpu {
         tpu_coalesce_ms: u64,
         cluster_confirmed_slot_sender: GossipDuplicateConfirmedSlotsSender,
         cost_model: &Arc<RwLock<CostModel>>,
+        banking_scheduler_type: TransactionSchedulerType,
+        sigverify_discard_policy: SigVerifyDiscardPolicy,
     ) -> Self {
+        let staked_nodes = Arc::new(RwLock::new(HashMap::new()));
+        let staked_nodes_updater_service = StakedNodesUpdaterService::new(
+            exit,
+            cluster_info.clone(),
+            bank_forks.clone(),
+            staked_nodes.clone(),
+        );
+
         let (packet_sender, packet_receiver) = channel();
         let fetch_stage = FetchStage::new_with_sender(
             transactions_sockets,
@@ -81,12 +95,19 @@ impl Tpu {
             &packet_sender,
             poh_recorder,
             tpu_coalesce_ms,
+            &staked_nodes,
         );
         let (verified_sender, verified_receiver) = unbounded();
 
         let sigverify_stage = {
             let verifier = TransactionSigVerifier::default();
-            SigVerifyStage::new(packet_receiver, verified_sender, verifier)
+            SigVerifyStage::new(
+                packet_receiver,
+                verified_sender,
+                verifier,
+                Some(staked_nodes),
+                Some(sigverify_discard_policy),
+            )
         };
 
         let (verified_vote_packets_sender, verified_vote_packets_receiver) = unbounded();
@@ -107,7 +128,7 @@ impl Tpu {
         );
 
         let cost_tracker = Arc::new(RwLock::new(CostTracker::new(cost_model.clone())));
-        let banking_stage = BankingStage::new(
+        let banking_stage = BankingStage::new_with_scheduler(
             cluster_info,
             poh_recorder,
             verified_receiver,
@@ -115,6 +136,7 @@ impl Tpu {
             transaction_status_sender,
             replay_vote_sender,
             cost_tracker,
+            banking_scheduler_type,
         );
 
         let broadcast_stage = broadcast_type.new_broadcast_stage(
@@ -134,6 +156,7 @@ impl Tpu {
             banking_stage,
             cluster_info_vote_listener,
             broadcast_stage,
+            staked_nodes_updater_service,
         }
     }
 
@@ -143,6 +166,7 @@ impl Tpu {
             self.sigverify_stage.join(),
             self.cluster_info_vote_listener.join(),
             self.banking_stage.join(),
+            self.staked_nodes_updater_service.join(),
         ];
         let broadcast_result = self.broadcast_stage.join();
         for result in results {
@@ -0,0 +1,556 @@
+//! The `transaction_scheduler` module implements the central scheduler of the banking stage.
+//! Buffered transactions are arranged in a conflict graph by the accounts they lock, and only
+//! batches of transactions that cannot contend for an account lock are handed to the worker
+//! threads, so workers no longer race each other for write locks on hot accounts.
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use solana_runtime::{bank::Bank, hashed_transaction::HashedTransaction};
+use solana_sdk::{message::Message, pubkey::Pubkey};
+use std::{
+    collections::{BTreeSet, HashMap},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+// Time to wait for a batch outcome before checking whether the bank still takes transactions
+const OUTCOME_RECV_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Number of the most contended accounts reported per interval
+const NUM_REPORTED_ACCOUNTS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransactionSchedulerType {
+    /// Every banking thread locks and executes the batches it receives on its own
+    ThreadLocal,
+    /// A single thread schedules non-conflicting batches onto the banking threads
+    Central,
+}
+
+impl Default for TransactionSchedulerType {
+    fn default() -> Self {
+        Self::ThreadLocal
+    }
+}
+
+impl FromStr for TransactionSchedulerType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "thread-local" => Ok(Self::ThreadLocal),
+            "central" => Ok(Self::Central),
+            _ => Err(format!("Unknown transaction scheduler: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AccountContentionCounts {
+    /// Transactions that locked the account
+    pub transactions: u64,
+    /// Transactions that write locked the account
+    pub write_locks: u64,
+    /// Transactions that had to wait on an earlier transaction because of the account
+    pub conflicts: u64,
+}
+
+/// Per-account lock contention, accumulated over the transactions scheduled since the last report
+#[derive(Debug, Default)]
+pub struct AccountContention {
+    accounts: HashMap<Pubkey, AccountContentionCounts>,
+}
+
+impl AccountContention {
+    pub fn get(&self, pubkey: &Pubkey) -> Option<&AccountContentionCounts> {
+        self.accounts.get(pubkey)
+    }
+
+    /// Returns up to `n` accounts with the most conflicts, most contended first
+    pub fn most_contended(&self, n: usize) -> Vec<(Pubkey, AccountContentionCounts)> {
+        let mut accounts: Vec<_> = self
+            .accounts
+            .iter()
+            .filter(|(_, counts)| counts.conflicts > 0)
+            .map(|(pubkey, counts)| (*pubkey, counts.clone()))
+            .collect();
+        accounts.sort_by(|(a_pubkey, a), (b_pubkey, b)| {
+            b.conflicts
+                .cmp(&a.conflicts)
+                .then_with(|| a_pubkey.cmp(b_pubkey))
+        });
+        accounts.truncate(n);
+        accounts
+    }
+
+    pub fn report(&mut self) {
+        for (pubkey, counts) in self.most_contended(NUM_REPORTED_ACCOUNTS) {
+            datapoint_info!(
+                "banking_stage-account_contention",
+                ("account", pubkey.to_string(), String),
+                ("transactions", counts.transactions as i64, i64),
+                ("write_locks", counts.write_locks as i64, i64),
+                ("conflicts", counts.conflicts as i64, i64),
+            );
+        }
+        self.accounts.clear();
+    }
+}
+
+#[derive(Default)]
+struct AccountLocks {
+    writer: Option<usize>,
+    readers: Vec<usize>,
+}
+
+/// Orders transactions that lock a common account, with at least one of them writing it, by
+/// their position in the buffer. A transaction becomes ready once every earlier transaction it
+/// conflicts with has completed, so ready transactions never conflict with each other or with
+/// transactions still executing.
+pub struct ConflictGraph {
+    dependents: Vec<Vec<usize>>,
+    num_dependencies: Vec<usize>,
+    ready: BTreeSet<usize>,
+}
+
+impl ConflictGraph {
+    pub fn new<'a>(
+        messages: impl Iterator<Item = &'a Message>,
+        contention: &mut AccountContention,
+    ) -> Self {
+        let mut account_locks: HashMap<Pubkey, AccountLocks> = HashMap::new();
+        let mut dependents: Vec<Vec<usize>> = vec![];
+        let mut num_dependencies = vec![];
+        let mut ready = BTreeSet::new();
+        for (id, message) in messages.enumerate() {
+            // An account listed more than once is locked once, for writing if any of its
+            // entries is writable, so that the transaction never depends on itself.
+            let mut accounts: Vec<(&Pubkey, bool)> = Vec::with_capacity(message.account_keys.len());
+            for (index, pubkey) in message.account_keys.iter().enumerate() {
+                let is_writable = message.is_writable(index);
+                match accounts.iter_mut().find(|(key, _)| *key == pubkey) {
+                    Some((_, writable)) => *writable |= is_writable,
+                    None => accounts.push((pubkey, is_writable)),
+                }
+            }
+            let mut dependencies = vec![];
+            for (pubkey, is_writable) in accounts {
+                let locks = account_locks.entry(*pubkey).or_default();
+                let num_dependencies_before = dependencies.len();
+                dependencies.extend(locks.writer);
+                let counts = contention.accounts.entry(*pubkey).or_default();
+                counts.transactions += 1;
+                if is_writable {
+                    counts.write_locks += 1;
+                    dependencies.append(&mut locks.readers);
+                    locks.writer = Some(id);
+                } else {
+                    locks.readers.push(id);
+                }
+                counts.conflicts += (dependencies.len() > num_dependencies_before) as u64;
+            }
+            dependencies.sort_unstable();
+            dependencies.dedup();
+            for dependency in &dependencies {
+                dependents[*dependency].push(id);
+            }
+            if dependencies.is_empty() {
+                ready.insert(id);
+            }
+            num_dependencies.push(dependencies.len());
+            dependents.push(vec![]);
+        }
+        Self {
+            dependents,
+            num_dependencies,
+            ready,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_dependencies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_dependencies.is_empty()
+    }
+
+    pub fn num_ready(&self) -> usize {
+        self.ready.len()
+    }
+
+    /// Takes up to `max_len` of the earliest ready transactions
+    pub fn next_batch(&mut self, max_len: usize) -> Vec<usize> {
+        let batch: Vec<_> = self.ready.iter().take(max_len).copied().collect();
+        for id in &batch {
+            self.ready.remove(id);
+        }
+        batch
+    }
+
+    /// Marks `id` as completed, readying the transactions that were only waiting on it.
+    /// Transactions that are never completed keep their dependents blocked.
+    pub fn complete(&mut self, id: usize) {
+        for dependent in std::mem::take(&mut self.dependents[id]) {
+            self.num_dependencies[dependent] -= 1;
+            if self.num_dependencies[dependent] == 0 {
+                self.ready.insert(dependent);
+            }
+        }
+    }
+}
+
+/// A batch of non-conflicting transactions sent to a worker thread
+pub struct ScheduledBatch {
+    pub bank: Arc<Bank>,
+    pub transactions: Vec<HashedTransaction<'static>>,
+    pub ids: Vec<usize>,
+}
+
+/// The result of a `ScheduledBatch`, sent back to the scheduler by the worker
+pub struct BatchOutcome {
+    pub worker: usize,
+    pub ids: Vec<usize>,
+    /// Indexes into `ids` of the transactions that should be retried
+    pub retryable_indexes: Vec<usize>,
+    pub max_height_reached: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ScheduleResult {
+    /// Transactions that were executed, or were rejected in a way that does not warrant a retry
+    pub processed: Vec<bool>,
+    pub num_batches: usize,
+    pub num_retried: usize,
+}
+
+pub struct TransactionScheduler {
+    batch_senders: Vec<Sender<ScheduledBatch>>,
+    outcome_receiver: Receiver<BatchOutcome>,
+    max_batch_len: usize,
+    contention: AccountContention,
+    last_report: Instant,
+}
+
+impl TransactionScheduler {
+    pub fn new(
+        batch_senders: Vec<Sender<ScheduledBatch>>,
+        outcome_receiver: Receiver<BatchOutcome>,
+        max_batch_len: usize,
+    ) -> Self {
+        Self {
+            batch_senders,
+            outcome_receiver,
+            max_batch_len,
+            contention: AccountContention::default(),
+            last_report: Instant::now(),
+        }
+    }
+
+    pub fn contention(&self) -> &AccountContention {
+        &self.contention
+    }
+
+    pub fn report(&mut self, report_interval_ms: u64) {
+        if self.last_report.elapsed().as_millis() > report_interval_ms as u128 {
+            self.contention.report();
+            self.last_report = Instant::now();
+        }
+    }
+
+    /// Executes `transactions` on the worker threads in batches that cannot conflict with each
+    /// other, until every transaction has been scheduled or the bank stops taking transactions.
+    /// The ready transactions are split evenly between the idle workers, up to the maximum
+    /// batch length. Transactions that have to be retried keep the transactions that conflict
+    /// with them from being scheduled until the next call.
+    pub fn schedule(
+        &mut self,
+        bank: &Arc<Bank>,
+        bank_creation_time: &Instant,
+        max_tx_ingestion_ns: u128,
+        transactions: &[HashedTransaction<'static>],
+    ) -> ScheduleResult {
+        let mut graph = ConflictGraph::new(
+            transactions
+                .iter()
+                .map(|transaction| transaction.transaction().message()),
+            &mut self.contention,
+        );
+        let mut result = ScheduleResult {
+            processed: vec![false; graph.len()],
+            ..ScheduleResult::default()
+        };
+        let mut idle_workers: Vec<_> = (0..self.batch_senders.len()).rev().collect();
+        let mut num_in_flight = 0;
+        let mut stopped = false;
+        loop {
+            while !stopped {
+                let worker = match idle_workers.pop() {
+                    Some(worker) => worker,
+                    None => break,
+                };
+                let num_workers = idle_workers.len() + 1;
+                let batch_len = (graph.num_ready() + num_workers - 1) / num_workers;
+                let ids = graph.next_batch(batch_len.min(self.max_batch_len));
+                if ids.is_empty() {
+                    idle_workers.push(worker);
+                    break;
+                }
+                let batch = ScheduledBatch {
+                    bank: bank.clone(),
+                    transactions: ids.iter().map(|id| transactions[*id].clone()).collect(),
+                    ids,
+                };
+                if self.batch_senders[worker].send(batch).is_err() {
+                    stopped = true;
+                    break;
+                }
+                result.num_batches += 1;
+                num_in_flight += 1;
+            }
+            if num_in_flight == 0 {
+                break;
+            }
+            let outcome = match self.outcome_receiver.recv_timeout(OUTCOME_RECV_TIMEOUT) {
+                Ok(outcome) => outcome,
+                Err(RecvTimeoutError::Timeout) => {
+                    stopped |= !Bank::should_bank_still_be_processing_txs(
+                        bank_creation_time,
+                        max_tx_ingestion_ns,
+                    );
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            num_in_flight -= 1;
+            idle_workers.push(outcome.worker);
+            for (index, id) in outcome.ids.into_iter().enumerate() {
+                if outcome.retryable_indexes.contains(&index) {
+                    result.num_retried += 1;
+                } else {
+                    result.processed[id] = true;
+                    graph.complete(id);
+                }
+            }
+            if outcome.max_height_reached
+                || !Bank::should_bank_still_be_processing_txs(
+                    bank_creation_time,
+                    max_tx_ingestion_ns,
+                )
+            {
+                stopped = true;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::unbounded;
+    use solana_ledger::genesis_utils::create_genesis_config;
+    use solana_sdk::{
+        hash::Hash,
+        instruction::AccountMeta,
+        signature::{Keypair, Signer},
+        system_instruction, system_transaction,
+    };
+    use std::{borrow::Cow, thread};
+
+    fn message(writable: &[Pubkey], readonly: &[Pubkey]) -> Message {
+        let payer = Pubkey::new_unique();
+        let mut instruction = system_instruction::transfer(&payer, &payer, 1);
+        instruction.accounts = writable
+            .iter()
+            .map(|pubkey| AccountMeta::new(*pubkey, false))
+            .chain(
+                readonly
+                    .iter()
+                    .map(|pubkey| AccountMeta::new_readonly(*pubkey, false)),
+            )
+            .collect();
+        Message::new(&[instruction], Some(&payer))
+    }
+
+    #[test]
+    fn test_conflict_graph() {
+        let hot = Pubkey::new_unique();
+        let shared = Pubkey::new_unique();
+        let messages = vec![
+            message(&[hot], &[shared]),
+            message(&[Pubkey::new_unique()], &[shared]),
+            message(&[hot], &[]),
+            message(&[shared], &[]),
+            message(&[Pubkey::new_unique()], &[]),
+        ];
+        let mut contention = AccountContention::default();
+        let mut graph = ConflictGraph::new(messages.iter(), &mut contention);
+        assert_eq!(graph.len(), 5);
+
+        // 2 waits on 0 for `hot`, 3 waits on the readers 0 and 1 of `shared`
+        assert_eq!(graph.next_batch(128), vec![0, 1, 4]);
+        assert!(graph.next_batch(128).is_empty());
+        graph.complete(1);
+        assert!(graph.next_batch(128).is_empty());
+        graph.complete(0);
+        assert_eq!(graph.next_batch(1), vec![2]);
+        assert_eq!(graph.next_batch(1), vec![3]);
+
+        let hot_counts = contention.get(&hot).unwrap();
+        assert_eq!(hot_counts.transactions, 2);
+        assert_eq!(hot_counts.write_locks, 2);
+        assert_eq!(hot_counts.conflicts, 1);
+        let shared_counts = contention.get(&shared).unwrap();
+        assert_eq!(shared_counts.transactions, 3);
+        assert_eq!(shared_counts.write_locks, 1);
+        assert_eq!(shared_counts.conflicts, 1);
+        let most_contended = contention.most_contended(10);
+        assert_eq!(most_contended.len(), 2);
+        contention.report();
+        assert!(contention.most_contended(10).is_empty());
+    }
+
+    #[test]
+    fn test_conflict_graph_duplicate_account() {
+        let hot = Pubkey::new_unique();
+        let mut duplicate = message(&[hot], &[]);
+        duplicate.account_keys.push(hot);
+        let messages = vec![duplicate, message(&[hot], &[])];
+        let mut contention = AccountContention::default();
+        let mut graph = ConflictGraph::new(messages.iter(), &mut contention);
+
+        assert_eq!(graph.next_batch(128), vec![0]);
+        assert!(graph.next_batch(128).is_empty());
+        graph.complete(0);
+        assert_eq!(graph.next_batch(128), vec![1]);
+
+        let hot_counts = contention.get(&hot).unwrap();
+        assert_eq!(hot_counts.transactions, 2);
+        assert_eq!(hot_counts.write_locks, 2);
+        assert_eq!(hot_counts.conflicts, 1);
+    }
+
+    #[test]
+    fn test_schedule() {
+        let genesis_config_info = create_genesis_config(10_000);
+        let bank = Arc::new(Bank::new(&genesis_config_info.genesis_config));
+        let mint_keypair = genesis_config_info.mint_keypair;
+        let to = Keypair::new();
+        // Every transfer writes the mint account, so they all have to execute one at a time
+        let transactions: Vec<_> = (0..4)
+            .map(|lamports| {
+                let transaction = system_transaction::transfer(
+                    &mint_keypair,
+                    &to.pubkey(),
+                    lamports + 1,
+                    Hash::default(),
+                );
+                let message_hash = transaction.message().hash();
+                HashedTransaction::new(Cow::Owned(transaction), message_hash)
+            })
+            .collect();
+
+        let (batch_sender, batch_receiver) = unbounded();
+        let (outcome_sender, outcome_receiver) = unbounded();
+        let worker = thread::spawn(move || {
+            let mut batch_lens = vec![];
+            for batch in batch_receiver.iter() {
+                let ScheduledBatch {
+                    transactions, ids, ..
+                } = batch;
+                batch_lens.push(transactions.len());
+                // Ask for the very first batch to be retried
+                let retryable_indexes = if batch_lens.len() == 1 {
+                    vec![0]
+                } else {
+                    vec![]
+                };
+                outcome_sender
+                    .send(BatchOutcome {
+                        worker: 0,
+                        ids,
+                        retryable_indexes,
+                        max_height_reached: false,
+                    })
+                    .unwrap();
+            }
+            batch_lens
+        });
+
+        let mut scheduler = TransactionScheduler::new(vec![batch_sender], outcome_receiver, 128);
+        let result = scheduler.schedule(&bank, &Instant::now(), u128::MAX, &transactions);
+        assert_eq!(result.processed, vec![false; 4]);
+        assert_eq!(result.num_batches, 1);
+        assert_eq!(result.num_retried, 1);
+
+        let result = scheduler.schedule(&bank, &Instant::now(), u128::MAX, &transactions[1..]);
+        assert_eq!(result.processed, vec![true; 3]);
+        assert_eq!(result.num_batches, 3);
+        assert_eq!(
+            scheduler
+                .contention()
+                .get(&mint_keypair.pubkey())
+                .unwrap()
+                .conflicts,
+            5
+        );
+
+        drop(scheduler);
+        assert_eq!(worker.join().unwrap(), vec![1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_schedule_spreads_batches() {
+        let genesis_config_info = create_genesis_config(10_000);
+        let bank = Arc::new(Bank::new(&genesis_config_info.genesis_config));
+        // Transfers between distinct accounts do not conflict
+        let transactions: Vec<_> = (0..5)
+            .map(|_| {
+                let transaction = system_transaction::transfer(
+                    &Keypair::new(),
+                    &Pubkey::new_unique(),
+                    1,
+                    Hash::default(),
+                );
+                let message_hash = transaction.message().hash();
+                HashedTransaction::new(Cow::Owned(transaction), message_hash)
+            })
+            .collect();
+
+        let (outcome_sender, outcome_receiver) = unbounded();
+        let (batch_senders, workers): (Vec<_>, Vec<_>) = (0..2)
+            .map(|worker| {
+                let (batch_sender, batch_receiver) = unbounded::<ScheduledBatch>();
+                let outcome_sender = outcome_sender.clone();
+                let worker = thread::spawn(move || {
+                    let mut batch_lens = vec![];
+                    for batch in batch_receiver.iter() {
+                        batch_lens.push(batch.ids.len());
+                        outcome_sender
+                            .send(BatchOutcome {
+                                worker,
+                                ids: batch.ids,
+                                retryable_indexes: vec![],
+                                max_height_reached: false,
+                            })
+                            .unwrap();
+                    }
+                    batch_lens
+                });
+                (batch_sender, worker)
+            })
+            .unzip();
+
+        let mut scheduler = TransactionScheduler::new(batch_senders, outcome_receiver, 128);
+        let result = scheduler.schedule(&bank, &Instant::now(), u128::MAX, &transactions);
+        assert_eq!(result.processed, vec![true; 5]);
+        assert_eq!(result.num_batches, 2);
+
+        drop(scheduler);
+        let batch_lens: Vec<_> = workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect();
+        assert_eq!(batch_lens, vec![vec![3], vec![2]]);
+    }
+}
@@ -3,6 +3,7 @@
 
 use crate::{
     accounts_hash_verifier::AccountsHashVerifier,
+    ancestor_hashes_service::AncestorHashesService,
     broadcast_stage::RetransmitSlotsSender,
     cache_block_meta_service::CacheBlockMetaSender,
     cluster_info_vote_listener::{
@@ -15,8 +16,9 @@ use crate::{
     cost_model::CostModel,
     cost_update_service::CostUpdateService,
     ledger_cleanup_service::LedgerCleanupService,
+    repair_service::OutstandingRepairs,
     replay_stage::{ReplayStage, ReplayStageConfig},
-    retransmit_stage::RetransmitStage,
+    retransmit_stage::{RetransmitStage, RetransmitTreeInspector},
     rewards_recorder_service::RewardsRecorderSender,
     shred_fetch_stage::ShredFetchStage,
     sigverify_shreds::ShredSigVerifier,
@@ -39,7 +41,6 @@ use solana_runtime::{
         AbsRequestHandler, AbsRequestSender, AccountsBackgroundService, SnapshotRequestHandler,
     },
     accounts_db::AccountShrinkThreshold,
-    bank::ExecuteTimings,
     bank_forks::BankForks,
     commitment::BlockCommitmentCache,
     snapshot_config::SnapshotConfig,
@@ -50,9 +51,10 @@ use std::{
     boxed::Box,
     collections::HashSet,
     net::UdpSocket,
+    path::PathBuf,
     sync::{
-        atomic::AtomicBool,
-        mpsc::{channel, Receiver, Sender},
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{channel, Receiver},
         Arc, Mutex, RwLock,
     },
     thread,
@@ -67,6 +69,7 @@ pub struct Tvu {
     accounts_background_service: AccountsBackgroundService,
     accounts_hash_verifier: AccountsHashVerifier,
     cost_update_service: CostUpdateService,
+    ancestor_hashes_service: AncestorHashesService,
 }
 
 pub struct Sockets {
@@ -74,15 +77,17 @@ pub struct Sockets {
     pub repair: UdpSocket,
     pub retransmit: Vec<UdpSocket>,
     pub forwards: Vec<UdpSocket>,
+    pub ancestor_hashes_requests: UdpSocket,
 }
 
 #[derive(Default)]
 pub struct TvuConfig {
-    pub max_ledger_shreds: Option<u64>,
+    pub max_ledger_shreds: Option<Arc<AtomicU64>>,
     pub shred_version: u16,
     pub halt_on_trusted_validators_accounts_hash_mismatch: bool,
-    pub trusted_validators: Option<HashSet<Pubkey>>,
-    pub repair_validators: Option<HashSet<Pubkey>>,
+    pub trusted_validators: Arc<RwLock<Option<HashSet<Pubkey>>>>,
+    pub repair_validators: Arc<RwLock<Option<HashSet<Pubkey>>>>,
+    pub snapshot_interval_slots: Option<Arc<AtomicU64>>,
     pub accounts_hash_fault_injection_slots: u64,
     pub accounts_db_caching_enabled: bool,
     pub test_hash_calculation: bool,
@@ -91,6 +96,7 @@ pub struct TvuConfig {
     pub rocksdb_max_compaction_jitter: Option<u64>,
     pub wait_for_vote_to_start_leader: bool,
     pub accounts_shrink_ratio: AccountShrinkThreshold,
+    pub bank_hash_details_dir: Option<PathBuf>,
 }
 
 impl Tvu {
@@ -137,6 +143,7 @@ impl Tvu {
             fetch: fetch_sockets,
             retransmit: retransmit_sockets,
             forwards: tvu_forward_sockets,
+            ancestor_hashes_requests: ancestor_hashes_socket,
         } = sockets;
 
         let (fetch_sender, fetch_receiver) = channel();
@@ -159,6 +166,8 @@ impl Tvu {
             fetch_receiver,
             verified_sender,
             ShredSigVerifier::new(bank_forks.clone(), leader_schedule_cache.clone()),
+            None,
+            None,
         );
 
         let cluster_slots = Arc::new(ClusterSlots::default());
@@ -167,6 +176,18 @@ impl Tvu {
         let max_compaction_jitter = tvu_config.rocksdb_max_compaction_jitter;
         let (duplicate_slots_sender, duplicate_slots_receiver) = unbounded();
         let (cluster_slots_update_sender, cluster_slots_update_receiver) = unbounded();
+        let (ancestor_hashes_request_sender, ancestor_hashes_request_receiver) = unbounded();
+        let (ancestor_duplicate_slots_sender, ancestor_duplicate_slots_receiver) = unbounded();
+        let ancestor_hashes_service = AncestorHashesService::new(
+            exit,
+            blockstore.clone(),
+            ancestor_hashes_socket,
+            cluster_info.clone(),
+            bank_forks.clone(),
+            tvu_config.repair_validators.clone(),
+            ancestor_hashes_request_receiver,
+            ancestor_duplicate_slots_sender,
+        );
         let retransmit_stage = RetransmitStage::new(
             bank_forks.clone(),
             leader_schedule_cache,
@@ -192,14 +213,14 @@ impl Tvu {
 
         let (ledger_cleanup_slot_sender, ledger_cleanup_slot_receiver) = channel();
 
-        let snapshot_interval_slots = {
-            if let Some(config) = bank_forks.read().unwrap().snapshot_config() {
-                config.snapshot_interval_slots
-            } else {
-                std::u64::MAX
-            }
-        };
-        info!("snapshot_interval_slots: {}", snapshot_interval_slots);
+        let snapshot_interval_slots = tvu_config
+            .snapshot_interval_slots
+            .clone()
+            .unwrap_or_else(|| Arc::new(AtomicU64::new(std::u64::MAX)));
+        info!(
+            "snapshot_interval_slots: {}",
+            snapshot_interval_slots.load(Ordering::Relaxed)
+        );
         let (snapshot_config, pending_snapshot_package) = snapshot_config_and_pending_package
             .map(|(snapshot_config, pending_snapshot_package)| {
                 (Some(snapshot_config), Some(pending_snapshot_package))
@@ -271,12 +292,11 @@ impl Tvu {
             cache_block_meta_sender,
             bank_notification_sender,
             wait_for_vote_to_start_leader: tvu_config.wait_for_vote_to_start_leader,
+            bank_hash_details_dir: tvu_config.bank_hash_details_dir,
+            cost_model: cost_model.clone(),
         };
 
-        let (cost_update_sender, cost_update_receiver): (
-            Sender<ExecuteTimings>,
-            Receiver<ExecuteTimings>,
-        ) = channel();
+        let (cost_update_sender, cost_update_receiver) = channel();
         let cost_update_service = CostUpdateService::new(
             exit.clone(),
             blockstore.clone(),
@@ -302,6 +322,8 @@ impl Tvu {
             gossip_verified_vote_hash_receiver,
             cluster_slots_update_sender,
             cost_update_sender,
+            ancestor_hashes_request_sender,
+            ancestor_duplicate_slots_receiver,
         );
 
         let ledger_cleanup_service = tvu_config.max_ledger_shreds.map(|max_ledger_shreds| {
@@ -333,9 +355,18 @@ impl Tvu {
             accounts_background_service,
             accounts_hash_verifier,
             cost_update_service,
+            ancestor_hashes_service,
         }
     }
 
+    pub fn retransmit_tree_inspector(&self) -> &RetransmitTreeInspector {
+        self.retransmit_stage.retransmit_tree_inspector()
+    }
+
+    pub fn outstanding_repairs(&self) -> &Arc<RwLock<OutstandingRepairs>> {
+        self.retransmit_stage.outstanding_repairs()
+    }
+
     pub fn join(self) -> thread::Result<()> {
         self.retransmit_stage.join()?;
         self.fetch_stage.join()?;
@@ -347,6 +378,7 @@ impl Tvu {
         self.replay_stage.join()?;
         self.accounts_hash_verifier.join()?;
         self.cost_update_service.join()?;
+        self.ancestor_hashes_service.join()?;
         Ok(())
     }
 }
@@ -419,6 +451,7 @@ pub mod tests {
                     retransmit: target1.sockets.retransmit_sockets,
                     fetch: target1.sockets.tvu,
                     forwards: target1.sockets.tvu_forwards,
+                    ancestor_hashes_requests: target1.sockets.ancestor_hashes_requests,
                 }
             },
             blockstore,
@@ -7,14 +7,19 @@ use crate::{
     completed_data_sets_service::CompletedDataSetsService,
     consensus::{reconcile_blockstore_roots_with_tower, Tower},
     cost_model::CostModel,
+    repair_service::OutstandingRepairs,
+    retransmit_stage::RetransmitTreeInspector,
     rewards_recorder_service::{RewardsRecorderSender, RewardsRecorderService},
     sample_performance_service::SamplePerformanceService,
     serve_repair::ServeRepair,
     serve_repair_service::ServeRepairService,
     sigverify,
+    sigverify_stage::SigVerifyDiscardPolicy,
     snapshot_packager_service::{PendingSnapshotPackage, SnapshotPackagerService},
     tpu::{Tpu, DEFAULT_TPU_COALESCE_MS},
+    transaction_scheduler::TransactionSchedulerType,
     tvu::{Sockets, Tvu, TvuConfig},
+    wen_restart,
 };
 use crossbeam_channel::{bounded, unbounded};
 use rand::{thread_rng, Rng};
@@ -75,11 +80,11 @@ use solana_sdk::{
 };
 use solana_vote_program::vote_state::VoteState;
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     net::SocketAddr,
     ops::Deref,
     path::{Path, PathBuf},
-    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
     sync::mpsc::Receiver,
     sync::{Arc, Mutex, RwLock},
     thread::{sleep, Builder, JoinHandle},
@@ -143,6 +148,14 @@ pub struct ValidatorConfig {
     pub validator_exit: Arc<RwLock<Exit>>,
     pub no_wait_for_vote_to_start_leader: bool,
     pub accounts_shrink_ratio: AccountShrinkThreshold,
+    pub dump_bank_hash_details_on_mismatch: bool,
+    pub banking_scheduler_type: TransactionSchedulerType,
+    pub sigverify_discard_policy: SigVerifyDiscardPolicy,
+    pub wen_restart: bool,
+    /// Outbound budgets of the gossip values, in bytes per second, by kind
+    pub gossip_data_budgets: HashMap<String, u64>,
+    pub gossip_push_compact_contact_info: bool,
+    pub gossip_push_restart_intent: bool,
 }
 
 impl Default for ValidatorConfig {
@@ -200,6 +213,50 @@ impl Default for ValidatorConfig {
             validator_exit: Arc::new(RwLock::new(Exit::default())),
             no_wait_for_vote_to_start_leader: true,
             accounts_shrink_ratio: AccountShrinkThreshold::default(),
+            dump_bank_hash_details_on_mismatch: false,
+            banking_scheduler_type: TransactionSchedulerType::default(),
+            sigverify_discard_policy: SigVerifyDiscardPolicy::default(),
+            wen_restart: false,
+            gossip_data_budgets: HashMap::new(),
+            gossip_push_compact_contact_info: false,
+            gossip_push_restart_intent: false,
+        }
+    }
+}
+
+// `LiveValidatorConfig` holds the subset of `ValidatorConfig` that the node operator may change
+// over the admin RPC channel while the validator is running, which would otherwise require a
+// restart and a lengthy catch up.
+#[derive(Clone, Debug, Default)]
+pub struct LiveValidatorConfig {
+    pub trusted_validators: Arc<RwLock<Option<HashSet<Pubkey>>>>, // None = trust all
+    pub repair_validators: Arc<RwLock<Option<HashSet<Pubkey>>>>,  // None = repair from all
+    pub accounts_hash_interval_slots: u64,
+    pub snapshot_interval_slots: Option<Arc<AtomicU64>>, // None = snapshots disabled
+    pub maximum_snapshots_to_retain: Option<Arc<AtomicUsize>>, // None = snapshots disabled
+    pub max_ledger_shreds: Option<Arc<AtomicU64>>,       // None = ledger cleanup disabled
+    pub max_transaction_history_slots: Option<Arc<AtomicU64>>, // None = retain all history
+}
+
+impl LiveValidatorConfig {
+    pub fn new(config: &ValidatorConfig) -> Self {
+        let snapshot_config = config.snapshot_config.as_ref();
+        Self {
+            trusted_validators: Arc::new(RwLock::new(config.trusted_validators.clone())),
+            repair_validators: Arc::new(RwLock::new(config.repair_validators.clone())),
+            accounts_hash_interval_slots: config.accounts_hash_interval_slots,
+            snapshot_interval_slots: snapshot_config.map(|snapshot_config| {
+                Arc::new(AtomicU64::new(snapshot_config.snapshot_interval_slots))
+            }),
+            maximum_snapshots_to_retain: snapshot_config.map(|snapshot_config| {
+                Arc::new(AtomicUsize::new(
+                    snapshot_config.maximum_snapshots_to_retain,
+                ))
+            }),
+            max_ledger_shreds: config
+                .max_ledger_shreds
+                .map(|max_ledger_shreds| Arc::new(AtomicU64::new(max_ledger_shreds))),
+            max_transaction_history_slots: config.rpc_config.max_transaction_history_slots.clone(),
         }
     }
 }
@@ -217,6 +274,7 @@ pub enum ValidatorStartProgress {
     LoadingLedger,
     StartingServices,
     Halted, // Validator halted due to `--dev-halt-at-slot` argument
+    WaitingForWenRestart,
     WaitingForSupermajority,
 
     // `Running` is the terminal state once the validator fully starts and all services are
@@ -261,6 +319,9 @@ pub struct Validator {
     tvu: Tvu,
     ip_echo_server: Option<solana_net_utils::IpEchoServer>,
     pub cluster_info: Arc<ClusterInfo>,
+    pub live_config: LiveValidatorConfig,
+    pub retransmit_tree_inspector: RetransmitTreeInspector,
+    pub outstanding_repairs: Arc<RwLock<OutstandingRepairs>>,
 }
 
 // in the distant future, get rid of ::new()/exit() and use Result properly...
@@ -364,11 +425,11 @@ impl Validator {
         let (replay_vote_sender, replay_vote_receiver) = unbounded();
         let (
             genesis_config,
-            bank_forks,
+            mut bank_forks,
             blockstore,
             ledger_signal_receiver,
             completed_slots_receiver,
-            leader_schedule_cache,
+            mut leader_schedule_cache,
             snapshot_hash,
             TransactionHistoryServices {
                 transaction_status_sender,
@@ -379,7 +440,7 @@ impl Validator {
                 cache_block_meta_sender,
                 cache_block_meta_service,
             },
-            tower,
+            mut tower,
         ) = new_banks_from_ledger(
             &id,
             vote_account,
@@ -392,6 +453,24 @@ impl Validator {
             config.no_poh_speed_test,
         );
 
+        let wen_restart = config.wen_restart.then(|| {
+            *start_progress.write().unwrap() = ValidatorStartProgress::WaitingForWenRestart;
+            run_wen_restart(
+                &node,
+                &identity_keypair,
+                vote_account,
+                &cluster_entrypoints,
+                config,
+                ledger_path,
+                &genesis_config,
+                &blockstore,
+                &mut bank_forks,
+                &mut leader_schedule_cache,
+                &mut tower,
+                &exit,
+            )
+        });
+
         *start_progress.write().unwrap() = ValidatorStartProgress::StartingServices;
 
         let leader_schedule_cache = Arc::new(leader_schedule_cache);
@@ -428,7 +507,11 @@ impl Validator {
 
         Self::print_node_info(&node);
 
-        if let Some(expected_shred_version) = config.expected_shred_version {
+        // The expected shred version is the one of the stalled cluster on a wen restart
+        if let Some(expected_shred_version) = config
+            .expected_shred_version
+            .filter(|_| wen_restart.is_none())
+        {
             if expected_shred_version != node.info.shred_version {
                 error!(
                     "shred version mismatch: expected {} found: {}",
@@ -440,6 +523,12 @@ impl Validator {
 
         let mut cluster_info = ClusterInfo::new(node.info.clone(), identity_keypair);
         cluster_info.set_contact_debug_interval(config.contact_debug_interval);
+        cluster_info.set_push_compact_contact_info(config.gossip_push_compact_contact_info);
+        cluster_info.set_push_restart_intent(config.gossip_push_restart_intent);
+        if let Err(err) = cluster_info.set_crds_data_budgets(&config.gossip_data_budgets) {
+            error!("Invalid gossip data budgets: {}", err);
+            abort();
+        }
         cluster_info.set_entrypoints(cluster_entrypoints);
         cluster_info.restore_contact_info(ledger_path, config.contact_save_interval);
         let cluster_info = Arc::new(cluster_info);
@@ -504,6 +593,7 @@ impl Validator {
         }
         let poh_recorder = Arc::new(Mutex::new(poh_recorder));
 
+        let live_config = LiveValidatorConfig::new(config);
         let rpc_override_health_check = Arc::new(AtomicBool::new(false));
         let (
             json_rpc_service,
@@ -530,7 +620,7 @@ impl Validator {
                     genesis_config.hash(),
                     ledger_path,
                     config.validator_exit.clone(),
-                    config.trusted_validators.clone(),
+                    live_config.trusted_validators.clone(),
                     rpc_override_health_check.clone(),
                     optimistically_confirmed_bank.clone(),
                     config.send_transaction_retry_ms,
@@ -615,7 +705,7 @@ impl Validator {
                     snapshot_hash,
                     &exit,
                     &cluster_info,
-                    snapshot_config.maximum_snapshots_to_retain,
+                    live_config.maximum_snapshots_to_retain.clone().unwrap(),
                 );
                 (
                     Some(snapshot_packager_service),
@@ -627,6 +717,7 @@ impl Validator {
 
         let waited_for_supermajority = if let Ok(waited) = wait_for_supermajority(
             config,
+            wen_restart,
             &bank,
             &cluster_info,
             rpc_override_health_check,
@@ -700,6 +791,11 @@ impl Validator {
                     .iter()
                     .map(|s| s.try_clone().expect("Failed to clone TVU forwards Sockets"))
                     .collect(),
+                ancestor_hashes_requests: node
+                    .sockets
+                    .ancestor_hashes_requests
+                    .try_clone()
+                    .expect("Failed to clone ancestor_hashes_requests socket"),
             },
             blockstore.clone(),
             ledger_signal_receiver,
@@ -723,12 +819,13 @@ impl Validator {
             bank_notification_sender.clone(),
             cluster_confirmed_slot_receiver,
             TvuConfig {
-                max_ledger_shreds: config.max_ledger_shreds,
+                max_ledger_shreds: live_config.max_ledger_shreds.clone(),
                 halt_on_trusted_validators_accounts_hash_mismatch: config
                     .halt_on_trusted_validators_accounts_hash_mismatch,
                 shred_version: node.info.shred_version,
-                trusted_validators: config.trusted_validators.clone(),
-                repair_validators: config.repair_validators.clone(),
+                trusted_validators: live_config.trusted_validators.clone(),
+                repair_validators: live_config.repair_validators.clone(),
+                snapshot_interval_slots: live_config.snapshot_interval_slots.clone(),
                 accounts_hash_fault_injection_slots: config.accounts_hash_fault_injection_slots,
                 accounts_db_caching_enabled: config.accounts_db_caching_enabled,
                 test_hash_calculation: config.accounts_db_test_hash_calculation,
@@ -737,6 +834,9 @@ impl Validator {
                 rocksdb_max_compaction_jitter: config.rocksdb_compaction_interval,
                 wait_for_vote_to_start_leader,
                 accounts_shrink_ratio: config.accounts_shrink_ratio,
+                bank_hash_details_dir: config
+                    .dump_bank_hash_details_on_mismatch
+                    .then(|| ledger_path.join("bank_hash_details")),
             },
             &max_slots,
             &cost_model,
@@ -766,8 +866,12 @@ impl Validator {
             config.tpu_coalesce_ms,
             cluster_confirmed_slot_sender,
             &cost_model,
+            config.banking_scheduler_type,
+            config.sigverify_discard_policy,
         );
 
+        let retransmit_tree_inspector = tvu.retransmit_tree_inspector().clone();
+        let outstanding_repairs = tvu.outstanding_repairs().clone();
         datapoint_info!("validator-new", ("id", id.to_string(), String));
         *start_progress.write().unwrap() = ValidatorStartProgress::Running;
         Self {
@@ -790,6 +894,9 @@ impl Validator {
             ip_echo_server,
             validator_exit: config.validator_exit.clone(),
             cluster_info,
+            live_config,
+            retransmit_tree_inspector,
+            outstanding_repairs,
         }
     }
 
@@ -1238,6 +1345,95 @@ fn new_banks_from_ledger(
     )
 }
 
+#[allow(clippy::too_many_arguments)]
+fn run_wen_restart(
+    node: &Node,
+    identity_keypair: &Arc<Keypair>,
+    vote_account: &Pubkey,
+    cluster_entrypoints: &[ContactInfo],
+    config: &ValidatorConfig,
+    ledger_path: &Path,
+    genesis_config: &GenesisConfig,
+    blockstore: &Blockstore,
+    bank_forks: &mut BankForks,
+    leader_schedule_cache: &mut LeaderScheduleCache,
+    tower: &mut Tower,
+    exit: &Arc<AtomicBool>,
+) -> (Slot, Hash) {
+    let snapshot_config = config.snapshot_config.as_ref().unwrap_or_else(|| {
+        error!("wen restart requires a snapshot config");
+        abort();
+    });
+
+    // Gossip with the rest of the stalled cluster, which is still on the shred version of
+    // the ledger before the restart
+    let shred_version = compute_shred_version(
+        &genesis_config.hash(),
+        Some(&bank_forks.root_bank().hard_forks().read().unwrap()),
+    );
+    if let Some(expected_shred_version) = config.expected_shred_version {
+        if expected_shred_version != shred_version {
+            error!(
+                "shred version mismatch: expected {} found: {}",
+                expected_shred_version, shred_version,
+            );
+            abort();
+        }
+    }
+    let mut contact_info = node.info.clone();
+    contact_info.wallclock = timestamp();
+    contact_info.shred_version = shred_version;
+    let cluster_info = ClusterInfo::new(contact_info, identity_keypair.clone());
+    cluster_info.set_entrypoints(cluster_entrypoints.to_vec());
+    let cluster_info = Arc::new(cluster_info);
+    let gossip_exit = Arc::new(AtomicBool::new(false));
+    let gossip_service = GossipService::new(
+        &cluster_info,
+        None,
+        node.sockets.gossip.try_clone().unwrap(),
+        config.gossip_validators.clone(),
+        false,
+        &gossip_exit,
+    );
+
+    let wen_restart = wen_restart::wait_for_wen_restart(
+        &cluster_info,
+        blockstore,
+        bank_forks,
+        tower.last_voted_slot_hash(),
+        exit,
+    );
+    gossip_exit.store(true, Ordering::Relaxed);
+    gossip_service.join().unwrap();
+    let wen_restart = wen_restart.unwrap_or_else(|err| {
+        error!("wen restart failed: {}", err);
+        abort();
+    });
+    leader_schedule_cache.set_root(&bank_forks.root_bank());
+
+    let archive_file = solana_runtime::snapshot_utils::bank_to_snapshot_archive(
+        ledger_path,
+        &bank_forks.root_bank(),
+        None,
+        &snapshot_config.snapshot_package_output_path,
+        snapshot_config.archive_format,
+        Some(bank_forks.root_bank().get_thread_pool()),
+        snapshot_config.maximum_snapshots_to_retain,
+    )
+    .unwrap_or_else(|err| {
+        error!("Unable to create snapshot: {}", err);
+        abort();
+    });
+    info!("created snapshot: {}", archive_file.display());
+
+    // Votes on the abandoned forks must not lock out voting on the new hard fork
+    let tower_path = config.tower_path.as_deref().unwrap_or(ledger_path);
+    *tower = Tower::new_from_bankforks(bank_forks, tower_path, &node.info.id, vote_account);
+    info!("Tower state after wen restart: {:?}", tower);
+
+    wen_restart
+}
+
 fn blockstore_contains_bad_shred_version(
     blockstore: &Blockstore,
     start_slot: Slot,
@@ -1368,12 +1564,16 @@ enum ValidatorError {
 // that is unrecoverable and the validator should exit.
 fn wait_for_supermajority(
     config: &ValidatorConfig,
+    wen_restart: Option<(Slot, Hash)>,
     bank: &Bank,
     cluster_info: &ClusterInfo,
     rpc_override_health_check: Arc<AtomicBool>,
     start_progress: &Arc<RwLock<ValidatorStartProgress>>,
 ) -> Result<bool, ValidatorError> {
-    if let Some(wait_for_supermajority) = config.wait_for_supermajority {
+    if let Some(wait_for_supermajority) = wen_restart
+        .map(|(restart_slot, _)| restart_slot)
+        .or(config.wait_for_supermajority)
+    {
         match wait_for_supermajority.cmp(&bank.slot()) {
             std::cmp::Ordering::Less => return Ok(false),
             std::cmp::Ordering::Greater => {
@@ -1391,7 +1591,12 @@ fn wait_for_supermajority(
         return Ok(false);
     }
 
-    if let Some(expected_bank_hash) = config.expected_bank_hash {
+    // On a wen restart the bank hash must also be the one agreed on through gossip
+    let expected_bank_hashes = config
+        .expected_bank_hash
+        .into_iter()
+        .chain(wen_restart.map(|(_, restart_hash)| restart_hash));
+    for expected_bank_hash in expected_bank_hashes {
         if bank.hash() != expected_bank_hash {
             error!(
                 "Bank hash({}) does not match expected value: {}",
@@ -1580,6 +1785,7 @@ pub fn is_snapshot_config_invalid(
 mod tests {
     use super::*;
     use solana_ledger::{create_new_tmp_ledger, genesis_utils::create_genesis_config_with_leader};
+    use solana_runtime::snapshot_utils::{ArchiveFormat, SnapshotVersion};
     use solana_sdk::genesis_config::create_genesis_config;
     use solana_sdk::poh_config::PohConfig;
     use std::fs::remove_dir_all;
@@ -1726,6 +1932,7 @@ mod tests {
 
         assert!(!wait_for_supermajority(
             &config,
+            None,
             &bank,
             &cluster_info,
             rpc_override_health_check.clone(),
@@ -1738,6 +1945,7 @@ mod tests {
         assert_eq!(
             wait_for_supermajority(
                 &config,
+                None,
                 &bank,
                 &cluster_info,
                 rpc_override_health_check.clone(),
@@ -1751,6 +1959,7 @@ mod tests {
         config.wait_for_supermajority = Some(0);
         assert!(!wait_for_supermajority(
             &config,
+            None,
             &bank,
             &cluster_info,
             rpc_override_health_check.clone(),
@@ -1764,6 +1973,35 @@ mod tests {
         assert_eq!(
             wait_for_supermajority(
                 &config,
+                None,
+                &bank,
+                &cluster_info,
+                rpc_override_health_check.clone(),
+                &start_progress,
+            ),
+            Err(ValidatorError::BadExpectedBankHash)
+        );
+
+        // bank=1, wen restart at 1, the expected bank hash is still checked
+        config.wait_for_supermajority = None;
+        assert_eq!(
+            wait_for_supermajority(
+                &config,
+                Some((1, bank.hash())),
+                &bank,
+                &cluster_info,
+                rpc_override_health_check.clone(),
+                &start_progress,
+            ),
+            Err(ValidatorError::BadExpectedBankHash)
+        );
+
+        // bank=1, wen restart at 1, but a different bank hash was agreed on
+        config.expected_bank_hash = None;
+        assert_eq!(
+            wait_for_supermajority(
+                &config,
+                Some((1, hash(&[1]))),
                 &bank,
                 &cluster_info,
                 rpc_override_health_check,
@@ -1782,6 +2020,82 @@ mod tests {
         assert!(!is_snapshot_config_invalid(5, 5));
     }
 
+    #[test]
+    fn test_live_validator_config() {
+        let repair_validator = solana_sdk::pubkey::new_rand();
+        let mut config = ValidatorConfig {
+            repair_validators: Some(vec![repair_validator].into_iter().collect()),
+            ..ValidatorConfig::default()
+        };
+        let live_config = LiveValidatorConfig::new(&config);
+        assert_eq!(*live_config.trusted_validators.read().unwrap(), None);
+        assert_eq!(
+            *live_config.repair_validators.read().unwrap(),
+            config.repair_validators
+        );
+        assert!(live_config.snapshot_interval_slots.is_none());
+        assert!(live_config.maximum_snapshots_to_retain.is_none());
+        assert!(live_config.max_ledger_shreds.is_none());
+        assert!(live_config.max_transaction_history_slots.is_none());
+
+        config.snapshot_config = Some(SnapshotConfig {
+            snapshot_interval_slots: 200,
+            snapshot_package_output_path: PathBuf::default(),
+            snapshot_path: PathBuf::default(),
+            archive_format: ArchiveFormat::TarBzip2,
+            snapshot_version: SnapshotVersion::default(),
+            maximum_snapshots_to_retain: 3,
+        });
+        config.max_ledger_shreds = Some(1_000);
+        config.rpc_config.max_transaction_history_slots = Some(Arc::new(AtomicU64::new(432_000)));
+        let live_config = LiveValidatorConfig::new(&config);
+        assert_eq!(
+            live_config
+                .snapshot_interval_slots
+                .as_ref()
+                .unwrap()
+                .load(Ordering::Relaxed),
+            200
+        );
+        assert_eq!(
+            live_config
+                .maximum_snapshots_to_retain
+                .as_ref()
+                .unwrap()
+                .load(Ordering::Relaxed),
+            3
+        );
+        assert_eq!(
+            live_config
+                .max_ledger_shreds
+                .as_ref()
+                .unwrap()
+                .load(Ordering::Relaxed),
+            1_000
+        );
+
+        // The RPC service shares the transaction history retention of the validator config
+        config
+            .rpc_config
+            .max_transaction_history_slots
+            .as_ref()
+            .unwrap()
+            .store(216_000, Ordering::Relaxed);
+        assert_eq!(
+            live_config
+                .max_transaction_history_slots
+                .as_ref()
+                .unwrap()
+                .load(Ordering::Relaxed),
+            216_000
+        );
+
+        // Clones share the same settings
+        let live_config_clone = live_config.clone();
+        *live_config_clone.repair_validators.write().unwrap() = None;
+        assert_eq!(*live_config.repair_validators.read().unwrap(), None);
+    }
+
     #[test]
     #[should_panic]
     fn test_poh_speed() {
@@ -0,0 +1,454 @@
+//! The `wen_restart` module implements the coordinated restart of a stalled cluster.
+//!
+//! Instead of the operators agreeing on a slot out of band, each restarting validator
+//! announces the fork it last voted on through gossip. Once enough of the stake has
+//! announced its fork, every validator picks the highest slot on the last voted forks of
+//! `WEN_RESTART_FORK_THRESHOLD_PERCENT` of the stake and announces its bank hash of that
+//! slot. The forks only tell the slots apart, so the validator then waits for the same
+//! share of the stake to announce the same slot with the same bank hash, before rooting
+//! it and registering a hard fork right after it. Validators which picked different slots
+//! end up with different shred versions, so they cannot make progress together, rather
+//! than fork the cluster.
+
+use solana_gossip::{
+    cluster_info::ClusterInfo,
+    crds::Cursor,
+    crds_value::{RestartLastVotedForkSlots, RestartSlotHash, MAX_RESTART_LAST_VOTED_FORK_SLOTS},
+};
+use solana_ledger::{
+    ancestor_iterator::AncestorIterator,
+    blockstore::{Blockstore, PurgeType},
+};
+use solana_runtime::{accounts_background_service::AbsRequestSender, bank_forks::BankForks};
+use solana_sdk::{clock::Slot, hash::Hash, pubkey::Pubkey};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicBool, Ordering},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// Stake which must have announced its last voted fork before the restart slot is picked
+pub const WEN_RESTART_PARTICIPATION_THRESHOLD_PERCENT: u64 = 80;
+/// Stake which must have the restart slot on its last voted fork, and then announce the
+/// same bank hash for it. With 80% of the stake participating, of which up to 5% may be
+/// malicious, an optimistically confirmed slot is on the last voted forks of at least
+/// 67% - 20% - 5% = 42% of the stake
+pub const WEN_RESTART_FORK_THRESHOLD_PERCENT: u64 = 42;
+// Time spent collecting the forks of late validators after the participation threshold
+// is reached, so that the validators are more likely to pick the restart slot from the
+// same announcements
+const WEN_RESTART_SETTLE_PERIOD: Duration = Duration::from_secs(10);
+const WEN_RESTART_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Error, Debug, PartialEq)]
+pub enum WenRestartError {
+    #[error("exit requested")]
+    Exit,
+
+    #[error("no slot is on the last voted forks of enough stake")]
+    NoRestartSlot,
+
+    #[error("restart slot {0} is older than the local root {1}")]
+    RestartSlotBeforeRoot(Slot, Slot),
+
+    /// Repair is not running yet when the restart slot is picked, so a validator which
+    /// did not replay the restart slot before the cluster stalled cannot restart from it.
+    /// Restarting from another slot would give it a different shred version than the rest
+    /// of the cluster, so it aborts instead, to be restarted from a snapshot of the
+    /// restart slot downloaded from another validator.
+    #[error("restart slot {0} has not been replayed from the local ledger")]
+    MissingRestartSlot(Slot),
+
+    #[error("not enough stake agrees with the local bank hash {1} of restart slot {0}")]
+    RestartSlotHashMismatch(Slot, Hash),
+}
+
+fn meets_fork_threshold(stake: u64, total_stake: u64) -> bool {
+    stake as u128 * 100 >= total_stake as u128 * WEN_RESTART_FORK_THRESHOLD_PERCENT as u128
+}
+
+/// Stake weighted tally of the last voted forks announced by the restarting validators
+pub struct LastVotedForkSlotsAggregate {
+    epoch_stakes: HashMap<Pubkey, u64>,
+    total_stake: u64,
+    participating_stake: u64,
+    last_voted_forks: HashMap<Pubkey, RestartLastVotedForkSlots>,
+    slots_stake: HashMap<Slot, u64>,
+}
+
+impl LastVotedForkSlotsAggregate {
+    pub fn new(epoch_stakes: HashMap<Pubkey, u64>) -> Self {
+        let total_stake = epoch_stakes.values().sum();
+        Self {
+            epoch_stakes,
+            total_stake,
+            participating_stake: 0,
+            last_voted_forks: HashMap::new(),
+            slots_stake: HashMap::new(),
+        }
+    }
+
+    /// Adds the last voted fork of a validator, replacing the one it announced before.
+    /// The forks of unstaked validators are ignored.
+    pub fn aggregate(&mut self, fork: RestartLastVotedForkSlots) {
+        let stake = match self.epoch_stakes.get(&fork.from) {
+            Some(stake) if *stake > 0 => *stake,
+            _ => return,
+        };
+        for slot in fork.to_slots() {
+            *self.slots_stake.entry(slot).or_default() += stake;
+        }
+        match self.last_voted_forks.insert(fork.from, fork) {
+            Some(old_fork) => {
+                for slot in old_fork.to_slots() {
+                    let slot_stake = self.slots_stake.get_mut(&slot).unwrap();
+                    *slot_stake -= stake;
+                    if *slot_stake == 0 {
+                        self.slots_stake.remove(&slot);
+                    }
+                }
+            }
+            None => self.participating_stake += stake,
+        }
+    }
+
+    pub fn participating_stake_percent(&self) -> u64 {
+        if self.total_stake == 0 {
+            return 0;
+        }
+        (self.participating_stake as u128 * 100 / self.total_stake as u128) as u64
+    }
+
+    /// Returns the highest slot on the last voted forks of at least
+    /// `WEN_RESTART_FORK_THRESHOLD_PERCENT` of the stake
+    pub fn restart_slot(&self) -> Option<Slot> {
+        self.slots_stake
+            .iter()
+            .filter(|(_, stake)| meets_fork_threshold(**stake, self.total_stake))
+            .map(|(slot, _)| *slot)
+            .max()
+    }
+
+    pub fn last_voted_forks(&self) -> impl Iterator<Item = &RestartLastVotedForkSlots> {
+        self.last_voted_forks.values()
+    }
+}
+
+/// Stake weighted tally of the bank hashes announced for the restart slots
+pub struct RestartSlotHashAggregate {
+    epoch_stakes: HashMap<Pubkey, u64>,
+    total_stake: u64,
+    announced_stake: u64,
+    slot_hashes: HashMap<Pubkey, (Slot, Hash)>,
+    slot_hashes_stake: HashMap<(Slot, Hash), u64>,
+}
+
+impl RestartSlotHashAggregate {
+    pub fn new(epoch_stakes: HashMap<Pubkey, u64>) -> Self {
+        let total_stake = epoch_stakes.values().sum();
+        Self {
+            epoch_stakes,
+            total_stake,
+            announced_stake: 0,
+            slot_hashes: HashMap::new(),
+            slot_hashes_stake: HashMap::new(),
+        }
+    }
+
+    /// Adds the restart slot hash of a validator, replacing the one it announced before.
+    /// The hashes of unstaked validators are ignored.
+    pub fn aggregate(&mut self, slot_hash: RestartSlotHash) {
+        let stake = match self.epoch_stakes.get(&slot_hash.from) {
+            Some(stake) if *stake > 0 => *stake,
+            _ => return,
+        };
+        let key = (slot_hash.slot, slot_hash.hash);
+        *self.slot_hashes_stake.entry(key).or_default() += stake;
+        match self.slot_hashes.insert(slot_hash.from, key) {
+            Some(old_key) => {
+                let old_stake = self.slot_hashes_stake.get_mut(&old_key).unwrap();
+                *old_stake -= stake;
+                if *old_stake == 0 {
+                    self.slot_hashes_stake.remove(&old_key);
+                }
+            }
+            None => self.announced_stake += stake,
+        }
+    }
+
+    /// Returns true if at least `WEN_RESTART_FORK_THRESHOLD_PERCENT` of the stake
+    /// announced `hash` for `slot`
+    pub fn is_agreed(&self, slot: Slot, hash: Hash) -> bool {
+        meets_fork_threshold(self.stake(slot, hash), self.total_stake)
+    }
+
+    /// Returns false once the stake which has not announced a hash yet can no longer
+    /// bring the stake agreeing on `hash` for `slot` to the threshold
+    pub fn can_agree(&self, slot: Slot, hash: Hash) -> bool {
+        let pending_stake = self.total_stake - self.announced_stake;
+        meets_fork_threshold(self.stake(slot, hash) + pending_stake, self.total_stake)
+    }
+
+    fn stake(&self, slot: Slot, hash: Hash) -> u64 {
+        self.slot_hashes_stake
+            .get(&(slot, hash))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Announces the last voted fork of this validator, waits for enough of the stake to
+/// announce theirs, and then for enough of the stake to agree on the bank hash of the
+/// restart slot, before rooting it in `bank_forks` with a hard fork registered right after
+/// it. The descendants of the restart slot are purged from `bank_forks` and `blockstore`.
+/// Returns the restart slot and its agreed bank hash, or `MissingRestartSlot` if it has
+/// not been replayed from the local ledger.
+pub fn wait_for_wen_restart(
+    cluster_info: &ClusterInfo,
+    blockstore: &Blockstore,
+    bank_forks: &mut BankForks,
+    last_vote: Option<(Slot, Hash)>,
+    exit: &AtomicBool,
+) -> Result<(Slot, Hash), WenRestartError> {
+    let root_bank = bank_forks.root_bank();
+    let (last_voted_slot, last_voted_hash) = match last_vote {
+        Some((slot, hash)) if slot >= root_bank.slot() => (slot, hash),
+        _ => (root_bank.slot(), root_bank.hash()),
+    };
+    // Ancestors below the local root are included, so that the common ancestors of the
+    // forks are counted for validators whose roots are behind others
+    let last_voted_fork: Vec<Slot> = AncestorIterator::new_inclusive(last_voted_slot, blockstore)
+        .take_while(|slot| last_voted_slot - slot < MAX_RESTART_LAST_VOTED_FORK_SLOTS)
+        .collect();
+    info!(
+        "wen restart: last voted slot {} hash {}, announcing {} slots",
+        last_voted_slot,
+        last_voted_hash,
+        last_voted_fork.len()
+    );
+    cluster_info.push_restart_last_voted_fork_slots(&last_voted_fork, last_voted_hash);
+
+    let epoch_stakes = root_bank
+        .epoch_staked_nodes(root_bank.epoch())
+        .unwrap_or_default();
+    let mut aggregate = LastVotedForkSlotsAggregate::new(epoch_stakes.clone());
+    let mut cursor = Cursor::default();
+    let mut participation_reached: Option<Instant> = None;
+    for i in 0.. {
+        if exit.load(Ordering::Relaxed) {
+            return Err(WenRestartError::Exit);
+        }
+        for fork in cluster_info.get_restart_last_voted_fork_slots(&mut cursor) {
+            aggregate.aggregate(fork);
+        }
+        let participating_stake_percent = aggregate.participating_stake_percent();
+        if participating_stake_percent >= WEN_RESTART_PARTICIPATION_THRESHOLD_PERCENT {
+            let participation_reached = *participation_reached.get_or_insert_with(Instant::now);
+            if participation_reached.elapsed() >= WEN_RESTART_SETTLE_PERIOD {
+                break;
+            }
+        }
+        if i % 10 == 0 {
+            info!(
+                "wen restart: {}% of the stake announced its last voted fork, waiting for {}%",
+                participating_stake_percent, WEN_RESTART_PARTICIPATION_THRESHOLD_PERCENT
+            );
+        }
+        sleep(WEN_RESTART_REFRESH_INTERVAL);
+    }
+
+    let restart_slot = aggregate
+        .restart_slot()
+        .ok_or(WenRestartError::NoRestartSlot)?;
+    let root = bank_forks.root();
+    if restart_slot < root {
+        return Err(WenRestartError::RestartSlotBeforeRoot(restart_slot, root));
+    }
+    let restart_bank = bank_forks
+        .get(restart_slot)
+        .filter(|bank| bank.is_frozen())
+        .cloned()
+        .ok_or(WenRestartError::MissingRestartSlot(restart_slot))?;
+    for fork in aggregate.last_voted_forks() {
+        if fork.last_voted_slot == restart_slot && fork.last_voted_hash != restart_bank.hash() {
+            warn!(
+                "wen restart: {} voted for restart slot {} with hash {}, local hash is {}",
+                fork.from,
+                restart_slot,
+                fork.last_voted_hash,
+                restart_bank.hash()
+            );
+        }
+    }
+    let restart_hash = restart_bank.hash();
+    cluster_info.push_restart_slot_hash(restart_slot, restart_hash);
+    let mut slot_hash_aggregate = RestartSlotHashAggregate::new(epoch_stakes);
+    let mut cursor = Cursor::default();
+    for i in 0.. {
+        if exit.load(Ordering::Relaxed) {
+            return Err(WenRestartError::Exit);
+        }
+        for slot_hash in cluster_info.get_restart_slot_hashes(&mut cursor) {
+            slot_hash_aggregate.aggregate(slot_hash);
+        }
+        if slot_hash_aggregate.is_agreed(restart_slot, restart_hash) {
+            break;
+        }
+        if !slot_hash_aggregate.can_agree(restart_slot, restart_hash) {
+            return Err(WenRestartError::RestartSlotHashMismatch(
+                restart_slot,
+                restart_hash,
+            ));
+        }
+        if i % 10 == 0 {
+            info!(
+                "wen restart: waiting for {}% of the stake to agree on slot {} hash {}",
+                WEN_RESTART_FORK_THRESHOLD_PERCENT, restart_slot, restart_hash
+            );
+        }
+        sleep(WEN_RESTART_REFRESH_INTERVAL);
+    }
+    info!(
+        "wen restart: restarting from slot {} hash {}",
+        restart_slot, restart_hash
+    );
+    datapoint_info!(
+        "wen_restart",
+        ("slot", restart_slot, i64),
+        (
+            "participating_stake_percent",
+            aggregate.participating_stake_percent(),
+            i64
+        ),
+    );
+
+    bank_forks.set_root(
+        restart_slot,
+        &AbsRequestSender::default(),
+        Some(restart_slot),
+    );
+    let mut descendants: Vec<Slot> = bank_forks
+        .descendants()
+        .get(&restart_slot)
+        .map(|descendants| descendants.iter().copied().collect())
+        .unwrap_or_default();
+    descendants.sort_unstable_by(|a, b| b.cmp(a));
+    for slot in descendants {
+        bank_forks.remove(slot);
+    }
+    restart_bank
+        .hard_forks()
+        .write()
+        .unwrap()
+        .register(restart_slot + 1);
+
+    blockstore
+        .set_roots(std::iter::once(&restart_slot))
+        .expect("Couldn't set root slot on wen restart");
+    if let Ok(Some(highest_slot)) = blockstore.highest_slot() {
+        if highest_slot > restart_slot {
+            blockstore.purge_from_next_slots(restart_slot + 1, highest_slot);
+            blockstore.purge_slots(restart_slot + 1, highest_slot, PurgeType::Exact);
+        }
+    }
+    Ok((restart_slot, restart_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::timing::timestamp;
+
+    fn new_fork(from: Pubkey, slots: &[Slot]) -> RestartLastVotedForkSlots {
+        RestartLastVotedForkSlots::new(from, timestamp(), slots, Hash::new_unique(), 0).unwrap()
+    }
+
+    #[test]
+    fn test_last_voted_fork_slots_aggregate() {
+        let validators: Vec<_> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let epoch_stakes = validators
+            .iter()
+            .zip(vec![30, 25, 20, 15, 10])
+            .map(|(pubkey, stake)| (*pubkey, stake))
+            .collect();
+        let mut aggregate = LastVotedForkSlotsAggregate::new(epoch_stakes);
+        assert_eq!(aggregate.participating_stake_percent(), 0);
+        assert_eq!(aggregate.restart_slot(), None);
+
+        // Forks of unstaked validators are ignored
+        aggregate.aggregate(new_fork(Pubkey::new_unique(), &[1, 2, 3]));
+        assert_eq!(aggregate.participating_stake_percent(), 0);
+
+        // Two forks split off slot 2
+        aggregate.aggregate(new_fork(validators[0], &[1, 2, 3, 4]));
+        assert_eq!(aggregate.restart_slot(), None);
+        aggregate.aggregate(new_fork(validators[1], &[1, 2, 5]));
+        assert_eq!(aggregate.restart_slot(), Some(2));
+        aggregate.aggregate(new_fork(validators[2], &[1, 2, 3]));
+        assert_eq!(aggregate.participating_stake_percent(), 75);
+        assert_eq!(aggregate.restart_slot(), Some(3));
+        aggregate.aggregate(new_fork(validators[3], &[1, 2, 3, 4, 6]));
+        assert_eq!(aggregate.participating_stake_percent(), 90);
+        assert_eq!(aggregate.restart_slot(), Some(4));
+
+        // A new announcement replaces the earlier fork of the validator
+        aggregate.aggregate(new_fork(validators[0], &[1, 2, 5, 7]));
+        assert_eq!(aggregate.participating_stake_percent(), 90);
+        assert_eq!(aggregate.restart_slot(), Some(5));
+        assert_eq!(aggregate.slots_stake.get(&4), Some(&15));
+        assert_eq!(aggregate.slots_stake.get(&3), Some(&35));
+        assert_eq!(aggregate.last_voted_forks().count(), 4);
+    }
+
+    #[test]
+    fn test_restart_slot_hash_aggregate() {
+        let validators: Vec<_> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let epoch_stakes = validators
+            .iter()
+            .zip(vec![30, 25, 20, 15, 10])
+            .map(|(pubkey, stake)| (*pubkey, stake))
+            .collect();
+        let mut aggregate = RestartSlotHashAggregate::new(epoch_stakes);
+        let new_slot_hash = |from, slot, hash| RestartSlotHash {
+            from,
+            wallclock: timestamp(),
+            slot,
+            hash,
+            shred_version: 0,
+        };
+        let (hash, other_hash) = (Hash::new_unique(), Hash::new_unique());
+        assert!(!aggregate.is_agreed(4, hash));
+        assert!(aggregate.can_agree(4, hash));
+
+        // Hashes of unstaked validators are ignored
+        aggregate.aggregate(new_slot_hash(Pubkey::new_unique(), 4, hash));
+        assert_eq!(aggregate.stake(4, hash), 0);
+
+        // Stake on different hashes of the same slot does not add up
+        aggregate.aggregate(new_slot_hash(validators[0], 4, hash));
+        aggregate.aggregate(new_slot_hash(validators[1], 4, other_hash));
+        assert!(!aggregate.is_agreed(4, hash));
+        assert!(!aggregate.is_agreed(4, other_hash));
+
+        // Nor does stake on the same hash of another slot
+        aggregate.aggregate(new_slot_hash(validators[2], 5, hash));
+        assert!(!aggregate.is_agreed(4, hash));
+        // The 25% of the stake yet to announce can still make up the difference
+        assert!(aggregate.can_agree(4, hash));
+
+        aggregate.aggregate(new_slot_hash(validators[3], 4, other_hash));
+        assert!(!aggregate.is_agreed(4, other_hash));
+        aggregate.aggregate(new_slot_hash(validators[4], 4, other_hash));
+        assert!(aggregate.is_agreed(4, other_hash));
+        assert!(!aggregate.can_agree(4, hash));
+
+        // A new announcement replaces the earlier hash of the validator
+        aggregate.aggregate(new_slot_hash(validators[1], 4, hash));
+        assert_eq!(aggregate.stake(4, hash), 55);
+        assert_eq!(aggregate.stake(4, other_hash), 25);
+        assert!(aggregate.is_agreed(4, hash));
+        assert!(!aggregate.is_agreed(4, other_hash));
+    }
+}
@@ -20,7 +20,7 @@ use solana_gossip::cluster_info::ClusterInfo;
 use solana_ledger::{
     blockstore::{self, Blockstore, BlockstoreInsertionMetrics, MAX_DATA_SHREDS_PER_SLOT},
     leader_schedule_cache::LeaderScheduleCache,
-    shred::{Nonce, Shred},
+    shred::{should_use_merkle_shreds, Nonce, Shred},
 };
 use solana_metrics::{inc_new_counter_debug, inc_new_counter_error};
 use solana_perf::packet::Packets;
@@ -60,9 +60,9 @@ pub fn should_retransmit_and_persist(
     root: u64,
     shred_version: u16,
 ) -> bool {
-    let slot_leader_pubkey = match bank {
+    let slot_leader_pubkey = match &bank {
         None => leader_schedule_cache.slot_leader_at(shred.slot(), None),
-        Some(bank) => leader_schedule_cache.slot_leader_at(shred.slot(), Some(&bank)),
+        Some(bank) => leader_schedule_cache.slot_leader_at(shred.slot(), Some(bank)),
     };
     if let Some(leader_id) = slot_leader_pubkey {
         if leader_id == *my_pubkey {
@@ -74,6 +74,13 @@ pub fn should_retransmit_and_persist(
         } else if shred.version() != shred_version {
             inc_new_counter_debug!("streamer-recv_window-incorrect_shred_version", 1);
             false
+        } else if shred.is_merkle()
+            && !bank
+                .map(|bank| should_use_merkle_shreds(shred.slot(), &bank))
+                .unwrap_or(false)
+        {
+            inc_new_counter_debug!("streamer-recv_window-merkle_shreds_disabled", 1);
+            false
         } else if shred.index() >= MAX_DATA_SHREDS_PER_SLOT as u32 {
             inc_new_counter_warn!("streamer-recv_window-shred_index_overrun", 1);
             false
@@ -347,6 +354,7 @@ pub struct WindowService {
     t_insert: JoinHandle<()>,
     t_check_duplicate: JoinHandle<()>,
     repair_service: RepairService,
+    outstanding_requests: Arc<RwLock<OutstandingRepairs>>,
 }
 
 impl WindowService {
@@ -406,7 +414,7 @@ impl WindowService {
             insert_receiver,
             duplicate_sender,
             completed_data_sets_sender,
-            outstanding_requests,
+            outstanding_requests.clone(),
         );
 
         let t_window = Self::start_recv_window_thread(
@@ -426,9 +434,14 @@ impl WindowService {
             t_insert,
             t_check_duplicate,
             repair_service,
+            outstanding_requests,
         }
     }
 
+    pub fn outstanding_repairs(&self) -> &Arc<RwLock<OutstandingRepairs>> {
+        &self.outstanding_requests
+    }
+
     fn start_check_duplicate_thread(
         cluster_info: Arc<ClusterInfo>,
         exit: Arc<AtomicBool>,
@@ -700,6 +713,38 @@ mod test {
             1
         ));
 
+        // Merkle shreds are only accepted from the epoch after the feature activation
+        let slot = MINIMUM_SLOTS_PER_EPOCH as u64;
+        let merkle_shredder = |slot| {
+            Shredder::new(slot, slot - 1, 0, 0)
+                .unwrap()
+                .with_merkle_variant(true)
+        };
+        let (merkle_shreds, _, _) =
+            merkle_shredder(slot).entries_to_shreds(&leader_keypair, &[Entry::default()], true, 0);
+        assert!(should_retransmit_and_persist(
+            &merkle_shreds[0],
+            Some(bank.clone()),
+            &cache,
+            &me_id,
+            0,
+            0
+        ));
+        let (merkle_shreds, _, _) = merkle_shredder(slot - 1).entries_to_shreds(
+            &leader_keypair,
+            &[Entry::default()],
+            true,
+            0,
+        );
+        assert!(!should_retransmit_and_persist(
+            &merkle_shreds[0],
+            Some(bank.clone()),
+            &cache,
+            &me_id,
+            0,
+            0
+        ));
+
         // If it's a coding shred, test that slot >= root
         let (common, coding) = Shredder::new_coding_shred_header(5, 5, 5, 6, 6, 0);
         let mut coding_shred =
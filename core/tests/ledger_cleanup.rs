@@ -246,7 +246,7 @@ mod tests {
         let cleaner = LedgerCleanupService::new(
             receiver,
             blockstore.clone(),
-            max_ledger_shreds,
+            Arc::new(AtomicU64::new(max_ledger_shreds)),
             &exit,
             compaction_interval,
             None,
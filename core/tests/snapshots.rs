@@ -65,7 +65,7 @@ mod tests {
         fs,
         path::PathBuf,
         sync::{
-            atomic::{AtomicBool, Ordering},
+            atomic::{AtomicBool, AtomicUsize, Ordering},
             mpsc::channel,
             Arc,
         },
@@ -452,7 +452,7 @@ mod tests {
             None,
             &exit,
             &cluster_info,
-            DEFAULT_MAX_SNAPSHOTS_TO_RETAIN,
+            Arc::new(AtomicUsize::new(DEFAULT_MAX_SNAPSHOTS_TO_RETAIN)),
         );
 
         let thread_pool = accounts_db::make_min_priority_thread_pool();
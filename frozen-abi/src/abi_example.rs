@@ -435,6 +435,12 @@ impl AbiExample for SocketAddr {
     }
 }
 
+impl AbiExample for IpAddr {
+    fn example() -> Self {
+        IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))
+    }
+}
+
 // This is a control flow indirection needed for digesting all variants of an enum
 pub trait AbiEnumVisitor: Serialize {
     fn visit_for_abi(&self, digester: &mut AbiDigester) -> DigestResult;
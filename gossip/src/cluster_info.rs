@@ -17,14 +17,17 @@ use {
         cluster_info_metrics::{
             submit_gossip_stats, Counter, GossipStats, ScopedTimer, TimedGuard,
         },
+        compact_contact_info::CompactContactInfo,
         contact_info::ContactInfo,
         crds::{Crds, Cursor},
+        crds_data_bandwidth::{self, CrdsDataBandwidth, CrdsDataTraffic},
         crds_gossip::CrdsGossip,
         crds_gossip_error::CrdsGossipError,
         crds_gossip_pull::{CrdsFilter, ProcessPullStats, CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS},
         crds_value::{
             self, CrdsData, CrdsValue, CrdsValueLabel, EpochSlotsIndex, LowestSlot, NodeInstance,
-            SnapshotHash, Version, Vote, MAX_WALLCLOCK,
+            RestartIntent, RestartLastVotedForkSlots, RestartSlotHash, SnapshotHash, Version, Vote,
+            MAX_WALLCLOCK,
         },
         data_budget::DataBudget,
         epoch_slots::EpochSlots,
@@ -73,7 +76,7 @@ use {
         fmt::Debug,
         fs::{self, File},
         io::BufReader,
-        iter::repeat,
+        iter::{once, repeat},
         net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, UdpSocket},
         ops::{Deref, Div},
         path::{Path, PathBuf},
@@ -137,6 +140,7 @@ pub enum ClusterInfoError {
     NoLeader,
     BadContactInfo,
     BadGossipAddress,
+    RestartIntentDisabled,
 }
 
 pub struct ClusterInfo {
@@ -147,6 +151,7 @@ pub struct ClusterInfo {
     /// Network entrypoints
     entrypoints: RwLock<Vec<ContactInfo>>,
     outbound_budget: DataBudget,
+    crds_data_bandwidth: CrdsDataBandwidth,
     my_contact_info: RwLock<ContactInfo>,
     ping_cache: Mutex<PingCache>,
     stats: GossipStats,
@@ -154,6 +159,11 @@ pub struct ClusterInfo {
     local_message_pending_push_queue: Mutex<Vec<CrdsValue>>,
     contact_debug_interval: u64, // milliseconds, 0 = disabled
     contact_save_interval: u64,  // milliseconds, 0 = disabled
+    // Nodes which predate CompactContactInfo fail to deserialize the packets
+    // carrying it, so it is only pushed once the cluster has upgraded.
+    push_compact_contact_info: bool,
+    // Likewise for RestartIntent.
+    push_restart_intent_enabled: bool,
     instance: RwLock<NodeInstance>,
     contact_info_path: PathBuf,
 }
@@ -256,7 +266,7 @@ pub fn make_accounts_hashes_message(
 pub(crate) type Ping = ping_pong::Ping<[u8; GOSSIP_PING_TOKEN_SIZE]>;
 
 // TODO These messages should go through the gpu pipeline for spam filtering
-#[frozen_abi(digest = "GANv3KVkTYF84kmg1bAuWEZd9MaiYzPquuu13hup3379")]
+#[frozen_abi(digest = "FaDoBRosU1AkWjhwBxATGSTCpJKZy38WuFvs1pkcpTmX")]
 #[derive(Serialize, Deserialize, Debug, AbiEnumVisitor, AbiExample)]
 #[allow(clippy::large_enum_variant)]
 pub(crate) enum Protocol {
@@ -367,7 +377,7 @@ impl Sanitize for Protocol {
 fn retain_staked(values: &mut Vec<CrdsValue>, stakes: &HashMap<Pubkey, u64>) {
     values.retain(|value| {
         match value.data {
-            CrdsData::ContactInfo(_) => true,
+            CrdsData::ContactInfo(_) | CrdsData::CompactContactInfo(_) => true,
             // May Impact new validators starting up without any stake yet.
             CrdsData::Vote(_, _) => true,
             // Unstaked nodes can still help repair.
@@ -381,7 +391,10 @@ fn retain_staked(values: &mut Vec<CrdsValue>, stakes: &HashMap<Pubkey, u64>) {
             CrdsData::LowestSlot(_, _)
             | CrdsData::AccountsHashes(_)
             | CrdsData::LegacyVersion(_)
-            | CrdsData::DuplicateShred(_, _) => {
+            | CrdsData::DuplicateShred(_, _)
+            | CrdsData::RestartLastVotedForkSlots(_)
+            | CrdsData::RestartIntent(_)
+            | CrdsData::RestartSlotHash(_) => {
                 let stake = stakes.get(&value.pubkey()).copied();
                 stake.unwrap_or_default() >= MIN_STAKE_FOR_GOSSIP
             }
@@ -402,6 +415,7 @@ impl ClusterInfo {
             keypair: RwLock::new(keypair),
             entrypoints: RwLock::new(vec![]),
             outbound_budget: DataBudget::default(),
+            crds_data_bandwidth: CrdsDataBandwidth::default(),
             my_contact_info: RwLock::new(contact_info),
             ping_cache: Mutex::new(PingCache::new(
                 GOSSIP_PING_CACHE_TTL,
@@ -411,6 +425,8 @@ impl ClusterInfo {
             socket: UdpSocket::bind("0.0.0.0:0").unwrap(),
             local_message_pending_push_queue: Mutex::default(),
             contact_debug_interval: DEFAULT_CONTACT_DEBUG_INTERVAL_MILLIS,
+            push_compact_contact_info: false,
+            push_restart_intent_enabled: false,
             instance: RwLock::new(NodeInstance::new(&mut thread_rng(), id, timestamp())),
             contact_info_path: PathBuf::default(),
             contact_save_interval: 0, // disabled
@@ -430,6 +446,7 @@ impl ClusterInfo {
             keypair: RwLock::new(self.keypair.read().unwrap().clone()),
             entrypoints: RwLock::new(self.entrypoints.read().unwrap().clone()),
             outbound_budget: self.outbound_budget.clone_non_atomic(),
+            crds_data_bandwidth: CrdsDataBandwidth::default(),
             my_contact_info: RwLock::new(my_contact_info),
             ping_cache: Mutex::new(self.ping_cache.lock().unwrap().mock_clone()),
             stats: GossipStats::default(),
@@ -441,6 +458,8 @@ impl ClusterInfo {
                     .clone(),
             ),
             contact_debug_interval: self.contact_debug_interval,
+            push_compact_contact_info: self.push_compact_contact_info,
+            push_restart_intent_enabled: self.push_restart_intent_enabled,
             instance: RwLock::new(NodeInstance::new(&mut thread_rng(), *new_id, timestamp())),
             contact_info_path: PathBuf::default(),
             contact_save_interval: 0, // disabled
@@ -451,6 +470,38 @@ impl ClusterInfo {
         self.contact_debug_interval = new;
     }
 
+    /// Enables pushing the CompactContactInfo alongside the legacy
+    /// ContactInfo. It is always received, regardless.
+    pub fn set_push_compact_contact_info(&mut self, enable: bool) {
+        self.push_compact_contact_info = enable;
+    }
+
+    /// Enables announcing restarts with `push_restart_intent`. The restart
+    /// intents of other nodes are always received, regardless.
+    pub fn set_push_restart_intent(&mut self, enable: bool) {
+        self.push_restart_intent_enabled = enable;
+    }
+
+    /// Sets the outbound budgets, in bytes per second, of the CrdsData kinds
+    /// by their names.
+    pub fn set_crds_data_budgets(
+        &mut self,
+        budgets: &HashMap<String, u64>,
+    ) -> Result<(), crds_data_bandwidth::Error> {
+        self.crds_data_bandwidth = CrdsDataBandwidth::new(budgets)?;
+        Ok(())
+    }
+
+    /// Returns the gossip traffic of each CrdsData kind since start, along
+    /// with its outbound budget in bytes per second, if any.
+    pub fn crds_data_traffic(&self) -> Vec<(&'static str, CrdsDataTraffic, Option<u64>)> {
+        self.crds_data_bandwidth
+            .traffic()
+            .into_iter()
+            .map(|(name, traffic)| (name, traffic, self.crds_data_bandwidth.budget(name)))
+            .collect()
+    }
+
     fn push_self(
         &self,
         stakes: &HashMap<Pubkey, u64>,
@@ -458,13 +509,24 @@ impl ClusterInfo {
     ) {
         let now = timestamp();
         self.my_contact_info.write().unwrap().wallclock = now;
-        let entries: Vec<_> = vec![
-            CrdsData::ContactInfo(self.my_contact_info()),
-            CrdsData::NodeInstance(self.instance.read().unwrap().with_wallclock(now)),
-        ]
-        .into_iter()
-        .map(|v| CrdsValue::new_signed(v, &self.keypair()))
-        .collect();
+        let my_contact_info = self.my_contact_info();
+        // The legacy contact-info is pushed until all nodes are able to read
+        // the compact one.
+        let compact_contact_info = if self.push_compact_contact_info {
+            Some(CrdsData::CompactContactInfo(CompactContactInfo::from(
+                &my_contact_info,
+            )))
+        } else {
+            None
+        };
+        let entries: Vec<_> = compact_contact_info
+            .into_iter()
+            .chain(vec![
+                CrdsData::ContactInfo(my_contact_info),
+                CrdsData::NodeInstance(self.instance.read().unwrap().with_wallclock(now)),
+            ])
+            .map(|v| CrdsValue::new_signed(v, &self.keypair()))
+            .collect();
         self.local_message_pending_push_queue
             .lock()
             .unwrap()
@@ -632,10 +694,8 @@ impl ClusterInfo {
     where
         F: FnOnce(&ContactInfo) -> Y,
     {
-        let label = CrdsValueLabel::ContactInfo(*id);
         let gossip = self.gossip.read().unwrap();
-        let entry = gossip.crds.get(&label)?;
-        Some(map(entry.value.contact_info()?))
+        Some(map(gossip.crds.get_contact_info(*id)?))
     }
 
     pub fn lookup_contact_info_by_gossip_addr(
@@ -1055,6 +1115,12 @@ impl ClusterInfo {
         }
     }
 
+    /// Returns the address of the ephemeral socket `send_transaction` sends
+    /// from, e.g. the votes of this node
+    pub fn send_transaction_addr(&self) -> SocketAddr {
+        self.socket.local_addr().unwrap()
+    }
+
     pub fn send_transaction(
         &self,
         transaction: &Transaction,
@@ -1140,6 +1206,114 @@ impl ClusterInfo {
             .collect()
     }
 
+    /// Announces the slots of the fork this node last voted on, for a
+    /// coordinated restart of the cluster.
+    pub fn push_restart_last_voted_fork_slots(&self, fork: &[Slot], last_voted_hash: Hash) {
+        let now = timestamp();
+        let fork = match RestartLastVotedForkSlots::new(
+            self.id(),
+            now,
+            fork,
+            last_voted_hash,
+            self.my_shred_version(),
+        ) {
+            Some(fork) => fork,
+            None => return,
+        };
+        let entry =
+            CrdsValue::new_signed(CrdsData::RestartLastVotedForkSlots(fork), &self.keypair());
+        if let Err(err) = self.gossip.write().unwrap().crds.insert(entry, now) {
+            error!("push_restart_last_voted_fork_slots failed: {:?}", err);
+        }
+    }
+
+    /// Returns the last voted forks inserted since the given cursor.
+    /// Excludes the forks announced under a different shred version.
+    pub fn get_restart_last_voted_fork_slots(
+        &self,
+        cursor: &mut Cursor,
+    ) -> Vec<RestartLastVotedForkSlots> {
+        let self_shred_version = self.my_shred_version();
+        let gossip = self.gossip.read().unwrap();
+        gossip
+            .crds
+            .get_entries(cursor)
+            .filter_map(|entry| entry.value.restart_last_voted_fork_slots())
+            .filter(|fork| fork.shred_version == self_shred_version)
+            .cloned()
+            .collect()
+    }
+
+    /// Announces the bank hash of the slot this node picked to restart the
+    /// cluster from.
+    pub fn push_restart_slot_hash(&self, slot: Slot, hash: Hash) {
+        let now = timestamp();
+        let slot_hash = RestartSlotHash {
+            from: self.id(),
+            wallclock: now,
+            slot,
+            hash,
+            shred_version: self.my_shred_version(),
+        };
+        let entry = CrdsValue::new_signed(CrdsData::RestartSlotHash(slot_hash), &self.keypair());
+        if let Err(err) = self.gossip.write().unwrap().crds.insert(entry, now) {
+            error!("push_restart_slot_hash failed: {:?}", err);
+        }
+    }
+
+    /// Returns the restart slot hashes inserted since the given cursor.
+    /// Excludes the hashes announced under a different shred version.
+    pub fn get_restart_slot_hashes(&self, cursor: &mut Cursor) -> Vec<RestartSlotHash> {
+        let self_shred_version = self.my_shred_version();
+        let gossip = self.gossip.read().unwrap();
+        gossip
+            .crds
+            .get_entries(cursor)
+            .filter_map(|entry| entry.value.restart_slot_hash())
+            .filter(|slot_hash| slot_hash.shred_version == self_shred_version)
+            .cloned()
+            .collect()
+    }
+
+    /// Announces that this node will restart at `start`, in milliseconds
+    /// since the UNIX epoch, for about `expected_duration_secs`. A zero
+    /// expected duration cancels the previous announcement.
+    pub fn push_restart_intent(
+        &self,
+        start: u64,
+        expected_duration_secs: u64,
+        target_version: Option<String>,
+    ) -> Result<(), ClusterInfoError> {
+        if !self.push_restart_intent_enabled {
+            return Err(ClusterInfoError::RestartIntentDisabled);
+        }
+        let now = timestamp();
+        let intent = RestartIntent {
+            from: self.id(),
+            wallclock: now,
+            start,
+            expected_duration_secs,
+            target_version,
+        };
+        let entry = CrdsValue::new_signed(CrdsData::RestartIntent(intent), &self.keypair());
+        self.local_message_pending_push_queue
+            .lock()
+            .unwrap()
+            .push(entry);
+        Ok(())
+    }
+
+    /// Returns the restart announced by the node, unless it was cancelled.
+    pub fn get_restart_intent(&self, pubkey: &Pubkey) -> Option<RestartIntent> {
+        let gossip = self.gossip.read().unwrap();
+        let entry = gossip.crds.get(&CrdsValueLabel::RestartIntent(*pubkey))?;
+        entry
+            .value
+            .restart_intent()
+            .filter(|intent| !intent.is_cancelled())
+            .cloned()
+    }
+
     pub fn get_node_version(&self, pubkey: &Pubkey) -> Option<solana_version::Version> {
         let gossip = self.gossip.read().unwrap();
         let version = gossip.crds.get(&CrdsValueLabel::Version(*pubkey));
@@ -1478,6 +1652,10 @@ impl ClusterInfo {
                 !data.is_empty()
             })
         }
+        push_messages.retain(|_, data| {
+            data.retain(|value| self.crds_data_bandwidth.take_budget(value));
+            !data.is_empty()
+        });
         let push_messages: Vec<_> = {
             let gossip =
                 self.time_gossip_read_lock("push_req_lookup", &self.stats.new_push_requests2);
@@ -1489,6 +1667,8 @@ impl ClusterInfo {
                 })
                 .collect()
         };
+        self.crds_data_bandwidth
+            .record_outbound(push_messages.iter().flat_map(|(_, messages)| messages));
         let messages: Vec<_> = push_messages
             .into_iter()
             .flat_map(|(peer, msgs)| {
@@ -1956,7 +2136,7 @@ impl ClusterInfo {
                     score
                 };
                 let score = match response.data {
-                    CrdsData::ContactInfo(_) => 2 * score,
+                    CrdsData::ContactInfo(_) | CrdsData::CompactContactInfo(_) => 2 * score,
                     _ => score,
                 };
                 ((addr, response), score)
@@ -1969,17 +2149,25 @@ impl ClusterInfo {
         let shuffle = WeightedShuffle::new(&mut rng, &scores).unwrap();
         let mut total_bytes = 0;
         let mut sent = 0;
-        for (addr, response) in shuffle.map(|i| &responses[i]) {
-            let response = vec![response.clone()];
+        for (addr, value) in shuffle.map(|i| &responses[i]) {
+            if !self.crds_data_bandwidth.take_budget(value) {
+                continue;
+            }
+            let response = vec![value.clone()];
             let response = Protocol::PullResponse(self_id, response);
             match Packet::from_data(Some(addr), response) {
-                Err(err) => error!("failed to write pull-response packet: {:?}", err),
+                Err(err) => {
+                    error!("failed to write pull-response packet: {:?}", err);
+                    self.crds_data_bandwidth.refund_budget(value);
+                }
                 Ok(packet) => {
                     if self.outbound_budget.take(packet.meta.size) {
+                        self.crds_data_bandwidth.record_outbound(once(value));
                         total_bytes += packet.meta.size;
                         packets.packets.push(packet);
                         sent += 1;
                     } else {
+                        self.crds_data_bandwidth.refund_budget(value);
                         inc_new_counter_info!("gossip_pull_request-no_budget", 1);
                         break;
                     }
@@ -2011,6 +2199,8 @@ impl ClusterInfo {
         if responses.is_empty() {
             return;
         }
+        self.crds_data_bandwidth
+            .record_inbound(responses.iter().flat_map(|(_, data)| data));
         fn extend<K, V>(hash_map: &mut HashMap<K, Vec<V>>, (key, mut value): (K, Vec<V>))
         where
             K: Eq + std::hash::Hash,
@@ -2192,6 +2382,8 @@ impl ClusterInfo {
         self.stats
             .push_message_value_count
             .add_relaxed(num_crds_values);
+        self.crds_data_bandwidth
+            .record_inbound(messages.iter().flat_map(|(_, data)| data));
         // Origins' pubkeys of upserted crds values.
         let origins: HashSet<_> = {
             let mut gossip =
@@ -2512,6 +2704,7 @@ impl ClusterInfo {
         )?;
         if last_print.elapsed() > SUBMIT_GOSSIP_STATS_INTERVAL {
             submit_gossip_stats(&self.stats, &self.gossip, &stakes);
+            self.crds_data_bandwidth.submit_metrics();
             *last_print = Instant::now();
         }
         Ok(())
@@ -2700,6 +2893,7 @@ pub struct Sockets {
     pub repair: UdpSocket,
     pub retransmit_sockets: Vec<UdpSocket>,
     pub serve_repair: UdpSocket,
+    pub ancestor_hashes_requests: UdpSocket,
 }
 
 #[derive(Debug)]
@@ -2732,6 +2926,7 @@ impl Node {
         let broadcast = vec![UdpSocket::bind("0.0.0.0:0").unwrap()];
         let retransmit_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
         let serve_repair = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let ancestor_hashes_requests = UdpSocket::bind("0.0.0.0:0").unwrap();
         let unused = UdpSocket::bind("0.0.0.0:0").unwrap();
         let info = ContactInfo {
             id: *pubkey,
@@ -2761,6 +2956,7 @@ impl Node {
                 repair,
                 retransmit_sockets: vec![retransmit_socket],
                 serve_repair,
+                ancestor_hashes_requests,
             },
         }
     }
@@ -2801,6 +2997,7 @@ impl Node {
         let (repair_port, repair) = Self::bind(bind_ip_addr, port_range);
         let (serve_repair_port, serve_repair) = Self::bind(bind_ip_addr, port_range);
         let (_, broadcast) = Self::bind(bind_ip_addr, port_range);
+        let (_, ancestor_hashes_requests) = Self::bind(bind_ip_addr, port_range);
 
         let rpc_port = find_available_port_in_range(bind_ip_addr, port_range).unwrap();
         let rpc_pubsub_port = find_available_port_in_range(bind_ip_addr, port_range).unwrap();
@@ -2835,6 +3032,7 @@ impl Node {
                 repair,
                 retransmit_sockets: vec![retransmit_socket],
                 serve_repair,
+                ancestor_hashes_requests,
             },
         }
     }
@@ -2869,6 +3067,8 @@ impl Node {
         let (_, broadcast) =
             multi_bind_in_range(bind_ip_addr, port_range, 4).expect("broadcast multi_bind");
 
+        let (_, ancestor_hashes_requests) = Self::bind(bind_ip_addr, port_range);
+
         let info = ContactInfo {
             id: *pubkey,
             gossip: SocketAddr::new(gossip_addr.ip(), gossip_port),
@@ -2898,6 +3098,7 @@ impl Node {
                 repair,
                 retransmit_sockets,
                 serve_repair,
+                ancestor_hashes_requests,
                 ip_echo: Some(ip_echo),
             },
         }
@@ -2931,7 +3132,7 @@ fn filter_on_shred_version(
             // Retain values with the same shred-vesion, or those which are
             // contact-info so that shred-versions can be updated.
             values.retain(|value| match &value.data {
-                CrdsData::ContactInfo(_) => true,
+                CrdsData::ContactInfo(_) | CrdsData::CompactContactInfo(_) => true,
                 _ => crds.get_shred_version(&value.pubkey()) == Some(self_shred_version),
             })
         } else {
@@ -2939,6 +3140,7 @@ fn filter_on_shred_version(
             // shred-version changes.
             values.retain(|value| match &value.data {
                 CrdsData::ContactInfo(node) => node.id == *from,
+                CrdsData::CompactContactInfo(node) => node.pubkey() == from,
                 _ => false,
             })
         }
@@ -3712,6 +3914,199 @@ mod tests {
         assert_eq!(slots[1].from, node_pubkey);
     }
 
+    #[test]
+    fn test_compact_contact_info_peers() {
+        let keypair = Arc::new(Keypair::new());
+        let mut contact_info = ContactInfo::new_localhost(&keypair.pubkey(), 0);
+        contact_info.shred_version = 42;
+        let cluster_info = ClusterInfo::new(contact_info, keypair);
+        // A peer which only advertises its compact contact-info, on IPv6.
+        let mut node = ContactInfo::new_with_socketaddr(&socketaddr!("[::1]:8000"));
+        node.shred_version = 42;
+        node.wallclock = timestamp();
+        let compact_node = CompactContactInfo::from(&node);
+        let value = CrdsValue::new_unsigned(CrdsData::CompactContactInfo(compact_node));
+        {
+            let mut gossip = cluster_info.gossip.write().unwrap();
+            assert!(gossip.crds.insert(value, timestamp()).is_ok());
+        }
+        assert_eq!(cluster_info.tvu_peers(), vec![node.clone()]);
+        assert_eq!(cluster_info.repair_peers(0), vec![node.clone()]);
+        assert_eq!(cluster_info.tpu_peers(), vec![node.clone()]);
+        assert_eq!(cluster_info.all_rpc_peers(), vec![node.clone()]);
+        assert_eq!(
+            cluster_info.lookup_contact_info(&node.id, |node| node.tpu),
+            Some(node.tpu)
+        );
+
+        // The legacy contact-info takes precedence once the peer advertises both.
+        let mut legacy_node = node.clone();
+        legacy_node.tpu = socketaddr!("[::1]:9000");
+        legacy_node.wallclock += 1;
+        let value = CrdsValue::new_unsigned(CrdsData::ContactInfo(legacy_node.clone()));
+        {
+            let mut gossip = cluster_info.gossip.write().unwrap();
+            assert!(gossip.crds.insert(value, timestamp()).is_ok());
+        }
+        assert_eq!(cluster_info.tpu_peers(), vec![legacy_node]);
+    }
+
+    #[test]
+    fn test_push_compact_contact_info() {
+        let keypair = Arc::new(Keypair::new());
+        let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), 0);
+        let mut cluster_info = ClusterInfo::new(contact_info, keypair.clone());
+        let label = CrdsValueLabel::CompactContactInfo(keypair.pubkey());
+        let has_compact_contact_info = |cluster_info: &ClusterInfo| {
+            let gossip = cluster_info.gossip.read().unwrap();
+            gossip.crds.get(&label).is_some()
+        };
+        cluster_info.flush_push_queue();
+        assert!(!has_compact_contact_info(&cluster_info));
+
+        cluster_info.set_push_compact_contact_info(true);
+        cluster_info.push_self(&HashMap::default(), None);
+        cluster_info.flush_push_queue();
+        assert!(has_compact_contact_info(&cluster_info));
+    }
+
+    #[test]
+    fn test_push_restart_last_voted_fork_slots() {
+        let keypair = Arc::new(Keypair::new());
+        let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), 0);
+        let cluster_info = ClusterInfo::new(contact_info, keypair.clone());
+        let mut cursor = Cursor::default();
+        assert!(cluster_info
+            .get_restart_last_voted_fork_slots(&mut cursor)
+            .is_empty());
+
+        let fork: Vec<Slot> = (0..crds_value::MAX_RESTART_LAST_VOTED_FORK_SLOTS).collect();
+        let hash = Hash::new_unique();
+        cluster_info.push_restart_last_voted_fork_slots(&fork, hash);
+        let forks = cluster_info.get_restart_last_voted_fork_slots(&mut cursor);
+        assert_eq!(forks.len(), 1);
+        assert_eq!(forks[0].from, keypair.pubkey());
+        assert_eq!(forks[0].last_voted_hash, hash);
+        assert_eq!(forks[0].to_slots(), fork);
+        assert!(cluster_info
+            .get_restart_last_voted_fork_slots(&mut cursor)
+            .is_empty());
+
+        // The largest fork fits in a single packet.
+        let value = CrdsValue::new_signed(
+            CrdsData::RestartLastVotedForkSlots(forks[0].clone()),
+            &keypair,
+        );
+        let push_message = Protocol::PushMessage(keypair.pubkey(), vec![value.clone()]);
+        assert!(serialized_size(&push_message).unwrap() < PACKET_DATA_SIZE as u64);
+        let pull_response = Protocol::PullResponse(keypair.pubkey(), vec![value]);
+        assert!(serialized_size(&pull_response).unwrap() < PACKET_DATA_SIZE as u64);
+
+        // Forks announced under a different shred version are excluded.
+        let other_keypair = Keypair::new();
+        let other_fork = RestartLastVotedForkSlots::new(
+            other_keypair.pubkey(),
+            timestamp(),
+            &fork,
+            hash,
+            cluster_info.my_shred_version() + 1,
+        )
+        .unwrap();
+        let entry = CrdsValue::new_signed(
+            CrdsData::RestartLastVotedForkSlots(other_fork),
+            &other_keypair,
+        );
+        assert!(cluster_info
+            .gossip
+            .write()
+            .unwrap()
+            .crds
+            .insert(entry, timestamp())
+            .is_ok());
+        assert!(cluster_info
+            .get_restart_last_voted_fork_slots(&mut cursor)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_push_restart_slot_hash() {
+        let keypair = Arc::new(Keypair::new());
+        let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), 0);
+        let cluster_info = ClusterInfo::new(contact_info, keypair.clone());
+        let mut cursor = Cursor::default();
+        assert!(cluster_info.get_restart_slot_hashes(&mut cursor).is_empty());
+
+        let hash = Hash::new_unique();
+        cluster_info.push_restart_slot_hash(42, hash);
+        let slot_hashes = cluster_info.get_restart_slot_hashes(&mut cursor);
+        assert_eq!(slot_hashes.len(), 1);
+        assert_eq!(slot_hashes[0].from, keypair.pubkey());
+        assert_eq!(slot_hashes[0].slot, 42);
+        assert_eq!(slot_hashes[0].hash, hash);
+        assert!(cluster_info.get_restart_slot_hashes(&mut cursor).is_empty());
+
+        // Hashes announced under a different shred version are excluded.
+        let other_keypair = Keypair::new();
+        let other_slot_hash = RestartSlotHash {
+            from: other_keypair.pubkey(),
+            wallclock: timestamp(),
+            slot: 42,
+            hash,
+            shred_version: cluster_info.my_shred_version() + 1,
+        };
+        let entry =
+            CrdsValue::new_signed(CrdsData::RestartSlotHash(other_slot_hash), &other_keypair);
+        assert!(cluster_info
+            .gossip
+            .write()
+            .unwrap()
+            .crds
+            .insert(entry, timestamp())
+            .is_ok());
+        assert!(cluster_info.get_restart_slot_hashes(&mut cursor).is_empty());
+    }
+
+    #[test]
+    fn test_push_restart_intent() {
+        let keypair = Arc::new(Keypair::new());
+        let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), 0);
+        let mut cluster_info = ClusterInfo::new(contact_info, keypair.clone());
+        assert_eq!(cluster_info.get_restart_intent(&keypair.pubkey()), None);
+
+        let start = timestamp() + 60_000;
+        let version = "1.8.1".to_string();
+        assert_eq!(
+            cluster_info.push_restart_intent(start, 300, Some(version.clone())),
+            Err(ClusterInfoError::RestartIntentDisabled)
+        );
+        cluster_info.set_push_restart_intent(true);
+        cluster_info
+            .push_restart_intent(start, 300, Some(version.clone()))
+            .unwrap();
+        cluster_info.flush_push_queue();
+        let intent = cluster_info.get_restart_intent(&keypair.pubkey()).unwrap();
+        assert_eq!(intent.start, start);
+        assert_eq!(intent.end(), start + 300_000);
+        assert_eq!(intent.target_version, Some(version));
+        let value = CrdsValue::new_signed(CrdsData::RestartIntent(intent.clone()), &keypair);
+        assert!(value.verify());
+        assert_eq!(value.sanitize(), Ok(()));
+
+        let mut long_intent = intent;
+        long_intent.target_version =
+            Some("1".repeat(crds_value::MAX_RESTART_INTENT_VERSION_LEN + 1));
+        assert!(long_intent.sanitize().is_err());
+        long_intent.target_version = None;
+        long_intent.expected_duration_secs = crds_value::MAX_RESTART_INTENT_DURATION_SECS + 1;
+        assert!(long_intent.sanitize().is_err());
+
+        // A zero expected duration cancels the announcement.
+        sleep(Duration::from_millis(1));
+        cluster_info.push_restart_intent(start, 0, None).unwrap();
+        cluster_info.flush_push_queue();
+        assert_eq!(cluster_info.get_restart_intent(&keypair.pubkey()), None);
+    }
+
     #[test]
     fn test_append_entrypoint_to_pulls() {
         let thread_pool = ThreadPoolBuilder::new().build().unwrap();
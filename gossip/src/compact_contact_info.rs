@@ -0,0 +1,454 @@
+//! Compact contact info of a node, gossiped alongside the legacy `ContactInfo`.
+//!
+//! Instead of a fixed set of socket addresses, the compact contact info holds
+//! a deduplicated list of IP addresses and a list of sockets tagged with the
+//! protocol they serve. Sockets are sorted by port and each stores the offset
+//! of its port from the port of the previous socket, so that a node
+//! advertising all its services on a single IP address costs only a few bytes
+//! per socket. Sockets with unknown tags are retained, so that new services
+//! can be advertised without breaking older nodes.
+
+use {
+    crate::{contact_info::ContactInfo, crds_value::sanitize_wallclock},
+    solana_sdk::{
+        pubkey::Pubkey,
+        sanitize::{Sanitize, SanitizeError},
+        short_vec,
+    },
+    std::{
+        collections::HashSet,
+        net::{IpAddr, SocketAddr},
+    },
+    thiserror::Error,
+};
+
+pub const SOCKET_TAG_GOSSIP: u8 = 0;
+pub const SOCKET_TAG_TVU: u8 = 1;
+pub const SOCKET_TAG_TVU_FORWARDS: u8 = 2;
+pub const SOCKET_TAG_REPAIR: u8 = 3;
+pub const SOCKET_TAG_TPU: u8 = 4;
+pub const SOCKET_TAG_TPU_FORWARDS: u8 = 5;
+pub const SOCKET_TAG_RPC: u8 = 6;
+pub const SOCKET_TAG_RPC_PUBSUB: u8 = 7;
+pub const SOCKET_TAG_SERVE_REPAIR: u8 = 8;
+
+// Bounds the size of the value, well below what fits in a packet.
+pub const MAX_COMPACT_CONTACT_INFO_ADDRS: usize = 16;
+pub const MAX_COMPACT_CONTACT_INFO_SOCKETS: usize = 32;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum Error {
+    #[error("duplicate IP address: {0}")]
+    DuplicateIpAddr(IpAddr),
+    #[error("duplicate socket tag: {0}")]
+    DuplicateSocketTag(u8),
+    #[error("invalid IP address index: {0}")]
+    InvalidIpAddrIndex(u8),
+    #[error("invalid port offset")]
+    InvalidPortOffset,
+    #[error("too many IP addresses")]
+    TooManyIpAddrs,
+    #[error("too many sockets")]
+    TooManySockets,
+    #[error("unused IP address: {0}")]
+    UnusedIpAddr(IpAddr),
+}
+
+impl From<Error> for SanitizeError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::InvalidIpAddrIndex(_) | Error::TooManyIpAddrs | Error::TooManySockets => {
+                SanitizeError::IndexOutOfBounds
+            }
+            Error::DuplicateIpAddr(_)
+            | Error::DuplicateSocketTag(_)
+            | Error::InvalidPortOffset
+            | Error::UnusedIpAddr(_) => SanitizeError::InvalidValue,
+        }
+    }
+}
+
+/// Structure representing a node on the network
+#[derive(Clone, Debug, Eq, PartialEq, AbiExample, Deserialize, Serialize)]
+pub struct CompactContactInfo {
+    pubkey: Pubkey,
+    wallclock: u64,
+    shred_version: u16,
+    // Unique IP addresses of the sockets.
+    #[serde(with = "short_vec")]
+    addrs: Vec<IpAddr>,
+    // Sockets sorted by port.
+    #[serde(with = "short_vec")]
+    sockets: Vec<SocketEntry>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, AbiExample, Deserialize, Serialize)]
+struct SocketEntry {
+    tag: u8,
+    // Index of the IP address in CompactContactInfo.addrs.
+    index: u8,
+    // Offset of the port from the port of the previous socket.
+    offset: u16,
+}
+
+impl CompactContactInfo {
+    pub fn new(pubkey: Pubkey, wallclock: u64, shred_version: u16) -> Self {
+        Self {
+            pubkey,
+            wallclock,
+            shred_version,
+            addrs: Vec::default(),
+            sockets: Vec::default(),
+        }
+    }
+
+    pub fn pubkey(&self) -> &Pubkey {
+        &self.pubkey
+    }
+
+    pub fn wallclock(&self) -> u64 {
+        self.wallclock
+    }
+
+    pub fn shred_version(&self) -> u16 {
+        self.shred_version
+    }
+
+    pub fn set_wallclock(&mut self, wallclock: u64) {
+        self.wallclock = wallclock;
+    }
+
+    pub fn set_shred_version(&mut self, shred_version: u16) {
+        self.shred_version = shred_version;
+    }
+
+    /// Returns the socket tagged with `tag`, if the node advertises one.
+    pub fn socket(&self, tag: u8) -> Option<SocketAddr> {
+        self.iter_sockets()
+            .find(|(socket_tag, _)| *socket_tag == tag)
+            .map(|(_, socket)| socket)
+    }
+
+    /// Returns the tagged sockets of the node in the order of their ports.
+    pub fn sockets(&self) -> Vec<(u8, SocketAddr)> {
+        self.iter_sockets().collect()
+    }
+
+    /// Adds the socket tagged with `tag`, replacing any socket with the same tag.
+    pub fn set_socket(&mut self, tag: u8, socket: SocketAddr) -> Result<(), Error> {
+        let mut sockets: Vec<_> = self
+            .iter_sockets()
+            .filter(|(socket_tag, _)| *socket_tag != tag)
+            .collect();
+        if sockets.len() >= MAX_COMPACT_CONTACT_INFO_SOCKETS {
+            return Err(Error::TooManySockets);
+        }
+        sockets.push((tag, socket));
+        self.set_sockets(sockets)
+    }
+
+    /// Removes the socket tagged with `tag`, if any.
+    pub fn remove_socket(&mut self, tag: u8) {
+        let sockets: Vec<_> = self
+            .iter_sockets()
+            .filter(|(socket_tag, _)| *socket_tag != tag)
+            .collect();
+        // Removing a socket cannot exceed the limits.
+        self.set_sockets(sockets).unwrap();
+    }
+
+    fn iter_sockets(&self) -> impl Iterator<Item = (u8, SocketAddr)> + '_ {
+        self.sockets.iter().scan(0u16, move |port, entry| {
+            *port = port.checked_add(entry.offset)?;
+            let addr = self.addrs.get(usize::from(entry.index))?;
+            Some((entry.tag, SocketAddr::new(*addr, *port)))
+        })
+    }
+
+    // Rebuilds the IP addresses and socket entries from the given sockets.
+    fn set_sockets(&mut self, mut sockets: Vec<(u8, SocketAddr)>) -> Result<(), Error> {
+        sockets.sort_by_key(|(tag, socket)| (socket.port(), *tag));
+        let mut addrs = Vec::<IpAddr>::new();
+        let mut entries = Vec::with_capacity(sockets.len());
+        let mut port = 0u16;
+        for (tag, socket) in sockets {
+            let index = match addrs.iter().position(|addr| *addr == socket.ip()) {
+                Some(index) => index,
+                None if addrs.len() >= MAX_COMPACT_CONTACT_INFO_ADDRS => {
+                    return Err(Error::TooManyIpAddrs)
+                }
+                None => {
+                    addrs.push(socket.ip());
+                    addrs.len() - 1
+                }
+            };
+            entries.push(SocketEntry {
+                tag,
+                index: index as u8,
+                offset: socket.port() - port,
+            });
+            port = socket.port();
+        }
+        self.addrs = addrs;
+        self.sockets = entries;
+        Ok(())
+    }
+
+    fn verify_sockets(&self) -> Result<(), Error> {
+        if self.addrs.len() > MAX_COMPACT_CONTACT_INFO_ADDRS {
+            return Err(Error::TooManyIpAddrs);
+        }
+        if self.sockets.len() > MAX_COMPACT_CONTACT_INFO_SOCKETS {
+            return Err(Error::TooManySockets);
+        }
+        let mut addrs = HashSet::with_capacity(self.addrs.len());
+        if let Some(addr) = self.addrs.iter().find(|addr| !addrs.insert(*addr)) {
+            return Err(Error::DuplicateIpAddr(*addr));
+        }
+        let mut tags = HashSet::with_capacity(self.sockets.len());
+        let mut used = vec![false; self.addrs.len()];
+        let mut port = 0u16;
+        for entry in &self.sockets {
+            if !tags.insert(entry.tag) {
+                return Err(Error::DuplicateSocketTag(entry.tag));
+            }
+            match used.get_mut(usize::from(entry.index)) {
+                Some(used) => *used = true,
+                None => return Err(Error::InvalidIpAddrIndex(entry.index)),
+            }
+            port = port
+                .checked_add(entry.offset)
+                .ok_or(Error::InvalidPortOffset)?;
+        }
+        match used.iter().position(|used| !used) {
+            Some(index) => Err(Error::UnusedIpAddr(self.addrs[index])),
+            None => Ok(()),
+        }
+    }
+}
+
+macro_rules! socket_getter {
+    ($(#[$doc:meta])* $name:ident, $tag:ident) => {
+        $(#[$doc])*
+        pub fn $name(&self) -> Option<SocketAddr> {
+            self.socket($tag)
+        }
+    };
+}
+
+impl CompactContactInfo {
+    socket_getter!(gossip, SOCKET_TAG_GOSSIP);
+    socket_getter!(tvu, SOCKET_TAG_TVU);
+    socket_getter!(tvu_forwards, SOCKET_TAG_TVU_FORWARDS);
+    socket_getter!(repair, SOCKET_TAG_REPAIR);
+    socket_getter!(tpu, SOCKET_TAG_TPU);
+    socket_getter!(tpu_forwards, SOCKET_TAG_TPU_FORWARDS);
+    socket_getter!(rpc, SOCKET_TAG_RPC);
+    socket_getter!(rpc_pubsub, SOCKET_TAG_RPC_PUBSUB);
+    socket_getter!(serve_repair, SOCKET_TAG_SERVE_REPAIR);
+}
+
+impl Sanitize for CompactContactInfo {
+    fn sanitize(&self) -> Result<(), SanitizeError> {
+        sanitize_wallclock(self.wallclock)?;
+        self.verify_sockets()?;
+        self.pubkey.sanitize()
+    }
+}
+
+impl From<&ContactInfo> for CompactContactInfo {
+    /// Unspecified sockets of the legacy contact info are left out.
+    fn from(node: &ContactInfo) -> Self {
+        let sockets = [
+            (SOCKET_TAG_GOSSIP, node.gossip),
+            (SOCKET_TAG_TVU, node.tvu),
+            (SOCKET_TAG_TVU_FORWARDS, node.tvu_forwards),
+            (SOCKET_TAG_REPAIR, node.repair),
+            (SOCKET_TAG_TPU, node.tpu),
+            (SOCKET_TAG_TPU_FORWARDS, node.tpu_forwards),
+            (SOCKET_TAG_RPC, node.rpc),
+            (SOCKET_TAG_RPC_PUBSUB, node.rpc_pubsub),
+            (SOCKET_TAG_SERVE_REPAIR, node.serve_repair),
+        ];
+        let mut info = Self::new(node.id, node.wallclock, node.shred_version);
+        // The legacy contact info has fewer sockets than the limits, so at
+        // most 9 IP addresses are needed.
+        info.set_sockets(
+            sockets
+                .iter()
+                .filter(|(_, socket)| *socket != SocketAddr::from(([0u8; 4], 0)))
+                .copied()
+                .collect(),
+        )
+        .unwrap();
+        info
+    }
+}
+
+impl From<&CompactContactInfo> for ContactInfo {
+    /// Sockets with tags unknown to the legacy contact info are left out.
+    fn from(node: &CompactContactInfo) -> Self {
+        let mut info = ContactInfo {
+            id: node.pubkey,
+            wallclock: node.wallclock,
+            shred_version: node.shred_version,
+            ..ContactInfo::default()
+        };
+        for (tag, socket) in node.iter_sockets() {
+            let addr = match tag {
+                SOCKET_TAG_GOSSIP => &mut info.gossip,
+                SOCKET_TAG_TVU => &mut info.tvu,
+                SOCKET_TAG_TVU_FORWARDS => &mut info.tvu_forwards,
+                SOCKET_TAG_REPAIR => &mut info.repair,
+                SOCKET_TAG_TPU => &mut info.tpu,
+                SOCKET_TAG_TPU_FORWARDS => &mut info.tpu_forwards,
+                SOCKET_TAG_RPC => &mut info.rpc,
+                SOCKET_TAG_RPC_PUBSUB => &mut info.rpc_pubsub,
+                SOCKET_TAG_SERVE_REPAIR => &mut info.serve_repair,
+                _ => continue,
+            };
+            *addr = socket;
+        }
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::crds_value::MAX_WALLCLOCK,
+        bincode::serialized_size,
+        solana_sdk::timing::timestamp,
+        std::net::{Ipv4Addr, Ipv6Addr},
+    };
+
+    #[test]
+    fn test_compact_contact_info_sockets() {
+        let mut node = CompactContactInfo::new(Pubkey::new_unique(), timestamp(), 42);
+        let ipv4 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let ipv6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        node.set_socket(SOCKET_TAG_TVU, SocketAddr::new(ipv4, 8002))
+            .unwrap();
+        node.set_socket(SOCKET_TAG_GOSSIP, SocketAddr::new(ipv4, 8001))
+            .unwrap();
+        node.set_socket(SOCKET_TAG_TPU, SocketAddr::new(ipv6, 8003))
+            .unwrap();
+        // A tag unknown to this version of the code.
+        node.set_socket(200, SocketAddr::new(ipv6, 8004)).unwrap();
+        assert_eq!(node.sanitize(), Ok(()));
+        assert_eq!(node.addrs, vec![ipv4, ipv6]);
+        assert_eq!(node.gossip(), Some(SocketAddr::new(ipv4, 8001)));
+        assert_eq!(node.tvu(), Some(SocketAddr::new(ipv4, 8002)));
+        assert_eq!(node.tpu(), Some(SocketAddr::new(ipv6, 8003)));
+        assert_eq!(node.socket(200), Some(SocketAddr::new(ipv6, 8004)));
+        assert_eq!(node.rpc(), None);
+        assert_eq!(
+            node.sockets
+                .iter()
+                .map(|entry| entry.offset)
+                .collect::<Vec<_>>(),
+            vec![8001, 1, 1, 1]
+        );
+
+        // Replacing a socket moves it to its new port, and drops unused addresses.
+        node.set_socket(SOCKET_TAG_TPU, SocketAddr::new(ipv4, 8000))
+            .unwrap();
+        node.remove_socket(200);
+        assert_eq!(node.sanitize(), Ok(()));
+        assert_eq!(node.addrs, vec![ipv4]);
+        assert_eq!(
+            node.sockets(),
+            vec![
+                (SOCKET_TAG_TPU, SocketAddr::new(ipv4, 8000)),
+                (SOCKET_TAG_GOSSIP, SocketAddr::new(ipv4, 8001)),
+                (SOCKET_TAG_TVU, SocketAddr::new(ipv4, 8002)),
+            ]
+        );
+
+        let bytes = bincode::serialize(&node).unwrap();
+        let other: CompactContactInfo = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(node, other);
+
+        // Too many addresses.
+        let mut node = CompactContactInfo::new(Pubkey::new_unique(), timestamp(), 42);
+        for i in 0..MAX_COMPACT_CONTACT_INFO_ADDRS {
+            let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, i as u8));
+            node.set_socket(i as u8, SocketAddr::new(addr, 8000))
+                .unwrap();
+        }
+        let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 1, 0));
+        assert_eq!(
+            node.set_socket(100, SocketAddr::new(addr, 8000)),
+            Err(Error::TooManyIpAddrs)
+        );
+        assert_eq!(node.sanitize(), Ok(()));
+    }
+
+    #[test]
+    fn test_compact_contact_info_sanitize() {
+        let ipv4 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let mut node = CompactContactInfo::new(Pubkey::new_unique(), timestamp(), 42);
+        node.set_socket(SOCKET_TAG_GOSSIP, SocketAddr::new(ipv4, 8001))
+            .unwrap();
+        node.set_socket(SOCKET_TAG_TVU, SocketAddr::new(ipv4, 8002))
+            .unwrap();
+        assert_eq!(node.sanitize(), Ok(()));
+
+        let mut other = node.clone();
+        other.wallclock = MAX_WALLCLOCK;
+        assert_eq!(other.sanitize(), Err(SanitizeError::ValueOutOfBounds));
+
+        let mut other = node.clone();
+        other.addrs.push(ipv4);
+        assert_eq!(other.verify_sockets(), Err(Error::DuplicateIpAddr(ipv4)));
+
+        let mut other = node.clone();
+        other.sockets[1].tag = SOCKET_TAG_GOSSIP;
+        assert_eq!(
+            other.verify_sockets(),
+            Err(Error::DuplicateSocketTag(SOCKET_TAG_GOSSIP))
+        );
+
+        let mut other = node.clone();
+        other.sockets[1].index = 1;
+        assert_eq!(other.verify_sockets(), Err(Error::InvalidIpAddrIndex(1)));
+        assert_eq!(other.sanitize(), Err(SanitizeError::IndexOutOfBounds));
+
+        let mut other = node.clone();
+        other.sockets[1].offset = u16::MAX;
+        assert_eq!(other.verify_sockets(), Err(Error::InvalidPortOffset));
+
+        let mut other = node;
+        let ipv6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        other.addrs.push(ipv6);
+        assert_eq!(other.verify_sockets(), Err(Error::UnusedIpAddr(ipv6)));
+        assert_eq!(other.sanitize(), Err(SanitizeError::InvalidValue));
+    }
+
+    #[test]
+    fn test_compact_contact_info_from_legacy() {
+        let mut node = ContactInfo::new_localhost(&Pubkey::new_unique(), timestamp());
+        node.shred_version = 42;
+        node.unused = ContactInfo::default().unused;
+        node.rpc = "[2001:db8::1]:8899".parse().unwrap();
+        let compact = CompactContactInfo::from(&node);
+        assert_eq!(compact.sanitize(), Ok(()));
+        assert_eq!(compact.pubkey(), &node.id);
+        assert_eq!(compact.wallclock(), node.wallclock);
+        assert_eq!(compact.shred_version(), 42);
+        assert_eq!(compact.addrs.len(), 2);
+        assert_eq!(compact.sockets.len(), 9);
+        assert_eq!(compact.gossip(), Some(node.gossip));
+        assert_eq!(compact.rpc(), Some(node.rpc));
+        assert_eq!(compact.serve_repair(), Some(node.serve_repair));
+        assert_eq!(ContactInfo::from(&compact), node);
+        assert!(serialized_size(&compact).unwrap() < serialized_size(&node).unwrap());
+
+        // Unspecified sockets are left out.
+        let node = ContactInfo::new_gossip_entry_point(&"127.0.0.1:8001".parse().unwrap());
+        let compact = CompactContactInfo::from(&node);
+        assert_eq!(compact.sockets(), vec![(SOCKET_TAG_GOSSIP, node.gossip)]);
+        assert_eq!(ContactInfo::from(&compact), node);
+    }
+}
@@ -69,6 +69,17 @@ pub struct Crds {
     purged: VecDeque<(Hash, u64 /*timestamp*/)>,
     // Mapping from nodes' pubkeys to their respective shred-version.
     shred_versions: HashMap<Pubkey, u16>,
+    // Nodes' CompactContactInfo converted to the legacy ContactInfo.
+    compact_nodes: HashMap<Pubkey, ContactInfo>,
+}
+
+/// How a value reached the local node.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum GossipRoute {
+    LocalMessage,
+    PullRequest,
+    PullResponse,
+    PushMessage,
 }
 
 #[derive(PartialEq, Debug)]
@@ -87,6 +98,8 @@ pub struct VersionedCrdsValue {
     pub(crate) local_timestamp: u64,
     /// value hash
     pub(crate) value_hash: Hash,
+    /// how the value was received
+    pub(crate) route: GossipRoute,
 }
 
 #[derive(Clone, Copy, Default)]
@@ -105,13 +118,14 @@ impl Cursor {
 }
 
 impl VersionedCrdsValue {
-    fn new(value: CrdsValue, cursor: Cursor, local_timestamp: u64) -> Self {
+    fn new(value: CrdsValue, cursor: Cursor, local_timestamp: u64, route: GossipRoute) -> Self {
         let value_hash = hash(&serialize(&value).unwrap());
         VersionedCrdsValue {
             ordinal: cursor.ordinal(),
             value,
             local_timestamp,
             value_hash,
+            route,
         }
     }
 }
@@ -129,6 +143,7 @@ impl Default for Crds {
             entries: BTreeMap::default(),
             purged: VecDeque::default(),
             shred_versions: HashMap::default(),
+            compact_nodes: HashMap::default(),
         }
     }
 }
@@ -170,9 +185,23 @@ impl Crds {
     }
 
     pub fn insert(&mut self, value: CrdsValue, now: u64) -> Result<(), CrdsError> {
+        self.insert_with_route(value, now, GossipRoute::LocalMessage)
+    }
+
+    pub(crate) fn insert_with_route(
+        &mut self,
+        value: CrdsValue,
+        now: u64,
+        route: GossipRoute,
+    ) -> Result<(), CrdsError> {
         let label = value.label();
         let pubkey = value.pubkey();
-        let value = VersionedCrdsValue::new(value, self.cursor, now);
+        // The legacy contact-info, if any, determines the node's shred-version.
+        let has_contact_info = matches!(value.data, CrdsData::CompactContactInfo(_))
+            && self
+                .table
+                .contains_key(&CrdsValueLabel::ContactInfo(pubkey));
+        let value = VersionedCrdsValue::new(value, self.cursor, now, route);
         match self.table.entry(label) {
             Entry::Vacant(entry) => {
                 let entry_index = entry.index();
@@ -182,6 +211,12 @@ impl Crds {
                         self.nodes.insert(entry_index);
                         self.shred_versions.insert(pubkey, node.shred_version);
                     }
+                    CrdsData::CompactContactInfo(node) => {
+                        if !has_contact_info {
+                            self.shred_versions.insert(pubkey, node.shred_version());
+                        }
+                        self.compact_nodes.insert(pubkey, ContactInfo::from(node));
+                    }
                     CrdsData::Vote(_, _) => {
                         self.votes.insert(value.ordinal, entry_index);
                     }
@@ -207,6 +242,12 @@ impl Crds {
                         // entry at this index was and stays contact-info.
                         debug_assert_matches!(entry.get().value.data, CrdsData::ContactInfo(_));
                     }
+                    CrdsData::CompactContactInfo(node) => {
+                        if !has_contact_info {
+                            self.shred_versions.insert(pubkey, node.shred_version());
+                        }
+                        self.compact_nodes.insert(pubkey, ContactInfo::from(node));
+                    }
                     CrdsData::Vote(_, _) => {
                         self.votes.remove(&entry.get().ordinal);
                         self.votes.insert(value.ordinal, entry_index);
@@ -245,9 +286,14 @@ impl Crds {
         self.table.get(label)
     }
 
+    /// Returns the legacy contact-info of the node, or its compact
+    /// contact-info converted to the legacy one.
     pub fn get_contact_info(&self, pubkey: Pubkey) -> Option<&ContactInfo> {
         let label = CrdsValueLabel::ContactInfo(pubkey);
-        self.table.get(&label)?.value.contact_info()
+        match self.table.get(&label) {
+            Some(entry) => entry.value.contact_info(),
+            None => self.compact_nodes.get(&pubkey),
+        }
     }
 
     pub(crate) fn get_shred_version(&self, pubkey: &Pubkey) -> Option<u16> {
@@ -264,12 +310,23 @@ impl Crds {
         self.nodes.iter().map(move |i| self.table.index(*i))
     }
 
-    /// Returns ContactInfo of all known nodes.
+    /// Returns ContactInfo of all known nodes, including the nodes which only
+    /// advertise a CompactContactInfo.
     pub fn get_nodes_contact_info(&self) -> impl Iterator<Item = &ContactInfo> {
-        self.get_nodes().map(|v| match &v.value.data {
+        let nodes = self.get_nodes().map(|v| match &v.value.data {
             CrdsData::ContactInfo(info) => info,
             _ => panic!("this should not happen!"),
-        })
+        });
+        let compact_nodes = self
+            .compact_nodes
+            .iter()
+            .filter(move |(pubkey, _)| {
+                !self
+                    .table
+                    .contains_key(&CrdsValueLabel::ContactInfo(**pubkey))
+            })
+            .map(|(_, node)| node);
+        nodes.chain(compact_nodes)
     }
 
     /// Returns all vote entries inserted since the given cursor.
@@ -446,6 +503,9 @@ impl Crds {
             CrdsData::ContactInfo(_) => {
                 self.nodes.swap_remove(&index);
             }
+            CrdsData::CompactContactInfo(_) => {
+                self.compact_nodes.remove(&value.value.pubkey());
+            }
             CrdsData::Vote(_, _) => {
                 self.votes.remove(&value.ordinal);
             }
@@ -564,6 +624,7 @@ mod tests {
     use {
         super::*,
         crate::{
+            compact_contact_info::CompactContactInfo,
             contact_info::ContactInfo,
             crds_value::{new_rand_timestamp, NodeInstance, SnapshotHash},
         },
@@ -1083,6 +1144,48 @@ mod tests {
         assert_eq!(crds.get_shred_version(&pubkey), None);
     }
 
+    #[test]
+    fn test_compact_contact_info() {
+        let mut rng = rand::thread_rng();
+        let pubkey = Pubkey::new_unique();
+        let mut crds = Crds::default();
+        let mut node = ContactInfo::new_rand(&mut rng, Some(pubkey));
+        node.shred_version = 42;
+        // The unused socket is not carried over to the compact contact-info.
+        node.unused = ContactInfo::default().unused;
+        let compact_node = CompactContactInfo::from(&node);
+        let value = CrdsValue::new_unsigned(CrdsData::CompactContactInfo(compact_node.clone()));
+        assert_eq!(crds.insert(value, timestamp()), Ok(()));
+        assert_eq!(crds.get_contact_info(pubkey), Some(&node));
+        assert_eq!(crds.get_nodes_contact_info().collect::<Vec<_>>(), [&node]);
+        assert_eq!(crds.get_nodes().count(), 0);
+        assert_eq!(crds.get_shred_version(&pubkey), Some(42));
+        // The legacy contact-info takes precedence over the compact one.
+        let mut legacy_node = node.clone();
+        legacy_node.wallclock += 1;
+        legacy_node.shred_version = 8;
+        let value = CrdsValue::new_unsigned(CrdsData::ContactInfo(legacy_node.clone()));
+        assert_eq!(crds.insert(value, timestamp()), Ok(()));
+        assert_eq!(crds.get_contact_info(pubkey), Some(&legacy_node));
+        assert_eq!(
+            crds.get_nodes_contact_info().collect::<Vec<_>>(),
+            [&legacy_node]
+        );
+        assert_eq!(crds.get_shred_version(&pubkey), Some(8));
+        let mut compact_node = compact_node;
+        compact_node.set_wallclock(node.wallclock + 1);
+        let value = CrdsValue::new_unsigned(CrdsData::CompactContactInfo(compact_node));
+        assert_eq!(crds.insert(value, timestamp()), Ok(()));
+        assert_eq!(crds.get_shred_version(&pubkey), Some(8));
+        // Falls back to the compact contact-info once the legacy one is removed.
+        crds.remove(&CrdsValueLabel::ContactInfo(pubkey), timestamp());
+        assert_eq!(crds.get_nodes_contact_info().count(), 1);
+        crds.remove(&CrdsValueLabel::CompactContactInfo(pubkey), timestamp());
+        assert_eq!(crds.get_contact_info(pubkey), None);
+        assert_eq!(crds.get_nodes_contact_info().count(), 0);
+        assert_eq!(crds.get_shred_version(&pubkey), None);
+    }
+
     #[test]
     #[allow(clippy::needless_collect)]
     fn test_drop() {
@@ -1166,8 +1269,9 @@ mod tests {
     #[allow(clippy::neg_cmp_op_on_partial_ord)]
     fn test_equal() {
         let val = CrdsValue::new_unsigned(CrdsData::ContactInfo(ContactInfo::default()));
-        let v1 = VersionedCrdsValue::new(val.clone(), Cursor::default(), 1);
-        let v2 = VersionedCrdsValue::new(val, Cursor::default(), 1);
+        let v1 =
+            VersionedCrdsValue::new(val.clone(), Cursor::default(), 1, GossipRoute::LocalMessage);
+        let v2 = VersionedCrdsValue::new(val, Cursor::default(), 1, GossipRoute::LocalMessage);
         assert_eq!(v1, v2);
         assert!(!(v1 != v2));
         assert!(!overrides(&v1.value, &v2));
@@ -1183,6 +1287,7 @@ mod tests {
             ))),
             Cursor::default(),
             1, // local_timestamp
+            GossipRoute::LocalMessage,
         );
         let v2 = VersionedCrdsValue::new(
             {
@@ -1192,6 +1297,7 @@ mod tests {
             },
             Cursor::default(),
             1, // local_timestamp
+            GossipRoute::LocalMessage,
         );
 
         assert_eq!(v1.value.label(), v2.value.label());
@@ -1217,6 +1323,7 @@ mod tests {
             ))),
             Cursor::default(),
             1, // local_timestamp
+            GossipRoute::LocalMessage,
         );
         let v2 = VersionedCrdsValue::new(
             CrdsValue::new_unsigned(CrdsData::ContactInfo(ContactInfo::new_localhost(
@@ -1225,6 +1332,7 @@ mod tests {
             ))),
             Cursor::default(),
             1, // local_timestamp
+            GossipRoute::LocalMessage,
         );
         assert_eq!(v1.value.label(), v2.value.label());
         assert!(overrides(&v1.value, &v2));
@@ -1243,6 +1351,7 @@ mod tests {
             ))),
             Cursor::default(),
             1, // local_timestamp
+            GossipRoute::LocalMessage,
         );
         let v2 = VersionedCrdsValue::new(
             CrdsValue::new_unsigned(CrdsData::ContactInfo(ContactInfo::new_localhost(
@@ -1251,6 +1360,7 @@ mod tests {
             ))),
             Cursor::default(),
             1, // local_timestamp
+            GossipRoute::LocalMessage,
         );
         assert_ne!(v1, v2);
         assert!(!(v1 == v2));
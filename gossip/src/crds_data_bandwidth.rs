@@ -0,0 +1,282 @@
+//! The `crds_data_bandwidth` module accounts the gossip traffic of each kind
+//! of CrdsData, and enforces optional outbound budgets per kind, so that a
+//! chatty kind (e.g. EpochSlots) cannot starve the others (e.g. Vote) of the
+//! global outbound budget.
+//!
+//! Only the values carried in push messages and pull responses are
+//! accounted, by their serialized size.
+
+use {
+    crate::{
+        crds_value::{CrdsValue, CRDS_DATA_NAMES},
+        data_budget::DataBudget,
+    },
+    bincode::serialized_size,
+    std::{
+        collections::{BTreeMap, HashMap},
+        sync::Mutex,
+    },
+    thiserror::Error,
+};
+
+// Interval at which the budgets are refilled.
+const BUDGET_INTERVAL_MS: u64 = 100;
+// Allow budget build-up to this many seconds of traffic.
+const MAX_BUDGET_SECONDS: u64 = 5;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum Error {
+    #[error("unknown crds data kind: {0}")]
+    UnknownCrdsData(String),
+    #[error("invalid budget for {0}: must be non-zero")]
+    ZeroBudget(String),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CrdsDataTraffic {
+    pub inbound_bytes: u64,
+    pub inbound_values: u64,
+    pub outbound_bytes: u64,
+    pub outbound_values: u64,
+    // Outbound values dropped because the budget of their kind was exhausted.
+    pub dropped_values: u64,
+}
+
+#[derive(Default)]
+struct TrafficCounters {
+    total: CrdsDataTraffic,
+    // Traffic since the last metrics submission.
+    interval: CrdsDataTraffic,
+}
+
+#[derive(Default)]
+pub struct CrdsDataBandwidth {
+    // Outbound bytes per second allowed for each kind, if limited.
+    budgets: HashMap<&'static str, (/*bytes per second:*/ u64, DataBudget)>,
+    traffic: Mutex<HashMap<&'static str, TrafficCounters>>,
+}
+
+fn value_size(value: &CrdsValue) -> u64 {
+    serialized_size(value).unwrap_or_default()
+}
+
+impl CrdsDataBandwidth {
+    /// Budgets are in bytes per second, by the names of the CrdsData kinds
+    /// they apply to. Kinds without a budget are only throttled by the global
+    /// outbound budget.
+    pub fn new(budgets: &HashMap<String, u64>) -> Result<Self, Error> {
+        let budgets = budgets
+            .iter()
+            .map(|(name, bytes_per_second)| {
+                let name = CRDS_DATA_NAMES
+                    .iter()
+                    .find(|known| **known == name.as_str())
+                    .ok_or_else(|| Error::UnknownCrdsData(name.clone()))?;
+                if *bytes_per_second == 0 {
+                    return Err(Error::ZeroBudget(name.to_string()));
+                }
+                Ok((*name, (*bytes_per_second, DataBudget::default())))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            budgets,
+            ..Self::default()
+        })
+    }
+
+    /// Returns the outbound budget of the kind, in bytes per second.
+    pub fn budget(&self, name: &str) -> Option<u64> {
+        self.budgets
+            .get(name)
+            .map(|(bytes_per_second, _)| *bytes_per_second)
+    }
+
+    /// Consumes the size of the value from the budget of its kind. Returns
+    /// false, and counts the value as dropped, if the budget is exhausted.
+    pub(crate) fn take_budget(&self, value: &CrdsValue) -> bool {
+        let name = value.data.name();
+        let (bytes_per_second, budget) = match self.budgets.get(name) {
+            None => return true,
+            Some(budget) => budget,
+        };
+        let bytes_per_interval = bytes_per_second * BUDGET_INTERVAL_MS / 1000;
+        budget.update(BUDGET_INTERVAL_MS, |bytes| {
+            std::cmp::min(
+                bytes.saturating_add(bytes_per_interval as usize),
+                (bytes_per_second * MAX_BUDGET_SECONDS) as usize,
+            )
+        });
+        if budget.take(value_size(value) as usize) {
+            return true;
+        }
+        self.record(std::iter::once(value), |traffic, _| {
+            traffic.dropped_values += 1;
+        });
+        false
+    }
+
+    /// Returns the size of a value which was not sent, after all, to the
+    /// budget of its kind.
+    pub(crate) fn refund_budget(&self, value: &CrdsValue) {
+        if let Some((_, budget)) = self.budgets.get(value.data.name()) {
+            budget.refund(value_size(value) as usize);
+        }
+    }
+
+    pub(crate) fn record_inbound<'a, I>(&self, values: I)
+    where
+        I: IntoIterator<Item = &'a CrdsValue>,
+    {
+        self.record(values, |traffic, size| {
+            traffic.inbound_bytes += size;
+            traffic.inbound_values += 1;
+        });
+    }
+
+    pub(crate) fn record_outbound<'a, I>(&self, values: I)
+    where
+        I: IntoIterator<Item = &'a CrdsValue>,
+    {
+        self.record(values, |traffic, size| {
+            traffic.outbound_bytes += size;
+            traffic.outbound_values += 1;
+        });
+    }
+
+    fn record<'a, I, F>(&self, values: I, update: F)
+    where
+        I: IntoIterator<Item = &'a CrdsValue>,
+        F: Fn(&mut CrdsDataTraffic, /*size:*/ u64),
+    {
+        // Aggregate locally so that the lock is only taken once.
+        let mut batch = HashMap::<&'static str, CrdsDataTraffic>::new();
+        for value in values {
+            update(
+                batch.entry(value.data.name()).or_default(),
+                value_size(value),
+            );
+        }
+        if batch.is_empty() {
+            return;
+        }
+        let mut traffic = self.traffic.lock().unwrap();
+        for (name, batch) in batch {
+            let counters = traffic.entry(name).or_default();
+            for traffic in [&mut counters.total, &mut counters.interval].iter_mut() {
+                traffic.inbound_bytes += batch.inbound_bytes;
+                traffic.inbound_values += batch.inbound_values;
+                traffic.outbound_bytes += batch.outbound_bytes;
+                traffic.outbound_values += batch.outbound_values;
+                traffic.dropped_values += batch.dropped_values;
+            }
+        }
+    }
+
+    /// Returns the traffic of each kind since start.
+    pub fn traffic(&self) -> BTreeMap<&'static str, CrdsDataTraffic> {
+        let traffic = self.traffic.lock().unwrap();
+        traffic
+            .iter()
+            .map(|(name, counters)| (*name, counters.total))
+            .collect()
+    }
+
+    pub(crate) fn submit_metrics(&self) {
+        let traffic: Vec<_> = {
+            let mut traffic = self.traffic.lock().unwrap();
+            traffic
+                .iter_mut()
+                .map(|(name, counters)| (*name, std::mem::take(&mut counters.interval)))
+                .collect()
+        };
+        for (name, traffic) in traffic {
+            datapoint_info!(
+                "cluster_info_crds_data_bandwidth",
+                ("kind", name.to_string(), String),
+                ("inbound_bytes", traffic.inbound_bytes, i64),
+                ("inbound_values", traffic.inbound_values, i64),
+                ("outbound_bytes", traffic.outbound_bytes, i64),
+                ("outbound_values", traffic.outbound_values, i64),
+                ("dropped_values", traffic.dropped_values, i64),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::crds_value::{CrdsData, Version},
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_crds_data_bandwidth_new() {
+        let budgets = vec![("Foo".to_string(), 1000)].into_iter().collect();
+        assert_eq!(
+            CrdsDataBandwidth::new(&budgets).err(),
+            Some(Error::UnknownCrdsData("Foo".to_string()))
+        );
+        let budgets = vec![("Vote".to_string(), 0)].into_iter().collect();
+        assert_eq!(
+            CrdsDataBandwidth::new(&budgets).err(),
+            Some(Error::ZeroBudget("Vote".to_string()))
+        );
+        let budgets = vec![("EpochSlots".to_string(), 1000)].into_iter().collect();
+        let bandwidth = CrdsDataBandwidth::new(&budgets).unwrap();
+        assert_eq!(bandwidth.budget("EpochSlots"), Some(1000));
+        assert_eq!(bandwidth.budget("Vote"), None);
+    }
+
+    #[test]
+    fn test_crds_data_bandwidth_traffic() {
+        let version =
+            CrdsValue::new_unsigned(CrdsData::Version(Version::new(Pubkey::new_unique())));
+        let size = value_size(&version);
+        // Budget for at most 2 values per interval.
+        let bytes_per_second = 1000 * (2 * size + 1) / BUDGET_INTERVAL_MS;
+        let budgets = vec![("Version".to_string(), bytes_per_second)]
+            .into_iter()
+            .collect();
+        let bandwidth = CrdsDataBandwidth::new(&budgets).unwrap();
+        assert!(bandwidth.take_budget(&version));
+        // The second value is dropped by the global budget, after it took
+        // from the budget of its kind.
+        assert!(bandwidth.take_budget(&version));
+        bandwidth.refund_budget(&version);
+        assert!(bandwidth.take_budget(&version));
+        assert!(!bandwidth.take_budget(&version));
+        bandwidth.record_outbound(vec![&version, &version]);
+        bandwidth.record_inbound(vec![&version]);
+
+        let traffic = bandwidth.traffic();
+        assert_eq!(traffic.len(), 1);
+        assert_eq!(
+            traffic["Version"],
+            CrdsDataTraffic {
+                inbound_bytes: size,
+                inbound_values: 1,
+                outbound_bytes: 2 * size,
+                outbound_values: 2,
+                dropped_values: 1,
+            }
+        );
+        // Metrics submission does not reset the totals.
+        bandwidth.submit_metrics();
+        assert_eq!(bandwidth.traffic()["Version"].outbound_values, 2);
+        assert_eq!(
+            bandwidth.traffic.lock().unwrap()["Version"].interval,
+            CrdsDataTraffic::default()
+        );
+    }
+
+    #[test]
+    fn test_crds_data_names() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let value = CrdsValue::new_rand(&mut rng, None);
+            assert!(CRDS_DATA_NAMES.contains(&value.data.name()));
+        }
+    }
+}
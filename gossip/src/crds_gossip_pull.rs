@@ -13,7 +13,7 @@ use {
     crate::{
         cluster_info::{Ping, CRDS_UNIQUE_PUBKEY_CAPACITY},
         contact_info::ContactInfo,
-        crds::{Crds, VersionedCrdsValue},
+        crds::{Crds, GossipRoute, VersionedCrdsValue},
         crds_gossip::{get_stake, get_weight},
         crds_gossip_error::CrdsGossipError,
         crds_value::CrdsValue,
@@ -330,7 +330,7 @@ impl CrdsGossipPull {
     {
         for caller in callers {
             let key = caller.pubkey();
-            let _ = crds.insert(caller, now);
+            let _ = crds.insert_with_route(caller, now, GossipRoute::PullRequest);
             crds.update_record_timestamp(&key, now);
         }
     }
@@ -409,12 +409,12 @@ impl CrdsGossipPull {
     ) {
         let mut owners = HashSet::new();
         for response in responses_expired_timeout {
-            let _ = crds.insert(response, now);
+            let _ = crds.insert_with_route(response, now, GossipRoute::PullResponse);
         }
         let mut num_inserts = 0;
         for response in responses {
             let owner = response.pubkey();
-            if let Ok(()) = crds.insert(response, now) {
+            if let Ok(()) = crds.insert_with_route(response, now, GossipRoute::PullResponse) {
                 num_inserts += 1;
                 owners.insert(owner);
             }
@@ -12,7 +12,7 @@ use {
     crate::{
         cluster_info::CRDS_UNIQUE_PUBKEY_CAPACITY,
         contact_info::ContactInfo,
-        crds::{Crds, Cursor},
+        crds::{Crds, Cursor, GossipRoute},
         crds_gossip::{get_stake, get_weight},
         crds_gossip_error::CrdsGossipError,
         crds_value::CrdsValue,
@@ -182,10 +182,11 @@ impl CrdsGossipPush {
             .entry(*from)
             .and_modify(|(_pruned, timestamp)| *timestamp = now)
             .or_insert((/*pruned:*/ false, now));
-        crds.insert(value, now).map_err(|_| {
-            self.num_old += 1;
-            CrdsGossipError::PushMessageOldVersion
-        })
+        crds.insert_with_route(value, now, GossipRoute::PushMessage)
+            .map_err(|_| {
+                self.num_old += 1;
+                CrdsGossipError::PushMessageOldVersion
+            })
     }
 
     /// New push message to broadcast to peers.
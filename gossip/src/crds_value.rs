@@ -1,6 +1,7 @@
 use {
     crate::{
         cluster_info::MAX_SNAPSHOT_HASHES,
+        compact_contact_info::CompactContactInfo,
         contact_info::ContactInfo,
         deprecated,
         duplicate_shred::{DuplicateShred, DuplicateShredIndex, MAX_DUPLICATE_SHREDS},
@@ -38,6 +39,16 @@ pub const MAX_VOTES: VoteIndex = 32;
 pub type EpochSlotsIndex = u8;
 pub const MAX_EPOCH_SLOTS: EpochSlotsIndex = 255;
 
+/// Maximum number of slots, counting back from the last voted slot, carried by
+/// RestartLastVotedForkSlots so that the value fits in a single push message.
+pub const MAX_RESTART_LAST_VOTED_FORK_SLOTS: Slot = 8_000;
+
+/// Maximum length of the target version carried by RestartIntent.
+pub const MAX_RESTART_INTENT_VERSION_LEN: usize = 32;
+/// Maximum expected duration of an announced restart, so that a stale
+/// announcement cannot hide a node being down for long.
+pub const MAX_RESTART_INTENT_DURATION_SECS: u64 = 24 * 60 * 60;
+
 /// CrdsValue that is replicated across the cluster
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, AbiExample)]
 pub struct CrdsValue {
@@ -91,6 +102,10 @@ pub enum CrdsData {
     Version(Version),
     NodeInstance(NodeInstance),
     DuplicateShred(DuplicateShredIndex, DuplicateShred),
+    RestartLastVotedForkSlots(RestartLastVotedForkSlots),
+    CompactContactInfo(CompactContactInfo),
+    RestartIntent(RestartIntent),
+    RestartSlotHash(RestartSlotHash),
 }
 
 impl Sanitize for CrdsData {
@@ -127,6 +142,10 @@ impl Sanitize for CrdsData {
                     shred.sanitize()
                 }
             }
+            CrdsData::RestartLastVotedForkSlots(slots) => slots.sanitize(),
+            CrdsData::CompactContactInfo(node) => node.sanitize(),
+            CrdsData::RestartIntent(intent) => intent.sanitize(),
+            CrdsData::RestartSlotHash(slot_hash) => slot_hash.sanitize(),
         }
     }
 }
@@ -137,7 +156,45 @@ pub(crate) fn new_rand_timestamp<R: Rng>(rng: &mut R) -> u64 {
     timestamp() - DELAY + rng.gen_range(0, 2 * DELAY)
 }
 
+/// Names of the CrdsData variants, as returned by `CrdsData::name`.
+pub const CRDS_DATA_NAMES: &[&str] = &[
+    "ContactInfo",
+    "Vote",
+    "LowestSlot",
+    "SnapshotHashes",
+    "AccountsHashes",
+    "EpochSlots",
+    "LegacyVersion",
+    "Version",
+    "NodeInstance",
+    "DuplicateShred",
+    "RestartLastVotedForkSlots",
+    "CompactContactInfo",
+    "RestartIntent",
+    "RestartSlotHash",
+];
+
 impl CrdsData {
+    /// Name of the variant, for diagnostics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CrdsData::ContactInfo(_) => "ContactInfo",
+            CrdsData::Vote(_, _) => "Vote",
+            CrdsData::LowestSlot(_, _) => "LowestSlot",
+            CrdsData::SnapshotHashes(_) => "SnapshotHashes",
+            CrdsData::AccountsHashes(_) => "AccountsHashes",
+            CrdsData::EpochSlots(_, _) => "EpochSlots",
+            CrdsData::LegacyVersion(_) => "LegacyVersion",
+            CrdsData::Version(_) => "Version",
+            CrdsData::NodeInstance(_) => "NodeInstance",
+            CrdsData::DuplicateShred(_, _) => "DuplicateShred",
+            CrdsData::RestartLastVotedForkSlots(_) => "RestartLastVotedForkSlots",
+            CrdsData::CompactContactInfo(_) => "CompactContactInfo",
+            CrdsData::RestartIntent(_) => "RestartIntent",
+            CrdsData::RestartSlotHash(_) => "RestartSlotHash",
+        }
+    }
+
     /// New random CrdsData for tests and benchmarks.
     fn new_rand<R: Rng>(rng: &mut R, pubkey: Option<Pubkey>) -> CrdsData {
         let kind = rng.gen_range(0, 7);
@@ -453,6 +510,139 @@ impl Sanitize for NodeInstance {
     }
 }
 
+/// The fork a node last voted on, announced when the cluster is restarted
+/// so that the nodes can agree on the slot to restart from.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, AbiExample)]
+pub struct RestartLastVotedForkSlots {
+    pub from: Pubkey,
+    pub wallclock: u64,
+    pub last_voted_slot: Slot,
+    pub last_voted_hash: Hash,
+    pub shred_version: u16,
+    // Bit i is set if slot last_voted_slot - i is on the fork.
+    offsets: Vec<u8>,
+}
+
+impl RestartLastVotedForkSlots {
+    /// Returns None if `slots` is empty. The last voted slot is the highest of
+    /// `slots`, and the slots older than MAX_RESTART_LAST_VOTED_FORK_SLOTS
+    /// before it are left out.
+    pub fn new(
+        from: Pubkey,
+        wallclock: u64,
+        slots: &[Slot],
+        last_voted_hash: Hash,
+        shred_version: u16,
+    ) -> Option<Self> {
+        let last_voted_slot = slots.iter().max().copied()?;
+        let offsets: Vec<_> = slots
+            .iter()
+            .map(|slot| last_voted_slot - slot)
+            .filter(|offset| *offset < MAX_RESTART_LAST_VOTED_FORK_SLOTS)
+            .collect();
+        // Offset 0, of the last voted slot itself, is always present.
+        let num_bytes = offsets.iter().max().unwrap() / 8 + 1;
+        let mut bits = vec![0u8; num_bytes as usize];
+        for offset in offsets {
+            bits[(offset / 8) as usize] |= 1 << (offset % 8);
+        }
+        Some(Self {
+            from,
+            wallclock,
+            last_voted_slot,
+            last_voted_hash,
+            shred_version,
+            offsets: bits,
+        })
+    }
+
+    /// Returns the slots on the fork in ascending order.
+    pub fn to_slots(&self) -> Vec<Slot> {
+        let mut slots: Vec<_> = (0..self.offsets.len() as Slot * 8)
+            .filter(|offset| self.offsets[(offset / 8) as usize] & (1 << (offset % 8)) != 0)
+            .filter_map(|offset| self.last_voted_slot.checked_sub(offset))
+            .collect();
+        slots.reverse();
+        slots
+    }
+}
+
+impl Sanitize for RestartLastVotedForkSlots {
+    fn sanitize(&self) -> Result<(), SanitizeError> {
+        sanitize_wallclock(self.wallclock)?;
+        if self.last_voted_slot >= MAX_SLOT {
+            return Err(SanitizeError::ValueOutOfBounds);
+        }
+        if self.offsets.len() as Slot > (MAX_RESTART_LAST_VOTED_FORK_SLOTS + 7) / 8 {
+            return Err(SanitizeError::ValueOutOfBounds);
+        }
+        self.from.sanitize()
+    }
+}
+
+/// A restart a node announces ahead of time, e.g. for an upgrade, so that
+/// monitoring can tell planned maintenance apart from a delinquent node.
+/// An announcement with a zero expected duration cancels the previous one.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, AbiExample)]
+pub struct RestartIntent {
+    pub from: Pubkey,
+    pub wallclock: u64,
+    /// Start of the restart, in milliseconds since the UNIX epoch.
+    pub start: u64,
+    pub expected_duration_secs: u64,
+    /// Software version the node will be running after the restart.
+    pub target_version: Option<String>,
+}
+
+impl RestartIntent {
+    /// End of the restart window, in milliseconds since the UNIX epoch.
+    pub fn end(&self) -> u64 {
+        self.start
+            .saturating_add(self.expected_duration_secs.saturating_mul(1000))
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.expected_duration_secs == 0
+    }
+}
+
+impl Sanitize for RestartIntent {
+    fn sanitize(&self) -> Result<(), SanitizeError> {
+        sanitize_wallclock(self.wallclock)?;
+        sanitize_wallclock(self.start)?;
+        if self.expected_duration_secs > MAX_RESTART_INTENT_DURATION_SECS {
+            return Err(SanitizeError::ValueOutOfBounds);
+        }
+        if let Some(version) = &self.target_version {
+            if version.len() > MAX_RESTART_INTENT_VERSION_LEN {
+                return Err(SanitizeError::ValueOutOfBounds);
+            }
+        }
+        self.from.sanitize()
+    }
+}
+
+/// The bank hash a node computed for the slot it picked to restart the cluster
+/// from, so that the nodes only restart once enough of the stake agrees on both.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, AbiExample)]
+pub struct RestartSlotHash {
+    pub from: Pubkey,
+    pub wallclock: u64,
+    pub slot: Slot,
+    pub hash: Hash,
+    pub shred_version: u16,
+}
+
+impl Sanitize for RestartSlotHash {
+    fn sanitize(&self) -> Result<(), SanitizeError> {
+        sanitize_wallclock(self.wallclock)?;
+        if self.slot >= MAX_SLOT {
+            return Err(SanitizeError::ValueOutOfBounds);
+        }
+        self.from.sanitize()
+    }
+}
+
 /// Type of the replicated value
 /// These are labels for values in a record that is associated with `Pubkey`
 #[derive(PartialEq, Hash, Eq, Clone, Debug)]
@@ -467,6 +657,10 @@ pub enum CrdsValueLabel {
     Version(Pubkey),
     NodeInstance(Pubkey),
     DuplicateShred(DuplicateShredIndex, Pubkey),
+    RestartLastVotedForkSlots(Pubkey),
+    CompactContactInfo(Pubkey),
+    RestartIntent(Pubkey),
+    RestartSlotHash(Pubkey),
 }
 
 impl fmt::Display for CrdsValueLabel {
@@ -482,6 +676,12 @@ impl fmt::Display for CrdsValueLabel {
             CrdsValueLabel::Version(_) => write!(f, "Version({})", self.pubkey()),
             CrdsValueLabel::NodeInstance(pk) => write!(f, "NodeInstance({})", pk),
             CrdsValueLabel::DuplicateShred(ix, pk) => write!(f, "DuplicateShred({}, {})", ix, pk),
+            CrdsValueLabel::RestartLastVotedForkSlots(_) => {
+                write!(f, "RestartLastVotedForkSlots({})", self.pubkey())
+            }
+            CrdsValueLabel::CompactContactInfo(pk) => write!(f, "CompactContactInfo({})", pk),
+            CrdsValueLabel::RestartIntent(pk) => write!(f, "RestartIntent({})", pk),
+            CrdsValueLabel::RestartSlotHash(pk) => write!(f, "RestartSlotHash({})", pk),
         }
     }
 }
@@ -499,6 +699,10 @@ impl CrdsValueLabel {
             CrdsValueLabel::Version(p) => *p,
             CrdsValueLabel::NodeInstance(p) => *p,
             CrdsValueLabel::DuplicateShred(_, p) => *p,
+            CrdsValueLabel::RestartLastVotedForkSlots(p) => *p,
+            CrdsValueLabel::CompactContactInfo(p) => *p,
+            CrdsValueLabel::RestartIntent(p) => *p,
+            CrdsValueLabel::RestartSlotHash(p) => *p,
         }
     }
 }
@@ -547,6 +751,10 @@ impl CrdsValue {
             CrdsData::Version(version) => version.wallclock,
             CrdsData::NodeInstance(node) => node.wallclock,
             CrdsData::DuplicateShred(_, shred) => shred.wallclock,
+            CrdsData::RestartLastVotedForkSlots(slots) => slots.wallclock,
+            CrdsData::CompactContactInfo(node) => node.wallclock(),
+            CrdsData::RestartIntent(intent) => intent.wallclock,
+            CrdsData::RestartSlotHash(slot_hash) => slot_hash.wallclock,
         }
     }
     pub fn pubkey(&self) -> Pubkey {
@@ -561,6 +769,10 @@ impl CrdsValue {
             CrdsData::Version(version) => version.from,
             CrdsData::NodeInstance(node) => node.from,
             CrdsData::DuplicateShred(_, shred) => shred.from,
+            CrdsData::RestartLastVotedForkSlots(slots) => slots.from,
+            CrdsData::CompactContactInfo(node) => *node.pubkey(),
+            CrdsData::RestartIntent(intent) => intent.from,
+            CrdsData::RestartSlotHash(slot_hash) => slot_hash.from,
         }
     }
     pub fn label(&self) -> CrdsValueLabel {
@@ -575,6 +787,12 @@ impl CrdsValue {
             CrdsData::Version(_) => CrdsValueLabel::Version(self.pubkey()),
             CrdsData::NodeInstance(node) => CrdsValueLabel::NodeInstance(node.from),
             CrdsData::DuplicateShred(ix, shred) => CrdsValueLabel::DuplicateShred(*ix, shred.from),
+            CrdsData::RestartLastVotedForkSlots(_) => {
+                CrdsValueLabel::RestartLastVotedForkSlots(self.pubkey())
+            }
+            CrdsData::CompactContactInfo(_) => CrdsValueLabel::CompactContactInfo(self.pubkey()),
+            CrdsData::RestartIntent(_) => CrdsValueLabel::RestartIntent(self.pubkey()),
+            CrdsData::RestartSlotHash(_) => CrdsValueLabel::RestartSlotHash(self.pubkey()),
         }
     }
     pub fn contact_info(&self) -> Option<&ContactInfo> {
@@ -634,6 +852,34 @@ impl CrdsValue {
         }
     }
 
+    pub fn restart_last_voted_fork_slots(&self) -> Option<&RestartLastVotedForkSlots> {
+        match &self.data {
+            CrdsData::RestartLastVotedForkSlots(slots) => Some(slots),
+            _ => None,
+        }
+    }
+
+    pub fn compact_contact_info(&self) -> Option<&CompactContactInfo> {
+        match &self.data {
+            CrdsData::CompactContactInfo(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn restart_intent(&self) -> Option<&RestartIntent> {
+        match &self.data {
+            CrdsData::RestartIntent(intent) => Some(intent),
+            _ => None,
+        }
+    }
+
+    pub fn restart_slot_hash(&self) -> Option<&RestartSlotHash> {
+        match &self.data {
+            CrdsData::RestartSlotHash(slot_hash) => Some(slot_hash),
+            _ => None,
+        }
+    }
+
     /// Returns the size (in bytes) of a CrdsValue
     pub fn size(&self) -> u64 {
         serialized_size(&self).expect("unable to serialize contact info")
@@ -685,12 +931,17 @@ pub(crate) fn sanitize_wallclock(wallclock: u64) -> Result<(), SanitizeError> {
 mod test {
     use {
         super::*,
-        crate::contact_info::ContactInfo,
+        crate::{
+            contact_info::ContactInfo,
+            duplicate_shred::{self, tests::new_rand_shred},
+        },
         bincode::{deserialize, Options},
         rand::SeedableRng,
         rand_chacha::ChaChaRng,
+        solana_ledger::shred::Shredder,
         solana_perf::test_tx::test_tx,
         solana_sdk::{
+            packet::PACKET_DATA_SIZE,
             signature::{Keypair, Signer},
             timing::timestamp,
         },
@@ -1059,4 +1310,99 @@ mod test {
         assert!(node.should_force_push(&pubkey));
         assert!(!node.should_force_push(&Pubkey::new_unique()));
     }
+
+    #[test]
+    fn test_restart_last_voted_fork_slots() {
+        let pubkey = Pubkey::new_unique();
+        let hash = Hash::new_unique();
+        assert_eq!(
+            RestartLastVotedForkSlots::new(pubkey, timestamp(), &[], hash, 1),
+            None
+        );
+        let slots = vec![
+            3,
+            6,
+            MAX_RESTART_LAST_VOTED_FORK_SLOTS,
+            MAX_RESTART_LAST_VOTED_FORK_SLOTS + 5,
+            MAX_RESTART_LAST_VOTED_FORK_SLOTS + 6,
+        ];
+        let fork = RestartLastVotedForkSlots::new(pubkey, timestamp(), &slots, hash, 1).unwrap();
+        assert_eq!(fork.last_voted_slot, MAX_RESTART_LAST_VOTED_FORK_SLOTS + 6);
+        // Slots 3 and 6 are too old to be included.
+        assert_eq!(fork.to_slots(), slots[2..].to_vec());
+        assert!(fork.sanitize().is_ok());
+        let value = CrdsValue::new_signed(
+            CrdsData::RestartLastVotedForkSlots(fork.clone()),
+            &Keypair::new(),
+        );
+        assert_eq!(
+            deserialize::<CrdsValue>(&serialize(&value).unwrap()).unwrap(),
+            value
+        );
+
+        let mut fork = fork;
+        fork.offsets = vec![0xff; MAX_RESTART_LAST_VOTED_FORK_SLOTS as usize / 8 + 1];
+        assert_eq!(fork.sanitize(), Err(SanitizeError::ValueOutOfBounds));
+    }
+    #[test]
+    fn test_crds_data_names() {
+        let mut rng = rand::thread_rng();
+        let pubkey = Pubkey::new_unique();
+        let now = timestamp();
+        // The legacy version has no constructor, but is a prefix of the version.
+        let legacy_version = LegacyVersion {
+            from: pubkey,
+            wallclock: now,
+            version: deserialize(&serialize(&solana_version::Version::default()).unwrap()).unwrap(),
+        };
+        let duplicate_shred = {
+            let leader = Keypair::new();
+            let shredder = Shredder::new(53084024, 53084023, 0, 0).unwrap();
+            let shred = new_rand_shred(&mut rng, 0, &shredder, &leader);
+            let other_payload = new_rand_shred(&mut rng, 0, &shredder, &leader).payload;
+            duplicate_shred::from_shred(
+                shred,
+                pubkey,
+                other_payload,
+                None::<fn(Slot) -> Option<Pubkey>>,
+                now,
+                PACKET_DATA_SIZE,
+            )
+            .unwrap()
+            .next()
+            .unwrap()
+        };
+        let data = vec![
+            CrdsData::ContactInfo(ContactInfo::new_localhost(&pubkey, now)),
+            CrdsData::Vote(0, Vote::new(pubkey, test_tx(), now)),
+            CrdsData::LowestSlot(0, LowestSlot::new(pubkey, 0, now)),
+            CrdsData::SnapshotHashes(SnapshotHash::new(pubkey, vec![])),
+            CrdsData::AccountsHashes(SnapshotHash::new(pubkey, vec![])),
+            CrdsData::EpochSlots(0, EpochSlots::new(pubkey, now)),
+            CrdsData::LegacyVersion(legacy_version),
+            CrdsData::Version(Version::new(pubkey)),
+            CrdsData::NodeInstance(NodeInstance::new(&mut rng, pubkey, now)),
+            CrdsData::DuplicateShred(0, duplicate_shred),
+            CrdsData::RestartLastVotedForkSlots(
+                RestartLastVotedForkSlots::new(pubkey, now, &[1], Hash::default(), 0).unwrap(),
+            ),
+            CrdsData::CompactContactInfo(CompactContactInfo::new(pubkey, now, 0)),
+            CrdsData::RestartIntent(RestartIntent {
+                from: pubkey,
+                wallclock: now,
+                start: now,
+                expected_duration_secs: 60,
+                target_version: None,
+            }),
+            CrdsData::RestartSlotHash(RestartSlotHash {
+                from: pubkey,
+                wallclock: now,
+                slot: 1,
+                hash: Hash::default(),
+                shred_version: 0,
+            }),
+        ];
+        let names: Vec<_> = data.iter().map(CrdsData::name).collect();
+        assert_eq!(names, CRDS_DATA_NAMES);
+    }
 }
@@ -31,6 +31,11 @@ impl DataBudget {
         }
     }
 
+    // Returns to the budget bytes which were taken but not used.
+    pub fn refund(&self, size: usize) {
+        self.bytes.fetch_add(size, Ordering::AcqRel);
+    }
+
     // Updates timestamp and returns true, if at least given milliseconds
     // has passed since last update. Otherwise returns false.
     fn can_update(&self, duration_millis: u64) -> bool {
@@ -108,5 +113,9 @@ mod tests {
         assert!(budget.take(3));
         assert!(budget.take(1));
         assert!(!budget.take(1)); // budget = 0.
+
+        budget.refund(2); // budget = 2.
+        assert!(budget.take(2));
+        assert!(!budget.take(1)); // budget = 0.
     }
 }
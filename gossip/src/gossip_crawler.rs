@@ -0,0 +1,342 @@
+//! The `gossip_crawler` module watches the gossip table of a spy node over a
+//! time window, and produces a snapshot of the network topology seen through
+//! it, for offline analysis of the health of the network.
+
+use {
+    crate::{
+        cluster_info::ClusterInfo,
+        contact_info::ContactInfo,
+        crds::{Crds, Cursor, GossipRoute},
+        crds_value::{CrdsData, CrdsValueLabel},
+    },
+    solana_sdk::{clock::Slot, hash::Hash, pubkey::Pubkey, timing::timestamp},
+    std::{
+        collections::{BTreeMap, HashMap},
+        net::SocketAddr,
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+};
+
+const CRAWL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct NodeTopology {
+    pub pubkey: String,
+    pub gossip: Option<SocketAddr>,
+    pub tvu: Option<SocketAddr>,
+    pub tpu: Option<SocketAddr>,
+    pub rpc: Option<SocketAddr>,
+    pub shred_version: u16,
+    pub version: Option<String>,
+    pub stake: Option<u64>,
+    pub snapshot_hashes: Vec<(Slot, String)>,
+    pub accounts_hashes: Vec<(Slot, String)>,
+    pub wallclock: u64,
+    // Number of gossip values originating from the node.
+    pub num_values: usize,
+}
+
+/// Nodes sharing a shred version or a software version.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct NodeGroup {
+    pub num_nodes: usize,
+    pub stake: u64,
+}
+
+/// Time it took the values of a kind to reach the crawler over a route, from
+/// the wallclock of their origin.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PropagationLatency {
+    pub kind: &'static str,
+    pub route: &'static str,
+    pub count: usize,
+    pub min_ms: u64,
+    pub median_ms: u64,
+    pub p90_ms: u64,
+    pub max_ms: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct NetworkTopology {
+    pub crawler: String,
+    // Start of the crawl, in milliseconds since the UNIX epoch.
+    pub start: u64,
+    pub duration_ms: u64,
+    pub total_stake: Option<u64>,
+    pub nodes: Vec<NodeTopology>,
+    pub shred_versions: BTreeMap<u16, NodeGroup>,
+    pub versions: BTreeMap<String, NodeGroup>,
+    pub propagation_latencies: Vec<PropagationLatency>,
+}
+
+#[derive(Default)]
+pub struct GossipCrawler {
+    cursor: Cursor,
+    // Propagation latencies in milliseconds, by kind of value and route.
+    latencies: HashMap<(&'static str, GossipRoute), Vec<u64>>,
+}
+
+fn route_name(route: GossipRoute) -> &'static str {
+    match route {
+        GossipRoute::LocalMessage => "local",
+        GossipRoute::PullRequest => "pull-request",
+        GossipRoute::PullResponse => "pull-response",
+        GossipRoute::PushMessage => "push",
+    }
+}
+
+fn valid_address(addr: SocketAddr) -> Option<SocketAddr> {
+    ContactInfo::is_valid_address(&addr).then(|| addr)
+}
+
+fn hashes_to_strings(hashes: &[(Slot, Hash)]) -> Vec<(Slot, String)> {
+    hashes
+        .iter()
+        .map(|(slot, hash)| (*slot, hash.to_string()))
+        .collect()
+}
+
+impl GossipCrawler {
+    /// Records the propagation latencies of the values inserted in `crds`
+    /// since the last call.
+    pub fn record_latencies(&mut self, crds: &Crds) {
+        for entry in crds.get_entries(&mut self.cursor) {
+            if entry.route == GossipRoute::LocalMessage {
+                continue;
+            }
+            // Wallclocks ahead of the local clock count as no latency.
+            let latency = entry
+                .local_timestamp
+                .saturating_sub(entry.value.wallclock());
+            self.latencies
+                .entry((entry.value.data.name(), entry.route))
+                .or_default()
+                .push(latency);
+        }
+    }
+
+    /// Returns the topology of the nodes in `crds`, other than the crawler.
+    pub fn topology(
+        &self,
+        crds: &Crds,
+        self_pubkey: &Pubkey,
+        stakes: Option<&HashMap<Pubkey, u64>>,
+    ) -> NetworkTopology {
+        let stake_of =
+            |pubkey: &Pubkey| stakes.map(|stakes| stakes.get(pubkey).copied().unwrap_or_default());
+        let mut nodes: Vec<_> = crds
+            .get_nodes_contact_info()
+            .filter(|node| node.id != *self_pubkey)
+            .map(|node| {
+                let version = crds
+                    .get(&CrdsValueLabel::Version(node.id))
+                    .and_then(|entry| match &entry.value.data {
+                        CrdsData::Version(version) => Some(version.version.to_string()),
+                        _ => None,
+                    });
+                let snapshot_hashes = crds
+                    .get(&CrdsValueLabel::SnapshotHashes(node.id))
+                    .and_then(|entry| entry.value.snapshot_hash())
+                    .map(|hashes| hashes_to_strings(&hashes.hashes))
+                    .unwrap_or_default();
+                let accounts_hashes = crds
+                    .get(&CrdsValueLabel::AccountsHashes(node.id))
+                    .and_then(|entry| entry.value.accounts_hash())
+                    .map(|hashes| hashes_to_strings(&hashes.hashes))
+                    .unwrap_or_default();
+                NodeTopology {
+                    pubkey: node.id.to_string(),
+                    gossip: valid_address(node.gossip),
+                    tvu: valid_address(node.tvu),
+                    tpu: valid_address(node.tpu),
+                    rpc: valid_address(node.rpc),
+                    shred_version: node.shred_version,
+                    version,
+                    stake: stake_of(&node.id),
+                    snapshot_hashes,
+                    accounts_hashes,
+                    wallclock: node.wallclock,
+                    num_values: crds.get_records(&node.id).count(),
+                }
+            })
+            .collect();
+        // Most staked nodes first.
+        nodes.sort_by(|a, b| b.stake.cmp(&a.stake).then_with(|| a.pubkey.cmp(&b.pubkey)));
+
+        let mut shred_versions = BTreeMap::<u16, NodeGroup>::new();
+        let mut versions = BTreeMap::<String, NodeGroup>::new();
+        for node in &nodes {
+            let version = node
+                .version
+                .clone()
+                .unwrap_or_else(|| "unknown".to_string());
+            for group in [
+                shred_versions.entry(node.shred_version).or_default(),
+                versions.entry(version).or_default(),
+            ]
+            .iter_mut()
+            {
+                group.num_nodes += 1;
+                group.stake += node.stake.unwrap_or_default();
+            }
+        }
+
+        let mut propagation_latencies: Vec<_> = self
+            .latencies
+            .iter()
+            .map(|((kind, route), latencies)| {
+                let mut latencies = latencies.clone();
+                latencies.sort_unstable();
+                let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+                PropagationLatency {
+                    kind: *kind,
+                    route: route_name(*route),
+                    count: latencies.len(),
+                    min_ms: percentile(0),
+                    median_ms: percentile(50),
+                    p90_ms: percentile(90),
+                    max_ms: percentile(100),
+                }
+            })
+            .collect();
+        propagation_latencies.sort_by(|a, b| (a.kind, a.route).cmp(&(b.kind, b.route)));
+
+        NetworkTopology {
+            total_stake: stakes.map(|stakes| stakes.values().sum()),
+            nodes,
+            shred_versions,
+            versions,
+            propagation_latencies,
+            ..NetworkTopology::default()
+        }
+    }
+}
+
+/// Watches the gossip table of `cluster_info` for `duration`, and returns the
+/// topology of the network seen through it. `stakes` maps the node pubkeys to
+/// their stake, if known.
+pub fn crawl(
+    cluster_info: &ClusterInfo,
+    duration: Duration,
+    stakes: Option<&HashMap<Pubkey, u64>>,
+) -> NetworkTopology {
+    let start = timestamp();
+    let crawl_start = Instant::now();
+    let mut crawler = GossipCrawler::default();
+    while crawl_start.elapsed() < duration {
+        crawler.record_latencies(&cluster_info.gossip.read().unwrap().crds);
+        sleep(CRAWL_POLL_INTERVAL);
+    }
+    let gossip = cluster_info.gossip.read().unwrap();
+    crawler.record_latencies(&gossip.crds);
+    let self_pubkey = cluster_info.id();
+    NetworkTopology {
+        crawler: self_pubkey.to_string(),
+        start,
+        duration_ms: crawl_start.elapsed().as_millis() as u64,
+        ..crawler.topology(&gossip.crds, &self_pubkey, stakes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::crds_value::{CrdsValue, SnapshotHash, Version},
+    };
+
+    #[test]
+    fn test_gossip_crawler_topology() {
+        let now = timestamp();
+        let self_pubkey = Pubkey::new_unique();
+        let mut crds = Crds::default();
+        let mut nodes: Vec<_> = (0..3)
+            .map(|_| ContactInfo::new_localhost(&Pubkey::new_unique(), now - 100))
+            .collect();
+        nodes[0].shred_version = 42;
+        nodes[1].shred_version = 42;
+        nodes[2].shred_version = 7;
+        let me = ContactInfo::new_localhost(&self_pubkey, now);
+        crds.insert(CrdsValue::new_unsigned(CrdsData::ContactInfo(me)), now)
+            .unwrap();
+        for (node, route) in nodes.iter().zip(vec![
+            GossipRoute::PushMessage,
+            GossipRoute::PushMessage,
+            GossipRoute::PullResponse,
+        ]) {
+            let value = CrdsValue::new_unsigned(CrdsData::ContactInfo(node.clone()));
+            crds.insert_with_route(value, now, route).unwrap();
+        }
+        let mut version = Version::new(nodes[0].id);
+        version.wallclock = now - 300;
+        let value = CrdsValue::new_unsigned(CrdsData::Version(version.clone()));
+        crds.insert_with_route(value, now, GossipRoute::PushMessage)
+            .unwrap();
+        let hash = Hash::new_unique();
+        let mut snapshot_hash = SnapshotHash::new(nodes[1].id, vec![(10, hash)]);
+        snapshot_hash.wallclock = now + 100;
+        let value = CrdsValue::new_unsigned(CrdsData::SnapshotHashes(snapshot_hash));
+        crds.insert_with_route(value, now, GossipRoute::PullResponse)
+            .unwrap();
+
+        let mut crawler = GossipCrawler::default();
+        crawler.record_latencies(&crds);
+        let stakes: HashMap<_, _> = vec![(nodes[0].id, 30), (nodes[1].id, 50)]
+            .into_iter()
+            .collect();
+        let topology = crawler.topology(&crds, &self_pubkey, Some(&stakes));
+
+        assert_eq!(topology.total_stake, Some(80));
+        assert_eq!(topology.nodes.len(), 3);
+        assert_eq!(topology.nodes[0].pubkey, nodes[1].id.to_string());
+        assert_eq!(topology.nodes[0].stake, Some(50));
+        assert_eq!(
+            topology.nodes[0].snapshot_hashes,
+            vec![(10, hash.to_string())]
+        );
+        assert_eq!(topology.nodes[0].version, None);
+        assert_eq!(topology.nodes[1].pubkey, nodes[0].id.to_string());
+        assert_eq!(topology.nodes[1].version, Some(version.version.to_string()));
+        assert_eq!(topology.nodes[1].num_values, 2);
+        assert_eq!(topology.nodes[1].tpu, Some(nodes[0].tpu));
+        assert_eq!(topology.nodes[2].stake, Some(0));
+        assert_eq!(
+            topology.shred_versions.get(&42),
+            Some(&NodeGroup {
+                num_nodes: 2,
+                stake: 80,
+            })
+        );
+        assert_eq!(
+            topology.shred_versions.get(&7),
+            Some(&NodeGroup {
+                num_nodes: 1,
+                stake: 0,
+            })
+        );
+        assert_eq!(topology.versions.get("unknown").unwrap().num_nodes, 2);
+
+        let latency = |kind, route| {
+            topology
+                .propagation_latencies
+                .iter()
+                .find(|latency| latency.kind == kind && latency.route == route)
+                .unwrap()
+        };
+        let contact_info_push = latency("ContactInfo", "push");
+        assert_eq!(contact_info_push.count, 2);
+        assert_eq!(contact_info_push.min_ms, 100);
+        assert_eq!(contact_info_push.max_ms, 100);
+        assert_eq!(latency("ContactInfo", "pull-response").count, 1);
+        assert_eq!(latency("Version", "push").median_ms, 300);
+        // Wallclocks ahead of the local clock.
+        assert_eq!(latency("SnapshotHashes", "pull-response").max_ms, 0);
+        // The crawler's own values are not counted.
+        assert_eq!(topology.propagation_latencies.len(), 4);
+
+        // Only the values inserted since the last call are recorded.
+        crawler.record_latencies(&crds);
+        assert_eq!(crawler.latencies.values().map(Vec::len).sum::<usize>(), 5);
+    }
+}
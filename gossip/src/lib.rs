@@ -3,9 +3,11 @@
 
 pub mod cluster_info;
 mod cluster_info_metrics;
+pub mod compact_contact_info;
 #[macro_use]
 pub mod contact_info;
 pub mod crds;
+pub mod crds_data_bandwidth;
 pub mod crds_gossip;
 pub mod crds_gossip_error;
 pub mod crds_gossip_pull;
@@ -16,6 +18,7 @@ pub mod data_budget;
 pub mod deprecated;
 pub mod duplicate_shred;
 pub mod epoch_slots;
+pub mod gossip_crawler;
 pub mod gossip_error;
 pub mod gossip_service;
 pub mod ping_pong;
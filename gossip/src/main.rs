@@ -7,14 +7,36 @@ use {
     },
     solana_clap_utils::{
         input_parsers::keypair_of,
-        input_validators::{is_keypair_or_ask_keyword, is_port, is_pubkey},
+        input_validators::{is_keypair_or_ask_keyword, is_port, is_pubkey, is_url},
+    },
+    solana_client::rpc_client::RpcClient,
+    solana_gossip::{
+        contact_info::ContactInfo,
+        gossip_crawler,
+        gossip_service::{discover, make_gossip_node},
+    },
+    solana_runtime::{
+        accounts_db::AccountShrinkThreshold,
+        accounts_index::AccountSecondaryIndexes,
+        hardened_unpack::{open_genesis_config, MAX_GENESIS_ARCHIVE_UNPACKED_SIZE},
+        snapshot_utils,
+    },
+    solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
     },
-    solana_gossip::{contact_info::ContactInfo, gossip_service::discover},
-    solana_sdk::pubkey::Pubkey,
     std::{
+        collections::HashMap,
         error,
+        fs::{self, File},
+        io,
         net::{IpAddr, Ipv4Addr, SocketAddr},
+        path::Path,
         process::exit,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
         time::Duration,
     },
 };
@@ -142,6 +164,81 @@ fn parse_matches() -> ArgMatches<'static> {
                         .help("Maximum time to wait in seconds [default: wait forever]"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("crawl")
+                .about("Crawl the gossip network and dump its topology as JSON")
+                .setting(AppSettings::DisableVersion)
+                .arg(
+                    Arg::with_name("entrypoint")
+                        .short("n")
+                        .long("entrypoint")
+                        .value_name("HOST:PORT")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(solana_net_utils::is_host_port)
+                        .help("Rendezvous with the cluster at this entrypoint"),
+                )
+                .arg(
+                    clap::Arg::with_name("gossip_port")
+                        .long("gossip-port")
+                        .value_name("PORT")
+                        .takes_value(true)
+                        .validator(is_port)
+                        .help("Gossip port number for the node"),
+                )
+                .arg(
+                    clap::Arg::with_name("gossip_host")
+                        .long("gossip-host")
+                        .value_name("HOST")
+                        .takes_value(true)
+                        .validator(solana_net_utils::is_host)
+                        .help("Gossip DNS name or IP address for the node to advertise in gossip \
+                               [default: ask --entrypoint]"),
+                )
+                .arg(
+                    Arg::with_name("identity")
+                        .short("i")
+                        .long("identity")
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .validator(is_keypair_or_ask_keyword)
+                        .help("Identity keypair [default: ephemeral keypair]"),
+                )
+                .arg(&shred_version_arg)
+                .arg(
+                    Arg::with_name("duration")
+                        .long("duration")
+                        .value_name("SECONDS")
+                        .takes_value(true)
+                        .default_value("60")
+                        .help("Time spent collecting gossip values, in seconds"),
+                )
+                .arg(
+                    Arg::with_name("rpc_url")
+                        .long("rpc-url")
+                        .value_name("URL")
+                        .takes_value(true)
+                        .validator(is_url)
+                        .help("Fetch the stake of the nodes from this RPC endpoint"),
+                )
+                .arg(
+                    Arg::with_name("ledger_path")
+                        .long("ledger")
+                        .value_name("DIR")
+                        .takes_value(true)
+                        .conflicts_with("rpc_url")
+                        .help("Read the stake of the nodes from the genesis and the \
+                               highest snapshot archive in this ledger directory"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .help("Write the topology to this file [default: stdout]"),
+                ),
+        )
         .get_matches()
 }
 
@@ -310,6 +407,106 @@ fn process_rpc_url(matches: &ArgMatches) -> std::io::Result<()> {
     Ok(())
 }
 
+fn get_stakes_from_rpc(rpc_url: &str) -> Result<HashMap<Pubkey, u64>, Box<dyn error::Error>> {
+    let vote_accounts = RpcClient::new(rpc_url.to_string()).get_vote_accounts()?;
+    let mut stakes = HashMap::new();
+    for vote_account in vote_accounts
+        .current
+        .iter()
+        .chain(vote_accounts.delinquent.iter())
+    {
+        let node_pubkey = vote_account.node_pubkey.parse::<Pubkey>()?;
+        *stakes.entry(node_pubkey).or_default() += vote_account.activated_stake;
+    }
+    Ok(stakes)
+}
+
+fn get_stakes_from_snapshot(
+    ledger_path: &Path,
+) -> Result<HashMap<Pubkey, u64>, Box<dyn error::Error>> {
+    let archive_info = snapshot_utils::get_highest_snapshot_archive_info(ledger_path)
+        .ok_or_else(|| format!("no snapshot archive found in {:?}", ledger_path))?;
+    let genesis_config = open_genesis_config(ledger_path, MAX_GENESIS_ARCHIVE_UNPACKED_SIZE);
+    let temp_dir = std::env::temp_dir().join(format!("solana-gossip-crawl-{}", std::process::id()));
+    let accounts_dir = temp_dir.join("accounts");
+    fs::create_dir_all(&accounts_dir)?;
+    let bank = snapshot_utils::bank_from_snapshot_archive(
+        &[accounts_dir],
+        &[], // frozen_account_pubkeys
+        &temp_dir,
+        &archive_info.path,
+        archive_info.archive_format,
+        &genesis_config,
+        None, // debug_keys
+        None, // additional_builtins
+        AccountSecondaryIndexes::default(),
+        false, // accounts_db_caching_enabled
+        None,  // limit_load_slot_count_from_snapshot
+        AccountShrinkThreshold::default(),
+        false, // test_hash_calculation
+    )
+    .map(|(bank, _timings)| bank);
+    let _ = fs::remove_dir_all(&temp_dir);
+    let bank = bank?;
+    Ok(bank.epoch_staked_nodes(bank.epoch()).unwrap_or_default())
+}
+
+fn process_crawl(matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+    let shred_version = value_t_or_exit!(matches, "shred_version", u16);
+    let duration = value_t_or_exit!(matches, "duration", u64);
+    let identity_keypair = keypair_of(matches, "identity").unwrap_or_else(Keypair::new);
+    let entrypoint_addr = parse_entrypoint(matches).unwrap();
+    let gossip_host = parse_gossip_host(matches, Some(entrypoint_addr));
+    let gossip_addr = SocketAddr::new(
+        gossip_host,
+        value_t!(matches, "gossip_port", u16).unwrap_or_else(|_| {
+            solana_net_utils::find_available_port_in_range(
+                IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
+                (0, 1),
+            )
+            .expect("unable to find an available gossip port")
+        }),
+    );
+    let stakes = if let Some(rpc_url) = matches.value_of("rpc_url") {
+        Some(get_stakes_from_rpc(rpc_url)?)
+    } else if let Some(ledger_path) = matches.value_of("ledger_path") {
+        Some(get_stakes_from_snapshot(Path::new(ledger_path))?)
+    } else {
+        None
+    };
+
+    let exit = Arc::new(AtomicBool::new(false));
+    eprintln!(
+        "Crawling gossip as {} for {}s...",
+        identity_keypair.pubkey(),
+        duration
+    );
+    let (gossip_service, _ip_echo, cluster_info) = make_gossip_node(
+        identity_keypair,
+        Some(&entrypoint_addr),
+        &exit,
+        Some(&gossip_addr),
+        shred_version,
+        true, // should_check_duplicate_instance
+    );
+    let topology = gossip_crawler::crawl(
+        &cluster_info,
+        Duration::from_secs(duration),
+        stakes.as_ref(),
+    );
+    exit.store(true, Ordering::Relaxed);
+    gossip_service.join().unwrap();
+
+    match matches.value_of("output") {
+        Some(output) => serde_json::to_writer_pretty(File::create(output)?, &topology)?,
+        None => {
+            serde_json::to_writer_pretty(io::stdout(), &topology)?;
+            println!();
+        }
+    }
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn error::Error>> {
     solana_logger::setup_with_default("solana=info");
 
@@ -322,6 +519,9 @@ fn main() -> Result<(), Box<dyn error::Error>> {
         ("rpc-url", Some(matches)) => {
             process_rpc_url(matches)?;
         }
+        ("crawl", Some(matches)) => {
+            process_crawl(matches)?;
+        }
         _ => unreachable!(),
     }
 
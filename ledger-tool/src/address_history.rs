@@ -0,0 +1,642 @@
+/// The `address-history` subcommands
+///
+/// Scans a range of confirmed blocks and writes, for each of a set of addresses, one CSV row per
+/// SOL balance change, SPL token balance change and parsed system or token instruction, inner
+/// instructions included, that touches the address. The token balance changes of the token
+/// accounts owned by an address are reported for the address as well.
+use clap::{value_t, App, Arg, ArgMatches};
+use log::*;
+use serde::Serialize;
+use solana_account_decoder::parse_token::spl_token_id_v2_0;
+use solana_clap_utils::{
+    input_parsers::pubkeys_of,
+    input_validators::{is_slot, is_valid_pubkey},
+};
+use solana_ledger::{blockstore::Blockstore, blockstore_db::BlockstoreError};
+use solana_sdk::{
+    clock::{Slot, UnixTimestamp},
+    instruction::CompiledInstruction,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_transaction_status::{
+    parse_instruction::ParsedInstructionEnum, parse_system::parse_system, parse_token::parse_token,
+    ConfirmedBlock, TransactionTokenBalance, TransactionWithStatusMeta,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::{self, stdout, Write},
+    str::FromStr,
+};
+
+const SOL_DECIMALS: u8 = 9;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressHistoryKind {
+    Sol,
+    Token,
+    Instruction,
+}
+
+/// One row of the output file. Amounts are decimal strings in whole units of the asset, SOL for
+/// `Sol` rows and the token for `Token` rows.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AddressHistoryRecord {
+    pub slot: Slot,
+    pub block_time: Option<UnixTimestamp>,
+    pub signature: String,
+    pub success: bool,
+    pub address: String,
+    pub kind: AddressHistoryKind,
+    pub mint: Option<String>,
+    /// The token account of `Token` rows, which is `address` unless the address owns it
+    pub token_account: Option<String>,
+    pub pre_balance: Option<String>,
+    pub post_balance: Option<String>,
+    pub change: Option<String>,
+    pub program: Option<&'static str>,
+    /// Index of the top-level instruction, `<index>.<inner index>` for inner instructions
+    pub instruction: Option<String>,
+    pub instruction_type: Option<String>,
+    /// The parsed instruction info as JSON
+    pub details: Option<String>,
+}
+
+/// Formats a raw `amount` of an asset with `decimals` decimal places, trailing zeros trimmed
+pub fn format_amount(amount: i128, decimals: u8) -> String {
+    let sign = if amount < 0 { "-" } else { "" };
+    let amount = amount.unsigned_abs();
+    let unit = 10u128.pow(decimals as u32);
+    let (whole, fraction) = (amount / unit, amount % unit);
+    if fraction == 0 {
+        format!("{}{}", sign, whole)
+    } else {
+        let fraction = format!("{:0width$}", fraction, width = decimals as usize);
+        format!("{}{}.{}", sign, whole, fraction.trim_end_matches('0'))
+    }
+}
+
+pub struct AddressHistory {
+    addresses: HashSet<Pubkey>,
+}
+
+impl AddressHistory {
+    pub fn new(addresses: impl IntoIterator<Item = Pubkey>) -> Self {
+        Self {
+            addresses: addresses.into_iter().collect(),
+        }
+    }
+
+    /// Returns the rows of every transaction in `block` that touches one of the addresses, in
+    /// block order
+    pub fn block_records(&self, slot: Slot, block: &ConfirmedBlock) -> Vec<AddressHistoryRecord> {
+        let mut records = vec![];
+        for transaction in &block.transactions {
+            self.transaction_records(slot, block.block_time, transaction, &mut records);
+        }
+        records
+    }
+
+    fn transaction_records(
+        &self,
+        slot: Slot,
+        block_time: Option<UnixTimestamp>,
+        transaction_with_meta: &TransactionWithStatusMeta,
+        records: &mut Vec<AddressHistoryRecord>,
+    ) {
+        let meta = match &transaction_with_meta.meta {
+            Some(meta) => meta,
+            None => return,
+        };
+        let message = transaction_with_meta.transaction.message();
+        let account_keys = &message.account_keys;
+        // The owner of a token account does not have to be part of the transaction
+        let is_owner = |balance: &TransactionTokenBalance| {
+            Pubkey::from_str(&balance.owner)
+                .map(|owner| self.addresses.contains(&owner))
+                .unwrap_or(false)
+        };
+        if !account_keys
+            .iter()
+            .any(|pubkey| self.addresses.contains(pubkey))
+            && !meta
+                .pre_token_balances
+                .iter()
+                .chain(meta.post_token_balances.iter())
+                .flatten()
+                .any(is_owner)
+        {
+            return;
+        }
+        let record = |address: &Pubkey, kind| AddressHistoryRecord {
+            slot,
+            block_time,
+            signature: transaction_with_meta.transaction.signatures[0].to_string(),
+            success: meta.status.is_ok(),
+            address: address.to_string(),
+            kind,
+            mint: None,
+            token_account: None,
+            pre_balance: None,
+            post_balance: None,
+            change: None,
+            program: None,
+            instruction: None,
+            instruction_type: None,
+            details: None,
+        };
+
+        for (index, address) in account_keys.iter().enumerate() {
+            if !self.addresses.contains(address) {
+                continue;
+            }
+            let (pre, post) = match (meta.pre_balances.get(index), meta.post_balances.get(index)) {
+                (Some(pre), Some(post)) if pre != post => (*pre, *post),
+                _ => continue,
+            };
+            records.push(AddressHistoryRecord {
+                pre_balance: Some(format_amount(pre as i128, SOL_DECIMALS)),
+                post_balance: Some(format_amount(post as i128, SOL_DECIMALS)),
+                change: Some(format_amount(post as i128 - pre as i128, SOL_DECIMALS)),
+                ..record(address, AddressHistoryKind::Sol)
+            });
+        }
+
+        // Token accounts that are created or closed by the transaction only have one side
+        let mut token_balances: BTreeMap<u8, (Option<&TransactionTokenBalance>, _)> =
+            BTreeMap::new();
+        for balance in meta.pre_token_balances.iter().flatten() {
+            token_balances.entry(balance.account_index).or_default().0 = Some(balance);
+        }
+        for balance in meta.post_token_balances.iter().flatten() {
+            token_balances.entry(balance.account_index).or_default().1 = Some(balance);
+        }
+        for (account_index, (pre, post)) in token_balances {
+            let token_account = match account_keys.get(account_index as usize) {
+                Some(token_account) => token_account,
+                None => continue,
+            };
+            let balance = pre.or(post).unwrap();
+            let owner = Pubkey::from_str(&balance.owner).ok();
+            let address = if self.addresses.contains(token_account) {
+                *token_account
+            } else {
+                match owner.filter(|owner| self.addresses.contains(owner)) {
+                    Some(owner) => owner,
+                    None => continue,
+                }
+            };
+            // A missing side is an empty account, an unparseable amount skips the row
+            let amount = |balance: Option<&TransactionTokenBalance>| match balance {
+                Some(balance) => balance
+                    .ui_token_amount
+                    .amount
+                    .parse::<u64>()
+                    .map(i128::from)
+                    .map_err(|_| &balance.ui_token_amount.amount),
+                None => Ok(0),
+            };
+            let (pre_amount, post_amount) = match (amount(pre), amount(post)) {
+                (Ok(pre_amount), Ok(post_amount)) => (pre_amount, post_amount),
+                (Err(amount), _) | (_, Err(amount)) => {
+                    warn!(
+                        "Skipping the token balance of {} in transaction {} at slot {}, unable \
+                         to parse amount {:?}",
+                        address, transaction_with_meta.transaction.signatures[0], slot, amount
+                    );
+                    continue;
+                }
+            };
+            if pre_amount == post_amount {
+                continue;
+            }
+            let decimals = balance.ui_token_amount.decimals;
+            records.push(AddressHistoryRecord {
+                mint: Some(balance.mint.clone()),
+                token_account: Some(token_account.to_string()),
+                pre_balance: Some(format_amount(pre_amount, decimals)),
+                post_balance: Some(format_amount(post_amount, decimals)),
+                change: Some(format_amount(post_amount - pre_amount, decimals)),
+                ..record(&address, AddressHistoryKind::Token)
+            });
+        }
+
+        let inner_instructions = meta.inner_instructions.as_ref();
+        for (index, instruction) in message.instructions.iter().enumerate() {
+            let inner = inner_instructions
+                .and_then(|inner_instructions| {
+                    inner_instructions
+                        .iter()
+                        .find(|inner| inner.index as usize == index)
+                })
+                .map(|inner| inner.instructions.as_slice())
+                .unwrap_or_default();
+            let instructions = std::iter::once((index.to_string(), instruction)).chain(
+                inner.iter().enumerate().map(|(inner_index, instruction)| {
+                    (format!("{}.{}", index, inner_index), instruction)
+                }),
+            );
+            for (position, instruction) in instructions {
+                let (program, parsed) = match parse_instruction(instruction, account_keys) {
+                    Some(parsed) => parsed,
+                    None => continue,
+                };
+                let addresses = instruction
+                    .accounts
+                    .iter()
+                    .filter_map(|index| account_keys.get(*index as usize))
+                    .filter(|address| self.addresses.contains(address))
+                    .collect::<HashSet<_>>();
+                for address in addresses {
+                    records.push(AddressHistoryRecord {
+                        program: Some(program),
+                        instruction: Some(position.clone()),
+                        instruction_type: Some(parsed.instruction_type.clone()),
+                        details: Some(parsed.info.to_string()),
+                        ..record(address, AddressHistoryKind::Instruction)
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Parses system and SPL token instructions, returning the program name with the instruction
+fn parse_instruction(
+    instruction: &CompiledInstruction,
+    account_keys: &[Pubkey],
+) -> Option<(&'static str, ParsedInstructionEnum)> {
+    let program_id = account_keys.get(instruction.program_id_index as usize)?;
+    if *program_id == system_program::id() {
+        parse_system(instruction, account_keys)
+            .ok()
+            .map(|parsed| ("system", parsed))
+    } else if *program_id == spl_token_id_v2_0() {
+        parse_token(instruction, account_keys)
+            .ok()
+            .map(|parsed| ("spl-token", parsed))
+    } else {
+        None
+    }
+}
+
+/// Writes the rows of a sequence of blocks to a CSV file
+pub struct AddressHistoryWriter<W: Write> {
+    history: AddressHistory,
+    writer: csv::Writer<W>,
+    num_blocks: usize,
+    num_records: usize,
+}
+
+impl<W: Write> AddressHistoryWriter<W> {
+    pub fn new(addresses: impl IntoIterator<Item = Pubkey>, writer: W) -> Self {
+        Self {
+            history: AddressHistory::new(addresses),
+            writer: csv::WriterBuilder::new().from_writer(writer),
+            num_blocks: 0,
+            num_records: 0,
+        }
+    }
+
+    pub fn write_block(&mut self, slot: Slot, block: &ConfirmedBlock) -> Result<(), String> {
+        for record in self.history.block_records(slot, block) {
+            self.writer
+                .serialize(record)
+                .map_err(|err| format!("Unable to write slot {}: {}", slot, err))?;
+            self.num_records += 1;
+        }
+        self.num_blocks += 1;
+        Ok(())
+    }
+
+    /// Flushes the output, returning the number of blocks scanned and rows written
+    pub fn finish(mut self) -> Result<(usize, usize), String> {
+        self.writer.flush().map_err(|err| err.to_string())?;
+        Ok((self.num_blocks, self.num_records))
+    }
+}
+
+/// Adds the arguments shared by the blockstore and BigTable `address-history` subcommands
+pub fn address_history_args<'a, 'b>(subcommand: App<'a, 'b>) -> App<'a, 'b> {
+    subcommand
+        .arg(
+            Arg::with_name("addresses")
+                .long("address")
+                .value_name("ADDRESS")
+                .validator(is_valid_pubkey)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(true)
+                .help(
+                    "Address to report; SPL token balances are reported for token accounts \
+                       and for the owners of token accounts",
+                ),
+        )
+        .arg(
+            Arg::with_name("starting_slot")
+                .long("starting-slot")
+                .value_name("SLOT")
+                .validator(is_slot)
+                .takes_value(true)
+                .default_value("0")
+                .help("Start scanning at this slot"),
+        )
+        .arg(
+            Arg::with_name("ending_slot")
+                .long("ending-slot")
+                .value_name("SLOT")
+                .validator(is_slot)
+                .takes_value(true)
+                .help("Stop scanning after this slot [default: last available slot]"),
+        )
+        .arg(
+            Arg::with_name("output_file")
+                .long("output-file")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Write the CSV output to FILE [default: stdout]"),
+        )
+}
+
+/// Returns the slot range and the writer configured by `address_history_args`
+pub fn address_history_writer_of(
+    arg_matches: &ArgMatches<'_>,
+) -> (Slot, Slot, AddressHistoryWriter<Box<dyn Write>>) {
+    let addresses = pubkeys_of(arg_matches, "addresses").unwrap();
+    let starting_slot = value_t!(arg_matches, "starting_slot", Slot).unwrap_or(0);
+    let ending_slot = value_t!(arg_matches, "ending_slot", Slot).unwrap_or(Slot::MAX);
+    let output: Box<dyn Write> = match arg_matches.value_of("output_file") {
+        Some(output_file) => Box::new(io::BufWriter::new(
+            File::create(output_file).unwrap_or_else(|err| {
+                eprintln!("Unable to create {}: {}", output_file, err);
+                std::process::exit(1);
+            }),
+        )),
+        None => Box::new(io::BufWriter::new(stdout())),
+    };
+    (
+        starting_slot,
+        ending_slot,
+        AddressHistoryWriter::new(addresses, output),
+    )
+}
+
+/// Writes the history of the rooted blocks from `starting_slot` to `ending_slot` in `blockstore`.
+/// The scan starts at the first root at or after both `starting_slot` and the lowest slot of the
+/// ledger, so that the roots without a block, like the snapshot slot of a ledger bootstrapped
+/// from a snapshot, are skipped. The roots whose block is missing or was cleaned up during the
+/// scan are skipped as well, and returned.
+pub fn write_blockstore_history<W: Write>(
+    blockstore: &Blockstore,
+    starting_slot: Slot,
+    ending_slot: Slot,
+    writer: &mut AddressHistoryWriter<W>,
+) -> Result<Vec<Slot>, String> {
+    let slots = blockstore
+        .rooted_slot_iterator(starting_slot.max(blockstore.lowest_slot()))
+        .map_err(|err| format!("Failed to iterate rooted slots: {:?}", err))?;
+    let mut skipped_slots = vec![];
+    for slot in slots.take_while(|slot| *slot <= ending_slot) {
+        let block = match blockstore.get_rooted_block(slot, false) {
+            Ok(block) => block,
+            Err(err @ BlockstoreError::SlotCleanedUp)
+            | Err(err @ BlockstoreError::SlotUnavailable) => {
+                warn!("Skipping block {}: {:?}", slot, err);
+                skipped_slots.push(slot);
+                continue;
+            }
+            Err(err) => return Err(format!("Failed to load block {}: {:?}", slot, err)),
+        };
+        writer.write_block(slot, &block)?;
+    }
+    Ok(skipped_slots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_account_decoder::parse_token::UiTokenAmount;
+    use solana_ledger::{blockstore::make_slot_entries, get_tmp_ledger_path};
+    use solana_sdk::{
+        hash::Hash,
+        message::Message,
+        native_token::LAMPORTS_PER_SOL,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    };
+    use solana_transaction_status::{InnerInstructions, TransactionStatusMeta};
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(0, 9), "0");
+        assert_eq!(format_amount(LAMPORTS_PER_SOL as i128, 9), "1");
+        assert_eq!(format_amount(1_500_000_000, 9), "1.5");
+        assert_eq!(format_amount(-5_000, 9), "-0.000005");
+        assert_eq!(format_amount(123, 0), "123");
+        assert_eq!(format_amount(-123, 2), "-1.23");
+    }
+
+    #[test]
+    fn test_block_records() {
+        let payer = Keypair::new();
+        let to = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let message = Message::new(
+            &[system_instruction::transfer(&payer.pubkey(), &to, 100)],
+            Some(&payer.pubkey()),
+        );
+        let transaction = Transaction::new(&[&payer], message, Hash::default());
+        let account_keys = transaction.message().account_keys.clone();
+        let to_index = account_keys.iter().position(|key| *key == to).unwrap() as u8;
+        let payer_index = account_keys
+            .iter()
+            .position(|key| *key == payer.pubkey())
+            .unwrap() as u8;
+        let inner_transfer = CompiledInstruction::new(
+            account_keys
+                .iter()
+                .position(|key| *key == system_program::id())
+                .unwrap() as u8,
+            &system_instruction::SystemInstruction::Transfer { lamports: 7 },
+            vec![payer_index, to_index],
+        );
+        let mut pre_balances = vec![0; account_keys.len()];
+        let mut post_balances = vec![0; account_keys.len()];
+        pre_balances[payer_index as usize] = LAMPORTS_PER_SOL;
+        post_balances[payer_index as usize] = LAMPORTS_PER_SOL - 5_107;
+        post_balances[to_index as usize] = 107;
+
+        let block = ConfirmedBlock {
+            previous_blockhash: Hash::default().to_string(),
+            blockhash: Hash::default().to_string(),
+            parent_slot: 4,
+            transactions: vec![TransactionWithStatusMeta {
+                transaction,
+                meta: Some(TransactionStatusMeta {
+                    status: Ok(()),
+                    fee: 5_000,
+                    pre_balances,
+                    post_balances,
+                    inner_instructions: Some(vec![InnerInstructions {
+                        index: 0,
+                        instructions: vec![inner_transfer],
+                    }]),
+                    log_messages: None,
+                    pre_token_balances: None,
+                    post_token_balances: None,
+                    rewards: None,
+                }),
+            }],
+            rewards: vec![],
+            block_time: Some(1_600_000_000),
+            block_height: Some(5),
+        };
+
+        assert!(AddressHistory::new(vec![other])
+            .block_records(5, &block)
+            .is_empty());
+
+        let records = AddressHistory::new(vec![to, other]).block_records(5, &block);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].kind, AddressHistoryKind::Sol);
+        assert_eq!(records[0].block_time, Some(1_600_000_000));
+        assert_eq!(records[0].pre_balance.as_deref(), Some("0"));
+        assert_eq!(records[0].change.as_deref(), Some("0.000000107"));
+        assert_eq!(records[1].kind, AddressHistoryKind::Instruction);
+        assert_eq!(records[1].program, Some("system"));
+        assert_eq!(records[1].instruction.as_deref(), Some("0"));
+        assert_eq!(records[1].instruction_type.as_deref(), Some("transfer"));
+        assert_eq!(records[2].instruction.as_deref(), Some("0.0"));
+        assert!(records[2]
+            .details
+            .as_ref()
+            .unwrap()
+            .contains("\"lamports\":7"));
+
+        let mut writer = AddressHistoryWriter::new(vec![to], vec![]);
+        writer.write_block(5, &block).unwrap();
+        assert_eq!(writer.finish().unwrap(), (1, 3));
+    }
+
+    #[test]
+    fn test_token_records() {
+        let payer = Keypair::new();
+        let token_account = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let message = Message::new(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &token_account,
+                1,
+            )],
+            Some(&payer.pubkey()),
+        );
+        let transaction = Transaction::new(&[&payer], message, Hash::default());
+        let account_index = transaction
+            .message()
+            .account_keys
+            .iter()
+            .position(|key| *key == token_account)
+            .unwrap() as u8;
+        let token_balance = |amount: &str| TransactionTokenBalance {
+            account_index,
+            mint: Pubkey::new_unique().to_string(),
+            ui_token_amount: UiTokenAmount {
+                ui_amount: None,
+                decimals: 2,
+                amount: amount.to_string(),
+                ui_amount_string: String::default(),
+            },
+            owner: owner.to_string(),
+        };
+        let block = |pre_amount: &str, post_amount: &str| ConfirmedBlock {
+            previous_blockhash: Hash::default().to_string(),
+            blockhash: Hash::default().to_string(),
+            parent_slot: 4,
+            transactions: vec![TransactionWithStatusMeta {
+                transaction: transaction.clone(),
+                meta: Some(TransactionStatusMeta {
+                    status: Ok(()),
+                    fee: 5_000,
+                    pre_balances: vec![0; 3],
+                    post_balances: vec![0; 3],
+                    inner_instructions: None,
+                    log_messages: None,
+                    pre_token_balances: Some(vec![token_balance(pre_amount)]),
+                    post_token_balances: Some(vec![token_balance(post_amount)]),
+                    rewards: None,
+                }),
+            }],
+            rewards: vec![],
+            block_time: None,
+            block_height: None,
+        };
+
+        // The owner is not part of the transaction
+        let records = AddressHistory::new(vec![owner]).block_records(5, &block("150", "100"));
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].kind, AddressHistoryKind::Token);
+        assert_eq!(records[0].address, owner.to_string());
+        assert_eq!(records[0].token_account, Some(token_account.to_string()));
+        assert_eq!(records[0].change.as_deref(), Some("-0.5"));
+
+        // The token account also gets a row for the transfer instruction
+        let records = AddressHistory::new(vec![token_account]).block_records(5, &block("1", "2"));
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].kind, AddressHistoryKind::Token);
+        assert_eq!(records[0].address, token_account.to_string());
+        assert_eq!(records[1].kind, AddressHistoryKind::Instruction);
+
+        // Unparseable amounts skip the row instead of reporting a zero balance
+        assert!(AddressHistory::new(vec![owner])
+            .block_records(5, &block("1.5", "2"))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_write_blockstore_history_from_snapshot() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&ledger_path).unwrap();
+            // A ledger bootstrapped from a snapshot of slot 3 has the snapshot slot rooted, but
+            // not its block
+            blockstore.set_roots(std::iter::once(&3)).unwrap();
+            for slot in 4..=7 {
+                let (shreds, _) = make_slot_entries(slot, slot - 1, 1);
+                blockstore.insert_shreds(shreds, None, false).unwrap();
+            }
+            blockstore.set_roots(vec![5, 7].iter()).unwrap();
+
+            let mut writer = AddressHistoryWriter::new(vec![Pubkey::new_unique()], vec![]);
+            assert!(
+                write_blockstore_history(&blockstore, 0, Slot::MAX, &mut writer)
+                    .unwrap()
+                    .is_empty()
+            );
+            assert_eq!(writer.finish().unwrap(), (2, 0));
+
+            // Slot 6 is not a root, the scan starts at slot 7
+            let mut writer = AddressHistoryWriter::new(vec![Pubkey::new_unique()], vec![]);
+            assert!(
+                write_blockstore_history(&blockstore, 6, Slot::MAX, &mut writer)
+                    .unwrap()
+                    .is_empty()
+            );
+            assert_eq!(writer.finish().unwrap(), (1, 0));
+
+            // The blocks missing from the ledger are skipped and reported
+            blockstore.set_roots(std::iter::once(&9)).unwrap();
+            let mut writer = AddressHistoryWriter::new(vec![Pubkey::new_unique()], vec![]);
+            assert_eq!(
+                write_blockstore_history(&blockstore, 6, Slot::MAX, &mut writer).unwrap(),
+                vec![9]
+            );
+            assert_eq!(writer.finish().unwrap(), (1, 0));
+        }
+        Blockstore::destroy(&ledger_path).expect("Expected successful database destruction");
+    }
+}
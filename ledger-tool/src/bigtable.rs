@@ -1,4 +1,5 @@
 /// The `bigtable` subcommand
+use crate::address_history::{self, AddressHistoryWriter};
 use clap::{value_t, value_t_or_exit, App, AppSettings, Arg, ArgMatches, SubCommand};
 use solana_clap_utils::{
     input_parsers::pubkey_of,
@@ -12,6 +13,7 @@ use solana_ledger::{blockstore::Blockstore, blockstore_db::AccessType};
 use solana_sdk::{clock::Slot, pubkey::Pubkey, signature::Signature};
 use solana_transaction_status::{ConfirmedBlock, EncodedTransaction, UiTransactionEncoding};
 use std::{
+    io::Write,
     path::Path,
     process::exit,
     result::Result,
@@ -78,6 +80,35 @@ async fn blocks(starting_slot: Slot, limit: usize) -> Result<(), Box<dyn std::er
     Ok(())
 }
 
+async fn address_history(
+    starting_slot: Slot,
+    ending_slot: Slot,
+    mut writer: AddressHistoryWriter<Box<dyn Write>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bigtable = solana_storage_bigtable::LedgerStorage::new(true, None)
+        .await
+        .map_err(|err| format!("Failed to connect to storage: {:?}", err))?;
+
+    let mut next_slot = starting_slot;
+    'outer: while next_slot <= ending_slot {
+        let slots = bigtable.get_confirmed_blocks(next_slot, 1000).await?;
+        if slots.is_empty() {
+            break;
+        }
+        for slot in slots {
+            if slot > ending_slot {
+                break 'outer;
+            }
+            let block = bigtable.get_confirmed_block(slot).await?;
+            writer.write_block(slot, &block)?;
+            next_slot = slot.saturating_add(1);
+        }
+    }
+    let (num_blocks, num_records) = writer.finish()?;
+    eprintln!("{} rows written from {} blocks", num_records, num_blocks);
+    Ok(())
+}
+
 async fn confirm(
     signature: &Signature,
     verbose: bool,
@@ -306,6 +337,12 @@ impl BigTableSubCommand for App<'_, '_> {
                                 .required(true),
                         ),
                 )
+                .subcommand(address_history::address_history_args(
+                    SubCommand::with_name("address-history").about(
+                        "Write the SOL and SPL token balance changes and the system and token \
+                         instructions of addresses in a range of confirmed blocks to a CSV file",
+                    ),
+                ))
                 .subcommand(
                     SubCommand::with_name("confirm")
                         .about("Confirm transaction by signature")
@@ -426,6 +463,12 @@ pub fn bigtable_process_command(ledger_path: &Path, matches: &ArgMatches<'_>) {
 
             runtime.block_on(blocks(starting_slot, limit))
         }
+        ("address-history", Some(arg_matches)) => {
+            let (starting_slot, ending_slot, writer) =
+                address_history::address_history_writer_of(arg_matches);
+
+            runtime.block_on(address_history(starting_slot, ending_slot, writer))
+        }
         ("confirm", Some(arg_matches)) => {
             let signature = arg_matches
                 .value_of("signature")
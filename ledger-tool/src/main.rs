@@ -28,6 +28,7 @@ use solana_ledger::{
 use solana_runtime::{
     bank::{Bank, RewardCalculationEvent},
     bank_forks::BankForks,
+    bank_hash_details::BankHashDetails,
     hardened_unpack::{open_genesis_config, MAX_GENESIS_ARCHIVE_UNPACKED_SIZE},
     snapshot_config::SnapshotConfig,
     snapshot_utils::{self, ArchiveFormat, SnapshotVersion, DEFAULT_MAX_SNAPSHOTS_TO_RETAIN},
@@ -61,10 +62,14 @@ use std::{
     process::{exit, Command, Stdio},
     str::FromStr,
     sync::{Arc, RwLock},
+    thread::sleep,
+    time::Duration,
 };
 
+mod address_history;
 mod bigtable;
 use bigtable::*;
+mod replay_trace;
 
 #[derive(PartialEq)]
 enum LedgerOutputMethod {
@@ -286,6 +291,70 @@ fn output_ledger(
     }
 }
 
+// Prints slots as the validator that owns the ledger completes them. Incomplete slots older
+// than the latest root were abandoned and are skipped.
+fn follow_ledger(
+    blockstore: &Blockstore,
+    starting_slot: Slot,
+    ending_slot: Slot,
+    allow_dead_slots: bool,
+    verbose_level: u64,
+    only_rooted: bool,
+    follow_interval: Duration,
+) {
+    let mut next_slot = starting_slot;
+    loop {
+        if let Err(err) = blockstore.try_catch_up_with_primary() {
+            eprintln!("Failed to catch up with primary blockstore: {:?}", err);
+            exit(1);
+        }
+        let last_root = blockstore.last_root();
+        let slot_iterator = blockstore
+            .slot_meta_iterator(next_slot)
+            .unwrap_or_else(|err| {
+                eprintln!(
+                    "Failed to load entries starting from slot {}: {:?}",
+                    next_slot, err
+                );
+                exit(1);
+            });
+
+        for (slot, slot_meta) in slot_iterator {
+            if slot > ending_slot {
+                return;
+            }
+            let is_root = blockstore.is_root(slot);
+            if only_rooted && !is_root {
+                if slot > last_root {
+                    break;
+                }
+                next_slot = slot + 1;
+                continue;
+            }
+            if !slot_meta.is_full() && !blockstore.is_dead(slot) {
+                if slot > last_root {
+                    break;
+                }
+                next_slot = slot + 1;
+                continue;
+            }
+
+            println!("Slot {} root?: {}", slot, is_root);
+            if let Err(err) = output_slot(
+                blockstore,
+                slot,
+                allow_dead_slots,
+                &LedgerOutputMethod::Print,
+                verbose_level,
+            ) {
+                eprintln!("{}", err);
+            }
+            next_slot = slot + 1;
+        }
+        sleep(follow_interval);
+    }
+}
+
 fn render_dot(dot: String, output_file: &str, output_format: &str) -> io::Result<()> {
     let mut child = Command::new("dot")
         .arg(format!("-T{}", output_format))
@@ -729,6 +798,46 @@ fn load_bank_forks(
     )
 }
 
+fn load_bank_hash_details(
+    arg_matches: &ArgMatches,
+    ledger_path: &Path,
+    wal_recovery_mode: Option<BlockstoreRecoveryMode>,
+    snapshot_archive_path: Option<PathBuf>,
+) -> BankHashDetails {
+    let slot = value_t_or_exit!(arg_matches, "halt_at_slot", Slot);
+    let process_options = ProcessOptions {
+        dev_halt_at_slot: Some(slot),
+        new_hard_forks: hardforks_of(arg_matches, "hard_forks"),
+        poh_verify: false,
+        accounts_db_caching_enabled: !arg_matches.is_present("no_accounts_db_caching"),
+        ..ProcessOptions::default()
+    };
+    let blockstore = open_blockstore(
+        ledger_path,
+        AccessType::TryPrimaryThenSecondary,
+        wal_recovery_mode,
+    );
+    let (bank_forks, _leader_schedule_cache, _snapshot_hash) = load_bank_forks(
+        arg_matches,
+        &open_genesis_config_by(ledger_path, arg_matches),
+        &blockstore,
+        process_options,
+        snapshot_archive_path,
+    )
+    .unwrap_or_else(|err| {
+        eprintln!("Failed to load ledger {:?}: {:?}", ledger_path, err);
+        exit(1);
+    });
+    let bank = bank_forks.get(slot).unwrap_or_else(|| {
+        eprintln!(
+            "Slot {} is not available in {:?}, it may precede the snapshot slot",
+            slot, ledger_path
+        );
+        exit(1);
+    });
+    BankHashDetails::new(bank)
+}
+
 fn compute_slot_cost(blockstore: &Blockstore, slot: Slot) -> Result<(), String> {
     if blockstore.is_dead(slot) {
         return Err("Dead slot".to_string());
@@ -752,11 +861,12 @@ fn compute_slot_cost(blockstore: &Blockstore, slot: Slot) -> Result<(), String>
         for transaction in &entry.transactions {
             programs += transaction.message().instructions.len();
             let tx_cost = cost_model.calculate_cost(transaction);
-            if cost_tracker.try_add(tx_cost).is_err() {
+            if let Err(err) = cost_tracker.try_add(tx_cost) {
                 println!(
-                    "Slot: {}, CostModel rejected transaction {:?}, stats {:?}!",
+                    "Slot: {}, CostModel rejected transaction {:?}, reason: {}, stats {:?}!",
                     slot,
                     transaction,
+                    err,
                     cost_tracker.get_stats()
                 );
             }
@@ -981,6 +1091,23 @@ fn main() {
                     .takes_value(false)
                     .help("Only print root slots"),
             )
+            .arg(
+                Arg::with_name("follow")
+                    .long("follow")
+                    .takes_value(false)
+                    .conflicts_with("num_slots")
+                    .help("Keep printing slots as they are completed by the validator that \
+                           owns the ledger, opening it as a secondary"),
+            )
+            .arg(
+                Arg::with_name("follow_interval_ms")
+                    .long("follow-interval-ms")
+                    .value_name("MILLISECONDS")
+                    .takes_value(true)
+                    .validator(is_parsable::<u64>)
+                    .default_value("400")
+                    .help("How often to catch up with the validator's blockstore"),
+            )
         )
         .subcommand(
             SubCommand::with_name("copy")
@@ -1097,6 +1224,50 @@ fn main() {
             .about("Prints the hash of the working bank after reading the ledger")
             .arg(&max_genesis_archive_unpacked_size_arg)
         )
+        .subcommand(
+            SubCommand::with_name("bank-hash-details")
+            .about("Write the bank hash inputs of a slot, including every account stored \
+                    in the slot, to a JSON file")
+            .arg(&no_snapshot_arg)
+            .arg(&account_paths_arg)
+            .arg(&halt_at_slot_arg.clone().required(true))
+            .arg(&hard_forks_arg)
+            .arg(&no_accounts_db_caching_arg)
+            .arg(&max_genesis_archive_unpacked_size_arg)
+            .arg(
+                Arg::with_name("output_directory")
+                    .long("output-dir")
+                    .value_name("DIR")
+                    .takes_value(true)
+                    .help("Output directory [default: LEDGER/bank_hash_details]"),
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("bank-hash-diff")
+            .about("Compare the bank hash inputs of a slot against another ledger or a \
+                    file written by bank-hash-details, and print the diverging accounts")
+            .arg(&no_snapshot_arg)
+            .arg(&halt_at_slot_arg.clone().required(true))
+            .arg(&hard_forks_arg)
+            .arg(&no_accounts_db_caching_arg)
+            .arg(&max_genesis_archive_unpacked_size_arg)
+            .arg(
+                Arg::with_name("other_ledger")
+                    .long("other-ledger")
+                    .value_name("DIR")
+                    .takes_value(true)
+                    .required_unless("expected_details")
+                    .conflicts_with("expected_details")
+                    .help("Ledger holding the expected version of the slot"),
+            )
+            .arg(
+                Arg::with_name("expected_details")
+                    .long("expected-details")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .help("File with the expected bank hash details of the slot"),
+            )
+        )
         .subcommand(
             SubCommand::with_name("bounds")
             .about("Print lowest and highest non-empty slots. \
@@ -1139,6 +1310,45 @@ fn main() {
                     .takes_value(false)
                     .help("After verifying the ledger, print some information about the account stores"),
             )
+        ).subcommand(
+            SubCommand::with_name("replay-trace")
+            .about("Replay a range of slots and output a JSON line per transaction with \
+                    account states, the instruction call tree and log messages")
+            .arg(&no_snapshot_arg)
+            .arg(&account_paths_arg)
+            .arg(&hard_forks_arg)
+            .arg(&no_bpf_jit_arg)
+            .arg(&max_genesis_archive_unpacked_size_arg)
+            .arg(
+                Arg::with_name("starting_slot")
+                    .long("starting-slot")
+                    .value_name("SLOT")
+                    .validator(is_slot)
+                    .takes_value(true)
+                    .required(true)
+                    .help("First slot to replay; the ledger is loaded up to its parent slot"),
+            )
+            .arg(
+                Arg::with_name("ending_slot")
+                    .long("ending-slot")
+                    .value_name("SLOT")
+                    .validator(is_slot)
+                    .takes_value(true)
+                    .help("Last slot to replay [default: starting slot]"),
+            )
+            .arg(
+                Arg::with_name("trace_file")
+                    .long("trace-file")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .help("Write the trace to FILE [default: stdout]"),
+            )
+        ).subcommand(
+            address_history::address_history_args(
+                SubCommand::with_name("address-history")
+                .about("Write the SOL and SPL token balance changes and the system and token \
+                        instructions of addresses in a range of rooted blocks to a CSV file")
+            )
         ).subcommand(
             SubCommand::with_name("graph")
             .about("Create a Graphviz rendering of the ledger")
@@ -1521,20 +1731,34 @@ fn main() {
             let allow_dead_slots = arg_matches.is_present("allow_dead_slots");
             let only_rooted = arg_matches.is_present("only_rooted");
             let verbose = matches.occurrences_of("verbose");
-            output_ledger(
-                open_blockstore(
-                    &ledger_path,
-                    AccessType::TryPrimaryThenSecondary,
-                    wal_recovery_mode,
-                ),
-                starting_slot,
-                ending_slot,
-                allow_dead_slots,
-                LedgerOutputMethod::Print,
-                num_slots,
-                verbose,
-                only_rooted,
-            );
+            if arg_matches.is_present("follow") {
+                let follow_interval =
+                    Duration::from_millis(value_t_or_exit!(arg_matches, "follow_interval_ms", u64));
+                follow_ledger(
+                    &open_blockstore(&ledger_path, AccessType::Secondary, wal_recovery_mode),
+                    starting_slot,
+                    ending_slot,
+                    allow_dead_slots,
+                    verbose,
+                    only_rooted,
+                    follow_interval,
+                );
+            } else {
+                output_ledger(
+                    open_blockstore(
+                        &ledger_path,
+                        AccessType::TryPrimaryThenSecondary,
+                        wal_recovery_mode,
+                    ),
+                    starting_slot,
+                    ending_slot,
+                    allow_dead_slots,
+                    LedgerOutputMethod::Print,
+                    num_slots,
+                    verbose,
+                    only_rooted,
+                );
+            }
         }
         ("copy", Some(arg_matches)) => {
             let starting_slot = value_t_or_exit!(arg_matches, "starting_slot", Slot);
@@ -1695,6 +1919,132 @@ fn main() {
                 }
             }
         }
+        ("bank-hash-details", Some(arg_matches)) => {
+            let details = load_bank_hash_details(
+                arg_matches,
+                &ledger_path,
+                wal_recovery_mode,
+                snapshot_archive_path,
+            );
+            let output_directory = value_t!(arg_matches, "output_directory", PathBuf)
+                .unwrap_or_else(|_| ledger_path.join("bank_hash_details"));
+            match details.write_to_dir(&output_directory) {
+                Ok(path) => println!("Wrote {}", path.display()),
+                Err(err) => {
+                    eprintln!("Unable to write bank hash details: {}", err);
+                    exit(1);
+                }
+            }
+        }
+        ("bank-hash-diff", Some(arg_matches)) => {
+            let expected = match value_t!(arg_matches, "expected_details", PathBuf) {
+                Ok(expected_details) => BankHashDetails::read_from_file(&expected_details)
+                    .unwrap_or_else(|err| {
+                        eprintln!("Unable to read {}: {}", expected_details.display(), err);
+                        exit(1);
+                    }),
+                Err(_) => {
+                    let other_ledger = value_t_or_exit!(arg_matches, "other_ledger", PathBuf);
+                    load_bank_hash_details(
+                        arg_matches,
+                        &other_ledger,
+                        wal_recovery_mode.clone(),
+                        None,
+                    )
+                }
+            };
+            let actual = load_bank_hash_details(
+                arg_matches,
+                &ledger_path,
+                wal_recovery_mode,
+                snapshot_archive_path,
+            );
+
+            let mismatched_fields = actual.mismatched_fields(&expected);
+            let account_diffs = actual.diff_accounts(&expected);
+            println!("Slot {}", actual.slot);
+            for &(field, expected_value, actual_value) in &[
+                ("bank hash", &expected.bank_hash, &actual.bank_hash),
+                (
+                    "parent bank hash",
+                    &expected.parent_bank_hash,
+                    &actual.parent_bank_hash,
+                ),
+                (
+                    "accounts delta hash",
+                    &expected.accounts_delta_hash,
+                    &actual.accounts_delta_hash,
+                ),
+                (
+                    "last blockhash",
+                    &expected.last_blockhash,
+                    &actual.last_blockhash,
+                ),
+            ] {
+                let marker = if expected_value == actual_value {
+                    " "
+                } else {
+                    "*"
+                };
+                println!(
+                    "{} {}: expected {}, actual {}",
+                    marker, field, expected_value, actual_value
+                );
+            }
+            let marker = if expected.signature_count == actual.signature_count {
+                " "
+            } else {
+                "*"
+            };
+            println!(
+                "{} signature count: expected {}, actual {}",
+                marker, expected.signature_count, actual.signature_count
+            );
+            println!(
+                "Accounts stored: expected {}, actual {}, diverging {}",
+                expected.accounts.len(),
+                actual.accounts.len(),
+                account_diffs.len()
+            );
+            for diff in &account_diffs {
+                match (&diff.expected, &diff.actual) {
+                    (Some(_), None) => println!("  {}: missing from actual", diff.pubkey),
+                    (None, Some(_)) => println!("  {}: missing from expected", diff.pubkey),
+                    (Some(expected), Some(actual)) => {
+                        println!("  {}: {}", diff.pubkey, diff.mismatched_fields.join(", "));
+                        if expected.lamports != actual.lamports {
+                            println!(
+                                "    lamports: expected {}, actual {}",
+                                expected.lamports, actual.lamports
+                            );
+                        }
+                        if expected.owner != actual.owner {
+                            println!(
+                                "    owner: expected {}, actual {}",
+                                expected.owner, actual.owner
+                            );
+                        }
+                        if expected.rent_epoch != actual.rent_epoch {
+                            println!(
+                                "    rent epoch: expected {}, actual {}",
+                                expected.rent_epoch, actual.rent_epoch
+                            );
+                        }
+                        if expected.data != actual.data {
+                            println!(
+                                "    data: expected {} bytes, actual {} bytes",
+                                base64::decode(&expected.data).unwrap_or_default().len(),
+                                base64::decode(&actual.data).unwrap_or_default().len()
+                            );
+                        }
+                    }
+                    (None, None) => unreachable!(),
+                }
+            }
+            if !mismatched_fields.is_empty() || !account_diffs.is_empty() {
+                exit(1);
+            }
+        }
         ("slot", Some(arg_matches)) => {
             let slots = values_t_or_exit!(arg_matches, "slots", Slot);
             let allow_dead_slots = arg_matches.is_present("allow_dead_slots");
@@ -1878,6 +2228,106 @@ fn main() {
             }
             println!("Ok");
         }
+        ("replay-trace", Some(arg_matches)) => {
+            let starting_slot = value_t_or_exit!(arg_matches, "starting_slot", Slot);
+            if starting_slot == 0 {
+                eprintln!("Slot 0 is the genesis bank, it cannot be replayed");
+                exit(1);
+            }
+            let ending_slot = value_t!(arg_matches, "ending_slot", Slot).unwrap_or(starting_slot);
+            let blockstore = open_blockstore(
+                &ledger_path,
+                AccessType::TryPrimaryThenSecondary,
+                wal_recovery_mode,
+            );
+            let parent_slot = match blockstore.meta(starting_slot) {
+                Ok(Some(meta)) => meta.parent_slot,
+                _ => {
+                    eprintln!("Slot {} is not available", starting_slot);
+                    exit(1);
+                }
+            };
+            let process_options = ProcessOptions {
+                dev_halt_at_slot: Some(parent_slot),
+                new_hard_forks: hardforks_of(arg_matches, "hard_forks"),
+                poh_verify: false,
+                bpf_jit: !arg_matches.is_present("no_bpf_jit"),
+                ..ProcessOptions::default()
+            };
+            let (bank_forks, leader_schedule_cache, _snapshot_hash) = load_bank_forks(
+                arg_matches,
+                &open_genesis_config_by(&ledger_path, arg_matches),
+                &blockstore,
+                process_options,
+                snapshot_archive_path,
+            )
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to load ledger: {:?}", err);
+                exit(1);
+            });
+            let parent_bank = bank_forks.get(parent_slot).cloned().unwrap_or_else(|| {
+                eprintln!(
+                    "Parent slot {} is not available, the snapshot may be too recent",
+                    parent_slot
+                );
+                exit(1);
+            });
+
+            let mut output: Box<dyn Write> = match arg_matches.value_of("trace_file") {
+                Some(trace_file) => Box::new(io::BufWriter::new(
+                    File::create(trace_file).unwrap_or_else(|err| {
+                        eprintln!("Unable to create {}: {}", trace_file, err);
+                        exit(1);
+                    }),
+                )),
+                None => Box::new(io::BufWriter::new(stdout())),
+            };
+            if let Err(err) = replay_trace::trace_slots(
+                &blockstore,
+                &leader_schedule_cache,
+                parent_bank,
+                starting_slot,
+                ending_slot,
+                &mut output,
+            )
+            .and_then(|()| output.flush().map_err(|err| err.to_string()))
+            {
+                eprintln!("Replay trace failed: {}", err);
+                exit(1);
+            }
+        }
+        ("address-history", Some(arg_matches)) => {
+            let (starting_slot, ending_slot, mut writer) =
+                address_history::address_history_writer_of(arg_matches);
+            let blockstore = open_blockstore(
+                &ledger_path,
+                AccessType::TryPrimaryThenSecondary,
+                wal_recovery_mode,
+            );
+            match address_history::write_blockstore_history(
+                &blockstore,
+                starting_slot,
+                ending_slot,
+                &mut writer,
+            )
+            .and_then(|skipped_slots| Ok((writer.finish()?, skipped_slots)))
+            {
+                Ok(((num_blocks, num_records), skipped_slots)) => {
+                    eprintln!("{} rows written from {} blocks", num_records, num_blocks);
+                    if !skipped_slots.is_empty() {
+                        eprintln!(
+                            "Skipped {} rooted slots without a block: {:?}",
+                            skipped_slots.len(),
+                            skipped_slots
+                        );
+                    }
+                }
+                Err(err) => {
+                    eprintln!("Address history failed: {}", err);
+                    exit(1);
+                }
+            }
+        }
         ("graph", Some(arg_matches)) => {
             let output_file = value_t_or_exit!(arg_matches, "graph_filename", String);
 
@@ -0,0 +1,475 @@
+/// The `replay-trace` subcommand
+///
+/// Replays a range of slots on top of a bank loaded from a snapshot and emits one JSON object per
+/// line for every transaction executed, followed by a summary line for each frozen slot. The
+/// replay stops at the first slot frozen with a different bank hash than the ledger recorded, as
+/// the traces of that slot and its descendants would not match what the cluster executed.
+use log::*;
+use serde::Serialize;
+use solana_ledger::{blockstore::Blockstore, leader_schedule_cache::LeaderScheduleCache};
+use solana_runtime::bank::{Bank, ExecuteTimings, InnerInstructions, TransactionResults};
+use solana_sdk::{
+    account::{AccountSharedData, ReadableAccount},
+    clock::{Slot, MAX_PROCESSING_AGE},
+    instruction::CompiledInstruction,
+    message::Message,
+    pubkey::Pubkey,
+    transaction::Transaction,
+};
+use std::{io::Write, iter::Peekable, sync::Arc, vec::IntoIter};
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TracedAccountState {
+    pub lamports: u64,
+    pub owner: String,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub data: String,
+}
+
+impl From<&AccountSharedData> for TracedAccountState {
+    fn from(account: &AccountSharedData) -> Self {
+        Self {
+            lamports: account.lamports(),
+            owner: account.owner().to_string(),
+            executable: account.executable(),
+            rent_epoch: account.rent_epoch(),
+            data: base64::encode(account.data()),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TracedAccount {
+    pub pubkey: String,
+    pub signer: bool,
+    pub writable: bool,
+    pub pre: Option<TracedAccountState>,
+    pub post: Option<TracedAccountState>,
+}
+
+/// One program invocation, either a top-level instruction or a cross-program invocation
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TracedInvocation {
+    pub program_id: String,
+    pub accounts: Vec<String>,
+    pub data: String,
+    pub stack_height: Option<usize>,
+    pub compute_units_consumed: Option<u64>,
+    pub result: Option<String>,
+    pub children: Vec<TracedInvocation>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TransactionTrace<'a> {
+    r#type: &'static str,
+    slot: Slot,
+    index: usize,
+    signature: String,
+    status: Result<(), String>,
+    accounts: Vec<TracedAccount>,
+    instructions: Vec<TracedInvocation>,
+    log_messages: &'a Option<Vec<String>>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SlotTrace {
+    r#type: &'static str,
+    slot: Slot,
+    parent_slot: Slot,
+    bank_hash: String,
+    /// Bank hash recorded in the ledger, when the slot was frozen by the validator
+    expected_bank_hash: Option<String>,
+    transaction_count: usize,
+}
+
+fn invocation_from_compiled(
+    message: &Message,
+    instruction: &CompiledInstruction,
+) -> TracedInvocation {
+    let key = |index: u8| {
+        message
+            .account_keys
+            .get(index as usize)
+            .map(|pubkey| pubkey.to_string())
+            .unwrap_or_default()
+    };
+    TracedInvocation {
+        program_id: key(instruction.program_id_index),
+        accounts: instruction
+            .accounts
+            .iter()
+            .map(|index| key(*index))
+            .collect(),
+        data: bs58::encode(&instruction.data).into_string(),
+        stack_height: None,
+        compute_units_consumed: None,
+        result: None,
+        children: vec![],
+    }
+}
+
+fn parse_invoke(log: &str) -> Option<(&str, usize)> {
+    let rest = log.strip_prefix("Program ")?;
+    let (program_id, depth) = rest.split_once(" invoke [")?;
+    let depth = depth.strip_suffix(']')?.parse().ok()?;
+    Some((program_id, depth))
+}
+
+fn parse_consumed(log: &str) -> Option<(&str, u64)> {
+    let rest = log.strip_prefix("Program ")?;
+    let (program_id, consumed) = rest.split_once(" consumed ")?;
+    let (consumed, _) = consumed.split_once(" of ")?;
+    Some((program_id, consumed.parse().ok()?))
+}
+
+fn parse_result(log: &str) -> Option<(&str, String)> {
+    let rest = log.strip_prefix("Program ")?;
+    if let Some(program_id) = rest.strip_suffix(" success") {
+        Some((program_id, "success".to_string()))
+    } else {
+        let (program_id, err) = rest.split_once(" failed: ")?;
+        Some((program_id, format!("failed: {}", err)))
+    }
+}
+
+fn nest_invocations(
+    invocations: &mut Peekable<IntoIter<TracedInvocation>>,
+    stack_height: usize,
+) -> Vec<TracedInvocation> {
+    let mut nested = vec![];
+    while let Some(next) = invocations.peek() {
+        let next_height = next.stack_height.unwrap_or(stack_height);
+        if next_height < stack_height {
+            break;
+        }
+        let mut invocation = invocations.next().unwrap();
+        invocation.children = nest_invocations(invocations, next_height + 1);
+        nested.push(invocation);
+    }
+    nested
+}
+
+/// Reconstructs the instruction call tree of a transaction from the recorded inner instructions
+/// and the program log messages.
+///
+/// Every program, builtin or BPF, logs "Program <id> invoke [<depth>]" when entered, so the invoke
+/// lines line up one-to-one with the top-level instructions interleaved with their recorded
+/// cross-program invocations. Compute units are only reported by BPF programs. If the logs were
+/// truncated the remaining invocations are still reported, without stack height, compute or
+/// result information.
+pub fn build_invocation_tree(
+    message: &Message,
+    inner_instructions: Option<&[InnerInstructions]>,
+    log_messages: Option<&[String]>,
+) -> Vec<TracedInvocation> {
+    let mut flattened = vec![];
+    for (index, instruction) in message.instructions.iter().enumerate() {
+        let mut top_level = invocation_from_compiled(message, instruction);
+        top_level.stack_height = Some(1);
+        flattened.push(top_level);
+        if let Some(inner) = inner_instructions.and_then(|inner| inner.get(index)) {
+            flattened.extend(
+                inner
+                    .iter()
+                    .map(|instruction| invocation_from_compiled(message, instruction)),
+            );
+        }
+    }
+
+    let mut next_invocation = 0;
+    let mut invoke_stack: Vec<usize> = vec![];
+    for log in log_messages.unwrap_or_default() {
+        if let Some((program_id, depth)) = parse_invoke(log) {
+            match flattened.get_mut(next_invocation) {
+                Some(invocation) if invocation.program_id == program_id => {
+                    invocation.stack_height = Some(depth);
+                    invoke_stack.push(next_invocation);
+                    next_invocation += 1;
+                }
+                _ => {
+                    warn!("Unexpected program invocation in log: {}", log);
+                    break;
+                }
+            }
+        } else if let Some((program_id, consumed)) = parse_consumed(log) {
+            if let Some(&top) = invoke_stack.last() {
+                if flattened[top].program_id == program_id {
+                    flattened[top].compute_units_consumed = Some(consumed);
+                }
+            }
+        } else if let Some((program_id, result)) = parse_result(log) {
+            if let Some(&top) = invoke_stack.last() {
+                if flattened[top].program_id == program_id {
+                    flattened[top].result = Some(result);
+                    invoke_stack.pop();
+                }
+            }
+        }
+    }
+
+    // Inner instructions without a matching invoke log still belong to their top-level instruction
+    for invocation in flattened.iter_mut().skip(next_invocation) {
+        if invocation.stack_height.is_none() {
+            invocation.stack_height = Some(2);
+        }
+    }
+
+    nest_invocations(&mut flattened.into_iter().peekable(), 1)
+}
+
+fn traced_accounts(bank: &Bank, message: &Message) -> Vec<TracedAccount> {
+    message
+        .account_keys
+        .iter()
+        .enumerate()
+        .map(|(index, pubkey)| TracedAccount {
+            pubkey: pubkey.to_string(),
+            signer: message.is_signer(index),
+            writable: message.is_writable(index),
+            pre: bank
+                .get_account(pubkey)
+                .as_ref()
+                .map(TracedAccountState::from),
+            post: None,
+        })
+        .collect()
+}
+
+fn trace_transaction(
+    bank: &Arc<Bank>,
+    transaction: &Transaction,
+    index: usize,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let message = transaction.message();
+    let mut accounts = traced_accounts(bank, message);
+
+    let batch = bank.prepare_batch(std::iter::once(transaction));
+    let mut timings = ExecuteTimings::default();
+    let (
+        TransactionResults {
+            execution_results, ..
+        },
+        _balances,
+        inner_instructions,
+        log_messages,
+    ) = bank.load_execute_and_commit_transactions(
+        &batch,
+        MAX_PROCESSING_AGE,
+        false,
+        true,
+        true,
+        &mut timings,
+    );
+    drop(batch);
+
+    for account in accounts.iter_mut() {
+        let pubkey = account.pubkey.parse::<Pubkey>().unwrap();
+        account.post = bank
+            .get_account(&pubkey)
+            .as_ref()
+            .map(TracedAccountState::from);
+    }
+
+    let log_messages = log_messages.into_iter().next().flatten();
+    let inner_instructions = inner_instructions.into_iter().next().flatten();
+    let trace = TransactionTrace {
+        r#type: "transaction",
+        slot: bank.slot(),
+        index,
+        signature: transaction.signatures[0].to_string(),
+        status: execution_results[0]
+            .0
+            .clone()
+            .map_err(|err| err.to_string()),
+        accounts,
+        instructions: build_invocation_tree(
+            message,
+            inner_instructions.as_deref(),
+            log_messages.as_deref(),
+        ),
+        log_messages: &log_messages,
+    };
+    serde_json::to_writer(&mut *output, &trace).map_err(|err| err.to_string())?;
+    writeln!(output).map_err(|err| err.to_string())
+}
+
+fn trace_slot(
+    blockstore: &Blockstore,
+    bank: &Arc<Bank>,
+    output: &mut dyn Write,
+) -> Result<usize, String> {
+    let slot = bank.slot();
+    let entries = blockstore
+        .get_slot_entries(slot, 0)
+        .map_err(|err| format!("Failed to load entries for slot {}: {:?}", slot, err))?;
+
+    let mut transaction_count = 0;
+    for entry in entries {
+        if entry.is_tick() {
+            bank.register_tick(&entry.hash);
+            continue;
+        }
+        // Transactions within an entry never conflict, so executing them one at a time yields
+        // the same results as the batched replay while keeping each account state isolated
+        for transaction in &entry.transactions {
+            trace_transaction(bank, transaction, transaction_count, output)?;
+            transaction_count += 1;
+        }
+    }
+    Ok(transaction_count)
+}
+
+/// Replays `starting_slot..=ending_slot` on top of `parent_bank`, following rooted slots where
+/// the ledger forks. Fails once a slot is frozen with another bank hash than the one recorded in
+/// the ledger, after writing its trace.
+pub fn trace_slots(
+    blockstore: &Blockstore,
+    leader_schedule_cache: &LeaderScheduleCache,
+    mut parent_bank: Arc<Bank>,
+    starting_slot: Slot,
+    ending_slot: Slot,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let mut slot = starting_slot;
+    while slot <= ending_slot {
+        if blockstore.is_dead(slot) {
+            return Err(format!("Slot {} is dead", slot));
+        }
+        let leader = leader_schedule_cache
+            .slot_leader_at(slot, Some(&parent_bank))
+            .ok_or_else(|| format!("Unable to determine the leader of slot {}", slot))?;
+        let bank = Arc::new(Bank::new_from_parent(&parent_bank, &leader, slot));
+        let transaction_count = trace_slot(blockstore, &bank, output)?;
+        bank.freeze();
+        let expected_bank_hash = blockstore.get_bank_hash(slot);
+
+        let trace = SlotTrace {
+            r#type: "slot",
+            slot,
+            parent_slot: parent_bank.slot(),
+            bank_hash: bank.hash().to_string(),
+            expected_bank_hash: expected_bank_hash.map(|hash| hash.to_string()),
+            transaction_count,
+        };
+        serde_json::to_writer(&mut *output, &trace).map_err(|err| err.to_string())?;
+        writeln!(output).map_err(|err| err.to_string())?;
+        match expected_bank_hash {
+            Some(expected_bank_hash) if expected_bank_hash != bank.hash() => {
+                return Err(format!(
+                    "Slot {} replayed with bank hash {}, but the ledger recorded {}",
+                    slot,
+                    bank.hash(),
+                    expected_bank_hash
+                ));
+            }
+            Some(_) => (),
+            None => warn!(
+                "No bank hash recorded for slot {}, unable to verify it",
+                slot
+            ),
+        }
+
+        let next_slots = blockstore
+            .meta(slot)
+            .map_err(|err| format!("Failed to read slot meta for {}: {:?}", slot, err))?
+            .map(|meta| meta.next_slots)
+            .unwrap_or_default();
+        let next_slot = next_slots
+            .iter()
+            .find(|next_slot| blockstore.is_root(**next_slot))
+            .or_else(|| next_slots.first());
+        match next_slot {
+            Some(next_slot) => slot = *next_slot,
+            None => break,
+        }
+        parent_bank = bank;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{hash::Hash, message::MessageHeader};
+
+    fn instruction(program_id_index: u8, accounts: Vec<u8>, data: Vec<u8>) -> CompiledInstruction {
+        CompiledInstruction {
+            program_id_index,
+            accounts,
+            data,
+        }
+    }
+
+    #[test]
+    fn test_build_invocation_tree() {
+        let program_a = Pubkey::new_unique();
+        let program_b = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        let message = Message {
+            header: MessageHeader {
+                num_required_signatures: 1,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 2,
+            },
+            account_keys: vec![account, program_a, program_b],
+            recent_blockhash: Hash::default(),
+            instructions: vec![
+                instruction(1, vec![0], vec![1, 2, 3]),
+                instruction(2, vec![0], vec![]),
+            ],
+        };
+        let inner_instructions = vec![
+            vec![
+                instruction(2, vec![0], vec![]),
+                instruction(1, vec![], vec![]),
+            ],
+            vec![],
+        ];
+        let logs: Vec<String> = vec![
+            format!("Program {} invoke [1]", program_a),
+            "Program log: hello".to_string(),
+            format!("Program {} invoke [2]", program_b),
+            format!("Program {} invoke [3]", program_a),
+            format!("Program {} consumed 10 of 1000 compute units", program_a),
+            format!("Program {} success", program_a),
+            format!("Program {} success", program_b),
+            format!("Program {} consumed 500 of 200000 compute units", program_a),
+            format!("Program {} success", program_a),
+            format!("Program {} invoke [1]", program_b),
+            format!("Program {} failed: custom program error: 0x1", program_b),
+        ];
+
+        let tree = build_invocation_tree(&message, Some(&inner_instructions), Some(&logs));
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].program_id, program_a.to_string());
+        assert_eq!(tree[0].data, bs58::encode(&[1, 2, 3]).into_string());
+        assert_eq!(tree[0].compute_units_consumed, Some(500));
+        assert_eq!(tree[0].result.as_deref(), Some("success"));
+        assert_eq!(tree[0].children.len(), 1);
+        let cpi = &tree[0].children[0];
+        assert_eq!(cpi.program_id, program_b.to_string());
+        assert_eq!(cpi.stack_height, Some(2));
+        assert_eq!(cpi.compute_units_consumed, None);
+        assert_eq!(cpi.children.len(), 1);
+        assert_eq!(cpi.children[0].stack_height, Some(3));
+        assert_eq!(cpi.children[0].compute_units_consumed, Some(10));
+        assert_eq!(
+            tree[1].result.as_deref(),
+            Some("failed: custom program error: 0x1")
+        );
+        assert!(tree[1].children.is_empty());
+
+        // Truncated logs still report every recorded invocation
+        let tree = build_invocation_tree(&message, Some(&inner_instructions), Some(&logs[..2]));
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].children.len(), 2);
+        assert_eq!(tree[0].result, None);
+    }
+}
@@ -142,6 +142,8 @@ pub struct Blockstore {
     rewards_cf: LedgerColumn<cf::Rewards>,
     blocktime_cf: LedgerColumn<cf::Blocktime>,
     perf_samples_cf: LedgerColumn<cf::PerfSamples>,
+    block_costs_cf: LedgerColumn<cf::BlockCosts>,
+    bank_hash_cf: LedgerColumn<cf::BankHash>,
     block_height_cf: LedgerColumn<cf::BlockHeight>,
     program_costs_cf: LedgerColumn<cf::ProgramCosts>,
     last_root: Arc<RwLock<Slot>>,
@@ -342,6 +344,8 @@ impl Blockstore {
         let rewards_cf = db.column();
         let blocktime_cf = db.column();
         let perf_samples_cf = db.column();
+        let block_costs_cf = db.column();
+        let bank_hash_cf = db.column();
         let block_height_cf = db.column();
         let program_costs_cf = db.column();
 
@@ -391,6 +395,8 @@ impl Blockstore {
             rewards_cf,
             blocktime_cf,
             perf_samples_cf,
+            block_costs_cf,
+            bank_hash_cf,
             block_height_cf,
             program_costs_cf,
             new_shreds_signals: vec![],
@@ -1520,12 +1526,15 @@ impl Blockstore {
 
         // Commit step: commit all changes to the mutable structures at once, or none at all.
         // We don't want only a subset of these changes going through.
-        write_batch.put_bytes::<cf::ShredData>(
-            (slot, index),
-            // Payload will be padded out to SHRED_PAYLOAD_SIZE
-            // But only need to store the bytes within data_header.size
-            &shred.payload[..shred.data_header.size as usize],
-        )?;
+        // Payload will be padded out to SHRED_PAYLOAD_SIZE
+        // But only need to store the bytes within data_header.size,
+        // except for Merkle shreds which carry their proof at the end
+        let payload = if shred.is_merkle() {
+            &shred.payload[..]
+        } else {
+            &shred.payload[..shred.data_header.size as usize]
+        };
+        write_batch.put_bytes::<cf::ShredData>((slot, index), payload)?;
         data_index.set_present(index, true);
         let newly_completed_data_sets = update_slot_meta(
             last_in_slot,
@@ -2689,6 +2698,26 @@ impl Blockstore {
         self.perf_samples_cf.put(index, perf_sample)
     }
 
+    pub fn get_recent_block_costs(&self, num: usize) -> Result<Vec<(Slot, BlockCost)>> {
+        Ok(self
+            .db
+            .iter::<cf::BlockCosts>(IteratorMode::End)?
+            .take(num)
+            .map(|(slot, data)| {
+                let block_cost = deserialize(&data).unwrap();
+                (slot, block_cost)
+            })
+            .collect())
+    }
+
+    pub fn get_block_cost(&self, slot: Slot) -> Result<Option<BlockCost>> {
+        self.block_costs_cf.get(slot)
+    }
+
+    pub fn write_block_cost(&self, slot: Slot, block_cost: &BlockCost) -> Result<()> {
+        self.block_costs_cf.put(slot, block_cost)
+    }
+
     pub fn read_program_costs(&self) -> Result<Vec<(Pubkey, u64)>> {
         Ok(self
             .db
@@ -2980,6 +3009,37 @@ impl Blockstore {
         self.dead_slots_cf.put(slot, &true)
     }
 
+    pub fn get_bank_hash(&self, slot: Slot) -> Option<Hash> {
+        self.bank_hash_cf
+            .get(slot)
+            .expect("fetch from BankHash column family failed")
+            .map(|versioned| versioned.frozen_hash())
+    }
+
+    pub fn is_duplicate_confirmed(&self, slot: Slot) -> bool {
+        self.bank_hash_cf
+            .get(slot)
+            .expect("fetch from BankHash column family failed")
+            .map(|versioned| versioned.is_duplicate_confirmed())
+            .unwrap_or(false)
+    }
+
+    pub fn insert_bank_hash(&self, slot: Slot, frozen_hash: Hash, is_duplicate_confirmed: bool) {
+        if let Some(prev_value) = self.bank_hash_cf.get(slot).unwrap() {
+            if prev_value.frozen_hash() == frozen_hash && prev_value.is_duplicate_confirmed() {
+                // Don't overwrite is_duplicate_confirmed == true with is_duplicate_confirmed == false,
+                // which may happen on startup when processing from blockstore processor because the
+                // blocks may not reflect earlier observed gossip votes from before the restart.
+                return;
+            }
+        }
+        let data = FrozenHashVersioned::Current(FrozenHashStatus {
+            frozen_hash,
+            is_duplicate_confirmed,
+        });
+        self.bank_hash_cf.put(slot, &data).unwrap()
+    }
+
     pub fn store_duplicate_if_not_existing(
         &self,
         slot: Slot,
@@ -3097,6 +3157,41 @@ impl Blockstore {
         self.db.is_primary_access()
     }
 
+    /// Catches a secondary blockstore up with the primary, then refreshes the metadata that is
+    /// otherwise only read when the blockstore is opened. A no-op for primary access.
+    pub fn try_catch_up_with_primary(&self) -> Result<()> {
+        if self.is_primary_access() {
+            return Ok(());
+        }
+        self.db.try_catch_up_with_primary()?;
+
+        *self.last_root.write().unwrap() = self.max_root();
+
+        let index0_frozen = self
+            .transaction_status_index_cf
+            .get(0)?
+            .map(|index0| index0.frozen)
+            .unwrap_or(false);
+        *self.active_transaction_status_index.write().unwrap() = if index0_frozen { 1 } else { 0 };
+
+        // The primary only records its lowest_cleanup_slot in memory, so infer it from the
+        // oldest slot that survived the primary's purges
+        if let Some((lowest_slot, _)) = self.db.iter::<cf::SlotMeta>(IteratorMode::Start)?.next() {
+            let mut lowest_cleanup_slot = self.lowest_cleanup_slot.write().unwrap();
+            *lowest_cleanup_slot = cmp::max(*lowest_cleanup_slot, lowest_slot.saturating_sub(1));
+        }
+        Ok(())
+    }
+
+    /// Returns the highest slot with a SlotMeta, if any
+    pub fn highest_slot(&self) -> Result<Option<Slot>> {
+        Ok(self
+            .db
+            .iter::<cf::SlotMeta>(IteratorMode::End)?
+            .next()
+            .map(|(slot, _)| slot))
+    }
+
     pub fn scan_and_fix_roots(&self, exit: &Arc<AtomicBool>) -> Result<()> {
         let ancestor_iterator = AncestorIterator::new(self.last_root(), self)
             .take_while(|&slot| slot >= self.lowest_cleanup_slot());
@@ -5905,6 +6000,38 @@ pub mod tests {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_try_catch_up_with_primary() {
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let primary = Blockstore::open(&blockstore_path).unwrap();
+            let secondary = Blockstore::open_with_access_type(
+                &blockstore_path,
+                AccessType::Secondary,
+                None,
+                true,
+            )
+            .unwrap();
+            assert!(!secondary.is_primary_access());
+            assert_eq!(secondary.highest_slot().unwrap(), None);
+
+            let (shreds, _) = make_slot_entries(1, 0, 10);
+            primary.insert_shreds(shreds, None, false).unwrap();
+            primary.set_roots(vec![0, 1].iter()).unwrap();
+
+            // Nothing is visible until the secondary catches up
+            assert_eq!(secondary.last_root(), 0);
+            assert!(secondary.meta(1).unwrap().is_none());
+
+            secondary.try_catch_up_with_primary().unwrap();
+            assert_eq!(secondary.last_root(), 1);
+            assert_eq!(secondary.highest_slot().unwrap(), Some(1));
+            assert!(secondary.meta(1).unwrap().unwrap().is_full());
+            assert!(secondary.is_root(1));
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_is_skipped() {
         let blockstore_path = get_tmp_ledger_path!();
@@ -7926,6 +8053,51 @@ pub mod tests {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_write_get_block_costs() {
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            let account = solana_sdk::pubkey::new_rand();
+            let block_costs: Vec<(Slot, BlockCost)> = (1..=5)
+                .map(|x| {
+                    (
+                        x,
+                        BlockCost {
+                            block_cost: 100 * x,
+                            block_cost_limit: 1_000,
+                            account_cost_limit: 500,
+                            transaction_count: x,
+                            would_exceed_block_limit_count: 0,
+                            would_exceed_account_limit_count: x - 1,
+                            account_costs: vec![(account, 100 * x)],
+                        },
+                    )
+                })
+                .collect();
+            for (slot, block_cost) in block_costs.iter() {
+                blockstore.write_block_cost(*slot, block_cost).unwrap();
+            }
+            assert_eq!(
+                blockstore.get_block_cost(3).unwrap(),
+                Some(block_costs[2].1.clone())
+            );
+            assert_eq!(blockstore.get_block_cost(6).unwrap(), None);
+
+            let mut expected_block_costs = block_costs[3..].to_vec();
+            expected_block_costs.reverse();
+            assert_eq!(
+                blockstore.get_recent_block_costs(2).unwrap(),
+                expected_block_costs
+            );
+
+            blockstore.purge_slots(0, 3, PurgeType::Exact);
+            assert_eq!(blockstore.get_block_cost(3).unwrap(), None);
+            assert_eq!(blockstore.get_recent_block_costs(10).unwrap().len(), 2);
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_lowest_slot() {
         let blockstore_path = get_tmp_ledger_path!();
@@ -8230,6 +8402,37 @@ pub mod tests {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_insert_bank_hash() {
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            let slot = 7;
+            assert!(blockstore.get_bank_hash(slot).is_none());
+            assert!(!blockstore.is_duplicate_confirmed(slot));
+
+            let frozen_hash = Hash::new_unique();
+            blockstore.insert_bank_hash(slot, frozen_hash, false);
+            assert_eq!(blockstore.get_bank_hash(slot), Some(frozen_hash));
+            assert!(!blockstore.is_duplicate_confirmed(slot));
+
+            blockstore.insert_bank_hash(slot, frozen_hash, true);
+            assert!(blockstore.is_duplicate_confirmed(slot));
+
+            // Duplicate confirmation of the same hash is not reverted
+            blockstore.insert_bank_hash(slot, frozen_hash, false);
+            assert_eq!(blockstore.get_bank_hash(slot), Some(frozen_hash));
+            assert!(blockstore.is_duplicate_confirmed(slot));
+
+            // A different version of the slot overwrites the previous one
+            let new_frozen_hash = Hash::new_unique();
+            blockstore.insert_bank_hash(slot, new_frozen_hash, false);
+            assert_eq!(blockstore.get_bank_hash(slot), Some(new_frozen_hash));
+            assert!(!blockstore.is_duplicate_confirmed(slot));
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_clear_unconfirmed_slot() {
         let blockstore_path = get_tmp_ledger_path!();
@@ -8397,6 +8600,7 @@ pub mod tests {
                         amount: "11".to_string(),
                         ui_amount_string: "1.1".to_string(),
                     },
+                    owner: Pubkey::new_unique().to_string(),
                 }]),
                 post_token_balances: Some(vec![TransactionTokenBalance {
                     account_index: 0,
@@ -8407,6 +8611,7 @@ pub mod tests {
                         amount: "11".to_string(),
                         ui_amount_string: "1.1".to_string(),
                     },
+                    owner: Pubkey::new_unique().to_string(),
                 }]),
                 rewards: Some(vec![Reward {
                     pubkey: "My11111111111111111111111111111111111111111".to_string(),
@@ -179,6 +179,14 @@ impl Blockstore {
                 .db
                 .delete_range_cf::<cf::PerfSamples>(&mut write_batch, from_slot, to_slot)
                 .is_ok()
+            & self
+                .db
+                .delete_range_cf::<cf::BlockCosts>(&mut write_batch, from_slot, to_slot)
+                .is_ok()
+            & self
+                .db
+                .delete_range_cf::<cf::BankHash>(&mut write_batch, from_slot, to_slot)
+                .is_ok()
             & self
                 .db
                 .delete_range_cf::<cf::BlockHeight>(&mut write_batch, from_slot, to_slot)
@@ -292,6 +300,14 @@ impl Blockstore {
                 .perf_samples_cf
                 .compact_range(from_slot, to_slot)
                 .unwrap_or(false)
+            && self
+                .block_costs_cf
+                .compact_range(from_slot, to_slot)
+                .unwrap_or(false)
+            && self
+                .bank_hash_cf
+                .compact_range(from_slot, to_slot)
+                .unwrap_or(false)
             && self
                 .block_height_cf
                 .compact_range(from_slot, to_slot)
@@ -73,6 +73,10 @@ const PERF_SAMPLES_CF: &str = "perf_samples";
 const BLOCK_HEIGHT_CF: &str = "block_height";
 /// Column family for ProgramCosts
 const PROGRAM_COSTS_CF: &str = "program_costs";
+/// Column family for BlockCosts
+const BLOCK_COSTS_CF: &str = "block_costs";
+/// Column family for BankHash
+const BANK_HASH_CF: &str = "bank_hashes";
 
 // 1 day is chosen for the same reasoning of DEFAULT_COMPACTION_SLOT_INTERVAL
 const PERIODIC_COMPACTION_SECONDS: u64 = 60 * 60 * 24;
@@ -180,12 +184,21 @@ pub mod columns {
     #[derive(Debug)]
     // The program costs column
     pub struct ProgramCosts;
+
+    #[derive(Debug)]
+    /// The block costs column
+    pub struct BlockCosts;
+
+    #[derive(Debug)]
+    /// The bank hash column
+    pub struct BankHash;
 }
 
 pub enum AccessType {
     PrimaryOnly,
     PrimaryOnlyForMaintenance, // this indicates no compaction
     TryPrimaryThenSecondary,
+    Secondary, // never takes the primary lock, for read replicas following a running validator
 }
 
 #[derive(Debug, PartialEq)]
@@ -263,9 +276,9 @@ impl Rocks {
         recovery_mode: Option<BlockstoreRecoveryMode>,
     ) -> Result<Rocks> {
         use columns::{
-            AddressSignatures, BlockHeight, Blocktime, DeadSlots, DuplicateSlots, ErasureMeta,
-            Index, Orphans, PerfSamples, ProgramCosts, Rewards, Root, ShredCode, ShredData,
-            SlotMeta, TransactionStatus, TransactionStatusIndex,
+            AddressSignatures, BankHash, BlockCosts, BlockHeight, Blocktime, DeadSlots,
+            DuplicateSlots, ErasureMeta, Index, Orphans, PerfSamples, ProgramCosts, Rewards, Root,
+            ShredCode, ShredData, SlotMeta, TransactionStatus, TransactionStatusIndex,
         };
 
         fs::create_dir_all(&path)?;
@@ -350,6 +363,14 @@ impl Rocks {
             ProgramCosts::NAME,
             get_cf_options::<ProgramCosts>(&access_type, &oldest_slot),
         );
+        let block_costs_cf_descriptor = ColumnFamilyDescriptor::new(
+            BlockCosts::NAME,
+            get_cf_options::<BlockCosts>(&access_type, &oldest_slot),
+        );
+        let bank_hash_cf_descriptor = ColumnFamilyDescriptor::new(
+            BankHash::NAME,
+            get_cf_options::<BankHash>(&access_type, &oldest_slot),
+        );
         // Don't forget to add to both run_purge_with_stats() and
         // compact_storage() in ledger/src/blockstore/blockstore_purge.rs!!
 
@@ -374,6 +395,8 @@ impl Rocks {
             (PerfSamples::NAME, perf_samples_cf_descriptor),
             (BlockHeight::NAME, block_height_cf_descriptor),
             (ProgramCosts::NAME, program_costs_cf_descriptor),
+            (BlockCosts::NAME, block_costs_cf_descriptor),
+            (BankHash::NAME, bank_hash_cf_descriptor),
         ];
         let cf_names: Vec<_> = cfs.iter().map(|c| c.0).collect();
 
@@ -388,28 +411,14 @@ impl Rocks {
                 match DB::open_cf_descriptors(&db_options, path, cfs.into_iter().map(|c| c.1)) {
                     Ok(db) => Rocks(db, ActualAccessType::Primary, oldest_slot),
                     Err(err) => {
-                        let secondary_path = path.join("solana-secondary");
-
                         warn!("Error when opening as primary: {}", err);
-                        warn!("Trying as secondary at : {:?}", secondary_path);
-                        warn!("This active secondary db use may temporarily cause the performance of another db use (like by validator) to degrade");
-
-                        // This is needed according to https://github.com/facebook/rocksdb/wiki/Secondary-instance
-                        db_options.set_max_open_files(-1);
-
-                        Rocks(
-                            DB::open_cf_as_secondary(
-                                &db_options,
-                                path,
-                                &secondary_path,
-                                cf_names.clone(),
-                            )?,
-                            ActualAccessType::Secondary,
-                            oldest_slot,
-                        )
+                        Self::open_secondary(path, db_options, &cf_names, oldest_slot)?
                     }
                 }
             }
+            AccessType::Secondary => {
+                Self::open_secondary(path, db_options, &cf_names, oldest_slot)?
+            }
         };
         // this is only needed for LedgerCleanupService. so guard with PrimaryOnly (i.e. running solana-validator)
         if matches!(access_type, AccessType::PrimaryOnly) {
@@ -471,11 +480,32 @@ impl Rocks {
         Ok(db)
     }
 
+    fn open_secondary(
+        path: &Path,
+        mut db_options: Options,
+        cf_names: &[&str],
+        oldest_slot: OldestSlot,
+    ) -> Result<Rocks> {
+        let secondary_path = path.join("solana-secondary");
+
+        warn!("Opening as secondary at : {:?}", secondary_path);
+        warn!("This active secondary db use may temporarily cause the performance of another db use (like by validator) to degrade");
+
+        // This is needed according to https://github.com/facebook/rocksdb/wiki/Secondary-instance
+        db_options.set_max_open_files(-1);
+
+        Ok(Rocks(
+            DB::open_cf_as_secondary(&db_options, path, &secondary_path, cf_names.to_vec())?,
+            ActualAccessType::Secondary,
+            oldest_slot,
+        ))
+    }
+
     fn columns(&self) -> Vec<&'static str> {
         use columns::{
-            AddressSignatures, BlockHeight, Blocktime, DeadSlots, DuplicateSlots, ErasureMeta,
-            Index, Orphans, PerfSamples, ProgramCosts, Rewards, Root, ShredCode, ShredData,
-            SlotMeta, TransactionStatus, TransactionStatusIndex,
+            AddressSignatures, BankHash, BlockCosts, BlockHeight, Blocktime, DeadSlots,
+            DuplicateSlots, ErasureMeta, Index, Orphans, PerfSamples, ProgramCosts, Rewards, Root,
+            ShredCode, ShredData, SlotMeta, TransactionStatus, TransactionStatusIndex,
         };
 
         vec![
@@ -496,6 +526,8 @@ impl Rocks {
             PerfSamples::NAME,
             BlockHeight::NAME,
             ProgramCosts::NAME,
+            BlockCosts::NAME,
+            BankHash::NAME,
         ]
     }
 
@@ -558,6 +590,11 @@ impl Rocks {
     fn is_primary_access(&self) -> bool {
         self.1 == ActualAccessType::Primary
     }
+
+    fn try_catch_up_with_primary(&self) -> Result<()> {
+        self.0.try_catch_up_with_primary()?;
+        Ok(())
+    }
 }
 
 pub trait Column {
@@ -759,6 +796,22 @@ impl TypedColumn for columns::PerfSamples {
     type Type = blockstore_meta::PerfSample;
 }
 
+impl SlotColumn for columns::BlockCosts {}
+impl ColumnName for columns::BlockCosts {
+    const NAME: &'static str = BLOCK_COSTS_CF;
+}
+impl TypedColumn for columns::BlockCosts {
+    type Type = blockstore_meta::BlockCost;
+}
+
+impl SlotColumn for columns::BankHash {}
+impl ColumnName for columns::BankHash {
+    const NAME: &'static str = BANK_HASH_CF;
+}
+impl TypedColumn for columns::BankHash {
+    type Type = blockstore_meta::FrozenHashVersioned;
+}
+
 impl SlotColumn for columns::BlockHeight {}
 impl ColumnName for columns::BlockHeight {
     const NAME: &'static str = BLOCK_HEIGHT_CF;
@@ -1059,6 +1112,12 @@ impl Database {
         self.backend.is_primary_access()
     }
 
+    /// Replays the writes made by the primary since this secondary was opened or last caught
+    /// up. Must only be called on a secondary.
+    pub fn try_catch_up_with_primary(&self) -> Result<()> {
+        self.backend.try_catch_up_with_primary()
+    }
+
     pub fn set_oldest_slot(&self, oldest_slot: Slot) {
         self.backend.2.set(oldest_slot);
     }
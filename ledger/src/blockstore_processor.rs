@@ -662,6 +662,7 @@ fn confirm_full_slot(
         transaction_status_sender,
         replay_vote_sender,
         opts.entry_callback.as_ref(),
+        None,
         recyclers,
         opts.allow_dead_slots,
     )?;
@@ -729,6 +730,7 @@ pub fn confirm_slot(
     transaction_status_sender: Option<&TransactionStatusSender>,
     replay_vote_sender: Option<&ReplayVoteSender>,
     entry_callback: Option<&ProcessCallback>,
+    replayed_transaction_callback: Option<&mut dyn FnMut(&Transaction)>,
     recyclers: &VerifyRecyclers,
     allow_dead_slots: bool,
 ) -> result::Result<(), BlockstoreProcessorError> {
@@ -830,6 +832,16 @@ pub fn confirm_slot(
 
     process_result?;
 
+    if let Some(replayed_transaction_callback) = replayed_transaction_callback {
+        for entry in &entries {
+            if let EntryType::Transactions(transactions) = entry {
+                for transaction in transactions {
+                    replayed_transaction_callback(transaction.transaction());
+                }
+            }
+        }
+    }
+
     progress.num_shreds += num_shreds;
     progress.num_entries += num_entries;
     progress.num_txs += num_txs;
@@ -48,6 +48,11 @@
 //!
 //! So, given a) - c), we must restrict data shred's payload length such that the entire coding
 //! payload can fit into one coding shred / packet.
+//!
+//! Merkle variants of both shred types end with the path from the shred up to the Merkle root of
+//! its erasure batch, and are signed by signing that root. The erasure code of a Merkle batch
+//! then covers the data shred's headers and payload, but not its signature, so the signature and
+//! inclusion proofs are only filled in once the coding shreds of the batch have been generated.
 
 use crate::{
     blockstore::MAX_DATA_SHREDS_PER_SLOT,
@@ -58,11 +63,12 @@ use bincode::config::Options;
 use core::cell::RefCell;
 use rayon::{
     iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator},
-    slice::ParallelSlice,
+    slice::{ParallelSlice, ParallelSliceMut},
     ThreadPool,
 };
 use serde::{Deserialize, Serialize};
 use solana_measure::measure::Measure;
+use solana_merkle_tree::MerkleTree;
 use solana_perf::packet::{limited_deserialize, Packet};
 use solana_rayon_threadlimit::get_thread_count;
 use solana_runtime::bank::Bank;
@@ -132,6 +138,21 @@ pub const OFFSET_OF_SHRED_SLOT: usize = SIZE_OF_SIGNATURE + SIZE_OF_SHRED_TYPE;
 pub const OFFSET_OF_SHRED_INDEX: usize = OFFSET_OF_SHRED_SLOT + SIZE_OF_SHRED_SLOT;
 pub const SHRED_PAYLOAD_SIZE: usize = PACKET_DATA_SIZE - SIZE_OF_NONCE;
 
+/// An erasure batch holds at most 2 * MAX_DATA_SHREDS_PER_FEC_BLOCK shreds, so the path from any
+/// of its shreds up to the Merkle root of the batch has at most 6 entries.
+pub const MAX_MERKLE_PROOF_ENTRIES: usize = 6;
+pub const SIZE_OF_MERKLE_PROOF_ENTRY: usize = 32;
+pub const SIZE_OF_MERKLE_PROOF: usize = 1 + MAX_MERKLE_PROOF_ENTRIES * SIZE_OF_MERKLE_PROOF_ENTRY;
+pub const OFFSET_OF_MERKLE_PROOF: usize = SHRED_PAYLOAD_SIZE - SIZE_OF_MERKLE_PROOF;
+/// Bytes of a Merkle shred covered by the erasure code: the data shred's headers and payload
+/// following the signature, or the coding shred's parity following its headers.
+pub const SIZE_OF_MERKLE_ERASURE_BLOCK: usize =
+    OFFSET_OF_MERKLE_PROOF - SIZE_OF_CODING_SHRED_HEADERS;
+pub const SIZE_OF_MERKLE_DATA_SHRED_PAYLOAD: usize = SIZE_OF_SIGNATURE
+    + SIZE_OF_MERKLE_ERASURE_BLOCK
+    - SIZE_OF_COMMON_SHRED_HEADER
+    - SIZE_OF_DATA_SHRED_HEADER;
+
 thread_local!(static PAR_THREAD_POOL: RefCell<ThreadPool> = RefCell::new(rayon::ThreadPoolBuilder::new()
                     .num_threads(get_thread_count())
                     .thread_name(|ix| format!("shredder_{}", ix))
@@ -141,6 +162,8 @@ thread_local!(static PAR_THREAD_POOL: RefCell<ThreadPool> = RefCell::new(rayon::
 /// The constants that define if a shred is data or coding
 pub const DATA_SHRED: u8 = 0b1010_0101;
 pub const CODING_SHRED: u8 = 0b0101_1010;
+pub const MERKLE_DATA_SHRED: u8 = 0b1010_0110;
+pub const MERKLE_CODING_SHRED: u8 = 0b0110_1010;
 
 pub const MAX_DATA_SHREDS_PER_FEC_BLOCK: u32 = 32;
 
@@ -178,6 +201,18 @@ impl Default for ShredType {
     }
 }
 
+impl ShredType {
+    pub fn is_data(self) -> bool {
+        self == ShredType(DATA_SHRED) || self == ShredType(MERKLE_DATA_SHRED)
+    }
+    pub fn is_code(self) -> bool {
+        self == ShredType(CODING_SHRED) || self == ShredType(MERKLE_CODING_SHRED)
+    }
+    pub fn is_merkle(self) -> bool {
+        self == ShredType(MERKLE_DATA_SHRED) || self == ShredType(MERKLE_CODING_SHRED)
+    }
+}
+
 /// A common header that is present in data and code shred headers
 #[derive(Serialize, Clone, Deserialize, Default, PartialEq, Debug)]
 pub struct ShredCommonHeader {
@@ -326,7 +361,7 @@ impl Shred {
         // so that erasure generation/recovery works correctly
         // But only the data_header.size is stored in blockstore.
         payload.resize(SHRED_PAYLOAD_SIZE, 0);
-        let shred = if common_header.shred_type == ShredType(CODING_SHRED) {
+        let shred = if common_header.shred_type.is_code() {
             let coding_header: CodingShredHeader =
                 Self::deserialize_obj(&mut start, SIZE_OF_CODING_SHRED_HEADER, &payload)?;
             Self {
@@ -335,7 +370,7 @@ impl Shred {
                 coding_header,
                 payload,
             }
-        } else if common_header.shred_type == ShredType(DATA_SHRED) {
+        } else if common_header.shred_type.is_data() {
             let data_header: DataShredHeader =
                 Self::deserialize_obj(&mut start, SIZE_OF_DATA_SHRED_HEADER, &payload)?;
             if u64::from(data_header.parent_offset) > common_header.slot {
@@ -390,7 +425,7 @@ impl Shred {
             &common_header,
         )
         .expect("Failed to write header into shred buffer");
-        if common_header.shred_type == ShredType(DATA_SHRED) {
+        if common_header.shred_type.is_data() {
             Self::serialize_obj_into(
                 &mut start,
                 SIZE_OF_DATA_SHRED_HEADER,
@@ -398,7 +433,7 @@ impl Shred {
                 &data_header,
             )
             .expect("Failed to write data header into shred buffer");
-        } else if common_header.shred_type == ShredType(CODING_SHRED) {
+        } else if common_header.shred_type.is_code() {
             Self::serialize_obj_into(
                 &mut start,
                 SIZE_OF_CODING_SHRED_HEADER,
@@ -469,15 +504,130 @@ impl Shred {
         self.common_header.signature
     }
 
+    fn set_signature(&mut self, signature: Signature) {
+        bincode::serialize_into(&mut self.payload[..SIZE_OF_SIGNATURE], &signature)
+            .expect("Failed to generate serialized signature");
+        self.common_header.signature = signature;
+    }
+
+    /// Turns a data shred into its Merkle variant. The signature and the inclusion proof of the
+    /// shred are only filled in by `Shredder::data_shreds_to_merkle_coding_shreds`.
+    pub fn set_merkle_variant(&mut self) {
+        assert!(self.is_data());
+        assert!(
+            usize::from(self.data_header.size) <= SIZE_OF_SIGNATURE + SIZE_OF_MERKLE_ERASURE_BLOCK
+        );
+        self.common_header.shred_type = ShredType(MERKLE_DATA_SHRED);
+        Self::serialize_obj_into(
+            &mut 0,
+            SIZE_OF_COMMON_SHRED_HEADER,
+            &mut self.payload,
+            &self.common_header,
+        )
+        .unwrap();
+    }
+
+    pub fn is_merkle(&self) -> bool {
+        self.common_header.shred_type.is_merkle()
+    }
+
+    fn merkle_leaf_index(&self) -> Option<usize> {
+        Self::merkle_leaf_index_from_headers(&self.common_header, &self.coding_header)
+    }
+
+    // The Merkle tree of an erasure batch has the data shreds as its first leaves, followed by
+    // the coding shreds.
+    fn merkle_leaf_index_from_headers(
+        common_header: &ShredCommonHeader,
+        coding_header: &CodingShredHeader,
+    ) -> Option<usize> {
+        let offset = common_header
+            .index
+            .checked_sub(common_header.fec_set_index)? as usize;
+        if common_header.shred_type.is_data() {
+            Some(offset)
+        } else {
+            Some(usize::from(coding_header.num_data_shreds) + offset)
+        }
+    }
+
+    // Everything between the signature and the inclusion proof.
+    fn merkle_leaf(&self) -> &[u8] {
+        &self.payload[SIZE_OF_SIGNATURE..OFFSET_OF_MERKLE_PROOF]
+    }
+
+    /// The siblings on the path from a Merkle shred up to the Merkle root of its erasure batch
+    pub fn merkle_proof(&self) -> Option<Vec<Hash>> {
+        if !self.is_merkle() {
+            return None;
+        }
+        Self::merkle_proof_from_payload(&self.payload)
+    }
+
+    fn merkle_proof_from_payload(payload: &[u8]) -> Option<Vec<Hash>> {
+        let proof = &payload[OFFSET_OF_MERKLE_PROOF..SHRED_PAYLOAD_SIZE];
+        let num_entries = usize::from(proof[0]);
+        if num_entries > MAX_MERKLE_PROOF_ENTRIES {
+            return None;
+        }
+        Some(
+            proof[1..]
+                .chunks(SIZE_OF_MERKLE_PROOF_ENTRY)
+                .take(num_entries)
+                .map(Hash::new)
+                .collect(),
+        )
+    }
+
+    fn set_merkle_proof(&mut self, proof: &[Hash]) {
+        assert!(proof.len() <= MAX_MERKLE_PROOF_ENTRIES);
+        let buf = &mut self.payload[OFFSET_OF_MERKLE_PROOF..];
+        buf[0] = proof.len() as u8;
+        for (entry, hash) in buf[1..].chunks_mut(SIZE_OF_MERKLE_PROOF_ENTRY).zip(proof) {
+            entry.copy_from_slice(hash.as_ref());
+        }
+    }
+
+    /// The Merkle root of the erasure batch as computed from the shred's inclusion proof, this is
+    /// the message signed by the leader
+    pub fn merkle_root(&self) -> Option<Hash> {
+        let proof = self.merkle_proof()?;
+        let index = self.merkle_leaf_index()?;
+        Some(MerkleTree::compute_root(self.merkle_leaf(), index, &proof))
+    }
+
+    /// Same as `merkle_root`, straight from the payload of a serialized Merkle shred, so that
+    /// packets can be checked against already verified roots without being copied. Returns the
+    /// slot of the shred along with the root.
+    pub fn merkle_root_from_payload(payload: &[u8]) -> Option<(Slot, Hash)> {
+        if payload.len() < SHRED_PAYLOAD_SIZE {
+            return None;
+        }
+        let mut start = 0;
+        let common_header: ShredCommonHeader =
+            Self::deserialize_obj(&mut start, SIZE_OF_COMMON_SHRED_HEADER, payload).ok()?;
+        if !common_header.shred_type.is_merkle() {
+            return None;
+        }
+        let coding_header = if common_header.shred_type.is_code() {
+            Self::deserialize_obj(&mut start, SIZE_OF_CODING_SHRED_HEADER, payload).ok()?
+        } else {
+            CodingShredHeader::default()
+        };
+        let index = Self::merkle_leaf_index_from_headers(&common_header, &coding_header)?;
+        let proof = Self::merkle_proof_from_payload(payload)?;
+        let leaf = &payload[SIZE_OF_SIGNATURE..OFFSET_OF_MERKLE_PROOF];
+        Some((
+            common_header.slot,
+            MerkleTree::compute_root(leaf, index, &proof),
+        ))
+    }
+
     pub fn seed(&self, leader_pubkey: Option<Pubkey>, root_bank: &Bank) -> [u8; 32] {
         if let Some(leader_pubkey) = leader_pubkey {
-            if enable_deterministic_seed(self.slot(), root_bank) {
-                let h = hashv(&[
-                    &self.slot().to_le_bytes(),
-                    &self.index().to_le_bytes(),
-                    &leader_pubkey.to_bytes(),
-                ]);
-                return h.to_bytes();
+            // All shreds of a Merkle erasure batch share the same signature
+            if self.is_merkle() || enable_deterministic_seed(self.slot(), root_bank) {
+                return hash_shred_seed(self.slot(), self.index(), &leader_pubkey);
             }
         }
 
@@ -489,10 +639,10 @@ impl Shred {
     }
 
     pub fn is_data(&self) -> bool {
-        self.common_header.shred_type == ShredType(DATA_SHRED)
+        self.common_header.shred_type.is_data()
     }
     pub fn is_code(&self) -> bool {
-        self.common_header.shred_type == ShredType(CODING_SHRED)
+        self.common_header.shred_type.is_code()
     }
 
     pub fn last_in_slot(&self) -> bool {
@@ -564,9 +714,45 @@ impl Shred {
         flags & SHRED_TICK_REFERENCE_MASK
     }
 
+    // Returns true if the packet holds a Merkle shred, with partial deserialize
+    pub fn is_merkle_packet(p: &Packet) -> bool {
+        p.meta.size > OFFSET_OF_SHRED_TYPE && ShredType(p.data[OFFSET_OF_SHRED_TYPE]).is_merkle()
+    }
+
     pub fn verify(&self, pubkey: &Pubkey) -> bool {
-        self.signature()
-            .verify(pubkey.as_ref(), &self.payload[SIZE_OF_SIGNATURE..])
+        if self.is_merkle() {
+            match self.merkle_root() {
+                Some(root) => self.signature().verify(pubkey.as_ref(), root.as_ref()),
+                None => false,
+            }
+        } else {
+            self.signature()
+                .verify(pubkey.as_ref(), &self.payload[SIZE_OF_SIGNATURE..])
+        }
+    }
+}
+
+fn hash_shred_seed(slot: Slot, index: u32, leader_pubkey: &Pubkey) -> [u8; 32] {
+    hashv(&[
+        &slot.to_le_bytes(),
+        &index.to_le_bytes(),
+        &leader_pubkey.to_bytes(),
+    ])
+    .to_bytes()
+}
+
+/// Returns the retransmit seed of the shred at (`slot`, `index`) without the
+/// shred itself, or None if the seed depends on the shred's signature.
+pub fn deterministic_shred_seed(
+    slot: Slot,
+    index: u32,
+    leader_pubkey: &Pubkey,
+    root_bank: &Bank,
+) -> Option<[u8; 32]> {
+    if should_use_merkle_shreds(slot, root_bank) || enable_deterministic_seed(slot, root_bank) {
+        Some(hash_shred_seed(slot, index, leader_pubkey))
+    } else {
+        None
     }
 }
 
@@ -585,6 +771,23 @@ fn enable_deterministic_seed(shred_slot: Slot, bank: &Bank) -> bool {
     }
 }
 
+/// Returns true if the leader of `shred_slot` should send Merkle shreds, which are accepted
+/// from the epoch following the activation of the feature on
+pub fn should_use_merkle_shreds(shred_slot: Slot, bank: &Bank) -> bool {
+    let feature_slot = bank
+        .feature_set
+        .activated_slot(&feature_set::merkle_shreds::id());
+    match feature_slot {
+        None => false,
+        Some(feature_slot) => {
+            let epoch_schedule = bank.epoch_schedule();
+            let feature_epoch = epoch_schedule.get_epoch(feature_slot);
+            let shred_epoch = epoch_schedule.get_epoch(shred_slot);
+            feature_epoch < shred_epoch
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Shredder {
     pub slot: Slot,
@@ -592,6 +795,7 @@ pub struct Shredder {
     version: u16,
     pub signing_coding_time: u128,
     reference_tick: u8,
+    merkle_variant: bool,
 }
 
 impl Shredder {
@@ -605,10 +809,17 @@ impl Shredder {
                 signing_coding_time: 0,
                 reference_tick,
                 version,
+                merkle_variant: false,
             })
         }
     }
 
+    /// Makes Merkle shreds instead of legacy ones, see `should_use_merkle_shreds`
+    pub fn with_merkle_variant(mut self, merkle_variant: bool) -> Self {
+        self.merkle_variant = merkle_variant;
+        self
+    }
+
     pub fn entries_to_shreds(
         &self,
         keypair: &Keypair,
@@ -617,7 +828,7 @@ impl Shredder {
         next_shred_index: u32,
     ) -> (Vec<Shred>, Vec<Shred>, u32) {
         let mut stats = ProcessShredsStats::default();
-        let (data_shreds, last_shred_index) = self.entries_to_data_shreds(
+        let (mut data_shreds, last_shred_index) = self.entries_to_data_shreds(
             keypair,
             entries,
             is_last_in_slot,
@@ -625,9 +836,17 @@ impl Shredder {
             next_shred_index, // fec_set_offset
             &mut stats,
         );
-        let coding_shreds =
+        let coding_shreds = if self.merkle_variant {
+            Self::data_shreds_to_merkle_coding_shreds(
+                keypair,
+                &mut data_shreds,
+                is_last_in_slot,
+                &mut stats,
+            )
+        } else {
             Self::data_shreds_to_coding_shreds(keypair, &data_shreds, is_last_in_slot, &mut stats)
-                .unwrap();
+        }
+        .unwrap();
         (data_shreds, coding_shreds, last_shred_index)
     }
 
@@ -657,7 +876,11 @@ impl Shredder {
         serialize_time.stop();
 
         let mut gen_data_time = Measure::start("shred_gen_data_time");
-        let payload_capacity = SIZE_OF_DATA_SHRED_PAYLOAD;
+        let payload_capacity = if self.merkle_variant {
+            SIZE_OF_MERKLE_DATA_SHRED_PAYLOAD
+        } else {
+            SIZE_OF_DATA_SHRED_PAYLOAD
+        };
         // Integer division to ensure we have enough shreds to fit all the data
         let num_shreds = (serialized_shreds.len() + payload_capacity - 1) / payload_capacity;
         let last_shred_index = next_shred_index + num_shreds as u32 - 1;
@@ -678,7 +901,12 @@ impl Shredder {
                 self.version,
                 fec_set_index.unwrap(),
             );
-            Shredder::sign_shred(keypair, &mut shred);
+            // Merkle data shreds are signed along with their erasure batch
+            if self.merkle_variant {
+                shred.set_merkle_variant();
+            } else {
+                Shredder::sign_shred(keypair, &mut shred);
+            }
             shred
         };
         let data_shreds: Vec<Shred> = PAR_THREAD_POOL.with(|thread_pool| {
@@ -740,11 +968,74 @@ impl Shredder {
         Ok(coding_shreds)
     }
 
+    /// Same as `data_shreds_to_coding_shreds`, for Merkle data shreds. Each erasure batch is
+    /// signed by the Merkle root of its data and coding shreds, and the signature and inclusion
+    /// proofs are written into every shred of the batch, including the data shreds.
+    pub fn data_shreds_to_merkle_coding_shreds(
+        keypair: &Keypair,
+        data_shreds: &mut [Shred],
+        is_last_in_slot: bool,
+        process_stats: &mut ProcessShredsStats,
+    ) -> Result<Vec<Shred>> {
+        if data_shreds.is_empty() {
+            return Ok(Vec::default());
+        }
+        if !data_shreds.iter().all(Shred::is_merkle) {
+            return Err(ShredError::InvalidShredType);
+        }
+        let mut gen_coding_time = Measure::start("gen_coding_shreds");
+        // 1) Generate coding shreds, one batch per FEC set
+        let mut coding_batches: Vec<Vec<Shred>> = PAR_THREAD_POOL.with(|thread_pool| {
+            thread_pool.borrow().install(|| {
+                data_shreds
+                    .par_chunks(MAX_DATA_SHREDS_PER_FEC_BLOCK as usize)
+                    .map(|shred_data_batch| {
+                        Shredder::generate_coding_shreds(shred_data_batch, is_last_in_slot)
+                    })
+                    .collect()
+            })
+        });
+        gen_coding_time.stop();
+
+        let mut sign_coding_time = Measure::start("sign_coding_shreds");
+        // 2) Sign the Merkle root of each batch
+        PAR_THREAD_POOL.with(|thread_pool| {
+            thread_pool.borrow().install(|| {
+                data_shreds
+                    .par_chunks_mut(MAX_DATA_SHREDS_PER_FEC_BLOCK as usize)
+                    .zip(coding_batches.par_iter_mut())
+                    .for_each(|(data_batch, coding_batch)| {
+                        Shredder::sign_erasure_batch(keypair, data_batch, coding_batch);
+                    })
+            })
+        });
+        sign_coding_time.stop();
+
+        process_stats.gen_coding_elapsed += gen_coding_time.as_us();
+        process_stats.sign_coding_elapsed += sign_coding_time.as_us();
+        Ok(coding_batches.into_iter().flatten().collect())
+    }
+
+    fn sign_erasure_batch(signer: &Keypair, data: &mut [Shred], coding: &mut [Shred]) {
+        let tree = {
+            let leaves: Vec<_> = data
+                .iter()
+                .chain(coding.iter())
+                .map(Shred::merkle_leaf)
+                .collect();
+            MerkleTree::new(&leaves)
+        };
+        let root = tree.get_root().expect("erasure batch is not empty");
+        let signature = signer.sign_message(root.as_ref());
+        for (index, shred) in data.iter_mut().chain(coding.iter_mut()).enumerate() {
+            shred.set_merkle_proof(&tree.find_siblings(index).unwrap());
+            shred.set_signature(signature);
+        }
+    }
+
     pub fn sign_shred(signer: &Keypair, shred: &mut Shred) {
         let signature = signer.sign_message(&shred.payload[SIZE_OF_SIGNATURE..]);
-        bincode::serialize_into(&mut shred.payload[..SIZE_OF_SIGNATURE], &signature)
-            .expect("Failed to generate serialized signature");
-        shred.common_header.signature = signature;
+        shred.set_signature(signature);
     }
 
     pub fn new_coding_shred_header(
@@ -775,16 +1066,24 @@ impl Shredder {
 
     /// Generates coding shreds for the data shreds in the current FEC set
     pub fn generate_coding_shreds(data: &[Shred], is_last_in_slot: bool) -> Vec<Shred> {
-        const PAYLOAD_ENCODE_SIZE: usize = SHRED_PAYLOAD_SIZE - SIZE_OF_CODING_SHRED_HEADERS;
         let ShredCommonHeader {
+            shred_type,
             slot,
             index,
             version,
             fec_set_index,
             ..
         } = data.first().unwrap().common_header;
+        let merkle_variant = shred_type.is_merkle();
+        // Merkle data shreds are encoded without their signature, which is filled in later on
+        let (data_offset, encode_size) = if merkle_variant {
+            (SIZE_OF_SIGNATURE, SIZE_OF_MERKLE_ERASURE_BLOCK)
+        } else {
+            (0, SHRED_PAYLOAD_SIZE - SIZE_OF_CODING_SHRED_HEADERS)
+        };
         assert_eq!(fec_set_index, index);
         assert!(data.iter().all(|shred| shred.common_header.slot == slot
+            && shred.common_header.shred_type == shred_type
             && shred.common_header.version == version
             && shred.common_header.fec_set_index == fec_set_index));
         let num_data = data.len();
@@ -797,9 +1096,9 @@ impl Shredder {
         };
         let data: Vec<_> = data
             .iter()
-            .map(|shred| &shred.payload[..PAYLOAD_ENCODE_SIZE])
+            .map(|shred| &shred.payload[data_offset..data_offset + encode_size])
             .collect();
-        let mut parity = vec![vec![0u8; PAYLOAD_ENCODE_SIZE]; num_coding];
+        let mut parity = vec![vec![0u8; encode_size]; num_coding];
         Session::new(num_data, num_coding)
             .unwrap()
             .encode(&data, &mut parity[..])
@@ -808,20 +1107,40 @@ impl Shredder {
             .iter()
             .enumerate()
             .map(|(i, parity)| {
-                let mut shred = Shred::new_empty_coding(
+                let mut shred = Self::new_empty_coding_shred(
                     slot,
                     fec_set_index + i as u32, // shred index
                     fec_set_index,
                     num_data,
                     num_coding,
                     version,
+                    merkle_variant,
                 );
-                shred.payload[SIZE_OF_CODING_SHRED_HEADERS..].copy_from_slice(parity);
+                shred.payload
+                    [SIZE_OF_CODING_SHRED_HEADERS..SIZE_OF_CODING_SHRED_HEADERS + encode_size]
+                    .copy_from_slice(parity);
                 shred
             })
             .collect()
     }
 
+    fn new_empty_coding_shred(
+        slot: Slot,
+        index: u32,
+        fec_set_index: u32,
+        num_data: usize,
+        num_code: usize,
+        version: u16,
+        merkle_variant: bool,
+    ) -> Shred {
+        let (mut header, coding_header) =
+            Self::new_coding_shred_header(slot, index, fec_set_index, num_data, num_code, version);
+        if merkle_variant {
+            header.shred_type = ShredType(MERKLE_CODING_SHRED);
+        }
+        Shred::new_empty_from_header(header, DataShredHeader::default(), coding_header)
+    }
+
     fn fill_in_missing_shreds(
         num_data: usize,
         num_coding: usize,
@@ -860,6 +1179,9 @@ impl Shredder {
         slot: Slot,
     ) -> std::result::Result<Vec<Shred>, reed_solomon_erasure::Error> {
         Self::verify_consistent_shred_payload_sizes("try_recovery()", &shreds)?;
+        if shreds[0].is_merkle() {
+            return Self::try_merkle_recovery(shreds, num_data, num_coding, first_index, slot);
+        }
         let mut recovered_data = vec![];
         let fec_set_size = num_data + num_coding;
 
@@ -948,6 +1270,126 @@ impl Shredder {
         Ok(recovered_data)
     }
 
+    // Merkle shreds are recovered by reconstructing every missing shred of the erasure batch, so
+    // that the Merkle tree of the batch can be rebuilt to produce the inclusion proofs of the
+    // recovered data shreds. Recovered shreds are dropped if the tree does not match the root
+    // signed by the leader.
+    fn try_merkle_recovery(
+        shreds: Vec<Shred>,
+        num_data: usize,
+        num_coding: usize,
+        first_index: usize,
+        slot: Slot,
+    ) -> std::result::Result<Vec<Shred>, reed_solomon_erasure::Error> {
+        let fec_set_size = num_data + num_coding;
+        if num_coding == 0 || shreds.len() >= fec_set_size {
+            return Ok(vec![]);
+        }
+        let (signature, version) = (shreds[0].signature(), shreds[0].version());
+        let root = shreds[0]
+            .merkle_root()
+            .ok_or(reed_solomon_erasure::Error::InvalidShardFlags)?;
+        let mut batch: Vec<Option<Shred>> = vec![None; fec_set_size];
+        for shred in shreds {
+            let position = match shred.merkle_leaf_index() {
+                Some(position) if position < fec_set_size => position,
+                _ => continue,
+            };
+            if shred.is_merkle()
+                && shred.slot() == slot
+                && shred.signature() == signature
+                && shred.merkle_root() == Some(root)
+                && shred.common_header.fec_set_index as usize == first_index
+                && shred.is_data() == (position < num_data)
+            {
+                batch[position] = Some(shred);
+            }
+        }
+
+        let mut blocks: Vec<_> = batch
+            .iter()
+            .enumerate()
+            .map(|(position, shred)| match shred {
+                None => vec![0u8; SIZE_OF_MERKLE_ERASURE_BLOCK],
+                Some(shred) => {
+                    let offset = if position < num_data {
+                        SIZE_OF_SIGNATURE
+                    } else {
+                        SIZE_OF_CODING_SHRED_HEADERS
+                    };
+                    shred.payload[offset..offset + SIZE_OF_MERKLE_ERASURE_BLOCK].to_vec()
+                }
+            })
+            .collect();
+        let present: Vec<_> = batch.iter().map(Option::is_some).collect();
+        let mut blocks_and_present: Vec<(&mut [u8], bool)> = blocks
+            .iter_mut()
+            .map(Vec::as_mut_slice)
+            .zip(present.iter().copied())
+            .collect();
+        Session::new(num_data, num_coding)?.decode_all_blocks(&mut blocks_and_present)?;
+
+        // Rebuild the missing shreds from their erasure blocks
+        let mut recovered = vec![false; fec_set_size];
+        for (position, block) in blocks.into_iter().enumerate() {
+            if batch[position].is_some() {
+                continue;
+            }
+            let shred = if position < num_data {
+                let mut payload = vec![0u8; SHRED_PAYLOAD_SIZE];
+                payload[SIZE_OF_SIGNATURE..SIZE_OF_SIGNATURE + SIZE_OF_MERKLE_ERASURE_BLOCK]
+                    .copy_from_slice(&block);
+                match Shred::new_from_serialized_shred(payload) {
+                    Ok(shred)
+                        if shred.is_merkle()
+                            && shred.is_data()
+                            && shred.slot() == slot
+                            && shred.index() as usize == first_index + position
+                            && shred.common_header.fec_set_index as usize == first_index =>
+                    {
+                        shred
+                    }
+                    _ => return Ok(vec![]),
+                }
+            } else {
+                let mut shred = Self::new_empty_coding_shred(
+                    slot,
+                    (first_index + position - num_data) as u32,
+                    first_index as u32,
+                    num_data,
+                    num_coding,
+                    version,
+                    true, // merkle_variant
+                );
+                shred.payload[SIZE_OF_CODING_SHRED_HEADERS..OFFSET_OF_MERKLE_PROOF]
+                    .copy_from_slice(&block);
+                shred
+            };
+            batch[position] = Some(shred);
+            recovered[position] = true;
+        }
+
+        let mut batch: Vec<Shred> = batch.into_iter().flatten().collect();
+        let tree = {
+            let leaves: Vec<_> = batch.iter().map(Shred::merkle_leaf).collect();
+            MerkleTree::new(&leaves)
+        };
+        if tree.get_root() != Some(&root) {
+            return Ok(vec![]);
+        }
+        let recovered_data = batch
+            .drain(..num_data)
+            .enumerate()
+            .filter(|(position, _)| recovered[*position])
+            .map(|(position, mut shred)| {
+                shred.set_merkle_proof(&tree.find_siblings(position).unwrap());
+                shred.set_signature(signature);
+                shred
+            })
+            .collect();
+        Ok(recovered_data)
+    }
+
     /// Combines all shreds to recreate the original buffer
     pub fn deshred(shreds: &[Shred]) -> std::result::Result<Vec<u8>, reed_solomon_erasure::Error> {
         use reed_solomon_erasure::Error::TooFewDataShards;
@@ -1062,9 +1504,9 @@ pub fn get_shred_slot_index_type(
         }
     }
 
-    let shred_type = p.data[OFFSET_OF_SHRED_TYPE];
-    if shred_type == DATA_SHRED || shred_type == CODING_SHRED {
-        return Some((slot, index, shred_type == DATA_SHRED));
+    let shred_type = ShredType(p.data[OFFSET_OF_SHRED_TYPE]);
+    if shred_type.is_data() || shred_type.is_code() {
+        return Some((slot, index, shred_type.is_data()));
     } else {
         stats.bad_shred_type += 1;
     }
@@ -1168,6 +1610,8 @@ pub mod tests {
             SIZE_OF_SHRED_INDEX,
             bincode::serialized_size(&ShredCommonHeader::default().index).unwrap() as usize
         );
+        assert_eq!(SIZE_OF_MERKLE_PROOF_ENTRY, size_of::<Hash>());
+        assert!(1 << MAX_MERKLE_PROOF_ENTRIES >= 2 * MAX_DATA_SHREDS_PER_FEC_BLOCK as usize);
     }
 
     fn verify_test_code_shred(shred: &Shred, index: u32, slot: Slot, pk: &Pubkey, verify: bool) {
@@ -1733,6 +2177,132 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_merkle_shreds_sign_and_verify() {
+        let mut rng = rand::thread_rng();
+        let keypair = Keypair::new();
+        let slot = 0x1234_5678;
+        let entries: Vec<_> = (0..5)
+            .map(|_| {
+                let txs = repeat_with(|| {
+                    let keypair = Keypair::new();
+                    system_transaction::transfer(&keypair, &keypair.pubkey(), 1, Hash::default())
+                })
+                .take(40)
+                .collect();
+                Entry::new(&Hash::default(), 1, txs)
+            })
+            .collect();
+        let shredder = Shredder::new(slot, slot - 5, 0, 0)
+            .unwrap()
+            .with_merkle_variant(true);
+        let (data_shreds, coding_shreds, _) =
+            shredder.entries_to_shreds(&keypair, &entries, true, 0);
+        // Several erasure batches
+        assert!(data_shreds.len() > MAX_DATA_SHREDS_PER_FEC_BLOCK as usize);
+        for shred in data_shreds.iter().chain(coding_shreds.iter()) {
+            assert!(shred.is_merkle());
+            assert!(shred.verify(&keypair.pubkey()));
+            assert!(!shred.verify(&Pubkey::new_unique()));
+            let batch_signature = data_shreds
+                .iter()
+                .find(|data| data.index() == shred.common_header.fec_set_index)
+                .unwrap()
+                .signature();
+            assert_eq!(shred.signature(), batch_signature);
+            let shred = Shred::new_from_serialized_shred(shred.payload.clone()).unwrap();
+            assert!(shred.verify(&keypair.pubkey()));
+            assert_eq!(
+                Shred::merkle_root_from_payload(&shred.payload),
+                Some((slot, shred.merkle_root().unwrap()))
+            );
+        }
+        let mut shred = data_shreds[rng.gen_range(0, data_shreds.len())].clone();
+        shred.payload[rng.gen_range(SIZE_OF_SIGNATURE, OFFSET_OF_MERKLE_PROOF)] ^= 1;
+        assert!(!shred.verify(&keypair.pubkey()));
+        let mut shred = coding_shreds[rng.gen_range(0, coding_shreds.len())].clone();
+        shred.payload[rng.gen_range(OFFSET_OF_MERKLE_PROOF + 1, SHRED_PAYLOAD_SIZE)] ^= 1;
+        assert!(!shred.verify(&keypair.pubkey()));
+
+        let deserialized: Vec<Entry> =
+            bincode::deserialize(&Shredder::deshred(&data_shreds).unwrap()).unwrap();
+        assert_eq!(deserialized, entries);
+    }
+
+    fn run_merkle_recovery(num_tx: usize, is_last_in_slot: bool) {
+        let mut rng = rand::thread_rng();
+        let txs = repeat_with(|| {
+            system_transaction::transfer(
+                &Keypair::new(),          // from
+                &Pubkey::new_unique(),    // to
+                rng.gen(),                // lamports
+                hash::new_rand(&mut rng), // recent block hash
+            )
+        })
+        .take(num_tx)
+        .collect();
+        let entry = Entry::new(&hash::new_rand(&mut rng), 1, txs);
+        let keypair = Keypair::new();
+        let slot = 71489660;
+        let shredder = Shredder::new(slot, slot - 1, 0, rng.gen())
+            .unwrap()
+            .with_merkle_variant(true);
+        let next_shred_index = rng.gen_range(1, 1024);
+        let (data_shreds, coding_shreds, _) =
+            shredder.entries_to_shreds(&keypair, &[entry], is_last_in_slot, next_shred_index);
+        let num_data_shreds = data_shreds.len();
+        let num_coding_shreds = coding_shreds.len();
+        let mut shreds = coding_shreds;
+        shreds.extend(data_shreds.iter().cloned());
+        shreds.shuffle(&mut rng);
+        shreds.truncate(num_data_shreds);
+        let exclude: HashSet<_> = shreds
+            .iter()
+            .filter(|shred| shred.is_data())
+            .map(|shred| shred.index())
+            .collect();
+        let recovered_shreds = Shredder::try_recovery(
+            shreds.clone(),
+            num_data_shreds,
+            num_coding_shreds,
+            next_shred_index as usize, // first index
+            slot,
+        )
+        .unwrap();
+        let expected: Vec<_> = data_shreds
+            .into_iter()
+            .filter(|shred| !exclude.contains(&shred.index()))
+            .collect();
+        assert_eq!(recovered_shreds, expected);
+        for shred in &recovered_shreds {
+            assert!(shred.verify(&keypair.pubkey()));
+        }
+
+        // A corrupted shred never yields recovered shreds which fail verification
+        if let Some(shred) = shreds.iter_mut().find(|shred| shred.is_code()) {
+            shred.payload[OFFSET_OF_MERKLE_PROOF - 1] ^= 1;
+            if let Ok(recovered_shreds) = Shredder::try_recovery(
+                shreds,
+                num_data_shreds,
+                num_coding_shreds,
+                next_shred_index as usize, // first index
+                slot,
+            ) {
+                for shred in &recovered_shreds {
+                    assert!(shred.verify(&keypair.pubkey()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_merkle_recovery() {
+        for num_tx in 0..50 {
+            run_merkle_recovery(num_tx, false);
+            run_merkle_recovery(num_tx, true);
+        }
+    }
+
     #[test]
     fn test_shred_version() {
         let keypair = Arc::new(Keypair::new());
@@ -1,5 +1,5 @@
 #![allow(clippy::implicit_hasher)]
-use crate::shred::{ShredType, SIZE_OF_NONCE};
+use crate::shred::{Shred, ShredType, SHRED_PAYLOAD_SIZE, SIZE_OF_NONCE, SIZE_OF_SIGNATURE};
 use rayon::{
     iter::{
         IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
@@ -18,14 +18,67 @@ use solana_perf::{
 use solana_rayon_threadlimit::get_thread_count;
 use solana_sdk::{
     clock::Slot,
+    hash::Hash,
     pubkey::Pubkey,
     signature::Signature,
     signature::{Keypair, Signer},
 };
-use std::sync::Arc;
-use std::{collections::HashMap, mem::size_of};
+use std::sync::{Arc, RwLock};
+use std::{
+    collections::{HashMap, VecDeque},
+    mem::size_of,
+};
 
 pub const SIGN_SHRED_GPU_MIN: usize = 256;
+pub const MAX_MERKLE_ROOT_CACHE_SIZE: usize = 8192;
+
+/// Merkle roots of erasure batches whose signature has already been verified. All shreds of a
+/// Merkle batch carry the same signature over the same root, so only the first shred received
+/// from each batch needs an ed25519 verification.
+#[derive(Default)]
+pub struct MerkleRootCache {
+    roots: RwLock<VerifiedMerkleRoots>,
+}
+
+#[derive(Default)]
+struct VerifiedMerkleRoots {
+    // (slot, merkle root) => (leader pubkey, signature)
+    roots: HashMap<(Slot, Hash), ([u8; 32], Signature)>,
+    // keys of `roots` in insertion order, the first one is evicted once the cache is full
+    insertion_order: VecDeque<(Slot, Hash)>,
+}
+
+impl MerkleRootCache {
+    fn contains(&self, slot: Slot, root: &Hash, pubkey: &[u8; 32], signature: &Signature) -> bool {
+        self.roots.read().unwrap().roots.get(&(slot, *root)) == Some(&(*pubkey, *signature))
+    }
+
+    fn insert(&self, slot: Slot, root: Hash, pubkey: [u8; 32], signature: Signature) {
+        let mut cache = self.roots.write().unwrap();
+        let VerifiedMerkleRoots {
+            roots,
+            insertion_order,
+        } = &mut *cache;
+        // Several threads may have verified the same root concurrently
+        if roots.insert((slot, root), (pubkey, signature)).is_some() {
+            return;
+        }
+        insertion_order.push_back((slot, root));
+        if insertion_order.len() > MAX_MERKLE_ROOT_CACHE_SIZE {
+            if let Some(oldest) = insertion_order.pop_front() {
+                roots.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.roots.read().unwrap().roots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
 
 lazy_static! {
     pub static ref SIGVERIFY_THREAD_POOL: ThreadPool = rayon::ThreadPoolBuilder::new()
@@ -44,6 +97,9 @@ lazy_static! {
 /// }
 /// Signature is the first thing in the packet, and slot is the first thing in the signed message.
 pub fn verify_shred_cpu(packet: &Packet, slot_leaders: &HashMap<u64, [u8; 32]>) -> Option<u8> {
+    if Shred::is_merkle_packet(packet) {
+        return verify_merkle_shred_cpu(packet, slot_leaders, None);
+    }
     let sig_start = 0;
     let sig_end = size_of::<Signature>();
     let slot_start = sig_end + size_of::<ShredType>();
@@ -75,7 +131,52 @@ pub fn verify_shred_cpu(packet: &Packet, slot_leaders: &HashMap<u64, [u8; 32]>)
     Some(1)
 }
 
-fn verify_shreds_cpu(batches: &[Packets], slot_leaders: &HashMap<u64, [u8; 32]>) -> Vec<Vec<u8>> {
+/// Merkle shreds sign the Merkle root of their erasure batch, which is computed from the
+/// shred's inclusion proof. Roots already verified are looked up in `merkle_root_cache`, before
+/// the signature is verified.
+fn verify_merkle_shred_cpu(
+    packet: &Packet,
+    slot_leaders: &HashMap<u64, [u8; 32]>,
+    merkle_root_cache: Option<&MerkleRootCache>,
+) -> Option<u8> {
+    if packet.meta.discard {
+        return Some(0);
+    }
+    let size = if packet.meta.repair {
+        packet.meta.size.saturating_sub(SIZE_OF_NONCE)
+    } else {
+        packet.meta.size
+    };
+    if size < SHRED_PAYLOAD_SIZE {
+        return Some(0);
+    }
+    let payload = &packet.data[..SHRED_PAYLOAD_SIZE];
+    let (slot, root) = match Shred::merkle_root_from_payload(payload) {
+        Some(slot_and_root) => slot_and_root,
+        None => return Some(0),
+    };
+    let pubkey = slot_leaders.get(&slot)?;
+    let signature = Signature::new(&payload[..SIZE_OF_SIGNATURE]);
+    if let Some(cache) = merkle_root_cache {
+        if cache.contains(slot, &root, pubkey, &signature) {
+            return Some(1);
+        }
+    }
+    trace!("merkle root {} signature {}", root, signature);
+    if !signature.verify(pubkey, root.as_ref()) {
+        return Some(0);
+    }
+    if let Some(cache) = merkle_root_cache {
+        cache.insert(slot, root, *pubkey, signature);
+    }
+    Some(1)
+}
+
+fn verify_shreds_cpu(
+    batches: &[Packets],
+    slot_leaders: &HashMap<u64, [u8; 32]>,
+    merkle_root_cache: &MerkleRootCache,
+) -> Vec<Vec<u8>> {
     use rayon::prelude::*;
     let count = batch_size(batches);
     debug!("CPU SHRED ECDSA for {}", count);
@@ -85,7 +186,14 @@ fn verify_shreds_cpu(batches: &[Packets], slot_leaders: &HashMap<u64, [u8; 32]>)
             .map(|p| {
                 p.packets
                     .par_iter()
-                    .map(|p| verify_shred_cpu(p, slot_leaders).unwrap_or(0))
+                    .map(|p| {
+                        if Shred::is_merkle_packet(p) {
+                            verify_merkle_shred_cpu(p, slot_leaders, Some(merkle_root_cache))
+                        } else {
+                            verify_shred_cpu(p, slot_leaders)
+                        }
+                        .unwrap_or(0)
+                    })
                     .collect()
             })
             .collect()
@@ -94,6 +202,31 @@ fn verify_shreds_cpu(batches: &[Packets], slot_leaders: &HashMap<u64, [u8; 32]>)
     rv
 }
 
+// The GPU verifies signatures over the packet payload, which Merkle shreds do not sign, so
+// their results are replaced by verifying the Merkle roots on the CPU.
+fn verify_merkle_shreds_after_gpu(
+    batches: &[Packets],
+    slot_leaders: &HashMap<u64, [u8; 32]>,
+    merkle_root_cache: &MerkleRootCache,
+    rvs: &mut [Vec<u8>],
+) {
+    use rayon::prelude::*;
+    SIGVERIFY_THREAD_POOL.install(|| {
+        batches
+            .par_iter()
+            .zip(rvs.par_iter_mut())
+            .for_each(|(batch, rv)| {
+                for (packet, rv) in batch.packets.iter().zip(rv.iter_mut()) {
+                    if Shred::is_merkle_packet(packet) {
+                        *rv =
+                            verify_merkle_shred_cpu(packet, slot_leaders, Some(merkle_root_cache))
+                                .unwrap_or(0);
+                    }
+                }
+            })
+    });
+}
+
 fn slot_key_data_for_gpu<
     T: Sync + Sized + Default + std::fmt::Debug + Eq + std::hash::Hash + Clone + Copy + AsRef<[u8]>,
 >(
@@ -223,10 +356,11 @@ pub fn verify_shreds_gpu(
     batches: &[Packets],
     slot_leaders: &HashMap<u64, [u8; 32]>,
     recycler_cache: &RecyclerCache,
+    merkle_root_cache: &MerkleRootCache,
 ) -> Vec<Vec<u8>> {
     let api = perf_libs::api();
     if api.is_none() {
-        return verify_shreds_cpu(batches, slot_leaders);
+        return verify_shreds_cpu(batches, slot_leaders, merkle_root_cache);
     }
     let api = api.unwrap();
 
@@ -288,6 +422,7 @@ pub fn verify_shreds_gpu(
     trace!("out buf {:?}", out);
 
     sigverify::copy_return_values(&v_sig_lens, &out, &mut rvs);
+    verify_merkle_shreds_after_gpu(batches, slot_leaders, merkle_root_cache, &mut rvs);
 
     inc_new_counter_debug!("ed25519_shred_verify_gpu", count);
     rvs
@@ -452,7 +587,10 @@ pub fn sign_shreds_gpu(
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::shred::{Shred, Shredder, SIZE_OF_DATA_SHRED_PAYLOAD};
+    use crate::{
+        entry::Entry,
+        shred::{Shred, Shredder, SIZE_OF_DATA_SHRED_PAYLOAD},
+    };
     use solana_sdk::signature::{Keypair, Signer};
 
     fn run_test_sigverify_shred_cpu(slot: Slot) {
@@ -525,7 +663,7 @@ pub mod tests {
             .iter()
             .cloned()
             .collect();
-        let rv = verify_shreds_cpu(&batch, &leader_slots);
+        let rv = verify_shreds_cpu(&batch, &leader_slots, &MerkleRootCache::default());
         assert_eq!(rv, vec![vec![1]]);
 
         let wrong_keypair = Keypair::new();
@@ -533,11 +671,11 @@ pub mod tests {
             .iter()
             .cloned()
             .collect();
-        let rv = verify_shreds_cpu(&batch, &leader_slots);
+        let rv = verify_shreds_cpu(&batch, &leader_slots, &MerkleRootCache::default());
         assert_eq!(rv, vec![vec![0]]);
 
         let leader_slots = HashMap::new();
-        let rv = verify_shreds_cpu(&batch, &leader_slots);
+        let rv = verify_shreds_cpu(&batch, &leader_slots, &MerkleRootCache::default());
         assert_eq!(rv, vec![vec![0]]);
 
         let leader_slots = [(slot, keypair.pubkey().to_bytes())]
@@ -545,7 +683,7 @@ pub mod tests {
             .cloned()
             .collect();
         batch[0].packets[0].meta.size = 0;
-        let rv = verify_shreds_cpu(&batch, &leader_slots);
+        let rv = verify_shreds_cpu(&batch, &leader_slots, &MerkleRootCache::default());
         assert_eq!(rv, vec![vec![0]]);
     }
 
@@ -554,6 +692,76 @@ pub mod tests {
         run_test_sigverify_shreds_cpu(0xdead_c0de);
     }
 
+    #[test]
+    fn test_sigverify_merkle_shreds() {
+        solana_logger::setup();
+        let slot = 0xdead_c0de;
+        let keypair = Keypair::new();
+        let shredder = Shredder::new(slot, slot - 1, 0, 0)
+            .unwrap()
+            .with_merkle_variant(true);
+        let (data_shreds, coding_shreds, _) =
+            shredder.entries_to_shreds(&keypair, &[Entry::default()], true, 0);
+        let mut batch = [Packets::default()];
+        for shred in data_shreds.iter().chain(coding_shreds.iter()) {
+            let mut packet = Packet::default();
+            shred.copy_to_packet(&mut packet);
+            batch[0].packets.push(packet);
+        }
+        let num_shreds = batch[0].packets.len();
+
+        let leader_slots = [(slot, keypair.pubkey().to_bytes())]
+            .iter()
+            .cloned()
+            .collect();
+        assert_eq!(
+            verify_shred_cpu(&batch[0].packets[0], &leader_slots),
+            Some(1)
+        );
+        let merkle_root_cache = MerkleRootCache::default();
+        let rv = verify_shreds_cpu(&batch, &leader_slots, &merkle_root_cache);
+        assert_eq!(rv, vec![vec![1; num_shreds]]);
+        // All shreds belong to the same erasure batch
+        assert_eq!(merkle_root_cache.len(), 1);
+
+        let wrong_keypair = Keypair::new();
+        let wrong_leader_slots = [(slot, wrong_keypair.pubkey().to_bytes())]
+            .iter()
+            .cloned()
+            .collect();
+        let rv = verify_shreds_cpu(&batch, &wrong_leader_slots, &merkle_root_cache);
+        assert_eq!(rv, vec![vec![0; num_shreds]]);
+
+        // A cached root does not vouch for a tampered shred
+        batch[0].packets[1].data[SIZE_OF_DATA_SHRED_PAYLOAD] ^= 1;
+        let rv = verify_shreds_cpu(&batch, &leader_slots, &merkle_root_cache);
+        assert_eq!(rv[0][0], 1);
+        assert_eq!(rv[0][1], 0);
+    }
+
+    #[test]
+    fn test_merkle_root_cache_eviction() {
+        let cache = MerkleRootCache::default();
+        let pubkey = Pubkey::new_unique().to_bytes();
+        let roots: Vec<_> = (0..=MAX_MERKLE_ROOT_CACHE_SIZE)
+            .map(|_| Hash::new_unique())
+            .collect();
+        for root in &roots {
+            cache.insert(0, *root, pubkey, Signature::default());
+        }
+        // Only the first root inserted is evicted
+        assert_eq!(cache.len(), MAX_MERKLE_ROOT_CACHE_SIZE);
+        assert!(!cache.contains(0, &roots[0], &pubkey, &Signature::default()));
+        for root in &roots[1..] {
+            assert!(cache.contains(0, root, &pubkey, &Signature::default()));
+        }
+
+        // Inserting a root again does not evict anything
+        cache.insert(0, roots[1], pubkey, Signature::default());
+        assert_eq!(cache.len(), MAX_MERKLE_ROOT_CACHE_SIZE);
+        assert!(cache.contains(0, &roots[2], &pubkey, &Signature::default()));
+    }
+
     fn run_test_sigverify_shreds_gpu(slot: Slot) {
         solana_logger::setup();
         let recycler_cache = RecyclerCache::default();
@@ -583,7 +791,12 @@ pub mod tests {
         .iter()
         .cloned()
         .collect();
-        let rv = verify_shreds_gpu(&batch, &leader_slots, &recycler_cache);
+        let rv = verify_shreds_gpu(
+            &batch,
+            &leader_slots,
+            &recycler_cache,
+            &MerkleRootCache::default(),
+        );
         assert_eq!(rv, vec![vec![1]]);
 
         let wrong_keypair = Keypair::new();
@@ -594,11 +807,21 @@ pub mod tests {
         .iter()
         .cloned()
         .collect();
-        let rv = verify_shreds_gpu(&batch, &leader_slots, &recycler_cache);
+        let rv = verify_shreds_gpu(
+            &batch,
+            &leader_slots,
+            &recycler_cache,
+            &MerkleRootCache::default(),
+        );
         assert_eq!(rv, vec![vec![0]]);
 
         let leader_slots = [(std::u64::MAX, [0u8; 32])].iter().cloned().collect();
-        let rv = verify_shreds_gpu(&batch, &leader_slots, &recycler_cache);
+        let rv = verify_shreds_gpu(
+            &batch,
+            &leader_slots,
+            &recycler_cache,
+            &MerkleRootCache::default(),
+        );
         assert_eq!(rv, vec![vec![0]]);
 
         batch[0].packets[0].meta.size = 0;
@@ -609,7 +832,12 @@ pub mod tests {
         .iter()
         .cloned()
         .collect();
-        let rv = verify_shreds_gpu(&batch, &leader_slots, &recycler_cache);
+        let rv = verify_shreds_gpu(
+            &batch,
+            &leader_slots,
+            &recycler_cache,
+            &MerkleRootCache::default(),
+        );
         assert_eq!(rv, vec![vec![0]]);
     }
 
@@ -652,14 +880,24 @@ pub mod tests {
         .cloned()
         .collect();
         //unsigned
-        let rv = verify_shreds_gpu(&batch, &pubkeys, &recycler_cache);
+        let rv = verify_shreds_gpu(
+            &batch,
+            &pubkeys,
+            &recycler_cache,
+            &MerkleRootCache::default(),
+        );
         assert_eq!(rv, vec![vec![0; num_packets]; num_batches]);
         //signed
         sign_shreds_gpu(&keypair, &pinned_keypair, &mut batch, &recycler_cache);
-        let rv = verify_shreds_cpu(&batch, &pubkeys);
+        let rv = verify_shreds_cpu(&batch, &pubkeys, &MerkleRootCache::default());
         assert_eq!(rv, vec![vec![1; num_packets]; num_batches]);
 
-        let rv = verify_shreds_gpu(&batch, &pubkeys, &recycler_cache);
+        let rv = verify_shreds_gpu(
+            &batch,
+            &pubkeys,
+            &recycler_cache,
+            &MerkleRootCache::default(),
+        );
         assert_eq!(rv, vec![vec![1; num_packets]; num_batches]);
     }
 
@@ -695,11 +933,11 @@ pub mod tests {
         .cloned()
         .collect();
         //unsigned
-        let rv = verify_shreds_cpu(&batch, &pubkeys);
+        let rv = verify_shreds_cpu(&batch, &pubkeys, &MerkleRootCache::default());
         assert_eq!(rv, vec![vec![0]]);
         //signed
         sign_shreds_cpu(&keypair, &mut batch);
-        let rv = verify_shreds_cpu(&batch, &pubkeys);
+        let rv = verify_shreds_cpu(&batch, &pubkeys, &MerkleRootCache::default());
         assert_eq!(rv, vec![vec![1]]);
     }
 
@@ -12,6 +12,9 @@ pub struct ValidatorInfo {
     pub voting_keypair: Arc<Keypair>,
     pub ledger_path: PathBuf,
     pub contact_info: ContactInfo,
+    /// Local ports of the UDP sockets of the node, which its packets are sent
+    /// from
+    pub udp_ports: Vec<u16>,
 }
 
 pub struct ClusterValidatorInfo {
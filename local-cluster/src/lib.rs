@@ -2,4 +2,5 @@
 pub mod cluster;
 pub mod cluster_tests;
 pub mod local_cluster;
+pub mod network_scenario;
 pub mod validator_configs;
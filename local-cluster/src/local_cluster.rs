@@ -94,6 +94,23 @@ impl Default for ClusterConfig {
     }
 }
 
+fn udp_ports(node: &Node) -> Vec<u16> {
+    let sockets = &node.sockets;
+    iter::once(&sockets.gossip)
+        .chain(&sockets.tvu)
+        .chain(&sockets.tvu_forwards)
+        .chain(&sockets.tpu)
+        .chain(&sockets.tpu_forwards)
+        .chain(&sockets.broadcast)
+        .chain(iter::once(&sockets.repair))
+        .chain(&sockets.retransmit_sockets)
+        .chain(iter::once(&sockets.serve_repair))
+        .chain(iter::once(&sockets.ancestor_hashes_requests))
+        .filter_map(|socket| socket.local_addr().ok())
+        .map(|addr| addr.port())
+        .collect()
+}
+
 pub struct LocalCluster {
     /// Keypair with funding to participate in the network
     pub funding_keypair: Keypair,
@@ -202,6 +219,7 @@ impl LocalCluster {
 
         let (leader_ledger_path, _blockhash) = create_new_tmp_ledger!(&genesis_config);
         let leader_contact_info = leader_node.info.clone();
+        let leader_udp_ports = udp_ports(&leader_node);
         let mut leader_config = safe_clone_config(&config.validator_configs[0]);
         leader_config.rpc_addrs = Some((leader_node.info.rpc, leader_node.info.rpc_pubsub));
         leader_config.account_paths = vec![leader_ledger_path.join("accounts")];
@@ -227,6 +245,7 @@ impl LocalCluster {
             voting_keypair: leader_vote_keypair,
             ledger_path: leader_ledger_path,
             contact_info: leader_contact_info.clone(),
+            udp_ports: leader_udp_ports,
         };
         let cluster_leader = ClusterValidatorInfo::new(
             leader_info,
@@ -319,6 +338,7 @@ impl LocalCluster {
         let validator_pubkey = validator_keypair.pubkey();
         let validator_node = Node::new_localhost_with_pubkey(&validator_keypair.pubkey());
         let contact_info = validator_node.info.clone();
+        let validator_udp_ports = udp_ports(&validator_node);
         let (ledger_path, _blockhash) = create_new_tmp_ledger!(&self.genesis_config);
 
         if validator_config.voting_disabled {
@@ -370,6 +390,7 @@ impl LocalCluster {
                 voting_keypair,
                 ledger_path,
                 contact_info,
+                udp_ports: validator_udp_ports,
             },
             safe_clone_config(validator_config),
             validator_server,
@@ -632,6 +653,7 @@ impl Cluster for LocalCluster {
         // Update the stored ContactInfo for this node
         let node = Node::new_localhost_with_pubkey(pubkey);
         cluster_validator_info.info.contact_info = node.info.clone();
+        cluster_validator_info.info.udp_ports = udp_ports(&node);
         cluster_validator_info.config.rpc_addrs = Some((node.info.rpc, node.info.rpc_pubsub));
 
         let entry_point_info = {
@@ -0,0 +1,220 @@
+//! In-process simulation of the net-shaper scenarios: the packets exchanged by
+//! the nodes of a local cluster are dropped or delayed by the streamer,
+//! according to the partitions and interconnects of each step.
+//!
+//! Nodes are told apart by the ports of their UDP sockets, including the
+//! ephemeral socket their votes are sent from. The packets sent from the other
+//! ephemeral sockets are left alone: those of clients, the transactions sent
+//! by the RPC service, and the packets forwarded by the banking stage to the
+//! next leader, which may include votes of the partition of the forwarder.
+
+use crate::local_cluster::LocalCluster;
+use log::*;
+use rand::{thread_rng, Rng};
+use solana_net_shaper::{
+    scenario::Scenario,
+    topology::{NetemConfig, NetworkTopology},
+};
+use solana_sdk::pubkey::Pubkey;
+use solana_streamer::packet_shaper::{set_packet_shaper, PacketFate, PacketShaper};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread::{self, Builder, JoinHandle},
+    time::{Duration, Instant},
+};
+
+#[derive(Default)]
+struct Links {
+    partitions: HashMap<u16, usize>,
+    interconnects: HashMap<(usize, usize), NetemConfig>,
+}
+
+pub struct ScenarioShaper {
+    node_ports: Vec<Vec<u16>>,
+    links: RwLock<Links>,
+}
+
+impl ScenarioShaper {
+    /// `node_ports` holds the UDP ports of each node, in the order the nodes
+    /// are assigned to the partitions
+    pub fn new(node_ports: Vec<Vec<u16>>) -> Self {
+        Self {
+            node_ports,
+            links: RwLock::default(),
+        }
+    }
+
+    pub fn apply(&self, topology: &NetworkTopology) -> Result<(), String> {
+        if !topology.verify() {
+            return Err("invalid topology".to_string());
+        }
+        let num_nodes = self.node_ports.len() as u64;
+        let partitions = self
+            .node_ports
+            .iter()
+            .enumerate()
+            .flat_map(|(index, ports)| {
+                let partition = topology.partition_of(index as u64, num_nodes);
+                ports.iter().map(move |port| (*port, partition))
+            })
+            .collect();
+        let interconnects = topology
+            .interconnects
+            .iter()
+            .map(|interconnect| {
+                let config: NetemConfig = interconnect.config.parse()?;
+                Ok(((interconnect.a as usize, interconnect.b as usize), config))
+            })
+            .collect::<Result<_, String>>()?;
+        *self.links.write().unwrap() = Links {
+            partitions,
+            interconnects,
+        };
+        Ok(())
+    }
+}
+
+impl PacketShaper for ScenarioShaper {
+    fn shape(&self, from: &SocketAddr, to: &SocketAddr) -> PacketFate {
+        let links = self.links.read().unwrap();
+        let config = links
+            .partitions
+            .get(&from.port())
+            .zip(links.partitions.get(&to.port()))
+            .and_then(|(a, b)| links.interconnects.get(&(*a, *b)));
+        let config = match config {
+            Some(config) => config,
+            None => return PacketFate::Deliver,
+        };
+        let mut rng = thread_rng();
+        if rng.gen_range(0.0, 100.0) < config.loss_percent {
+            return PacketFate::Drop;
+        }
+        let jitter = config.jitter_ms as i64;
+        let delay_ms = config.delay_ms as i64 + rng.gen_range(-jitter, jitter + 1);
+        if delay_ms > 0 {
+            PacketFate::Delay(Duration::from_millis(delay_ms as u64))
+        } else {
+            PacketFate::Deliver
+        }
+    }
+}
+
+/// Plays a scenario against a local cluster, until the runner is dropped. The
+/// last topology of the scenario remains applied once its timeline is over.
+pub struct ScenarioRunner {
+    exit: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ScenarioRunner {
+    /// Starts the scenario, with `nodes` assigned to the partitions in order.
+    /// Nodes restarted afterwards bind new ports, and are no longer shaped.
+    pub fn start(cluster: &LocalCluster, nodes: &[Pubkey], scenario: Scenario) -> Self {
+        scenario.verify().expect("invalid scenario");
+        let node_ports = nodes
+            .iter()
+            .map(|pubkey| {
+                let node = &cluster.validators[pubkey];
+                let mut ports = node.info.udp_ports.clone();
+                if let Some(validator) = &node.validator {
+                    ports.push(validator.cluster_info.send_transaction_addr().port());
+                }
+                ports
+            })
+            .collect();
+        let shaper = Arc::new(ScenarioShaper::new(node_ports));
+        set_packet_shaper(Some(shaper.clone()));
+        let exit = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let exit = exit.clone();
+            Builder::new()
+                .name("solana-network-scenario".to_string())
+                .spawn(move || {
+                    let start = Instant::now();
+                    for (at, topology) in scenario.timeline() {
+                        while start.elapsed() < at {
+                            if exit.load(Ordering::Relaxed) {
+                                return;
+                            }
+                            thread::sleep(Duration::from_millis(100));
+                        }
+                        info!(
+                            "network scenario at {:?}: partitions {:?}, interconnects {:?}",
+                            at, topology.partitions, topology.interconnects
+                        );
+                        shaper.apply(&topology).unwrap();
+                    }
+                    info!("network scenario complete");
+                })
+                .unwrap()
+        };
+        Self {
+            exit,
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops the scenario, and removes the shaper
+    pub fn stop(self) {
+        drop(self)
+    }
+}
+
+impl Drop for ScenarioRunner {
+    fn drop(&mut self) {
+        self.exit.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+        set_packet_shaper(None);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use solana_net_shaper::topology::NetworkInterconnect;
+
+    #[test]
+    fn test_scenario_shaper() {
+        let shaper = ScenarioShaper::new(vec![vec![1000, 1001], vec![2000], vec![3000]]);
+        let addr = |port| SocketAddr::from(([127, 0, 0, 1], port));
+        // Nothing is shaped until a topology is applied.
+        assert_eq!(shaper.shape(&addr(1000), &addr(2000)), PacketFate::Deliver);
+
+        let topology = NetworkTopology {
+            partitions: vec![34, 66],
+            interconnects: vec![
+                NetworkInterconnect {
+                    a: 0,
+                    b: 1,
+                    config: "loss 100%".to_string(),
+                },
+                NetworkInterconnect {
+                    a: 1,
+                    b: 0,
+                    config: "delay 50ms".to_string(),
+                },
+            ],
+        };
+        shaper.apply(&topology).unwrap();
+        assert_eq!(shaper.shape(&addr(1001), &addr(2000)), PacketFate::Drop);
+        assert_eq!(shaper.shape(&addr(1000), &addr(3000)), PacketFate::Drop);
+        assert_eq!(
+            shaper.shape(&addr(3000), &addr(1000)),
+            PacketFate::Delay(Duration::from_millis(50))
+        );
+        // Within a partition, or from unknown sockets.
+        assert_eq!(shaper.shape(&addr(2000), &addr(3000)), PacketFate::Deliver);
+        assert_eq!(shaper.shape(&addr(4000), &addr(1000)), PacketFate::Deliver);
+
+        shaper.apply(&NetworkTopology::default()).unwrap();
+        assert_eq!(shaper.shape(&addr(1000), &addr(2000)), PacketFate::Deliver);
+    }
+}
@@ -57,6 +57,13 @@ pub fn safe_clone_config(config: &ValidatorConfig) -> ValidatorConfig {
         poh_hashes_per_batch: config.poh_hashes_per_batch,
         no_wait_for_vote_to_start_leader: config.no_wait_for_vote_to_start_leader,
         accounts_shrink_ratio: config.accounts_shrink_ratio,
+        dump_bank_hash_details_on_mismatch: config.dump_bank_hash_details_on_mismatch,
+        banking_scheduler_type: config.banking_scheduler_type,
+        sigverify_discard_policy: config.sigverify_discard_policy,
+        wen_restart: config.wen_restart,
+        gossip_data_budgets: config.gossip_data_budgets.clone(),
+        gossip_push_compact_contact_info: config.gossip_push_compact_contact_info,
+        gossip_push_restart_intent: config.gossip_push_restart_intent,
     }
 }
 
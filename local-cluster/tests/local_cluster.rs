@@ -35,8 +35,13 @@ use solana_local_cluster::{
     cluster::{Cluster, ClusterValidatorInfo},
     cluster_tests,
     local_cluster::{ClusterConfig, LocalCluster},
+    network_scenario::ScenarioRunner,
     validator_configs::*,
 };
+use solana_net_shaper::{
+    scenario::{Scenario, ScenarioStep},
+    topology::{NetworkInterconnect, NetworkTopology},
+};
 use solana_runtime::{
     snapshot_config::SnapshotConfig,
     snapshot_utils::{self, ArchiveFormat},
@@ -476,6 +481,52 @@ fn test_cluster_partition_1_1_1() {
     )
 }
 
+#[test]
+#[serial]
+fn test_network_scenario_partition_and_heal() {
+    solana_logger::setup_with_default(RUST_LOG_FILTER);
+    error!("test_network_scenario_partition_and_heal");
+    let num_nodes = 2;
+    let cluster = LocalCluster::new_with_equal_stakes(num_nodes, 10_000, 100);
+    let partition = |config: &str| NetworkTopology {
+        partitions: vec![50, 50],
+        interconnects: vec![
+            NetworkInterconnect {
+                a: 0,
+                b: 1,
+                config: config.to_string(),
+            },
+            NetworkInterconnect {
+                a: 1,
+                b: 0,
+                config: config.to_string(),
+            },
+        ],
+    };
+    // Neither half of the stake can root on its own until the partition heals.
+    // Only the votes sent directly to a leader across the partition are
+    // dropped, not those forwarded to it by the banking stage
+    let scenario = Scenario {
+        steps: vec![
+            ScenarioStep {
+                at_secs: 0,
+                ramp_secs: 0,
+                topology: partition("loss 100%"),
+            },
+            ScenarioStep {
+                at_secs: 30,
+                ramp_secs: 0,
+                topology: partition("delay 20ms 5ms"),
+            },
+        ],
+    };
+    let nodes: Vec<_> = cluster.validators.keys().cloned().collect();
+    let runner = ScenarioRunner::start(&cluster, &nodes, scenario);
+    cluster.check_no_new_roots(20, "test_network_scenario_partition_and_heal");
+    cluster.check_for_new_roots(16, "test_network_scenario_partition_and_heal");
+    runner.stop();
+}
+
 fn create_custom_leader_schedule(
     validator_num_slots: &[usize],
 ) -> (LeaderSchedule, Vec<Arc<Keypair>>) {
@@ -3010,6 +3061,87 @@ fn test_hard_fork_invalidates_tower() {
     cluster.lock().unwrap().check_for_new_roots(16, "hard fork");
 }
 
+#[test]
+#[serial]
+fn test_wen_restart() {
+    solana_logger::setup_with_default(RUST_LOG_FILTER);
+
+    // First set up the cluster with 2 nodes, both taking snapshots so they can restart
+    // from the restart slot
+    let slots_per_epoch = 2048;
+    let node_stakes = vec![60, 40];
+    let snapshot_interval_slots = 10_000;
+    let snapshot_test_configs: Vec<_> = (0..node_stakes.len())
+        .map(|_| setup_snapshot_validator_config(snapshot_interval_slots, 1))
+        .collect();
+
+    let validator_keys: Vec<_> = (0..node_stakes.len())
+        .map(|_| (Arc::new(Keypair::new()), true))
+        .collect();
+    let validator_a_pubkey = validator_keys[0].0.pubkey();
+    let validator_b_pubkey = validator_keys[1].0.pubkey();
+
+    let mut config = ClusterConfig {
+        cluster_lamports: 100_000,
+        node_stakes,
+        validator_configs: snapshot_test_configs
+            .iter()
+            .map(|snapshot_test_config| safe_clone_config(&snapshot_test_config.validator_config))
+            .collect(),
+        validator_keys: Some(validator_keys),
+        slots_per_epoch,
+        stakers_slot_offset: slots_per_epoch,
+        skip_warmup_slots: true,
+        ..ClusterConfig::default()
+    };
+    let mut cluster = LocalCluster::new(&mut config);
+
+    let val_a_ledger_path = cluster.ledger_path(&validator_a_pubkey);
+    let min_root = 15;
+    loop {
+        sleep(Duration::from_millis(100));
+
+        if let Some(root) = root_in_tower(&val_a_ledger_path, &validator_a_pubkey) {
+            if root >= min_root {
+                break;
+            }
+        }
+    }
+
+    // Stall the cluster by shutting down all the nodes
+    let mut validator_a_info = cluster.exit_node(&validator_a_pubkey);
+    let mut validator_b_info = cluster.exit_node(&validator_b_pubkey);
+    validator_a_info.config.wen_restart = true;
+    validator_b_info.config.wen_restart = true;
+
+    // Both nodes block in Validator::new() until the other announces its last voted fork,
+    // and then its bank hash of the restart slot
+    let restart_context_a =
+        cluster.create_restart_context(&validator_a_pubkey, &mut validator_a_info);
+    let restart_context_b =
+        cluster.create_restart_context(&validator_b_pubkey, &mut validator_b_info);
+    let thread_a = std::thread::spawn(move || {
+        LocalCluster::restart_node_with_context(validator_a_info, restart_context_a)
+    });
+    let thread_b = std::thread::spawn(move || {
+        LocalCluster::restart_node_with_context(validator_b_info, restart_context_b)
+    });
+    cluster.add_node(&validator_a_pubkey, thread_a.join().unwrap());
+    cluster.add_node(&validator_b_pubkey, thread_b.join().unwrap());
+
+    // Both nodes created a snapshot at the restart slot, which is past the old root
+    for snapshot_test_config in &snapshot_test_configs {
+        let archive_info = snapshot_utils::get_highest_snapshot_archive_info(
+            snapshot_test_config.snapshot_output_path.path(),
+        )
+        .unwrap();
+        assert!(archive_info.slot >= min_root);
+    }
+
+    // new slots should be rooted after the wen restart
+    cluster.check_for_new_roots(16, "wen restart");
+}
+
 #[test]
 #[serial]
 fn test_no_optimistic_confirmation_violation_with_tower() {
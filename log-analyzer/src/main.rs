@@ -1,14 +1,21 @@
 #![allow(clippy::integer_arithmetic)]
 extern crate byte_unit;
 
+mod validator_log;
+
 use byte_unit::Byte;
-use clap::{crate_description, crate_name, value_t_or_exit, App, Arg, ArgMatches, SubCommand};
+use clap::{
+    crate_description, crate_name, value_t_or_exit, values_t_or_exit, App, Arg, ArgMatches,
+    SubCommand,
+};
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
 use std::ops::Sub;
 use std::path::PathBuf;
+use validator_log::{SlotRecord, TimelineBuilder, CSV_HEADER};
 
 #[derive(Deserialize, Serialize, Debug)]
 struct IpAddrMapping {
@@ -196,6 +203,37 @@ fn analyze_logs(matches: &ArgMatches) {
     });
 }
 
+fn process_validator_logs(matches: &ArgMatches) {
+    let mut builder = TimelineBuilder::default();
+    for log_path in values_t_or_exit!(matches, "file", String) {
+        let log = File::open(&log_path)
+            .unwrap_or_else(|err| panic!("Unable to open {}: {}", log_path, err));
+        for line in BufReader::new(log).split(b'\n') {
+            // Logs may hold the occasional non UTF-8 line, e.g. when truncated.
+            let line = line.expect("Unable to read log file");
+            builder.process_line(&String::from_utf8_lossy(&line));
+        }
+    }
+    let timeline = builder.finish();
+
+    let mut output: Box<dyn Write> = match matches.value_of("output") {
+        Some(path) => Box::new(File::create(path).expect("Unable to create output file")),
+        None => Box::new(io::stdout()),
+    };
+    let records: Vec<SlotRecord> = timeline.iter().map(SlotRecord::from).collect();
+    if matches.value_of("output_format") == Some("csv") {
+        writeln!(output, "{}", CSV_HEADER).unwrap();
+        for record in &records {
+            writeln!(output, "{}", record.to_csv()).unwrap();
+        }
+    } else {
+        writeln!(output, "{}", serde_json::to_string(&records).unwrap()).unwrap();
+    }
+
+    let num_slowest = value_t_or_exit!(matches, "top", usize);
+    eprintln!("{}", validator_log::summary(&timeline, num_slowest));
+}
+
 fn main() {
     solana_logger::setup();
 
@@ -246,11 +284,52 @@ fn main() {
                         .help("List all differences"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("validator")
+                .about("Build a per-slot timeline from validator log files")
+                .arg(
+                    Arg::with_name("file")
+                        .short("f")
+                        .long("file")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required(true)
+                        .help("Validator log file, may be specified multiple times"),
+                )
+                .arg(
+                    Arg::with_name("output_format")
+                        .long("output-format")
+                        .value_name("FORMAT")
+                        .takes_value(true)
+                        .possible_values(&["json", "csv"])
+                        .default_value("json")
+                        .help("Format of the timeline"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .help("Write the timeline to this file instead of stdout"),
+                )
+                .arg(
+                    Arg::with_name("top")
+                        .long("top")
+                        .value_name("N")
+                        .takes_value(true)
+                        .default_value("10")
+                        .help("Number of the slowest slots listed in the summary"),
+                ),
+        )
         .get_matches();
 
     match matches.subcommand() {
         ("iftop", Some(args_matches)) => process_iftop_logs(args_matches),
         ("analyze", Some(args_matches)) => analyze_logs(args_matches),
+        ("validator", Some(args_matches)) => process_validator_logs(args_matches),
         _ => {}
     };
 }
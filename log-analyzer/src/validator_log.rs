@@ -0,0 +1,453 @@
+//! Parsing of validator logs into a per-slot timeline of the replay stage: the
+//! first shred received, the slot full, replayed, voted on and rooted.
+//!
+//! The timeline is assembled from the replay stage log lines, and from the
+//! datapoints which `solana-metrics` logs alongside submitting them, so it is
+//! available even when no InfluxDB was configured.
+
+use chrono::DateTime;
+use serde::Serialize;
+use solana_metrics::datapoint::FieldValue;
+use std::collections::{BTreeMap, HashMap};
+
+pub type Slot = u64;
+
+pub struct LogLine<'a> {
+    /// Unix timestamp, in milliseconds
+    pub timestamp: i64,
+    pub message: &'a str,
+}
+
+/// Parses a line formatted by `solana-logger`, e.g.
+/// `[2021-08-10T12:34:56.123456789Z INFO  solana_core::replay_stage] new root 42`
+pub fn parse_log_line(line: &str) -> Option<LogLine> {
+    let (header, message) = line.strip_prefix('[')?.split_once("] ")?;
+    let timestamp = header.split_whitespace().next()?;
+    Some(LogLine {
+        timestamp: DateTime::parse_from_rfc3339(timestamp)
+            .ok()?
+            .timestamp_millis(),
+        message,
+    })
+}
+
+pub struct Datapoint<'a> {
+    pub name: &'a str,
+    pub fields: HashMap<&'a str, FieldValue>,
+}
+
+impl Datapoint<'_> {
+    pub fn get_i64(&self, name: &str) -> Option<i64> {
+        match self.fields.get(name)? {
+            FieldValue::I64(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a `datapoint: <name> <field>=<value> ...` message, the values being
+/// encoded as in the InfluxDB line protocol.
+pub fn parse_datapoint(message: &str) -> Option<Datapoint> {
+    let message = message.strip_prefix("datapoint: ")?;
+    let (name, mut rest) = message.split_once(' ').unwrap_or((message, ""));
+    let mut fields = HashMap::new();
+    while let Some((field, value)) = rest.split_once('=') {
+        // String values are quoted, and may contain spaces and escaped quotes.
+        let end = if value.starts_with('"') {
+            let mut escaped = false;
+            value
+                .char_indices()
+                .skip(1)
+                .find(|(_, c)| {
+                    let end = *c == '"' && !escaped;
+                    escaped = *c == '\\' && !escaped;
+                    end
+                })
+                .map(|(i, _)| i + 1)?
+        } else {
+            value.find(' ').unwrap_or_else(|| value.len())
+        };
+        fields.insert(field.trim(), FieldValue::parse(&value[..end])?);
+        rest = value[end..].trim_start();
+    }
+    Some(Datapoint { name, fields })
+}
+
+/// The milestones of a slot, as Unix timestamps in milliseconds
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SlotTimeline {
+    pub slot: Slot,
+    pub parent: Option<Slot>,
+    pub leader: bool,
+    pub dead: bool,
+    pub first_shred: Option<i64>,
+    pub full: Option<i64>,
+    /// When the bank was created, and its replay started
+    pub bank_created: Option<i64>,
+    pub replayed: Option<i64>,
+    pub voted: Option<i64>,
+    pub rooted: Option<i64>,
+    /// Time spent replaying the slot, as reported by the replay stage
+    pub replay_elapsed_us: Option<i64>,
+}
+
+fn elapsed(from: Option<i64>, to: Option<i64>) -> Option<i64> {
+    Some(to? - from?)
+}
+
+impl SlotTimeline {
+    /// From the first shred received to the slot full
+    pub fn full_ms(&self) -> Option<i64> {
+        elapsed(self.first_shred, self.full)
+    }
+
+    /// From the slot full to the bank frozen
+    pub fn replay_ms(&self) -> Option<i64> {
+        elapsed(self.full, self.replayed)
+    }
+
+    /// From the bank frozen to the vote
+    pub fn vote_ms(&self) -> Option<i64> {
+        elapsed(self.replayed, self.voted)
+    }
+
+    /// From the vote to the root
+    pub fn root_ms(&self) -> Option<i64> {
+        elapsed(self.voted, self.rooted)
+    }
+
+    /// From the first shred received to the bank frozen
+    pub fn shred_to_replayed_ms(&self) -> Option<i64> {
+        elapsed(self.first_shred, self.replayed)
+    }
+}
+
+/// A row of the timeline output, with the durations between milestones
+#[derive(Serialize)]
+pub struct SlotRecord<'a> {
+    #[serde(flatten)]
+    pub timeline: &'a SlotTimeline,
+    pub full_ms: Option<i64>,
+    pub replay_ms: Option<i64>,
+    pub vote_ms: Option<i64>,
+    pub root_ms: Option<i64>,
+    pub shred_to_replayed_ms: Option<i64>,
+}
+
+impl<'a> From<&'a SlotTimeline> for SlotRecord<'a> {
+    fn from(timeline: &'a SlotTimeline) -> Self {
+        Self {
+            timeline,
+            full_ms: timeline.full_ms(),
+            replay_ms: timeline.replay_ms(),
+            vote_ms: timeline.vote_ms(),
+            root_ms: timeline.root_ms(),
+            shred_to_replayed_ms: timeline.shred_to_replayed_ms(),
+        }
+    }
+}
+
+pub const CSV_HEADER: &str = "slot,parent,leader,dead,first_shred,full,bank_created,replayed,\
+    voted,rooted,replay_elapsed_us,full_ms,replay_ms,vote_ms,root_ms,shred_to_replayed_ms";
+
+impl SlotRecord<'_> {
+    pub fn to_csv(&self) -> String {
+        let timeline = self.timeline;
+        let mut columns = vec![
+            timeline.slot.to_string(),
+            timeline
+                .parent
+                .map(|slot| slot.to_string())
+                .unwrap_or_default(),
+            timeline.leader.to_string(),
+            timeline.dead.to_string(),
+        ];
+        columns.extend(
+            [
+                timeline.first_shred,
+                timeline.full,
+                timeline.bank_created,
+                timeline.replayed,
+                timeline.voted,
+                timeline.rooted,
+                timeline.replay_elapsed_us,
+                self.full_ms,
+                self.replay_ms,
+                self.vote_ms,
+                self.root_ms,
+                self.shred_to_replayed_ms,
+            ]
+            .iter()
+            .map(|value| value.map(|v| v.to_string()).unwrap_or_default()),
+        );
+        columns.join(",")
+    }
+}
+
+// Keeps the earliest occurrence of a milestone, as log files may overlap.
+fn set_earliest(milestone: &mut Option<i64>, timestamp: i64) {
+    if milestone.map_or(true, |earliest| timestamp < earliest) {
+        *milestone = Some(timestamp);
+    }
+}
+
+fn parse_slot(value: &str) -> Option<Slot> {
+    value.trim().parse().ok()
+}
+
+#[derive(Default)]
+pub struct TimelineBuilder {
+    slots: BTreeMap<Slot, SlotTimeline>,
+    roots: Vec<(i64, Slot)>,
+}
+
+impl TimelineBuilder {
+    fn slot(&mut self, slot: Slot) -> &mut SlotTimeline {
+        self.slots.entry(slot).or_insert_with(|| SlotTimeline {
+            slot,
+            ..SlotTimeline::default()
+        })
+    }
+
+    pub fn process_line(&mut self, line: &str) {
+        let line = match parse_log_line(line) {
+            Some(line) => line,
+            None => return,
+        };
+        let timestamp = line.timestamp;
+        let message = line.message;
+        if let Some(datapoint) = parse_datapoint(message) {
+            self.process_datapoint(timestamp, &datapoint);
+        } else if let Some(rest) = message.strip_prefix("new fork:") {
+            // new fork:<slot> parent:<parent> root:<root>
+            let mut parts = rest.split(' ');
+            let slot = parts.next().and_then(parse_slot);
+            let parent = parts
+                .next()
+                .and_then(|part| part.strip_prefix("parent:"))
+                .and_then(parse_slot);
+            if let (Some(slot), Some(parent)) = (slot, parent) {
+                let timeline = self.slot(slot);
+                timeline.parent = Some(parent);
+                set_earliest(&mut timeline.bank_created, timestamp);
+            }
+        } else if let Some(slot) = message.strip_prefix("bank frozen: ").and_then(parse_slot) {
+            set_earliest(&mut self.slot(slot).replayed, timestamp);
+        } else if let Some(rest) = message.strip_prefix("voting: ") {
+            // voting: <slot> <fork weight>
+            if let Some(slot) = rest.split(' ').next().and_then(parse_slot) {
+                set_earliest(&mut self.slot(slot).voted, timestamp);
+            }
+        } else if let Some(slot) = message.strip_prefix("new root ").and_then(parse_slot) {
+            self.roots.push((timestamp, slot));
+        }
+    }
+
+    fn process_datapoint(&mut self, timestamp: i64, datapoint: &Datapoint) {
+        let slot = match datapoint.get_i64("slot") {
+            Some(slot) if slot >= 0 => slot as Slot,
+            _ => return,
+        };
+        match datapoint.name {
+            "shred_insert_is_full" => {
+                let timeline = self.slot(slot);
+                set_earliest(&mut timeline.full, timestamp);
+                if let Some(total_time_ms) = datapoint.get_i64("total_time_ms") {
+                    set_earliest(&mut timeline.first_shred, timestamp - total_time_ms);
+                }
+            }
+            "replay-slot-stats" => {
+                self.slot(slot).replay_elapsed_us = datapoint.get_i64("replay_total_elapsed");
+            }
+            "replay-stage-mark_dead_slot" => self.slot(slot).dead = true,
+            "replay_stage-my_leader_slot" => self.slot(slot).leader = true,
+            _ => (),
+        }
+    }
+
+    /// Returns the timeline of all the slots seen. A new root roots its
+    /// ancestors, as far as the parents were logged.
+    pub fn finish(mut self) -> Vec<SlotTimeline> {
+        self.roots.sort_unstable();
+        for (timestamp, root) in std::mem::take(&mut self.roots) {
+            let mut slot = Some(root);
+            while let Some(timeline) = slot.and_then(|slot| self.slots.get_mut(&slot)) {
+                if timeline.rooted.is_some() {
+                    break;
+                }
+                timeline.rooted = Some(timestamp);
+                slot = timeline.parent;
+            }
+        }
+        self.slots
+            .into_iter()
+            .map(|(_, timeline)| timeline)
+            .collect()
+    }
+}
+
+fn percentile(sorted: &[i64], percent: usize) -> i64 {
+    sorted[(sorted.len() - 1) * percent / 100]
+}
+
+/// Summarizes the timeline: slot counts, the distribution of the duration of
+/// each stage, and the `num_slowest` slots taking the longest to replay from
+/// their first shred.
+pub fn summary(timeline: &[SlotTimeline], num_slowest: usize) -> String {
+    let mut lines = vec![];
+    let last_root = timeline
+        .iter()
+        .filter(|t| t.rooted.is_some())
+        .map(|t| t.slot)
+        .max();
+    let abandoned = timeline
+        .iter()
+        .filter(|t| t.rooted.is_none() && last_root.map_or(false, |root| t.slot < root))
+        .count();
+    lines.push(format!(
+        "{} slots: {} rooted, {} abandoned, {} dead, {} leader",
+        timeline.len(),
+        timeline.iter().filter(|t| t.rooted.is_some()).count(),
+        abandoned,
+        timeline.iter().filter(|t| t.dead).count(),
+        timeline.iter().filter(|t| t.leader).count(),
+    ));
+
+    lines.push(format!(
+        "{:<22} {:>8} {:>8} {:>8} {:>8}",
+        "stage (ms)", "count", "p50", "p90", "max"
+    ));
+    let stages: [(&str, fn(&SlotTimeline) -> Option<i64>); 5] = [
+        ("shred -> full", SlotTimeline::full_ms),
+        ("full -> replayed", SlotTimeline::replay_ms),
+        ("replayed -> voted", SlotTimeline::vote_ms),
+        ("voted -> rooted", SlotTimeline::root_ms),
+        ("shred -> replayed", SlotTimeline::shred_to_replayed_ms),
+    ];
+    for (stage, duration) in stages.iter() {
+        let mut durations: Vec<_> = timeline.iter().filter_map(duration).collect();
+        if durations.is_empty() {
+            continue;
+        }
+        durations.sort_unstable();
+        lines.push(format!(
+            "{:<22} {:>8} {:>8} {:>8} {:>8}",
+            stage,
+            durations.len(),
+            percentile(&durations, 50),
+            percentile(&durations, 90),
+            durations[durations.len() - 1],
+        ));
+    }
+
+    let mut slowest: Vec<_> = timeline
+        .iter()
+        .filter_map(|t| Some((t.shred_to_replayed_ms()?, t)))
+        .collect();
+    slowest.sort_by(|(a, _), (b, _)| b.cmp(a));
+    slowest.truncate(num_slowest);
+    if !slowest.is_empty() {
+        lines.push("slowest slots, from the first shred to replayed:".to_string());
+        for (duration, t) in slowest {
+            let optional = |value: Option<i64>| {
+                value
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "-".to_string())
+            };
+            lines.push(format!(
+                "  slot {}: {}ms (full {}ms, replay {}ms, vote {}ms, root {}ms){}",
+                t.slot,
+                duration,
+                optional(t.full_ms()),
+                optional(t.replay_ms()),
+                optional(t.vote_ms()),
+                optional(t.root_ms()),
+                if t.leader { ", leader" } else { "" },
+            ));
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = r#"[2021-08-10T12:00:00.000000000Z INFO  solana_core::replay_stage] new fork:10 parent:9 root:8
+[2021-08-10T12:00:00.400000000Z INFO  solana_metrics::metrics] datapoint: shred_insert_is_full total_time_ms=300i slot=10i last_index=5i num_repaired=0i num_recovered=0i
+[2021-08-10T12:00:00.500000000Z INFO  solana_core::replay_stage] bank frozen: 10
+[2021-08-10T12:00:00.510000000Z INFO  solana_metrics::metrics] datapoint: replay-slot-stats slot=10i replay_total_elapsed=90000i
+[2021-08-10T12:00:00.600000000Z INFO  solana_core::replay_stage] voting: 10 1234
+[2021-08-10T12:00:00.700000000Z INFO  solana_core::replay_stage] new fork:11 parent:10 root:8
+[2021-08-10T12:00:00.800000000Z INFO  solana_core::replay_stage] new fork:12 parent:10 root:8
+[2021-08-10T12:00:00.900000000Z INFO  solana_metrics::metrics] datapoint: replay_stage-my_leader_slot slot=12i
+[2021-08-10T12:00:01.000000000Z INFO  solana_metrics::metrics] datapoint: replay-stage-mark_dead_slot error="InvalidBlock(\"bad block\")" slot=11i
+continuation of a multiline message
+[2021-08-10T12:00:05.000000000Z INFO  solana_core::replay_stage] new root 12
+"#;
+
+    #[test]
+    fn test_parse_datapoint() {
+        let datapoint =
+            parse_datapoint("datapoint: foo a=1i b=\"x \\\"y\\\" z\" c=true d=0.5").unwrap();
+        assert_eq!(datapoint.name, "foo");
+        assert_eq!(datapoint.get_i64("a"), Some(1));
+        assert_eq!(
+            datapoint.fields["b"],
+            FieldValue::String("x \"y\" z".to_string())
+        );
+        assert_eq!(datapoint.fields["c"], FieldValue::Bool(true));
+        assert_eq!(datapoint.fields["d"], FieldValue::F64(0.5));
+        assert_eq!(parse_datapoint("datapoint: bar").unwrap().fields.len(), 0);
+        assert!(parse_datapoint("new root 12").is_none());
+    }
+
+    #[test]
+    fn test_timeline() {
+        let mut builder = TimelineBuilder::default();
+        LOG.lines().for_each(|line| builder.process_line(line));
+        let timeline = builder.finish();
+        let start = parse_log_line(LOG.lines().next().unwrap())
+            .unwrap()
+            .timestamp;
+        let at = |ms| Some(start + ms);
+
+        assert_eq!(timeline.len(), 3);
+        assert_eq!(
+            timeline[0],
+            SlotTimeline {
+                slot: 10,
+                parent: Some(9),
+                leader: false,
+                dead: false,
+                first_shred: at(100),
+                full: at(400),
+                bank_created: at(0),
+                replayed: at(500),
+                voted: at(600),
+                rooted: at(5000),
+                replay_elapsed_us: Some(90_000),
+            }
+        );
+        let record = SlotRecord::from(&timeline[0]);
+        assert_eq!(record.full_ms, Some(300));
+        assert_eq!(record.replay_ms, Some(100));
+        assert_eq!(record.vote_ms, Some(100));
+        assert_eq!(record.root_ms, Some(4400));
+        assert_eq!(record.shred_to_replayed_ms, Some(400));
+        assert_eq!(
+            record.to_csv().split(',').count(),
+            CSV_HEADER.split(',').count()
+        );
+
+        // Dead, and abandoned by the root.
+        assert!(timeline[1].dead);
+        assert_eq!(timeline[1].rooted, None);
+        assert!(timeline[2].leader);
+        assert_eq!(timeline[2].rooted, at(5000));
+
+        let summary = summary(&timeline, 1);
+        assert!(summary.starts_with("3 slots: 2 rooted, 1 abandoned, 1 dead, 1 leader"));
+        assert!(summary.contains("slot 10: 400ms"));
+    }
+}
@@ -175,6 +175,51 @@ impl MerkleTree {
         }
         Some(path)
     }
+
+    /// Returns the siblings of the nodes on the path from the leaf at `index` up to, but
+    /// excluding, the root; ordered from the leaf level upwards
+    pub fn find_siblings(&self, index: usize) -> Option<Vec<Hash>> {
+        if index >= self.leaf_count {
+            return None;
+        }
+
+        let mut level_len = self.leaf_count;
+        let mut level_start = 0;
+        let mut node_index = index;
+        let mut siblings = Vec::new();
+        while level_len > 1 {
+            let level = &self.nodes[level_start..(level_start + level_len)];
+            let sibling = if node_index % 2 == 0 {
+                // The last entry of an odd length level is its own sibling
+                level.get(node_index + 1).unwrap_or(&level[node_index])
+            } else {
+                &level[node_index - 1]
+            };
+            siblings.push(*sibling);
+            node_index /= 2;
+
+            level_start += level_len;
+            level_len = MerkleTree::next_level_len(level_len);
+        }
+        Some(siblings)
+    }
+
+    /// Computes the root of the tree holding `item` at leaf `index`, given the siblings returned
+    /// by `find_siblings`
+    pub fn compute_root(item: &[u8], index: usize, siblings: &[Hash]) -> Hash {
+        let (root, _) =
+            siblings
+                .iter()
+                .fold((hash_leaf!(item), index), |(node, index), sibling| {
+                    let node = if index % 2 == 0 {
+                        hash_intermediate!(node, sibling)
+                    } else {
+                        hash_intermediate!(sibling, node)
+                    };
+                    (node, index / 2)
+                });
+        root
+    }
 }
 
 #[cfg(test)]
@@ -247,6 +292,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_compute_root_from_siblings() {
+        for len in 1..TEST.len() {
+            let mt = MerkleTree::new(&TEST[..len]);
+            let root = mt.get_root().unwrap();
+            for (i, s) in TEST[..len].iter().enumerate() {
+                let siblings = mt.find_siblings(i).unwrap();
+                assert_eq!(&MerkleTree::compute_root(s, i, &siblings), root);
+                for (j, s) in BAD.iter().enumerate() {
+                    assert_ne!(&MerkleTree::compute_root(s, j, &siblings), root);
+                }
+            }
+            assert_eq!(mt.find_siblings(len), None);
+        }
+    }
+
     #[test]
     fn test_proof_entry_instantiation_lsib_set() {
         ProofEntry::new(&Hash::default(), Some(&Hash::default()), None);
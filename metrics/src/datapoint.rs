@@ -38,6 +38,42 @@ impl DataPoint {
     }
 }
 
+/// The value of a datapoint field, decoded from its InfluxDB line protocol
+/// encoding.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldValue {
+    I64(i64),
+    F64(f64),
+    Bool(bool),
+    String(String),
+}
+
+impl FieldValue {
+    pub fn parse(value: &str) -> Option<Self> {
+        if let Some(value) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            return Some(FieldValue::String(value.replace("\\\"", "\"")));
+        }
+        if let Some(value) = value.strip_suffix('i') {
+            return value.parse().ok().map(FieldValue::I64);
+        }
+        match value {
+            "true" => Some(FieldValue::Bool(true)),
+            "false" => Some(FieldValue::Bool(false)),
+            _ => value.parse().ok().map(FieldValue::F64),
+        }
+    }
+
+    /// Returns the value as a number, or None for strings.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FieldValue::I64(value) => Some(*value as f64),
+            FieldValue::F64(value) => Some(*value),
+            FieldValue::Bool(value) => Some(if *value { 1.0 } else { 0.0 }),
+            FieldValue::String(_) => None,
+        }
+    }
+}
+
 impl fmt::Display for DataPoint {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "datapoint: {}", self.name)?;
@@ -149,6 +185,25 @@ macro_rules! datapoint_trace {
 
 #[cfg(test)]
 mod test {
+    use super::*;
+
+    #[test]
+    fn test_field_value() {
+        assert_eq!(FieldValue::parse("12i"), Some(FieldValue::I64(12)));
+        assert_eq!(FieldValue::parse("-3i"), Some(FieldValue::I64(-3)));
+        assert_eq!(FieldValue::parse("12.5"), Some(FieldValue::F64(12.5)));
+        assert_eq!(FieldValue::parse("true"), Some(FieldValue::Bool(true)));
+        assert_eq!(
+            FieldValue::parse("\"say \\\"hi\\\"\""),
+            Some(FieldValue::String("say \"hi\"".to_string()))
+        );
+        assert_eq!(FieldValue::parse("foo"), None);
+        assert_eq!(FieldValue::parse("1.5i"), None);
+        assert_eq!(FieldValue::Bool(false).as_f64(), Some(0.0));
+        assert_eq!(FieldValue::I64(7).as_f64(), Some(7.0));
+        assert_eq!(FieldValue::String("7".to_string()).as_f64(), None);
+    }
+
     #[test]
     fn test_datapoint() {
         datapoint_debug!("name", ("field name", "test".to_string(), String));
@@ -2,4 +2,9 @@
 pub mod counter;
 pub mod datapoint;
 mod metrics;
-pub use crate::metrics::{flush, query, set_host_id, set_panic_hook, submit};
+pub mod metrics_writer;
+mod prometheus;
+pub use crate::{
+    metrics::{add_metrics_writer, flush, query, set_host_id, set_panic_hook, submit},
+    prometheus::start_prometheus_exporter,
+};
@@ -1,6 +1,11 @@
 //! The `metrics` module enables sending measurements to an `InfluxDB` instance
 
-use crate::{counter::CounterPoint, datapoint::DataPoint};
+use crate::{
+    counter::CounterPoint,
+    datapoint::DataPoint,
+    metrics_writer::{self, FanoutMetricsWriter, MetricsWriter},
+    prometheus::REGISTRY,
+};
 use gethostname::gethostname;
 use lazy_static::lazy_static;
 use log::*;
@@ -39,12 +44,6 @@ struct MetricsAgent {
     sender: Sender<MetricsCommand>,
 }
 
-trait MetricsWriter {
-    // Write the points and empty the vector.  Called on the internal
-    // MetricsAgent worker thread.
-    fn write(&self, points: Vec<DataPoint>);
-}
-
 struct InfluxDbMetricsWriter {
     write_url: Option<String>,
 }
@@ -138,7 +137,7 @@ impl Default for MetricsAgent {
             .unwrap_or(4000);
 
         Self::new(
-            Arc::new(InfluxDbMetricsWriter::new()),
+            METRICS_WRITERS.clone(),
             Duration::from_secs(10),
             max_points_per_sec,
         )
@@ -243,6 +242,9 @@ impl MetricsAgent {
                     }
                     MetricsCommand::Submit(point, level) => {
                         log!(level, "{}", point);
+                        if REGISTRY.is_enabled() {
+                            REGISTRY.observe_point(&point);
+                        }
                         let (_, points) = points_map
                             .entry(level)
                             .or_insert((HashMap::new(), Vec::new()));
@@ -250,6 +252,9 @@ impl MetricsAgent {
                     }
                     MetricsCommand::SubmitCounter(counter, level, bucket) => {
                         debug!("{:?}", counter);
+                        if REGISTRY.is_enabled() {
+                            REGISTRY.observe_counter(&counter);
+                        }
                         let (counters, _) = points_map
                             .entry(level)
                             .or_insert((HashMap::new(), Vec::new()));
@@ -328,7 +333,7 @@ fn get_singleton_agent() -> Arc<Mutex<MetricsAgent>> {
 }
 
 lazy_static! {
-    static ref HOST_ID: Arc<RwLock<String>> = {
+    pub(crate) static ref HOST_ID: Arc<RwLock<String>> = {
         Arc::new(RwLock::new({
             let hostname: String = gethostname()
                 .into_string()
@@ -338,6 +343,22 @@ lazy_static! {
     };
 }
 
+lazy_static! {
+    // The sinks the singleton metrics agent writes to.
+    static ref METRICS_WRITERS: Arc<FanoutMetricsWriter> = {
+        let mut writers: Vec<Arc<dyn MetricsWriter + Send + Sync>> =
+            vec![Arc::new(InfluxDbMetricsWriter::new())];
+        writers.extend(metrics_writer::writers_from_env());
+        Arc::new(FanoutMetricsWriter::new(writers))
+    };
+}
+
+/// Registers an additional sink for the datapoints and counters submitted from
+/// any thread.
+pub fn add_metrics_writer(writer: Arc<dyn MetricsWriter + Send + Sync>) {
+    METRICS_WRITERS.add(writer);
+}
+
 pub fn set_host_id(host_id: String) {
     info!("host id: {}", host_id);
     *HOST_ID.write().unwrap() = host_id;
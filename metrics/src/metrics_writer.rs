@@ -0,0 +1,447 @@
+//! The `metrics_writer` module defines the sinks the metrics agent writes the
+//! datapoints to. Besides InfluxDB, which is configured by
+//! `SOLANA_METRICS_CONFIG`, the following built-in sinks are enabled by their
+//! environment variable:
+//!
+//! * `SOLANA_METRICS_FILE="path=<file>[,max_size=<bytes>][,max_files=<count>]"`
+//!   appends the datapoints as newline-delimited JSON to a local file, which
+//!   is rotated to `<file>.1`, `<file>.2`, ... once it exceeds `max_size`.
+//! * `SOLANA_METRICS_STATSD="host=<host:port>[,prefix=<prefix>]"` sends the
+//!   numeric fields of the datapoints as StatsD gauges over UDP.
+
+use {
+    crate::{
+        datapoint::{DataPoint, FieldValue},
+        metrics::HOST_ID,
+    },
+    log::*,
+    std::{
+        collections::HashMap,
+        env,
+        ffi::OsString,
+        fmt::Write as _,
+        fs::{self, File, OpenOptions},
+        io::{self, Write},
+        net::{SocketAddr, ToSocketAddrs, UdpSocket},
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex, RwLock},
+    },
+};
+
+const DEFAULT_MAX_FILE_SIZE: u64 = 100 * 1024 * 1024;
+const DEFAULT_MAX_FILES: usize = 5;
+const DEFAULT_STATSD_PREFIX: &str = "solana";
+// Keep StatsD datagrams within the MTU of common networks.
+const MAX_STATSD_DATAGRAM_SIZE: usize = 1432;
+
+pub trait MetricsWriter {
+    // Write the points and empty the vector.  Called on the internal
+    // MetricsAgent worker thread.
+    fn write(&self, points: Vec<DataPoint>);
+}
+
+/// Writes the datapoints to each of the registered writers.
+#[derive(Default)]
+pub struct FanoutMetricsWriter {
+    writers: RwLock<Vec<Arc<dyn MetricsWriter + Send + Sync>>>,
+}
+
+impl FanoutMetricsWriter {
+    pub fn new(writers: Vec<Arc<dyn MetricsWriter + Send + Sync>>) -> Self {
+        Self {
+            writers: RwLock::new(writers),
+        }
+    }
+
+    pub fn add(&self, writer: Arc<dyn MetricsWriter + Send + Sync>) {
+        self.writers.write().unwrap().push(writer);
+    }
+}
+
+impl MetricsWriter for FanoutMetricsWriter {
+    fn write(&self, points: Vec<DataPoint>) {
+        let writers = self.writers.read().unwrap();
+        if let Some((last, writers)) = writers.split_last() {
+            for writer in writers {
+                writer.write(points.clone());
+            }
+            last.write(points);
+        }
+    }
+}
+
+/// Appends the datapoints as newline-delimited JSON to a local file, rotating
+/// it once it exceeds the maximum size.
+pub struct JsonFileMetricsWriter {
+    path: PathBuf,
+    max_size: u64,
+    // Number of rotated files to keep.
+    max_files: usize,
+    // The open file and its size.
+    file: Mutex<Option<(File, u64)>>,
+}
+
+impl JsonFileMetricsWriter {
+    pub fn new<P: Into<PathBuf>>(path: P, max_size: u64, max_files: usize) -> Self {
+        Self {
+            path: path.into(),
+            max_size,
+            max_files,
+            file: Mutex::default(),
+        }
+    }
+
+    fn from_config(config: &str) -> Result<Self, String> {
+        let config = parse_config(config, &["path", "max_size", "max_files"])?;
+        let path = config.get("path").ok_or("path is missing")?;
+        let max_size = match config.get("max_size") {
+            None => DEFAULT_MAX_FILE_SIZE,
+            Some(max_size) => max_size
+                .parse()
+                .map_err(|err| format!("invalid max_size: {}", err))?,
+        };
+        let max_files = match config.get("max_files") {
+            None => DEFAULT_MAX_FILES,
+            Some(max_files) => max_files
+                .parse()
+                .map_err(|err| format!("invalid max_files: {}", err))?,
+        };
+        Ok(Self::new(*path, max_size, max_files))
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = OsString::from(self.path.as_os_str());
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path);
+        }
+        for index in (1..self.max_files).rev() {
+            let path = self.rotated_path(index);
+            if path.exists() {
+                fs::rename(path, self.rotated_path(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))
+    }
+
+    fn append(&self, file: &mut Option<(File, u64)>, buf: &[u8]) -> io::Result<()> {
+        let rotate = matches!(file, Some((_, size))
+            if *size > 0 && size.saturating_add(buf.len() as u64) > self.max_size);
+        if rotate {
+            *file = None;
+            self.rotate()?;
+        }
+        if file.is_none() {
+            *file = Some(open_append(&self.path)?);
+        }
+        let (file, size) = file.as_mut().unwrap();
+        file.write_all(buf)?;
+        *size += buf.len() as u64;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<(File, u64)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok((file, size))
+}
+
+impl MetricsWriter for JsonFileMetricsWriter {
+    fn write(&self, points: Vec<DataPoint>) {
+        let host_id = HOST_ID.read().unwrap().clone();
+        let mut lines = String::new();
+        for point in &points {
+            write_json_point(&mut lines, point, &host_id);
+            lines.push('\n');
+        }
+        let mut file = self.file.lock().unwrap();
+        if let Err(err) = self.append(&mut file, lines.as_bytes()) {
+            warn!("failed to write metrics to {:?}: {}", self.path, err);
+            *file = None;
+        }
+    }
+}
+
+fn write_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_json_point(out: &mut String, point: &DataPoint, host_id: &str) {
+    out.push_str("{\"name\":");
+    write_json_string(out, point.name);
+    out.push_str(",\"host_id\":");
+    write_json_string(out, host_id);
+    let _ = write!(out, ",\"timestamp\":{},\"fields\":{{", point.timestamp);
+    for (i, (name, value)) in point.fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_json_string(out, name);
+        out.push(':');
+        match FieldValue::parse(value) {
+            Some(FieldValue::I64(value)) => {
+                let _ = write!(out, "{}", value);
+            }
+            Some(FieldValue::F64(value)) if value.is_finite() => {
+                let _ = write!(out, "{}", value);
+            }
+            Some(FieldValue::Bool(value)) => {
+                let _ = write!(out, "{}", value);
+            }
+            Some(FieldValue::String(value)) => write_json_string(out, &value),
+            Some(FieldValue::F64(_)) | None => out.push_str("null"),
+        }
+    }
+    out.push_str("}}");
+}
+
+/// Sends the numeric fields of the datapoints as StatsD gauges, named
+/// `<prefix>.<datapoint>.<field>`.
+pub struct StatsdMetricsWriter {
+    socket: UdpSocket,
+    addr: SocketAddr,
+    prefix: String,
+}
+
+impl StatsdMetricsWriter {
+    pub fn new(addr: SocketAddr, prefix: &str) -> io::Result<Self> {
+        let bind_addr = if addr.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        Ok(Self {
+            socket: UdpSocket::bind(bind_addr)?,
+            addr,
+            prefix: prefix.to_string(),
+        })
+    }
+
+    fn from_config(config: &str) -> Result<Self, String> {
+        let config = parse_config(config, &["host", "prefix"])?;
+        let host = config.get("host").ok_or("host is missing")?;
+        let addr = host
+            .to_socket_addrs()
+            .map_err(|err| format!("invalid host: {}", err))?
+            .next()
+            .ok_or_else(|| format!("invalid host: {}", host))?;
+        let prefix = config.get("prefix").unwrap_or(&DEFAULT_STATSD_PREFIX);
+        Self::new(addr, prefix).map_err(|err| err.to_string())
+    }
+
+    fn metric_name(&self, point: &str, field: &str) -> String {
+        let name = if self.prefix.is_empty() {
+            format!("{}.{}", point, field)
+        } else {
+            format!("{}.{}.{}", self.prefix, point, field)
+        };
+        name.chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' | '.' => c,
+                _ => '_',
+            })
+            .collect()
+    }
+
+    fn send(&self, datagram: &str) {
+        if let Err(err) = self.socket.send_to(datagram.as_bytes(), self.addr) {
+            warn!("failed to send metrics to {}: {}", self.addr, err);
+        }
+    }
+}
+
+impl MetricsWriter for StatsdMetricsWriter {
+    fn write(&self, points: Vec<DataPoint>) {
+        let mut datagram = String::new();
+        for point in &points {
+            for (field, value) in &point.fields {
+                let value = match FieldValue::parse(value).and_then(|value| value.as_f64()) {
+                    Some(value) if value.is_finite() => value,
+                    _ => continue,
+                };
+                let name = self.metric_name(point.name, field);
+                // A signed gauge value is applied as a delta, so negative
+                // values are sent as a reset to zero followed by the delta.
+                let line = if value < 0.0 {
+                    format!("{}:0|g\n{}:{}|g", name, name, value)
+                } else {
+                    format!("{}:{}|g", name, value)
+                };
+                if !datagram.is_empty()
+                    && datagram.len() + 1 + line.len() > MAX_STATSD_DATAGRAM_SIZE
+                {
+                    self.send(&datagram);
+                    datagram.clear();
+                }
+                if !datagram.is_empty() {
+                    datagram.push('\n');
+                }
+                datagram.push_str(&line);
+            }
+        }
+        if !datagram.is_empty() {
+            self.send(&datagram);
+        }
+    }
+}
+
+// Parses a comma separated list of key=value pairs.
+fn parse_config<'a>(config: &'a str, keys: &[&str]) -> Result<HashMap<&'a str, &'a str>, String> {
+    config
+        .split(',')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) if keys.contains(&key) => Ok((key, value)),
+            _ => Err(format!("invalid entry: '{}'", pair)),
+        })
+        .collect()
+}
+
+/// Returns the built-in writers enabled by their environment variables.
+pub(crate) fn writers_from_env() -> Vec<Arc<dyn MetricsWriter + Send + Sync>> {
+    let mut writers: Vec<Arc<dyn MetricsWriter + Send + Sync>> = Vec::new();
+    if let Ok(config) = env::var("SOLANA_METRICS_FILE") {
+        match JsonFileMetricsWriter::from_config(&config) {
+            Ok(writer) => {
+                info!("writing metrics to {:?}", writer.path);
+                writers.push(Arc::new(writer));
+            }
+            Err(err) => warn!("SOLANA_METRICS_FILE is invalid: {}", err),
+        }
+    }
+    if let Ok(config) = env::var("SOLANA_METRICS_STATSD") {
+        match StatsdMetricsWriter::from_config(&config) {
+            Ok(writer) => {
+                info!("sending metrics to statsd at {}", writer.addr);
+                writers.push(Arc::new(writer));
+            }
+            Err(err) => warn!("SOLANA_METRICS_STATSD is invalid: {}", err),
+        }
+    }
+    writers
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, rand::Rng, std::time::Duration};
+
+    fn new_point(i: i64) -> DataPoint {
+        DataPoint::new("point")
+            .add_field_i64("i", i)
+            .add_field_f64("f", -1.5)
+            .add_field_bool("b", true)
+            .add_field_str("s", "a \"quoted\"\n")
+            .to_owned()
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let keys = ["path", "max_size"];
+        let config = parse_config("path=/tmp/foo,max_size=10", &keys).unwrap();
+        assert_eq!(config["path"], "/tmp/foo");
+        assert_eq!(config["max_size"], "10");
+        assert!(parse_config("path=/tmp/foo,bar=10", &keys).is_err());
+        assert!(parse_config("path", &keys).is_err());
+        assert!(JsonFileMetricsWriter::from_config("max_size=10").is_err());
+        assert!(JsonFileMetricsWriter::from_config("path=/tmp/foo,max_size=x").is_err());
+    }
+
+    #[test]
+    fn test_write_json_point() {
+        let mut point = new_point(42);
+        point.timestamp = 123;
+        let mut out = String::new();
+        write_json_point(&mut out, &point, "host");
+        assert_eq!(
+            out,
+            "{\"name\":\"point\",\"host_id\":\"host\",\"timestamp\":123,\
+             \"fields\":{\"i\":42,\"f\":-1.5,\"b\":true,\"s\":\"a \\\"quoted\\\"\\n\"}}"
+        );
+    }
+
+    #[test]
+    fn test_json_file_metrics_writer() {
+        let dir = env::temp_dir().join(format!(
+            "solana-metrics-writer-{}",
+            rand::thread_rng().gen::<u64>()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("metrics.json");
+        let mut line = String::new();
+        write_json_point(&mut line, &new_point(0), &HOST_ID.read().unwrap());
+        // Room for two points per file.
+        let writer = JsonFileMetricsWriter::new(&path, 2 * (line.len() as u64 + 1), 2);
+        for i in 0..7 {
+            writer.write(vec![new_point(i)]);
+        }
+        let read_points = |path: &Path| fs::read_to_string(path).unwrap().lines().count();
+        assert_eq!(read_points(&path), 1);
+        assert_eq!(read_points(&writer.rotated_path(1)), 2);
+        assert_eq!(read_points(&writer.rotated_path(2)), 2);
+        assert!(!writer.rotated_path(3).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_statsd_metrics_writer() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let writer = StatsdMetricsWriter::new(socket.local_addr().unwrap(), "test").unwrap();
+        writer.write(vec![new_point(42)]);
+        let mut buf = [0u8; MAX_STATSD_DATAGRAM_SIZE];
+        let size = socket.recv(&mut buf).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf[..size]).unwrap(),
+            "test.point.i:42|g\ntest.point.f:0|g\ntest.point.f:-1.5|g\ntest.point.b:1|g"
+        );
+
+        // Datagrams are split at the size limit.
+        writer.write((0..100).map(new_point).collect());
+        let mut num_lines = 0;
+        while num_lines < 400 {
+            let size = socket.recv(&mut buf).unwrap();
+            assert!(size <= MAX_STATSD_DATAGRAM_SIZE);
+            num_lines += std::str::from_utf8(&buf[..size]).unwrap().lines().count();
+        }
+        assert_eq!(num_lines, 400);
+    }
+
+    #[test]
+    fn test_fanout_metrics_writer() {
+        struct CountingWriter(Mutex<usize>);
+        impl MetricsWriter for CountingWriter {
+            fn write(&self, points: Vec<DataPoint>) {
+                *self.0.lock().unwrap() += points.len();
+            }
+        }
+        let first = Arc::new(CountingWriter(Mutex::default()));
+        let second = Arc::new(CountingWriter(Mutex::default()));
+        let fanout = FanoutMetricsWriter::default();
+        fanout.add(first.clone());
+        fanout.write(vec![new_point(0)]);
+        fanout.add(second.clone());
+        fanout.write(vec![new_point(1), new_point(2)]);
+        assert_eq!(*first.0.lock().unwrap(), 3);
+        assert_eq!(*second.0.lock().unwrap(), 2);
+    }
+}
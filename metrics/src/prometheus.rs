@@ -0,0 +1,397 @@
+//! The `prometheus` module exposes the submitted datapoints and counters over
+//! HTTP, in the Prometheus text exposition format.
+//!
+//! Each numeric field of a datapoint becomes a metric named
+//! `solana_<datapoint>_<field>`, labeled by the host id and the string fields
+//! of the datapoint. Timing fields (`*_us`, `*_ms`, `*_ns`) are exported as
+//! histograms and the other fields as gauges. Counters are exported as
+//! `solana_<counter>_total`.
+
+use {
+    crate::{
+        counter::CounterPoint,
+        datapoint::{DataPoint, FieldValue},
+        metrics::HOST_ID,
+    },
+    lazy_static::lazy_static,
+    log::*,
+    std::{
+        collections::BTreeMap,
+        fmt::Write as _,
+        io::{self, BufRead, BufReader, Write},
+        net::{SocketAddr, TcpListener, TcpStream},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread::Builder,
+        time::Duration,
+    },
+};
+
+const NUM_HISTOGRAM_BUCKETS: usize = 13;
+// Upper bounds of the histogram buckets, in the unit of the timing field.
+const HISTOGRAM_BUCKETS: [f64; NUM_HISTOGRAM_BUCKETS] = [
+    1.0, 4.0, 16.0, 64.0, 256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0,
+    16777216.0,
+];
+// Guards against unbounded memory use from high cardinality string fields.
+const MAX_SERIES_PER_METRIC: usize = 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    pub(crate) static ref REGISTRY: Arc<Registry> = Arc::default();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MetricType {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl MetricType {
+    fn name(self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Histogram => "histogram",
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Value {
+    Counter(i64),
+    Gauge(f64),
+    Histogram {
+        // Observations in each bucket, not cumulative.
+        buckets: [u64; NUM_HISTOGRAM_BUCKETS],
+        sum: f64,
+        count: u64,
+    },
+}
+
+impl Value {
+    fn new(metric_type: MetricType) -> Self {
+        match metric_type {
+            MetricType::Counter => Value::Counter(0),
+            MetricType::Gauge => Value::Gauge(0.0),
+            MetricType::Histogram => Value::Histogram {
+                buckets: [0; NUM_HISTOGRAM_BUCKETS],
+                sum: 0.0,
+                count: 0,
+            },
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        match self {
+            Value::Counter(total) => *total = total.saturating_add(value as i64),
+            Value::Gauge(gauge) => *gauge = value,
+            Value::Histogram {
+                buckets,
+                sum,
+                count,
+            } => {
+                if let Some(bucket) = HISTOGRAM_BUCKETS.iter().position(|le| value <= *le) {
+                    buckets[bucket] += 1;
+                }
+                *sum += value;
+                *count += 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Metric {
+    metric_type: MetricType,
+    // Series by their rendered labels, excluding the host id.
+    series: BTreeMap<String, Value>,
+}
+
+#[derive(Default)]
+pub(crate) struct Registry {
+    enabled: AtomicBool,
+    metrics: Mutex<BTreeMap<String, Metric>>,
+}
+
+impl Registry {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn observe_point(&self, point: &DataPoint) {
+        let mut labels = Vec::new();
+        let mut values = Vec::new();
+        for (name, value) in &point.fields {
+            match FieldValue::parse(value) {
+                Some(FieldValue::String(value)) => labels.push((sanitize_name(name), value)),
+                Some(value) => values.extend(value.as_f64().map(|value| (name, value))),
+                None => (),
+            }
+        }
+        let labels = render_labels(&labels);
+        let mut metrics = self.metrics.lock().unwrap();
+        for (name, value) in values {
+            let metric_type = if is_timing_field(name) {
+                MetricType::Histogram
+            } else {
+                MetricType::Gauge
+            };
+            let name = sanitize_name(&format!("solana_{}_{}", point.name, name));
+            observe(&mut metrics, name, metric_type, &labels, value);
+        }
+    }
+
+    pub(crate) fn observe_counter(&self, counter: &CounterPoint) {
+        let name = sanitize_name(&format!("solana_{}_total", counter.name));
+        let mut metrics = self.metrics.lock().unwrap();
+        observe(
+            &mut metrics,
+            name,
+            MetricType::Counter,
+            "",
+            counter.count as f64,
+        );
+    }
+
+    fn render(&self, host_id: &str) -> String {
+        let host_id = format!("host_id=\"{}\"", escape_label_value(host_id));
+        let mut out = String::new();
+        let metrics = self.metrics.lock().unwrap();
+        for (name, metric) in metrics.iter() {
+            let _ = writeln!(out, "# TYPE {} {}", name, metric.metric_type.name());
+            for (labels, value) in &metric.series {
+                let labels = if labels.is_empty() {
+                    host_id.clone()
+                } else {
+                    format!("{},{}", host_id, labels)
+                };
+                match value {
+                    Value::Counter(total) => {
+                        let _ = writeln!(out, "{}{{{}}} {}", name, labels, total);
+                    }
+                    Value::Gauge(gauge) => {
+                        let _ = writeln!(out, "{}{{{}}} {}", name, labels, gauge);
+                    }
+                    Value::Histogram {
+                        buckets,
+                        sum,
+                        count,
+                    } => {
+                        let mut cumulative = 0;
+                        for (le, observations) in HISTOGRAM_BUCKETS.iter().zip(buckets.iter()) {
+                            cumulative += observations;
+                            let _ = writeln!(
+                                out,
+                                "{}_bucket{{{},le=\"{}\"}} {}",
+                                name, labels, le, cumulative
+                            );
+                        }
+                        let _ =
+                            writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, count);
+                        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, sum);
+                        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, count);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn observe(
+    metrics: &mut BTreeMap<String, Metric>,
+    name: String,
+    metric_type: MetricType,
+    labels: &str,
+    value: f64,
+) {
+    let metric = metrics.entry(name).or_insert_with(|| Metric {
+        metric_type,
+        series: BTreeMap::new(),
+    });
+    if metric.metric_type != metric_type {
+        return;
+    }
+    if let Some(series) = metric.series.get_mut(labels) {
+        series.observe(value);
+    } else if metric.series.len() < MAX_SERIES_PER_METRIC {
+        let mut series = Value::new(metric_type);
+        series.observe(value);
+        metric.series.insert(labels.to_string(), series);
+    }
+}
+
+fn is_timing_field(name: &str) -> bool {
+    name.ends_with("_us") || name.ends_with("_ms") || name.ends_with("_ns")
+}
+
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn render_labels(labels: &[(String, String)]) -> String {
+    // Sorted, and deduplicated by name.
+    let labels: BTreeMap<_, _> = labels.iter().cloned().collect();
+    labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn handle_request(stream: TcpStream, registry: &Registry) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the request headers.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+    let (status, body) = if request_line.starts_with("GET ") && path == "/metrics" {
+        let host_id = HOST_ID.read().unwrap().clone();
+        ("200 OK", registry.render(&host_id))
+    } else {
+        ("404 Not Found", String::new())
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn serve(listener: TcpListener, registry: Arc<Registry>) -> io::Result<SocketAddr> {
+    let local_addr = listener.local_addr()?;
+    registry.enabled.store(true, Ordering::Relaxed);
+    Builder::new()
+        .name("solana-metrics-prometheus".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|stream| handle_request(stream, &registry));
+                if let Err(err) = result {
+                    debug!("prometheus request failed: {}", err);
+                }
+            }
+        })?;
+    Ok(local_addr)
+}
+
+/// Starts serving the datapoints and counters submitted from now on at
+/// `http://<addr>/metrics`, alongside the InfluxDB writer if configured.
+/// Returns the address the endpoint is bound to.
+pub fn start_prometheus_exporter(addr: SocketAddr) -> io::Result<SocketAddr> {
+    let local_addr = serve(TcpListener::bind(addr)?, REGISTRY.clone())?;
+    info!(
+        "serving prometheus metrics at http://{}/metrics",
+        local_addr
+    );
+    Ok(local_addr)
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, std::io::Read};
+
+    #[test]
+    fn test_render() {
+        let registry = Registry::default();
+        for elapsed in &[3, 20] {
+            registry.observe_point(
+                DataPoint::new("bank-stats")
+                    .add_field_str("kind", "Vote")
+                    .add_field_i64("num_txs", 7)
+                    .add_field_i64("elapsed_us", *elapsed)
+                    .add_field_bool("ok", true),
+            );
+        }
+        let mut counter = CounterPoint::new("packets-recv");
+        counter.count = 5;
+        registry.observe_counter(&counter);
+        registry.observe_counter(&counter);
+
+        let out = registry.render("host");
+        assert!(out.contains("# TYPE solana_packets_recv_total counter\n"));
+        assert!(out.contains("solana_packets_recv_total{host_id=\"host\"} 10\n"));
+        assert!(out.contains("# TYPE solana_bank_stats_num_txs gauge\n"));
+        assert!(out.contains("solana_bank_stats_num_txs{host_id=\"host\",kind=\"Vote\"} 7\n"));
+        assert!(out.contains("solana_bank_stats_ok{host_id=\"host\",kind=\"Vote\"} 1\n"));
+        assert!(out.contains("# TYPE solana_bank_stats_elapsed_us histogram\n"));
+        assert!(out.contains(
+            "solana_bank_stats_elapsed_us_bucket{host_id=\"host\",kind=\"Vote\",le=\"4\"} 1\n"
+        ));
+        assert!(out.contains(
+            "solana_bank_stats_elapsed_us_bucket{host_id=\"host\",kind=\"Vote\",le=\"64\"} 2\n"
+        ));
+        assert!(out.contains(
+            "solana_bank_stats_elapsed_us_bucket{host_id=\"host\",kind=\"Vote\",le=\"+Inf\"} 2\n"
+        ));
+        assert!(
+            out.contains("solana_bank_stats_elapsed_us_sum{host_id=\"host\",kind=\"Vote\"} 23\n")
+        );
+        assert!(
+            out.contains("solana_bank_stats_elapsed_us_count{host_id=\"host\",kind=\"Vote\"} 2\n")
+        );
+    }
+
+    #[test]
+    fn test_max_series() {
+        let registry = Registry::default();
+        for i in 0..MAX_SERIES_PER_METRIC + 10 {
+            registry.observe_point(
+                DataPoint::new("point")
+                    .add_field_str("id", &i.to_string())
+                    .add_field_i64("value", 1),
+            );
+        }
+        let metrics = registry.metrics.lock().unwrap();
+        assert_eq!(
+            metrics["solana_point_value"].series.len(),
+            MAX_SERIES_PER_METRIC
+        );
+    }
+
+    #[test]
+    fn test_serve() {
+        let registry = Arc::new(Registry::default());
+        registry.observe_point(DataPoint::new("point").add_field_i64("value", 42));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = serve(listener, registry.clone()).unwrap();
+        assert!(registry.is_enabled());
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let response = get("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("solana_point_value{host_id="));
+        assert!(get("/foo").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
@@ -0,0 +1,3 @@
+#![allow(clippy::integer_arithmetic)]
+pub mod scenario;
+pub mod topology;
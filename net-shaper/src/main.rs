@@ -4,158 +4,9 @@ use clap::{
     SubCommand,
 };
 
-use rand::{thread_rng, Rng};
-use serde::{Deserialize, Serialize};
+use solana_net_shaper::{scenario::Scenario, topology::NetworkTopology};
 use std::path::PathBuf;
-use std::{fs, io};
-
-#[derive(Deserialize, Serialize, Debug)]
-struct NetworkInterconnect {
-    pub a: u8,
-    pub b: u8,
-    pub config: String,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct NetworkTopology {
-    pub partitions: Vec<u8>,
-    pub interconnects: Vec<NetworkInterconnect>,
-}
-
-impl Default for NetworkTopology {
-    fn default() -> Self {
-        Self {
-            partitions: vec![100],
-            interconnects: vec![],
-        }
-    }
-}
-
-impl NetworkTopology {
-    pub fn verify(&self) -> bool {
-        let sum: u8 = self.partitions.iter().sum();
-        if sum != 100 {
-            return false;
-        }
-
-        for x in self.interconnects.iter() {
-            if x.a as usize > self.partitions.len() || x.b as usize > self.partitions.len() {
-                return false;
-            }
-        }
-
-        true
-    }
-
-    pub fn new_from_stdin() -> Self {
-        let mut input = String::new();
-        println!("Configure partition map (must add up to 100, e.g. [70, 20, 10]):");
-        let partitions_str = match io::stdin().read_line(&mut input) {
-            Ok(_) => input,
-            Err(error) => panic!("error: {}", error),
-        };
-
-        let partitions: Vec<u8> = serde_json::from_str(&partitions_str)
-            .expect("Failed to parse input. It must be a JSON string");
-
-        let mut interconnects: Vec<NetworkInterconnect> = vec![];
-
-        for i in 0..partitions.len() - 1 {
-            for j in i + 1..partitions.len() {
-                println!("Configure interconnect ({} <-> {}):", i, j);
-                let mut input = String::new();
-                let mut interconnect_config = match io::stdin().read_line(&mut input) {
-                    Ok(_) => input,
-                    Err(error) => panic!("error: {}", error),
-                };
-
-                if interconnect_config.ends_with('\n') {
-                    interconnect_config.pop();
-                    if interconnect_config.ends_with('\r') {
-                        interconnect_config.pop();
-                    }
-                }
-
-                if !interconnect_config.is_empty() {
-                    let interconnect = NetworkInterconnect {
-                        a: i as u8,
-                        b: j as u8,
-                        config: interconnect_config.clone(),
-                    };
-                    interconnects.push(interconnect);
-                    let interconnect = NetworkInterconnect {
-                        a: j as u8,
-                        b: i as u8,
-                        config: interconnect_config,
-                    };
-                    interconnects.push(interconnect);
-                }
-            }
-        }
-
-        Self {
-            partitions,
-            interconnects,
-        }
-    }
-
-    fn new_random(max_partitions: usize, max_packet_drop: u8, max_packet_delay: u32) -> Self {
-        let mut rng = thread_rng();
-        let num_partitions = rng.gen_range(0, max_partitions + 1);
-
-        if num_partitions == 0 {
-            return NetworkTopology::default();
-        }
-
-        let mut partitions = vec![];
-        let mut used_partition = 0;
-        for i in 0..num_partitions {
-            let partition = if i == num_partitions - 1 {
-                100 - used_partition
-            } else {
-                rng.gen_range(0, 100 - used_partition - num_partitions + i)
-            };
-            used_partition += partition;
-            partitions.push(partition as u8);
-        }
-
-        let mut interconnects: Vec<NetworkInterconnect> = vec![];
-        for i in 0..partitions.len() - 1 {
-            for j in i + 1..partitions.len() {
-                let drop_config = if max_packet_drop > 0 {
-                    let packet_drop = rng.gen_range(0, max_packet_drop + 1);
-                    format!("loss {}% 25% ", packet_drop)
-                } else {
-                    String::default()
-                };
-
-                let config = if max_packet_delay > 0 {
-                    let packet_delay = rng.gen_range(0, max_packet_delay + 1);
-                    format!("{}delay {}ms 10ms", drop_config, packet_delay)
-                } else {
-                    drop_config
-                };
-
-                let interconnect = NetworkInterconnect {
-                    a: i as u8,
-                    b: j as u8,
-                    config: config.clone(),
-                };
-                interconnects.push(interconnect);
-                let interconnect = NetworkInterconnect {
-                    a: j as u8,
-                    b: i as u8,
-                    config,
-                };
-                interconnects.push(interconnect);
-            }
-        }
-        Self {
-            partitions,
-            interconnects,
-        }
-    }
-}
+use std::{fs, thread::sleep, time::Instant};
 
 fn run(
     cmd: &str,
@@ -346,20 +197,6 @@ fn delete_all_filters(interface: &str) {
     );
 }
 
-fn identify_my_partition(partitions: &[u8], index: u64, size: u64) -> usize {
-    let mut my_partition = 0;
-    let mut watermark = 0;
-    for (i, p) in partitions.iter().enumerate() {
-        watermark += *p;
-        if u64::from(watermark) >= index * 100 / size {
-            my_partition = i;
-            break;
-        }
-    }
-
-    my_partition
-}
-
 fn partition_id_to_tos(partition: usize) -> u8 {
     if partition < 4 {
         2u8.pow(partition as u32 + 1)
@@ -382,6 +219,38 @@ fn shape_network(matches: &ArgMatches) {
     }
 }
 
+fn run_scenario(matches: &ArgMatches) {
+    let scenario_path = PathBuf::from(value_t_or_exit!(matches, "file", String));
+    let scenario = fs::read_to_string(&scenario_path).expect("Unable to read scenario file");
+    let scenario: Scenario =
+        serde_json::from_str(&scenario).expect("Failed to parse scenario as JSON");
+    if let Err(err) = scenario.verify() {
+        panic!("Failed to verify the scenario file: {}", err);
+    }
+    let interface = value_t_or_exit!(matches, "iface", String);
+    let network_size = value_t_or_exit!(matches, "size", u64);
+    let my_index = value_t_or_exit!(matches, "position", u64);
+
+    let start = Instant::now();
+    for (at, topology) in scenario.timeline() {
+        sleep(at.checked_sub(start.elapsed()).unwrap_or_default());
+        println!(
+            "{:?}: switching to partitions {:?}, interconnects {:?}",
+            at, topology.partitions, topology.interconnects
+        );
+        if !shape_network_steps(&topology, &interface, network_size, my_index) {
+            delete_ifb(interface.as_str());
+            flush_iptables_rule();
+            eprintln!("Failed to apply the topology at {:?}", at);
+            return;
+        }
+    }
+    println!(
+        "Scenario complete after {:?}, the last topology remains applied",
+        start.elapsed()
+    );
+}
+
 fn shape_network_steps(
     topology: &NetworkTopology,
     interface: &str,
@@ -395,7 +264,7 @@ fn shape_network_steps(
     assert!(my_index < network_size);
 
     // Figure out partition we belong in
-    let my_partition = identify_my_partition(&topology.partitions, my_index + 1, network_size);
+    let my_partition = topology.partition_of(my_index, network_size);
 
     // Clear any lingering state
     println!(
@@ -534,6 +403,46 @@ fn main() {
                         .help("Position of current node in the network"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Shape the network following the timeline of a scenario file")
+                .arg(
+                    Arg::with_name("file")
+                        .short("f")
+                        .long("file")
+                        .value_name("scenario file")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Location of the scenario file"),
+                )
+                .arg(
+                    Arg::with_name("size")
+                        .short("s")
+                        .long("size")
+                        .value_name("network size")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Number of nodes in the network"),
+                )
+                .arg(
+                    Arg::with_name("iface")
+                        .short("i")
+                        .long("iface")
+                        .value_name("network interface name")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Name of network interface"),
+                )
+                .arg(
+                    Arg::with_name("position")
+                        .short("p")
+                        .long("position")
+                        .value_name("position of node")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Position of current node in the network"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("cleanup")
                 .about("Remove the network filters using config file")
@@ -616,6 +525,7 @@ fn main() {
 
     match matches.subcommand() {
         ("shape", Some(args_matches)) => shape_network(args_matches),
+        ("run", Some(args_matches)) => run_scenario(args_matches),
         ("cleanup", Some(args_matches)) => {
             let interfaces = value_t_or_exit!(args_matches, "iface", String);
             let iface = parse_interface(&interfaces);
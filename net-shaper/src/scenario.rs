@@ -0,0 +1,248 @@
+//! The `scenario` module describes a timeline of network topologies, e.g.
+//! partitioning the network 30 seconds in, healing it at 90 seconds and then
+//! ramping up the latency over a minute:
+//!
+//! ```json
+//! {
+//!   "steps": [
+//!     {
+//!       "at_secs": 30,
+//!       "topology": {
+//!         "partitions": [50, 50],
+//!         "interconnects": [
+//!           { "a": 0, "b": 1, "config": "loss 100%" },
+//!           { "a": 1, "b": 0, "config": "loss 100%" }
+//!         ]
+//!       }
+//!     },
+//!     { "at_secs": 90, "topology": { "partitions": [50, 50], "interconnects": [] } },
+//!     {
+//!       "at_secs": 120,
+//!       "ramp_secs": 60,
+//!       "topology": {
+//!         "partitions": [50, 50],
+//!         "interconnects": [
+//!           { "a": 0, "b": 1, "config": "delay 200ms 20ms" },
+//!           { "a": 1, "b": 0, "config": "delay 200ms 20ms" }
+//!         ]
+//!       }
+//!     }
+//!   ]
+//! }
+//! ```
+
+use crate::topology::{NetemConfig, NetworkInterconnect, NetworkTopology};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+/// Interval between the intermediate topologies of a ramp
+pub const RAMP_INTERVAL_SECS: u64 = 5;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ScenarioStep {
+    /// Time at which the step is applied, from the start of the scenario
+    pub at_secs: u64,
+    /// When non-zero, the interconnects are ramped linearly from the previous
+    /// step over this many seconds, starting at `at_secs`. The partitions of
+    /// both steps must match.
+    #[serde(default)]
+    pub ramp_secs: u64,
+    pub topology: NetworkTopology,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct Scenario {
+    pub steps: Vec<ScenarioStep>,
+}
+
+fn parse_interconnects(
+    topology: &NetworkTopology,
+) -> Result<BTreeMap<(u8, u8), NetemConfig>, String> {
+    topology
+        .interconnects
+        .iter()
+        .map(|interconnect| {
+            let config: NetemConfig = interconnect.config.parse()?;
+            Ok(((interconnect.a, interconnect.b), config))
+        })
+        .collect()
+}
+
+impl Scenario {
+    pub fn verify(&self) -> Result<(), String> {
+        let mut previous: Option<&ScenarioStep> = None;
+        for (i, step) in self.steps.iter().enumerate() {
+            if !step.topology.verify() {
+                return Err(format!("step {}: invalid topology", i));
+            }
+            if let Some(previous) = previous {
+                if step.at_secs < previous.at_secs + previous.ramp_secs {
+                    return Err(format!("step {}: starts before step {} ends", i, i - 1));
+                }
+            }
+            if step.ramp_secs > 0 {
+                let previous =
+                    previous.ok_or_else(|| "step 0: nothing to ramp from".to_string())?;
+                if previous.topology.partitions != step.topology.partitions {
+                    return Err(format!("step {}: partitions differ from the ramp start", i));
+                }
+                parse_interconnects(&previous.topology)
+                    .and_then(|_| parse_interconnects(&step.topology))
+                    .map_err(|err| format!("step {}: {}", i, err))?;
+            }
+            previous = Some(step);
+        }
+        Ok(())
+    }
+
+    /// Returns the topologies to apply, and when, with the ramps expanded into
+    /// intermediate topologies every `RAMP_INTERVAL_SECS`
+    pub fn timeline(&self) -> Vec<(Duration, NetworkTopology)> {
+        let mut timeline = vec![];
+        let mut previous: Option<&ScenarioStep> = None;
+        for step in &self.steps {
+            match previous {
+                Some(previous) if step.ramp_secs > 0 => {
+                    let from = parse_interconnects(&previous.topology).unwrap_or_default();
+                    let to = parse_interconnects(&step.topology).unwrap_or_default();
+                    let num_increments =
+                        ((step.ramp_secs + RAMP_INTERVAL_SECS - 1) / RAMP_INTERVAL_SECS).max(1);
+                    for increment in 1..=num_increments {
+                        let fraction = increment as f64 / num_increments as f64;
+                        let interconnects = from
+                            .keys()
+                            .chain(to.keys())
+                            .collect::<BTreeSet<_>>()
+                            .into_iter()
+                            .filter_map(|(a, b)| {
+                                let from = from.get(&(*a, *b)).copied().unwrap_or_default();
+                                let to = to.get(&(*a, *b)).copied().unwrap_or_default();
+                                let config = from.interpolate(&to, fraction);
+                                (!config.is_empty()).then(|| NetworkInterconnect {
+                                    a: *a,
+                                    b: *b,
+                                    config: config.to_string(),
+                                })
+                            })
+                            .collect();
+                        let at_secs = step.at_secs + step.ramp_secs * increment / num_increments;
+                        timeline.push((
+                            Duration::from_secs(at_secs),
+                            NetworkTopology {
+                                partitions: step.topology.partitions.clone(),
+                                interconnects,
+                            },
+                        ));
+                    }
+                }
+                _ => timeline.push((Duration::from_secs(step.at_secs), step.topology.clone())),
+            }
+            previous = Some(step);
+        }
+        timeline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_step(at_secs: u64, ramp_secs: u64, config: &str) -> ScenarioStep {
+        let interconnects = if config.is_empty() {
+            vec![]
+        } else {
+            vec![NetworkInterconnect {
+                a: 0,
+                b: 1,
+                config: config.to_string(),
+            }]
+        };
+        ScenarioStep {
+            at_secs,
+            ramp_secs,
+            topology: NetworkTopology {
+                partitions: vec![50, 50],
+                interconnects,
+            },
+        }
+    }
+
+    #[test]
+    fn test_verify_scenario() {
+        let scenario = Scenario {
+            steps: vec![new_step(30, 0, "loss 100%"), new_step(90, 0, "")],
+        };
+        assert_eq!(scenario.verify(), Ok(()));
+
+        let scenario = Scenario {
+            steps: vec![new_step(30, 0, ""), new_step(20, 0, "")],
+        };
+        assert!(scenario.verify().is_err());
+
+        let scenario = Scenario {
+            steps: vec![new_step(30, 10, "delay 10ms")],
+        };
+        assert!(scenario.verify().is_err());
+
+        let scenario = Scenario {
+            steps: vec![new_step(0, 0, ""), new_step(30, 10, "rate 1mbit")],
+        };
+        assert!(scenario.verify().is_err());
+    }
+
+    #[test]
+    fn test_scenario_timeline() {
+        let scenario = Scenario {
+            steps: vec![
+                new_step(30, 0, "loss 100%"),
+                new_step(90, 0, ""),
+                new_step(120, 10, "delay 200ms"),
+            ],
+        };
+        assert_eq!(scenario.verify(), Ok(()));
+        let timeline: Vec<_> = scenario
+            .timeline()
+            .into_iter()
+            .map(|(at, topology)| {
+                let configs: Vec<_> = topology
+                    .interconnects
+                    .into_iter()
+                    .map(|interconnect| interconnect.config)
+                    .collect();
+                (at.as_secs(), configs)
+            })
+            .collect();
+        assert_eq!(
+            timeline,
+            vec![
+                (30, vec!["loss 100%".to_string()]),
+                (90, vec![]),
+                (125, vec!["delay 100ms 0ms".to_string()]),
+                (130, vec!["delay 200ms 0ms".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_scenario() {
+        let scenario: Scenario = serde_json::from_str(
+            r#"{"steps": [
+                {"at_secs": 30, "topology": {"partitions": [100], "interconnects": []}}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            scenario,
+            Scenario {
+                steps: vec![ScenarioStep {
+                    at_secs: 30,
+                    ramp_secs: 0,
+                    topology: NetworkTopology::default(),
+                }],
+            }
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//! The `topology` module describes how the nodes are split into partitions,
+//! and the `tc netem` parameters applied to the traffic between partitions.
+
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::{fmt, io, str::FromStr};
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct NetworkInterconnect {
+    pub a: u8,
+    pub b: u8,
+    pub config: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct NetworkTopology {
+    pub partitions: Vec<u8>,
+    pub interconnects: Vec<NetworkInterconnect>,
+}
+
+impl Default for NetworkTopology {
+    fn default() -> Self {
+        Self {
+            partitions: vec![100],
+            interconnects: vec![],
+        }
+    }
+}
+
+impl NetworkTopology {
+    pub fn verify(&self) -> bool {
+        let sum: u8 = self.partitions.iter().sum();
+        if sum != 100 {
+            return false;
+        }
+
+        for x in self.interconnects.iter() {
+            if x.a as usize > self.partitions.len() || x.b as usize > self.partitions.len() {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Returns the partition of the node at `index`, out of `size` nodes
+    pub fn partition_of(&self, index: u64, size: u64) -> usize {
+        let mut watermark = 0;
+        for (i, p) in self.partitions.iter().enumerate() {
+            watermark += *p;
+            if u64::from(watermark) >= (index + 1) * 100 / size {
+                return i;
+            }
+        }
+        0
+    }
+
+    pub fn new_from_stdin() -> Self {
+        let mut input = String::new();
+        println!("Configure partition map (must add up to 100, e.g. [70, 20, 10]):");
+        let partitions_str = match io::stdin().read_line(&mut input) {
+            Ok(_) => input,
+            Err(error) => panic!("error: {}", error),
+        };
+
+        let partitions: Vec<u8> = serde_json::from_str(&partitions_str)
+            .expect("Failed to parse input. It must be a JSON string");
+
+        let mut interconnects: Vec<NetworkInterconnect> = vec![];
+
+        for i in 0..partitions.len() - 1 {
+            for j in i + 1..partitions.len() {
+                println!("Configure interconnect ({} <-> {}):", i, j);
+                let mut input = String::new();
+                let mut interconnect_config = match io::stdin().read_line(&mut input) {
+                    Ok(_) => input,
+                    Err(error) => panic!("error: {}", error),
+                };
+
+                if interconnect_config.ends_with('\n') {
+                    interconnect_config.pop();
+                    if interconnect_config.ends_with('\r') {
+                        interconnect_config.pop();
+                    }
+                }
+
+                if !interconnect_config.is_empty() {
+                    let interconnect = NetworkInterconnect {
+                        a: i as u8,
+                        b: j as u8,
+                        config: interconnect_config.clone(),
+                    };
+                    interconnects.push(interconnect);
+                    let interconnect = NetworkInterconnect {
+                        a: j as u8,
+                        b: i as u8,
+                        config: interconnect_config,
+                    };
+                    interconnects.push(interconnect);
+                }
+            }
+        }
+
+        Self {
+            partitions,
+            interconnects,
+        }
+    }
+
+    pub fn new_random(max_partitions: usize, max_packet_drop: u8, max_packet_delay: u32) -> Self {
+        let mut rng = thread_rng();
+        let num_partitions = rng.gen_range(0, max_partitions + 1);
+
+        if num_partitions == 0 {
+            return NetworkTopology::default();
+        }
+
+        let mut partitions = vec![];
+        let mut used_partition = 0;
+        for i in 0..num_partitions {
+            let partition = if i == num_partitions - 1 {
+                100 - used_partition
+            } else {
+                rng.gen_range(0, 100 - used_partition - num_partitions + i)
+            };
+            used_partition += partition;
+            partitions.push(partition as u8);
+        }
+
+        let mut interconnects: Vec<NetworkInterconnect> = vec![];
+        for i in 0..partitions.len() - 1 {
+            for j in i + 1..partitions.len() {
+                let drop_config = if max_packet_drop > 0 {
+                    let packet_drop = rng.gen_range(0, max_packet_drop + 1);
+                    format!("loss {}% 25% ", packet_drop)
+                } else {
+                    String::default()
+                };
+
+                let config = if max_packet_delay > 0 {
+                    let packet_delay = rng.gen_range(0, max_packet_delay + 1);
+                    format!("{}delay {}ms 10ms", drop_config, packet_delay)
+                } else {
+                    drop_config
+                };
+
+                let interconnect = NetworkInterconnect {
+                    a: i as u8,
+                    b: j as u8,
+                    config: config.clone(),
+                };
+                interconnects.push(interconnect);
+                let interconnect = NetworkInterconnect {
+                    a: j as u8,
+                    b: i as u8,
+                    config,
+                };
+                interconnects.push(interconnect);
+            }
+        }
+        Self {
+            partitions,
+            interconnects,
+        }
+    }
+}
+
+/// The `tc netem` parameters which can be simulated in-process and ramped
+/// between the steps of a scenario
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct NetemConfig {
+    pub loss_percent: f64,
+    pub delay_ms: u64,
+    pub jitter_ms: u64,
+}
+
+fn parse_percent(value: &str) -> Option<f64> {
+    value
+        .strip_suffix('%')
+        .unwrap_or(value)
+        .parse()
+        .ok()
+        .filter(|percent| (0.0..=100.0).contains(percent))
+}
+
+fn parse_time_ms(value: &str) -> Option<u64> {
+    let (number, scale) = if let Some(number) = value.strip_suffix("ms") {
+        (number, 1.0)
+    } else if let Some(number) = value.strip_suffix("us") {
+        (number, 0.001)
+    } else if let Some(number) = value.strip_suffix('s') {
+        (number, 1000.0)
+    } else {
+        (value, 1.0)
+    };
+    let time = number.parse::<f64>().ok()? * scale;
+    if time >= 0.0 {
+        Some(time.round() as u64)
+    } else {
+        None
+    }
+}
+
+impl FromStr for NetemConfig {
+    type Err = String;
+
+    /// Parses `[loss <percent> [<correlation>]] [delay <time> [<jitter> [<correlation>]]]`.
+    /// The correlations are only honored by `tc`.
+    fn from_str(config: &str) -> Result<Self, Self::Err> {
+        let mut netem = NetemConfig::default();
+        let mut tokens = config.split_whitespace().peekable();
+        while let Some(token) = tokens.next() {
+            let invalid = || format!("invalid {} in netem config: '{}'", token, config);
+            match token {
+                "loss" => {
+                    tokens.next_if_eq(&"random");
+                    netem.loss_percent =
+                        tokens.next().and_then(parse_percent).ok_or_else(invalid)?;
+                    tokens.next_if(|value| parse_percent(value).is_some());
+                }
+                "delay" => {
+                    netem.delay_ms = tokens.next().and_then(parse_time_ms).ok_or_else(invalid)?;
+                    if let Some(jitter) = tokens.peek().and_then(|value| parse_time_ms(value)) {
+                        netem.jitter_ms = jitter;
+                        tokens.next();
+                        tokens.next_if(|value| parse_percent(value).is_some());
+                    }
+                }
+                _ => return Err(format!("unsupported netem parameter: '{}'", token)),
+            }
+        }
+        Ok(netem)
+    }
+}
+
+impl fmt::Display for NetemConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parameters = vec![];
+        if self.loss_percent > 0.0 {
+            parameters.push(format!("loss {}%", self.loss_percent));
+        }
+        if self.delay_ms > 0 || self.jitter_ms > 0 {
+            parameters.push(format!("delay {}ms {}ms", self.delay_ms, self.jitter_ms));
+        }
+        write!(f, "{}", parameters.join(" "))
+    }
+}
+
+impl NetemConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == NetemConfig::default()
+    }
+
+    /// Returns the parameters `fraction` of the way from `self` to `other`
+    pub fn interpolate(&self, other: &Self, fraction: f64) -> Self {
+        let ms = |from: u64, to: u64| (from as f64 + (to as f64 - from as f64) * fraction).round();
+        Self {
+            loss_percent: self.loss_percent + (other.loss_percent - self.loss_percent) * fraction,
+            delay_ms: ms(self.delay_ms, other.delay_ms) as u64,
+            jitter_ms: ms(self.jitter_ms, other.jitter_ms) as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition_of() {
+        let topology = NetworkTopology {
+            partitions: vec![50, 25, 25],
+            interconnects: vec![],
+        };
+        let partitions: Vec<_> = (0..8).map(|i| topology.partition_of(i, 8)).collect();
+        assert_eq!(partitions, vec![0, 0, 0, 0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_parse_netem_config() {
+        assert_eq!("".parse(), Ok(NetemConfig::default()));
+        assert_eq!(
+            "loss 15% 25% delay 50ms 10ms".parse(),
+            Ok(NetemConfig {
+                loss_percent: 15.0,
+                delay_ms: 50,
+                jitter_ms: 10,
+            })
+        );
+        assert_eq!(
+            "delay 1s 5ms 25%".parse(),
+            Ok(NetemConfig {
+                loss_percent: 0.0,
+                delay_ms: 1000,
+                jitter_ms: 5,
+            })
+        );
+        assert_eq!(
+            "loss random 100%".parse(),
+            Ok(NetemConfig {
+                loss_percent: 100.0,
+                ..NetemConfig::default()
+            })
+        );
+        assert!("loss 120%".parse::<NetemConfig>().is_err());
+        assert!("delay".parse::<NetemConfig>().is_err());
+        assert!("rate 1mbit".parse::<NetemConfig>().is_err());
+    }
+
+    #[test]
+    fn test_netem_config_interpolate() {
+        let from = NetemConfig::default();
+        let to: NetemConfig = "loss 50% delay 200ms 20ms".parse().unwrap();
+        let halfway = from.interpolate(&to, 0.5);
+        assert_eq!(halfway.to_string(), "loss 25% delay 100ms 10ms");
+        assert_eq!(halfway.to_string().parse(), Ok(halfway));
+        assert_eq!(to.interpolate(&from, 1.0), from);
+        assert!(from.to_string().is_empty());
+    }
+}
@@ -121,6 +121,7 @@ Operate a configured testnet
    --parition          - Percentage of network that should be configured with netem
    --config-file       - Configuration file for partition and netem configuration
    --netem-cmd         - Optional command argument to netem. Default is "add". Use "cleanup" to remove rules.
+                         Use "run" to play the scenario timeline of the configuration file.
 
  update-specific options:
    --platform linux|osx|windows       - Deploy the tarball using 'solana-install deploy ...' for the
@@ -1115,7 +1116,7 @@ logs)
 netem)
   if [[ -n $netemConfigFile ]]; then
     remoteNetemConfigFile="$(basename "$netemConfigFile")"
-    if [[ $netemCommand = "add" ]]; then
+    if [[ $netemCommand = "add" || $netemCommand = "run" ]]; then
       for ipAddress in "${validatorIpList[@]}"; do
         "$here"/scp.sh "$netemConfigFile" solana@"$ipAddress":"$SOLANA_HOME"
       done
@@ -17,10 +17,108 @@
 /// ```bash
 /// export TWILIO_CONFIG='ACCOUNT=<account>,TOKEN=<securityToken>,TO=<receivingNumber>,FROM=<sendingNumber>'
 /// ```
+///
+/// To post a JSON payload to a generic webhook, optionally rendered from a template in which
+/// `{{message}}`, `{{severity}}`, `{{action}}` and `{{incident}}` are replaced:
+/// ```bash
+/// export WEBHOOK_URL=...
+/// export WEBHOOK_TEMPLATE='{"text":"{{severity}}: {{message}}"}'
+/// ```
+///
+/// To send emails through an SMTP relay, the username and password being optional:
+/// ```bash
+/// export SMTP_CONFIG='HOST=<host:port>,FROM=<address>,TO=<address>[;<address>...],USERNAME=<username>,PASSWORD=<password>'
+/// ```
+///
+/// To open and resolve PagerDuty incidents, having an Events API v2 integration (the plain
+/// messages, which are not tied to an incident, are only logged):
+/// ```bash
+/// export PAGERDUTY_INTEGRATION_KEY=...
+/// ```
+///
+/// All the variables but `TWILIO_CONFIG` can be prefixed, see `Notifier::new`.
 use log::*;
 use reqwest::{blocking::Client, StatusCode};
+use serde_derive::{Deserialize, Serialize};
 use serde_json::json;
-use std::{env, str::FromStr, thread::sleep, time::Duration};
+use std::{env, fmt, str::FromStr, thread::sleep, time::Duration};
+
+mod pager_duty;
+mod smtp;
+mod webhook;
+
+use {pager_duty::PagerDuty, smtp::SmtpConfig, webhook::WebHook};
+
+/// The severity of a notification, from the least to the most severe.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+    Critical,
+}
+
+impl Default for Severity {
+    fn default() -> Self {
+        Severity::Warning
+    }
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "Info"),
+            Severity::Warning => write!(f, "Warning"),
+            Severity::Error => write!(f, "Error"),
+            Severity::Critical => write!(f, "Critical"),
+        }
+    }
+}
+
+/// Whether a notification opens or resolves an incident. The backends without
+/// a notion of incident simply send the message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NotificationType {
+    Message,
+    /// Opens the incident, or updates it if it is already open
+    Trigger {
+        incident: String,
+    },
+    Resolve {
+        incident: String,
+    },
+}
+
+impl NotificationType {
+    pub fn action(&self) -> &'static str {
+        match self {
+            NotificationType::Message => "message",
+            NotificationType::Trigger { .. } => "trigger",
+            NotificationType::Resolve { .. } => "resolve",
+        }
+    }
+
+    pub fn incident(&self) -> Option<&str> {
+        match self {
+            NotificationType::Message => None,
+            NotificationType::Trigger { incident } | NotificationType::Resolve { incident } => {
+                Some(incident)
+            }
+        }
+    }
+}
 
 struct TelegramWebHook {
     bot_token: String,
@@ -75,17 +173,20 @@ fn get_twilio_config() -> Result<Option<TwilioWebHook>, String> {
     Ok(Some(config))
 }
 
-enum NotificationType {
+enum NotificationChannel {
     Discord(String),
     Slack(String),
     Telegram(TelegramWebHook),
     Twilio(TwilioWebHook),
+    WebHook(WebHook),
+    Smtp(SmtpConfig),
+    PagerDuty(PagerDuty),
     Log(Level),
 }
 
 pub struct Notifier {
     client: Client,
-    notifiers: Vec<NotificationType>,
+    notifiers: Vec<NotificationChannel>,
 }
 
 impl Notifier {
@@ -99,29 +200,51 @@ impl Notifier {
         let mut notifiers = vec![];
 
         if let Ok(webhook) = env::var(format!("{}DISCORD_WEBHOOK", env_prefix)) {
-            notifiers.push(NotificationType::Discord(webhook));
+            notifiers.push(NotificationChannel::Discord(webhook));
         }
         if let Ok(webhook) = env::var(format!("{}SLACK_WEBHOOK", env_prefix)) {
-            notifiers.push(NotificationType::Slack(webhook));
+            notifiers.push(NotificationChannel::Slack(webhook));
         }
 
         if let (Ok(bot_token), Ok(chat_id)) = (
             env::var(format!("{}TELEGRAM_BOT_TOKEN", env_prefix)),
             env::var(format!("{}TELEGRAM_CHAT_ID", env_prefix)),
         ) {
-            notifiers.push(NotificationType::Telegram(TelegramWebHook {
+            notifiers.push(NotificationChannel::Telegram(TelegramWebHook {
                 bot_token,
                 chat_id,
             }));
         }
 
         if let Ok(Some(webhook)) = get_twilio_config() {
-            notifiers.push(NotificationType::Twilio(webhook));
+            notifiers.push(NotificationChannel::Twilio(webhook));
+        }
+
+        if let Ok(url) = env::var(format!("{}WEBHOOK_URL", env_prefix)) {
+            let template = env::var(format!("{}WEBHOOK_TEMPLATE", env_prefix))
+                .unwrap_or_else(|_| webhook::DEFAULT_TEMPLATE.to_string());
+            notifiers.push(NotificationChannel::WebHook(WebHook { url, template }));
+        }
+
+        if let Ok(config_var) = env::var(format!("{}SMTP_CONFIG", env_prefix)) {
+            match smtp::parse_smtp_config(&config_var) {
+                Ok(config) => notifiers.push(NotificationChannel::Smtp(config)),
+                Err(err) => warn!("{}", err),
+            }
+        }
+
+        if let Ok(integration_key) = env::var(format!("{}PAGERDUTY_INTEGRATION_KEY", env_prefix)) {
+            let events_url = env::var(format!("{}PAGERDUTY_EVENTS_URL", env_prefix))
+                .unwrap_or_else(|_| pager_duty::DEFAULT_EVENTS_URL.to_string());
+            notifiers.push(NotificationChannel::PagerDuty(PagerDuty {
+                integration_key,
+                events_url,
+            }));
         }
 
         if let Ok(log_level) = env::var(format!("{}LOG_NOTIFIER_LEVEL", env_prefix)) {
             match Level::from_str(&log_level) {
-                Ok(level) => notifiers.push(NotificationType::Log(level)),
+                Ok(level) => notifiers.push(NotificationChannel::Log(level)),
                 Err(e) => warn!(
                     "could not parse specified log notifier level string ({}): {}",
                     log_level, e
@@ -141,10 +264,10 @@ impl Notifier {
         self.notifiers.is_empty()
     }
 
-    pub fn send(&self, msg: &str) {
+    pub fn send(&self, msg: &str, severity: Severity, notification_type: &NotificationType) {
         for notifier in &self.notifiers {
             match notifier {
-                NotificationType::Discord(webhook) => {
+                NotificationChannel::Discord(webhook) => {
                     for line in msg.split('\n') {
                         // Discord rate limiting is aggressive, limit to 1 message a second
                         sleep(Duration::from_millis(1000));
@@ -171,14 +294,14 @@ impl Notifier {
                         }
                     }
                 }
-                NotificationType::Slack(webhook) => {
+                NotificationChannel::Slack(webhook) => {
                     let data = json!({ "text": msg });
                     if let Err(err) = self.client.post(webhook).json(&data).send() {
                         warn!("Failed to send Slack message: {:?}", err);
                     }
                 }
 
-                NotificationType::Telegram(TelegramWebHook { chat_id, bot_token }) => {
+                NotificationChannel::Telegram(TelegramWebHook { chat_id, bot_token }) => {
                     let data = json!({ "chat_id": chat_id, "text": msg });
                     let url = format!("https://api.telegram.org/bot{}/sendMessage", bot_token);
 
@@ -187,7 +310,7 @@ impl Notifier {
                     }
                 }
 
-                NotificationType::Twilio(TwilioWebHook {
+                NotificationChannel::Twilio(TwilioWebHook {
                     account,
                     token,
                     to,
@@ -202,10 +325,163 @@ impl Notifier {
                         warn!("Failed to send Twilio message: {:?}", err);
                     }
                 }
-                NotificationType::Log(level) => {
+                NotificationChannel::WebHook(webhook) => {
+                    match webhook.render(msg, severity, notification_type) {
+                        Ok(data) => {
+                            if let Err(err) = self.client.post(&webhook.url).json(&data).send() {
+                                warn!("Failed to send webhook message: {:?}", err);
+                            }
+                        }
+                        Err(err) => warn!("Failed to render webhook message: {}", err),
+                    }
+                }
+                NotificationChannel::Smtp(config) => {
+                    let first_line = msg.lines().next().unwrap_or_default();
+                    let subject = match notification_type {
+                        NotificationType::Resolve { .. } => format!("[Resolved] {}", first_line),
+                        _ => format!("[{}] {}", severity, first_line),
+                    };
+                    if let Err(err) = smtp::send_email(config, &subject, msg) {
+                        warn!("Failed to send email: {:?}", err);
+                    }
+                }
+                NotificationChannel::PagerDuty(pager_duty) => {
+                    if let Some(event) = pager_duty.event(msg, severity, notification_type) {
+                        let response = self
+                            .client
+                            .post(&pager_duty.events_url)
+                            .json(&event)
+                            .send()
+                            .and_then(|response| response.error_for_status());
+                        if let Err(err) = response {
+                            warn!("Failed to send PagerDuty event: {:?}", err);
+                        }
+                    } else {
+                        info!(
+                            "Not sending a message without incident to PagerDuty: {}",
+                            msg
+                        );
+                    }
+                }
+                NotificationChannel::Log(level) => {
                     log!(*level, "{}", msg)
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        serde_json::Value,
+        std::{
+            io::{BufRead, BufReader, Read, Write},
+            net::TcpListener,
+            thread,
+        },
+    };
+
+    // Starts an HTTP stand-in accepting `count` requests, and returns its url
+    // along with a handle to the JSON bodies of the requests.
+    fn start_http_server(count: usize) -> (String, thread::JoinHandle<Vec<Value>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            (0..count)
+                .map(|_| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        let line = line.trim_end().to_ascii_lowercase();
+                        if line.is_empty() {
+                            break;
+                        }
+                        if let Some(value) = line.strip_prefix("content-length:") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    write!(
+                        stream,
+                        "HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    )
+                    .unwrap();
+                    serde_json::from_slice(&body).unwrap()
+                })
+                .collect()
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_send_webhook() {
+        let (url, handle) = start_http_server(1);
+        env::set_var("TEST_WEBHOOK_WEBHOOK_URL", url);
+        env::set_var(
+            "TEST_WEBHOOK_WEBHOOK_TEMPLATE",
+            r#"{"text":"{{severity}}: {{message}}"}"#,
+        );
+        let notifier = Notifier::new("TEST_WEBHOOK_");
+        notifier.send("hello", Severity::Warning, &NotificationType::Message);
+        assert_eq!(
+            handle.join().unwrap(),
+            vec![json!({"text": "warning: hello"})]
+        );
+    }
+
+    #[test]
+    fn test_send_pager_duty() {
+        let (url, handle) = start_http_server(2);
+        env::set_var("TEST_PAGERDUTY_PAGERDUTY_INTEGRATION_KEY", "key");
+        env::set_var("TEST_PAGERDUTY_PAGERDUTY_EVENTS_URL", url);
+        let notifier = Notifier::new("TEST_PAGERDUTY_");
+        let incident = "testnet/delinquent".to_string();
+        // Plain messages are not sent.
+        notifier.send("hello", Severity::Info, &NotificationType::Message);
+        notifier.send(
+            "delinquent",
+            Severity::Critical,
+            &NotificationType::Trigger {
+                incident: incident.clone(),
+            },
+        );
+        notifier.send(
+            "all clear",
+            Severity::Critical,
+            &NotificationType::Resolve { incident },
+        );
+        let events = handle.join().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["event_action"], "trigger");
+        assert_eq!(events[0]["dedup_key"], "testnet/delinquent");
+        assert_eq!(events[0]["payload"]["severity"], "critical");
+        assert_eq!(events[1]["event_action"], "resolve");
+        assert_eq!(events[1]["dedup_key"], "testnet/delinquent");
+    }
+
+    #[test]
+    fn test_send_email() {
+        let (host, handle) = smtp::test::start_smtp_server();
+        env::set_var(
+            "TEST_SMTP_SMTP_CONFIG",
+            format!("HOST={},FROM=a@localhost,TO=b@localhost", host),
+        );
+        let notifier = Notifier::new("TEST_SMTP_");
+        notifier.send(
+            "all clear\ndetails",
+            Severity::Error,
+            &NotificationType::Resolve {
+                incident: "testnet/delinquent".to_string(),
+            },
+        );
+        let transcript = handle.join().unwrap();
+        assert!(transcript.contains(&"Subject: [Resolved] all clear".to_string()));
+        assert!(transcript.contains(&"details".to_string()));
+    }
+}
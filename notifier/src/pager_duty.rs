@@ -0,0 +1,96 @@
+//! PagerDuty Events API v2, opening and resolving incidents by dedup key.
+
+use {
+    crate::{NotificationType, Severity},
+    serde_json::{json, Value},
+};
+
+pub(crate) const DEFAULT_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+// The API rejects longer summaries.
+const MAX_SUMMARY_LEN: usize = 1024;
+
+pub(crate) struct PagerDuty {
+    pub integration_key: String,
+    pub events_url: String,
+}
+
+impl PagerDuty {
+    /// Returns the event to enqueue, or `None` for the plain messages which
+    /// are not tied to an incident.
+    pub fn event(
+        &self,
+        msg: &str,
+        severity: Severity,
+        notification_type: &NotificationType,
+    ) -> Option<Value> {
+        let incident = notification_type.incident()?;
+        let mut summary = msg.to_string();
+        if summary.len() > MAX_SUMMARY_LEN {
+            let mut len = MAX_SUMMARY_LEN;
+            while !summary.is_char_boundary(len) {
+                len -= 1;
+            }
+            summary.truncate(len);
+        }
+        Some(json!({
+            "routing_key": self.integration_key,
+            "event_action": notification_type.action(),
+            "dedup_key": incident,
+            "payload": {
+                "summary": summary,
+                "source": "solana",
+                "severity": severity.as_str(),
+            },
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pager_duty_event() {
+        let pager_duty = PagerDuty {
+            integration_key: "key".to_string(),
+            events_url: DEFAULT_EVENTS_URL.to_string(),
+        };
+        assert_eq!(
+            pager_duty.event("hello", Severity::Info, &NotificationType::Message),
+            None
+        );
+        let incident = "testnet/delinquent".to_string();
+        assert_eq!(
+            pager_duty.event(
+                "validator delinquent",
+                Severity::Error,
+                &NotificationType::Trigger {
+                    incident: incident.clone()
+                }
+            ),
+            Some(json!({
+                "routing_key": "key",
+                "event_action": "trigger",
+                "dedup_key": "testnet/delinquent",
+                "payload": {
+                    "summary": "validator delinquent",
+                    "source": "solana",
+                    "severity": "error",
+                },
+            }))
+        );
+        let event = pager_duty
+            .event(
+                &"é".repeat(MAX_SUMMARY_LEN),
+                Severity::Info,
+                &NotificationType::Resolve { incident },
+            )
+            .unwrap();
+        assert_eq!(event["event_action"], "resolve");
+        assert_eq!(
+            event["payload"]["summary"].as_str().unwrap().len(),
+            MAX_SUMMARY_LEN
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Minimal SMTP client, sending plain text emails through a relay.
+//!
+//! The connection is not encrypted, so the relay is expected to be local or
+//! reached through a trusted network.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct SmtpConfig {
+    /// The relay, as `host:port`
+    pub host: String,
+    pub from: String,
+    pub to: Vec<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl SmtpConfig {
+    fn complete(&self) -> bool {
+        !(self.host.is_empty()
+            || self.from.is_empty()
+            || self.to.is_empty()
+            || self.username.is_some() != self.password.is_some())
+    }
+}
+
+/// Parses `HOST=<host:port>,FROM=<address>,TO=<address>[;<address>...]`,
+/// optionally followed by `,USERNAME=<username>,PASSWORD=<password>`.
+pub(crate) fn parse_smtp_config(config_var: &str) -> Result<SmtpConfig, String> {
+    let mut config = SmtpConfig::default();
+    for pair in config_var.split(',') {
+        let nv: Vec<_> = pair.splitn(2, '=').collect();
+        if nv.len() != 2 {
+            return Err(format!("SMTP_CONFIG is invalid: '{}'", pair));
+        }
+        let v = nv[1].to_string();
+        match nv[0] {
+            "HOST" => config.host = v,
+            "FROM" => config.from = v,
+            "TO" => config.to = v.split(';').map(str::to_string).collect(),
+            "USERNAME" => config.username = Some(v),
+            "PASSWORD" => config.password = Some(v),
+            _ => return Err(format!("SMTP_CONFIG is invalid: '{}'", pair)),
+        }
+    }
+    if !config.complete() {
+        return Err("SMTP_CONFIG is incomplete".to_string());
+    }
+    Ok(config)
+}
+
+struct SmtpConnection {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+}
+
+impl SmtpConnection {
+    fn connect(host: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(host)?;
+        stream.set_read_timeout(Some(SMTP_TIMEOUT))?;
+        stream.set_write_timeout(Some(SMTP_TIMEOUT))?;
+        let mut connection = Self {
+            reader: BufReader::new(stream.try_clone()?),
+            stream,
+        };
+        connection.expect_reply(220)?;
+        Ok(connection)
+    }
+
+    // Reads a possibly multiline reply, and checks its code.
+    fn expect_reply(&mut self, code: u16) -> io::Result<()> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection closed",
+                ));
+            }
+            let reply_code = line.get(..3).and_then(|code| code.parse::<u16>().ok());
+            if reply_code != Some(code) {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("unexpected reply: {}", line.trim_end()),
+                ));
+            }
+            // The last line of a reply has a space after the code.
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+
+    fn command(&mut self, command: &str, code: u16) -> io::Result<()> {
+        write!(self.stream, "{}\r\n", command)?;
+        self.expect_reply(code)
+    }
+}
+
+// Escapes the lines starting with a dot, and normalizes the line endings.
+fn dot_stuff(body: &str) -> String {
+    body.lines()
+        .map(|line| {
+            if line.starts_with('.') {
+                format!(".{}\r\n", line)
+            } else {
+                format!("{}\r\n", line)
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn send_email(config: &SmtpConfig, subject: &str, body: &str) -> io::Result<()> {
+    let mut connection = SmtpConnection::connect(&config.host)?;
+    connection.command("EHLO localhost", 250)?;
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        let credentials = base64::encode(format!("\0{}\0{}", username, password));
+        connection.command(&format!("AUTH PLAIN {}", credentials), 235)?;
+    }
+    connection.command(&format!("MAIL FROM:<{}>", config.from), 250)?;
+    for to in &config.to {
+        connection.command(&format!("RCPT TO:<{}>", to), 250)?;
+    }
+    connection.command("DATA", 354)?;
+    let message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\r\n{}.",
+        config.from,
+        config.to.join(", "),
+        subject.replace(&['\r', '\n'][..], " "),
+        dot_stuff(body),
+    );
+    connection.command(&message, 250)?;
+    connection.command("QUIT", 221)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use {
+        super::*,
+        std::{net::TcpListener, thread},
+    };
+
+    /// Starts an SMTP stand-in accepting a single email, and returns its
+    /// address along with a handle to the transcript of the session.
+    pub(crate) fn start_smtp_server() -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut stream = stream;
+            let mut transcript = vec![];
+            write!(stream, "220 localhost ready\r\n").unwrap();
+            let mut data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                let line = line.trim_end().to_string();
+                transcript.push(line.clone());
+                let reply = if data {
+                    if line != "." {
+                        continue;
+                    }
+                    data = false;
+                    "250 queued"
+                } else if line.starts_with("EHLO") {
+                    "250-localhost\r\n250 AUTH PLAIN"
+                } else if line.starts_with("AUTH") {
+                    "235 authenticated"
+                } else if line == "DATA" {
+                    data = true;
+                    "354 go ahead"
+                } else if line == "QUIT" {
+                    write!(stream, "221 bye\r\n").unwrap();
+                    break;
+                } else {
+                    "250 ok"
+                };
+                write!(stream, "{}\r\n", reply).unwrap();
+            }
+            transcript
+        });
+        (host, handle)
+    }
+
+    #[test]
+    fn test_parse_smtp_config() {
+        assert_eq!(
+            parse_smtp_config("HOST=localhost:25,FROM=a@b.c,TO=d@e.f;g@h.i"),
+            Ok(SmtpConfig {
+                host: "localhost:25".to_string(),
+                from: "a@b.c".to_string(),
+                to: vec!["d@e.f".to_string(), "g@h.i".to_string()],
+                username: None,
+                password: None,
+            })
+        );
+        assert!(parse_smtp_config("HOST=localhost:25,FROM=a@b.c").is_err());
+        assert!(parse_smtp_config("HOST=localhost:25,FROM=a@b.c,TO=d@e.f,USERNAME=u").is_err());
+        assert!(parse_smtp_config("HOST=localhost:25,FOO=bar").is_err());
+    }
+
+    #[test]
+    fn test_send_email() {
+        let (host, handle) = start_smtp_server();
+        let config = SmtpConfig {
+            host,
+            from: "watchtower@localhost".to_string(),
+            to: vec!["ops@localhost".to_string()],
+            username: Some("user".to_string()),
+            password: Some("pass".to_string()),
+        };
+        send_email(&config, "[Critical] alert", "first line\n.second line").unwrap();
+        let transcript = handle.join().unwrap();
+        assert_eq!(
+            transcript,
+            vec![
+                "EHLO localhost",
+                "AUTH PLAIN AHVzZXIAcGFzcw==",
+                "MAIL FROM:<watchtower@localhost>",
+                "RCPT TO:<ops@localhost>",
+                "DATA",
+                "From: watchtower@localhost",
+                "To: ops@localhost",
+                "Subject: [Critical] alert",
+                "Content-Type: text/plain; charset=utf-8",
+                "",
+                "first line",
+                "..second line",
+                ".",
+                "QUIT",
+            ]
+        );
+    }
+}
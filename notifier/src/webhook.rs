@@ -0,0 +1,97 @@
+//! Generic JSON webhook, posting a payload rendered from a template.
+
+use {
+    crate::{NotificationType, Severity},
+    serde_json::Value,
+};
+
+/// Placeholders are replaced by the JSON-escaped value of the variable, so
+/// they are meant to be used within JSON strings.
+pub(crate) const DEFAULT_TEMPLATE: &str = concat!(
+    r#"{"message":"{{message}}","severity":"{{severity}}","#,
+    r#""action":"{{action}}","incident":"{{incident}}"}"#
+);
+
+pub(crate) struct WebHook {
+    pub url: String,
+    pub template: String,
+}
+
+impl WebHook {
+    /// Renders the payload, or returns an error if the rendered template is
+    /// not valid JSON.
+    pub fn render(
+        &self,
+        msg: &str,
+        severity: Severity,
+        notification_type: &NotificationType,
+    ) -> Result<Value, String> {
+        let payload = render_template(
+            &self.template,
+            &[
+                ("message", msg),
+                ("severity", severity.as_str()),
+                ("action", notification_type.action()),
+                ("incident", notification_type.incident().unwrap_or_default()),
+            ],
+        );
+        serde_json::from_str(&payload).map_err(|err| format!("invalid payload: {}", err))
+    }
+}
+
+/// Replaces each `{{name}}` of the template by the JSON-escaped value of the
+/// variable. Unknown placeholders are left as is.
+pub(crate) fn render_template(template: &str, vars: &[(&str, &str)]) -> String {
+    vars.iter()
+        .fold(template.to_string(), |out, (name, value)| {
+            let value = Value::String(value.to_string()).to_string();
+            // Strip the quotes around the escaped string.
+            out.replace(&format!("{{{{{}}}}}", name), &value[1..value.len() - 1])
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, serde_json::json};
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!(
+            render_template(
+                "{\"text\":\"{{message}} ({{severity}}) {{other}}\"}",
+                &[("message", "a \"quoted\"\nmessage"), ("severity", "info")]
+            ),
+            "{\"text\":\"a \\\"quoted\\\"\\nmessage (info) {{other}}\"}"
+        );
+    }
+
+    #[test]
+    fn test_webhook_render() {
+        let webhook = WebHook {
+            url: String::default(),
+            template: DEFAULT_TEMPLATE.to_string(),
+        };
+        assert_eq!(
+            webhook.render(
+                "validator delinquent",
+                Severity::Critical,
+                &NotificationType::Trigger {
+                    incident: "mainnet/delinquent".to_string()
+                }
+            ),
+            Ok(json!({
+                "message": "validator delinquent",
+                "severity": "critical",
+                "action": "trigger",
+                "incident": "mainnet/delinquent",
+            }))
+        );
+        let webhook = WebHook {
+            url: String::default(),
+            template: "{\"text\": {{message}}}".to_string(),
+        };
+        assert!(webhook
+            .render("oops", Severity::Info, &NotificationType::Message)
+            .is_err());
+    }
+}
@@ -245,6 +245,14 @@ fn get_packet_offsets(packet: &Packet, current_offset: usize) -> PacketOffsets {
     }
 }
 
+/// Returns the number of signatures of the transaction in the packet, or None
+/// if it does not match the message header or does not fit in the packet.
+pub fn get_packet_num_signatures(packet: &Packet) -> Option<u32> {
+    do_get_packet_offsets(packet, 0)
+        .ok()
+        .map(|offsets| offsets.sig_len)
+}
+
 pub fn generate_offsets(batches: &[Packets], recycler: &Recycler<TxOffset>) -> TxOffsets {
     debug!("allocating..");
     let mut signature_offsets: PinnedVec<_> = recycler.allocate("sig_offsets");
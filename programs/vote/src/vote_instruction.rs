@@ -3,7 +3,7 @@
 
 use crate::{
     id,
-    vote_state::{self, Vote, VoteAuthorize, VoteInit, VoteState},
+    vote_state::{self, Vote, VoteAuthorize, VoteInit, VoteState, VoteStateUpdate},
 };
 use log::*;
 use num_derive::{FromPrimitive, ToPrimitive};
@@ -44,6 +44,39 @@ pub enum VoteError {
 
     #[error("authorized voter has already been changed this epoch")]
     TooSoonToReauthorize,
+
+    #[error("vote state update slots are not ordered")]
+    SlotsNotOrdered,
+
+    #[error("vote state update confirmations are not ordered")]
+    ConfirmationsNotOrdered,
+
+    #[error("vote state update has a lockout with zero confirmations")]
+    ZeroConfirmations,
+
+    #[error("vote state update has a lockout with too many confirmations")]
+    ConfirmationTooLarge,
+
+    #[error("vote state update rolls back the root")]
+    RootRollBack,
+
+    #[error("vote state update rolls back the confirmations of a lockout")]
+    ConfirmationRollBack,
+
+    #[error("vote state update has a slot at or below the root")]
+    SlotSmallerThanRoot,
+
+    #[error("vote state update has too many lockouts")]
+    TooManyVotes,
+
+    #[error("vote state update has a lockout which does not cover the next vote")]
+    NewVoteStateLockoutMismatch,
+
+    #[error("vote state update drops a lockout that is not expired")]
+    LockoutConflict,
+
+    #[error("vote state update root is not on the bank's fork")]
+    RootOnDifferentFork,
 }
 
 impl<E> DecodeError<E> for VoteError {
@@ -123,6 +156,24 @@ pub enum VoteInstruction {
     ///   2. [SIGNER] Vote or withdraw authority
     ///   3. [SIGNER] New vote or withdraw authority
     AuthorizeChecked(VoteAuthorize),
+
+    /// Replace the vote account's tower with the voter's complete lockout tower and root
+    ///
+    /// # Account references
+    ///   0. [WRITE] Vote account to vote with
+    ///   1. [] Slot hashes sysvar
+    ///   2. [] Clock sysvar
+    ///   3. [SIGNER] Vote authority
+    UpdateVoteState(VoteStateUpdate),
+
+    /// Replace the vote account's tower with the voter's complete lockout tower and root
+    ///
+    /// # Account references
+    ///   0. [WRITE] Vote account to vote with
+    ///   1. [] Slot hashes sysvar
+    ///   2. [] Clock sysvar
+    ///   3. [SIGNER] Vote authority
+    UpdateVoteStateSwitch(VoteStateUpdate, Hash),
 }
 
 fn initialize_account(vote_pubkey: &Pubkey, vote_init: &VoteInit) -> Instruction {
@@ -280,6 +331,45 @@ pub fn vote_switch(
     )
 }
 
+pub fn update_vote_state(
+    vote_pubkey: &Pubkey,
+    authorized_voter_pubkey: &Pubkey,
+    vote_state_update: VoteStateUpdate,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*vote_pubkey, false),
+        AccountMeta::new_readonly(sysvar::slot_hashes::id(), false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*authorized_voter_pubkey, true),
+    ];
+
+    Instruction::new_with_bincode(
+        id(),
+        &VoteInstruction::UpdateVoteState(vote_state_update),
+        account_metas,
+    )
+}
+
+pub fn update_vote_state_switch(
+    vote_pubkey: &Pubkey,
+    authorized_voter_pubkey: &Pubkey,
+    vote_state_update: VoteStateUpdate,
+    proof_hash: Hash,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*vote_pubkey, false),
+        AccountMeta::new_readonly(sysvar::slot_hashes::id(), false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*authorized_voter_pubkey, true),
+    ];
+
+    Instruction::new_with_bincode(
+        id(),
+        &VoteInstruction::UpdateVoteStateSwitch(vote_state_update, proof_hash),
+        account_metas,
+    )
+}
+
 pub fn withdraw(
     vote_pubkey: &Pubkey,
     authorized_withdrawer_pubkey: &Pubkey,
@@ -384,6 +474,23 @@ pub fn process_instruction(
                 Err(InstructionError::InvalidInstructionData)
             }
         }
+        VoteInstruction::UpdateVoteState(vote_state_update)
+        | VoteInstruction::UpdateVoteStateSwitch(vote_state_update, _) => {
+            if invoke_context
+                .is_feature_active(&feature_set::allow_votes_to_directly_update_vote_state::id())
+            {
+                inc_new_counter_info!("vote-state-native", 1);
+                vote_state::process_vote_state_update(
+                    me,
+                    &from_keyed_account::<SlotHashes>(keyed_account_at_index(keyed_accounts, 1)?)?,
+                    &from_keyed_account::<Clock>(keyed_account_at_index(keyed_accounts, 2)?)?,
+                    vote_state_update,
+                    &signers,
+                )
+            } else {
+                Err(InstructionError::InvalidInstructionData)
+            }
+        }
     }
 }
 
@@ -506,6 +613,23 @@ mod tests {
             )),
             Err(InstructionError::InvalidAccountData),
         );
+        assert_eq!(
+            process_instruction(&update_vote_state(
+                &Pubkey::default(),
+                &Pubkey::default(),
+                VoteStateUpdate::default(),
+            )),
+            Err(InstructionError::InvalidAccountData),
+        );
+        assert_eq!(
+            process_instruction(&update_vote_state_switch(
+                &Pubkey::default(),
+                &Pubkey::default(),
+                VoteStateUpdate::default(),
+                Hash::default(),
+            )),
+            Err(InstructionError::InvalidAccountData),
+        );
         assert_eq!(
             process_instruction(&authorize(
                 &Pubkey::default(),
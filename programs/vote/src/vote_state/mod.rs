@@ -65,6 +65,52 @@ impl Vote {
     }
 }
 
+#[derive(Serialize, Default, Deserialize, Debug, PartialEq, Eq, Clone, AbiExample)]
+pub struct VoteStateUpdate {
+    /// The proposed tower, starting with the oldest lockout
+    pub lockouts: VecDeque<Lockout>,
+    /// The proposed root
+    pub root: Option<Slot>,
+    /// signature of the bank's state at the last slot
+    pub hash: Hash,
+    /// processing timestamp of last slot
+    pub timestamp: Option<UnixTimestamp>,
+}
+
+impl VoteStateUpdate {
+    pub fn new(lockouts: VecDeque<Lockout>, root: Option<Slot>, hash: Hash) -> Self {
+        Self {
+            lockouts,
+            root,
+            hash,
+            timestamp: None,
+        }
+    }
+
+    pub fn slots(&self) -> Vec<Slot> {
+        self.lockouts.iter().map(|lockout| lockout.slot).collect()
+    }
+
+    pub fn last_voted_slot(&self) -> Option<Slot> {
+        self.lockouts.back().map(|lockout| lockout.slot)
+    }
+
+    pub fn last_voted_slot_hash(&self) -> Option<(Slot, Hash)> {
+        self.last_voted_slot().map(|slot| (slot, self.hash))
+    }
+}
+
+impl From<VoteStateUpdate> for Vote {
+    /// The slots of the proposed tower, as voted on by a legacy `Vote`
+    fn from(vote_state_update: VoteStateUpdate) -> Self {
+        Self {
+            slots: vote_state_update.slots(),
+            hash: vote_state_update.hash,
+            timestamp: vote_state_update.timestamp,
+        }
+    }
+}
+
 #[derive(Serialize, Default, Deserialize, Debug, PartialEq, Eq, Clone, AbiExample)]
 pub struct Lockout {
     pub slot: Slot,
@@ -362,6 +408,197 @@ impl VoteState {
         Ok(())
     }
 
+    fn check_update_vote_state_slots_are_valid(
+        &self,
+        vote_state_update: &mut VoteStateUpdate,
+        slot_hashes: &[(Slot, Hash)],
+    ) -> Result<(), VoteError> {
+        let last_vote_state_update_slot = vote_state_update
+            .last_voted_slot()
+            .ok_or(VoteError::EmptySlots)?;
+
+        // The proposed tower must vote on something new
+        if self.last_voted_slot().map_or(false, |last_voted_slot| {
+            last_vote_state_update_slot <= last_voted_slot
+        }) {
+            return Err(VoteError::VoteTooOld);
+        }
+
+        // slot_hashes is sorted from the newest to the oldest slot
+        let earliest_slot_hash_in_history = match slot_hashes.last() {
+            Some((slot, _hash)) => *slot,
+            None => return Err(VoteError::SlotsMismatch),
+        };
+        if last_vote_state_update_slot < earliest_slot_hash_in_history {
+            return Err(VoteError::VoteTooOld);
+        }
+        let find_slot_hash =
+            |slot: Slot| slot_hashes.binary_search_by(|(s, _hash)| slot.cmp(s)).ok();
+
+        // A root which is too old for the history can't be checked against this fork, so it
+        // falls back to the current root, which was already verified
+        if let Some(root) = vote_state_update.root {
+            if root < earliest_slot_hash_in_history {
+                vote_state_update.root = self.root_slot;
+            } else if find_slot_hash(root).is_none() {
+                return Err(VoteError::RootOnDifferentFork);
+            }
+        }
+
+        // Every slot new enough to be in the history must be an ancestor on this fork. Older
+        // slots can't be checked, they are only kept if the current tower already has them.
+        let mut retained = VecDeque::with_capacity(vote_state_update.lockouts.len());
+        for lockout in vote_state_update.lockouts.drain(..) {
+            if lockout.slot >= earliest_slot_hash_in_history {
+                if find_slot_hash(lockout.slot).is_none() {
+                    info!(
+                        "{} dropped vote state update {:?} failed to match slot: {:?}",
+                        self.node_pubkey, lockout, slot_hashes,
+                    );
+                    inc_new_counter_info!("dropped-vote-slot", 1);
+                    return Err(VoteError::SlotsMismatch);
+                }
+            } else if !self.contains_slot(lockout.slot) {
+                continue;
+            }
+            retained.push_back(lockout);
+        }
+        vote_state_update.lockouts = retained;
+
+        let (_slot, hash) = slot_hashes
+            [find_slot_hash(last_vote_state_update_slot).ok_or(VoteError::SlotsMismatch)?];
+        if hash != vote_state_update.hash {
+            warn!(
+                "{} dropped vote state update {:?} failed to match hash {} {}",
+                self.node_pubkey, vote_state_update, vote_state_update.hash, hash
+            );
+            inc_new_counter_info!("dropped-vote-hash", 1);
+            return Err(VoteError::SlotHashMismatch);
+        }
+        Ok(())
+    }
+
+    /// Replace the tower with `new_state` and `new_root`, which must be a valid successor of the
+    /// current tower: slots and confirmations ordered, no rolled back root or confirmations, and
+    /// any dropped vote expired by a later one
+    pub fn process_new_vote_state(
+        &mut self,
+        new_state: VecDeque<Lockout>,
+        new_root: Option<Slot>,
+        timestamp: Option<UnixTimestamp>,
+        epoch: Epoch,
+    ) -> Result<(), VoteError> {
+        if new_state.is_empty() {
+            return Err(VoteError::EmptySlots);
+        }
+        if new_state.len() > MAX_LOCKOUT_HISTORY {
+            return Err(VoteError::TooManyVotes);
+        }
+
+        match (new_root, self.root_slot) {
+            (Some(new_root), Some(current_root)) if new_root < current_root => {
+                return Err(VoteError::RootRollBack);
+            }
+            (None, Some(_)) => return Err(VoteError::RootRollBack),
+            _ => (),
+        }
+
+        let mut previous_vote: Option<&Lockout> = None;
+        for vote in &new_state {
+            if vote.confirmation_count == 0 {
+                return Err(VoteError::ZeroConfirmations);
+            }
+            if vote.confirmation_count > MAX_LOCKOUT_HISTORY as u32 {
+                return Err(VoteError::ConfirmationTooLarge);
+            }
+            if new_root.map_or(false, |new_root| vote.slot <= new_root) {
+                return Err(VoteError::SlotSmallerThanRoot);
+            }
+            if let Some(previous_vote) = previous_vote {
+                if previous_vote.slot >= vote.slot {
+                    return Err(VoteError::SlotsNotOrdered);
+                }
+                if previous_vote.confirmation_count <= vote.confirmation_count {
+                    return Err(VoteError::ConfirmationsNotOrdered);
+                }
+                if previous_vote.last_locked_out_slot() < vote.slot {
+                    return Err(VoteError::NewVoteStateLockoutMismatch);
+                }
+            }
+            previous_vote = Some(vote);
+        }
+
+        // Every slot rooted by this update earns a credit: the new root itself, and the older
+        // votes of the current tower it finalized along the way
+        let mut current_vote_index = 0;
+        let mut finalized_slot_count = 1;
+        if let Some(new_root) = new_root {
+            for current_vote in &self.votes {
+                if current_vote.slot > new_root {
+                    break;
+                }
+                if current_vote.slot != new_root {
+                    finalized_slot_count += 1;
+                }
+                current_vote_index += 1;
+            }
+        }
+
+        // Votes of the current tower missing from the new one must have been expired by later
+        // votes, and the ones kept can't lose confirmations
+        let mut new_vote_index = 0;
+        while current_vote_index < self.votes.len() && new_vote_index < new_state.len() {
+            let current_vote = &self.votes[current_vote_index];
+            let new_vote = &new_state[new_vote_index];
+            match current_vote.slot.cmp(&new_vote.slot) {
+                Ordering::Less => {
+                    if current_vote.last_locked_out_slot() >= new_vote.slot {
+                        return Err(VoteError::LockoutConflict);
+                    }
+                    current_vote_index += 1;
+                }
+                Ordering::Equal => {
+                    if new_vote.confirmation_count < current_vote.confirmation_count {
+                        return Err(VoteError::ConfirmationRollBack);
+                    }
+                    current_vote_index += 1;
+                    new_vote_index += 1;
+                }
+                Ordering::Greater => {
+                    new_vote_index += 1;
+                }
+            }
+        }
+
+        if let Some(timestamp) = timestamp {
+            let last_slot = new_state.back().unwrap().slot;
+            self.process_timestamp(last_slot, timestamp)?;
+        }
+        if self.root_slot != new_root {
+            for _ in 0..finalized_slot_count {
+                self.increment_credits(epoch);
+            }
+        }
+        self.root_slot = new_root;
+        self.votes = new_state;
+        Ok(())
+    }
+
+    pub fn process_vote_state_update(
+        &mut self,
+        mut vote_state_update: VoteStateUpdate,
+        slot_hashes: &[SlotHash],
+        epoch: Epoch,
+    ) -> Result<(), VoteError> {
+        self.check_update_vote_state_slots_are_valid(&mut vote_state_update, slot_hashes)?;
+        self.process_new_vote_state(
+            vote_state_update.lockouts,
+            vote_state_update.root,
+            vote_state_update.timestamp,
+            epoch,
+        )
+    }
+
     pub fn process_next_vote_slot(&mut self, next_vote_slot: Slot, epoch: Epoch) {
         // Ignore votes for slots earlier than we already have votes for
         if self
@@ -440,6 +677,12 @@ impl VoteState {
         self.last_lockout().map(|v| v.slot)
     }
 
+    pub fn contains_slot(&self, candidate_slot: Slot) -> bool {
+        self.votes
+            .binary_search_by(|lockout| lockout.slot.cmp(&candidate_slot))
+            .is_ok()
+    }
+
     // Upto MAX_LOCKOUT_HISTORY many recent unexpired
     // vote slots pushed onto the stack.
     pub fn tower(&self) -> Vec<Slot> {
@@ -757,6 +1000,27 @@ pub fn process_vote<S: std::hash::BuildHasher>(
     vote_account.set_state(&VoteStateVersions::new_current(vote_state))
 }
 
+pub fn process_vote_state_update<S: std::hash::BuildHasher>(
+    vote_account: &KeyedAccount,
+    slot_hashes: &[SlotHash],
+    clock: &Clock,
+    vote_state_update: VoteStateUpdate,
+    signers: &HashSet<Pubkey, S>,
+) -> Result<(), InstructionError> {
+    let versioned = State::<VoteStateVersions>::state(vote_account)?;
+
+    if versioned.is_uninitialized() {
+        return Err(InstructionError::UninitializedAccount);
+    }
+
+    let mut vote_state = versioned.convert_to_current();
+    let authorized_voter = vote_state.get_and_update_authorized_voter(clock.epoch)?;
+    verify_authorized_signer(&authorized_voter, signers)?;
+
+    vote_state.process_vote_state_update(vote_state_update, slot_hashes, clock.epoch)?;
+    vote_account.set_state(&VoteStateVersions::new_current(vote_state))
+}
+
 pub fn create_account_with_authorized(
     node_pubkey: &Pubkey,
     authorized_voter: &Pubkey,
@@ -1627,6 +1891,244 @@ mod tests {
         );
     }
 
+    fn build_lockouts(slots_and_confirmations: &[(Slot, u32)]) -> VecDeque<Lockout> {
+        slots_and_confirmations
+            .iter()
+            .map(|(slot, confirmation_count)| Lockout {
+                slot: *slot,
+                confirmation_count: *confirmation_count,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_process_new_vote_state_matches_process_vote() {
+        let mut vote_state = VoteState::default();
+        let mut vote_state_from_updates = VoteState::default();
+
+        // skip a few slots along the way so that some lockouts expire
+        for slot in (0..100).filter(|slot| slot % 7 != 3) {
+            vote_state.process_slot_vote_unchecked(slot);
+            vote_state_from_updates
+                .process_new_vote_state(vote_state.votes.clone(), vote_state.root_slot, None, 0)
+                .unwrap();
+            assert_eq!(vote_state.votes, vote_state_from_updates.votes);
+            assert_eq!(vote_state.root_slot, vote_state_from_updates.root_slot);
+            assert_eq!(vote_state.credits(), vote_state_from_updates.credits());
+        }
+        assert!(vote_state_from_updates.root_slot.is_some());
+    }
+
+    #[test]
+    fn test_process_new_vote_state_credits_every_finalized_slot() {
+        let mut vote_state = VoteState::default();
+        vote_state
+            .process_new_vote_state(build_lockouts(&[(2, 3), (3, 2), (4, 1)]), None, None, 0)
+            .unwrap();
+        assert_eq!(vote_state.credits(), 0);
+
+        // rooting 3 finalizes 2 as well
+        vote_state
+            .process_new_vote_state(build_lockouts(&[(4, 2), (5, 1)]), Some(3), None, 0)
+            .unwrap();
+        assert_eq!(vote_state.credits(), 2);
+        assert_eq!(vote_state.root_slot, Some(3));
+    }
+
+    #[test]
+    fn test_process_new_vote_state_invalid() {
+        let mut vote_state = VoteState::default();
+        vote_state
+            .process_new_vote_state(build_lockouts(&[(4, 3), (5, 2), (6, 1)]), Some(2), None, 0)
+            .unwrap();
+
+        let mut check = |lockouts: &[(Slot, u32)], root: Option<Slot>, expected: VoteError| {
+            let original = vote_state.clone();
+            assert_eq!(
+                vote_state.process_new_vote_state(build_lockouts(lockouts), root, None, 0),
+                Err(expected)
+            );
+            assert_eq!(vote_state, original);
+        };
+        check(&[], Some(2), VoteError::EmptySlots);
+        let too_many: Vec<_> = (0..=MAX_LOCKOUT_HISTORY as u64)
+            .map(|i| (10 + i, (MAX_LOCKOUT_HISTORY as u64 + 1 - i) as u32))
+            .collect();
+        check(&too_many, Some(2), VoteError::TooManyVotes);
+        check(&[(7, 1)], Some(1), VoteError::RootRollBack);
+        check(&[(7, 1)], None, VoteError::RootRollBack);
+        check(&[(7, 0)], Some(2), VoteError::ZeroConfirmations);
+        check(
+            &[(7, MAX_LOCKOUT_HISTORY as u32 + 1)],
+            Some(2),
+            VoteError::ConfirmationTooLarge,
+        );
+        check(&[(2, 2), (7, 1)], Some(2), VoteError::SlotSmallerThanRoot);
+        check(&[(6, 2), (5, 1)], Some(2), VoteError::SlotsNotOrdered);
+        check(
+            &[(5, 1), (6, 1)],
+            Some(2),
+            VoteError::ConfirmationsNotOrdered,
+        );
+        check(
+            &[(5, 2), (100, 1)],
+            Some(2),
+            VoteError::NewVoteStateLockoutMismatch,
+        );
+        check(
+            &[(4, 3), (5, 1), (6, 1)],
+            Some(2),
+            VoteError::ConfirmationsNotOrdered,
+        );
+        check(
+            &[(4, 3), (6, 2), (7, 1)],
+            Some(2),
+            VoteError::LockoutConflict,
+        );
+        check(&[(4, 2), (7, 1)], Some(2), VoteError::ConfirmationRollBack);
+    }
+
+    #[test]
+    fn test_check_update_vote_state_slots_are_valid() {
+        let mut vote_state = VoteState::default();
+        vote_state
+            .process_new_vote_state(build_lockouts(&[(4, 2), (6, 1)]), Some(2), None, 0)
+            .unwrap();
+        let hash = hash(&[1u8]);
+        // newest first, slot 5 is on another fork
+        let slot_hashes: Vec<_> = vec![(8, hash), (7, hash), (6, hash), (4, hash), (3, hash)];
+
+        let mut vote_state_update =
+            VoteStateUpdate::new(build_lockouts(&[(4, 3), (6, 2), (8, 1)]), Some(3), hash);
+        assert_eq!(
+            vote_state
+                .check_update_vote_state_slots_are_valid(&mut vote_state_update, &slot_hashes),
+            Ok(())
+        );
+        assert_eq!(vote_state_update.slots(), vec![4, 6, 8]);
+        assert_eq!(vote_state_update.root, Some(3));
+
+        // slots and roots too old for the history are dropped or replaced by the current root
+        let mut vote_state_update = VoteStateUpdate::new(
+            build_lockouts(&[(1, 5), (2, 4), (4, 3), (6, 2), (8, 1)]),
+            Some(0),
+            hash,
+        );
+        vote_state.votes.push_front(Lockout {
+            slot: 2,
+            confirmation_count: 3,
+        });
+        assert_eq!(
+            vote_state
+                .check_update_vote_state_slots_are_valid(&mut vote_state_update, &slot_hashes),
+            Ok(())
+        );
+        assert_eq!(vote_state_update.slots(), vec![2, 4, 6, 8]);
+        assert_eq!(vote_state_update.root, Some(2));
+        vote_state.votes.pop_front();
+
+        let check = |vote_state_update: VoteStateUpdate, expected: VoteError| {
+            let mut vote_state_update = vote_state_update;
+            assert_eq!(
+                vote_state
+                    .check_update_vote_state_slots_are_valid(&mut vote_state_update, &slot_hashes),
+                Err(expected)
+            );
+        };
+        check(
+            VoteStateUpdate::new(VecDeque::new(), Some(3), hash),
+            VoteError::EmptySlots,
+        );
+        check(
+            VoteStateUpdate::new(build_lockouts(&[(4, 2), (6, 1)]), Some(3), hash),
+            VoteError::VoteTooOld,
+        );
+        check(
+            VoteStateUpdate::new(build_lockouts(&[(5, 2), (8, 1)]), Some(3), hash),
+            VoteError::SlotsMismatch,
+        );
+        check(
+            VoteStateUpdate::new(build_lockouts(&[(7, 2), (9, 1)]), Some(3), hash),
+            VoteError::SlotsMismatch,
+        );
+        check(
+            VoteStateUpdate::new(build_lockouts(&[(6, 2), (8, 1)]), Some(5), hash),
+            VoteError::RootOnDifferentFork,
+        );
+        check(
+            VoteStateUpdate::new(build_lockouts(&[(6, 2), (8, 1)]), Some(3), Hash::default()),
+            VoteError::SlotHashMismatch,
+        );
+        assert_eq!(
+            vote_state.check_update_vote_state_slots_are_valid(
+                &mut VoteStateUpdate::new(build_lockouts(&[(8, 1)]), Some(3), hash),
+                &[]
+            ),
+            Err(VoteError::SlotsMismatch)
+        );
+    }
+
+    #[test]
+    fn test_process_vote_state_update() {
+        let (vote_pubkey, vote_account) = create_test_account();
+        let hash = hash(&[1u8]);
+        let slot_hashes: Vec<_> = vec![(3, hash), (2, hash), (1, hash)];
+        let mut vote_state_update =
+            VoteStateUpdate::new(build_lockouts(&[(1, 3), (2, 2), (3, 1)]), None, hash);
+        vote_state_update.timestamp = Some(42);
+
+        // the vote authority must sign
+        let keyed_accounts = &[KeyedAccount::new(&vote_pubkey, false, &vote_account)];
+        let signers: HashSet<Pubkey> = get_signers(keyed_accounts);
+        assert_eq!(
+            process_vote_state_update(
+                &keyed_accounts[0],
+                &slot_hashes,
+                &Clock::default(),
+                vote_state_update.clone(),
+                &signers,
+            ),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+
+        let keyed_accounts = &[KeyedAccount::new(&vote_pubkey, true, &vote_account)];
+        let signers: HashSet<Pubkey> = get_signers(keyed_accounts);
+        assert_eq!(
+            process_vote_state_update(
+                &keyed_accounts[0],
+                &slot_hashes,
+                &Clock::default(),
+                vote_state_update.clone(),
+                &signers,
+            ),
+            Ok(())
+        );
+        let vote_state: VoteState = StateMut::<VoteStateVersions>::state(&*vote_account.borrow())
+            .unwrap()
+            .convert_to_current();
+        assert_eq!(vote_state.votes, vote_state_update.lockouts);
+        assert_eq!(vote_state.root_slot, None);
+        assert_eq!(
+            vote_state.last_timestamp,
+            BlockTimestamp {
+                slot: 3,
+                timestamp: 42
+            }
+        );
+
+        // resubmitting the same tower is too old
+        assert_eq!(
+            process_vote_state_update(
+                &keyed_accounts[0],
+                &slot_hashes,
+                &Clock::default(),
+                vote_state_update,
+                &signers,
+            ),
+            Err(VoteError::VoteTooOld.into())
+        );
+    }
+
     #[test]
     fn test_vote_state_commission_split() {
         let vote_state = VoteState::default();
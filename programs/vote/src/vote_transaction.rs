@@ -41,6 +41,12 @@ pub fn parse_vote_transaction(tx: &Transaction) -> Option<(Pubkey, Vote, Option<
                             VoteInstruction::VoteSwitch(vote, hash) => {
                                 Some((*key, vote, Some(hash)))
                             }
+                            VoteInstruction::UpdateVoteState(vote_state_update) => {
+                                Some((*key, Vote::from(vote_state_update), None))
+                            }
+                            VoteInstruction::UpdateVoteStateSwitch(vote_state_update, hash) => {
+                                Some((*key, Vote::from(vote_state_update), Some(hash)))
+                            }
                             _ => None,
                         })
                     })
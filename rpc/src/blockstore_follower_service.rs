@@ -0,0 +1,139 @@
+//! The `blockstore_follower_service` keeps a secondary blockstore caught up with the validator
+//! that owns the primary, so that a separate process can serve historical RPC requests from
+//! the same ledger
+
+use {
+    crate::max_slots::MaxSlots,
+    solana_ledger::blockstore::Blockstore,
+    solana_runtime::commitment::BlockCommitmentCache,
+    std::{
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc, RwLock,
+        },
+        thread::{self, sleep, Builder, JoinHandle},
+        time::Duration,
+    },
+};
+
+pub const DEFAULT_FOLLOW_INTERVAL_MS: u64 = 400;
+
+pub struct BlockstoreFollowerService {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl BlockstoreFollowerService {
+    pub fn new(
+        blockstore: Arc<Blockstore>,
+        block_commitment_cache: Arc<RwLock<BlockCommitmentCache>>,
+        max_slots: Arc<MaxSlots>,
+        max_complete_transaction_status_slot: Arc<AtomicU64>,
+        follow_interval: Duration,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let exit = exit.clone();
+        let thread_hdl = Builder::new()
+            .name("solana-blockstore-follower".to_string())
+            .spawn(move || loop {
+                if exit.load(Ordering::Relaxed) {
+                    break;
+                }
+                if let Err(err) = blockstore.try_catch_up_with_primary() {
+                    warn!("Failed to catch up with primary blockstore: {:?}", err);
+                } else {
+                    Self::update_slots(
+                        &blockstore,
+                        &block_commitment_cache,
+                        &max_slots,
+                        &max_complete_transaction_status_slot,
+                    );
+                }
+                sleep(follow_interval);
+            })
+            .unwrap();
+        Self { thread_hdl }
+    }
+
+    fn update_slots(
+        blockstore: &Blockstore,
+        block_commitment_cache: &RwLock<BlockCommitmentCache>,
+        max_slots: &MaxSlots,
+        max_complete_transaction_status_slot: &AtomicU64,
+    ) {
+        // The follower has no view of cluster votes, so only the roots the primary has
+        // recorded are treated as finalized
+        let root = blockstore.last_root();
+        let highest_slot = blockstore.highest_slot().ok().flatten().unwrap_or(root);
+
+        let prev_root = block_commitment_cache
+            .read()
+            .unwrap()
+            .highest_confirmed_root();
+        if root > prev_root {
+            block_commitment_cache
+                .write()
+                .unwrap()
+                .set_highest_confirmed_root(root);
+            datapoint_info!("blockstore-follower", ("root", root, i64));
+        }
+        max_slots
+            .shred_insert
+            .fetch_max(highest_slot, Ordering::Relaxed);
+        max_complete_transaction_status_slot.fetch_max(root, Ordering::SeqCst);
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_ledger::{
+            blockstore::make_slot_entries, blockstore_db::AccessType, get_tmp_ledger_path,
+        },
+    };
+
+    #[test]
+    fn test_update_slots() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let primary = Blockstore::open(&ledger_path).unwrap();
+            let secondary =
+                Blockstore::open_with_access_type(&ledger_path, AccessType::Secondary, None, true)
+                    .unwrap();
+            let block_commitment_cache = RwLock::new(BlockCommitmentCache::default());
+            let max_slots = MaxSlots::default();
+            let max_complete_transaction_status_slot = AtomicU64::default();
+
+            for (slot, parent_slot) in [(1, 0), (2, 1)].iter() {
+                let (shreds, _) = make_slot_entries(*slot, *parent_slot, 1);
+                primary.insert_shreds(shreds, None, false).unwrap();
+            }
+            primary.set_roots(vec![0, 1].iter()).unwrap();
+
+            secondary.try_catch_up_with_primary().unwrap();
+            BlockstoreFollowerService::update_slots(
+                &secondary,
+                &block_commitment_cache,
+                &max_slots,
+                &max_complete_transaction_status_slot,
+            );
+            assert_eq!(
+                block_commitment_cache
+                    .read()
+                    .unwrap()
+                    .highest_confirmed_root(),
+                1
+            );
+            assert_eq!(max_slots.shred_insert.load(Ordering::Relaxed), 2);
+            assert_eq!(
+                max_complete_transaction_status_slot.load(Ordering::Relaxed),
+                1
+            );
+        }
+        Blockstore::destroy(&ledger_path).unwrap();
+    }
+}
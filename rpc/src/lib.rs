@@ -1,4 +1,5 @@
 #![allow(clippy::integer_arithmetic)]
+pub mod blockstore_follower_service;
 pub mod max_slots;
 pub mod optimistically_confirmed_bank_tracker;
 pub mod parsed_token_accounts;
@@ -97,6 +97,7 @@ type RpcCustomResult<T> = std::result::Result<T, RpcCustomError>;
 
 pub const MAX_REQUEST_PAYLOAD_SIZE: usize = 50 * (1 << 10); // 50kB
 pub const PERFORMANCE_SAMPLES_LIMIT: usize = 720;
+pub const BLOCK_COSTS_LIMIT: usize = 150;
 
 // Limit the length of the `epoch_credits` array for each validator in a `get_vote_accounts`
 // response
@@ -142,6 +143,7 @@ pub struct JsonRpcConfig {
     pub minimal_api: bool,
     pub obsolete_v1_7_api: bool,
     pub rpc_scan_and_fix_roots: bool,
+    pub max_transaction_history_slots: Option<Arc<AtomicU64>>, // None = retain all history
 }
 
 #[derive(Clone)]
@@ -300,7 +302,12 @@ impl JsonRpcRequestProcessor {
             ))),
             blockstore,
             validator_exit: create_validator_exit(&exit),
-            health: Arc::new(RpcHealth::new(cluster_info.clone(), None, 0, exit.clone())),
+            health: Arc::new(RpcHealth::new(
+                cluster_info.clone(),
+                Arc::default(),
+                0,
+                exit.clone(),
+            )),
             cluster_info,
             genesis_hash,
             transaction_sender: Arc::new(Mutex::new(sender)),
@@ -894,10 +901,7 @@ impl JsonRpcRequestProcessor {
             if let BlockstoreError::SlotCleanedUp = result.as_ref().unwrap_err() {
                 return Err(RpcCustomError::BlockCleanedUp {
                     slot,
-                    first_available_block: self
-                        .blockstore
-                        .get_first_available_block()
-                        .unwrap_or_default(),
+                    first_available_block: self.get_first_available_local_block(),
                 }
                 .into());
             }
@@ -905,6 +909,29 @@ impl JsonRpcRequestProcessor {
         Ok(())
     }
 
+    // Transaction history of slots older than this is no longer served from the local ledger,
+    // even if ledger cleanup has not caught up with the retention limit yet
+    fn get_lowest_retained_transaction_history_slot(&self) -> Slot {
+        self.config
+            .max_transaction_history_slots
+            .as_ref()
+            .map(|max_transaction_history_slots| {
+                self.block_commitment_cache
+                    .read()
+                    .unwrap()
+                    .highest_confirmed_root()
+                    .saturating_sub(max_transaction_history_slots.load(Ordering::Relaxed))
+            })
+            .unwrap_or_default()
+    }
+
+    fn get_first_available_local_block(&self) -> Slot {
+        self.blockstore
+            .get_first_available_block()
+            .unwrap_or_default()
+            .max(self.get_lowest_retained_transaction_history_slot())
+    }
+
     fn check_bigtable_result<T>(
         &self,
         result: &std::result::Result<T, solana_storage_bigtable::Error>,
@@ -944,7 +971,11 @@ impl JsonRpcRequestProcessor {
                     .unwrap()
                     .highest_confirmed_root()
             {
-                let result = self.blockstore.get_rooted_block(slot, true);
+                let result = if slot < self.get_lowest_retained_transaction_history_slot() {
+                    Err(BlockstoreError::SlotCleanedUp)
+                } else {
+                    self.blockstore.get_rooted_block(slot, true)
+                };
                 self.check_blockstore_root(&result, slot)?;
                 if result.is_err() {
                     if let Some(bigtable_ledger_storage) = &self.bigtable_ledger_storage {
@@ -1316,7 +1347,11 @@ impl JsonRpcRequestProcessor {
             } else {
                 self.blockstore.get_rooted_transaction(signature)
             };
-            match transaction.unwrap_or(None) {
+            let lowest_retained_slot = self.get_lowest_retained_transaction_history_slot();
+            match transaction
+                .unwrap_or(None)
+                .filter(|confirmed_transaction| confirmed_transaction.slot >= lowest_retained_slot)
+            {
                 Some(mut confirmed_transaction) => {
                     if commitment.is_confirmed()
                         && confirmed_bank // should be redundant
@@ -1373,6 +1408,10 @@ impl JsonRpcRequestProcessor {
                     .unwrap()
                     .highest_confirmed_root(),
             );
+            let start_slot = max(
+                start_slot,
+                self.get_lowest_retained_transaction_history_slot(),
+            );
             self.blockstore
                 .get_confirmed_signatures_for_address(pubkey, start_slot, end_slot)
                 .unwrap_or_default()
@@ -1409,6 +1448,8 @@ impl JsonRpcRequestProcessor {
                 .blockstore
                 .get_confirmed_signatures_for_address2(address, highest_slot, before, until, limit)
                 .map_err(|err| Error::invalid_params(format!("{}", err)))?;
+            let lowest_retained_slot = self.get_lowest_retained_transaction_history_slot();
+            results.retain(|result| result.slot >= lowest_retained_slot);
 
             if results.len() < limit {
                 if let Some(bigtable_ledger_storage) = &self.bigtable_ledger_storage {
@@ -1460,10 +1501,7 @@ impl JsonRpcRequestProcessor {
     }
 
     pub fn get_first_available_block(&self) -> Slot {
-        let slot = self
-            .blockstore
-            .get_first_available_block()
-            .unwrap_or_default();
+        let slot = self.get_first_available_local_block();
 
         if let Some(bigtable_ledger_storage) = &self.bigtable_ledger_storage {
             let bigtable_slot = self
@@ -2405,6 +2443,13 @@ pub mod rpc_full {
             limit: Option<usize>,
         ) -> Result<Vec<RpcPerfSample>>;
 
+        #[rpc(meta, name = "getRecentBlockCosts")]
+        fn get_recent_block_costs(
+            &self,
+            meta: Self::Metadata,
+            limit: Option<usize>,
+        ) -> Result<Vec<RpcBlockCost>>;
+
         #[rpc(meta, name = "getBlockCommitment")]
         fn get_block_commitment(
             &self,
@@ -2768,6 +2813,50 @@ pub mod rpc_full {
                 .collect())
         }
 
+        fn get_recent_block_costs(
+            &self,
+            meta: Self::Metadata,
+            limit: Option<usize>,
+        ) -> Result<Vec<RpcBlockCost>> {
+            debug!("get_recent_block_costs request received");
+
+            let limit = limit.unwrap_or(BLOCK_COSTS_LIMIT);
+
+            if limit > BLOCK_COSTS_LIMIT {
+                return Err(Error::invalid_params(format!(
+                    "Invalid limit; max {}",
+                    BLOCK_COSTS_LIMIT
+                )));
+            }
+
+            Ok(meta
+                .blockstore
+                .get_recent_block_costs(limit)
+                .map_err(|err| {
+                    warn!("get_recent_block_costs failed: {:?}", err);
+                    Error::invalid_request()
+                })?
+                .into_iter()
+                .map(|(slot, block_cost)| RpcBlockCost {
+                    slot,
+                    block_cost: block_cost.block_cost,
+                    block_cost_limit: block_cost.block_cost_limit,
+                    account_cost_limit: block_cost.account_cost_limit,
+                    transaction_count: block_cost.transaction_count,
+                    would_exceed_block_limit_count: block_cost.would_exceed_block_limit_count,
+                    would_exceed_account_limit_count: block_cost.would_exceed_account_limit_count,
+                    account_costs: block_cost
+                        .account_costs
+                        .into_iter()
+                        .map(|(pubkey, cost)| RpcAccountCost {
+                            pubkey: pubkey.to_string(),
+                            cost,
+                        })
+                        .collect(),
+                })
+                .collect())
+        }
+
         fn get_cluster_nodes(&self, meta: Self::Metadata) -> Result<Vec<RpcContactInfo>> {
             debug!("get_cluster_nodes rpc request received");
             let cluster_info = &meta.cluster_info;
@@ -2801,6 +2890,13 @@ pub mod rpc_full {
                             version,
                             feature_set,
                             shred_version: Some(my_shred_version),
+                            restart_intent: cluster_info.get_restart_intent(&contact_info.id).map(
+                                |intent| RpcRestartIntent {
+                                    start: intent.start,
+                                    expected_duration_secs: intent.expected_duration_secs,
+                                    target_version: intent.target_version,
+                                },
+                            ),
                         })
                     } else {
                         None // Exclude spy nodes
@@ -3916,7 +4012,7 @@ pub mod tests {
         solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes},
         solana_gossip::{contact_info::ContactInfo, socketaddr},
         solana_ledger::{
-            blockstore_meta::PerfSample,
+            blockstore_meta::{BlockCost, PerfSample},
             blockstore_processor::fill_blockstore_slot_with_ticks,
             genesis_utils::{create_genesis_config, GenesisConfigInfo},
         },
@@ -4098,6 +4194,19 @@ pub mod tests {
             .write_perf_sample(0, &sample1)
             .expect("write to blockstore");
 
+        let block_cost = BlockCost {
+            block_cost: 500,
+            block_cost_limit: 10_000,
+            account_cost_limit: 1_000,
+            transaction_count: 4,
+            would_exceed_block_limit_count: 0,
+            would_exceed_account_limit_count: 1,
+            account_costs: vec![(leader_pubkey, 500)],
+        };
+        blockstore
+            .write_block_cost(0, &block_cost)
+            .expect("write to blockstore");
+
         let max_slots = Arc::new(MaxSlots::default());
         max_slots.retransmit.store(42, Ordering::Relaxed);
         max_slots.shred_insert.store(43, Ordering::Relaxed);
@@ -4271,6 +4380,63 @@ pub mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_get_recent_block_costs() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler {
+            io,
+            meta,
+            leader_pubkey,
+            ..
+        } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getRecentBlockCosts"}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": [
+                {
+                    "slot": 0,
+                    "blockCost": 500,
+                    "blockCostLimit": 10_000,
+                    "accountCostLimit": 1_000,
+                    "transactionCount": 4,
+                    "wouldExceedBlockLimitCount": 0,
+                    "wouldExceedAccountLimitCount": 1,
+                    "accountCosts": [
+                        {
+                            "pubkey": leader_pubkey.to_string(),
+                            "cost": 500
+                        }
+                    ]
+                }
+            ],
+        });
+        let expected: Response =
+            serde_json::from_value(expected).expect("expected response deserialization");
+        assert_eq!(expected, result);
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getRecentBlockCosts","params":[1000]}"#;
+        let res = io.handle_request_sync(req, meta);
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "error": {
+                "code": -32602,
+                "message": "Invalid limit; max 150"
+            },
+            "id": 1
+        });
+        let expected: Response =
+            serde_json::from_value(expected).expect("expected response deserialization");
+        assert_eq!(expected, result);
+    }
+
     #[test]
     fn test_rpc_get_recent_performance_samples_invalid_limit() {
         let bob_pubkey = solana_sdk::pubkey::new_rand();
@@ -6183,6 +6349,52 @@ pub mod tests {
         assert_eq!(confirmed_block.rewards.unwrap(), vec![]);
     }
 
+    #[test]
+    fn test_get_block_transaction_history_retention() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler {
+            io,
+            mut meta,
+            block_commitment_cache,
+            ..
+        } = start_rpc_handler_with_tx_and_blockstore(&bob_pubkey, vec![0, 1, 3, 4, 8]);
+        block_commitment_cache
+            .write()
+            .unwrap()
+            .set_highest_confirmed_root(8);
+        let max_transaction_history_slots = Arc::new(AtomicU64::new(5));
+        meta.config.max_transaction_history_slots = Some(max_transaction_history_slots.clone());
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getBlock","params":[1]}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        assert_eq!(
+            res,
+            Some(
+                r#"{"jsonrpc":"2.0","error":{"code":-32001,"message":"Block 1 cleaned up, does not exist on node. First available block: 3"},"id":1}"#.to_string(),
+            )
+        );
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getBlock","params":[3]}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert!(result["result"].is_object());
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getFirstAvailableBlock"}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(result["result"], 3);
+
+        // Extending the retention brings the older block back
+        max_transaction_history_slots.store(8, Ordering::Relaxed);
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getBlock","params":[1]}"#;
+        let res = io.handle_request_sync(req, meta);
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert!(result["result"].is_object());
+    }
+
     #[test]
     fn test_get_block_production() {
         let bob_pubkey = solana_sdk::pubkey::new_rand();
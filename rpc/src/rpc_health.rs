@@ -4,7 +4,7 @@ use {
     std::{
         collections::HashSet,
         sync::atomic::{AtomicBool, Ordering},
-        sync::Arc,
+        sync::{Arc, RwLock},
     },
 };
 
@@ -17,7 +17,7 @@ pub enum RpcHealthStatus {
 
 pub struct RpcHealth {
     cluster_info: Arc<ClusterInfo>,
-    trusted_validators: Option<HashSet<Pubkey>>,
+    trusted_validators: Arc<RwLock<Option<HashSet<Pubkey>>>>,
     health_check_slot_distance: u64,
     override_health_check: Arc<AtomicBool>,
     #[cfg(test)]
@@ -27,7 +27,7 @@ pub struct RpcHealth {
 impl RpcHealth {
     pub fn new(
         cluster_info: Arc<ClusterInfo>,
-        trusted_validators: Option<HashSet<Pubkey>>,
+        trusted_validators: Arc<RwLock<Option<HashSet<Pubkey>>>>,
         health_check_slot_distance: u64,
         override_health_check: Arc<AtomicBool>,
     ) -> Self {
@@ -51,7 +51,7 @@ impl RpcHealth {
 
         if self.override_health_check.load(Ordering::Relaxed) {
             RpcHealthStatus::Ok
-        } else if let Some(trusted_validators) = &self.trusted_validators {
+        } else if let Some(trusted_validators) = &*self.trusted_validators.read().unwrap() {
             match (
                 self.cluster_info
                     .get_accounts_hash_for_node(&self.cluster_info.id(), |hashes| {
@@ -119,7 +119,7 @@ impl RpcHealth {
     pub(crate) fn stub() -> Arc<Self> {
         Arc::new(Self::new(
             Arc::new(ClusterInfo::default()),
-            None,
+            Arc::default(),
             42,
             Arc::new(AtomicBool::new(false)),
         ))
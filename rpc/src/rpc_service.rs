@@ -281,7 +281,7 @@ impl JsonRpcService {
         genesis_hash: Hash,
         ledger_path: &Path,
         validator_exit: Arc<RwLock<Exit>>,
-        trusted_validators: Option<HashSet<Pubkey>>,
+        trusted_validators: Arc<RwLock<Option<HashSet<Pubkey>>>>,
         override_health_check: Arc<AtomicBool>,
         optimistically_confirmed_bank: Arc<RwLock<OptimisticallyConfirmedBank>>,
         send_transaction_retry_ms: u64,
@@ -541,7 +541,7 @@ mod tests {
             Hash::default(),
             &PathBuf::from("farf"),
             validator_exit,
-            None,
+            Arc::default(),
             Arc::new(AtomicBool::new(false)),
             optimistically_confirmed_bank,
             1000,
@@ -720,9 +720,13 @@ mod tests {
             solana_sdk::pubkey::new_rand(),
         ];
 
+        let live_trusted_validators = Arc::new(RwLock::new(Some(
+            trusted_validators.clone().into_iter().collect(),
+        )));
+
         let health = Arc::new(RpcHealth::new(
             cluster_info.clone(),
-            Some(trusted_validators.clone().into_iter().collect()),
+            live_trusted_validators.clone(),
             health_check_slot_distance,
             override_health_check.clone(),
         ));
@@ -793,5 +797,14 @@ mod tests {
             )
             .unwrap();
         assert_eq!(rm.health_check(), "behind");
+
+        // No longer tracking the trusted validator that is far ahead
+        live_trusted_validators
+            .write()
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .remove(&trusted_validators[2]);
+        assert_eq!(rm.health_check(), "ok");
     }
 }
@@ -5126,6 +5126,59 @@ impl AccountsDb {
         ret
     }
 
+    /// Returns the latest version of every account stored in `slot` along with its hash, which
+    /// are the inputs to the slot's accounts delta hash
+    pub fn get_pubkey_hash_account_for_slot(
+        &self,
+        slot: Slot,
+    ) -> Vec<(Pubkey, Hash, AccountSharedData)> {
+        type ScanResult = ScanStorageResult<
+            (Pubkey, Hash, AccountSharedData),
+            DashMap<Pubkey, (u64, Hash, AccountSharedData)>,
+        >;
+        let scan_result: ScanResult = self.scan_account_storage(
+            slot,
+            |loaded_account: LoadedAccount| {
+                // Cache only has one version per key, don't need to worry about versioning
+                Some((
+                    *loaded_account.pubkey(),
+                    loaded_account.loaded_hash(),
+                    loaded_account.take_account(),
+                ))
+            },
+            |accum: &DashMap<Pubkey, (u64, Hash, AccountSharedData)>,
+             loaded_account: LoadedAccount| {
+                let loaded_write_version = loaded_account.write_version();
+                match accum.entry(*loaded_account.pubkey()) {
+                    Occupied(mut occupied_entry) => {
+                        if loaded_write_version > occupied_entry.get().0 {
+                            occupied_entry.insert((
+                                loaded_write_version,
+                                loaded_account.loaded_hash(),
+                                loaded_account.take_account(),
+                            ));
+                        }
+                    }
+                    Vacant(vacant_entry) => {
+                        vacant_entry.insert((
+                            loaded_write_version,
+                            loaded_account.loaded_hash(),
+                            loaded_account.take_account(),
+                        ));
+                    }
+                }
+            },
+        );
+
+        match scan_result {
+            ScanStorageResult::Cached(cached_result) => cached_result,
+            ScanStorageResult::Stored(stored_result) => stored_result
+                .into_iter()
+                .map(|(pubkey, (_latest_write_version, hash, account))| (pubkey, hash, account))
+                .collect(),
+        }
+    }
+
     fn update_index(
         &self,
         slot: Slot,
@@ -8753,6 +8806,41 @@ pub mod tests {
         assert_eq!(bank_hash.stats.num_executable_accounts, 1);
     }
 
+    #[test]
+    fn test_get_pubkey_hash_account_for_slot() {
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+
+        let key = solana_sdk::pubkey::new_rand();
+        let other_key = solana_sdk::pubkey::new_rand();
+        let some_slot: Slot = 0;
+        let account = AccountSharedData::new(1, 0, &key);
+        let mut updated_account = account.clone();
+        updated_account.checked_add_lamports(1).unwrap();
+
+        db.store_uncached(some_slot, &[(&key, &account), (&other_key, &account)]);
+        db.store_uncached(some_slot, &[(&key, &updated_account)]);
+
+        let mut accounts = db.get_pubkey_hash_account_for_slot(some_slot);
+        accounts.sort_by_key(|(pubkey, _hash, _account)| *pubkey);
+        let mut expected = vec![
+            (
+                key,
+                AccountsDb::hash_account(some_slot, &updated_account, &key),
+                updated_account,
+            ),
+            (
+                other_key,
+                AccountsDb::hash_account(some_slot, &account, &other_key),
+                account,
+            ),
+        ];
+        expected.sort_by_key(|(pubkey, _hash, _account)| *pubkey);
+        assert_eq!(accounts, expected);
+        assert!(db
+            .get_pubkey_hash_account_for_slot(some_slot + 1)
+            .is_empty());
+    }
+
     #[test]
     fn test_calculate_accounts_hash_check_hash_mismatch() {
         solana_logger::setup();
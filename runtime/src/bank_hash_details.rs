@@ -0,0 +1,297 @@
+//! The `bank_hash_details` module captures the inputs to a bank's hash, including every account
+//! stored in the bank's slot, so that bank hash mismatches between nodes can be diagnosed offline
+
+use crate::bank::Bank;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    account::{AccountSharedData, ReadableAccount},
+    clock::{Epoch, Slot},
+    hash::Hash,
+    pubkey::Pubkey,
+};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BankHashAccount {
+    pub pubkey: String,
+    pub hash: String,
+    pub lamports: u64,
+    pub owner: String,
+    pub executable: bool,
+    pub rent_epoch: Epoch,
+    pub data: String,
+}
+
+impl BankHashAccount {
+    fn new(pubkey: &Pubkey, hash: &Hash, account: &AccountSharedData) -> Self {
+        Self {
+            pubkey: pubkey.to_string(),
+            hash: hash.to_string(),
+            lamports: account.lamports(),
+            owner: account.owner().to_string(),
+            executable: account.executable(),
+            rent_epoch: account.rent_epoch(),
+            data: base64::encode(account.data()),
+        }
+    }
+
+    /// Names of the fields that differ from `other`, the account hash is only reported if the
+    /// account contents agree
+    pub fn mismatched_fields(&self, other: &Self) -> Vec<&'static str> {
+        let mut fields = vec![];
+        if self.lamports != other.lamports {
+            fields.push("lamports");
+        }
+        if self.owner != other.owner {
+            fields.push("owner");
+        }
+        if self.executable != other.executable {
+            fields.push("executable");
+        }
+        if self.rent_epoch != other.rent_epoch {
+            fields.push("rentEpoch");
+        }
+        if self.data != other.data {
+            fields.push("data");
+        }
+        if fields.is_empty() && self.hash != other.hash {
+            fields.push("hash");
+        }
+        fields
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BankHashDetails {
+    pub slot: Slot,
+    pub bank_hash: String,
+    pub parent_bank_hash: String,
+    pub accounts_delta_hash: String,
+    pub signature_count: u64,
+    pub last_blockhash: String,
+    /// Accounts stored in this slot, sorted by pubkey
+    pub accounts: Vec<BankHashAccount>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BankHashAccountDiff {
+    pub pubkey: String,
+    pub expected: Option<BankHashAccount>,
+    pub actual: Option<BankHashAccount>,
+    pub mismatched_fields: Vec<&'static str>,
+}
+
+impl BankHashDetails {
+    /// Collects the hash inputs of a frozen bank
+    pub fn new(bank: &Bank) -> Self {
+        let slot = bank.slot();
+        let mut accounts: Vec<_> = bank
+            .rc
+            .accounts
+            .accounts_db
+            .get_pubkey_hash_account_for_slot(slot)
+            .iter()
+            .map(|(pubkey, hash, account)| BankHashAccount::new(pubkey, hash, account))
+            .collect();
+        accounts.sort_by(|a, b| a.pubkey.cmp(&b.pubkey));
+
+        Self {
+            slot,
+            bank_hash: bank.hash().to_string(),
+            parent_bank_hash: bank.parent_hash().to_string(),
+            accounts_delta_hash: bank.rc.accounts.bank_hash_info_at(slot).hash.to_string(),
+            signature_count: bank.signature_count(),
+            last_blockhash: bank.last_blockhash().to_string(),
+            accounts,
+        }
+    }
+
+    /// Name of the file holding the details of `slot` frozen with `bank_hash`
+    pub fn filename(slot: Slot, bank_hash: &str) -> String {
+        format!("{}-{}.json", slot, bank_hash)
+    }
+
+    /// Writes the details as JSON into `dir`, returning the path of the file. An existing file
+    /// for the same slot and bank hash is left untouched.
+    pub fn write_to_dir(&self, dir: &Path) -> io::Result<PathBuf> {
+        let path = dir.join(Self::filename(self.slot, &self.bank_hash));
+        if !path.exists() {
+            fs::create_dir_all(dir)?;
+            let file = BufWriter::new(File::create(&path)?);
+            serde_json::to_writer_pretty(file, self)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        }
+        Ok(path)
+    }
+
+    pub fn read_from_file(path: &Path) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        serde_json::from_reader(file).map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+
+    /// Returns the hash inputs of `self` that differ from `expected`, by name
+    pub fn mismatched_fields(&self, expected: &Self) -> Vec<&'static str> {
+        let mut fields = vec![];
+        if self.slot != expected.slot {
+            fields.push("slot");
+        }
+        if self.bank_hash != expected.bank_hash {
+            fields.push("bankHash");
+        }
+        if self.parent_bank_hash != expected.parent_bank_hash {
+            fields.push("parentBankHash");
+        }
+        if self.accounts_delta_hash != expected.accounts_delta_hash {
+            fields.push("accountsDeltaHash");
+        }
+        if self.signature_count != expected.signature_count {
+            fields.push("signatureCount");
+        }
+        if self.last_blockhash != expected.last_blockhash {
+            fields.push("lastBlockhash");
+        }
+        fields
+    }
+
+    /// Returns every account that is missing from either side or whose contents differ
+    pub fn diff_accounts(&self, expected: &Self) -> Vec<BankHashAccountDiff> {
+        let mut accounts: BTreeMap<&str, (Option<&BankHashAccount>, Option<&BankHashAccount>)> =
+            BTreeMap::new();
+        for account in &expected.accounts {
+            accounts.entry(&account.pubkey).or_default().0 = Some(account);
+        }
+        for account in &self.accounts {
+            accounts.entry(&account.pubkey).or_default().1 = Some(account);
+        }
+
+        accounts
+            .into_iter()
+            .filter_map(|(pubkey, (expected, actual))| {
+                let mismatched_fields = match (expected, actual) {
+                    (Some(expected), Some(actual)) => {
+                        let fields = actual.mismatched_fields(expected);
+                        if fields.is_empty() {
+                            return None;
+                        }
+                        fields
+                    }
+                    _ => vec![],
+                };
+                Some(BankHashAccountDiff {
+                    pubkey: pubkey.to_string(),
+                    expected: expected.cloned(),
+                    actual: actual.cloned(),
+                    mismatched_fields,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesis_utils::create_genesis_config;
+    use solana_sdk::system_transaction;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_bank_hash_details() {
+        let genesis_config_info = create_genesis_config(10_000);
+        let bank0 = Arc::new(Bank::new(&genesis_config_info.genesis_config));
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), 1);
+        let to = Pubkey::new_unique();
+        bank1
+            .process_transaction(&system_transaction::transfer(
+                &genesis_config_info.mint_keypair,
+                &to,
+                100,
+                bank0.last_blockhash(),
+            ))
+            .unwrap();
+        bank1.freeze();
+
+        let details = BankHashDetails::new(&bank1);
+        assert_eq!(details.slot, 1);
+        assert_eq!(details.bank_hash, bank1.hash().to_string());
+        assert_eq!(details.parent_bank_hash, bank0.hash().to_string());
+        assert_eq!(details.signature_count, 1);
+        let transferred = details
+            .accounts
+            .iter()
+            .find(|account| account.pubkey == to.to_string())
+            .unwrap();
+        assert_eq!(transferred.lamports, 100);
+        assert!(details.mismatched_fields(&details).is_empty());
+        assert!(details.diff_accounts(&details).is_empty());
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = details.write_to_dir(dir.path()).unwrap();
+        assert_eq!(BankHashDetails::read_from_file(&path).unwrap(), details);
+    }
+
+    #[test]
+    fn test_diff_accounts() {
+        let account = |pubkey: &str, lamports| BankHashAccount {
+            pubkey: pubkey.to_string(),
+            hash: Hash::default().to_string(),
+            lamports,
+            owner: Pubkey::default().to_string(),
+            executable: false,
+            rent_epoch: 0,
+            data: String::new(),
+        };
+        let expected = BankHashDetails {
+            slot: 1,
+            bank_hash: Hash::default().to_string(),
+            parent_bank_hash: Hash::default().to_string(),
+            accounts_delta_hash: Hash::default().to_string(),
+            signature_count: 1,
+            last_blockhash: Hash::default().to_string(),
+            accounts: vec![account("a", 1), account("b", 2), account("c", 3)],
+        };
+        let mut actual = expected.clone();
+        actual.bank_hash = Hash::new_unique().to_string();
+        actual.accounts = vec![account("a", 1), account("b", 5), account("d", 4)];
+        actual.accounts[0].hash = Hash::new_unique().to_string();
+
+        assert_eq!(actual.mismatched_fields(&expected), vec!["bankHash"]);
+        let diffs = actual.diff_accounts(&expected);
+        assert_eq!(
+            diffs,
+            vec![
+                BankHashAccountDiff {
+                    pubkey: "a".to_string(),
+                    expected: Some(expected.accounts[0].clone()),
+                    actual: Some(actual.accounts[0].clone()),
+                    mismatched_fields: vec!["hash"],
+                },
+                BankHashAccountDiff {
+                    pubkey: "b".to_string(),
+                    expected: Some(account("b", 2)),
+                    actual: Some(account("b", 5)),
+                    mismatched_fields: vec!["lamports"],
+                },
+                BankHashAccountDiff {
+                    pubkey: "c".to_string(),
+                    expected: Some(account("c", 3)),
+                    actual: None,
+                    mismatched_fields: vec![],
+                },
+                BankHashAccountDiff {
+                    pubkey: "d".to_string(),
+                    expected: None,
+                    actual: Some(account("d", 4)),
+                    mismatched_fields: vec![],
+                },
+            ]
+        );
+    }
+}
@@ -11,6 +11,7 @@ pub mod append_vec;
 pub mod bank;
 pub mod bank_client;
 pub mod bank_forks;
+pub mod bank_hash_details;
 pub mod bank_utils;
 pub mod blockhash_queue;
 pub mod bloom;
@@ -19,6 +19,10 @@ iface="$(ip link show | grep mtu | grep -iv loopback | grep "state UP" | awk 'BE
 
 if [[ "$1" = cleanup ]]; then
   $sudo ~solana/.cargo/bin/solana-net-shaper cleanup -f "$2" -s "$3" -p "$4" -i "$iface"
+elif [[ "$1" = run ]]; then
+  # Scenarios run for a while, so let them play out in the background
+  $sudo nohup ~solana/.cargo/bin/solana-net-shaper run -f "$2" -s "$3" -p "$4" -i "$iface" \
+    > ~solana/net-shaper.log 2>&1 &
 else
   $sudo ~solana/.cargo/bin/solana-net-shaper shape -f "$2" -s "$3" -p "$4" -i "$iface"
 fi
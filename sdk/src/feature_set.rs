@@ -167,6 +167,14 @@ pub mod rent_for_sysvars {
     solana_sdk::declare_id!("BKCPBQQBZqggVnFso5nQ8rQ4RwwogYwjuUt9biBjxwNF");
 }
 
+pub mod merkle_shreds {
+    solana_sdk::declare_id!("MrKLShRdZYqJT7QU7pvTQ8dCxJrRZkW5cT5Ksa1qvCN");
+}
+
+pub mod allow_votes_to_directly_update_vote_state {
+    solana_sdk::declare_id!("Ff8b1fBeB86q8cjq47ZhsQLgv5EkHu3G1C99zjUfAzrq");
+}
+
 lazy_static! {
     /// Map of feature identifiers to user-visible description
     pub static ref FEATURE_NAMES: HashMap<Pubkey, &'static str> = [
@@ -208,6 +216,8 @@ lazy_static! {
         (updated_verify_policy::id(), "Update verify policy"),
         (neon_evm_compute_budget::id(), "bump neon_evm's compute budget"),
         (rent_for_sysvars::id(), "collect rent from accounts owned by sysvars"),
+        (merkle_shreds::id(), "sign shreds by the merkle root of their erasure batch"),
+        (allow_votes_to_directly_update_vote_state::id(), "enable direct vote state update"),
         /*************** ADD NEW FEATURES HERE ***************/
     ]
     .iter()
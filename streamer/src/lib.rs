@@ -1,9 +1,13 @@
 #![allow(clippy::integer_arithmetic)]
 pub mod packet;
+pub mod packet_shaper;
 pub mod recvmmsg;
 pub mod sendmmsg;
 pub mod streamer;
 
+#[macro_use]
+extern crate lazy_static;
+
 #[macro_use]
 extern crate log;
 
@@ -0,0 +1,186 @@
+//! The `packet_shaper` module lets a process wide `PacketShaper` drop or delay
+//! the packets received by the streamer, to simulate network conditions
+//! between in-process nodes without root privileges.
+
+use crate::packet::{Packet, Packets};
+use crate::streamer::PacketSender;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, Builder};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PacketFate {
+    Deliver,
+    Drop,
+    Delay(Duration),
+}
+
+pub trait PacketShaper: Send + Sync {
+    /// Decides the fate of a packet sent from `from` and received by the
+    /// socket bound to `to`
+    fn shape(&self, from: &SocketAddr, to: &SocketAddr) -> PacketFate;
+}
+
+lazy_static! {
+    static ref PACKET_SHAPER: RwLock<Option<Arc<dyn PacketShaper>>> = RwLock::new(None);
+}
+
+// Lets the receivers skip the lock when no shaper is installed, which is the
+// case outside of the simulations
+static PACKET_SHAPER_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the shaper applied by all the streamer receivers of the process,
+/// or removes it if `None`
+pub fn set_packet_shaper(shaper: Option<Arc<dyn PacketShaper>>) {
+    let mut packet_shaper = PACKET_SHAPER.write().unwrap();
+    PACKET_SHAPER_ENABLED.store(shaper.is_some(), Ordering::Relaxed);
+    *packet_shaper = shaper;
+}
+
+pub(crate) fn packet_shaper() -> Option<Arc<dyn PacketShaper>> {
+    if !PACKET_SHAPER_ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    PACKET_SHAPER.read().unwrap().clone()
+}
+
+/// Removes the dropped and delayed packets from `msgs`, and returns the
+/// delayed packets batched by delay
+pub(crate) fn shape_packets(
+    shaper: &dyn PacketShaper,
+    msgs: &mut Packets,
+    to: &SocketAddr,
+) -> Vec<(Duration, Packets)> {
+    let mut num_delivered = 0;
+    let mut delayed: BTreeMap<Duration, Vec<Packet>> = BTreeMap::new();
+    for index in 0..msgs.packets.len() {
+        match shaper.shape(&msgs.packets[index].meta.addr(), to) {
+            PacketFate::Deliver => {
+                msgs.packets[..].swap(num_delivered, index);
+                num_delivered += 1;
+            }
+            PacketFate::Drop => (),
+            PacketFate::Delay(delay) => delayed
+                .entry(delay)
+                .or_default()
+                .push(std::mem::take(&mut msgs.packets[index])),
+        }
+    }
+    msgs.packets.truncate(num_delivered);
+    delayed
+        .into_iter()
+        .map(|(delay, packets)| (delay, Packets::new(packets)))
+        .collect()
+}
+
+/// Forwards the delayed packets to the receiver channel once their delay has
+/// elapsed
+pub(crate) struct DelayLine {
+    sender: Sender<(Instant, Packets)>,
+}
+
+impl DelayLine {
+    pub(crate) fn new(packet_sender: PacketSender, exit: Arc<AtomicBool>) -> Self {
+        let (sender, receiver) = channel::<(Instant, Packets)>();
+        Builder::new()
+            .name("solana-delay-line".to_string())
+            .spawn(move || {
+                let mut pending: Vec<(Instant, Packets)> = vec![];
+                loop {
+                    if exit.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let now = Instant::now();
+                    let timeout = pending
+                        .iter()
+                        .map(|(release, _)| release.saturating_duration_since(now))
+                        .min()
+                        .unwrap_or_else(|| Duration::from_secs(1))
+                        .min(Duration::from_secs(1));
+                    match receiver.recv_timeout(timeout) {
+                        Ok(delayed) => pending.push(delayed),
+                        Err(RecvTimeoutError::Timeout) => (),
+                        Err(RecvTimeoutError::Disconnected) if pending.is_empty() => return,
+                        Err(RecvTimeoutError::Disconnected) => thread::sleep(timeout),
+                    }
+                    let now = Instant::now();
+                    let (mut due, not_due): (Vec<_>, Vec<_>) = pending
+                        .into_iter()
+                        .partition(|(release, _)| *release <= now);
+                    pending = not_due;
+                    due.sort_by_key(|(release, _)| *release);
+                    for (_, packets) in due {
+                        if packet_sender.send(packets).is_err() {
+                            return;
+                        }
+                    }
+                }
+            })
+            .unwrap();
+        Self { sender }
+    }
+
+    pub(crate) fn send(&self, delay: Duration, packets: Packets) {
+        let _ = self.sender.send((Instant::now() + delay, packets));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::packet::PACKET_DATA_SIZE;
+
+    struct PortShaper;
+
+    impl PacketShaper for PortShaper {
+        fn shape(&self, from: &SocketAddr, _to: &SocketAddr) -> PacketFate {
+            match from.port() {
+                1 => PacketFate::Drop,
+                2 => PacketFate::Delay(Duration::from_millis(10)),
+                _ => PacketFate::Deliver,
+            }
+        }
+    }
+
+    #[test]
+    fn test_shape_packets() {
+        let to: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let mut msgs = Packets::default();
+        for port in &[1, 2, 3, 2] {
+            let mut packet = Packet::default();
+            packet.meta.size = PACKET_DATA_SIZE;
+            let from = SocketAddr::from(([127, 0, 0, 1], *port));
+            packet.meta.set_addr(&from);
+            msgs.packets.push(packet);
+        }
+        let delayed = shape_packets(&PortShaper, &mut msgs, &to);
+        assert_eq!(msgs.packets.len(), 1);
+        assert_eq!(msgs.packets[0].meta.addr().port(), 3);
+        assert_eq!(delayed.len(), 1);
+        assert_eq!(delayed[0].0, Duration::from_millis(10));
+        assert_eq!(delayed[0].1.packets.len(), 2);
+    }
+
+    #[test]
+    fn test_delay_line() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = channel();
+        let delay_line = DelayLine::new(sender, exit.clone());
+        let start = Instant::now();
+        delay_line.send(
+            Duration::from_millis(200),
+            Packets::new(vec![Packet::default()]),
+        );
+        delay_line.send(Duration::from_millis(100), Packets::default());
+        let packets = receiver.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert!(packets.packets.is_empty());
+        let packets = receiver.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(packets.packets.len(), 1);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        exit.store(true, Ordering::Relaxed);
+    }
+}
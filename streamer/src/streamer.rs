@@ -2,6 +2,7 @@
 //!
 
 use crate::packet::{self, send_to, Packets, PacketsRecycler, PACKETS_PER_BATCH};
+use crate::packet_shaper::{self, DelayLine};
 use crate::recvmmsg::NUM_RCVMMSGS;
 use solana_sdk::timing::{duration_as_ms, timestamp};
 use std::net::UdpSocket;
@@ -42,6 +43,8 @@ fn recv_loop(
     let mut call_count = 0;
     let mut now = Instant::now();
     let mut num_max_received = 0; // Number of times maximum packets were received
+    let local_addr = sock.local_addr()?;
+    let mut delay_line: Option<DelayLine> = None;
     loop {
         let mut msgs = if use_pinned_memory {
             Packets::new_with_recycler(recycler.clone(), PACKETS_PER_BATCH, name)
@@ -60,7 +63,16 @@ fn recv_loop(
                 }
                 recv_count += len;
                 call_count += 1;
-                if len > 0 {
+                if let Some(shaper) = packet_shaper::packet_shaper() {
+                    for (delay, packets) in
+                        packet_shaper::shape_packets(&*shaper, &mut msgs, &local_addr)
+                    {
+                        delay_line
+                            .get_or_insert_with(|| DelayLine::new(channel.clone(), exit.clone()))
+                            .send(delay, packets);
+                    }
+                }
+                if !msgs.packets.is_empty() {
                     channel.send(msgs)?;
                 }
                 break;
@@ -121,6 +121,59 @@ pub fn parse_vote(
                 }),
             })
         }
+        VoteInstruction::UpdateVoteState(vote_state_update) => {
+            check_num_vote_accounts(&instruction.accounts, 4)?;
+            let vote_state_update = json!({
+                "lockouts": vote_state_update
+                    .lockouts
+                    .iter()
+                    .map(|lockout| json!({
+                        "slot": lockout.slot,
+                        "confirmationCount": lockout.confirmation_count,
+                    }))
+                    .collect::<Vec<_>>(),
+                "root": vote_state_update.root,
+                "hash": vote_state_update.hash.to_string(),
+                "timestamp": vote_state_update.timestamp,
+            });
+            Ok(ParsedInstructionEnum {
+                instruction_type: "updateVoteState".to_string(),
+                info: json!({
+                    "voteAccount": account_keys[instruction.accounts[0] as usize].to_string(),
+                    "slotHashesSysvar": account_keys[instruction.accounts[1] as usize].to_string(),
+                    "clockSysvar": account_keys[instruction.accounts[2] as usize].to_string(),
+                    "voteAuthority": account_keys[instruction.accounts[3] as usize].to_string(),
+                    "voteStateUpdate": vote_state_update,
+                }),
+            })
+        }
+        VoteInstruction::UpdateVoteStateSwitch(vote_state_update, hash) => {
+            check_num_vote_accounts(&instruction.accounts, 4)?;
+            let vote_state_update = json!({
+                "lockouts": vote_state_update
+                    .lockouts
+                    .iter()
+                    .map(|lockout| json!({
+                        "slot": lockout.slot,
+                        "confirmationCount": lockout.confirmation_count,
+                    }))
+                    .collect::<Vec<_>>(),
+                "root": vote_state_update.root,
+                "hash": vote_state_update.hash.to_string(),
+                "timestamp": vote_state_update.timestamp,
+            });
+            Ok(ParsedInstructionEnum {
+                instruction_type: "updateVoteStateSwitch".to_string(),
+                info: json!({
+                    "voteAccount": account_keys[instruction.accounts[0] as usize].to_string(),
+                    "slotHashesSysvar": account_keys[instruction.accounts[1] as usize].to_string(),
+                    "clockSysvar": account_keys[instruction.accounts[2] as usize].to_string(),
+                    "voteAuthority": account_keys[instruction.accounts[3] as usize].to_string(),
+                    "voteStateUpdate": vote_state_update,
+                    "hash": hash.to_string(),
+                }),
+            })
+        }
         VoteInstruction::AuthorizeChecked(authority_type) => {
             check_num_vote_accounts(&instruction.accounts, 4)?;
             Ok(ParsedInstructionEnum {
@@ -147,8 +200,9 @@ mod test {
     use solana_sdk::{hash::Hash, message::Message, pubkey::Pubkey};
     use solana_vote_program::{
         vote_instruction,
-        vote_state::{Vote, VoteAuthorize, VoteInit},
+        vote_state::{Lockout, Vote, VoteAuthorize, VoteInit, VoteStateUpdate},
     };
+    use std::collections::VecDeque;
 
     #[test]
     #[allow(clippy::same_item_push)]
@@ -308,6 +362,77 @@ mod test {
         );
         assert!(parse_vote(&message.instructions[0], &keys[0..3]).is_err());
 
+        let vote_state_update = VoteStateUpdate {
+            lockouts: VecDeque::from(vec![
+                Lockout {
+                    slot: 2,
+                    confirmation_count: 2,
+                },
+                Lockout {
+                    slot: 4,
+                    confirmation_count: 1,
+                },
+            ]),
+            root: Some(1),
+            hash,
+            timestamp: Some(1_234_567_890),
+        };
+        let expected_vote_state_update = json!({
+            "lockouts": [
+                {
+                    "slot": 2,
+                    "confirmationCount": 2,
+                },
+                {
+                    "slot": 4,
+                    "confirmationCount": 1,
+                },
+            ],
+            "root": 1,
+            "hash": hash.to_string(),
+            "timestamp": 1_234_567_890,
+        });
+        let instruction =
+            vote_instruction::update_vote_state(&keys[1], &keys[0], vote_state_update.clone());
+        let message = Message::new(&[instruction], None);
+        assert_eq!(
+            parse_vote(&message.instructions[0], &keys[0..4]).unwrap(),
+            ParsedInstructionEnum {
+                instruction_type: "updateVoteState".to_string(),
+                info: json!({
+                    "voteAccount": keys[1].to_string(),
+                    "slotHashesSysvar": keys[2].to_string(),
+                    "clockSysvar": keys[3].to_string(),
+                    "voteAuthority": keys[0].to_string(),
+                    "voteStateUpdate": expected_vote_state_update,
+                }),
+            }
+        );
+        assert!(parse_vote(&message.instructions[0], &keys[0..3]).is_err());
+
+        let instruction = vote_instruction::update_vote_state_switch(
+            &keys[1],
+            &keys[0],
+            vote_state_update,
+            proof_hash,
+        );
+        let message = Message::new(&[instruction], None);
+        assert_eq!(
+            parse_vote(&message.instructions[0], &keys[0..4]).unwrap(),
+            ParsedInstructionEnum {
+                instruction_type: "updateVoteStateSwitch".to_string(),
+                info: json!({
+                    "voteAccount": keys[1].to_string(),
+                    "slotHashesSysvar": keys[2].to_string(),
+                    "clockSysvar": keys[3].to_string(),
+                    "voteAuthority": keys[0].to_string(),
+                    "voteStateUpdate": expected_vote_state_update,
+                    "hash": proof_hash.to_string(),
+                }),
+            }
+        );
+        assert!(parse_vote(&message.instructions[0], &keys[0..3]).is_err());
+
         let authority_type = VoteAuthorize::Voter;
         let instruction =
             vote_instruction::authorize_checked(&keys[1], &keys[0], &keys[3], authority_type);
@@ -5,16 +5,34 @@ use {
     jsonrpc_ipc_server::{RequestContext, ServerBuilder},
     jsonrpc_server_utils::tokio,
     log::*,
-    solana_core::validator::ValidatorStartProgress,
-    solana_gossip::cluster_info::ClusterInfo,
+    serde_derive::{Deserialize, Serialize},
+    solana_core::{
+        cluster_nodes::RetransmitTreeNode,
+        ledger_cleanup_service::DEFAULT_MIN_MAX_LEDGER_SHREDS,
+        outstanding_requests::PeerRequestStats,
+        repair_service::{repair_peer_reputation, OutstandingRepairs},
+        retransmit_stage::{RetransmitPeerStats, RetransmitTreeInfo, RetransmitTreeInspector},
+        validator::{is_snapshot_config_invalid, LiveValidatorConfig, ValidatorStartProgress},
+    },
+    solana_gossip::{
+        cluster_info::ClusterInfo,
+        crds_value::{
+            MAX_RESTART_INTENT_DURATION_SECS, MAX_RESTART_INTENT_VERSION_LEN, MAX_WALLCLOCK,
+        },
+    },
     solana_sdk::{
+        clock::Slot,
         exit::Exit,
+        pubkey::Pubkey,
         signature::{read_keypair_file, Keypair, Signer},
     },
     std::{
+        collections::HashSet,
+        fmt::{self, Display},
         net::SocketAddr,
         path::Path,
-        sync::{Arc, RwLock},
+        str::FromStr,
+        sync::{atomic::Ordering, Arc, RwLock},
         thread::{self, Builder},
         time::{Duration, SystemTime},
     },
@@ -28,9 +46,385 @@ pub struct AdminRpcRequestMetadata {
     pub validator_exit: Arc<RwLock<Exit>>,
     pub authorized_voter_keypairs: Arc<RwLock<Vec<Arc<Keypair>>>>,
     pub cluster_info: Arc<RwLock<Option<Arc<ClusterInfo>>>>,
+    pub live_config: Arc<RwLock<Option<LiveValidatorConfig>>>,
+    pub retransmit_tree_inspector: Arc<RwLock<Option<RetransmitTreeInspector>>>,
+    pub outstanding_repairs: Arc<RwLock<Option<Arc<RwLock<OutstandingRepairs>>>>>,
 }
 impl Metadata for AdminRpcRequestMetadata {}
 
+impl AdminRpcRequestMetadata {
+    fn with_live_config<F, R>(&self, func: F) -> Result<R>
+    where
+        F: FnOnce(&LiveValidatorConfig) -> Result<R>,
+    {
+        if let Some(live_config) = self.live_config.read().unwrap().as_ref() {
+            func(live_config)
+        } else {
+            Err(jsonrpc_core::error::Error::invalid_params(
+                "Retry once validator start up is complete",
+            ))
+        }
+    }
+}
+
+/// The validator settings that may be changed without a restart
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminRpcLiveConfig {
+    pub trusted_validators: Option<Vec<String>>, // None = trust all
+    pub repair_validators: Option<Vec<String>>,  // None = repair from all
+    pub snapshot_interval_slots: Option<u64>,    // None = snapshots disabled
+    pub maximum_snapshots_to_retain: Option<usize>,
+    pub max_ledger_shreds: Option<u64>, // None = ledger cleanup disabled
+    pub max_transaction_history_slots: Option<u64>, // None = retain all history
+}
+
+impl From<&LiveValidatorConfig> for AdminRpcLiveConfig {
+    fn from(live_config: &LiveValidatorConfig) -> Self {
+        fn to_strings(validators: &Option<HashSet<Pubkey>>) -> Option<Vec<String>> {
+            validators.as_ref().map(|validators| {
+                let mut validators: Vec<_> = validators.iter().map(|id| id.to_string()).collect();
+                validators.sort();
+                validators
+            })
+        }
+        Self {
+            trusted_validators: to_strings(&live_config.trusted_validators.read().unwrap()),
+            repair_validators: to_strings(&live_config.repair_validators.read().unwrap()),
+            snapshot_interval_slots: live_config
+                .snapshot_interval_slots
+                .as_ref()
+                .map(|slots| slots.load(Ordering::Relaxed)),
+            maximum_snapshots_to_retain: live_config
+                .maximum_snapshots_to_retain
+                .as_ref()
+                .map(|count| count.load(Ordering::Relaxed)),
+            max_ledger_shreds: live_config
+                .max_ledger_shreds
+                .as_ref()
+                .map(|shreds| shreds.load(Ordering::Relaxed)),
+            max_transaction_history_slots: live_config
+                .max_transaction_history_slots
+                .as_ref()
+                .map(|slots| slots.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Display for AdminRpcLiveConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn write_validators(
+            f: &mut fmt::Formatter,
+            name: &str,
+            validators: &Option<Vec<String>>,
+            unset: &str,
+        ) -> fmt::Result {
+            match validators {
+                None => writeln!(f, "{}: {}", name, unset),
+                Some(validators) => {
+                    writeln!(f, "{}: {}", name, validators.len())?;
+                    for validator in validators {
+                        writeln!(f, "  {}", validator)?;
+                    }
+                    Ok(())
+                }
+            }
+        }
+        fn write_value<T: Display>(
+            f: &mut fmt::Formatter,
+            name: &str,
+            value: &Option<T>,
+        ) -> fmt::Result {
+            match value {
+                None => writeln!(f, "{}: disabled", name),
+                Some(value) => writeln!(f, "{}: {}", name, value),
+            }
+        }
+        write_validators(f, "Trusted validators", &self.trusted_validators, "all")?;
+        write_validators(f, "Repair validators", &self.repair_validators, "all")?;
+        write_value(f, "Snapshot interval slots", &self.snapshot_interval_slots)?;
+        write_value(
+            f,
+            "Maximum snapshots to retain",
+            &self.maximum_snapshots_to_retain,
+        )?;
+        write_value(f, "Max ledger shreds", &self.max_ledger_shreds)?;
+        match self.max_transaction_history_slots {
+            None => writeln!(f, "Max transaction history slots: all"),
+            Some(slots) => writeln!(f, "Max transaction history slots: {}", slots),
+        }
+    }
+}
+
+/// A peer in the turbine retransmit tree, with the shreds received from it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminRpcTurbineNode {
+    pub pubkey: String,
+    pub stake: u64,
+    pub tvu: SocketAddr,
+    pub tvu_forwards: SocketAddr,
+    pub shreds_received: Option<u64>, // None = no shreds received from the peer
+    pub duplicates_received: Option<u64>,
+    pub forwarded_received: Option<u64>,
+    pub last_received: Option<u64>,
+}
+
+impl AdminRpcTurbineNode {
+    fn new(node: &RetransmitTreeNode, stats: Option<&RetransmitPeerStats>) -> Self {
+        Self {
+            pubkey: node.pubkey.to_string(),
+            stake: node.stake,
+            tvu: node.tvu,
+            tvu_forwards: node.tvu_forwards,
+            shreds_received: stats.map(|stats| stats.num_shreds),
+            duplicates_received: stats.map(|stats| stats.num_duplicates),
+            forwarded_received: stats.map(|stats| stats.num_forwarded),
+            last_received: stats.map(|stats| stats.last_received),
+        }
+    }
+}
+
+/// The turbine retransmit tree of a shred as seen from the validator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminRpcTurbineTree {
+    pub slot: Slot,
+    pub shred_index: u32,
+    pub slot_leader: Option<String>,
+    pub index: usize,
+    pub layer: usize,
+    pub anchor: bool,
+    pub parent: Option<AdminRpcTurbineNode>, // None = receives from the leader or root
+    pub neighbors: Vec<AdminRpcTurbineNode>,
+    pub children: Vec<AdminRpcTurbineNode>,
+}
+
+impl From<&RetransmitTreeInfo> for AdminRpcTurbineTree {
+    fn from(info: &RetransmitTreeInfo) -> Self {
+        let to_node = |node: &RetransmitTreeNode| {
+            AdminRpcTurbineNode::new(node, info.peer_stats.get(&node.pubkey))
+        };
+        Self {
+            slot: info.slot,
+            shred_index: info.shred_index,
+            slot_leader: info.tree.slot_leader.map(|leader| leader.to_string()),
+            index: info.tree.index,
+            layer: info.tree.layer,
+            anchor: info.tree.anchor,
+            parent: info.tree.parent.as_ref().map(to_node),
+            neighbors: info.tree.neighbors.iter().map(to_node).collect(),
+            children: info.tree.children.iter().map(to_node).collect(),
+        }
+    }
+}
+
+impl Display for AdminRpcTurbineTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn write_nodes(
+            f: &mut fmt::Formatter,
+            name: &str,
+            nodes: &[&AdminRpcTurbineNode],
+        ) -> fmt::Result {
+            writeln!(f, "{}: {}", name, nodes.len())?;
+            if nodes.is_empty() {
+                return Ok(());
+            }
+            writeln!(
+                f,
+                "  {:<44} {:>20} {:<21} {:>8} {:>10} {:>9} {:>13}",
+                "Identity", "Stake", "TVU", "Shreds", "Duplicates", "Forwarded", "Last Received"
+            )?;
+            for node in nodes {
+                let to_string = |value: Option<u64>| {
+                    value
+                        .map(|value| value.to_string())
+                        .unwrap_or_else(|| "-".to_string())
+                };
+                writeln!(
+                    f,
+                    "  {:<44} {:>20} {:<21} {:>8} {:>10} {:>9} {:>13}",
+                    node.pubkey,
+                    node.stake,
+                    node.tvu.to_string(),
+                    to_string(node.shreds_received),
+                    to_string(node.duplicates_received),
+                    to_string(node.forwarded_received),
+                    to_string(node.last_received),
+                )?;
+            }
+            Ok(())
+        }
+        writeln!(
+            f,
+            "Slot {} shred {}, leader: {}",
+            self.slot,
+            self.shred_index,
+            self.slot_leader.as_deref().unwrap_or("unknown")
+        )?;
+        writeln!(
+            f,
+            "Position {} in layer {}{}",
+            self.index,
+            self.layer,
+            if self.index == 0 {
+                " (root)"
+            } else if self.anchor {
+                " (neighborhood anchor)"
+            } else {
+                ""
+            }
+        )?;
+        write_nodes(f, "Parent", &self.parent.iter().collect::<Vec<_>>())?;
+        write_nodes(f, "Neighbors", &self.neighbors.iter().collect::<Vec<_>>())?;
+        write_nodes(f, "Children", &self.children.iter().collect::<Vec<_>>())
+    }
+}
+
+/// The repair requests sent to a peer, and how it responded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminRpcRepairPeer {
+    pub pubkey: String,
+    pub requests: u64,
+    pub responses: u64,
+    pub invalid_responses: u64,
+    pub response_rate: Option<f64>,
+    pub average_latency_ms: Option<u64>, // None = no responses received
+    pub reputation: f64,
+}
+
+impl AdminRpcRepairPeer {
+    fn new(pubkey: &Pubkey, stats: &PeerRequestStats) -> Self {
+        Self {
+            pubkey: pubkey.to_string(),
+            requests: stats.requests,
+            responses: stats.responses,
+            invalid_responses: stats.invalid_responses,
+            response_rate: stats.response_rate(),
+            average_latency_ms: stats.average_latency_ms(),
+            reputation: repair_peer_reputation(Some(stats)),
+        }
+    }
+}
+
+/// The peers repair requests were recently sent to, least reputable first
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminRpcRepairPeers {
+    pub peers: Vec<AdminRpcRepairPeer>,
+}
+
+impl Display for AdminRpcRepairPeers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.peers.is_empty() {
+            return writeln!(f, "No repair requests sent recently");
+        }
+        writeln!(
+            f,
+            "{:<44} {:>8} {:>9} {:>7} {:>13} {:>11} {:>10}",
+            "Identity",
+            "Requests",
+            "Responses",
+            "Invalid",
+            "Response Rate",
+            "Latency ms",
+            "Reputation"
+        )?;
+        for peer in &self.peers {
+            writeln!(
+                f,
+                "{:<44} {:>8} {:>9} {:>7} {:>13} {:>11} {:>10.2}",
+                peer.pubkey,
+                peer.requests,
+                peer.responses,
+                peer.invalid_responses,
+                peer.response_rate
+                    .map(|rate| format!("{:.1}%", rate * 100.0))
+                    .unwrap_or_else(|| "-".to_string()),
+                peer.average_latency_ms
+                    .map(|latency| latency.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                peer.reputation,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The gossip traffic of a kind of gossip value since start
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminRpcGossipDataTraffic {
+    pub kind: String,
+    pub inbound_bytes: u64,
+    pub inbound_values: u64,
+    pub outbound_bytes: u64,
+    pub outbound_values: u64,
+    pub dropped_values: u64,
+    pub budget_bytes_per_second: Option<u64>, // None = not limited
+}
+
+/// The gossip traffic of each kind of gossip value, most outbound bytes first
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminRpcGossipBandwidth {
+    pub kinds: Vec<AdminRpcGossipDataTraffic>,
+}
+
+impl Display for AdminRpcGossipBandwidth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.kinds.is_empty() {
+            return writeln!(f, "No gossip traffic yet");
+        }
+        writeln!(
+            f,
+            "{:<26} {:>14} {:>10} {:>14} {:>10} {:>9} {:>12}",
+            "Kind",
+            "Inbound Bytes",
+            "Inbound",
+            "Outbound Bytes",
+            "Outbound",
+            "Dropped",
+            "Budget B/s"
+        )?;
+        for kind in &self.kinds {
+            writeln!(
+                f,
+                "{:<26} {:>14} {:>10} {:>14} {:>10} {:>9} {:>12}",
+                kind.kind,
+                kind.inbound_bytes,
+                kind.inbound_values,
+                kind.outbound_bytes,
+                kind.outbound_values,
+                kind.dropped_values,
+                kind.budget_bytes_per_second
+                    .map(|budget| budget.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_validators(validators: Option<Vec<String>>) -> Result<Option<HashSet<Pubkey>>> {
+    validators
+        .map(|validators| {
+            validators
+                .iter()
+                .map(|validator| {
+                    Pubkey::from_str(validator).map_err(|err| {
+                        jsonrpc_core::error::Error::invalid_params(format!(
+                            "Invalid pubkey {}: {}",
+                            validator, err
+                        ))
+                    })
+                })
+                .collect()
+        })
+        .transpose()
+}
+
 #[rpc]
 pub trait AdminRpc {
     type Metadata;
@@ -58,6 +452,70 @@ pub trait AdminRpc {
 
     #[rpc(meta, name = "setIdentity")]
     fn set_identity(&self, meta: Self::Metadata, keypair_file: String) -> Result<()>;
+
+    #[rpc(meta, name = "liveConfig")]
+    fn live_config(&self, meta: Self::Metadata) -> Result<AdminRpcLiveConfig>;
+
+    #[rpc(meta, name = "setTrustedValidators")]
+    fn set_trusted_validators(
+        &self,
+        meta: Self::Metadata,
+        trusted_validators: Option<Vec<String>>,
+    ) -> Result<()>;
+
+    #[rpc(meta, name = "setRepairValidators")]
+    fn set_repair_validators(
+        &self,
+        meta: Self::Metadata,
+        repair_validators: Option<Vec<String>>,
+    ) -> Result<()>;
+
+    #[rpc(meta, name = "setSnapshotInterval")]
+    fn set_snapshot_interval(
+        &self,
+        meta: Self::Metadata,
+        snapshot_interval_slots: u64,
+    ) -> Result<()>;
+
+    #[rpc(meta, name = "setMaximumSnapshotsToRetain")]
+    fn set_maximum_snapshots_to_retain(
+        &self,
+        meta: Self::Metadata,
+        maximum_snapshots_to_retain: usize,
+    ) -> Result<()>;
+
+    #[rpc(meta, name = "setMaxLedgerShreds")]
+    fn set_max_ledger_shreds(&self, meta: Self::Metadata, max_ledger_shreds: u64) -> Result<()>;
+
+    #[rpc(meta, name = "setMaxTransactionHistorySlots")]
+    fn set_max_transaction_history_slots(
+        &self,
+        meta: Self::Metadata,
+        max_transaction_history_slots: u64,
+    ) -> Result<()>;
+
+    #[rpc(meta, name = "turbineTree")]
+    fn turbine_tree(
+        &self,
+        meta: Self::Metadata,
+        slot: Slot,
+        shred_index: u32,
+    ) -> Result<AdminRpcTurbineTree>;
+
+    #[rpc(meta, name = "repairPeers")]
+    fn repair_peers(&self, meta: Self::Metadata) -> Result<AdminRpcRepairPeers>;
+
+    #[rpc(meta, name = "gossipBandwidth")]
+    fn gossip_bandwidth(&self, meta: Self::Metadata) -> Result<AdminRpcGossipBandwidth>;
+
+    #[rpc(meta, name = "announceRestart")]
+    fn announce_restart(
+        &self,
+        meta: Self::Metadata,
+        start: u64,
+        expected_duration_secs: u64,
+        target_version: Option<String>,
+    ) -> Result<()>;
 }
 
 pub struct AdminRpcImpl;
@@ -151,6 +609,269 @@ impl AdminRpc for AdminRpcImpl {
             ))
         }
     }
+
+    fn live_config(&self, meta: Self::Metadata) -> Result<AdminRpcLiveConfig> {
+        debug!("live_config request received");
+        meta.with_live_config(|live_config| Ok(AdminRpcLiveConfig::from(live_config)))
+    }
+
+    fn set_trusted_validators(
+        &self,
+        meta: Self::Metadata,
+        trusted_validators: Option<Vec<String>>,
+    ) -> Result<()> {
+        debug!("set_trusted_validators request received");
+        let trusted_validators = parse_validators(trusted_validators)?;
+        meta.with_live_config(|live_config| {
+            warn!("Trusted validators set to {:?}", trusted_validators);
+            *live_config.trusted_validators.write().unwrap() = trusted_validators;
+            Ok(())
+        })
+    }
+
+    fn set_repair_validators(
+        &self,
+        meta: Self::Metadata,
+        repair_validators: Option<Vec<String>>,
+    ) -> Result<()> {
+        debug!("set_repair_validators request received");
+        let repair_validators = parse_validators(repair_validators)?;
+        meta.with_live_config(|live_config| {
+            warn!("Repair validators set to {:?}", repair_validators);
+            *live_config.repair_validators.write().unwrap() = repair_validators;
+            Ok(())
+        })
+    }
+
+    fn set_snapshot_interval(
+        &self,
+        meta: Self::Metadata,
+        snapshot_interval_slots: u64,
+    ) -> Result<()> {
+        debug!("set_snapshot_interval request received");
+        meta.with_live_config(|live_config| {
+            let live_snapshot_interval_slots = live_config
+                .snapshot_interval_slots
+                .as_ref()
+                .ok_or_else(|| {
+                    jsonrpc_core::error::Error::invalid_params("Snapshots are disabled")
+                })?;
+            if snapshot_interval_slots == 0
+                || is_snapshot_config_invalid(
+                    snapshot_interval_slots,
+                    live_config.accounts_hash_interval_slots,
+                )
+            {
+                return Err(jsonrpc_core::error::Error::invalid_params(format!(
+                    "Snapshot interval must be a non-zero multiple of the accounts hash \
+                     interval ({} slots)",
+                    live_config.accounts_hash_interval_slots
+                )));
+            }
+            warn!("Snapshot interval set to {} slots", snapshot_interval_slots);
+            live_snapshot_interval_slots.store(snapshot_interval_slots, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+
+    fn set_maximum_snapshots_to_retain(
+        &self,
+        meta: Self::Metadata,
+        maximum_snapshots_to_retain: usize,
+    ) -> Result<()> {
+        debug!("set_maximum_snapshots_to_retain request received");
+        meta.with_live_config(|live_config| {
+            let live_maximum_snapshots_to_retain = live_config
+                .maximum_snapshots_to_retain
+                .as_ref()
+                .ok_or_else(|| {
+                    jsonrpc_core::error::Error::invalid_params("Snapshots are disabled")
+                })?;
+            if maximum_snapshots_to_retain == 0 {
+                return Err(jsonrpc_core::error::Error::invalid_params(
+                    "At least one snapshot must be retained",
+                ));
+            }
+            warn!(
+                "Maximum snapshots to retain set to {}",
+                maximum_snapshots_to_retain
+            );
+            live_maximum_snapshots_to_retain.store(maximum_snapshots_to_retain, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+
+    fn set_max_ledger_shreds(&self, meta: Self::Metadata, max_ledger_shreds: u64) -> Result<()> {
+        debug!("set_max_ledger_shreds request received");
+        meta.with_live_config(|live_config| {
+            let live_max_ledger_shreds =
+                live_config.max_ledger_shreds.as_ref().ok_or_else(|| {
+                    jsonrpc_core::error::Error::invalid_params(
+                        "Ledger cleanup is disabled, restart with --limit-ledger-size to enable it",
+                    )
+                })?;
+            if max_ledger_shreds < DEFAULT_MIN_MAX_LEDGER_SHREDS {
+                return Err(jsonrpc_core::error::Error::invalid_params(format!(
+                    "Max ledger shreds is too small, the minimum value is {}",
+                    DEFAULT_MIN_MAX_LEDGER_SHREDS
+                )));
+            }
+            warn!("Max ledger shreds set to {}", max_ledger_shreds);
+            live_max_ledger_shreds.store(max_ledger_shreds, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+
+    fn set_max_transaction_history_slots(
+        &self,
+        meta: Self::Metadata,
+        max_transaction_history_slots: u64,
+    ) -> Result<()> {
+        debug!("set_max_transaction_history_slots request received");
+        meta.with_live_config(|live_config| {
+            let live_max_transaction_history_slots = live_config
+                .max_transaction_history_slots
+                .as_ref()
+                .ok_or_else(|| {
+                    jsonrpc_core::error::Error::invalid_params(
+                        "Transaction history retention is not limited, restart with \
+                         --rpc-max-transaction-history-slots to limit it",
+                    )
+                })?;
+            if max_transaction_history_slots == 0 {
+                return Err(jsonrpc_core::error::Error::invalid_params(
+                    "At least one slot of transaction history must be retained",
+                ));
+            }
+            warn!(
+                "Max transaction history slots set to {}",
+                max_transaction_history_slots
+            );
+            live_max_transaction_history_slots
+                .store(max_transaction_history_slots, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+
+    fn turbine_tree(
+        &self,
+        meta: Self::Metadata,
+        slot: Slot,
+        shred_index: u32,
+    ) -> Result<AdminRpcTurbineTree> {
+        debug!("turbine_tree request received");
+        if let Some(inspector) = meta.retransmit_tree_inspector.read().unwrap().as_ref() {
+            inspector
+                .get_retransmit_tree(slot, shred_index)
+                .map(|info| AdminRpcTurbineTree::from(&info))
+                .map_err(|err| jsonrpc_core::error::Error::invalid_params(err.to_string()))
+        } else {
+            Err(jsonrpc_core::error::Error::invalid_params(
+                "Retry once validator start up is complete",
+            ))
+        }
+    }
+
+    fn repair_peers(&self, meta: Self::Metadata) -> Result<AdminRpcRepairPeers> {
+        debug!("repair_peers request received");
+        if let Some(outstanding_repairs) = meta.outstanding_repairs.read().unwrap().as_ref() {
+            let mut peers: Vec<_> = outstanding_repairs
+                .read()
+                .unwrap()
+                .peer_stats()
+                .iter()
+                .map(|(pubkey, stats)| AdminRpcRepairPeer::new(pubkey, stats))
+                .collect();
+            peers.sort_by(|a, b| {
+                a.reputation
+                    .partial_cmp(&b.reputation)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| b.requests.cmp(&a.requests))
+            });
+            Ok(AdminRpcRepairPeers { peers })
+        } else {
+            Err(jsonrpc_core::error::Error::invalid_params(
+                "Retry once validator start up is complete",
+            ))
+        }
+    }
+
+    fn gossip_bandwidth(&self, meta: Self::Metadata) -> Result<AdminRpcGossipBandwidth> {
+        debug!("gossip_bandwidth request received");
+        if let Some(cluster_info) = meta.cluster_info.read().unwrap().as_ref() {
+            let mut kinds: Vec<_> = cluster_info
+                .crds_data_traffic()
+                .into_iter()
+                .map(|(kind, traffic, budget)| AdminRpcGossipDataTraffic {
+                    kind: kind.to_string(),
+                    inbound_bytes: traffic.inbound_bytes,
+                    inbound_values: traffic.inbound_values,
+                    outbound_bytes: traffic.outbound_bytes,
+                    outbound_values: traffic.outbound_values,
+                    dropped_values: traffic.dropped_values,
+                    budget_bytes_per_second: budget,
+                })
+                .collect();
+            kinds.sort_by(|a, b| b.outbound_bytes.cmp(&a.outbound_bytes));
+            Ok(AdminRpcGossipBandwidth { kinds })
+        } else {
+            Err(jsonrpc_core::error::Error::invalid_params(
+                "Retry once validator start up is complete",
+            ))
+        }
+    }
+
+    fn announce_restart(
+        &self,
+        meta: Self::Metadata,
+        start: u64,
+        expected_duration_secs: u64,
+        target_version: Option<String>,
+    ) -> Result<()> {
+        debug!("announce_restart request received");
+        if start >= MAX_WALLCLOCK {
+            return Err(jsonrpc_core::error::Error::invalid_params(format!(
+                "Start must be less than {} milliseconds since the UNIX epoch",
+                MAX_WALLCLOCK
+            )));
+        }
+        if expected_duration_secs > MAX_RESTART_INTENT_DURATION_SECS {
+            return Err(jsonrpc_core::error::Error::invalid_params(format!(
+                "Expected duration must be at most {} seconds",
+                MAX_RESTART_INTENT_DURATION_SECS
+            )));
+        }
+        if let Some(target_version) = &target_version {
+            if target_version.len() > MAX_RESTART_INTENT_VERSION_LEN {
+                return Err(jsonrpc_core::error::Error::invalid_params(format!(
+                    "Target version must be at most {} characters",
+                    MAX_RESTART_INTENT_VERSION_LEN
+                )));
+            }
+        }
+        if let Some(cluster_info) = meta.cluster_info.read().unwrap().as_ref() {
+            cluster_info
+                .push_restart_intent(start, expected_duration_secs, target_version.clone())
+                .map_err(|_| {
+                    jsonrpc_core::error::Error::invalid_params(
+                        "Restart announcements are disabled, see --gossip-push-restart-intent",
+                    )
+                })?;
+            if expected_duration_secs == 0 {
+                warn!("Restart announcement cancelled");
+            } else {
+                warn!(
+                    "Restart announced at {} for {}s, target version: {:?}",
+                    start, expected_duration_secs, target_version
+                );
+            }
+            Ok(())
+        } else {
+            Err(jsonrpc_core::error::Error::invalid_params(
+                "Retry once validator start up is complete",
+            ))
+        }
+    }
 }
 
 // Start the Admin RPC interface
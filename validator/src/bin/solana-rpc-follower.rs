@@ -0,0 +1,230 @@
+//! A read-only RPC node that serves historical blocks and transactions from the ledger of a
+//! validator running on the same machine, by following its blockstore as a secondary
+use {
+    clap::{crate_description, crate_name, value_t_or_exit, App, Arg},
+    log::*,
+    solana_clap_utils::input_validators::is_parsable,
+    solana_gossip::{cluster_info::ClusterInfo, contact_info::ContactInfo},
+    solana_ledger::{
+        blockstore::Blockstore, blockstore_db::AccessType,
+        leader_schedule_cache::LeaderScheduleCache,
+    },
+    solana_rpc::{
+        blockstore_follower_service::{BlockstoreFollowerService, DEFAULT_FOLLOW_INTERVAL_MS},
+        max_slots::MaxSlots,
+        optimistically_confirmed_bank_tracker::OptimisticallyConfirmedBank,
+        rpc::JsonRpcConfig,
+        rpc_service::JsonRpcService,
+    },
+    solana_runtime::{
+        accounts_index::AccountSecondaryIndexes,
+        bank::Bank,
+        bank_forks::BankForks,
+        commitment::BlockCommitmentCache,
+        hardened_unpack::{open_genesis_config, MAX_GENESIS_ARCHIVE_UNPACKED_SIZE},
+    },
+    solana_sdk::{
+        exit::Exit,
+        rpc_port,
+        signature::{Keypair, Signer},
+        timing::timestamp,
+    },
+    solana_validator::{port_validator, redirect_stderr_to_file},
+    std::{
+        net::SocketAddr,
+        path::PathBuf,
+        process::exit,
+        sync::{
+            atomic::{AtomicBool, AtomicU64},
+            Arc, RwLock,
+        },
+        time::Duration,
+    },
+};
+
+fn main() {
+    let default_rpc_port = rpc_port::DEFAULT_RPC_PORT.to_string();
+    let default_follow_interval_ms = DEFAULT_FOLLOW_INTERVAL_MS.to_string();
+    let default_rpc_threads = num_cpus::get().to_string();
+
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(solana_version::version!())
+        .arg(
+            Arg::with_name("ledger_path")
+                .short("l")
+                .long("ledger")
+                .value_name("DIR")
+                .takes_value(true)
+                .required(true)
+                .help("Use DIR as the ledger location of the validator to follow"),
+        )
+        .arg(
+            Arg::with_name("rpc_bind_address")
+                .long("rpc-bind-address")
+                .value_name("HOST")
+                .takes_value(true)
+                .validator(solana_net_utils::is_host)
+                .default_value("127.0.0.1")
+                .help("IP address to bind the RPC port"),
+        )
+        .arg(
+            Arg::with_name("rpc_port")
+                .long("rpc-port")
+                .value_name("PORT")
+                .takes_value(true)
+                .validator(port_validator)
+                .default_value(&default_rpc_port)
+                .help("Serve JSON RPC on this port"),
+        )
+        .arg(
+            Arg::with_name("rpc_threads")
+                .long("rpc-threads")
+                .value_name("NUMBER")
+                .validator(is_parsable::<usize>)
+                .takes_value(true)
+                .default_value(&default_rpc_threads)
+                .help("Number of threads to use for servicing RPC requests"),
+        )
+        .arg(
+            Arg::with_name("follow_interval_ms")
+                .long("follow-interval-ms")
+                .value_name("MILLISECONDS")
+                .validator(is_parsable::<u64>)
+                .takes_value(true)
+                .default_value(&default_follow_interval_ms)
+                .help("How often to catch up with the validator's blockstore"),
+        )
+        .arg(
+            Arg::with_name("accounts")
+                .long("accounts")
+                .value_name("DIR")
+                .takes_value(true)
+                .required(true)
+                .help(
+                    "Use DIR for the genesis bank's accounts, \
+                     it must not be inside the ledger of the validator to follow",
+                ),
+        )
+        .arg(
+            Arg::with_name("logfile")
+                .short("o")
+                .long("log")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Redirect logging to the specified file, '-' for standard error"),
+        )
+        .get_matches();
+
+    let ledger_path = PathBuf::from(value_t_or_exit!(matches, "ledger_path", String));
+    let rpc_bind_address =
+        solana_net_utils::parse_host(matches.value_of("rpc_bind_address").unwrap())
+            .expect("invalid rpc_bind_address");
+    let rpc_port = value_t_or_exit!(matches, "rpc_port", u16);
+    let rpc_threads = value_t_or_exit!(matches, "rpc_threads", usize);
+    let follow_interval =
+        Duration::from_millis(value_t_or_exit!(matches, "follow_interval_ms", u64));
+    let account_path = PathBuf::from(value_t_or_exit!(matches, "accounts", String));
+    if account_path.starts_with(&ledger_path) {
+        eprintln!(
+            "--accounts {:?} must not be inside the ledger of the validator to follow",
+            account_path
+        );
+        exit(1);
+    }
+
+    let logfile = matches
+        .value_of("logfile")
+        .filter(|logfile| *logfile != "-")
+        .map(|logfile| logfile.to_string());
+    let _logger_thread = redirect_stderr_to_file(logfile);
+    info!("{} {}", crate_name!(), solana_version::version!());
+
+    let genesis_config = open_genesis_config(&ledger_path, MAX_GENESIS_ARCHIVE_UNPACKED_SIZE);
+    let blockstore = Arc::new(
+        Blockstore::open_with_access_type(&ledger_path, AccessType::Secondary, None, true)
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to open ledger at {:?}: {:?}", ledger_path, err);
+                exit(1);
+            }),
+    );
+    if let Err(err) = blockstore.try_catch_up_with_primary() {
+        eprintln!("Failed to catch up with primary blockstore: {:?}", err);
+        exit(1);
+    }
+
+    // Historical requests are served from the blockstore, the bank only satisfies the RPC
+    // service's need for one
+    let bank = Bank::new_with_paths(
+        &genesis_config,
+        vec![account_path],
+        &[],
+        None,
+        None,
+        AccountSecondaryIndexes::default(),
+        false,
+        Default::default(),
+        false,
+    );
+    let leader_schedule_cache = Arc::new(LeaderScheduleCache::new_from_bank(&bank));
+    let bank_forks = Arc::new(RwLock::new(BankForks::new(bank)));
+    let optimistically_confirmed_bank =
+        OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks);
+
+    let mut block_commitment_cache = BlockCommitmentCache::default();
+    block_commitment_cache.set_highest_confirmed_root(blockstore.max_root());
+    let block_commitment_cache = Arc::new(RwLock::new(block_commitment_cache));
+    let max_slots = Arc::new(MaxSlots::default());
+    let max_complete_transaction_status_slot = Arc::new(AtomicU64::new(blockstore.max_root()));
+
+    let identity_keypair = Arc::new(Keypair::new());
+    let cluster_info = Arc::new(ClusterInfo::new(
+        ContactInfo::new_localhost(&identity_keypair.pubkey(), timestamp()),
+        identity_keypair,
+    ));
+
+    let exit = Arc::new(AtomicBool::new(false));
+    let blockstore_follower_service = BlockstoreFollowerService::new(
+        blockstore.clone(),
+        block_commitment_cache.clone(),
+        max_slots.clone(),
+        max_complete_transaction_status_slot.clone(),
+        follow_interval,
+        &exit,
+    );
+
+    let rpc_addr = SocketAddr::new(rpc_bind_address, rpc_port);
+    let rpc_service = JsonRpcService::new(
+        rpc_addr,
+        JsonRpcConfig {
+            enable_rpc_transaction_history: true,
+            enable_cpi_and_log_storage: true,
+            rpc_threads,
+            ..JsonRpcConfig::default()
+        },
+        None,
+        bank_forks,
+        block_commitment_cache,
+        blockstore,
+        cluster_info,
+        None,
+        genesis_config.hash(),
+        &ledger_path,
+        Arc::new(RwLock::new(Exit::default())),
+        Arc::default(),
+        Arc::new(AtomicBool::new(false)),
+        optimistically_confirmed_bank,
+        2000,
+        2,
+        max_slots,
+        leader_schedule_cache,
+        max_complete_transaction_status_slot,
+    );
+    info!("Serving RPC on {}, following {:?}", rpc_addr, ledger_path);
+
+    rpc_service.join().expect("rpc service");
+    exit.store(true, std::sync::atomic::Ordering::Relaxed);
+    blockstore_follower_service
+        .join()
+        .expect("follower service");
+}
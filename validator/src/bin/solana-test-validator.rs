@@ -502,6 +502,9 @@ fn main() {
     genesis.max_ledger_shreds = value_of(&matches, "limit_ledger_size");
 
     let admin_service_cluster_info = Arc::new(RwLock::new(None));
+    let admin_service_live_config = Arc::new(RwLock::new(None));
+    let admin_service_retransmit_tree_inspector = Arc::new(RwLock::new(None));
+    let admin_service_outstanding_repairs = Arc::new(RwLock::new(None));
     admin_rpc_service::run(
         &ledger_path,
         admin_rpc_service::AdminRpcRequestMetadata {
@@ -514,6 +517,9 @@ fn main() {
             validator_exit: genesis.validator_exit.clone(),
             authorized_voter_keypairs: genesis.authorized_voter_keypairs.clone(),
             cluster_info: admin_service_cluster_info.clone(),
+            live_config: admin_service_live_config.clone(),
+            retransmit_tree_inspector: admin_service_retransmit_tree_inspector.clone(),
+            outstanding_repairs: admin_service_outstanding_repairs.clone(),
         },
     );
     let dashboard = if output == Output::Dashboard {
@@ -587,6 +593,11 @@ fn main() {
     match genesis.start_with_mint_address(mint_address) {
         Ok(test_validator) => {
             *admin_service_cluster_info.write().unwrap() = Some(test_validator.cluster_info());
+            *admin_service_live_config.write().unwrap() = Some(test_validator.live_config());
+            *admin_service_retransmit_tree_inspector.write().unwrap() =
+                Some(test_validator.retransmit_tree_inspector());
+            *admin_service_outstanding_repairs.write().unwrap() =
+                Some(test_validator.outstanding_repairs());
             if let Some(dashboard) = dashboard {
                 dashboard.run(Duration::from_millis(250));
             }
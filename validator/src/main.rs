@@ -22,7 +22,9 @@ use {
     },
     solana_core::{
         ledger_cleanup_service::{DEFAULT_MAX_LEDGER_SHREDS, DEFAULT_MIN_MAX_LEDGER_SHREDS},
+        sigverify_stage::SigVerifyDiscardPolicy,
         tpu::DEFAULT_TPU_COALESCE_MS,
+        transaction_scheduler::TransactionSchedulerType,
         validator::{
             is_snapshot_config_invalid, Validator, ValidatorConfig, ValidatorStartProgress,
         },
@@ -32,6 +34,7 @@ use {
     solana_gossip::{
         cluster_info::{ClusterInfo, Node, VALIDATOR_PORT_RANGE},
         contact_info::ContactInfo,
+        crds_value::CRDS_DATA_NAMES,
         gossip_service::GossipService,
     },
     solana_ledger::blockstore_db::BlockstoreRecoveryMode,
@@ -62,7 +65,7 @@ use {
         redirect_stderr_to_file,
     },
     std::{
-        collections::{HashSet, VecDeque},
+        collections::{HashMap, HashSet, VecDeque},
         env,
         fs::{self, File},
         net::{IpAddr, SocketAddr, TcpListener, UdpSocket},
@@ -70,7 +73,7 @@ use {
         process::exit,
         str::FromStr,
         sync::{
-            atomic::{AtomicBool, Ordering},
+            atomic::{AtomicBool, AtomicU64, Ordering},
             Arc, RwLock,
         },
         thread::sleep,
@@ -321,6 +324,30 @@ fn hash_validator(hash: String) -> Result<(), String> {
         .map_err(|e| format!("{:?}", e))
 }
 
+fn parse_gossip_data_budget(budget: &str) -> Result<(String, u64), String> {
+    let (kind, bytes_per_second) = budget
+        .split_once(':')
+        .ok_or_else(|| format!("expected KIND:BYTES_PER_SECOND, got {}", budget))?;
+    if !CRDS_DATA_NAMES.contains(&kind) {
+        return Err(format!(
+            "unknown gossip value kind {}, expected one of: {}",
+            kind,
+            CRDS_DATA_NAMES.join(", ")
+        ));
+    }
+    match bytes_per_second.parse::<u64>() {
+        Ok(bytes_per_second) if bytes_per_second > 0 => Ok((kind.to_string(), bytes_per_second)),
+        _ => Err(format!(
+            "invalid bytes per second for {}: {}",
+            kind, bytes_per_second
+        )),
+    }
+}
+
+fn is_gossip_data_budget(budget: String) -> Result<(), String> {
+    parse_gossip_data_budget(&budget).map(|_| ())
+}
+
 fn is_trusted_validator(id: &Pubkey, trusted_validators: &Option<HashSet<Pubkey>>) -> bool {
     if let Some(trusted_validators) = trusted_validators {
         trusted_validators.contains(id)
@@ -1218,6 +1245,16 @@ pub fn main() {
                 .help("Include CPI inner instructions and logs in the \
                         historical transaction info stored"),
         )
+        .arg(
+            Arg::with_name("rpc_max_transaction_history_slots")
+                .long("rpc-max-transaction-history-slots")
+                .value_name("SLOT_COUNT")
+                .requires("enable_rpc_transaction_history")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Only serve historical transaction info of this many slots behind the \
+                       latest root from the local ledger [default: serve all available slots]"),
+        )
         .arg(
             Arg::with_name("rpc_max_multiple_accounts")
                 .long("rpc-max-multiple-accounts")
@@ -1305,6 +1342,16 @@ pub fn main() {
                       Useful for validators running behind a load balancer or proxy \
                       [default: use --rpc-bind-address / --rpc-port]"),
         )
+        .arg(
+            Arg::with_name("prometheus_metrics_address")
+                .long("prometheus-metrics-address")
+                .value_name("HOST:PORT")
+                .takes_value(true)
+                .validator(solana_net_utils::is_host_port)
+                .help("Serve the metrics of the validator at http://HOST:PORT/metrics \
+                      in the Prometheus text format, in addition to submitting them \
+                      to the InfluxDB instance configured by SOLANA_METRICS_CONFIG, if any"),
+        )
         .arg(
             Arg::with_name("dynamic_port_range")
                 .long("dynamic-port-range")
@@ -1368,6 +1415,34 @@ pub fn main() {
                 .default_value("10000")
                 .help("Milliseconds between printing contact debug from gossip."),
         )
+        .arg(
+            Arg::with_name("gossip_data_budget")
+                .long("gossip-data-budget")
+                .value_name("KIND:BYTES_PER_SECOND")
+                .takes_value(true)
+                .multiple(true)
+                .validator(is_gossip_data_budget)
+                .help("Limit the outbound gossip traffic of a kind of gossip value, \
+                       e.g. EpochSlots:100000, so that it cannot starve the other kinds \
+                       of the gossip bandwidth. May be specified multiple times"),
+        )
+        .arg(
+            Arg::with_name("gossip_push_compact_contact_info")
+                .long("gossip-push-compact-contact-info")
+                .takes_value(false)
+                .help("Push the compact contact-info to gossip, alongside the legacy one. \
+                       Nodes which do not support it drop the gossip packets carrying it, \
+                       so only enable once the whole cluster has upgraded"),
+        )
+        .arg(
+            Arg::with_name("gossip_push_restart_intent")
+                .long("gossip-push-restart-intent")
+                .takes_value(false)
+                .help("Allow announcing restarts on gossip with the announce-restart command. \
+                       Nodes which do not support restart announcements drop the gossip \
+                       packets carrying them, so only enable once the whole cluster has \
+                       upgraded"),
+        )
         .arg(
             Arg::with_name("no_poh_speed_test")
                 .long("no-poh-speed-test")
@@ -1432,7 +1507,8 @@ pub fn main() {
                 .value_name("HASH")
                 .takes_value(true)
                 .validator(hash_validator)
-                .help("When wait-for-supermajority <x>, require the bank at <x> to have this hash"),
+                .help("When wait-for-supermajority <x>, require the bank at <x> to have this hash. \
+                       With --wen-restart, require the bank at the restart slot to have it"),
         )
         .arg(
             Arg::with_name("expected_shred_version")
@@ -1461,6 +1537,20 @@ pub fn main() {
                 .help("After processing the ledger and the next slot is SLOT, wait until a \
                        supermajority of stake is visible on gossip before starting PoH"),
         )
+        .arg(
+            Arg::with_name("wen_restart")
+                .long("wen-restart")
+                .takes_value(false)
+                .conflicts_with("wait_for_supermajority")
+                .help("Restart a stalled cluster without agreeing on a restart slot out of band: \
+                       announce the last voted fork on gossip, pick the highest slot on the \
+                       last voted forks of enough stake, root it once enough stake announces \
+                       the same bank hash for it, then register a hard fork after it and \
+                       wait until a supermajority of stake is visible on gossip before starting \
+                       PoH. Requires snapshots to be enabled. Exits if the restart slot is \
+                       missing from the local ledger, in which case the validator has to be \
+                       restarted from a snapshot of the restart slot"),
+        )
         .arg(
             Arg::with_name("no_wait_for_vote_to_start_leader")
                 .hidden(true)
@@ -1469,6 +1559,15 @@ pub fn main() {
                       production until it sees a vote land in a rooted slot. This prevents
                       double signing. Turn off to risk double signing a block."),
         )
+        .arg(
+            Arg::with_name("dump_bank_hash_details_on_mismatch")
+                .long("dump-bank-hash-details-on-mismatch")
+                .takes_value(false)
+                .help("When the cluster duplicate confirms a slot with a different bank hash \
+                       than ours, write the inputs of our bank hash, including every account \
+                       stored in the slot, to LEDGER/bank_hash_details for offline comparison \
+                       with `solana-ledger-tool bank-hash-diff`"),
+        )
         .arg(
             Arg::with_name("hard_forks")
                 .long("hard-fork")
@@ -1545,6 +1644,32 @@ pub fn main() {
                 .validator(is_parsable::<u64>)
                 .help("Milliseconds to wait in the TPU receiver for packet coalescing."),
         )
+        .arg(
+            Arg::with_name("banking_scheduler")
+                .long("banking-scheduler")
+                .value_name("SCHEDULER")
+                .takes_value(true)
+                .possible_values(&["thread-local", "central"])
+                .default_value("thread-local")
+                .help("How the banking stage assigns transactions to its threads: \
+                       thread-local threads each lock and execute the batches they receive, \
+                       central schedules batches that cannot contend for account locks \
+                       from a single thread"),
+        )
+        .arg(
+            Arg::with_name("sigverify_discard_policy")
+                .long("sigverify-discard-policy")
+                .value_name("POLICY")
+                .takes_value(true)
+                .possible_values(&["tail-drop", "prefer-staked", "prefer-more-signatures"])
+                .default_value("tail-drop")
+                .help("Which transaction packets signature verification discards when more \
+                       are queued than it can keep up with: \
+                       tail-drop discards the packets received last, \
+                       prefer-staked discards the packets from the least staked senders first, \
+                       prefer-more-signatures discards the packets of the transactions with \
+                       the fewest signatures first"),
+        )
         .arg(
             Arg::with_name("rocksdb_max_compaction_jitter")
                 .long("rocksdb-max-compaction-jitter-slots")
@@ -1904,6 +2029,10 @@ pub fn main() {
             SubCommand::with_name("init")
             .about("Initialize the ledger directory then exit")
         )
+        .subcommand(
+            SubCommand::with_name("live-config")
+            .about("Display the validator settings that can be changed without a restart")
+        )
         .subcommand(
             SubCommand::with_name("monitor")
             .about("Monitor the validator")
@@ -1912,6 +2041,32 @@ pub fn main() {
             SubCommand::with_name("run")
             .about("Run the validator")
         )
+        .subcommand(
+            SubCommand::with_name("repair-validators")
+            .about("Adjust the validators to request repairs from")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .setting(AppSettings::InferSubcommands)
+            .subcommand(
+                SubCommand::with_name("set")
+                .about("Only request repairs from these validators")
+                .arg(
+                    Arg::with_name("validators")
+                        .index(1)
+                        .value_name("VALIDATOR IDENTITY")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(true)
+                        .validator(is_pubkey)
+                        .help("Validator identity pubkeys")
+                )
+                .after_help("Note: the change only applies to the currently running validator instance")
+            )
+            .subcommand(
+                SubCommand::with_name("clear")
+                .about("Request repairs from any validator")
+                .after_help("Note: the change only applies to the currently running validator instance")
+            )
+        )
         .subcommand(
             SubCommand::with_name("set-identity")
             .about("Set the validator identity")
@@ -1937,6 +2092,158 @@ pub fn main() {
             )
             .after_help("Note: the new filter only applies to the currently running validator instance")
         )
+        .subcommand(
+            SubCommand::with_name("set-limit-ledger-size")
+            .about("Adjust the number of shreds to retain in the ledger")
+            .arg(
+                Arg::with_name("limit_ledger_size")
+                    .index(1)
+                    .value_name("SHRED_COUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .validator(is_parsable::<u64>)
+                    .help("Keep this amount of shreds in root slots")
+            )
+            .after_help("Note: only applies to a validator started with --limit-ledger-size, \
+                         and only to the currently running validator instance")
+        )
+        .subcommand(
+            SubCommand::with_name("set-rpc-max-transaction-history-slots")
+            .about("Adjust the number of slots of transaction history served over JSON RPC")
+            .arg(
+                Arg::with_name("rpc_max_transaction_history_slots")
+                    .index(1)
+                    .value_name("SLOT_COUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .validator(is_parsable::<u64>)
+                    .help("Serve transaction history of this many slots behind the latest root")
+            )
+            .after_help("Note: only applies to a validator started with \
+                         --rpc-max-transaction-history-slots, and only to the currently running \
+                         validator instance")
+        )
+        .subcommand(
+            SubCommand::with_name("set-maximum-snapshots-to-retain")
+            .about("Adjust the number of snapshots to retain")
+            .arg(
+                Arg::with_name("maximum_snapshots_to_retain")
+                    .index(1)
+                    .value_name("NUMBER")
+                    .takes_value(true)
+                    .required(true)
+                    .validator(is_parsable::<usize>)
+                    .help("The maximum number of snapshots to hold on to when purging older snapshots")
+            )
+            .after_help("Note: the change only applies to the currently running validator instance")
+        )
+        .subcommand(
+            SubCommand::with_name("set-snapshot-interval")
+            .about("Adjust the snapshot interval")
+            .arg(
+                Arg::with_name("snapshot_interval_slots")
+                    .index(1)
+                    .value_name("SLOTS")
+                    .takes_value(true)
+                    .required(true)
+                    .validator(is_parsable::<u64>)
+                    .help("Number of slots between generating snapshots")
+            )
+            .after_help("Note: the change only applies to the currently running validator instance")
+        )
+        .subcommand(
+            SubCommand::with_name("trusted-validators")
+            .about("Adjust the trusted validators")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .setting(AppSettings::InferSubcommands)
+            .subcommand(
+                SubCommand::with_name("set")
+                .about("Only trust these validators")
+                .arg(
+                    Arg::with_name("validators")
+                        .index(1)
+                        .value_name("VALIDATOR IDENTITY")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(true)
+                        .validator(is_pubkey)
+                        .help("Validator identity pubkeys")
+                )
+                .after_help("Note: the change only applies to the currently running validator instance")
+            )
+            .subcommand(
+                SubCommand::with_name("clear")
+                .about("Trust all validators")
+                .after_help("Note: the change only applies to the currently running validator instance")
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("turbine-tree")
+            .about("Display the turbine retransmit tree of a shred for this validator")
+            .arg(
+                Arg::with_name("slot")
+                    .index(1)
+                    .value_name("SLOT")
+                    .takes_value(true)
+                    .required(true)
+                    .validator(is_slot)
+                    .help("Slot of the shred")
+            )
+            .arg(
+                Arg::with_name("shred_index")
+                    .index(2)
+                    .value_name("SHRED_INDEX")
+                    .takes_value(true)
+                    .default_value("0")
+                    .validator(is_parsable::<u32>)
+                    .help("Index of the shred within the slot")
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("repair-peers")
+            .about("Display the peers this validator recently sent repair requests to")
+        )
+        .subcommand(
+            SubCommand::with_name("announce-restart")
+            .about("Announce on gossip that the validator is about to restart, e.g. for an \
+                    upgrade, so that monitoring does not report it as delinquent")
+            .arg(
+                Arg::with_name("start_in")
+                    .long("start-in")
+                    .takes_value(true)
+                    .value_name("MINUTES")
+                    .validator(is_parsable::<u64>)
+                    .default_value("0")
+                    .help("Time until the restart starts")
+            )
+            .arg(
+                Arg::with_name("duration")
+                    .long("duration")
+                    .takes_value(true)
+                    .value_name("MINUTES")
+                    .validator(is_parsable::<u64>)
+                    .required_unless("cancel")
+                    .help("Expected duration of the restart")
+            )
+            .arg(
+                Arg::with_name("target_version")
+                    .long("target-version")
+                    .takes_value(true)
+                    .value_name("VERSION")
+                    .help("Software version the validator will be running after the restart")
+            )
+            .arg(
+                Arg::with_name("cancel")
+                    .long("cancel")
+                    .takes_value(false)
+                    .conflicts_with_all(&["duration", "target_version"])
+                    .help("Cancel the previous announcement")
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("gossip-bandwidth")
+            .about("Display the gossip traffic of each kind of gossip value since start")
+        )
         .subcommand(
             SubCommand::with_name("wait-for-restart-window")
             .about("Monitor the validator for a good time to restart")
@@ -2010,6 +2317,17 @@ pub fn main() {
             }
         }
         ("init", _) => Operation::Initialize,
+        ("live-config", _) => {
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            let live_config = admin_rpc_service::runtime()
+                .block_on(async move { admin_client.await?.live_config().await })
+                .unwrap_or_else(|err| {
+                    println!("liveConfig request failed: {}", err);
+                    exit(1);
+                });
+            print!("{}", live_config);
+            return;
+        }
         ("exit", Some(subcommand_matches)) => {
             let min_idle_time = value_t_or_exit!(subcommand_matches, "min_idle_time", usize);
             let force = subcommand_matches.is_present("force");
@@ -2040,6 +2358,34 @@ pub fn main() {
             monitor_validator(&ledger_path);
             return;
         }
+        ("repair-validators", Some(repair_validators_subcommand_matches)) => {
+            let validators = match repair_validators_subcommand_matches.subcommand() {
+                ("set", Some(subcommand_matches)) => Some(
+                    values_t_or_exit!(subcommand_matches, "validators", Pubkey)
+                        .into_iter()
+                        .map(|validator| validator.to_string())
+                        .collect::<Vec<_>>(),
+                ),
+                ("clear", _) => None,
+                _ => unreachable!(),
+            };
+            let message = match &validators {
+                Some(validators) => format!("Repair validators set to: {}", validators.join(", ")),
+                None => "Repairing from all validators".to_string(),
+            };
+
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            admin_rpc_service::runtime()
+                .block_on(
+                    async move { admin_client.await?.set_repair_validators(validators).await },
+                )
+                .unwrap_or_else(|err| {
+                    println!("setRepairValidators request failed: {}", err);
+                    exit(1);
+                });
+            println!("{}", message);
+            return;
+        }
         ("set-identity", Some(subcommand_matches)) => {
             let identity_keypair = value_t_or_exit!(subcommand_matches, "identity", String);
 
@@ -2074,6 +2420,181 @@ pub fn main() {
                 });
             return;
         }
+        ("set-limit-ledger-size", Some(subcommand_matches)) => {
+            let limit_ledger_size = value_t_or_exit!(subcommand_matches, "limit_ledger_size", u64);
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            admin_rpc_service::runtime()
+                .block_on(async move {
+                    admin_client
+                        .await?
+                        .set_max_ledger_shreds(limit_ledger_size)
+                        .await
+                })
+                .unwrap_or_else(|err| {
+                    println!("setMaxLedgerShreds request failed: {}", err);
+                    exit(1);
+                });
+            println!("Ledger size limit set to: {}", limit_ledger_size);
+            return;
+        }
+        ("set-rpc-max-transaction-history-slots", Some(subcommand_matches)) => {
+            let max_transaction_history_slots =
+                value_t_or_exit!(subcommand_matches, "rpc_max_transaction_history_slots", u64);
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            admin_rpc_service::runtime()
+                .block_on(async move {
+                    admin_client
+                        .await?
+                        .set_max_transaction_history_slots(max_transaction_history_slots)
+                        .await
+                })
+                .unwrap_or_else(|err| {
+                    println!("setMaxTransactionHistorySlots request failed: {}", err);
+                    exit(1);
+                });
+            println!(
+                "Transaction history retention set to: {} slots",
+                max_transaction_history_slots
+            );
+            return;
+        }
+        ("set-maximum-snapshots-to-retain", Some(subcommand_matches)) => {
+            let maximum_snapshots_to_retain =
+                value_t_or_exit!(subcommand_matches, "maximum_snapshots_to_retain", usize);
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            admin_rpc_service::runtime()
+                .block_on(async move {
+                    admin_client
+                        .await?
+                        .set_maximum_snapshots_to_retain(maximum_snapshots_to_retain)
+                        .await
+                })
+                .unwrap_or_else(|err| {
+                    println!("setMaximumSnapshotsToRetain request failed: {}", err);
+                    exit(1);
+                });
+            println!(
+                "Maximum snapshots to retain set to: {}",
+                maximum_snapshots_to_retain
+            );
+            return;
+        }
+        ("set-snapshot-interval", Some(subcommand_matches)) => {
+            let snapshot_interval_slots =
+                value_t_or_exit!(subcommand_matches, "snapshot_interval_slots", u64);
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            admin_rpc_service::runtime()
+                .block_on(async move {
+                    admin_client
+                        .await?
+                        .set_snapshot_interval(snapshot_interval_slots)
+                        .await
+                })
+                .unwrap_or_else(|err| {
+                    println!("setSnapshotInterval request failed: {}", err);
+                    exit(1);
+                });
+            println!("Snapshot interval set to: {}", snapshot_interval_slots);
+            return;
+        }
+        ("trusted-validators", Some(trusted_validators_subcommand_matches)) => {
+            let validators = match trusted_validators_subcommand_matches.subcommand() {
+                ("set", Some(subcommand_matches)) => Some(
+                    values_t_or_exit!(subcommand_matches, "validators", Pubkey)
+                        .into_iter()
+                        .map(|validator| validator.to_string())
+                        .collect::<Vec<_>>(),
+                ),
+                ("clear", _) => None,
+                _ => unreachable!(),
+            };
+            let message = match &validators {
+                Some(validators) => format!("Trusted validators set to: {}", validators.join(", ")),
+                None => "Trusting all validators".to_string(),
+            };
+
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            admin_rpc_service::runtime()
+                .block_on(
+                    async move { admin_client.await?.set_trusted_validators(validators).await },
+                )
+                .unwrap_or_else(|err| {
+                    println!("setTrustedValidators request failed: {}", err);
+                    exit(1);
+                });
+            println!("{}", message);
+            return;
+        }
+        ("turbine-tree", Some(subcommand_matches)) => {
+            let slot = value_t_or_exit!(subcommand_matches, "slot", Slot);
+            let shred_index = value_t_or_exit!(subcommand_matches, "shred_index", u32);
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            let turbine_tree = admin_rpc_service::runtime()
+                .block_on(async move { admin_client.await?.turbine_tree(slot, shred_index).await })
+                .unwrap_or_else(|err| {
+                    println!("turbineTree request failed: {}", err);
+                    exit(1);
+                });
+            print!("{}", turbine_tree);
+            return;
+        }
+        ("announce-restart", Some(subcommand_matches)) => {
+            let start_in = value_t_or_exit!(subcommand_matches, "start_in", u64);
+            let duration = value_t!(subcommand_matches, "duration", u64).unwrap_or_default();
+            let target_version = value_t!(subcommand_matches, "target_version", String).ok();
+            let start = start_in
+                .checked_mul(60 * 1000)
+                .and_then(|start_in| solana_sdk::timing::timestamp().checked_add(start_in))
+                .unwrap_or_else(|| {
+                    println!("--start-in is too large");
+                    exit(1);
+                });
+            let duration = duration.checked_mul(60).unwrap_or_else(|| {
+                println!("--duration is too large");
+                exit(1);
+            });
+
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            admin_rpc_service::runtime()
+                .block_on(async move {
+                    admin_client
+                        .await?
+                        .announce_restart(start, duration, target_version)
+                        .await
+                })
+                .unwrap_or_else(|err| {
+                    println!("announceRestart request failed: {}", err);
+                    exit(1);
+                });
+            if duration == 0 {
+                println!("Restart announcement cancelled");
+            } else {
+                println!("Restart announced");
+            }
+            return;
+        }
+        ("gossip-bandwidth", _) => {
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            let gossip_bandwidth = admin_rpc_service::runtime()
+                .block_on(async move { admin_client.await?.gossip_bandwidth().await })
+                .unwrap_or_else(|err| {
+                    println!("gossipBandwidth request failed: {}", err);
+                    exit(1);
+                });
+            print!("{}", gossip_bandwidth);
+            return;
+        }
+        ("repair-peers", _) => {
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            let repair_peers = admin_rpc_service::runtime()
+                .block_on(async move { admin_client.await?.repair_peers().await })
+                .unwrap_or_else(|err| {
+                    println!("repairPeers request failed: {}", err);
+                    exit(1);
+                });
+            print!("{}", repair_peers);
+            return;
+        }
         ("wait-for-restart-window", Some(subcommand_matches)) => {
             let min_idle_time = value_t_or_exit!(subcommand_matches, "min_idle_time", usize);
             wait_for_restart_window(&ledger_path, min_idle_time).unwrap_or_else(|err| {
@@ -2254,6 +2775,13 @@ pub fn main() {
                 .map(Duration::from_secs),
             account_indexes: account_indexes.clone(),
             rpc_scan_and_fix_roots: matches.is_present("rpc_scan_and_fix_roots"),
+            max_transaction_history_slots: value_t!(
+                matches,
+                "rpc_max_transaction_history_slots",
+                u64
+            )
+            .ok()
+            .map(|slots| Arc::new(AtomicU64::new(slots))),
         },
         rpc_addrs: value_t!(matches, "rpc_port", u16).ok().map(|rpc_port| {
             (
@@ -2286,6 +2814,7 @@ pub fn main() {
         },
         voting_disabled: matches.is_present("no_voting") || restricted_repair_only_mode,
         wait_for_supermajority: value_t!(matches, "wait_for_supermajority", Slot).ok(),
+        wen_restart: matches.is_present("wen_restart"),
         trusted_validators,
         repair_validators,
         gossip_validators,
@@ -2316,6 +2845,28 @@ pub fn main() {
         tpu_coalesce_ms,
         no_wait_for_vote_to_start_leader: matches.is_present("no_wait_for_vote_to_start_leader"),
         accounts_shrink_ratio,
+        dump_bank_hash_details_on_mismatch: matches
+            .is_present("dump_bank_hash_details_on_mismatch"),
+        banking_scheduler_type: value_t_or_exit!(
+            matches,
+            "banking_scheduler",
+            TransactionSchedulerType
+        ),
+        sigverify_discard_policy: value_t_or_exit!(
+            matches,
+            "sigverify_discard_policy",
+            SigVerifyDiscardPolicy
+        ),
+        gossip_data_budgets: matches
+            .values_of("gossip_data_budget")
+            .map(|budgets| {
+                budgets
+                    .map(|budget| parse_gossip_data_budget(budget).unwrap())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        gossip_push_compact_contact_info: matches.is_present("gossip_push_compact_contact_info"),
+        gossip_push_restart_intent: matches.is_present("gossip_push_restart_intent"),
         ..ValidatorConfig::default()
     };
 
@@ -2512,6 +3063,9 @@ pub fn main() {
 
     let start_progress = Arc::new(RwLock::new(ValidatorStartProgress::default()));
     let admin_service_cluster_info = Arc::new(RwLock::new(None));
+    let admin_service_live_config = Arc::new(RwLock::new(None));
+    let admin_service_retransmit_tree_inspector = Arc::new(RwLock::new(None));
+    let admin_service_outstanding_repairs = Arc::new(RwLock::new(None));
     admin_rpc_service::run(
         &ledger_path,
         admin_rpc_service::AdminRpcRequestMetadata {
@@ -2521,6 +3075,9 @@ pub fn main() {
             start_progress: start_progress.clone(),
             authorized_voter_keypairs: authorized_voter_keypairs.clone(),
             cluster_info: admin_service_cluster_info.clone(),
+            live_config: admin_service_live_config.clone(),
+            retransmit_tree_inspector: admin_service_retransmit_tree_inspector.clone(),
+            outstanding_repairs: admin_service_outstanding_repairs.clone(),
         },
     );
 
@@ -2615,6 +3172,16 @@ pub fn main() {
 
     solana_metrics::set_host_id(identity_keypair.pubkey().to_string());
     solana_metrics::set_panic_hook("validator");
+    if let Some(address) = matches.value_of("prometheus_metrics_address") {
+        let address = solana_net_utils::parse_host_port(address).unwrap_or_else(|e| {
+            eprintln!("failed to parse prometheus metrics address: {}", e);
+            exit(1);
+        });
+        if let Err(err) = solana_metrics::start_prometheus_exporter(address) {
+            eprintln!("failed to start prometheus metrics endpoint: {}", err);
+            exit(1);
+        }
+    }
 
     if validator_config.cuda {
         solana_perf::perf_libs::init_cuda();
@@ -2665,6 +3232,11 @@ pub fn main() {
         start_progress,
     );
     *admin_service_cluster_info.write().unwrap() = Some(validator.cluster_info.clone());
+    *admin_service_live_config.write().unwrap() = Some(validator.live_config.clone());
+    *admin_service_retransmit_tree_inspector.write().unwrap() =
+        Some(validator.retransmit_tree_inspector.clone());
+    *admin_service_outstanding_repairs.write().unwrap() =
+        Some(validator.outstanding_repairs.clone());
 
     if let Some(filename) = init_complete_file {
         File::create(filename).unwrap_or_else(|_| {
@@ -0,0 +1,272 @@
+//! Tracking of the alerts raised by the rule failures: an alert is notified
+//! once it has failed more than the unhealthy threshold of consecutive checks,
+//! again only if its severity changes, and an all clear is notified once it no
+//! longer fails.
+
+use {
+    crate::{
+        config::AlertSettings,
+        rules::Failure,
+        state::{Alert, ClusterState},
+    },
+    log::*,
+    solana_metrics::{datapoint_error, datapoint_info},
+    solana_notifier::{NotificationType, Severity},
+    std::{collections::HashSet, time::Duration},
+};
+
+/// Updates the alerts of the cluster with the failures of a check, and sends
+/// the resulting notifications through `notify`. A notified alert triggers an
+/// incident keyed by the cluster name and the alert key, which is resolved by
+/// its all clear. The message of an ongoing alert, e.g. with the current skip
+/// rate, is updated without notifying it again.
+///
+/// Only the alerts of `checked_rules` are cleared, so that the alerts of the
+/// rules which could not be evaluated, e.g. because of an RPC error, are kept
+/// as is. The alerts of the rules no longer in `rule_names` are dropped
+/// silently.
+pub fn update_alerts<F>(
+    cluster_name: &str,
+    rule_names: &HashSet<&str>,
+    checked_rules: &HashSet<&str>,
+    failures: Vec<(&AlertSettings, Failure)>,
+    state: &mut ClusterState,
+    now: u64,
+    mut notify: F,
+) where
+    F: FnMut(&[String], &str, Severity, &NotificationType),
+{
+    let mut failed_keys = HashSet::new();
+    let mut ok = true;
+    for (settings, failure) in failures {
+        error!(
+            "{}: {} sanity failure: {}",
+            cluster_name, failure.rule, failure.message
+        );
+        ok = false;
+        if failure.event {
+            notify(
+                &settings.notify,
+                &format!(
+                    "solana-watchtower: {}: {}: {}: {}",
+                    cluster_name, settings.severity, failure.rule, failure.message
+                ),
+                settings.severity,
+                &NotificationType::Message,
+            );
+            datapoint_error!(
+                "watchtower-sanity-failure",
+                ("cluster", cluster_name, String),
+                ("test", failure.rule, String),
+                ("err", failure.message, String),
+                ("severity", settings.severity.to_string(), String)
+            );
+            continue;
+        }
+        failed_keys.insert(failure.key.clone());
+        let alert = state
+            .alerts
+            .entry(failure.key.clone())
+            .or_insert_with(|| Alert {
+                rule: failure.rule.clone(),
+                message: failure.message.clone(),
+                severity: settings.severity,
+                notify: settings.notify.clone(),
+                since: now,
+                failures: 0,
+                notified: false,
+            });
+        alert.failures += 1;
+        if alert.failures <= settings.unhealthy_threshold {
+            info!(
+                "{}: Failure {} of {}: {}",
+                cluster_name, alert.failures, settings.unhealthy_threshold, failure.message
+            );
+        } else if !alert.notified || alert.severity != settings.severity {
+            notify(
+                &settings.notify,
+                &format!(
+                    "solana-watchtower: {}: {}: {}: {}",
+                    cluster_name, settings.severity, failure.rule, failure.message
+                ),
+                settings.severity,
+                &NotificationType::Trigger {
+                    incident: format!("{}/{}", cluster_name, failure.key),
+                },
+            );
+            datapoint_error!(
+                "watchtower-sanity-failure",
+                ("cluster", cluster_name, String),
+                ("test", failure.rule, String),
+                ("err", failure.message, String),
+                ("severity", settings.severity.to_string(), String)
+            );
+            alert.severity = settings.severity;
+            alert.notify = settings.notify.clone();
+            alert.notified = true;
+        }
+        alert.message = failure.message;
+    }
+    datapoint_info!(
+        "watchtower-sanity",
+        ("cluster", cluster_name, String),
+        ("ok", ok, bool)
+    );
+
+    let cleared_keys: Vec<_> = state
+        .alerts
+        .iter()
+        .filter(|(key, alert)| {
+            !failed_keys.contains(*key)
+                && (checked_rules.contains(alert.rule.as_str())
+                    || !rule_names.contains(alert.rule.as_str()))
+        })
+        .map(|(key, _)| key.clone())
+        .collect();
+    for key in cleared_keys {
+        let alert = state.alerts.remove(&key).unwrap();
+        if alert.notified && rule_names.contains(alert.rule.as_str()) {
+            // Drop milliseconds in message
+            let alarm_duration = Duration::from_secs(now.saturating_sub(alert.since) / 1000);
+            let all_clear_msg = format!(
+                "All clear: {}: {} after {}",
+                alert.rule,
+                alert.message,
+                humantime::format_duration(alarm_duration)
+            );
+            info!("{}: {}", cluster_name, all_clear_msg);
+            notify(
+                &alert.notify,
+                &format!("solana-watchtower: {}: {}", cluster_name, all_clear_msg),
+                alert.severity,
+                &NotificationType::Resolve {
+                    incident: format!("{}/{}", cluster_name, key),
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_failure(rule: &'static str, subject: &str, event: bool) -> Failure {
+        Failure {
+            rule,
+            key: format!("{}/{}", rule, subject),
+            message: format!("{} failed", subject),
+            event,
+        }
+    }
+
+    #[test]
+    fn test_update_alerts() {
+        let settings = AlertSettings {
+            severity: Severity::Critical,
+            notify: vec!["pager".to_string()],
+            unhealthy_threshold: 1,
+        };
+        let rule_names: HashSet<_> = vec!["delinquent", "commission-change"]
+            .into_iter()
+            .collect();
+        let mut state = ClusterState::default();
+        let mut update = |failures: Vec<Failure>, checked_rules: &[&str], now| {
+            let mut notifications = vec![];
+            update_alerts(
+                "testnet",
+                &rule_names,
+                &checked_rules.iter().cloned().collect(),
+                failures.into_iter().map(|f| (&settings, f)).collect(),
+                &mut state,
+                now,
+                |notify, msg, _severity, notification_type| {
+                    notifications.push((
+                        notify.to_vec(),
+                        msg.to_string(),
+                        notification_type.clone(),
+                    ))
+                },
+            );
+            notifications
+        };
+
+        // Notified after more than one consecutive failure.
+        let failure = || vec![new_failure("delinquent", "foo", false)];
+        assert_eq!(update(failure(), &["delinquent"], 1000), vec![]);
+        assert_eq!(
+            update(failure(), &["delinquent"], 2000),
+            vec![(
+                vec!["pager".to_string()],
+                "solana-watchtower: testnet: Critical: delinquent: foo failed".to_string(),
+                NotificationType::Trigger {
+                    incident: "testnet/delinquent/foo".to_string()
+                },
+            )]
+        );
+        // Not notified again, even though the message changes.
+        assert_eq!(update(failure(), &["delinquent"], 3000), vec![]);
+        let mut changed_failure = new_failure("delinquent", "foo", false);
+        changed_failure.message = "foo failed again".to_string();
+        assert_eq!(update(vec![changed_failure], &["delinquent"], 3500), vec![]);
+        assert_eq!(
+            state.alerts["delinquent/foo"].message,
+            "foo failed again".to_string()
+        );
+        // Not cleared if the rule was not checked.
+        assert_eq!(update(vec![], &[], 4000), vec![]);
+        // Events are notified right away, and not tracked.
+        assert_eq!(
+            update(
+                vec![new_failure("commission-change", "bar", true)],
+                &["delinquent", "commission-change"],
+                5000
+            ),
+            vec![
+                (
+                    vec!["pager".to_string()],
+                    "solana-watchtower: testnet: Critical: commission-change: bar failed"
+                        .to_string(),
+                    NotificationType::Message,
+                ),
+                (
+                    vec!["pager".to_string()],
+                    "solana-watchtower: testnet: All clear: delinquent: foo failed again after 4s"
+                        .to_string(),
+                    NotificationType::Resolve {
+                        incident: "testnet/delinquent/foo".to_string()
+                    },
+                ),
+            ]
+        );
+        assert!(state.alerts.is_empty());
+
+        // Alerts of removed rules are dropped silently.
+        state.alerts.insert(
+            "balance/foo".to_string(),
+            Alert {
+                rule: "balance".to_string(),
+                message: "foo failed".to_string(),
+                severity: Severity::Warning,
+                notify: vec![],
+                since: 0,
+                failures: 5,
+                notified: true,
+            },
+        );
+        let mut notifications = vec![];
+        update_alerts(
+            "testnet",
+            &rule_names,
+            &HashSet::new(),
+            vec![],
+            &mut state,
+            6000,
+            |notify, msg, _severity, _notification_type| {
+                notifications.push((notify.to_vec(), msg.to_string()))
+            },
+        );
+        assert_eq!(notifications, vec![]);
+        assert!(state.alerts.is_empty());
+    }
+}
@@ -0,0 +1,407 @@
+//! Configuration of the clusters monitored by the watchtower, and of the alert
+//! rules evaluated against each of them.
+
+use {
+    serde_derive::{Deserialize, Serialize},
+    solana_notifier::Severity,
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        collections::{HashMap, HashSet},
+        fs::File,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+pub const DEFAULT_INTERVAL_SECS: u64 = 60;
+pub const DEFAULT_RESTART_GRACE_PERIOD_SECS: u64 = 300;
+pub const DEFAULT_NOTIFIER: &str = "default";
+
+fn default_interval_secs() -> u64 {
+    DEFAULT_INTERVAL_SECS
+}
+
+fn default_restart_grace_period_secs() -> u64 {
+    DEFAULT_RESTART_GRACE_PERIOD_SECS
+}
+
+fn default_notify() -> Vec<String> {
+    vec![DEFAULT_NOTIFIER.to_string()]
+}
+
+fn default_unhealthy_threshold() -> usize {
+    1
+}
+
+/// How the alerts raised by a rule are reported.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct AlertSettings {
+    #[serde(default)]
+    pub severity: Severity,
+    /// Names of the notifiers the alerts are routed to. The `default`
+    /// notifier is configured by the `SLACK_WEBHOOK`, `TELEGRAM_BOT_TOKEN`,
+    /// ... environment variables, and a notifier named `foo` by the same
+    /// variables prefixed with `FOO_`.
+    #[serde(default = "default_notify")]
+    pub notify: Vec<String>,
+    /// An alert is notified once it has failed more than this many
+    /// consecutive checks.
+    #[serde(default = "default_unhealthy_threshold")]
+    pub unhealthy_threshold: usize,
+}
+
+impl Default for AlertSettings {
+    fn default() -> Self {
+        Self {
+            severity: Severity::default(),
+            notify: default_notify(),
+            unhealthy_threshold: default_unhealthy_threshold(),
+        }
+    }
+}
+
+/// The rules, and their thresholds. Validators are given by their identity
+/// pubkeys.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Rule {
+    /// The transaction count of the cluster is not advancing.
+    TransactionCount,
+    /// The cluster is not producing new blockhashes.
+    RecentBlockhash,
+    /// The validators are delinquent, or missing from the vote accounts.
+    Delinquency { validators: Vec<String> },
+    /// The identity balance of the validators is below the minimum.
+    Balance {
+        validators: Vec<String>,
+        minimum_balance_sol: f64,
+    },
+    /// The current stake of the cluster is below the minimum.
+    ActiveStake { minimum_percent: f64 },
+    /// The validators skipped more than the maximum share of their leader
+    /// slots, over the most recent window of slots.
+    SkipRate {
+        validators: Vec<String>,
+        window_slots: u64,
+        max_percent: f64,
+    },
+    /// The validators earned fewer vote credits this epoch than the best
+    /// performing validator, by more than the maximum.
+    VoteCreditsLag {
+        validators: Vec<String>,
+        max_lag: u64,
+    },
+    /// The commission of the validators changed. All the validators are
+    /// watched if none are given.
+    CommissionChange {
+        #[serde(default)]
+        validators: Vec<String>,
+    },
+    /// The activated stake of the validators dropped by more than the
+    /// maximum share. All the validators are watched if none are given.
+    StakeDrop {
+        #[serde(default)]
+        validators: Vec<String>,
+        max_drop_percent: f64,
+    },
+    /// The validators run another version than the expected one, which
+    /// defaults to the version of the RPC node.
+    VersionMismatch {
+        validators: Vec<String>,
+        #[serde(default)]
+        expected_version: Option<String>,
+    },
+    /// The RPC endpoints of the cluster are unhealthy.
+    RpcHealth,
+    /// The RPC endpoints of the cluster are behind the most advanced one by
+    /// more than the maximum number of slots.
+    SlotLag { max_lag: u64 },
+}
+
+impl Rule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::TransactionCount => "transaction-count",
+            Rule::RecentBlockhash => "recent-blockhash",
+            Rule::Delinquency { .. } => "delinquent",
+            Rule::Balance { .. } => "balance",
+            Rule::ActiveStake { .. } => "current-stake",
+            Rule::SkipRate { .. } => "skip-rate",
+            Rule::VoteCreditsLag { .. } => "vote-credits-lag",
+            Rule::CommissionChange { .. } => "commission-change",
+            Rule::StakeDrop { .. } => "stake-drop",
+            Rule::VersionMismatch { .. } => "version-mismatch",
+            Rule::RpcHealth => "rpc-health",
+            Rule::SlotLag { .. } => "slot-lag",
+        }
+    }
+
+    fn validators(&self) -> &[String] {
+        match self {
+            Rule::Delinquency { validators }
+            | Rule::Balance { validators, .. }
+            | Rule::SkipRate { validators, .. }
+            | Rule::VoteCreditsLag { validators, .. }
+            | Rule::CommissionChange { validators }
+            | Rule::StakeDrop { validators, .. }
+            | Rule::VersionMismatch { validators, .. } => validators,
+            Rule::TransactionCount
+            | Rule::RecentBlockhash
+            | Rule::ActiveStake { .. }
+            | Rule::RpcHealth
+            | Rule::SlotLag { .. } => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RuleConfig {
+    /// Names the alerts of the rule, instead of its type, e.g. `skip-rate`.
+    /// Rules of the same type must be given distinct names.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub rule: Rule,
+    #[serde(flatten)]
+    pub settings: AlertSettings,
+}
+
+impl RuleConfig {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or_else(|| self.rule.name())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ClusterConfig {
+    pub name: String,
+    /// The first endpoint serves the cluster wide queries. All the endpoints
+    /// are checked by the `rpc_health` and `slot_lag` rules.
+    pub rpc_urls: Vec<String>,
+    #[serde(default)]
+    pub address_labels: HashMap<String, String>,
+    /// Ignore HTTP 502 Bad Gateway errors from the RPC endpoints.
+    #[serde(default)]
+    pub ignore_http_bad_gateway: bool,
+    /// Time past the expected end of a restart announced by a validator
+    /// during which the validator is not reported as delinquent.
+    #[serde(default = "default_restart_grace_period_secs")]
+    pub restart_grace_period_secs: u64,
+    /// How failures to query the RPC endpoints are reported.
+    #[serde(default)]
+    pub rpc_error: AlertSettings,
+    pub rules: Vec<RuleConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MonitorConfig {
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+    /// File in which the alerts are persisted, so that they are not notified
+    /// again after a restart.
+    #[serde(default)]
+    pub state_file: Option<PathBuf>,
+    pub clusters: Vec<ClusterConfig>,
+}
+
+impl MonitorConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let config: Self =
+            serde_yaml::from_reader(file).map_err(|err| format!("{}: {}", path.display(), err))?;
+        config.sanitize()?;
+        Ok(config)
+    }
+
+    pub fn sanitize(&self) -> Result<(), String> {
+        if self.interval_secs == 0 {
+            return Err("interval_secs must be non-zero".to_string());
+        }
+        if self.clusters.is_empty() {
+            return Err("no clusters to monitor".to_string());
+        }
+        let mut names = HashSet::new();
+        for cluster in &self.clusters {
+            if !names.insert(&cluster.name) {
+                return Err(format!("duplicate cluster: {}", cluster.name));
+            }
+            if cluster.rpc_urls.is_empty() {
+                return Err(format!("{}: no rpc_urls", cluster.name));
+            }
+            let mut rule_names = HashSet::new();
+            for rule in &cluster.rules {
+                if !rule_names.insert(rule.name()) {
+                    return Err(format!(
+                        "{}: duplicate rule name: {}, rules of the same type need distinct names",
+                        cluster.name,
+                        rule.name()
+                    ));
+                }
+                for validator in rule.rule.validators() {
+                    Pubkey::from_str(validator).map_err(|_| {
+                        format!(
+                            "{}: {}: invalid validator identity: {}",
+                            cluster.name,
+                            rule.name(),
+                            validator
+                        )
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the names of all the notifiers alerts are routed to.
+    pub fn notifiers(&self) -> HashSet<&str> {
+        self.clusters
+            .iter()
+            .flat_map(|cluster| {
+                cluster
+                    .rules
+                    .iter()
+                    .map(|rule| &rule.settings)
+                    .chain(std::iter::once(&cluster.rpc_error))
+            })
+            .flat_map(|settings| settings.notify.iter().map(String::as_str))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let validator = Pubkey::new_unique().to_string();
+        let yaml = format!(
+            "
+clusters:
+  - name: testnet
+    rpc_urls: [\"http://127.0.0.1:8899\", \"http://127.0.0.2:8899\"]
+    rules:
+      - type: transaction_count
+      - type: delinquency
+        validators: [{}]
+        severity: critical
+        notify: [default, pager]
+        unhealthy_threshold: 3
+      - type: skip_rate
+        validators: [{}]
+        window_slots: 1000
+        max_percent: 25
+      - type: skip_rate
+        name: skip-rate-warning
+        validators: [{}]
+        window_slots: 1000
+        max_percent: 10
+",
+            validator, validator, validator
+        );
+        let config: MonitorConfig = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(config.sanitize(), Ok(()));
+        assert_eq!(config.interval_secs, DEFAULT_INTERVAL_SECS);
+        assert_eq!(config.state_file, None);
+        let cluster = &config.clusters[0];
+        assert_eq!(cluster.name, "testnet");
+        assert_eq!(cluster.rpc_urls.len(), 2);
+        assert_eq!(cluster.rpc_error, AlertSettings::default());
+        assert_eq!(
+            cluster.restart_grace_period_secs,
+            DEFAULT_RESTART_GRACE_PERIOD_SECS
+        );
+        assert_eq!(
+            cluster.rules,
+            vec![
+                RuleConfig {
+                    name: None,
+                    rule: Rule::TransactionCount,
+                    settings: AlertSettings::default(),
+                },
+                RuleConfig {
+                    name: None,
+                    rule: Rule::Delinquency {
+                        validators: vec![validator.clone()],
+                    },
+                    settings: AlertSettings {
+                        severity: Severity::Critical,
+                        notify: vec!["default".to_string(), "pager".to_string()],
+                        unhealthy_threshold: 3,
+                    },
+                },
+                RuleConfig {
+                    name: None,
+                    rule: Rule::SkipRate {
+                        validators: vec![validator.clone()],
+                        window_slots: 1000,
+                        max_percent: 25.0,
+                    },
+                    settings: AlertSettings::default(),
+                },
+                RuleConfig {
+                    name: Some("skip-rate-warning".to_string()),
+                    rule: Rule::SkipRate {
+                        validators: vec![validator],
+                        window_slots: 1000,
+                        max_percent: 10.0,
+                    },
+                    settings: AlertSettings::default(),
+                },
+            ]
+        );
+        let rule_names: Vec<_> = cluster.rules.iter().map(RuleConfig::name).collect();
+        assert_eq!(
+            rule_names,
+            vec![
+                "transaction-count",
+                "delinquent",
+                "skip-rate",
+                "skip-rate-warning"
+            ]
+        );
+        assert_eq!(
+            config.notifiers(),
+            vec!["default", "pager"].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn test_sanitize_config() {
+        let config = |rpc_urls: Vec<String>, rules: Vec<Rule>| MonitorConfig {
+            interval_secs: DEFAULT_INTERVAL_SECS,
+            state_file: None,
+            clusters: vec![ClusterConfig {
+                name: "testnet".to_string(),
+                rpc_urls,
+                address_labels: HashMap::new(),
+                ignore_http_bad_gateway: false,
+                restart_grace_period_secs: DEFAULT_RESTART_GRACE_PERIOD_SECS,
+                rpc_error: AlertSettings::default(),
+                rules: rules
+                    .into_iter()
+                    .map(|rule| RuleConfig {
+                        name: None,
+                        rule,
+                        settings: AlertSettings::default(),
+                    })
+                    .collect(),
+            }],
+        };
+        let rpc_urls = vec!["http://127.0.0.1:8899".to_string()];
+        assert!(config(rpc_urls.clone(), vec![Rule::RpcHealth])
+            .sanitize()
+            .is_ok());
+        assert!(config(vec![], vec![Rule::RpcHealth]).sanitize().is_err());
+        let rule = Rule::Delinquency {
+            validators: vec!["foo".to_string()],
+        };
+        assert!(config(rpc_urls.clone(), vec![rule]).sanitize().is_err());
+        // The alerts of both rules would be keyed by the same name.
+        let rules = vec![
+            Rule::SlotLag { max_lag: 100 },
+            Rule::SlotLag { max_lag: 1000 },
+        ];
+        assert!(config(rpc_urls, rules).sanitize().is_err());
+    }
+}
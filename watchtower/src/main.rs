@@ -1,42 +1,38 @@
 //! A command-line executable for monitoring the health of a cluster
 #![allow(clippy::integer_arithmetic)]
 
+mod alerts;
+mod config;
+mod rules;
+mod state;
+
 use {
+    crate::{
+        config::{AlertSettings, ClusterConfig, MonitorConfig, Rule, RuleConfig, DEFAULT_NOTIFIER},
+        rules::{Failure, Snapshot, RPC_ERROR},
+        state::{ClusterState, State},
+    },
     clap::{crate_description, crate_name, value_t, value_t_or_exit, App, Arg},
     log::*,
     solana_clap_utils::{
         input_parsers::pubkeys_of,
         input_validators::{is_parsable, is_pubkey_or_keypair, is_url},
     },
-    solana_cli_output::display::format_labeled_address,
-    solana_client::{client_error, rpc_client::RpcClient, rpc_response::RpcVoteAccountStatus},
-    solana_metrics::{datapoint_error, datapoint_info},
-    solana_notifier::Notifier,
-    solana_sdk::{
-        hash::Hash,
-        native_token::{sol_to_lamports, Sol},
-        pubkey::Pubkey,
-    },
+    solana_client::{client_error, rpc_client::RpcClient},
+    solana_notifier::{Notifier, Severity},
+    solana_sdk::timing::timestamp,
     std::{
-        collections::HashMap,
+        collections::{HashMap, HashSet},
         error,
+        iter::once,
+        path::{Path, PathBuf},
+        process::exit,
         thread::sleep,
-        time::{Duration, Instant},
+        time::Duration,
     },
 };
 
-struct Config {
-    address_labels: HashMap<String, String>,
-    ignore_http_bad_gateway: bool,
-    interval: Duration,
-    json_rpc_url: String,
-    minimum_validator_identity_balance: u64,
-    monitor_active_stake: bool,
-    unhealthy_threshold: usize,
-    validator_identity_pubkeys: Vec<Pubkey>,
-}
-
-fn get_config() -> Config {
+fn get_config() -> MonitorConfig {
     let matches = App::new(crate_name!())
         .about(crate_description!())
         .version(solana_version::version!())
@@ -56,7 +52,31 @@ fn get_config() -> Config {
         and a sending number owned by that account,
         define environment variable before running `solana-watchtower`:
 
-        export TWILIO_CONFIG='ACCOUNT=<account>,TOKEN=<securityToken>,TO=<receivingNumber>,FROM=<sendingNumber>'")
+        export TWILIO_CONFIG='ACCOUNT=<account>,TOKEN=<securityToken>,TO=<receivingNumber>,FROM=<sendingNumber>'
+
+        To post alerts to a generic JSON webhook, send them by email, or open and
+        resolve PagerDuty incidents:
+
+        export WEBHOOK_URL=...
+        export SMTP_CONFIG='HOST=<host:port>,FROM=<address>,TO=<address>[;<address>...]'
+        export PAGERDUTY_INTEGRATION_KEY=...")
+        .arg(
+            Arg::with_name("monitor_config")
+                .long("monitor-config")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("YAML file describing the clusters to monitor, and the alert rules \
+                    to evaluate against them. The cluster and sanity check arguments \
+                    are ignored when given"),
+        )
+        .arg(
+            Arg::with_name("state_file")
+                .long("state-file")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("File in which the ongoing alerts are persisted, so that they \
+                    are not notified again after a restart"),
+        )
         .arg({
             let arg = Arg::with_name("config_file")
                 .short("C")
@@ -125,6 +145,16 @@ fn get_config() -> Config {
                 .takes_value(false)
                 .help("Alert when the current stake for the cluster drops below 80%"),
         )
+        .arg(
+            Arg::with_name("restart_grace_period")
+                .long("restart-grace-period")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .default_value("300")
+                .validator(is_parsable::<u64>)
+                .help("Time past the expected end of a restart announced by a validator \
+                    during which the validator is not reported as delinquent"),
+        )
         .arg(
             Arg::with_name("ignore_http_bad_gateway")
                 .long("ignore-http-bad-gateway")
@@ -136,70 +166,183 @@ fn get_config() -> Config {
         )
         .get_matches();
 
+    let state_file = matches.value_of("state_file").map(PathBuf::from);
+    if let Some(monitor_config) = matches.value_of("monitor_config") {
+        let mut config = MonitorConfig::load(Path::new(monitor_config)).unwrap_or_else(|err| {
+            eprintln!("Unable to load the monitor config: {}", err);
+            exit(1);
+        });
+        if state_file.is_some() {
+            config.state_file = state_file;
+        }
+        return config;
+    }
+
     let config = if let Some(config_file) = matches.value_of("config_file") {
         solana_cli_config::Config::load(config_file).unwrap_or_default()
     } else {
         solana_cli_config::Config::default()
     };
 
-    let interval = Duration::from_secs(value_t_or_exit!(matches, "interval", u64));
+    let interval_secs = value_t_or_exit!(matches, "interval", u64);
     let unhealthy_threshold = value_t_or_exit!(matches, "unhealthy_threshold", usize);
-    let minimum_validator_identity_balance = sol_to_lamports(value_t_or_exit!(
-        matches,
-        "minimum_validator_identity_balance",
-        f64
-    ));
+    let minimum_balance_sol = value_t_or_exit!(matches, "minimum_validator_identity_balance", f64);
     let json_rpc_url =
         value_t!(matches, "json_rpc_url", String).unwrap_or_else(|_| config.json_rpc_url.clone());
-    let validator_identity_pubkeys: Vec<_> = pubkeys_of(&matches, "validator_identities")
+    let validators: Vec<_> = pubkeys_of(&matches, "validator_identities")
         .unwrap_or_else(Vec::new)
-        .into_iter()
+        .iter()
+        .map(ToString::to_string)
         .collect();
 
     let monitor_active_stake = matches.is_present("monitor_active_stake");
+    let restart_grace_period_secs = value_t_or_exit!(matches, "restart_grace_period", u64);
     let ignore_http_bad_gateway = matches.is_present("ignore_http_bad_gateway");
 
-    let config = Config {
-        address_labels: config.address_labels,
-        ignore_http_bad_gateway,
-        interval,
-        json_rpc_url,
-        minimum_validator_identity_balance,
-        monitor_active_stake,
+    info!("RPC URL: {}", json_rpc_url);
+    info!("Monitored validators: {:?}", validators);
+
+    // The sanity checks of the command-line, as rules against a single cluster.
+    let mut rules = vec![Rule::TransactionCount, Rule::RecentBlockhash];
+    if monitor_active_stake {
+        rules.push(Rule::ActiveStake {
+            minimum_percent: 80.,
+        });
+    }
+    if !validators.is_empty() {
+        rules.push(Rule::Delinquency {
+            validators: validators.clone(),
+        });
+        rules.push(Rule::Balance {
+            validators,
+            minimum_balance_sol,
+        });
+    }
+    let settings = AlertSettings {
+        severity: Severity::default(),
+        notify: vec![DEFAULT_NOTIFIER.to_string()],
         unhealthy_threshold,
-        validator_identity_pubkeys,
     };
+    MonitorConfig {
+        interval_secs,
+        state_file,
+        clusters: vec![ClusterConfig {
+            name: json_rpc_url.clone(),
+            rpc_urls: vec![json_rpc_url],
+            address_labels: config.address_labels,
+            ignore_http_bad_gateway,
+            restart_grace_period_secs,
+            rpc_error: settings.clone(),
+            rules: rules
+                .into_iter()
+                .map(|rule| RuleConfig {
+                    name: None,
+                    rule,
+                    settings: settings.clone(),
+                })
+                .collect(),
+        }],
+    }
+}
 
-    info!("RPC URL: {}", config.json_rpc_url);
-    info!(
-        "Monitored validators: {:?}",
-        config.validator_identity_pubkeys
-    );
-    config
+fn new_notifier(name: &str) -> Notifier {
+    if name == DEFAULT_NOTIFIER {
+        Notifier::default()
+    } else {
+        Notifier::new(&format!("{}_", name.to_uppercase()))
+    }
 }
 
-fn get_cluster_info(
-    config: &Config,
-    rpc_client: &RpcClient,
-) -> client_error::Result<(u64, Hash, RpcVoteAccountStatus, HashMap<Pubkey, u64>)> {
-    let transaction_count = rpc_client.get_transaction_count()?;
-    let recent_blockhash = rpc_client.get_recent_blockhash()?.0;
-    let vote_accounts = rpc_client.get_vote_accounts()?;
+fn is_ignored_error(cluster: &ClusterConfig, err: &client_error::ClientError) -> bool {
+    if let client_error::ClientErrorKind::Reqwest(reqwest_err) = err.kind() {
+        if let Some(client_error::reqwest::StatusCode::BAD_GATEWAY) = reqwest_err.status() {
+            return cluster.ignore_http_bad_gateway;
+        }
+    }
+    false
+}
 
-    let mut validator_balances = HashMap::new();
-    for validator_identity in &config.validator_identity_pubkeys {
-        validator_balances.insert(
-            *validator_identity,
-            rpc_client.get_balance(validator_identity)?,
-        );
+fn check_cluster(
+    cluster: &ClusterConfig,
+    rpc_clients: &[RpcClient],
+    state: &mut ClusterState,
+    notifiers: &HashMap<&str, Notifier>,
+) {
+    let mut failures = vec![];
+    // The alerts of the rules which could not be checked are left as is.
+    let mut checked_rules = HashSet::new();
+    let mut rpc_error = None;
+    match Snapshot::new(&rpc_clients[0]) {
+        Ok(snapshot) => {
+            info!(
+                "{}: Current transaction count: {}",
+                cluster.name, snapshot.transaction_count
+            );
+            info!(
+                "{}: Recent blockhash: {}",
+                cluster.name, snapshot.recent_blockhash
+            );
+            info!(
+                "{}: Current validator count: {}",
+                cluster.name,
+                snapshot.vote_accounts.current.len()
+            );
+            info!(
+                "{}: Delinquent validator count: {}",
+                cluster.name,
+                snapshot.vote_accounts.delinquent.len()
+            );
+            for rule in &cluster.rules {
+                let name = rule.name();
+                match rules::check(name, &rule.rule, cluster, rpc_clients, &snapshot, state) {
+                    Ok(rule_failures) => {
+                        checked_rules.insert(name);
+                        failures.extend(rule_failures.into_iter().map(|f| (&rule.settings, f)));
+                    }
+                    Err(err) => {
+                        warn!("{}: Unable to check {}: {}", cluster.name, name, err);
+                        rpc_error.get_or_insert(err);
+                    }
+                }
+            }
+        }
+        Err(err) => rpc_error = Some(err),
+    }
+    match rpc_error {
+        None => {
+            checked_rules.insert(RPC_ERROR);
+        }
+        Some(err) if is_ignored_error(cluster, &err) => {
+            warn!("{}: Error suppressed: {}", cluster.name, err);
+        }
+        Some(err) => {
+            checked_rules.insert(RPC_ERROR);
+            failures.push((&cluster.rpc_error, Failure::rpc_error(err.to_string())));
+        }
     }
 
-    Ok((
-        transaction_count,
-        recent_blockhash,
-        vote_accounts,
-        validator_balances,
-    ))
+    let rule_names: HashSet<_> = cluster
+        .rules
+        .iter()
+        .map(RuleConfig::name)
+        .chain(once(RPC_ERROR))
+        .collect();
+    alerts::update_alerts(
+        &cluster.name,
+        &rule_names,
+        &checked_rules,
+        failures,
+        state,
+        timestamp(),
+        |notify, msg, severity, notification_type| {
+            for name in notify {
+                match notifiers.get(name.as_str()) {
+                    Some(notifier) => notifier.send(msg, severity, notification_type),
+                    None => warn!("Unknown notifier {}: {}", name, msg),
+                }
+            }
+        },
+    );
 }
 
 fn main() -> Result<(), Box<dyn error::Error>> {
@@ -208,174 +351,44 @@ fn main() -> Result<(), Box<dyn error::Error>> {
 
     let config = get_config();
 
-    let rpc_client = RpcClient::new(config.json_rpc_url.clone());
-    let notifier = Notifier::default();
-    let mut last_transaction_count = 0;
-    let mut last_recent_blockhash = Hash::default();
-    let mut last_notification_msg = "".into();
-    let mut num_consecutive_failures = 0;
-    let mut last_success = Instant::now();
+    let notifiers: HashMap<_, _> = config
+        .notifiers()
+        .into_iter()
+        .map(|name| (name, new_notifier(name)))
+        .collect();
+    let rpc_clients: Vec<Vec<_>> = config
+        .clusters
+        .iter()
+        .map(|cluster| {
+            info!("Monitoring {} through {:?}", cluster.name, cluster.rpc_urls);
+            cluster
+                .rpc_urls
+                .iter()
+                .cloned()
+                .map(RpcClient::new)
+                .collect()
+        })
+        .collect();
+    let mut state = config
+        .state_file
+        .as_deref()
+        .map(State::load)
+        .unwrap_or_default();
 
     loop {
-        let failure = match get_cluster_info(&config, &rpc_client) {
-            Ok((transaction_count, recent_blockhash, vote_accounts, validator_balances)) => {
-                info!("Current transaction count: {}", transaction_count);
-                info!("Recent blockhash: {}", recent_blockhash);
-                info!("Current validator count: {}", vote_accounts.current.len());
-                info!(
-                    "Delinquent validator count: {}",
-                    vote_accounts.delinquent.len()
-                );
-
-                let mut failures = vec![];
-
-                let total_current_stake = vote_accounts
-                    .current
-                    .iter()
-                    .map(|vote_account| vote_account.activated_stake)
-                    .sum();
-                let total_delinquent_stake = vote_accounts
-                    .delinquent
-                    .iter()
-                    .map(|vote_account| vote_account.activated_stake)
-                    .sum();
-
-                let total_stake = total_current_stake + total_delinquent_stake;
-                let current_stake_percent = total_current_stake as f64 * 100. / total_stake as f64;
-                info!(
-                    "Current stake: {:.2}% | Total stake: {}, current stake: {}, delinquent: {}",
-                    current_stake_percent,
-                    Sol(total_stake),
-                    Sol(total_current_stake),
-                    Sol(total_delinquent_stake)
-                );
-
-                if transaction_count > last_transaction_count {
-                    last_transaction_count = transaction_count;
-                } else {
-                    failures.push((
-                        "transaction-count",
-                        format!(
-                            "Transaction count is not advancing: {} <= {}",
-                            transaction_count, last_transaction_count
-                        ),
-                    ));
-                }
-
-                if recent_blockhash != last_recent_blockhash {
-                    last_recent_blockhash = recent_blockhash;
-                } else {
-                    failures.push((
-                        "recent-blockhash",
-                        format!("Unable to get new blockhash: {}", recent_blockhash),
-                    ));
-                }
-
-                if config.monitor_active_stake && current_stake_percent < 80. {
-                    failures.push((
-                        "current-stake",
-                        format!("Current stake is {:.2}%", current_stake_percent),
-                    ));
-                }
-
-                let mut validator_errors = vec![];
-                for validator_identity in config.validator_identity_pubkeys.iter() {
-                    let formatted_validator_identity = format_labeled_address(
-                        &validator_identity.to_string(),
-                        &config.address_labels,
-                    );
-                    if vote_accounts
-                        .delinquent
-                        .iter()
-                        .any(|vai| vai.node_pubkey == *validator_identity.to_string())
-                    {
-                        validator_errors
-                            .push(format!("{} delinquent", formatted_validator_identity));
-                    } else if !vote_accounts
-                        .current
-                        .iter()
-                        .any(|vai| vai.node_pubkey == *validator_identity.to_string())
-                    {
-                        validator_errors.push(format!("{} missing", formatted_validator_identity));
-                    }
-
-                    if let Some(balance) = validator_balances.get(validator_identity) {
-                        if *balance < config.minimum_validator_identity_balance {
-                            failures.push((
-                                "balance",
-                                format!("{} has {}", formatted_validator_identity, Sol(*balance)),
-                            ));
-                        }
-                    }
-                }
-
-                if !validator_errors.is_empty() {
-                    failures.push(("delinquent", validator_errors.join(",")));
-                }
-
-                for failure in failures.iter() {
-                    error!("{} sanity failure: {}", failure.0, failure.1);
-                }
-                failures.into_iter().next() // Only report the first failure if any
-            }
-            Err(err) => {
-                let mut failure = Some(("rpc-error", err.to_string()));
-
-                if let client_error::ClientErrorKind::Reqwest(reqwest_err) = err.kind() {
-                    if let Some(client_error::reqwest::StatusCode::BAD_GATEWAY) =
-                        reqwest_err.status()
-                    {
-                        if config.ignore_http_bad_gateway {
-                            warn!("Error suppressed: {}", err);
-                            failure = None;
-                        }
-                    }
-                }
-                failure
-            }
-        };
-
-        if let Some((failure_test_name, failure_error_message)) = &failure {
-            let notification_msg = format!(
-                "solana-watchtower: Error: {}: {}",
-                failure_test_name, failure_error_message
-            );
-            num_consecutive_failures += 1;
-            if num_consecutive_failures > config.unhealthy_threshold {
-                datapoint_info!("watchtower-sanity", ("ok", false, bool));
-                if last_notification_msg != notification_msg {
-                    notifier.send(&notification_msg);
-                }
-                datapoint_error!(
-                    "watchtower-sanity-failure",
-                    ("test", failure_test_name, String),
-                    ("err", failure_error_message, String)
-                );
-                last_notification_msg = notification_msg;
-            } else {
-                info!(
-                    "Failure {} of {}: {}",
-                    num_consecutive_failures, config.unhealthy_threshold, notification_msg
-                );
-            }
-        } else {
-            datapoint_info!("watchtower-sanity", ("ok", true, bool));
-            if !last_notification_msg.is_empty() {
-                let alarm_duration = Instant::now().duration_since(last_success);
-                let alarm_duration = alarm_duration - config.interval; // Subtract the period before the first error
-                let alarm_duration = Duration::from_secs(alarm_duration.as_secs()); // Drop milliseconds in message
-
-                let all_clear_msg = format!(
-                    "All clear after {}",
-                    humantime::format_duration(alarm_duration)
+        for (cluster, rpc_clients) in config.clusters.iter().zip(&rpc_clients) {
+            let cluster_state = state.clusters.entry(cluster.name.clone()).or_default();
+            check_cluster(cluster, rpc_clients, cluster_state, &notifiers);
+        }
+        if let Some(state_file) = &config.state_file {
+            if let Err(err) = state.save(state_file) {
+                warn!(
+                    "Unable to save the state to {}: {}",
+                    state_file.display(),
+                    err
                 );
-                info!("{}", all_clear_msg);
-                notifier.send(&format!("solana-watchtower: {}", all_clear_msg));
             }
-            last_notification_msg = "".into();
-            last_success = Instant::now();
-            num_consecutive_failures = 0;
         }
-        sleep(config.interval);
+        sleep(Duration::from_secs(config.interval_secs));
     }
 }
@@ -0,0 +1,687 @@
+//! Evaluation of the alert rules against the state of a cluster.
+
+use {
+    crate::{
+        config::{ClusterConfig, Rule},
+        state::ClusterState,
+    },
+    log::*,
+    solana_cli_output::display::format_labeled_address,
+    solana_client::{
+        client_error,
+        rpc_client::RpcClient,
+        rpc_config::{RpcBlockProductionConfig, RpcBlockProductionConfigRange},
+        rpc_response::{RpcContactInfo, RpcVoteAccountInfo, RpcVoteAccountStatus},
+    },
+    solana_sdk::{
+        hash::Hash,
+        native_token::{sol_to_lamports, Sol},
+        pubkey::Pubkey,
+        timing::timestamp,
+    },
+    std::{collections::HashMap, str::FromStr},
+};
+
+/// Name of the failures to query the RPC endpoints.
+pub const RPC_ERROR: &str = "rpc-error";
+
+/// A rule failure, reported as an alert.
+#[derive(Debug, PartialEq)]
+pub struct Failure {
+    /// Name of the rule, as configured.
+    pub rule: String,
+    /// Identifies the alert across checks, e.g. "delinquent/<identity>".
+    pub key: String,
+    pub message: String,
+    /// Events, such as a commission change, are notified once and are not
+    /// followed by an all clear.
+    pub event: bool,
+}
+
+impl Failure {
+    fn new(rule: &str, message: String) -> Self {
+        Self {
+            rule: rule.to_string(),
+            key: rule.to_string(),
+            message,
+            event: false,
+        }
+    }
+
+    // A failure of the rule for one subject, e.g. a validator.
+    fn with_subject(rule: &str, subject: &str, message: String) -> Self {
+        Self {
+            rule: rule.to_string(),
+            key: format!("{}/{}", rule, subject),
+            message,
+            event: false,
+        }
+    }
+
+    fn new_event(rule: &str, subject: &str, message: String) -> Self {
+        Self {
+            event: true,
+            ..Self::with_subject(rule, subject, message)
+        }
+    }
+
+    pub fn rpc_error(message: String) -> Self {
+        Self {
+            rule: RPC_ERROR.to_string(),
+            key: RPC_ERROR.to_string(),
+            message,
+            event: false,
+        }
+    }
+}
+
+/// The state of the cluster queried once per check, and shared by the rules.
+pub struct Snapshot {
+    pub transaction_count: u64,
+    pub recent_blockhash: Hash,
+    pub vote_accounts: RpcVoteAccountStatus,
+    pub cluster_nodes: Vec<RpcContactInfo>,
+}
+
+impl Snapshot {
+    pub fn new(rpc_client: &RpcClient) -> client_error::Result<Self> {
+        Ok(Self {
+            transaction_count: rpc_client.get_transaction_count()?,
+            recent_blockhash: rpc_client.get_recent_blockhash()?.0,
+            vote_accounts: rpc_client.get_vote_accounts()?,
+            cluster_nodes: rpc_client.get_cluster_nodes()?,
+        })
+    }
+
+    fn vote_accounts(&self) -> impl Iterator<Item = &RpcVoteAccountInfo> {
+        self.vote_accounts
+            .current
+            .iter()
+            .chain(self.vote_accounts.delinquent.iter())
+    }
+
+    fn is_delinquent(&self, node_pubkey: &str) -> bool {
+        self.vote_accounts
+            .delinquent
+            .iter()
+            .any(|vote_account| vote_account.node_pubkey == node_pubkey)
+    }
+
+    fn is_current(&self, node_pubkey: &str) -> bool {
+        self.vote_accounts
+            .current
+            .iter()
+            .any(|vote_account| vote_account.node_pubkey == node_pubkey)
+    }
+
+    // Returns true if the node is within a restart it announced.
+    fn is_restarting(&self, node_pubkey: &str, grace_period_secs: u64) -> bool {
+        let now = timestamp();
+        let grace_period_ms = grace_period_secs.saturating_mul(1000);
+        self.cluster_nodes
+            .iter()
+            .filter(|node| node.pubkey == node_pubkey)
+            .filter_map(|node| node.restart_intent.as_ref())
+            .any(|intent| intent.start <= now && now < intent.end().saturating_add(grace_period_ms))
+    }
+}
+
+/// Evaluates the rule, returning its failures, which are keyed by the `name`
+/// of the rule.
+pub fn check(
+    name: &str,
+    rule: &Rule,
+    cluster: &ClusterConfig,
+    rpc_clients: &[RpcClient],
+    snapshot: &Snapshot,
+    state: &mut ClusterState,
+) -> client_error::Result<Vec<Failure>> {
+    let rpc_client = &rpc_clients[0];
+    let label = |pubkey: &str| format_labeled_address(pubkey, &cluster.address_labels);
+    // Whether the rule applies to the validator, an empty list meaning all.
+    let is_watched = |validators: &[String], node_pubkey: &str| -> bool {
+        validators.is_empty() || validators.iter().any(|v| v == node_pubkey)
+    };
+    let mut failures = vec![];
+    match rule {
+        Rule::TransactionCount => {
+            if snapshot.transaction_count > state.last_transaction_count {
+                state.last_transaction_count = snapshot.transaction_count;
+            } else {
+                failures.push(Failure::new(
+                    name,
+                    format!(
+                        "Transaction count is not advancing: {} <= {}",
+                        snapshot.transaction_count, state.last_transaction_count
+                    ),
+                ));
+            }
+        }
+        Rule::RecentBlockhash => {
+            let recent_blockhash = snapshot.recent_blockhash.to_string();
+            if recent_blockhash != state.last_recent_blockhash {
+                state.last_recent_blockhash = recent_blockhash;
+            } else {
+                failures.push(Failure::new(
+                    name,
+                    format!("Unable to get new blockhash: {}", recent_blockhash),
+                ));
+            }
+        }
+        Rule::Delinquency { validators } => {
+            for validator in validators {
+                if snapshot.is_restarting(validator, cluster.restart_grace_period_secs) {
+                    info!("{} is restarting as announced", label(validator));
+                } else if snapshot.is_delinquent(validator) {
+                    failures.push(Failure::with_subject(
+                        name,
+                        validator,
+                        format!("{} delinquent", label(validator)),
+                    ));
+                } else if !snapshot.is_current(validator) {
+                    failures.push(Failure::with_subject(
+                        name,
+                        validator,
+                        format!("{} missing", label(validator)),
+                    ));
+                }
+            }
+        }
+        Rule::Balance {
+            validators,
+            minimum_balance_sol,
+        } => {
+            for validator in validators {
+                let balance = rpc_client.get_balance(&Pubkey::from_str(validator).unwrap())?;
+                if balance < sol_to_lamports(*minimum_balance_sol) {
+                    failures.push(Failure::with_subject(
+                        name,
+                        validator,
+                        format!("{} has {}", label(validator), Sol(balance)),
+                    ));
+                }
+            }
+        }
+        Rule::ActiveStake { minimum_percent } => {
+            let total_current_stake: u64 = snapshot
+                .vote_accounts
+                .current
+                .iter()
+                .map(|vote_account| vote_account.activated_stake)
+                .sum();
+            let total_delinquent_stake: u64 = snapshot
+                .vote_accounts
+                .delinquent
+                .iter()
+                .map(|vote_account| vote_account.activated_stake)
+                .sum();
+            // Validators restarting as announced are not counted as delinquent.
+            let total_restarting_stake: u64 = snapshot
+                .vote_accounts
+                .delinquent
+                .iter()
+                .filter(|vote_account| {
+                    snapshot
+                        .is_restarting(&vote_account.node_pubkey, cluster.restart_grace_period_secs)
+                })
+                .map(|vote_account| vote_account.activated_stake)
+                .sum();
+            let total_stake = total_current_stake + total_delinquent_stake;
+            let current_stake_percent = total_current_stake as f64 * 100. / total_stake as f64;
+            let current_or_restarting_stake_percent =
+                (total_current_stake + total_restarting_stake) as f64 * 100. / total_stake as f64;
+            info!(
+                "Current stake: {:.2}% | Total stake: {}, current stake: {}, delinquent: {}",
+                current_stake_percent,
+                Sol(total_stake),
+                Sol(total_current_stake),
+                Sol(total_delinquent_stake)
+            );
+            if current_or_restarting_stake_percent < *minimum_percent {
+                failures.push(Failure::new(
+                    name,
+                    format!("Current stake is {:.2}%", current_stake_percent),
+                ));
+            }
+        }
+        Rule::SkipRate {
+            validators,
+            window_slots,
+            max_percent,
+        } => {
+            let slot = rpc_client.get_slot()?;
+            let block_production = rpc_client
+                .get_block_production_with_config(RpcBlockProductionConfig {
+                    range: Some(RpcBlockProductionConfigRange {
+                        first_slot: slot.saturating_sub(*window_slots),
+                        last_slot: None,
+                    }),
+                    ..RpcBlockProductionConfig::default()
+                })?
+                .value;
+            for validator in validators {
+                let (leader_slots, blocks_produced) = block_production
+                    .by_identity
+                    .get(validator)
+                    .cloned()
+                    .unwrap_or_default();
+                if leader_slots == 0 {
+                    continue;
+                }
+                let skip_rate =
+                    (leader_slots - blocks_produced) as f64 * 100. / leader_slots as f64;
+                if skip_rate > *max_percent {
+                    failures.push(Failure::with_subject(
+                        name,
+                        validator,
+                        format!(
+                            "{} skipped {:.2}% of its leader slots over the last {} slots",
+                            label(validator),
+                            skip_rate,
+                            window_slots
+                        ),
+                    ));
+                }
+            }
+        }
+        Rule::VoteCreditsLag {
+            validators,
+            max_lag,
+        } => {
+            let epoch = snapshot
+                .vote_accounts()
+                .filter_map(|vote_account| vote_account.epoch_credits.last())
+                .map(|(epoch, _, _)| *epoch)
+                .max();
+            // Credits earned by the vote account in the current epoch.
+            let epoch_credits = |vote_account: &RpcVoteAccountInfo| {
+                vote_account
+                    .epoch_credits
+                    .iter()
+                    .find(|(credits_epoch, _, _)| Some(*credits_epoch) == epoch)
+                    .map(|(_, credits, prev_credits)| credits.saturating_sub(*prev_credits))
+                    .unwrap_or_default()
+            };
+            let max_credits = snapshot.vote_accounts().map(epoch_credits).max();
+            for vote_account in snapshot.vote_accounts() {
+                if !validators.contains(&vote_account.node_pubkey) {
+                    continue;
+                }
+                let lag = max_credits
+                    .unwrap_or_default()
+                    .saturating_sub(epoch_credits(vote_account));
+                if lag > *max_lag {
+                    failures.push(Failure::with_subject(
+                        name,
+                        &vote_account.node_pubkey,
+                        format!(
+                            "{} is {} vote credits behind the best validator",
+                            label(&vote_account.node_pubkey),
+                            lag
+                        ),
+                    ));
+                }
+            }
+        }
+        Rule::CommissionChange { validators } => {
+            for vote_account in snapshot.vote_accounts() {
+                if !is_watched(validators, &vote_account.node_pubkey) {
+                    continue;
+                }
+                let previous = state
+                    .commissions
+                    .insert(vote_account.vote_pubkey.clone(), vote_account.commission);
+                if let Some(previous) =
+                    previous.filter(|previous| *previous != vote_account.commission)
+                {
+                    failures.push(Failure::new_event(
+                        name,
+                        &vote_account.vote_pubkey,
+                        format!(
+                            "{} changed its commission from {}% to {}%",
+                            label(&vote_account.node_pubkey),
+                            previous,
+                            vote_account.commission
+                        ),
+                    ));
+                }
+            }
+        }
+        Rule::StakeDrop {
+            validators,
+            max_drop_percent,
+        } => {
+            for vote_account in snapshot.vote_accounts() {
+                if !is_watched(validators, &vote_account.node_pubkey) {
+                    continue;
+                }
+                let previous = state
+                    .activated_stakes
+                    .insert(
+                        vote_account.vote_pubkey.clone(),
+                        vote_account.activated_stake,
+                    )
+                    .unwrap_or_default();
+                if previous == 0 || vote_account.activated_stake >= previous {
+                    continue;
+                }
+                let drop_percent =
+                    (previous - vote_account.activated_stake) as f64 * 100. / previous as f64;
+                if drop_percent > *max_drop_percent {
+                    failures.push(Failure::new_event(
+                        name,
+                        &vote_account.vote_pubkey,
+                        format!(
+                            "{} stake dropped by {:.2}% from {} to {}",
+                            label(&vote_account.node_pubkey),
+                            drop_percent,
+                            Sol(previous),
+                            Sol(vote_account.activated_stake)
+                        ),
+                    ));
+                }
+            }
+        }
+        Rule::VersionMismatch {
+            validators,
+            expected_version,
+        } => {
+            let expected_version = match expected_version {
+                Some(expected_version) => expected_version.clone(),
+                None => rpc_client.get_version()?.solana_core,
+            };
+            for node in &snapshot.cluster_nodes {
+                if !validators.contains(&node.pubkey) {
+                    continue;
+                }
+                if let Some(version) = node
+                    .version
+                    .as_ref()
+                    .filter(|version| **version != expected_version)
+                {
+                    failures.push(Failure::with_subject(
+                        name,
+                        &node.pubkey,
+                        format!(
+                            "{} is running {}, expected {}",
+                            label(&node.pubkey),
+                            version,
+                            expected_version
+                        ),
+                    ));
+                }
+            }
+        }
+        Rule::RpcHealth => {
+            for (url, rpc_client) in cluster.rpc_urls.iter().zip(rpc_clients) {
+                if let Err(err) = rpc_client.get_health() {
+                    failures.push(Failure::with_subject(
+                        name,
+                        url,
+                        format!("{} is unhealthy: {}", url, err),
+                    ));
+                }
+            }
+        }
+        Rule::SlotLag { max_lag } => {
+            let slots: Vec<_> = cluster
+                .rpc_urls
+                .iter()
+                .zip(rpc_clients)
+                .filter_map(|(url, rpc_client)| match rpc_client.get_slot() {
+                    Ok(slot) => Some((url, slot)),
+                    Err(err) => {
+                        warn!("Unable to get the slot of {}: {}", url, err);
+                        None
+                    }
+                })
+                .collect();
+            let max_slot = slots
+                .iter()
+                .map(|(_, slot)| *slot)
+                .max()
+                .unwrap_or_default();
+            for (url, slot) in slots {
+                let lag = max_slot - slot;
+                if lag > *max_lag {
+                    failures.push(Failure::with_subject(
+                        name,
+                        url,
+                        format!("{} is {} slots behind", url, lag),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::config::{AlertSettings, DEFAULT_RESTART_GRACE_PERIOD_SECS},
+    };
+
+    fn new_vote_account(
+        node_pubkey: &str,
+        activated_stake: u64,
+        commission: u8,
+        epoch_credits: Vec<(u64, u64, u64)>,
+    ) -> RpcVoteAccountInfo {
+        RpcVoteAccountInfo {
+            vote_pubkey: format!("vote-{}", node_pubkey),
+            node_pubkey: node_pubkey.to_string(),
+            activated_stake,
+            commission,
+            epoch_vote_account: true,
+            epoch_credits,
+            last_vote: 0,
+            root_slot: 0,
+        }
+    }
+
+    fn new_cluster() -> ClusterConfig {
+        ClusterConfig {
+            name: "testnet".to_string(),
+            // Not queried by the rules under test.
+            rpc_urls: vec!["http://127.0.0.1:1".to_string()],
+            address_labels: HashMap::new(),
+            ignore_http_bad_gateway: false,
+            restart_grace_period_secs: DEFAULT_RESTART_GRACE_PERIOD_SECS,
+            rpc_error: AlertSettings::default(),
+            rules: vec![],
+        }
+    }
+
+    fn new_snapshot(
+        current: Vec<RpcVoteAccountInfo>,
+        delinquent: Vec<RpcVoteAccountInfo>,
+    ) -> Snapshot {
+        Snapshot {
+            transaction_count: 10,
+            recent_blockhash: Hash::new_unique(),
+            vote_accounts: RpcVoteAccountStatus {
+                current,
+                delinquent,
+            },
+            cluster_nodes: vec![],
+        }
+    }
+
+    fn check_rule(rule: &Rule, snapshot: &Snapshot, state: &mut ClusterState) -> Vec<Failure> {
+        let cluster = new_cluster();
+        let rpc_clients: Vec<_> = cluster
+            .rpc_urls
+            .iter()
+            .map(|url| RpcClient::new(url.clone()))
+            .collect();
+        check(rule.name(), rule, &cluster, &rpc_clients, snapshot, state).unwrap()
+    }
+
+    #[test]
+    fn test_check_progress() {
+        let snapshot = new_snapshot(vec![], vec![]);
+        let mut state = ClusterState::default();
+        for rule in &[Rule::TransactionCount, Rule::RecentBlockhash] {
+            assert_eq!(check_rule(rule, &snapshot, &mut state), vec![]);
+            let failures = check_rule(rule, &snapshot, &mut state);
+            assert_eq!(failures.len(), 1);
+            assert_eq!(failures[0].key, rule.name());
+            assert!(!failures[0].event);
+        }
+    }
+
+    #[test]
+    fn test_check_delinquency() {
+        let snapshot = new_snapshot(
+            vec![new_vote_account("current", 100, 10, vec![])],
+            vec![new_vote_account("delinquent", 100, 10, vec![])],
+        );
+        let rule = Rule::Delinquency {
+            validators: vec![
+                "current".to_string(),
+                "delinquent".to_string(),
+                "missing".to_string(),
+            ],
+        };
+        let failures = check_rule(&rule, &snapshot, &mut ClusterState::default());
+        assert_eq!(
+            failures,
+            vec![
+                Failure::with_subject(
+                    rule.name(),
+                    "delinquent",
+                    "delinquent delinquent".to_string()
+                ),
+                Failure::with_subject(rule.name(), "missing", "missing missing".to_string()),
+            ]
+        );
+        assert_eq!(failures[0].key, "delinquent/delinquent");
+    }
+
+    #[test]
+    fn test_check_active_stake() {
+        let snapshot = new_snapshot(
+            vec![new_vote_account("current", 70, 10, vec![])],
+            vec![new_vote_account("delinquent", 30, 10, vec![])],
+        );
+        let rule = Rule::ActiveStake {
+            minimum_percent: 80.,
+        };
+        let failures = check_rule(&rule, &snapshot, &mut ClusterState::default());
+        assert_eq!(failures[0].message, "Current stake is 70.00%");
+        let rule = Rule::ActiveStake {
+            minimum_percent: 60.,
+        };
+        assert_eq!(
+            check_rule(&rule, &snapshot, &mut ClusterState::default()),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_check_vote_credits_lag() {
+        let snapshot = new_snapshot(
+            vec![
+                new_vote_account("best", 100, 10, vec![(4, 500, 400), (5, 1000, 500)]),
+                new_vote_account("good", 100, 10, vec![(5, 950, 500)]),
+                new_vote_account("lagging", 100, 10, vec![(5, 700, 500)]),
+            ],
+            vec![new_vote_account("stale", 100, 10, vec![(4, 500, 400)])],
+        );
+        let rule = Rule::VoteCreditsLag {
+            validators: vec![
+                "good".to_string(),
+                "lagging".to_string(),
+                "stale".to_string(),
+            ],
+            max_lag: 100,
+        };
+        let failures = check_rule(&rule, &snapshot, &mut ClusterState::default());
+        let keys: Vec<_> = failures
+            .iter()
+            .map(|failure| failure.key.as_str())
+            .collect();
+        assert_eq!(
+            keys,
+            vec!["vote-credits-lag/lagging", "vote-credits-lag/stale"]
+        );
+        assert_eq!(
+            failures[0].message,
+            "lagging is 300 vote credits behind the best validator"
+        );
+    }
+
+    #[test]
+    fn test_check_commission_change() {
+        let rule = Rule::CommissionChange { validators: vec![] };
+        let mut state = ClusterState::default();
+        let snapshot = new_snapshot(vec![new_vote_account("node", 100, 10, vec![])], vec![]);
+        assert_eq!(check_rule(&rule, &snapshot, &mut state), vec![]);
+        assert_eq!(check_rule(&rule, &snapshot, &mut state), vec![]);
+
+        let snapshot = new_snapshot(vec![], vec![new_vote_account("node", 100, 100, vec![])]);
+        let failures = check_rule(&rule, &snapshot, &mut state);
+        assert_eq!(
+            failures,
+            vec![Failure::new_event(
+                rule.name(),
+                "vote-node",
+                "node changed its commission from 10% to 100%".to_string()
+            )]
+        );
+        assert_eq!(check_rule(&rule, &snapshot, &mut state), vec![]);
+
+        // Validators not watched are ignored.
+        let rule = Rule::CommissionChange {
+            validators: vec!["other".to_string()],
+        };
+        let snapshot = new_snapshot(vec![new_vote_account("node", 100, 0, vec![])], vec![]);
+        assert_eq!(check_rule(&rule, &snapshot, &mut state), vec![]);
+    }
+
+    #[test]
+    fn test_check_stake_drop() {
+        let rule = Rule::StakeDrop {
+            validators: vec![],
+            max_drop_percent: 10.,
+        };
+        let mut state = ClusterState::default();
+        for (stake, failed) in &[(1000, false), (950, false), (2000, false), (1000, true)] {
+            let snapshot = new_snapshot(vec![new_vote_account("node", *stake, 10, vec![])], vec![]);
+            let failures = check_rule(&rule, &snapshot, &mut state);
+            assert_eq!(!failures.is_empty(), *failed);
+            assert!(failures.iter().all(|failure| failure.event));
+        }
+    }
+
+    #[test]
+    fn test_check_version_mismatch() {
+        let node = |pubkey: &str, version: &str| RpcContactInfo {
+            pubkey: pubkey.to_string(),
+            gossip: None,
+            tpu: None,
+            rpc: None,
+            version: Some(version.to_string()),
+            feature_set: None,
+            shred_version: None,
+            restart_intent: None,
+        };
+        let mut snapshot = new_snapshot(vec![], vec![]);
+        snapshot.cluster_nodes = vec![node("new", "1.8.1"), node("old", "1.7.9")];
+        let rule = Rule::VersionMismatch {
+            validators: vec!["new".to_string(), "old".to_string()],
+            expected_version: Some("1.8.1".to_string()),
+        };
+        let failures = check_rule(&rule, &snapshot, &mut ClusterState::default());
+        assert_eq!(
+            failures,
+            vec![Failure::with_subject(
+                rule.name(),
+                "old",
+                "old is running 1.7.9, expected 1.8.1".to_string()
+            )]
+        );
+    }
+}
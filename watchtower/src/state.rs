@@ -0,0 +1,118 @@
+//! State of the watchtower, persisted across restarts so that ongoing alerts
+//! are not notified again, and so that changes are detected against the
+//! values observed before the restart.
+
+use {
+    log::*,
+    serde_derive::{Deserialize, Serialize},
+    solana_notifier::Severity,
+    std::{
+        collections::BTreeMap,
+        fs::{self, File},
+        io,
+        path::Path,
+    },
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Alert {
+    pub rule: String,
+    pub message: String,
+    pub severity: Severity,
+    pub notify: Vec<String>,
+    /// Time of the first failure, in milliseconds since the UNIX epoch.
+    pub since: u64,
+    /// Number of consecutive failures.
+    pub failures: usize,
+    pub notified: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct ClusterState {
+    /// Ongoing alerts, by key.
+    #[serde(default)]
+    pub alerts: BTreeMap<String, Alert>,
+    #[serde(default)]
+    pub last_transaction_count: u64,
+    #[serde(default)]
+    pub last_recent_blockhash: String,
+    /// Last observed commission, by vote account.
+    #[serde(default)]
+    pub commissions: BTreeMap<String, u8>,
+    /// Last observed activated stake, by vote account.
+    #[serde(default)]
+    pub activated_stakes: BTreeMap<String, u64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct State {
+    /// State of each cluster, by name.
+    #[serde(default)]
+    pub clusters: BTreeMap<String, ClusterState>,
+}
+
+impl State {
+    /// Loads the state from the file, or returns the default state if the file
+    /// does not exist or cannot be parsed.
+    pub fn load(path: &Path) -> Self {
+        match File::open(path) {
+            Ok(file) => serde_json::from_reader(file).unwrap_or_else(|err| {
+                warn!("Ignoring invalid state file {}: {}", path.display(), err);
+                Self::default()
+            }),
+            Err(err) => {
+                if err.kind() != io::ErrorKind::NotFound {
+                    warn!("Unable to read state file {}: {}", path.display(), err);
+                }
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        // Write to a temporary file first, so that the state file is never
+        // left truncated.
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path)?;
+        serde_json::to_writer_pretty(&file, self)?;
+        file.sync_all()?;
+        fs::rename(tmp_path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::env};
+
+    #[test]
+    fn test_save_load_state() {
+        let path = env::temp_dir().join(format!(
+            "solana-watchtower-state-{}.json",
+            solana_sdk::pubkey::Pubkey::new_unique()
+        ));
+        assert_eq!(State::load(&path), State::default());
+
+        let mut state = State::default();
+        let cluster = state.clusters.entry("testnet".to_string()).or_default();
+        cluster.last_transaction_count = 42;
+        cluster.commissions.insert("vote".to_string(), 10);
+        cluster.alerts.insert(
+            "delinquent/foo".to_string(),
+            Alert {
+                rule: "delinquent".to_string(),
+                message: "foo delinquent".to_string(),
+                severity: Severity::Critical,
+                notify: vec!["default".to_string()],
+                since: 1234,
+                failures: 2,
+                notified: true,
+            },
+        );
+        state.save(&path).unwrap();
+        assert_eq!(State::load(&path), state);
+
+        fs::write(&path, "garbage").unwrap();
+        assert_eq!(State::load(&path), State::default());
+        fs::remove_file(&path).unwrap();
+    }
+}